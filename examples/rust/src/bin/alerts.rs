@@ -0,0 +1,364 @@
+use {
+    clap::Parser,
+    futures::{sink::SinkExt, stream::StreamExt},
+    log::{error, info, warn},
+    serde_json::json,
+    solana_sdk::{native_token::lamports_to_sol, signature::Signature},
+    std::{collections::BTreeMap, env, fmt, str::FromStr},
+    tonic::transport::channel::ClientTlsConfig,
+    yellowstone_grpc_client::GeyserGrpcClient,
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
+        SubscribeRequestFilterAccounts, SubscribeRequestFilterTransactions, SubscribeUpdateAccount,
+        SubscribeUpdateTransactionStatus,
+    },
+};
+
+const FILTER_BALANCE: &str = "balance";
+const FILTER_ERROR_RATE_PREFIX: &str = "error-rate:";
+const FILTER_SIGNATURE: &str = "signature";
+
+#[derive(Debug, Clone, Parser)]
+#[clap(author, version, about)]
+struct Args {
+    #[clap(short, long, default_value_t = String::from("http://127.0.0.1:10000"))]
+    /// Service endpoint
+    endpoint: String,
+
+    #[clap(long)]
+    x_token: Option<String>,
+
+    /// Discord webhook url
+    #[clap(long)]
+    discord_webhook: Vec<String>,
+
+    /// Slack incoming webhook url
+    #[clap(long)]
+    slack_webhook: Vec<String>,
+
+    /// Alert when balance of the account drops below the threshold,
+    /// `<PUBKEY>:<LAMPORTS>`
+    #[clap(long)]
+    balance_below: Vec<KeyValue<u64>>,
+
+    /// Alert when balance of the account rises above the threshold,
+    /// `<PUBKEY>:<LAMPORTS>`
+    #[clap(long)]
+    balance_above: Vec<KeyValue<u64>>,
+
+    /// Alert when share of failed transactions of the program exceeds the
+    /// threshold, `<PROGRAM>:<RATIO>`, e.g. `<PROGRAM>:0.25`
+    #[clap(long)]
+    error_rate: Vec<KeyValue<f64>>,
+
+    /// Number of the latest slots used to calculate error rate
+    #[clap(long, default_value_t = 150)]
+    error_rate_window: u64,
+
+    /// Min number of transactions in the window to calculate error rate
+    #[clap(long, default_value_t = 20)]
+    error_rate_min_transactions: u64,
+
+    /// Alert when transaction with the signature is processed
+    #[clap(long)]
+    signature: Vec<String>,
+}
+
+/// Rule argument in form of `<KEY>:<VALUE>`
+#[derive(Debug, Clone)]
+struct KeyValue<T> {
+    key: String,
+    value: T,
+}
+
+impl<T> FromStr for KeyValue<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected `<KEY>:<VALUE>`, got `{s}`"))?;
+        Ok(Self {
+            key: key.to_owned(),
+            value: value
+                .parse()
+                .map_err(|error| format!("invalid value `{value}`: {error}"))?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct BalanceRule {
+    pubkey: String,
+    threshold: u64,
+    above: bool,
+    /// Whether the last seen balance was beyond the threshold
+    beyond: Option<bool>,
+}
+
+impl BalanceRule {
+    fn check(&mut self, lamports: u64) -> Option<String> {
+        let beyond = if self.above {
+            lamports > self.threshold
+        } else {
+            lamports < self.threshold
+        };
+        let crossed = beyond && self.beyond != Some(true);
+        self.beyond = Some(beyond);
+        crossed.then(|| {
+            format!(
+                "balance of `{}` is {} SOL, {} threshold {} SOL",
+                self.pubkey,
+                lamports_to_sol(lamports),
+                if self.above { "above" } else { "below" },
+                lamports_to_sol(self.threshold),
+            )
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ErrorRateRule {
+    program: String,
+    threshold: f64,
+    /// Total and failed transactions by slot
+    slots: BTreeMap<u64, (u64, u64)>,
+    alerting: bool,
+}
+
+impl ErrorRateRule {
+    fn check(
+        &mut self,
+        slot: u64,
+        failed: bool,
+        window: u64,
+        min_transactions: u64,
+    ) -> Option<String> {
+        let counts = self.slots.entry(slot).or_default();
+        counts.0 += 1;
+        counts.1 += u64::from(failed);
+
+        let newest = *self.slots.keys().next_back().expect("not empty");
+        self.slots = self.slots.split_off(&newest.saturating_sub(window - 1));
+
+        let (total, failed) = self.slots.values().fold((0, 0), |(total, failed), counts| {
+            (total + counts.0, failed + counts.1)
+        });
+        if total < min_transactions {
+            return None;
+        }
+
+        let rate = failed as f64 / total as f64;
+        let spike = rate > self.threshold;
+        let started = spike && !self.alerting;
+        if self.alerting && !spike {
+            info!("error rate of {} is back to {rate:.3}", self.program);
+        }
+        self.alerting = spike;
+        started.then(|| {
+            format!(
+                "error rate of `{}` is {:.1}% ({failed} of {total} transactions in the last {window} slots), threshold {:.1}%",
+                self.program,
+                rate * 100.0,
+                self.threshold * 100.0,
+            )
+        })
+    }
+}
+
+struct Rules {
+    balances: Vec<BalanceRule>,
+    error_rates: Vec<ErrorRateRule>,
+    error_rate_window: u64,
+    error_rate_min_transactions: u64,
+}
+
+impl Rules {
+    fn new(args: &Args) -> Self {
+        let balances = args
+            .balance_below
+            .iter()
+            .map(|rule| (rule, false))
+            .chain(args.balance_above.iter().map(|rule| (rule, true)))
+            .map(|(rule, above)| BalanceRule {
+                pubkey: rule.key.clone(),
+                threshold: rule.value,
+                above,
+                beyond: None,
+            })
+            .collect();
+        let error_rates = args
+            .error_rate
+            .iter()
+            .map(|rule| ErrorRateRule {
+                program: rule.key.clone(),
+                threshold: rule.value,
+                slots: BTreeMap::new(),
+                alerting: false,
+            })
+            .collect();
+        Self {
+            balances,
+            error_rates,
+            error_rate_window: args.error_rate_window.max(1),
+            error_rate_min_transactions: args.error_rate_min_transactions,
+        }
+    }
+
+    fn subscribe_request(&self, signatures: &[String]) -> SubscribeRequest {
+        let mut request = SubscribeRequest::default().with_commitment(CommitmentLevel::Confirmed);
+        if !self.balances.is_empty() {
+            request = request.with_accounts(
+                FILTER_BALANCE,
+                SubscribeRequestFilterAccounts::with_account(
+                    self.balances.iter().map(|rule| &rule.pubkey),
+                ),
+            );
+        }
+        // one filter per program, matched rule is found by the filter name
+        for rule in &self.error_rates {
+            request.transactions_status.insert(
+                format!("{FILTER_ERROR_RATE_PREFIX}{}", rule.program),
+                SubscribeRequestFilterTransactions::for_account([&rule.program]).with_vote(false),
+            );
+        }
+        for signature in signatures {
+            request.transactions_status.insert(
+                format!("{FILTER_SIGNATURE}:{signature}"),
+                SubscribeRequestFilterTransactions::for_signature(signature),
+            );
+        }
+        request
+    }
+
+    fn on_account(&mut self, msg: SubscribeUpdateAccount) -> Vec<String> {
+        let Some(account) = msg.account else {
+            return vec![];
+        };
+        let pubkey = bs58::encode(&account.pubkey).into_string();
+        self.balances
+            .iter_mut()
+            .filter(|rule| rule.pubkey == pubkey)
+            .filter_map(|rule| rule.check(account.lamports))
+            .collect()
+    }
+
+    fn on_transaction(
+        &mut self,
+        filters: &[String],
+        msg: SubscribeUpdateTransactionStatus,
+    ) -> Vec<String> {
+        let signature = Signature::try_from(msg.signature.as_slice())
+            .map(|signature| signature.to_string())
+            .unwrap_or_default();
+        let mut alerts = vec![];
+        for filter in filters {
+            if let Some(program) = filter.strip_prefix(FILTER_ERROR_RATE_PREFIX) {
+                for rule in self
+                    .error_rates
+                    .iter_mut()
+                    .filter(|rule| rule.program == program)
+                {
+                    alerts.extend(rule.check(
+                        msg.slot,
+                        msg.err.is_some(),
+                        self.error_rate_window,
+                        self.error_rate_min_transactions,
+                    ));
+                }
+            } else if filter.starts_with(FILTER_SIGNATURE) {
+                alerts.push(format!(
+                    "transaction `{signature}` {} in slot {}",
+                    if msg.err.is_some() {
+                        "failed"
+                    } else {
+                        "succeeded"
+                    },
+                    msg.slot
+                ));
+            }
+        }
+        alerts
+    }
+}
+
+struct Webhooks {
+    http: reqwest::Client,
+    discord: Vec<String>,
+    slack: Vec<String>,
+}
+
+impl Webhooks {
+    /// Post the alert to all webhooks, failed deliveries are logged only so
+    /// alerting is not stopped by an unavailable webhook
+    async fn post(&self, text: &str) {
+        let discord = self
+            .discord
+            .iter()
+            .map(|url| (url, json!({ "content": text })));
+        let slack = self.slack.iter().map(|url| (url, json!({ "text": text })));
+        for (url, body) in discord.chain(slack) {
+            let result = self
+                .http
+                .post(url)
+                .json(&body)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            if let Err(error) = result {
+                error!("failed to post alert: {error}");
+            }
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env::set_var(
+        env_logger::DEFAULT_FILTER_ENV,
+        env::var_os(env_logger::DEFAULT_FILTER_ENV).unwrap_or_else(|| "info".into()),
+    );
+    env_logger::init();
+
+    let args = Args::parse();
+    if args.discord_webhook.is_empty() && args.slack_webhook.is_empty() {
+        warn!("no webhooks, alerts are logged only");
+    }
+
+    let mut rules = Rules::new(&args);
+    let request = rules.subscribe_request(&args.signature);
+    let webhooks = Webhooks {
+        http: reqwest::Client::new(),
+        discord: args.discord_webhook,
+        slack: args.slack_webhook,
+    };
+
+    let mut client = GeyserGrpcClient::build_from_shared(args.endpoint)?
+        .x_token(args.x_token)?
+        .tls_config(ClientTlsConfig::new().with_native_roots())?
+        .connect()
+        .await?;
+    let (mut subscribe_tx, mut stream) = client.subscribe().await?;
+    subscribe_tx.send(request).await?;
+    info!("stream opened");
+
+    while let Some(message) = stream.next().await {
+        let update = message?;
+        let alerts = match update.update_oneof {
+            Some(UpdateOneof::Account(msg)) => rules.on_account(msg),
+            Some(UpdateOneof::TransactionStatus(msg)) => rules.on_transaction(&update.filters, msg),
+            _ => vec![],
+        };
+        for alert in alerts {
+            info!("alert: {alert}");
+            webhooks.post(&alert).await;
+        }
+    }
+    info!("stream closed");
+
+    Ok(())
+}
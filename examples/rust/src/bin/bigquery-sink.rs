@@ -0,0 +1,602 @@
+use {
+    anyhow::Context,
+    clap::Parser,
+    futures::{
+        sink::SinkExt,
+        stream::{Stream, StreamExt},
+    },
+    gcp_bigquery_client::{
+        model::{
+            table::Table, table_field_schema::TableFieldSchema, table_schema::TableSchema,
+            time_partitioning::TimePartitioning,
+        },
+        storage::{ColumnType, FieldDescriptor, StreamName, TableDescriptor},
+        Client,
+    },
+    log::info,
+    std::{collections::HashMap, env, fmt},
+    tonic::{transport::channel::ClientTlsConfig, Status},
+    yellowstone_grpc_client::{
+        sink::{
+            run_finalized_sink, run_finalized_sink_with_dlq, DeadLetterFile, FinalizedSink,
+            SinkRetryConfig,
+        },
+        transform::{transform_stream, BuiltinTransform, TransformChain},
+        GeyserGrpcClient,
+    },
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
+        SubscribeRequestFilterAccounts, SubscribeRequestFilterSlots,
+        SubscribeRequestFilterTransactions, SubscribeUpdate, SubscribeUpdateAccount,
+        SubscribeUpdateTransaction,
+    },
+};
+
+/// Max rows in one `AppendRows` request, request size is limited by 10MiB
+const APPEND_ROWS_CHUNK: usize = 500;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+#[derive(Debug, Clone, Parser)]
+#[clap(author, version, about)]
+struct Args {
+    #[clap(short, long, default_value_t = String::from("http://127.0.0.1:10000"))]
+    /// Service endpoint
+    endpoint: String,
+
+    #[clap(long)]
+    x_token: Option<String>,
+
+    /// Path to the service account key of GCP
+    #[clap(long)]
+    sa_key: String,
+
+    /// GCP project of the dataset
+    #[clap(long)]
+    project: String,
+
+    /// BigQuery dataset, should exist
+    #[clap(long)]
+    dataset: String,
+
+    /// Table for transactions, created if not exists
+    #[clap(long, default_value_t = String::from("transactions"))]
+    transactions_table: String,
+
+    /// Table for account updates, created if not exists
+    #[clap(long, default_value_t = String::from("accounts"))]
+    accounts_table: String,
+
+    /// Write transactions which include any of accounts
+    #[clap(long)]
+    transactions_account_include: Vec<String>,
+
+    /// Write vote transactions
+    #[clap(long, default_value_t = false)]
+    transactions_vote: bool,
+
+    /// Write updates of accounts owned by any of programs
+    #[clap(long)]
+    accounts_owner: Vec<String>,
+
+    /// Write data of accounts
+    #[clap(long, default_value_t = false)]
+    accounts_data: bool,
+
+    /// Write updates rejected by BigQuery to the file instead of stopping,
+    /// applied to finalized commit mode only
+    #[clap(long)]
+    dead_letter_file: Option<String>,
+
+    /// Attempts to write the slot while BigQuery is unavailable, the sink is
+    /// stopped after the last attempt
+    #[clap(long, default_value_t = 3)]
+    dead_letter_attempts: u32,
+
+    /// Write updates at processed commitment without waiting for finalization,
+    /// rows of abandoned forks are not removed
+    #[clap(long, default_value_t = false)]
+    processed: bool,
+
+    /// Transforms applied to updates before they are written, in order:
+    /// drop-votes, drop-failed, drop-account-data, drop-transaction-logs,
+    /// account-data-slice=OFFSET:LENGTH
+    #[clap(long)]
+    transform: Vec<BuiltinTransform>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Column {
+    Int64,
+    Bool,
+    String,
+    Bytes,
+    Date,
+    Timestamp,
+}
+
+impl Column {
+    fn field_schema(self, name: &str) -> TableFieldSchema {
+        match self {
+            Self::Int64 => TableFieldSchema::integer(name),
+            Self::Bool => TableFieldSchema::bool(name),
+            Self::String => TableFieldSchema::string(name),
+            Self::Bytes => TableFieldSchema::bytes(name),
+            Self::Date => TableFieldSchema::date(name),
+            Self::Timestamp => TableFieldSchema::timestamp(name),
+        }
+    }
+
+    const fn column_type(self) -> ColumnType {
+        match self {
+            Self::Int64 => ColumnType::Int64,
+            Self::Bool => ColumnType::Bool,
+            Self::String => ColumnType::String,
+            Self::Bytes => ColumnType::Bytes,
+            // days since epoch
+            Self::Date => ColumnType::Int32,
+            // microseconds since epoch
+            Self::Timestamp => ColumnType::Int64,
+        }
+    }
+}
+
+/// Columns of the transactions table, field numbers of `TransactionRow` are
+/// positions in the list starting from 1
+const TRANSACTIONS_COLUMNS: &[(&str, Column)] = &[
+    ("slot", Column::Int64),
+    ("block_date", Column::Date),
+    ("block_time", Column::Timestamp),
+    ("commitment", Column::String),
+    ("signature", Column::String),
+    ("index", Column::Int64),
+    ("is_vote", Column::Bool),
+    ("success", Column::Bool),
+    ("error", Column::String),
+    ("fee", Column::Int64),
+    ("compute_units_consumed", Column::Int64),
+];
+
+/// Columns of the accounts table, field numbers of `AccountRow` are positions
+/// in the list starting from 1
+const ACCOUNTS_COLUMNS: &[(&str, Column)] = &[
+    ("slot", Column::Int64),
+    ("block_date", Column::Date),
+    ("block_time", Column::Timestamp),
+    ("commitment", Column::String),
+    ("pubkey", Column::String),
+    ("owner", Column::String),
+    ("lamports", Column::Int64),
+    ("executable", Column::Bool),
+    ("rent_epoch", Column::Int64),
+    ("data_len", Column::Int64),
+    ("data", Column::Bytes),
+    ("write_version", Column::Int64),
+    ("txn_signature", Column::String),
+];
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct TransactionRow {
+    #[prost(int64, tag = "1")]
+    slot: i64,
+    #[prost(int32, tag = "2")]
+    block_date: i32,
+    #[prost(int64, optional, tag = "3")]
+    block_time: Option<i64>,
+    #[prost(string, tag = "4")]
+    commitment: String,
+    #[prost(string, tag = "5")]
+    signature: String,
+    #[prost(int64, tag = "6")]
+    index: i64,
+    #[prost(bool, tag = "7")]
+    is_vote: bool,
+    #[prost(bool, tag = "8")]
+    success: bool,
+    #[prost(string, optional, tag = "9")]
+    error: Option<String>,
+    #[prost(int64, tag = "10")]
+    fee: i64,
+    #[prost(int64, optional, tag = "11")]
+    compute_units_consumed: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct AccountRow {
+    #[prost(int64, tag = "1")]
+    slot: i64,
+    #[prost(int32, tag = "2")]
+    block_date: i32,
+    #[prost(int64, optional, tag = "3")]
+    block_time: Option<i64>,
+    #[prost(string, tag = "4")]
+    commitment: String,
+    #[prost(string, tag = "5")]
+    pubkey: String,
+    #[prost(string, tag = "6")]
+    owner: String,
+    #[prost(int64, tag = "7")]
+    lamports: i64,
+    #[prost(bool, tag = "8")]
+    executable: bool,
+    #[prost(int64, tag = "9")]
+    rent_epoch: i64,
+    #[prost(int64, tag = "10")]
+    data_len: i64,
+    #[prost(bytes = "vec", optional, tag = "11")]
+    data: Option<Vec<u8>>,
+    #[prost(int64, tag = "12")]
+    write_version: i64,
+    #[prost(string, optional, tag = "13")]
+    txn_signature: Option<String>,
+}
+
+/// Block time of the slot, partitioning date is derived from it
+#[derive(Debug, Clone, Copy)]
+struct RowTime {
+    block_date: i32,
+    block_time: Option<i64>,
+}
+
+impl RowTime {
+    /// `block_time` in seconds from block meta, `fallback` in seconds is used
+    /// for the date if block meta is not received
+    const fn new(block_time: Option<i64>, fallback: i64) -> Self {
+        let seconds = match block_time {
+            Some(seconds) => seconds,
+            None => fallback,
+        };
+        Self {
+            block_date: seconds.div_euclid(SECONDS_PER_DAY) as i32,
+            block_time: match block_time {
+                Some(seconds) => Some(seconds * 1_000_000),
+                None => None,
+            },
+        }
+    }
+}
+
+impl TransactionRow {
+    fn new(msg: SubscribeUpdateTransaction, time: RowTime, commitment: &str) -> Option<Self> {
+        let tx = msg.transaction?;
+        let meta = tx.meta.unwrap_or_default();
+        let error = meta.err.map(|error| hex::encode(error.err));
+        Some(Self {
+            slot: msg.slot as i64,
+            block_date: time.block_date,
+            block_time: time.block_time,
+            commitment: commitment.to_owned(),
+            signature: bs58::encode(tx.signature).into_string(),
+            index: tx.index as i64,
+            is_vote: tx.is_vote,
+            success: error.is_none(),
+            error,
+            fee: meta.fee as i64,
+            compute_units_consumed: meta.compute_units_consumed.map(|units| units as i64),
+        })
+    }
+}
+
+impl AccountRow {
+    fn new(
+        msg: SubscribeUpdateAccount,
+        time: RowTime,
+        commitment: &str,
+        with_data: bool,
+    ) -> Option<Self> {
+        let account = msg.account?;
+        Some(Self {
+            slot: msg.slot as i64,
+            block_date: time.block_date,
+            block_time: time.block_time,
+            commitment: commitment.to_owned(),
+            pubkey: bs58::encode(account.pubkey).into_string(),
+            owner: bs58::encode(account.owner).into_string(),
+            lamports: account.lamports as i64,
+            executable: account.executable,
+            rent_epoch: account.rent_epoch as i64,
+            data_len: account.data.len() as i64,
+            data: with_data.then_some(account.data),
+            write_version: account.write_version as i64,
+            txn_signature: account
+                .txn_signature
+                .map(|signature| bs58::encode(signature).into_string()),
+        })
+    }
+}
+
+fn table_descriptor(columns: &[(&str, Column)]) -> TableDescriptor {
+    TableDescriptor {
+        field_descriptors: columns
+            .iter()
+            .enumerate()
+            .map(|(index, (name, column))| FieldDescriptor {
+                number: index as u32 + 1,
+                name: (*name).to_owned(),
+                typ: column.column_type(),
+            })
+            .collect(),
+    }
+}
+
+/// Create the table partitioned by `block_date` or check that existing table
+/// has all columns
+async fn ensure_table(
+    client: &Client,
+    args: &Args,
+    table_id: &str,
+    columns: &[(&str, Column)],
+) -> anyhow::Result<()> {
+    if let Ok(table) = client
+        .table()
+        .get(&args.project, &args.dataset, table_id, None)
+        .await
+    {
+        let fields = table.schema.fields.unwrap_or_default();
+        for (name, _column) in columns {
+            anyhow::ensure!(
+                fields.iter().any(|field| field.name == *name),
+                "table {table_id} has no column {name}"
+            );
+        }
+        info!("table {table_id} exists");
+        return Ok(());
+    }
+
+    let schema = TableSchema::new(
+        columns
+            .iter()
+            .map(|(name, column)| column.field_schema(name))
+            .collect(),
+    );
+    let table = Table::new(&args.project, &args.dataset, table_id, schema)
+        .time_partitioning(TimePartitioning::per_day().field("block_date"));
+    client
+        .table()
+        .create(table)
+        .await
+        .with_context(|| format!("failed to create table {table_id}"))?;
+    info!("table {table_id} created");
+    Ok(())
+}
+
+struct BigQuerySink {
+    client: Client,
+    transactions_stream: StreamName,
+    transactions_descriptor: TableDescriptor,
+    accounts_stream: StreamName,
+    accounts_descriptor: TableDescriptor,
+    accounts_data: bool,
+    commitment: &'static str,
+}
+
+impl BigQuerySink {
+    async fn write(&mut self, updates: Vec<SubscribeUpdate>) -> anyhow::Result<()> {
+        let block_times = updates
+            .iter()
+            .filter_map(|update| match &update.update_oneof {
+                Some(UpdateOneof::BlockMeta(msg)) => msg
+                    .block_time
+                    .map(|block_time| (msg.slot, block_time.timestamp)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut transactions = vec![];
+        let mut accounts = vec![];
+        for update in updates {
+            let created_at = update
+                .created_at
+                .map(|created_at| created_at.seconds)
+                .unwrap_or_default();
+            match update.update_oneof {
+                Some(UpdateOneof::Transaction(msg)) => {
+                    let time = RowTime::new(block_times.get(&msg.slot).copied(), created_at);
+                    transactions.extend(TransactionRow::new(msg, time, self.commitment));
+                }
+                Some(UpdateOneof::Account(msg)) => {
+                    let time = RowTime::new(block_times.get(&msg.slot).copied(), created_at);
+                    accounts.extend(AccountRow::new(
+                        msg,
+                        time,
+                        self.commitment,
+                        self.accounts_data,
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Self::append(
+            &mut self.client,
+            &self.transactions_stream,
+            &self.transactions_descriptor,
+            &transactions,
+        )
+        .await
+        .context("failed to append transactions")?;
+        Self::append(
+            &mut self.client,
+            &self.accounts_stream,
+            &self.accounts_descriptor,
+            &accounts,
+        )
+        .await
+        .context("failed to append accounts")
+    }
+
+    async fn append<M: prost::Message>(
+        client: &mut Client,
+        stream: &StreamName,
+        descriptor: &TableDescriptor,
+        rows: &[M],
+    ) -> anyhow::Result<()> {
+        for chunk in rows.chunks(APPEND_ROWS_CHUNK) {
+            let mut responses = client
+                .storage_mut()
+                .append_rows(stream, descriptor, chunk, "yellowstone-grpc".to_owned())
+                .await?;
+            while let Some(response) = responses.next().await {
+                let response = response?;
+                if let Some(error) = response.row_errors.first() {
+                    return Err(RowRejected {
+                        index: error.index,
+                        message: error.message.clone(),
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Row can't be written at all, retry will fail too
+#[derive(Debug)]
+struct RowRejected {
+    index: i64,
+    message: String,
+}
+
+impl fmt::Display for RowRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "row {} rejected: {}", self.index, self.message)
+    }
+}
+
+impl std::error::Error for RowRejected {}
+
+impl FinalizedSink for BigQuerySink {
+    type Error = anyhow::Error;
+
+    async fn commit(&mut self, slot: u64, updates: Vec<SubscribeUpdate>) -> anyhow::Result<()> {
+        let count = updates.len();
+        self.write(updates).await?;
+        info!("slot {slot} committed, {count} updates");
+        Ok(())
+    }
+
+    fn is_retryable(&self, error: &Self::Error) -> bool {
+        error.downcast_ref::<RowRejected>().is_none()
+    }
+}
+
+/// Write updates as they arrive, every batch is appended with one request per table
+async fn run_processed(
+    stream: impl Stream<Item = Result<SubscribeUpdate, Status>>,
+    sink: &mut BigQuerySink,
+) -> anyhow::Result<()> {
+    let mut stream = std::pin::pin!(stream.ready_chunks(APPEND_ROWS_CHUNK));
+    while let Some(messages) = stream.next().await {
+        let updates = messages.into_iter().collect::<Result<Vec<_>, _>>()?;
+        sink.write(updates).await?;
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env::set_var(
+        env_logger::DEFAULT_FILTER_ENV,
+        env::var_os(env_logger::DEFAULT_FILTER_ENV).unwrap_or_else(|| "info".into()),
+    );
+    env_logger::init();
+
+    let args = Args::parse();
+
+    let bigquery = Client::from_service_account_key_file(&args.sa_key)
+        .await
+        .context("failed to create BigQuery client")?;
+    ensure_table(
+        &bigquery,
+        &args,
+        &args.transactions_table,
+        TRANSACTIONS_COLUMNS,
+    )
+    .await?;
+    ensure_table(&bigquery, &args, &args.accounts_table, ACCOUNTS_COLUMNS).await?;
+    let mut sink = BigQuerySink {
+        client: bigquery,
+        transactions_stream: StreamName::new_default(
+            args.project.clone(),
+            args.dataset.clone(),
+            args.transactions_table.clone(),
+        ),
+        transactions_descriptor: table_descriptor(TRANSACTIONS_COLUMNS),
+        accounts_stream: StreamName::new_default(
+            args.project.clone(),
+            args.dataset.clone(),
+            args.accounts_table.clone(),
+        ),
+        accounts_descriptor: table_descriptor(ACCOUNTS_COLUMNS),
+        accounts_data: args.accounts_data,
+        commitment: if args.processed {
+            "processed"
+        } else {
+            "finalized"
+        },
+    };
+
+    let transform = args.transform.iter().copied().collect::<TransformChain>();
+    let mut request = SubscribeRequest::default()
+        .with_blocks_meta("bigquery")
+        .with_commitment(CommitmentLevel::Processed);
+    if !args.transactions_account_include.is_empty() {
+        request = request.with_transactions(
+            "bigquery",
+            SubscribeRequestFilterTransactions::for_account(&args.transactions_account_include)
+                .with_vote(args.transactions_vote),
+        );
+    }
+    if !args.accounts_owner.is_empty() {
+        request = request.with_accounts(
+            "bigquery",
+            SubscribeRequestFilterAccounts::with_owner(&args.accounts_owner),
+        );
+    }
+    if !args.processed {
+        // all slot statuses are required to stage updates until finalization
+        request = request.with_slots(
+            "bigquery",
+            SubscribeRequestFilterSlots {
+                filter_by_commitment: Some(false),
+                ..Default::default()
+            },
+        );
+    }
+
+    let mut client = GeyserGrpcClient::build_from_shared(args.endpoint)?
+        .x_token(args.x_token)?
+        .tls_config(ClientTlsConfig::new().with_native_roots())?
+        .connect()
+        .await?;
+    let (mut subscribe_tx, stream) = client.subscribe().await?;
+    subscribe_tx.send(request).await?;
+    info!("stream opened");
+    let stream = transform_stream(stream, transform);
+
+    if args.processed {
+        run_processed(stream, &mut sink).await
+    } else {
+        let last_committed = match args.dead_letter_file {
+            Some(path) => {
+                let mut dlq = DeadLetterFile::open(&path)
+                    .with_context(|| format!("failed to open dead letter file {path}"))?;
+                run_finalized_sink_with_dlq(
+                    stream,
+                    &mut sink,
+                    &mut dlq,
+                    None,
+                    SinkRetryConfig {
+                        max_attempts: args.dead_letter_attempts,
+                        ..Default::default()
+                    },
+                )
+                .await?
+            }
+            None => run_finalized_sink(stream, &mut sink, None).await?,
+        };
+        info!("stream closed, last committed slot: {last_committed:?}");
+        Ok(())
+    }
+}
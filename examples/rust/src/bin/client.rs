@@ -17,7 +17,10 @@ use {
         time::{Duration, Instant, SystemTime, UNIX_EPOCH},
     },
     tokio::{fs, sync::Mutex},
-    tonic::transport::{channel::ClientTlsConfig, Certificate},
+    tonic::{
+        codec::CompressionEncoding,
+        transport::{channel::ClientTlsConfig, Certificate},
+    },
     yellowstone_grpc_client::{GeyserGrpcClient, GeyserGrpcClientError, Interceptor},
     yellowstone_grpc_proto::{
         convert_from,
@@ -33,7 +36,8 @@ use {
             SubscribeRequestFilterAccountsFilterMemcmp, SubscribeRequestFilterBlocks,
             SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterEntry,
             SubscribeRequestFilterSlots, SubscribeRequestFilterTransactions, SubscribeRequestPing,
-            SubscribeUpdateAccountInfo, SubscribeUpdateEntry, SubscribeUpdateTransactionInfo,
+            SubscribeRequestSlotBatch, SubscribeRequestSlotManifest, SubscribeUpdateAccountInfo,
+            SubscribeUpdateEntry, SubscribeUpdateTransactionInfo,
         },
         prost::Message,
     },
@@ -109,6 +113,15 @@ struct Args {
     #[clap(long, default_value_t = 1024 * 1024 * 1024)]
     max_decoding_message_size: usize,
 
+    /// Compress requests sent to the server
+    #[clap(long)]
+    send_compressed: Option<ArgsCompression>,
+
+    /// Ask the server to compress updates, blocks and accounts of busy programs
+    /// are compressed several times
+    #[clap(long)]
+    accept_compressed: Option<ArgsCompression>,
+
     /// Commitment level: processed, confirmed or finalized
     #[clap(long)]
     commitment: Option<ArgsCommitment>,
@@ -166,11 +179,32 @@ impl Args {
         if let Some(duration) = self.timeout_ms {
             builder = builder.timeout(Duration::from_millis(duration));
         }
+        if let Some(compression) = self.send_compressed {
+            builder = builder.send_compressed(compression.into());
+        }
+        if let Some(compression) = self.accept_compressed {
+            builder = builder.accept_compressed(compression.into());
+        }
 
         builder.connect().await.map_err(Into::into)
     }
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ArgsCompression {
+    Gzip,
+    Zstd,
+}
+
+impl From<ArgsCompression> for CompressionEncoding {
+    fn from(compression: ArgsCompression) -> Self {
+        match compression {
+            ArgsCompression::Gzip => CompressionEncoding::Gzip,
+            ArgsCompression::Zstd => CompressionEncoding::Zstd,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, ValueEnum)]
 enum ArgsCommitment {
     #[default]
@@ -202,6 +236,7 @@ enum Action {
     GetLatestBlockhash,
     GetBlockHeight,
     GetSlot,
+    GetSlotWatermarks,
     IsBlockhashValid {
         #[clap(long, short)]
         blockhash: String,
@@ -218,6 +253,14 @@ struct ActionSubscribe {
     /// Filter by presence of field txn_signature
     accounts_nonempty_txn_signature: Option<bool>,
 
+    /// Skip accounts with empty data
+    #[clap(long)]
+    accounts_nonempty_data: bool,
+
+    /// Receive only accounts with changed owner
+    #[clap(long)]
+    accounts_owner_changed: bool,
+
     /// Filter by Account Pubkey
     #[clap(long)]
     accounts_account: Vec<String>,
@@ -290,6 +333,26 @@ struct ActionSubscribe {
     #[clap(long)]
     transactions_account_required: Vec<String>,
 
+    /// Filter transactions by substring in log messages
+    #[clap(long)]
+    transactions_log_contains: Vec<String>,
+
+    /// Filter transactions by fee payer
+    #[clap(long)]
+    transactions_fee_payer: Vec<String>,
+
+    /// Filter transactions by minimum fee in lamports
+    #[clap(long)]
+    transactions_fee_min: Option<u64>,
+
+    /// Filter transactions by minimum compute unit price in micro-lamports
+    #[clap(long)]
+    transactions_compute_unit_price_min: Option<u64>,
+
+    /// Filter transactions by program invoked in outer or inner instructions
+    #[clap(long)]
+    transactions_program_invoked: Vec<String>,
+
     /// Subscribe on transactions_status updates
     #[clap(long)]
     transactions_status: bool,
@@ -318,6 +381,26 @@ struct ActionSubscribe {
     #[clap(long)]
     transactions_status_account_required: Vec<String>,
 
+    /// Filter transactions by substring in log messages for transactions_status
+    #[clap(long)]
+    transactions_status_log_contains: Vec<String>,
+
+    /// Filter transactions by fee payer for transactions_status
+    #[clap(long)]
+    transactions_status_fee_payer: Vec<String>,
+
+    /// Filter transactions by minimum fee in lamports for transactions_status
+    #[clap(long)]
+    transactions_status_fee_min: Option<u64>,
+
+    /// Filter transactions by minimum compute unit price in micro-lamports for transactions_status
+    #[clap(long)]
+    transactions_status_compute_unit_price_min: Option<u64>,
+
+    /// Filter transactions by program invoked in outer or inner instructions for transactions_status
+    #[clap(long)]
+    transactions_status_program_invoked: Vec<String>,
+
     #[clap(long)]
     entries: bool,
 
@@ -353,6 +436,26 @@ struct ActionSubscribe {
     #[clap(long)]
     ping: Option<i32>,
 
+    /// Receive manifest with counts of delivered updates after each slot
+    #[clap(long)]
+    slot_manifest: bool,
+
+    /// Include content hash to slot manifest
+    #[clap(long)]
+    slot_manifest_content_hash: bool,
+
+    /// Receive matched accounts and transactions of the slot in one batch
+    #[clap(long)]
+    slot_batch: bool,
+
+    /// Max encoded size of one slot batch frame
+    #[clap(long)]
+    slot_batch_max_frame_size: Option<u64>,
+
+    /// Subscription template from the server config, filters are not sent
+    #[clap(long)]
+    template: Option<String>,
+
     /// Resubscribe (only to slots) after
     #[clap(long)]
     resub: Option<usize>,
@@ -447,6 +550,11 @@ impl Action {
                             account: accounts_account,
                             owner: args.accounts_owner.clone(),
                             filters,
+                            nonempty_data: args.accounts_nonempty_data.then_some(true),
+                            owner_changed: args.accounts_owner_changed.then_some(true),
+                            pda: vec![],
+                            data_slice: vec![],
+                            commitment: None,
                         },
                     );
                 }
@@ -473,6 +581,11 @@ impl Action {
                             account_include: args.transactions_account_include.clone(),
                             account_exclude: args.transactions_account_exclude.clone(),
                             account_required: args.transactions_account_required.clone(),
+                            log_contains: args.transactions_log_contains.clone(),
+                            fee_payer: args.transactions_fee_payer.clone(),
+                            fee_min: args.transactions_fee_min,
+                            compute_unit_price_min: args.transactions_compute_unit_price_min,
+                            program_invoked: args.transactions_program_invoked.clone(),
                         },
                     );
                 }
@@ -488,6 +601,11 @@ impl Action {
                             account_include: args.transactions_status_account_include.clone(),
                             account_exclude: args.transactions_status_account_exclude.clone(),
                             account_required: args.transactions_status_account_required.clone(),
+                            log_contains: args.transactions_status_log_contains.clone(),
+                            fee_payer: args.transactions_status_fee_payer.clone(),
+                            fee_min: args.transactions_status_fee_min,
+                            compute_unit_price_min: args.transactions_status_compute_unit_price_min,
+                            program_invoked: args.transactions_status_program_invoked.clone(),
                         },
                     );
                 }
@@ -530,9 +648,19 @@ impl Action {
                 }
 
                 let ping = args.ping.map(|id| SubscribeRequestPing { id });
+                let slot_batch = args.slot_batch.then_some(SubscribeRequestSlotBatch {
+                    max_frame_size: args.slot_batch_max_frame_size,
+                });
 
-                Some((
-                    SubscribeRequest {
+                let request = match &args.template {
+                    Some(template) => SubscribeRequest {
+                        ping,
+                        from_slot: args.from_slot,
+                        template: Some(template.clone()),
+                        slot_batch,
+                        ..Default::default()
+                    },
+                    None => SubscribeRequest {
                         slots,
                         accounts,
                         transactions,
@@ -544,7 +672,17 @@ impl Action {
                         accounts_data_slice,
                         ping,
                         from_slot: args.from_slot,
+                        slot_manifest: args.slot_manifest.then_some(SubscribeRequestSlotManifest {
+                            content_hash: args.slot_manifest_content_hash,
+                        }),
+                        template: None,
+                        slot_batch,
+                        request_id: None,
                     },
+                };
+
+                Some((
+                    request,
                     args.resub.unwrap_or(0),
                     args.stats,
                     args.verify_encoding,
@@ -630,6 +768,11 @@ async fn main() -> anyhow::Result<()> {
                     .await
                     .map_err(anyhow::Error::new)
                     .map(|response| info!("response: {response:?}")),
+                Action::GetSlotWatermarks => client
+                    .get_slot_watermarks()
+                    .await
+                    .map_err(anyhow::Error::new)
+                    .map(|response| info!("response: {response:?}")),
                 Action::IsBlockhashValid { blockhash } => client
                     .is_blockhash_valid(blockhash.clone(), commitment)
                     .await
@@ -707,8 +850,11 @@ async fn geyser_subscribe(
                         Some(UpdateOneof::Entry(_)) => (&mut pb_entries_c, &pb_entries),
                         Some(UpdateOneof::BlockMeta(_)) => (&mut pb_blocks_mt_c, &pb_blocks_mt),
                         Some(UpdateOneof::Block(_)) => (&mut pb_blocks_c, &pb_blocks),
+                        Some(UpdateOneof::SlotManifest(_)) => (&mut pb_slots_c, &pb_slots),
+                        Some(UpdateOneof::SlotBatch(_)) => (&mut pb_blocks_c, &pb_blocks),
                         Some(UpdateOneof::Ping(_)) => (&mut pb_pp_c, &pb_pp),
                         Some(UpdateOneof::Pong(_)) => (&mut pb_pp_c, &pb_pp),
+                        Some(UpdateOneof::FilterApplied(_)) => (&mut pb_pp_c, &pb_pp),
                         None => {
                             pb_multi.println("update not found in the message")?;
                             break;
@@ -767,12 +913,10 @@ async fn geyser_subscribe(
                     continue;
                 }
 
-                let filters = msg.filters;
                 let created_at: SystemTime = msg
-                    .created_at
-                    .ok_or(anyhow::anyhow!("no created_at in the message"))?
-                    .try_into()
-                    .context("failed to parse created_at")?;
+                    .created_at_system_time()
+                    .ok_or(anyhow::anyhow!("no valid created_at in the message"))?;
+                let filters = msg.filters;
                 match msg.update_oneof {
                     Some(UpdateOneof::Account(msg)) => {
                         let account = msg
@@ -884,6 +1028,45 @@ async fn geyser_subscribe(
                             .await?;
                     }
                     Some(UpdateOneof::Pong(_)) => {}
+                    Some(UpdateOneof::SlotManifest(msg)) => {
+                        print_update(
+                            "slotManifest",
+                            created_at,
+                            &filters,
+                            json!({
+                                "slot": msg.slot,
+                                "accounts": msg.accounts,
+                                "transactions": msg.transactions,
+                                "transactionsStatus": msg.transactions_status,
+                                "entries": msg.entries,
+                                "blocksMeta": msg.blocks_meta,
+                                "blocks": msg.blocks,
+                                "contentHash": msg.content_hash,
+                            }),
+                        );
+                    }
+                    Some(UpdateOneof::SlotBatch(msg)) => {
+                        print_update(
+                            "slotBatch",
+                            created_at,
+                            &filters,
+                            json!({
+                                "slot": msg.slot,
+                                "index": msg.index,
+                                "isLast": msg.is_last,
+                                "accounts": msg.accounts.into_iter().filter_map(|msg| msg.account).map(create_pretty_account).collect::<Result<Value, _>>()?,
+                                "transactions": msg.transactions.into_iter().filter_map(|msg| msg.transaction).map(create_pretty_transaction).collect::<Result<Value, _>>()?,
+                            }),
+                        );
+                    }
+                    Some(UpdateOneof::FilterApplied(msg)) => {
+                        print_update(
+                            "filterApplied",
+                            created_at,
+                            &filters,
+                            json!({ "requestId": msg.request_id }),
+                        );
+                    }
                     None => {
                         error!("update not found in the message");
                         break;
@@ -915,6 +1098,10 @@ async fn geyser_subscribe(
                     accounts_data_slice: Vec::default(),
                     ping: None,
                     from_slot: None,
+                    slot_manifest: None,
+                    template: None,
+                    slot_batch: None,
+                    request_id: None,
                 })
                 .await
                 .map_err(GeyserGrpcClientError::SubscribeSendError)?;
@@ -0,0 +1,257 @@
+use {
+    clap::{Parser, ValueEnum},
+    futures::{sink::SinkExt, stream::StreamExt},
+    log::{error, info},
+    rumqttc::{AsyncClient, MqttOptions, QoS},
+    serde_json::json,
+    std::{env, time::Duration},
+    tonic::transport::channel::ClientTlsConfig,
+    yellowstone_grpc_client::{
+        transform::{transform_stream, BuiltinTransform, TransformChain},
+        GeyserGrpcClient,
+    },
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, CommitmentLevel, SlotStatus, SubscribeRequest,
+        SubscribeRequestFilterAccounts, SubscribeRequestFilterSlots,
+        SubscribeRequestFilterTransactions, SubscribeUpdate,
+    },
+};
+
+/// Capacity of the queue of outgoing MQTT requests
+const MQTT_QUEUE_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone, Parser)]
+#[clap(author, version, about)]
+struct Args {
+    #[clap(short, long, default_value_t = String::from("http://127.0.0.1:10000"))]
+    /// Service endpoint
+    endpoint: String,
+
+    #[clap(long)]
+    x_token: Option<String>,
+
+    /// Commitment level: processed, confirmed or finalized
+    #[clap(long)]
+    commitment: Option<ArgsCommitment>,
+
+    /// MQTT broker host
+    #[clap(long, default_value_t = String::from("127.0.0.1"))]
+    mqtt_host: String,
+
+    /// MQTT broker port
+    #[clap(long, default_value_t = 1883)]
+    mqtt_port: u16,
+
+    #[clap(long, default_value_t = String::from("yellowstone-grpc"))]
+    mqtt_client_id: String,
+
+    #[clap(long)]
+    mqtt_username: Option<String>,
+
+    #[clap(long)]
+    mqtt_password: Option<String>,
+
+    /// Delivery guarantee of published messages
+    #[clap(long, default_value_t = ArgsQoS::AtMostOnce, value_enum)]
+    mqtt_qos: ArgsQoS,
+
+    /// Root of the topic hierarchy
+    #[clap(long, default_value_t = String::from("solana"))]
+    topic_prefix: String,
+
+    /// Publish account updates as retained messages, so new subscribers
+    /// receive the latest state of every account immediately
+    #[clap(long, default_value_t = false)]
+    retain_accounts: bool,
+
+    /// Publish slot updates to `{prefix}/slots/{status}`
+    #[clap(long, default_value_t = false)]
+    slots: bool,
+
+    /// Publish updates of accounts to `{prefix}/accounts/{owner}/{pubkey}`
+    #[clap(long)]
+    accounts_account: Vec<String>,
+
+    /// Publish updates of accounts owned by any of programs
+    #[clap(long)]
+    accounts_owner: Vec<String>,
+
+    /// Publish transactions which include any of accounts to
+    /// `{prefix}/transactions/{signature}`
+    #[clap(long)]
+    transactions_account_include: Vec<String>,
+
+    /// Transforms applied to updates before they are written, in order:
+    /// drop-votes, drop-failed, drop-account-data, drop-transaction-logs,
+    /// account-data-slice=OFFSET:LENGTH
+    #[clap(long)]
+    transform: Vec<BuiltinTransform>,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum ArgsCommitment {
+    #[default]
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl From<ArgsCommitment> for CommitmentLevel {
+    fn from(commitment: ArgsCommitment) -> Self {
+        match commitment {
+            ArgsCommitment::Processed => CommitmentLevel::Processed,
+            ArgsCommitment::Confirmed => CommitmentLevel::Confirmed,
+            ArgsCommitment::Finalized => CommitmentLevel::Finalized,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ArgsQoS {
+    AtMostOnce,
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+impl From<ArgsQoS> for QoS {
+    fn from(qos: ArgsQoS) -> Self {
+        match qos {
+            ArgsQoS::AtMostOnce => QoS::AtMostOnce,
+            ArgsQoS::AtLeastOnce => QoS::AtLeastOnce,
+            ArgsQoS::ExactlyOnce => QoS::ExactlyOnce,
+        }
+    }
+}
+
+/// Topic, retain flag and compact JSON summary of the update, `None` for
+/// updates which are not published
+fn summary(
+    update: SubscribeUpdate,
+    prefix: &str,
+    retain_accounts: bool,
+) -> Option<(String, bool, serde_json::Value)> {
+    match update.update_oneof? {
+        UpdateOneof::Account(msg) => {
+            let account = msg.account?;
+            let pubkey = bs58::encode(&account.pubkey).into_string();
+            let owner = bs58::encode(&account.owner).into_string();
+            let payload = json!({
+                "slot": msg.slot,
+                "pubkey": pubkey,
+                "owner": owner,
+                "lamports": account.lamports,
+                "data_len": account.data.len(),
+                "write_version": account.write_version,
+                "txn_signature": account
+                    .txn_signature
+                    .map(|signature| bs58::encode(signature).into_string()),
+            });
+            Some((
+                format!("{prefix}/accounts/{owner}/{pubkey}"),
+                retain_accounts,
+                payload,
+            ))
+        }
+        UpdateOneof::Transaction(msg) => {
+            let tx = msg.transaction?;
+            let signature = bs58::encode(&tx.signature).into_string();
+            let meta = tx.meta.unwrap_or_default();
+            let payload = json!({
+                "slot": msg.slot,
+                "signature": signature,
+                "index": tx.index,
+                "is_vote": tx.is_vote,
+                "success": meta.err.is_none(),
+                "fee": meta.fee,
+                "compute_units_consumed": meta.compute_units_consumed,
+            });
+            Some((format!("{prefix}/transactions/{signature}"), false, payload))
+        }
+        UpdateOneof::Slot(msg) => {
+            let status = SlotStatus::try_from(msg.status)
+                .map(|status| status.as_str_name().to_lowercase())
+                .unwrap_or_else(|_| msg.status.to_string());
+            let payload = json!({
+                "slot": msg.slot,
+                "parent": msg.parent,
+                "status": status,
+            });
+            Some((format!("{prefix}/slots/{status}"), false, payload))
+        }
+        _ => None,
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env::set_var(
+        env_logger::DEFAULT_FILTER_ENV,
+        env::var_os(env_logger::DEFAULT_FILTER_ENV).unwrap_or_else(|| "info".into()),
+    );
+    env_logger::init();
+
+    let args = Args::parse();
+
+    let mut options = MqttOptions::new(&args.mqtt_client_id, &args.mqtt_host, args.mqtt_port);
+    options.set_keep_alive(Duration::from_secs(10));
+    if let Some(username) = args.mqtt_username {
+        options.set_credentials(username, args.mqtt_password.unwrap_or_default());
+    }
+    let (mqtt, mut eventloop) = AsyncClient::new(options, MQTT_QUEUE_CAPACITY);
+    // event loop sends queued messages and reconnects to the broker on errors
+    tokio::spawn(async move {
+        loop {
+            if let Err(error) = eventloop.poll().await {
+                error!("MQTT connection error: {error}");
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    });
+
+    let transform = args.transform.iter().copied().collect::<TransformChain>();
+    let commitment: CommitmentLevel = args.commitment.unwrap_or_default().into();
+    let mut request = SubscribeRequest::default().with_commitment(commitment);
+    if args.slots {
+        request = request.with_slots("mqtt", SubscribeRequestFilterSlots::default());
+    }
+    if !args.accounts_account.is_empty() || !args.accounts_owner.is_empty() {
+        request = request.with_accounts(
+            "mqtt",
+            SubscribeRequestFilterAccounts {
+                account: args.accounts_account,
+                owner: args.accounts_owner,
+                ..Default::default()
+            },
+        );
+    }
+    if !args.transactions_account_include.is_empty() {
+        request = request.with_transactions(
+            "mqtt",
+            SubscribeRequestFilterTransactions::for_account(&args.transactions_account_include),
+        );
+    }
+
+    let mut client = GeyserGrpcClient::build_from_shared(args.endpoint)?
+        .x_token(args.x_token)?
+        .tls_config(ClientTlsConfig::new().with_native_roots())?
+        .connect()
+        .await?;
+    let (mut subscribe_tx, stream) = client.subscribe().await?;
+    subscribe_tx.send(request).await?;
+    info!("stream opened");
+
+    let mut stream = std::pin::pin!(transform_stream(stream, transform));
+    let qos = args.mqtt_qos.into();
+    while let Some(message) = stream.next().await {
+        let Some((topic, retain, payload)) =
+            summary(message?, &args.topic_prefix, args.retain_accounts)
+        else {
+            continue;
+        };
+        mqtt.publish(topic, qos, retain, payload.to_string())
+            .await?;
+    }
+    info!("stream closed");
+
+    Ok(())
+}
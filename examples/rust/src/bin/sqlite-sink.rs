@@ -0,0 +1,243 @@
+use {
+    anyhow::Context,
+    clap::{Parser, ValueEnum},
+    futures::{sink::SinkExt, stream::StreamExt},
+    log::info,
+    rusqlite::{params, Connection},
+    std::env,
+    tonic::transport::channel::ClientTlsConfig,
+    yellowstone_grpc_client::{
+        transform::{transform_stream, BuiltinTransform, TransformChain},
+        GeyserGrpcClient,
+    },
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
+        SubscribeRequestFilterAccounts, SubscribeRequestFilterTransactions, SubscribeUpdate,
+    },
+};
+
+/// Max updates written in one SQLite transaction
+const WRITE_BATCH: usize = 1_000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS accounts (
+    slot INTEGER NOT NULL,
+    pubkey TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    lamports INTEGER NOT NULL,
+    executable INTEGER NOT NULL,
+    rent_epoch INTEGER NOT NULL,
+    data BLOB NOT NULL,
+    write_version INTEGER NOT NULL,
+    txn_signature TEXT
+);
+CREATE INDEX IF NOT EXISTS accounts_slot ON accounts (slot);
+CREATE INDEX IF NOT EXISTS accounts_pubkey ON accounts (pubkey, slot);
+
+CREATE TABLE IF NOT EXISTS transactions (
+    slot INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    idx INTEGER NOT NULL,
+    is_vote INTEGER NOT NULL,
+    success INTEGER NOT NULL,
+    fee INTEGER NOT NULL,
+    compute_units_consumed INTEGER
+);
+CREATE INDEX IF NOT EXISTS transactions_slot ON transactions (slot);
+CREATE INDEX IF NOT EXISTS transactions_signature ON transactions (signature);
+";
+
+#[derive(Debug, Clone, Parser)]
+#[clap(author, version, about)]
+struct Args {
+    #[clap(short, long, default_value_t = String::from("http://127.0.0.1:10000"))]
+    /// Service endpoint
+    endpoint: String,
+
+    #[clap(long)]
+    x_token: Option<String>,
+
+    /// Commitment level: processed, confirmed or finalized
+    #[clap(long)]
+    commitment: Option<ArgsCommitment>,
+
+    /// Path to the database, created if not exists
+    #[clap(long, default_value_t = String::from("yellowstone.sqlite"))]
+    db: String,
+
+    /// Keep updates of the last N slots, older rows are removed
+    #[clap(long, default_value_t = 1_000)]
+    retention_slots: u64,
+
+    /// Write updates of accounts
+    #[clap(long)]
+    accounts_account: Vec<String>,
+
+    /// Write updates of accounts owned by any of programs
+    #[clap(long)]
+    accounts_owner: Vec<String>,
+
+    /// Write transactions which include any of accounts
+    #[clap(long)]
+    transactions_account_include: Vec<String>,
+
+    /// Transforms applied to updates before they are written, in order:
+    /// drop-votes, drop-failed, drop-account-data, drop-transaction-logs,
+    /// account-data-slice=OFFSET:LENGTH
+    #[clap(long)]
+    transform: Vec<BuiltinTransform>,
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+enum ArgsCommitment {
+    #[default]
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl From<ArgsCommitment> for CommitmentLevel {
+    fn from(commitment: ArgsCommitment) -> Self {
+        match commitment {
+            ArgsCommitment::Processed => CommitmentLevel::Processed,
+            ArgsCommitment::Confirmed => CommitmentLevel::Confirmed,
+            ArgsCommitment::Finalized => CommitmentLevel::Finalized,
+        }
+    }
+}
+
+struct SqliteSink {
+    conn: Connection,
+    retention_slots: u64,
+    max_slot: u64,
+}
+
+impl SqliteSink {
+    fn open(path: &str, retention_slots: u64) -> anyhow::Result<Self> {
+        let conn = Connection::open(path).with_context(|| format!("failed to open {path}"))?;
+        // readers are not blocked by the writer and vice versa
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn,
+            retention_slots,
+            max_slot: 0,
+        })
+    }
+
+    /// Write updates in one transaction and remove rows out of retention window
+    fn write(&mut self, updates: Vec<SubscribeUpdate>) -> anyhow::Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert_account = tx.prepare_cached(
+                "INSERT INTO accounts (slot, pubkey, owner, lamports, executable, rent_epoch, data, write_version, txn_signature) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            let mut insert_transaction = tx.prepare_cached(
+                "INSERT INTO transactions (slot, signature, idx, is_vote, success, fee, compute_units_consumed) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for update in updates {
+                match update.update_oneof {
+                    Some(UpdateOneof::Account(msg)) => {
+                        let Some(account) = msg.account else {
+                            continue;
+                        };
+                        self.max_slot = self.max_slot.max(msg.slot);
+                        insert_account.execute(params![
+                            msg.slot as i64,
+                            bs58::encode(account.pubkey).into_string(),
+                            bs58::encode(account.owner).into_string(),
+                            account.lamports as i64,
+                            account.executable,
+                            account.rent_epoch as i64,
+                            account.data,
+                            account.write_version as i64,
+                            account
+                                .txn_signature
+                                .map(|signature| bs58::encode(signature).into_string()),
+                        ])?;
+                    }
+                    Some(UpdateOneof::Transaction(msg)) => {
+                        let Some(transaction) = msg.transaction else {
+                            continue;
+                        };
+                        self.max_slot = self.max_slot.max(msg.slot);
+                        let meta = transaction.meta.unwrap_or_default();
+                        insert_transaction.execute(params![
+                            msg.slot as i64,
+                            bs58::encode(transaction.signature).into_string(),
+                            transaction.index as i64,
+                            transaction.is_vote,
+                            meta.err.is_none(),
+                            meta.fee as i64,
+                            meta.compute_units_consumed.map(|units| units as i64),
+                        ])?;
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(min_slot) = self.max_slot.checked_sub(self.retention_slots) {
+                for table in ["accounts", "transactions"] {
+                    tx.execute(
+                        &format!("DELETE FROM {table} WHERE slot <= ?1"),
+                        params![min_slot as i64],
+                    )?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env::set_var(
+        env_logger::DEFAULT_FILTER_ENV,
+        env::var_os(env_logger::DEFAULT_FILTER_ENV).unwrap_or_else(|| "info".into()),
+    );
+    env_logger::init();
+
+    let args = Args::parse();
+
+    let mut sink = SqliteSink::open(&args.db, args.retention_slots)?;
+    let transform = args.transform.iter().copied().collect::<TransformChain>();
+
+    let commitment: CommitmentLevel = args.commitment.unwrap_or_default().into();
+    let mut request = SubscribeRequest::default().with_commitment(commitment);
+    if !args.accounts_account.is_empty() || !args.accounts_owner.is_empty() {
+        request = request.with_accounts(
+            "sqlite",
+            SubscribeRequestFilterAccounts {
+                account: args.accounts_account,
+                owner: args.accounts_owner,
+                ..Default::default()
+            },
+        );
+    }
+    if !args.transactions_account_include.is_empty() {
+        request = request.with_transactions(
+            "sqlite",
+            SubscribeRequestFilterTransactions::for_account(&args.transactions_account_include),
+        );
+    }
+
+    let mut client = GeyserGrpcClient::build_from_shared(args.endpoint)?
+        .x_token(args.x_token)?
+        .tls_config(ClientTlsConfig::new().with_native_roots())?
+        .connect()
+        .await?;
+    let (mut subscribe_tx, stream) = client.subscribe().await?;
+    subscribe_tx.send(request).await?;
+    info!("stream opened, writing to {}", args.db);
+
+    let mut stream = std::pin::pin!(transform_stream(stream, transform).ready_chunks(WRITE_BATCH));
+    while let Some(messages) = stream.next().await {
+        let updates = messages.into_iter().collect::<Result<Vec<_>, _>>()?;
+        tokio::task::block_in_place(|| sink.write(updates))?;
+    }
+    info!("stream closed, last slot: {}", sink.max_slot);
+
+    Ok(())
+}
@@ -104,6 +104,11 @@ async fn main() -> anyhow::Result<()> {
                 account_include: args.account_include,
                 account_exclude: args.account_exclude,
                 account_required: args.account_required,
+                log_contains: vec![],
+                fee_payer: vec![],
+                fee_min: None,
+                compute_unit_price_min: None,
+                program_invoked: vec![],
             } },
             entry: HashMap::new(),
             blocks: HashMap::new(),
@@ -112,6 +117,10 @@ async fn main() -> anyhow::Result<()> {
             accounts_data_slice: vec![],
             ping: None,
             from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
         })
         .await?;
 
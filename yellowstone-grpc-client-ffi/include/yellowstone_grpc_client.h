@@ -0,0 +1,42 @@
+#ifndef YELLOWSTONE_GRPC_CLIENT_H
+#define YELLOWSTONE_GRPC_CLIENT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define YGC_OK 0
+#define YGC_ERR_ARGS 1
+#define YGC_ERR_CONNECT 2
+#define YGC_ERR_STREAM 3
+
+typedef struct YgcRuntime YgcRuntime;
+
+/* `data` is encoded protobuf `geyser.SubscribeUpdate`, valid only during the call.
+ * Return non-zero value to stop the stream. */
+typedef int (*YgcUpdateCallback)(void *user_data, const uint8_t *data, size_t len);
+
+YgcRuntime *ygc_runtime_new(void);
+
+void ygc_runtime_free(YgcRuntime *runtime);
+
+/* Last error on the current thread or NULL, valid until the next call. */
+const char *ygc_last_error(void);
+
+/* Blocks until the stream is finished or `callback` returns non-zero.
+ * `x_token` can be NULL, `request_json` is `geyser.SubscribeRequest` in protobuf JSON format. */
+int ygc_subscribe(const YgcRuntime *runtime,
+                  const char *endpoint,
+                  const char *x_token,
+                  const char *request_json,
+                  YgcUpdateCallback callback,
+                  void *user_data);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* YELLOWSTONE_GRPC_CLIENT_H */
@@ -0,0 +1,180 @@
+//! C ABI for `yellowstone-grpc-client`, see `include/yellowstone_grpc_client.h`.
+//!
+//! Updates are passed to the callback as encoded protobuf `SubscribeUpdate`, so any
+//! protobuf implementation can be used on the other side.
+
+use {
+    futures::stream::StreamExt,
+    prost::Message,
+    std::{
+        cell::RefCell,
+        ffi::{c_char, c_int, c_void, CStr, CString},
+        ptr,
+    },
+    tokio::runtime::{Builder, Runtime},
+    tonic::transport::ClientTlsConfig,
+    yellowstone_grpc_client::GeyserGrpcClient,
+    yellowstone_grpc_proto::prelude::SubscribeRequest,
+};
+
+pub const YGC_OK: c_int = 0;
+pub const YGC_ERR_ARGS: c_int = 1;
+pub const YGC_ERR_CONNECT: c_int = 2;
+pub const YGC_ERR_STREAM: c_int = 3;
+
+/// Callback for every received update, non-zero return value stops the stream
+pub type YgcUpdateCallback =
+    extern "C" fn(user_data: *mut c_void, data: *const u8, len: usize) -> c_int;
+
+#[derive(Debug)]
+pub struct YgcRuntime {
+    runtime: Runtime,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(error: impl ToString) {
+    let error = CString::new(error.to_string().replace('\0', " ")).expect("no nul bytes");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(error));
+}
+
+/// # Safety
+///
+/// `value` should be null or valid nul-terminated string
+unsafe fn read_str<'a>(value: *const c_char, name: &str) -> Result<Option<&'a str>, c_int> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(value).to_str().map(Some).map_err(|_error| {
+        set_last_error(format!("{name} is not valid UTF-8"));
+        YGC_ERR_ARGS
+    })
+}
+
+/// Create tokio runtime used for all calls, returns null on error
+#[no_mangle]
+pub extern "C" fn ygc_runtime_new() -> *mut YgcRuntime {
+    match Builder::new_multi_thread()
+        .thread_name("ygcFfi")
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => Box::into_raw(Box::new(YgcRuntime { runtime })),
+        Err(error) => {
+            set_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+///
+/// `runtime` should be created by `ygc_runtime_new` and not used after this call
+#[no_mangle]
+pub unsafe extern "C" fn ygc_runtime_free(runtime: *mut YgcRuntime) {
+    if !runtime.is_null() {
+        drop(Box::from_raw(runtime));
+    }
+}
+
+/// Last error on the current thread, valid until next call on the same thread
+#[no_mangle]
+pub extern "C" fn ygc_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |error| error.as_ptr())
+    })
+}
+
+/// Connect to `endpoint`, subscribe with `request_json` (`SubscribeRequest` in
+/// protobuf JSON format) and block until the stream is finished or `callback`
+/// returns non-zero value.
+///
+/// # Safety
+///
+/// `runtime` should be valid pointer from `ygc_runtime_new`, `endpoint` and
+/// `request_json` valid nul-terminated strings, `x_token` null or valid
+/// nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ygc_subscribe(
+    runtime: *const YgcRuntime,
+    endpoint: *const c_char,
+    x_token: *const c_char,
+    request_json: *const c_char,
+    callback: YgcUpdateCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let Some(runtime) = runtime.as_ref() else {
+        set_last_error("runtime is null");
+        return YGC_ERR_ARGS;
+    };
+    let (endpoint, x_token, request_json) = match (
+        read_str(endpoint, "endpoint"),
+        read_str(x_token, "x_token"),
+        read_str(request_json, "request_json"),
+    ) {
+        (Ok(Some(endpoint)), Ok(x_token), Ok(Some(request_json))) => {
+            (endpoint, x_token, request_json)
+        }
+        (Err(code), _, _) | (_, Err(code), _) | (_, _, Err(code)) => return code,
+        _ => {
+            set_last_error("endpoint and request_json are required");
+            return YGC_ERR_ARGS;
+        }
+    };
+    let request: SubscribeRequest = match serde_json::from_str(request_json) {
+        Ok(request) => request,
+        Err(error) => {
+            set_last_error(format!("failed to parse request_json: {error}"));
+            return YGC_ERR_ARGS;
+        }
+    };
+
+    runtime.runtime.block_on(async move {
+        let client = GeyserGrpcClient::build_from_shared(endpoint.to_owned())
+            .and_then(|builder| builder.x_token(x_token))
+            .and_then(|builder| builder.tls_config(ClientTlsConfig::new().with_native_roots()));
+        let mut client = match client {
+            Ok(builder) => match builder.connect().await {
+                Ok(client) => client,
+                Err(error) => {
+                    set_last_error(error);
+                    return YGC_ERR_CONNECT;
+                }
+            },
+            Err(error) => {
+                set_last_error(error);
+                return YGC_ERR_ARGS;
+            }
+        };
+
+        let mut stream = match client.subscribe_once(request).await {
+            Ok(stream) => stream,
+            Err(error) => {
+                set_last_error(error);
+                return YGC_ERR_CONNECT;
+            }
+        };
+
+        let mut buf = Vec::new();
+        while let Some(message) = stream.next().await {
+            match message {
+                Ok(update) => {
+                    buf.clear();
+                    update.encode(&mut buf).expect("vec has enough capacity");
+                    if callback(user_data, buf.as_ptr(), buf.len()) != 0 {
+                        break;
+                    }
+                }
+                Err(status) => {
+                    set_last_error(status);
+                    return YGC_ERR_STREAM;
+                }
+            }
+        }
+        YGC_OK
+    })
+}
@@ -0,0 +1,23 @@
+import { EventEmitter } from "node:events";
+
+export interface SubscribeOptions {
+  /** Encoded `geyser.SubscribeUpdate` instead of JSON */
+  raw?: boolean;
+  /** Number of buffered updates */
+  buffer?: number;
+}
+
+export declare class Subscription extends EventEmitter {
+  start(): this;
+  close(): Promise<void>;
+  [Symbol.asyncIterator](): AsyncIterator<any>;
+  on(event: "update", listener: (update: any) => void): this;
+  on(event: "reconnect", listener: (error: Error) => void): this;
+  on(event: "error", listener: (error: Error) => void): this;
+  on(event: "close", listener: () => void): this;
+}
+
+export declare class Client {
+  constructor(endpoint: string, xToken?: string);
+  subscribe(request: object, options?: SubscribeOptions): Subscription;
+}
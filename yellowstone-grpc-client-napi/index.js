@@ -0,0 +1,54 @@
+const { EventEmitter } = require("node:events");
+const binding = require("./binding.js");
+
+class Subscription extends EventEmitter {
+  constructor(inner, raw) {
+    super();
+    this.inner = inner;
+    this.raw = raw;
+    this.closed = false;
+  }
+
+  // Emit `update` and `error` events until `close` is called
+  start() {
+    (async () => {
+      for await (const update of this) {
+        this.emit("update", update);
+      }
+      this.emit("close");
+    })().catch((error) => this.emit("error", error));
+    return this;
+  }
+
+  async *[Symbol.asyncIterator]() {
+    while (!this.closed) {
+      const message = await this.inner.next(this.raw);
+      if (message === null) {
+        return;
+      }
+      if (message.error !== undefined && message.error !== null) {
+        this.emit("reconnect", new Error(message.error));
+        continue;
+      }
+      yield this.raw ? message.raw : message.json;
+    }
+  }
+
+  async close() {
+    this.closed = true;
+    await this.inner.close();
+  }
+}
+
+class Client {
+  constructor(endpoint, xToken) {
+    this.inner = new binding.Client(endpoint, xToken);
+  }
+
+  subscribe(request, options = {}) {
+    const inner = this.inner.subscribe(request, options.buffer);
+    return new Subscription(inner, options.raw === true);
+  }
+}
+
+module.exports = { Client, Subscription };
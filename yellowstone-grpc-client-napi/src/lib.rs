@@ -0,0 +1,128 @@
+use {
+    futures::stream::{self, StreamExt},
+    napi::{bindgen_prelude::Buffer, Error, Result},
+    napi_derive::napi,
+    prost::Message,
+    serde_json::Value,
+    std::{pin::pin, sync::Arc},
+    tokio::{
+        sync::{mpsc, Mutex},
+        task::JoinHandle,
+    },
+    tonic::transport::ClientTlsConfig,
+    yellowstone_grpc_client::{
+        managed::{ConnectionEvent, ManagedConfig},
+        GeyserGrpcBuilder, GeyserGrpcClient,
+    },
+    yellowstone_grpc_proto::prelude::{SubscribeRequest, SubscribeUpdate},
+};
+
+type UpdatesRx = Arc<Mutex<mpsc::Receiver<std::result::Result<SubscribeUpdate, String>>>>;
+
+/// Updates of the managed subscription, errors of reconnects are sent as
+/// errors, subscription is finished after not retryable error
+async fn subscribe_loop(
+    builder: GeyserGrpcBuilder,
+    request: SubscribeRequest,
+    tx: mpsc::Sender<std::result::Result<SubscribeUpdate, String>>,
+) {
+    let (updates, events) = builder.subscribe_managed(request, ManagedConfig::default());
+    let updates = updates.map(|update| update.map_err(|error| error.to_string()));
+    let errors = events.filter_map(|event| async move {
+        match event {
+            ConnectionEvent::ReconnectScheduled { error, .. } => Some(Err(error)),
+            _ => None,
+        }
+    });
+    let mut messages = pin!(stream::select(updates, errors));
+    while let Some(message) = messages.next().await {
+        if tx.send(message).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[napi]
+pub struct Client {
+    endpoint: String,
+    x_token: Option<String>,
+}
+
+#[napi]
+impl Client {
+    #[napi(constructor)]
+    pub fn new(endpoint: String, x_token: Option<String>) -> Self {
+        Self { endpoint, x_token }
+    }
+
+    /// `request` is `SubscribeRequest` in protobuf JSON format, stream is reconnected
+    /// automatically on errors
+    #[napi]
+    pub fn subscribe(&self, request: Value, buffer: Option<u32>) -> Result<Subscription> {
+        let request: SubscribeRequest = serde_json::from_value(request)
+            .map_err(|error| Error::from_reason(format!("invalid request: {error}")))?;
+        let builder = GeyserGrpcClient::build_from_shared(self.endpoint.clone())
+            .and_then(|builder| builder.x_token(self.x_token.clone()))
+            .and_then(|builder| builder.tls_config(ClientTlsConfig::new().with_native_roots()))
+            .map_err(|error| Error::from_reason(error.to_string()))?;
+        let (tx, rx) = mpsc::channel(buffer.unwrap_or(1024).max(1) as usize);
+        let task = napi::bindgen_prelude::spawn(subscribe_loop(builder, request, tx));
+        Ok(Subscription {
+            rx: Arc::new(Mutex::new(rx)),
+            task,
+        })
+    }
+}
+
+#[napi(object)]
+pub struct Update {
+    /// Set when stream failed, stream is reconnected after the error unless
+    /// it's the last update of the subscription
+    pub error: Option<String>,
+    /// Encoded `geyser.SubscribeUpdate`
+    pub raw: Option<Buffer>,
+    /// `geyser.SubscribeUpdate` in protobuf JSON format
+    pub json: Option<Value>,
+}
+
+#[napi]
+pub struct Subscription {
+    rx: UpdatesRx,
+    task: JoinHandle<()>,
+}
+
+#[napi]
+impl Subscription {
+    /// Next update or `null` if subscription is closed
+    #[napi]
+    pub async fn next(&self, raw: Option<bool>) -> Result<Option<Update>> {
+        let message = self.rx.lock().await.recv().await;
+        Ok(match message {
+            Some(Ok(update)) if raw.unwrap_or(false) => Some(Update {
+                error: None,
+                raw: Some(update.encode_to_vec().into()),
+                json: None,
+            }),
+            Some(Ok(update)) => Some(Update {
+                error: None,
+                raw: None,
+                json: Some(
+                    serde_json::to_value(&update)
+                        .map_err(|error| Error::from_reason(error.to_string()))?,
+                ),
+            }),
+            Some(Err(error)) => Some(Update {
+                error: Some(error),
+                raw: None,
+                json: None,
+            }),
+            None => None,
+        })
+    }
+
+    /// Pending `next` returns `null` once buffered updates are received
+    #[napi]
+    pub fn close(&self) {
+        self.task.abort();
+    }
+}
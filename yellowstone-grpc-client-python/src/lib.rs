@@ -0,0 +1,257 @@
+use {
+    futures::stream::{self, StreamExt},
+    prost::Message,
+    pyo3::{
+        exceptions::{PyRuntimeError, PyStopAsyncIteration, PyValueError},
+        prelude::*,
+        types::{PyBytes, PyDict, PyList},
+    },
+    serde_json::Value,
+    std::{pin::pin, sync::Arc},
+    tokio::{
+        sync::{mpsc, Mutex},
+        task::JoinHandle,
+    },
+    tonic::transport::ClientTlsConfig,
+    yellowstone_grpc_client::{
+        managed::{ConnectionEvent, ManagedConfig},
+        GeyserGrpcBuilder, GeyserGrpcClient,
+    },
+    yellowstone_grpc_proto::prelude::{
+        SubscribeRequest, SubscribeRequestFilterAccounts, SubscribeRequestFilterTransactions,
+        SubscribeUpdate,
+    },
+};
+
+type UpdatesRx = Arc<Mutex<mpsc::Receiver<Result<SubscribeUpdate, String>>>>;
+
+fn json_to_py(py: Python<'_>, value: Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(value) => value.into_pyobject(py)?.to_owned().into_any().unbind(),
+        Value::Number(value) => {
+            if let Some(value) = value.as_i64() {
+                value.into_pyobject(py)?.into_any().unbind()
+            } else if let Some(value) = value.as_u64() {
+                value.into_pyobject(py)?.into_any().unbind()
+            } else {
+                value.as_f64().into_pyobject(py)?.into_any().unbind()
+            }
+        }
+        Value::String(value) => value.into_pyobject(py)?.into_any().unbind(),
+        Value::Array(values) => {
+            let list = PyList::empty(py);
+            for value in values {
+                list.append(json_to_py(py, value)?)?;
+            }
+            list.into_any().unbind()
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, json_to_py(py, value)?)?;
+            }
+            dict.into_any().unbind()
+        }
+    })
+}
+
+fn py_to_json<T: serde::de::DeserializeOwned>(value: &Bound<'_, PyAny>) -> PyResult<T> {
+    let json: String = if let Ok(json) = value.extract::<String>() {
+        json
+    } else {
+        value
+            .py()
+            .import("json")?
+            .call_method1("dumps", (value,))?
+            .extract()?
+    };
+    serde_json::from_str(&json).map_err(|error| PyValueError::new_err(error.to_string()))
+}
+
+fn to_py<T: serde::Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    let value =
+        serde_json::to_value(value).map_err(|error| PyValueError::new_err(error.to_string()))?;
+    json_to_py(py, value)
+}
+
+fn update_to_py(py: Python<'_>, update: SubscribeUpdate, raw: bool) -> PyResult<PyObject> {
+    if raw {
+        Ok(PyBytes::new(py, &update.encode_to_vec())
+            .into_any()
+            .unbind())
+    } else {
+        to_py(py, &update)
+    }
+}
+
+/// Updates of the managed subscription, errors of reconnects are sent as
+/// errors, subscription is finished after not retryable error
+async fn subscribe_loop(
+    builder: GeyserGrpcBuilder,
+    request: SubscribeRequest,
+    tx: mpsc::Sender<Result<SubscribeUpdate, String>>,
+) {
+    let (updates, events) = builder.subscribe_managed(request, ManagedConfig::default());
+    let updates = updates.map(|update| update.map_err(|error| error.to_string()));
+    let errors = events.filter_map(|event| async move {
+        match event {
+            ConnectionEvent::ReconnectScheduled { error, .. } => Some(Err(error)),
+            _ => None,
+        }
+    });
+    let mut messages = pin!(stream::select(updates, errors));
+    while let Some(message) = messages.next().await {
+        if tx.send(message).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Client with automatic reconnect, `subscribe` returns iterator which can be used
+/// with `for` and `async for`.
+#[pyclass]
+struct Client {
+    endpoint: String,
+    x_token: Option<String>,
+}
+
+#[pymethods]
+impl Client {
+    #[new]
+    #[pyo3(signature = (endpoint, x_token=None))]
+    fn new(endpoint: String, x_token: Option<String>) -> Self {
+        Self { endpoint, x_token }
+    }
+
+    /// `request` is `SubscribeRequest` as dict or JSON string, updates are dicts
+    /// or encoded protobuf bytes with `raw=True`
+    #[pyo3(signature = (request, raw=false, errors=false, buffer=1024))]
+    fn subscribe(
+        &self,
+        request: &Bound<'_, PyAny>,
+        raw: bool,
+        errors: bool,
+        buffer: usize,
+    ) -> PyResult<Subscription> {
+        let request: SubscribeRequest = py_to_json(request)?;
+        let builder = GeyserGrpcClient::build_from_shared(self.endpoint.clone())
+            .and_then(|builder| builder.x_token(self.x_token.clone()))
+            .and_then(|builder| builder.tls_config(ClientTlsConfig::new().with_native_roots()))
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        let (tx, rx) = mpsc::channel(buffer.max(1));
+        let task =
+            pyo3_async_runtimes::tokio::get_runtime().spawn(subscribe_loop(builder, request, tx));
+        Ok(Subscription {
+            rx: Arc::new(Mutex::new(rx)),
+            task,
+            raw,
+            errors,
+        })
+    }
+}
+
+/// Stream of updates, errors are skipped (stream is reconnected) unless `errors=True`
+#[pyclass]
+struct Subscription {
+    rx: UpdatesRx,
+    task: JoinHandle<()>,
+    raw: bool,
+    errors: bool,
+}
+
+impl Subscription {
+    async fn recv(rx: UpdatesRx, errors: bool) -> PyResult<Option<SubscribeUpdate>> {
+        let mut rx = rx.lock().await;
+        loop {
+            match rx.recv().await {
+                Some(Ok(update)) => return Ok(Some(update)),
+                Some(Err(error)) if errors => return Err(PyRuntimeError::new_err(error)),
+                Some(Err(_error)) => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl Subscription {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        let rx = Arc::clone(&self.rx);
+        let errors = self.errors;
+        let update = py.allow_threads(|| {
+            pyo3_async_runtimes::tokio::get_runtime().block_on(Self::recv(rx, errors))
+        })?;
+        update
+            .map(|update| update_to_py(py, update, self.raw))
+            .transpose()
+    }
+
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let rx = Arc::clone(&self.rx);
+        let (raw, errors) = (self.raw, self.errors);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            match Self::recv(rx, errors).await? {
+                Some(update) => Python::with_gil(|py| update_to_py(py, update, raw)),
+                None => Err(PyStopAsyncIteration::new_err(())),
+            }
+        })
+    }
+
+    /// Stop receiving updates and close the stream, pending iteration is
+    /// finished once buffered updates are received
+    fn close(&self) {
+        self.task.abort();
+    }
+}
+
+/// Accounts filter as dict, for `SubscribeRequest.accounts`
+#[pyfunction]
+#[pyo3(signature = (account=vec![], owner=vec![]))]
+fn filter_accounts(py: Python<'_>, account: Vec<String>, owner: Vec<String>) -> PyResult<PyObject> {
+    let filter = SubscribeRequestFilterAccounts {
+        account,
+        owner,
+        ..Default::default()
+    };
+    to_py(py, &filter)
+}
+
+/// Transactions filter as dict, for `SubscribeRequest.transactions`
+#[pyfunction]
+#[pyo3(signature = (account_include=vec![], account_exclude=vec![], account_required=vec![], vote=None, failed=None))]
+fn filter_transactions(
+    py: Python<'_>,
+    account_include: Vec<String>,
+    account_exclude: Vec<String>,
+    account_required: Vec<String>,
+    vote: Option<bool>,
+    failed: Option<bool>,
+) -> PyResult<PyObject> {
+    let filter = SubscribeRequestFilterTransactions::for_account(account_include)
+        .with_account_exclude(account_exclude)
+        .with_account_required(account_required);
+    let filter = SubscribeRequestFilterTransactions {
+        vote,
+        failed,
+        ..filter
+    };
+    to_py(py, &filter)
+}
+
+#[pymodule]
+fn yellowstone_grpc(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Client>()?;
+    m.add_class::<Subscription>()?;
+    m.add_function(wrap_pyfunction!(filter_accounts, m)?)?;
+    m.add_function(wrap_pyfunction!(filter_transactions, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,377 @@
+//! Cache of address lookup tables built from accounts updates.
+//!
+//! Tables are updated from `AddressLookupTab1e1111111111111111111111111` owned
+//! accounts and used to resolve `address_table_lookups` of v0 transactions into
+//! full list of account keys: static keys, then writable and readonly loaded
+//! addresses in the order of lookups.
+
+use {
+    futures::stream::{Stream, StreamExt},
+    std::collections::HashMap,
+    tonic::Status,
+    yellowstone_grpc_proto::{
+        base58,
+        prelude::{
+            subscribe_update::UpdateOneof, Message, SubscribeRequestFilterAccounts,
+            SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateTransactionInfo,
+        },
+    },
+};
+
+pub const ADDRESS_LOOKUP_TABLE_PROGRAM_ID: &str = "AddressLookupTab1e1111111111111111111111111";
+
+/// Size of `LookupTableMeta` with `ProgramState` tag and padding
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+/// `ProgramState::LookupTable`
+const LOOKUP_TABLE_STATE: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AltError {
+    #[error("invalid lookup table data")]
+    InvalidData,
+    #[error("lookup table not found: {0}")]
+    TableNotFound(String),
+    #[error("invalid index {index} for lookup table {table}")]
+    InvalidIndex { table: String, index: u8 },
+    #[error("transaction message is missed")]
+    MessageMissed,
+}
+
+pub type AltResult<T> = Result<T, AltError>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressLookupTable {
+    pub deactivation_slot: u64,
+    pub last_extended_slot: u64,
+    pub addresses: Vec<Vec<u8>>,
+    /// Slot of the account update
+    pub slot: u64,
+}
+
+impl AddressLookupTable {
+    pub fn parse(data: &[u8], slot: u64) -> AltResult<Self> {
+        let meta = data
+            .get(..LOOKUP_TABLE_META_SIZE)
+            .ok_or(AltError::InvalidData)?;
+        if u32::from_le_bytes(meta[0..4].try_into().expect("valid size")) != LOOKUP_TABLE_STATE {
+            return Err(AltError::InvalidData);
+        }
+        let addresses = &data[LOOKUP_TABLE_META_SIZE..];
+        if addresses.len() % 32 != 0 {
+            return Err(AltError::InvalidData);
+        }
+        Ok(Self {
+            deactivation_slot: u64::from_le_bytes(meta[4..12].try_into().expect("valid size")),
+            last_extended_slot: u64::from_le_bytes(meta[12..20].try_into().expect("valid size")),
+            addresses: addresses.chunks(32).map(|chunk| chunk.to_vec()).collect(),
+            slot,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedTransaction {
+    pub slot: u64,
+    pub transaction: SubscribeUpdateTransactionInfo,
+    /// Static keys followed by loaded writable and readonly addresses
+    pub account_keys: AltResult<Vec<Vec<u8>>>,
+}
+
+/// Lookup tables by pubkey
+#[derive(Debug, Default, Clone)]
+pub struct AddressLookupTableCache {
+    tables: HashMap<Vec<u8>, AddressLookupTable>,
+}
+
+impl AddressLookupTableCache {
+    /// Accounts filter for all lookup tables
+    pub fn filter() -> SubscribeRequestFilterAccounts {
+        SubscribeRequestFilterAccounts::with_owner([ADDRESS_LOOKUP_TABLE_PROGRAM_ID])
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    pub fn get(&self, pubkey: &[u8]) -> Option<&AddressLookupTable> {
+        self.tables.get(pubkey)
+    }
+
+    pub fn insert(&mut self, pubkey: Vec<u8>, table: AddressLookupTable) {
+        self.tables.insert(pubkey, table);
+    }
+
+    /// Apply account update, closed and non-table accounts are removed
+    pub fn update_account(&mut self, msg: &SubscribeUpdateAccount) -> AltResult<()> {
+        let Some(account) = &msg.account else {
+            return Ok(());
+        };
+        if let Some(table) = self.tables.get(&account.pubkey) {
+            if table.slot > msg.slot {
+                return Ok(());
+            }
+        }
+
+        if account.lamports == 0 || account.data.is_empty() {
+            self.tables.remove(&account.pubkey);
+            return Ok(());
+        }
+        match AddressLookupTable::parse(&account.data, msg.slot) {
+            Ok(table) => {
+                self.tables.insert(account.pubkey.clone(), table);
+                Ok(())
+            }
+            Err(error) => {
+                self.tables.remove(&account.pubkey);
+                Err(error)
+            }
+        }
+    }
+
+    /// Resolve account keys of the message, legacy messages return static keys
+    pub fn resolve_message(&self, message: &Message) -> AltResult<Vec<Vec<u8>>> {
+        let mut writable = vec![];
+        let mut readonly = vec![];
+        for lookup in message.address_table_lookups.iter() {
+            let table = self
+                .tables
+                .get(&lookup.account_key)
+                .ok_or_else(|| AltError::TableNotFound(encode(&lookup.account_key)))?;
+            for (indexes, keys) in [
+                (&lookup.writable_indexes, &mut writable),
+                (&lookup.readonly_indexes, &mut readonly),
+            ] {
+                for &index in indexes.iter() {
+                    let address = table.addresses.get(index as usize).ok_or_else(|| {
+                        AltError::InvalidIndex {
+                            table: encode(&lookup.account_key),
+                            index,
+                        }
+                    })?;
+                    keys.push(address.clone());
+                }
+            }
+        }
+
+        Ok(message
+            .account_keys
+            .iter()
+            .cloned()
+            .chain(writable)
+            .chain(readonly)
+            .collect())
+    }
+
+    pub fn resolve_transaction(
+        &self,
+        tx: &SubscribeUpdateTransactionInfo,
+    ) -> AltResult<Vec<Vec<u8>>> {
+        let message = tx
+            .transaction
+            .as_ref()
+            .and_then(|tx| tx.message.as_ref())
+            .ok_or(AltError::MessageMissed)?;
+        self.resolve_message(message)
+    }
+
+    /// Convert stream of updates to stream of resolved transactions, lookup
+    /// tables are updated from accounts updates in the same stream
+    pub fn into_stream<S>(
+        mut self,
+        stream: S,
+    ) -> impl Stream<Item = Result<ResolvedTransaction, Status>>
+    where
+        S: Stream<Item = Result<SubscribeUpdate, Status>>,
+    {
+        stream.filter_map(move |message| {
+            let item = match message {
+                Ok(SubscribeUpdate {
+                    update_oneof: Some(UpdateOneof::Account(msg)),
+                    ..
+                }) => {
+                    // invalid tables are removed from the cache
+                    let _ = self.update_account(&msg);
+                    None
+                }
+                Ok(SubscribeUpdate {
+                    update_oneof: Some(UpdateOneof::Transaction(msg)),
+                    ..
+                }) => msg.transaction.map(|transaction| {
+                    Ok(ResolvedTransaction {
+                        slot: msg.slot,
+                        account_keys: self.resolve_transaction(&transaction),
+                        transaction,
+                    })
+                }),
+                Ok(_) => None,
+                Err(status) => Some(Err(status)),
+            };
+            futures::future::ready(item)
+        })
+    }
+}
+
+fn encode(pubkey: &[u8]) -> String {
+    base58::encode(pubkey)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            AddressLookupTable, AddressLookupTableCache, AltError, LOOKUP_TABLE_META_SIZE,
+            LOOKUP_TABLE_STATE,
+        },
+        futures::{stream, StreamExt},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, Message, MessageAddressTableLookup, SubscribeUpdate,
+            SubscribeUpdateAccount, SubscribeUpdateAccountInfo, SubscribeUpdateTransaction,
+            SubscribeUpdateTransactionInfo, Transaction,
+        },
+    };
+
+    fn table_data(addresses: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; LOOKUP_TABLE_META_SIZE];
+        data[0..4].copy_from_slice(&LOOKUP_TABLE_STATE.to_le_bytes());
+        data[4..12].copy_from_slice(&u64::MAX.to_le_bytes());
+        for address in addresses {
+            data.extend_from_slice(&[*address; 32]);
+        }
+        data
+    }
+
+    fn account_update(pubkey: u8, slot: u64, data: Vec<u8>) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: vec![pubkey; 32],
+                    lamports: 1,
+                    data,
+                    ..Default::default()
+                }),
+                slot,
+                is_startup: false,
+                previous_owner: None,
+                data_omitted: false,
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn message(lookups: Vec<MessageAddressTableLookup>) -> Message {
+        Message {
+            account_keys: vec![vec![1; 32]],
+            versioned: true,
+            address_table_lookups: lookups,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let table = AddressLookupTable::parse(&table_data(&[7, 8]), 5).unwrap();
+        assert_eq!(table.deactivation_slot, u64::MAX);
+        assert_eq!(table.addresses, vec![vec![7; 32], vec![8; 32]]);
+
+        let mut data = table_data(&[7]);
+        data.push(0);
+        assert_eq!(
+            AddressLookupTable::parse(&data, 5),
+            Err(AltError::InvalidData)
+        );
+        assert_eq!(
+            AddressLookupTable::parse(&[0; LOOKUP_TABLE_META_SIZE], 5),
+            Err(AltError::InvalidData)
+        );
+    }
+
+    #[test]
+    fn test_resolve_message() {
+        let mut cache = AddressLookupTableCache::default();
+        cache.insert(
+            vec![2; 32],
+            AddressLookupTable::parse(&table_data(&[10, 11, 12]), 1).unwrap(),
+        );
+        cache.insert(
+            vec![3; 32],
+            AddressLookupTable::parse(&table_data(&[20, 21]), 1).unwrap(),
+        );
+
+        let keys = cache
+            .resolve_message(&message(vec![
+                MessageAddressTableLookup {
+                    account_key: vec![2; 32],
+                    writable_indexes: vec![2],
+                    readonly_indexes: vec![0],
+                },
+                MessageAddressTableLookup {
+                    account_key: vec![3; 32],
+                    writable_indexes: vec![1],
+                    readonly_indexes: vec![],
+                },
+            ]))
+            .unwrap();
+        assert_eq!(
+            keys,
+            vec![vec![1; 32], vec![12; 32], vec![21; 32], vec![10; 32]]
+        );
+
+        assert!(matches!(
+            cache.resolve_message(&message(vec![MessageAddressTableLookup {
+                account_key: vec![3; 32],
+                writable_indexes: vec![5],
+                readonly_indexes: vec![],
+            }])),
+            Err(AltError::InvalidIndex { index: 5, .. })
+        ));
+        assert!(matches!(
+            cache.resolve_message(&message(vec![MessageAddressTableLookup {
+                account_key: vec![4; 32],
+                writable_indexes: vec![],
+                readonly_indexes: vec![0],
+            }])),
+            Err(AltError::TableNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let transaction = SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                transaction: Some(SubscribeUpdateTransactionInfo {
+                    transaction: Some(Transaction {
+                        signatures: vec![vec![0; 64]],
+                        message: Some(message(vec![MessageAddressTableLookup {
+                            account_key: vec![2; 32],
+                            writable_indexes: vec![],
+                            readonly_indexes: vec![1],
+                        }])),
+                    }),
+                    ..Default::default()
+                }),
+                slot: 11,
+                invocations: vec![],
+            })),
+            ..Default::default()
+        };
+        let updates = vec![
+            Ok(account_update(2, 10, table_data(&[10, 11]))),
+            // outdated update is ignored
+            Ok(account_update(2, 9, vec![])),
+            Ok(transaction),
+        ];
+
+        let items = AddressLookupTableCache::default()
+            .into_stream(stream::iter(updates))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(items.len(), 1);
+        let resolved = items[0].as_ref().unwrap();
+        assert_eq!(resolved.slot, 11);
+        assert_eq!(resolved.account_keys, Ok(vec![vec![1; 32], vec![11; 32]]));
+    }
+}
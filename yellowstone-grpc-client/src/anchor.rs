@@ -0,0 +1,663 @@
+//! Decoding of Anchor events and instructions from transactions in the stream.
+//!
+//! Events are decoded from `Program data: ` log messages and from self-CPI
+//! instructions (`emit_cpi!`), instructions are decoded from outer and inner
+//! instructions. Decoded values are returned as JSON, 64-bit and bigger integers
+//! are encoded as strings, pubkeys as base58 and `bytes` as base64.
+
+use {
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    serde_json::{json, Map, Value},
+    sha2::{Digest, Sha256},
+    std::collections::HashMap,
+    yellowstone_grpc_proto::{base58, prelude::SubscribeUpdateTransactionInfo},
+};
+
+/// Tag of self-CPI instruction used by `emit_cpi!`
+pub const EVENT_IX_TAG_LE: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+
+const LOG_PROGRAM_DATA: &str = "Program data: ";
+
+#[derive(Debug, thiserror::Error)]
+pub enum AnchorError {
+    #[error("invalid IDL: {0}")]
+    InvalidIdl(String),
+    #[error("unexpected end of data")]
+    UnexpectedEof,
+    #[error("unknown type: {0}")]
+    UnknownType(String),
+    #[error("invalid UTF-8 string")]
+    InvalidUtf8,
+    #[error("invalid variant {1} for enum {0}")]
+    InvalidVariant(String, u32),
+    #[error("invalid option tag: {0}")]
+    InvalidOption(u8),
+}
+
+pub type AnchorResult<T> = Result<T, AnchorError>;
+
+#[derive(Debug, Clone)]
+struct IdlInstruction {
+    name: String,
+    discriminator: [u8; 8],
+    args: Vec<(String, Value)>,
+}
+
+#[derive(Debug, Clone)]
+struct IdlEvent {
+    name: String,
+    discriminator: [u8; 8],
+}
+
+/// Parsed Anchor IDL, format of Anchor >= 0.30 and legacy IDL are supported
+#[derive(Debug, Clone)]
+pub struct AnchorIdl {
+    address: Option<String>,
+    instructions: Vec<IdlInstruction>,
+    events: Vec<IdlEvent>,
+    types: HashMap<String, Value>,
+}
+
+impl AnchorIdl {
+    pub fn parse(json: &str) -> AnchorResult<Self> {
+        let idl: Value = serde_json::from_str(json)
+            .map_err(|error| AnchorError::InvalidIdl(error.to_string()))?;
+
+        let types = idl["types"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .chain(idl["accounts"].as_array().into_iter().flatten())
+            .chain(idl["events"].as_array().into_iter().flatten())
+            .filter_map(|ty| {
+                let name = ty["name"].as_str()?;
+                let value = match (ty.get("type"), ty.get("fields")) {
+                    (Some(value), _) => value.clone(),
+                    // legacy IDL events
+                    (None, Some(fields)) => json!({ "kind": "struct", "fields": fields }),
+                    (None, None) => return None,
+                };
+                Some((name.to_owned(), value))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let instructions = idl["instructions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|ix| {
+                let name = Self::parse_name(ix)?;
+                let discriminator = match Self::parse_discriminator(ix)? {
+                    Some(discriminator) => discriminator,
+                    None => Self::sighash("global", &to_snake_case(&name)),
+                };
+                let args = ix["args"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|arg| Ok((Self::parse_name(arg)?, arg["type"].clone())))
+                    .collect::<AnchorResult<_>>()?;
+                Ok(IdlInstruction {
+                    name,
+                    discriminator,
+                    args,
+                })
+            })
+            .collect::<AnchorResult<_>>()?;
+
+        let events = idl["events"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|event| {
+                let name = Self::parse_name(event)?;
+                let discriminator = match Self::parse_discriminator(event)? {
+                    Some(discriminator) => discriminator,
+                    None => Self::sighash("event", &name),
+                };
+                Ok(IdlEvent {
+                    name,
+                    discriminator,
+                })
+            })
+            .collect::<AnchorResult<_>>()?;
+
+        Ok(Self {
+            address: idl["address"].as_str().map(ToOwned::to_owned),
+            instructions,
+            events,
+            types,
+        })
+    }
+
+    fn parse_name(value: &Value) -> AnchorResult<String> {
+        value["name"]
+            .as_str()
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| AnchorError::InvalidIdl("name is missed".to_owned()))
+    }
+
+    fn parse_discriminator(value: &Value) -> AnchorResult<Option<[u8; 8]>> {
+        let Some(discriminator) = value.get("discriminator") else {
+            return Ok(None);
+        };
+        serde_json::from_value(discriminator.clone())
+            .map(Some)
+            .map_err(|error| AnchorError::InvalidIdl(format!("invalid discriminator: {error}")))
+    }
+
+    fn sighash(namespace: &str, name: &str) -> [u8; 8] {
+        let hash = Sha256::digest(format!("{namespace}:{name}"));
+        hash[..8].try_into().expect("valid length")
+    }
+
+    /// Program address from IDL (Anchor >= 0.30)
+    pub fn address(&self) -> Option<&str> {
+        self.address.as_deref()
+    }
+
+    pub fn decode_event(&self, data: &[u8]) -> AnchorResult<Option<DecodedEvent>> {
+        let Some(event) = self
+            .events
+            .iter()
+            .find(|event| data.starts_with(&event.discriminator))
+        else {
+            return Ok(None);
+        };
+        let ty = self
+            .types
+            .get(&event.name)
+            .ok_or_else(|| AnchorError::UnknownType(event.name.clone()))?;
+        let mut reader = BorshReader::new(&data[8..]);
+        let value = reader.read_defined(self, &event.name, ty)?;
+        Ok(Some(DecodedEvent {
+            name: event.name.clone(),
+            data: value,
+        }))
+    }
+
+    pub fn decode_instruction(&self, data: &[u8]) -> AnchorResult<Option<DecodedInstruction>> {
+        let Some(ix) = self
+            .instructions
+            .iter()
+            .find(|ix| data.starts_with(&ix.discriminator))
+        else {
+            return Ok(None);
+        };
+        let mut reader = BorshReader::new(&data[8..]);
+        let mut args = Map::new();
+        for (name, ty) in ix.args.iter() {
+            args.insert(name.clone(), reader.read(self, ty)?);
+        }
+        Ok(Some(DecodedInstruction {
+            name: ix.name.clone(),
+            args: Value::Object(args),
+        }))
+    }
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len() + 4);
+    for (index, ch) in name.chars().enumerate() {
+        if ch.is_uppercase() {
+            if index > 0 {
+                result.push('_');
+            }
+            result.extend(ch.to_lowercase());
+        } else {
+            result.push(ch);
+        }
+    }
+    result
+}
+
+struct BorshReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BorshReader<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn take(&mut self, len: usize) -> AnchorResult<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(AnchorError::UnexpectedEof);
+        }
+        let (value, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(value)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> AnchorResult<[u8; N]> {
+        self.take(N)
+            .map(|value| value.try_into().expect("valid length"))
+    }
+
+    fn read_len(&mut self) -> AnchorResult<usize> {
+        Ok(u32::from_le_bytes(self.take_array()?) as usize)
+    }
+
+    fn read(&mut self, idl: &AnchorIdl, ty: &Value) -> AnchorResult<Value> {
+        if let Some(name) = ty.as_str() {
+            return self.read_primitive(name);
+        }
+
+        if let Some(inner) = ty.get("vec") {
+            let len = self.read_len()?;
+            return (0..len)
+                .map(|_| self.read(idl, inner))
+                .collect::<AnchorResult<Vec<_>>>()
+                .map(Value::Array);
+        }
+        if let Some(inner) = ty.get("option").or_else(|| ty.get("coption")) {
+            let tag = if ty.get("coption").is_some() {
+                u32::from_le_bytes(self.take_array()?) as u8
+            } else {
+                self.take_array::<1>()?[0]
+            };
+            return match tag {
+                0 => Ok(Value::Null),
+                1 => self.read(idl, inner),
+                tag => Err(AnchorError::InvalidOption(tag)),
+            };
+        }
+        if let Some(array) = ty.get("array").and_then(Value::as_array) {
+            let (Some(inner), Some(len)) = (array.first(), array.get(1).and_then(Value::as_u64))
+            else {
+                return Err(AnchorError::UnknownType(ty.to_string()));
+            };
+            return (0..len)
+                .map(|_| self.read(idl, inner))
+                .collect::<AnchorResult<Vec<_>>>()
+                .map(Value::Array);
+        }
+        if let Some(defined) = ty.get("defined") {
+            let name = defined
+                .as_str()
+                .or_else(|| defined["name"].as_str())
+                .ok_or_else(|| AnchorError::UnknownType(ty.to_string()))?;
+            let defined_ty = idl
+                .types
+                .get(name)
+                .ok_or_else(|| AnchorError::UnknownType(name.to_owned()))?;
+            return self.read_defined(idl, name, defined_ty);
+        }
+
+        Err(AnchorError::UnknownType(ty.to_string()))
+    }
+
+    fn read_primitive(&mut self, name: &str) -> AnchorResult<Value> {
+        Ok(match name {
+            "bool" => Value::Bool(self.take_array::<1>()?[0] != 0),
+            "u8" => u8::from_le_bytes(self.take_array()?).into(),
+            "i8" => i8::from_le_bytes(self.take_array()?).into(),
+            "u16" => u16::from_le_bytes(self.take_array()?).into(),
+            "i16" => i16::from_le_bytes(self.take_array()?).into(),
+            "u32" => u32::from_le_bytes(self.take_array()?).into(),
+            "i32" => i32::from_le_bytes(self.take_array()?).into(),
+            "f32" => f32::from_le_bytes(self.take_array()?).into(),
+            "f64" => f64::from_le_bytes(self.take_array()?).into(),
+            "u64" => u64::from_le_bytes(self.take_array()?).to_string().into(),
+            "i64" => i64::from_le_bytes(self.take_array()?).to_string().into(),
+            "u128" => u128::from_le_bytes(self.take_array()?).to_string().into(),
+            "i128" => i128::from_le_bytes(self.take_array()?).to_string().into(),
+            "pubkey" | "publicKey" => base58::encode(self.take(32)?).into(),
+            "string" => {
+                let len = self.read_len()?;
+                std::str::from_utf8(self.take(len)?)
+                    .map_err(|_| AnchorError::InvalidUtf8)?
+                    .into()
+            }
+            "bytes" => {
+                let len = self.read_len()?;
+                BASE64.encode(self.take(len)?).into()
+            }
+            name => return Err(AnchorError::UnknownType(name.to_owned())),
+        })
+    }
+
+    fn read_defined(&mut self, idl: &AnchorIdl, name: &str, ty: &Value) -> AnchorResult<Value> {
+        match ty["kind"].as_str() {
+            Some("struct") => self.read_fields(idl, &ty["fields"]),
+            Some("enum") => {
+                let variant = self.take_array::<1>()?[0] as usize;
+                let variant = ty["variants"]
+                    .as_array()
+                    .and_then(|variants| variants.get(variant))
+                    .ok_or_else(|| AnchorError::InvalidVariant(name.to_owned(), variant as u32))?;
+                let variant_name = variant["name"]
+                    .as_str()
+                    .ok_or_else(|| AnchorError::InvalidIdl("name is missed".to_owned()))?;
+                match variant.get("fields") {
+                    Some(fields) => {
+                        let mut map = Map::new();
+                        map.insert(variant_name.to_owned(), self.read_fields(idl, fields)?);
+                        Ok(Value::Object(map))
+                    }
+                    None => Ok(Value::String(variant_name.to_owned())),
+                }
+            }
+            Some("type") | Some("alias") => self.read(idl, &ty["alias"]),
+            _ => Err(AnchorError::UnknownType(name.to_owned())),
+        }
+    }
+
+    // named fields as object, tuple fields as array
+    fn read_fields(&mut self, idl: &AnchorIdl, fields: &Value) -> AnchorResult<Value> {
+        let fields = fields.as_array().map(Vec::as_slice).unwrap_or_default();
+        if fields.iter().all(|field| field.get("name").is_some()) {
+            let mut map = Map::new();
+            for field in fields {
+                let name = AnchorIdl::parse_name(field)?;
+                map.insert(name, self.read(idl, &field["type"])?);
+            }
+            Ok(Value::Object(map))
+        } else {
+            fields
+                .iter()
+                .map(|ty| self.read(idl, ty))
+                .collect::<AnchorResult<Vec<_>>>()
+                .map(Value::Array)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedEvent {
+    pub name: String,
+    pub data: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedInstruction {
+    pub name: String,
+    pub args: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedProgramEvent {
+    /// Program id in base58
+    pub program_id: String,
+    pub event: DecodedEvent,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedProgramInstruction {
+    /// Program id in base58
+    pub program_id: String,
+    /// Index of outer instruction
+    pub index: usize,
+    /// Index in inner instructions
+    pub inner_index: Option<usize>,
+    pub instruction: DecodedInstruction,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DecodedTransaction {
+    pub events: Vec<DecodedProgramEvent>,
+    pub instructions: Vec<DecodedProgramInstruction>,
+    /// Errors for data that matched a discriminator but failed to decode
+    pub errors: Vec<String>,
+}
+
+/// Decoder for multiple programs, IDL is selected by program id
+#[derive(Debug, Default, Clone)]
+pub struct AnchorDecoder {
+    idls: HashMap<String, AnchorIdl>,
+}
+
+impl AnchorDecoder {
+    /// Add IDL, `program_id` is required for IDL older than Anchor 0.30
+    pub fn add_idl(&mut self, program_id: Option<String>, idl: AnchorIdl) -> AnchorResult<()> {
+        let program_id = program_id
+            .or_else(|| idl.address.clone())
+            .ok_or_else(|| AnchorError::InvalidIdl("program id is missed".to_owned()))?;
+        self.idls.insert(program_id, idl);
+        Ok(())
+    }
+
+    pub fn decode_transaction(&self, tx: &SubscribeUpdateTransactionInfo) -> DecodedTransaction {
+        let mut decoded = DecodedTransaction::default();
+        let (Some(transaction), Some(meta)) = (&tx.transaction, &tx.meta) else {
+            return decoded;
+        };
+        let Some(message) = &transaction.message else {
+            return decoded;
+        };
+
+        let account_keys = message
+            .account_keys
+            .iter()
+            .chain(meta.loaded_writable_addresses.iter())
+            .chain(meta.loaded_readonly_addresses.iter())
+            .map(base58::encode)
+            .collect::<Vec<_>>();
+
+        let mut decode_ix =
+            |program_id_index: u32, data: &[u8], index: usize, inner_index: Option<usize>| {
+                let Some(program_id) = account_keys.get(program_id_index as usize) else {
+                    return;
+                };
+                let Some(idl) = self.idls.get(program_id) else {
+                    return;
+                };
+                let result = if let Some(data) = data.strip_prefix(&EVENT_IX_TAG_LE) {
+                    idl.decode_event(data).map(|event| {
+                        if let Some(event) = event {
+                            decoded.events.push(DecodedProgramEvent {
+                                program_id: program_id.clone(),
+                                event,
+                            });
+                        }
+                    })
+                } else {
+                    idl.decode_instruction(data).map(|instruction| {
+                        if let Some(instruction) = instruction {
+                            decoded.instructions.push(DecodedProgramInstruction {
+                                program_id: program_id.clone(),
+                                index,
+                                inner_index,
+                                instruction,
+                            });
+                        }
+                    })
+                };
+                if let Err(error) = result {
+                    decoded.errors.push(format!("{program_id}: {error}"));
+                }
+            };
+
+        for (index, ix) in message.instructions.iter().enumerate() {
+            decode_ix(ix.program_id_index, &ix.data, index, None);
+            for inner in meta
+                .inner_instructions
+                .iter()
+                .filter(|inner| inner.index as usize == index)
+            {
+                for (inner_index, ix) in inner.instructions.iter().enumerate() {
+                    decode_ix(ix.program_id_index, &ix.data, index, Some(inner_index));
+                }
+            }
+        }
+
+        for (program_id, data) in ProgramDataLogs::new(&meta.log_messages) {
+            let Some(idl) = self.idls.get(program_id) else {
+                continue;
+            };
+            match idl.decode_event(&data) {
+                Ok(Some(event)) => decoded.events.push(DecodedProgramEvent {
+                    program_id: program_id.to_owned(),
+                    event,
+                }),
+                Ok(None) => {}
+                Err(error) => decoded.errors.push(format!("{program_id}: {error}")),
+            }
+        }
+
+        decoded
+    }
+}
+
+/// Iterator over `Program data: ` logs with program id from invoke stack
+struct ProgramDataLogs<'a> {
+    logs: std::slice::Iter<'a, String>,
+    stack: Vec<&'a str>,
+}
+
+impl<'a> ProgramDataLogs<'a> {
+    fn new(logs: &'a [String]) -> Self {
+        Self {
+            logs: logs.iter(),
+            stack: vec![],
+        }
+    }
+}
+
+impl<'a> Iterator for ProgramDataLogs<'a> {
+    type Item = (&'a str, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for log in self.logs.by_ref() {
+            if let Some(data) = log.strip_prefix(LOG_PROGRAM_DATA) {
+                if let (Some(program_id), Ok(data)) = (self.stack.last(), BASE64.decode(data)) {
+                    return Some((*program_id, data));
+                }
+            } else if let Some(rest) = log.strip_prefix("Program ") {
+                let mut parts = rest.split(' ');
+                match (parts.next(), parts.next()) {
+                    (Some(program_id), Some("invoke")) => self.stack.push(program_id),
+                    (Some(_program_id), Some("success" | "failed:")) => {
+                        self.stack.pop();
+                    }
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{AnchorDecoder, AnchorIdl, BASE64, EVENT_IX_TAG_LE},
+        base64::Engine,
+        serde_json::json,
+        yellowstone_grpc_proto::prelude::{
+            CompiledInstruction, InnerInstruction, InnerInstructions, Message,
+            SubscribeUpdateTransactionInfo, Transaction, TransactionStatusMeta,
+        },
+    };
+
+    const PROGRAM_ID: &str = "11111111111111111111111111111112";
+
+    fn idl() -> AnchorIdl {
+        AnchorIdl::parse(
+            &json!({
+                "address": PROGRAM_ID,
+                "instructions": [{
+                    "name": "deposit",
+                    "discriminator": [1, 2, 3, 4, 5, 6, 7, 8],
+                    "accounts": [],
+                    "args": [{ "name": "amount", "type": "u64" }, { "name": "memo", "type": { "option": "string" } }]
+                }],
+                "events": [{ "name": "Deposited", "discriminator": [8, 7, 6, 5, 4, 3, 2, 1] }],
+                "types": [{
+                    "name": "Deposited",
+                    "type": {
+                        "kind": "struct",
+                        "fields": [
+                            { "name": "amount", "type": "u64" },
+                            { "name": "side", "type": { "defined": { "name": "Side" } } }
+                        ]
+                    }
+                }, {
+                    "name": "Side",
+                    "type": { "kind": "enum", "variants": [{ "name": "Bid" }, { "name": "Ask" }] }
+                }]
+            })
+            .to_string(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_decode_event_and_instruction() {
+        let idl = idl();
+
+        let mut event = vec![8, 7, 6, 5, 4, 3, 2, 1];
+        event.extend_from_slice(&42u64.to_le_bytes());
+        event.push(1);
+        let decoded = idl.decode_event(&event).unwrap().unwrap();
+        assert_eq!(decoded.name, "Deposited");
+        assert_eq!(decoded.data, json!({ "amount": "42", "side": "Ask" }));
+
+        let mut ix = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        ix.extend_from_slice(&7u64.to_le_bytes());
+        ix.push(1);
+        ix.extend_from_slice(&2u32.to_le_bytes());
+        ix.extend_from_slice(b"hi");
+        let decoded = idl.decode_instruction(&ix).unwrap().unwrap();
+        assert_eq!(decoded.name, "deposit");
+        assert_eq!(decoded.args, json!({ "amount": "7", "memo": "hi" }));
+
+        assert!(idl.decode_instruction(&[0; 8]).unwrap().is_none());
+        assert!(idl.decode_event(&event[..10]).is_err());
+    }
+
+    #[test]
+    fn test_decode_transaction() {
+        let mut decoder = AnchorDecoder::default();
+        decoder.add_idl(None, idl()).unwrap();
+
+        let mut event = vec![8, 7, 6, 5, 4, 3, 2, 1];
+        event.extend_from_slice(&42u64.to_le_bytes());
+        event.push(0);
+        let mut cpi_event = EVENT_IX_TAG_LE.to_vec();
+        cpi_event.extend_from_slice(&event);
+
+        let tx = SubscribeUpdateTransactionInfo {
+            transaction: Some(Transaction {
+                signatures: vec![vec![0; 64]],
+                message: Some(Message {
+                    account_keys: vec![vec![0; 32], bs58::decode(PROGRAM_ID).into_vec().unwrap()],
+                    instructions: vec![CompiledInstruction {
+                        program_id_index: 1,
+                        accounts: vec![],
+                        data: vec![1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                    }],
+                    ..Default::default()
+                }),
+            }),
+            meta: Some(TransactionStatusMeta {
+                log_messages: vec![
+                    format!("Program {PROGRAM_ID} invoke [1]"),
+                    format!("Program data: {}", BASE64.encode(&event)),
+                    format!("Program {PROGRAM_ID} success"),
+                ],
+                inner_instructions: vec![InnerInstructions {
+                    index: 0,
+                    instructions: vec![InnerInstruction {
+                        program_id_index: 1,
+                        accounts: vec![],
+                        data: cpi_event,
+                        stack_height: Some(2),
+                    }],
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let decoded = decoder.decode_transaction(&tx);
+        assert!(decoded.errors.is_empty(), "{:?}", decoded.errors);
+        assert_eq!(decoded.instructions.len(), 1);
+        assert_eq!(decoded.instructions[0].instruction.name, "deposit");
+        assert_eq!(decoded.events.len(), 2);
+        assert!(decoded
+            .events
+            .iter()
+            .all(|event| event.event.data == json!({ "amount": "42", "side": "Bid" })));
+    }
+}
@@ -0,0 +1,527 @@
+//! Filling of slot gaps in the stream with blocks fetched from Solana RPC.
+//!
+//! Gaps are detected by `parent_slot` of blocks and blocks meta: when parent of
+//! the received block is newer than the last seen block, missed slots are
+//! requested with [`BlockFetcher`] and synthetic slot, transaction, block meta
+//! and block updates are injected before the received update. Subscription
+//! should include `blocks_meta` for gap detection, transactions of the
+//! received slot could be delivered before the gap is detected.
+//!
+//! [`BlockFetcher`] is usually implemented with `getBlock` from RPC client and
+//! `yellowstone_grpc_proto::convert_to::create_block`.
+
+use {
+    futures::stream::{self, Stream, StreamExt},
+    std::{collections::VecDeque, future::Future, pin::Pin},
+    tonic::Status,
+    yellowstone_grpc_proto::{
+        base58,
+        invocation::{invocations, invoked_programs},
+        prelude::{
+            subscribe_update::UpdateOneof, CommitmentLevel, SlotStatus, SubscribeRequest,
+            SubscribeRequestFilterTransactions, SubscribeUpdate, SubscribeUpdateBlock,
+            SubscribeUpdateBlockMeta, SubscribeUpdateSlot, SubscribeUpdateTransaction,
+            SubscribeUpdateTransactionInfo, SubscribeUpdateTransactionInvocation, Transaction,
+            TransactionStatusMeta,
+        },
+    },
+};
+
+/// Source of missed blocks
+pub trait BlockFetcher {
+    type Error;
+
+    /// `None` for skipped slot
+    fn get_block(
+        &mut self,
+        slot: u64,
+    ) -> impl Future<Output = Result<Option<SubscribeUpdateBlock>, Self::Error>> + Send;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackfillError<E> {
+    #[error("gRPC status: {0}")]
+    Status(#[from] Status),
+    #[error("failed to fetch block {slot}: {error}")]
+    Fetch { slot: u64, error: E },
+    #[error("gap from {from} to {to} is bigger than {max_gap} slots")]
+    GapTooLarge { from: u64, to: u64, max_gap: u64 },
+}
+
+/// Filters used for synthetic updates, usually created from the subscribe request
+#[derive(Debug, Clone)]
+pub struct BackfillConfig {
+    /// Maximum number of slots fetched for a single gap
+    pub max_gap: u64,
+    pub slot_status: SlotStatus,
+    pub slots: Vec<String>,
+    pub transactions: Vec<(String, SubscribeRequestFilterTransactions)>,
+    pub blocks_meta: Vec<String>,
+    pub blocks: Vec<String>,
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            max_gap: 150,
+            slot_status: SlotStatus::SlotConfirmed,
+            slots: vec![],
+            transactions: vec![],
+            blocks_meta: vec![],
+            blocks: vec![],
+        }
+    }
+}
+
+impl BackfillConfig {
+    /// Transactions filters are applied on the client side: `vote`, `failed`,
+    /// `signature`, accounts, `log_contains`, fees and invoked programs (accounts of blocks filters are ignored)
+    pub fn from_request(request: &SubscribeRequest) -> Self {
+        let slot_status = match CommitmentLevel::try_from(request.commitment.unwrap_or_default()) {
+            Ok(CommitmentLevel::Processed) => SlotStatus::SlotProcessed,
+            Ok(CommitmentLevel::Finalized) => SlotStatus::SlotFinalized,
+            _ => SlotStatus::SlotConfirmed,
+        };
+        Self {
+            slot_status,
+            slots: request.slots.keys().cloned().collect(),
+            transactions: request
+                .transactions
+                .iter()
+                .map(|(name, filter)| (name.clone(), filter.clone()))
+                .collect(),
+            blocks_meta: request.blocks_meta.keys().cloned().collect(),
+            blocks: request.blocks.keys().cloned().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Synthetic updates in the same order as the server sends them
+    pub fn create_updates(&self, block: SubscribeUpdateBlock) -> Vec<SubscribeUpdate> {
+        let mut updates = vec![];
+
+        if !self.slots.is_empty() {
+            updates.push(create_update(
+                self.slots.clone(),
+                UpdateOneof::Slot(SubscribeUpdateSlot {
+                    slot: block.slot,
+                    parent: Some(block.parent_slot),
+                    status: self.slot_status as i32,
+                    dead_error: None,
+                }),
+            ));
+        }
+
+        for transaction in block.transactions.iter() {
+            let (filters, matched): (Vec<_>, Vec<_>) = self
+                .transactions
+                .iter()
+                .filter(|(_name, filter)| matches_transaction(filter, transaction))
+                .map(|(name, filter)| (name.clone(), filter))
+                .unzip();
+            if !filters.is_empty() {
+                updates.push(create_update(
+                    filters,
+                    UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                        transaction: Some(transaction.clone()),
+                        slot: block.slot,
+                        invocations: matched_invocations(&matched, transaction),
+                    }),
+                ));
+            }
+        }
+
+        if !self.blocks_meta.is_empty() {
+            updates.push(create_update(
+                self.blocks_meta.clone(),
+                UpdateOneof::BlockMeta(SubscribeUpdateBlockMeta {
+                    slot: block.slot,
+                    blockhash: block.blockhash.clone(),
+                    rewards: block.rewards.clone(),
+                    block_time: block.block_time,
+                    block_height: block.block_height,
+                    parent_slot: block.parent_slot,
+                    parent_blockhash: block.parent_blockhash.clone(),
+                    executed_transaction_count: block.executed_transaction_count,
+                    entries_count: block.entries_count,
+                    ticks_count: block.ticks_count,
+                }),
+            ));
+        }
+
+        if !self.blocks.is_empty() {
+            updates.push(create_update(
+                self.blocks.clone(),
+                UpdateOneof::Block(block),
+            ));
+        }
+
+        updates
+    }
+}
+
+const fn create_update(filters: Vec<String>, update: UpdateOneof) -> SubscribeUpdate {
+    SubscribeUpdate {
+        filters,
+        update_oneof: Some(update),
+        created_at: None,
+        sequence: 0,
+        ingested_at: None,
+        replica: String::new(),
+    }
+}
+
+fn matches_transaction(
+    filter: &SubscribeRequestFilterTransactions,
+    transaction: &SubscribeUpdateTransactionInfo,
+) -> bool {
+    if filter.vote.is_some_and(|vote| vote != transaction.is_vote) {
+        return false;
+    }
+
+    let meta = transaction.meta.as_ref();
+    let failed = meta.is_some_and(|meta| meta.err.is_some());
+    if filter.failed.is_some_and(|value| value != failed) {
+        return false;
+    }
+
+    if let Some(signature) = &filter.signature {
+        if base58::encode(&transaction.signature) != *signature {
+            return false;
+        }
+    }
+
+    let accounts = transaction
+        .transaction
+        .as_ref()
+        .and_then(|tx| tx.message.as_ref())
+        .map(|message| message.account_keys.iter())
+        .into_iter()
+        .flatten()
+        .chain(meta.into_iter().flat_map(|meta| {
+            meta.loaded_writable_addresses
+                .iter()
+                .chain(meta.loaded_readonly_addresses.iter())
+        }))
+        .map(base58::encode)
+        .collect::<Vec<_>>();
+    let contains = |pubkey: &String| accounts.contains(pubkey);
+    if !filter.account_include.is_empty() && !filter.account_include.iter().any(contains) {
+        return false;
+    }
+    if filter.account_exclude.iter().any(contains) {
+        return false;
+    }
+    if !filter.account_required.iter().all(contains) {
+        return false;
+    }
+
+    let default_tx = Transaction::default();
+    let tx = transaction.transaction.as_ref().unwrap_or(&default_tx);
+    if !filter.fee_payer.is_empty()
+        && !tx
+            .fee_payer()
+            .is_some_and(|pubkey| filter.fee_payer.contains(&base58::encode(pubkey)))
+    {
+        return false;
+    }
+    if !filter.matches_fee(meta.map_or(0, |meta| meta.fee), tx) {
+        return false;
+    }
+    if !filter.program_invoked.is_empty()
+        && !invoked_programs(tx, meta.unwrap_or(&TransactionStatusMeta::default()))
+            .any(|program_id| filter.program_invoked.contains(&base58::encode(program_id)))
+    {
+        return false;
+    }
+
+    filter.matches_logs(meta.map(|meta| meta.log_messages.as_slice()).unwrap_or(&[]))
+}
+
+/// Invocations of programs from `program_invoked` of matched filters, same as
+/// the server annotates transactions
+fn matched_invocations(
+    filters: &[&SubscribeRequestFilterTransactions],
+    transaction: &SubscribeUpdateTransactionInfo,
+) -> Vec<SubscribeUpdateTransactionInvocation> {
+    if filters
+        .iter()
+        .all(|filter| filter.program_invoked.is_empty())
+    {
+        return vec![];
+    }
+    let Some(tx) = transaction.transaction.as_ref() else {
+        return vec![];
+    };
+    let default_meta = TransactionStatusMeta::default();
+    let meta = transaction.meta.as_ref().unwrap_or(&default_meta);
+    invocations(tx, meta)
+        .filter(|invocation| {
+            let program_id = base58::encode(invocation.program_id());
+            filters
+                .iter()
+                .any(|filter| filter.program_invoked.contains(&program_id))
+        })
+        .map(Into::into)
+        .collect()
+}
+
+/// Tracking of the last received block
+#[derive(Debug, Default, Clone)]
+pub struct Backfill {
+    config: BackfillConfig,
+    last_slot: Option<u64>,
+}
+
+impl Backfill {
+    /// `last_slot` is the last processed block before reconnect, if any
+    pub const fn new(config: BackfillConfig, last_slot: Option<u64>) -> Self {
+        Self { config, last_slot }
+    }
+
+    pub const fn last_slot(&self) -> Option<u64> {
+        self.last_slot
+    }
+
+    /// Returns range of missed slots, if any, and updates the last slot
+    pub fn check_gap(&mut self, update: &SubscribeUpdate) -> Option<(u64, u64)> {
+        let (slot, parent_slot) = match &update.update_oneof {
+            Some(UpdateOneof::Block(msg)) => (msg.slot, msg.parent_slot),
+            Some(UpdateOneof::BlockMeta(msg)) => (msg.slot, msg.parent_slot),
+            _ => return None,
+        };
+
+        let last_slot = self
+            .last_slot
+            .replace(slot.max(self.last_slot.unwrap_or(0)));
+        match last_slot {
+            Some(last_slot) if parent_slot > last_slot => Some((last_slot + 1, parent_slot)),
+            _ => None,
+        }
+    }
+
+    /// Synthetic updates for the range of missed slots
+    pub async fn fetch<F: BlockFetcher>(
+        &self,
+        fetcher: &mut F,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<SubscribeUpdate>, BackfillError<F::Error>> {
+        let max_gap = self.config.max_gap;
+        if to - from + 1 > max_gap {
+            return Err(BackfillError::GapTooLarge { from, to, max_gap });
+        }
+
+        let mut updates = vec![];
+        for slot in from..=to {
+            let block = fetcher
+                .get_block(slot)
+                .await
+                .map_err(|error| BackfillError::Fetch { slot, error })?;
+            if let Some(block) = block {
+                updates.extend(self.config.create_updates(block));
+            }
+        }
+        Ok(updates)
+    }
+
+    /// Wrap stream of updates, stream is finished after the first error
+    pub fn into_stream<S, F>(
+        self,
+        stream: S,
+        fetcher: F,
+    ) -> impl Stream<Item = Result<SubscribeUpdate, BackfillError<F::Error>>>
+    where
+        S: Stream<Item = Result<SubscribeUpdate, Status>> + Send + 'static,
+        F: BlockFetcher,
+    {
+        type Inner = Pin<Box<dyn Stream<Item = Result<SubscribeUpdate, Status>> + Send>>;
+        let state: (Self, Inner, F, VecDeque<SubscribeUpdate>, bool) =
+            (self, Box::pin(stream), fetcher, VecDeque::new(), false);
+
+        stream::unfold(
+            state,
+            |(mut backfill, mut stream, mut fetcher, mut queue, failed)| async move {
+                if let Some(update) = queue.pop_front() {
+                    return Some((Ok(update), (backfill, stream, fetcher, queue, failed)));
+                }
+                if failed {
+                    return None;
+                }
+
+                let item = match stream.next().await? {
+                    Ok(update) => match backfill.check_gap(&update) {
+                        Some((from, to)) => match backfill.fetch(&mut fetcher, from, to).await {
+                            Ok(updates) => {
+                                queue.extend(updates);
+                                queue.push_back(update);
+                                Ok(queue.pop_front().expect("non empty queue"))
+                            }
+                            Err(error) => Err(error),
+                        },
+                        None => Ok(update),
+                    },
+                    Err(status) => Err(status.into()),
+                };
+                let failed = item.is_err();
+                Some((item, (backfill, stream, fetcher, queue, failed)))
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{Backfill, BackfillConfig, BackfillError, BlockFetcher},
+        futures::{stream, StreamExt},
+        std::future::Future,
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
+            SubscribeRequestFilterSlots, SubscribeRequestFilterTransactions, SubscribeUpdate,
+            SubscribeUpdateBlock, SubscribeUpdateBlockMeta, SubscribeUpdateTransactionInfo,
+            TransactionStatusMeta,
+        },
+    };
+
+    struct Blocks;
+
+    impl BlockFetcher for Blocks {
+        type Error = &'static str;
+
+        fn get_block(
+            &mut self,
+            slot: u64,
+        ) -> impl Future<Output = Result<Option<SubscribeUpdateBlock>, Self::Error>> + Send
+        {
+            let result = match slot {
+                // skipped
+                12 => Ok(None),
+                20 => Err("rpc error"),
+                _ => Ok(Some(SubscribeUpdateBlock {
+                    slot,
+                    parent_slot: if slot == 13 { 11 } else { slot - 1 },
+                    transactions: vec![
+                        SubscribeUpdateTransactionInfo {
+                            is_vote: true,
+                            ..Default::default()
+                        },
+                        SubscribeUpdateTransactionInfo {
+                            meta: Some(TransactionStatusMeta {
+                                log_messages: vec!["Program log: Swap".to_owned()],
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                })),
+            };
+            async move { result }
+        }
+    }
+
+    fn block_meta(slot: u64, parent_slot: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            filters: vec!["meta".to_owned()],
+            update_oneof: Some(UpdateOneof::BlockMeta(SubscribeUpdateBlockMeta {
+                slot,
+                parent_slot,
+                ..Default::default()
+            })),
+            created_at: None,
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        }
+    }
+
+    fn kinds(updates: &[SubscribeUpdate]) -> Vec<(&'static str, u64)> {
+        updates
+            .iter()
+            .map(|update| match update.update_oneof.as_ref().unwrap() {
+                UpdateOneof::Slot(msg) => ("slot", msg.slot),
+                UpdateOneof::Transaction(msg) => ("transaction", msg.slot),
+                UpdateOneof::BlockMeta(msg) => ("block_meta", msg.slot),
+                UpdateOneof::Block(msg) => ("block", msg.slot),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    fn config() -> BackfillConfig {
+        let request = SubscribeRequest::default()
+            .with_slots("slots", SubscribeRequestFilterSlots::default())
+            .with_transactions(
+                "swaps",
+                SubscribeRequestFilterTransactions::default()
+                    .with_vote(false)
+                    .with_log_contains(["Swap"]),
+            )
+            .with_blocks_meta("meta")
+            .with_commitment(CommitmentLevel::Confirmed);
+        BackfillConfig::from_request(&request)
+    }
+
+    #[test]
+    fn test_create_updates() {
+        let updates = config().create_updates(SubscribeUpdateBlock {
+            slot: 5,
+            parent_slot: 4,
+            transactions: vec![SubscribeUpdateTransactionInfo {
+                is_vote: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        assert_eq!(kinds(&updates), vec![("slot", 5), ("block_meta", 5)]);
+        assert_eq!(updates[1].filters, vec!["meta".to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let updates = vec![
+            Ok(block_meta(10, 9)),
+            Ok(block_meta(11, 10)),
+            Ok(block_meta(14, 13)),
+        ];
+        let items = Backfill::new(config(), Some(8))
+            .into_stream(stream::iter(updates), Blocks)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            kinds(&items),
+            vec![
+                ("slot", 9),
+                ("transaction", 9),
+                ("block_meta", 9),
+                ("block_meta", 10),
+                ("block_meta", 11),
+                ("slot", 13),
+                ("transaction", 13),
+                ("block_meta", 13),
+                ("block_meta", 14),
+            ]
+        );
+
+        let updates = vec![Ok(block_meta(21, 20)), Ok(block_meta(22, 21))];
+        let items = Backfill::new(config(), Some(19))
+            .into_stream(stream::iter(updates), Blocks)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(items.len(), 1);
+        assert!(matches!(
+            items[0],
+            Err(BackfillError::Fetch { slot: 20, .. })
+        ));
+
+        let mut backfill = Backfill::new(config(), Some(0));
+        let (from, to) = backfill.check_gap(&block_meta(1000, 999)).unwrap();
+        assert!(matches!(
+            backfill.fetch(&mut Blocks, from, to).await,
+            Err(BackfillError::GapTooLarge { from: 1, .. })
+        ));
+        assert_eq!(backfill.last_slot(), Some(1000));
+    }
+}
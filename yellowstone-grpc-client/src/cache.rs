@@ -0,0 +1,249 @@
+//! Latest slot and blockhash answered from the live subscription.
+//!
+//! Cache is updated from slot and block meta updates of the stream, cached
+//! values older than `ttl` are not used and unary request is sent instead.
+//! Unary responses are cached too, so hot loops send at most one request per
+//! `ttl` for every commitment.
+
+use {
+    futures::stream::{Stream, StreamExt},
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, CommitmentLevel, GetLatestBlockhashResponse,
+        GetSlotResponse, SlotStatus, SubscribeUpdate,
+    },
+};
+
+/// Same as `MAX_RECENT_BLOCKHASHES` used by the server
+const MAX_RECENT_BLOCKHASHES: u64 = 300;
+
+#[derive(Debug, Clone, Copy)]
+struct Cached<T> {
+    value: T,
+    slot: u64,
+    updated_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    slots: HashMap<i32, Cached<u64>>,
+    blockhashes: HashMap<i32, Cached<GetLatestBlockhashResponse>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChainStateCache {
+    /// Commitment of the subscription, blocks meta are received only for it
+    commitment: CommitmentLevel,
+    ttl: Duration,
+    state: Arc<Mutex<CacheState>>,
+}
+
+impl ChainStateCache {
+    pub fn new(commitment: CommitmentLevel, ttl: Duration) -> Self {
+        Self {
+            commitment,
+            ttl,
+            state: Arc::default(),
+        }
+    }
+
+    /// Apply slot or block meta update, other updates are ignored
+    pub fn update(&self, update: &SubscribeUpdate) {
+        let now = Instant::now();
+        let mut state = self.state.lock().expect("unpoisoned mutex");
+        match &update.update_oneof {
+            Some(UpdateOneof::Slot(msg)) => {
+                let commitment = match SlotStatus::try_from(msg.status) {
+                    Ok(SlotStatus::SlotProcessed) => CommitmentLevel::Processed,
+                    Ok(SlotStatus::SlotConfirmed) => CommitmentLevel::Confirmed,
+                    Ok(SlotStatus::SlotFinalized) => CommitmentLevel::Finalized,
+                    _ => return,
+                };
+                Self::set(&mut state.slots, commitment, msg.slot, msg.slot, now);
+            }
+            Some(UpdateOneof::BlockMeta(msg)) => {
+                let Some(block_height) = msg.block_height else {
+                    return;
+                };
+                let response = GetLatestBlockhashResponse {
+                    slot: msg.slot,
+                    blockhash: msg.blockhash.clone(),
+                    last_valid_block_height: block_height.block_height + MAX_RECENT_BLOCKHASHES,
+                };
+                Self::set(
+                    &mut state.blockhashes,
+                    self.commitment,
+                    response,
+                    msg.slot,
+                    now,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Pass updates through and keep the cache updated
+    pub fn inspect_stream<S, E>(&self, stream: S) -> impl Stream<Item = Result<SubscribeUpdate, E>>
+    where
+        S: Stream<Item = Result<SubscribeUpdate, E>>,
+    {
+        let cache = self.clone();
+        stream.inspect(move |message| {
+            if let Ok(update) = message {
+                cache.update(update);
+            }
+        })
+    }
+
+    fn set<T>(
+        values: &mut HashMap<i32, Cached<T>>,
+        commitment: CommitmentLevel,
+        value: T,
+        slot: u64,
+        updated_at: Instant,
+    ) {
+        let cached = Cached {
+            value,
+            slot,
+            updated_at,
+        };
+        match values.get_mut(&(commitment as i32)) {
+            // do not go back on reordered updates
+            Some(current) if current.slot > slot => {}
+            Some(current) => *current = cached,
+            None => {
+                values.insert(commitment as i32, cached);
+            }
+        }
+    }
+
+    fn get<T: Clone>(
+        &self,
+        values: &HashMap<i32, Cached<T>>,
+        commitment: CommitmentLevel,
+    ) -> Option<T> {
+        values
+            .get(&(commitment as i32))
+            .filter(|cached| cached.updated_at.elapsed() < self.ttl)
+            .map(|cached| cached.value.clone())
+    }
+
+    pub fn get_slot(&self, commitment: CommitmentLevel) -> Option<GetSlotResponse> {
+        let state = self.state.lock().expect("unpoisoned mutex");
+        self.get(&state.slots, commitment)
+            .map(|slot| GetSlotResponse { slot })
+    }
+
+    pub fn get_latest_blockhash(
+        &self,
+        commitment: CommitmentLevel,
+    ) -> Option<GetLatestBlockhashResponse> {
+        let state = self.state.lock().expect("unpoisoned mutex");
+        self.get(&state.blockhashes, commitment)
+    }
+
+    /// Save unary response
+    pub fn set_slot(&self, commitment: CommitmentLevel, response: &GetSlotResponse) {
+        let mut state = self.state.lock().expect("unpoisoned mutex");
+        Self::set(
+            &mut state.slots,
+            commitment,
+            response.slot,
+            response.slot,
+            Instant::now(),
+        );
+    }
+
+    /// Save unary response
+    pub fn set_latest_blockhash(
+        &self,
+        commitment: CommitmentLevel,
+        response: &GetLatestBlockhashResponse,
+    ) {
+        let mut state = self.state.lock().expect("unpoisoned mutex");
+        Self::set(
+            &mut state.blockhashes,
+            commitment,
+            response.clone(),
+            response.slot,
+            Instant::now(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{ChainStateCache, MAX_RECENT_BLOCKHASHES},
+        futures::{stream, StreamExt},
+        std::time::Duration,
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, BlockHeight, CommitmentLevel,
+            GetLatestBlockhashResponse, GetSlotResponse, SlotStatus, SubscribeUpdate,
+            SubscribeUpdateBlockMeta, SubscribeUpdateSlot,
+        },
+    };
+
+    fn slot(slot: u64, status: SlotStatus) -> Result<SubscribeUpdate, ()> {
+        Ok(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                status: status as i32,
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let cache = ChainStateCache::new(CommitmentLevel::Confirmed, Duration::from_secs(60));
+        let updates = vec![
+            slot(10, SlotStatus::SlotProcessed),
+            slot(9, SlotStatus::SlotConfirmed),
+            // reordered
+            slot(8, SlotStatus::SlotProcessed),
+            Ok(SubscribeUpdate {
+                update_oneof: Some(UpdateOneof::BlockMeta(SubscribeUpdateBlockMeta {
+                    slot: 9,
+                    blockhash: "hash".to_owned(),
+                    block_height: Some(BlockHeight { block_height: 7 }),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            }),
+        ];
+        let count = cache.inspect_stream(stream::iter(updates)).count().await;
+        assert_eq!(count, 4);
+
+        assert_eq!(
+            cache.get_slot(CommitmentLevel::Processed),
+            Some(GetSlotResponse { slot: 10 })
+        );
+        assert_eq!(
+            cache.get_slot(CommitmentLevel::Confirmed),
+            Some(GetSlotResponse { slot: 9 })
+        );
+        assert_eq!(cache.get_slot(CommitmentLevel::Finalized), None);
+        assert_eq!(
+            cache.get_latest_blockhash(CommitmentLevel::Confirmed),
+            Some(GetLatestBlockhashResponse {
+                slot: 9,
+                blockhash: "hash".to_owned(),
+                last_valid_block_height: 7 + MAX_RECENT_BLOCKHASHES,
+            })
+        );
+        assert_eq!(cache.get_latest_blockhash(CommitmentLevel::Finalized), None);
+    }
+
+    #[test]
+    fn test_ttl() {
+        let cache = ChainStateCache::new(CommitmentLevel::Confirmed, Duration::ZERO);
+        cache.set_slot(CommitmentLevel::Finalized, &GetSlotResponse { slot: 5 });
+        assert_eq!(cache.get_slot(CommitmentLevel::Finalized), None);
+    }
+}
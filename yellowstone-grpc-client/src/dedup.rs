@@ -0,0 +1,339 @@
+//! Transactions delivered once across commitment levels.
+//!
+//! Streams with transactions at multiple commitment levels (merged
+//! subscriptions or processed transactions with slot status updates) deliver
+//! the same transaction up to three times. Transactions and transactions
+//! statuses are passed only on the first delivery, repeated deliveries are
+//! suppressed and confirmed / finalized slot status updates emit commitment
+//! upgrade of every delivered transaction of the slot. Slots are kept for
+//! `retain_slots` after the finalized slot to catch late duplicates, dead slots
+//! are dropped. Streams without finalized slot status updates are pruned by
+//! the highest seen slot: slots older than `retain_slots` plus
+//! [`TransactionDedup::FINALIZATION_LAG_MAX`] are dropped. Other updates are
+//! passed as is.
+
+use {
+    futures::stream::{self, Stream, StreamExt},
+    std::collections::{BTreeMap, HashSet},
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, CommitmentLevel, SlotStatus, SubscribeUpdate,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DedupUpdate {
+    /// First delivery of the transaction or other update
+    Update(Box<SubscribeUpdate>),
+    /// Delivered transaction reached higher commitment
+    CommitmentUpgrade {
+        slot: u64,
+        signature: Vec<u8>,
+        commitment: CommitmentLevel,
+    },
+}
+
+#[derive(Debug)]
+struct SlotState {
+    commitment: CommitmentLevel,
+    transactions: HashSet<Vec<u8>>,
+    statuses: HashSet<Vec<u8>>,
+    /// Unique signatures in delivery order
+    signatures: Vec<Vec<u8>>,
+}
+
+impl Default for SlotState {
+    fn default() -> Self {
+        Self {
+            commitment: CommitmentLevel::Processed,
+            transactions: HashSet::new(),
+            statuses: HashSet::new(),
+            signatures: Vec::new(),
+        }
+    }
+}
+
+impl SlotState {
+    /// Returns `true` on the first delivery
+    fn insert(&mut self, signature: &[u8], is_status: bool) -> bool {
+        let (set, other) = if is_status {
+            (&mut self.statuses, &self.transactions)
+        } else {
+            (&mut self.transactions, &self.statuses)
+        };
+        if set.contains(signature) {
+            return false;
+        }
+        set.insert(signature.to_vec());
+        if !other.contains(signature) {
+            self.signatures.push(signature.to_vec());
+        }
+        true
+    }
+}
+
+#[derive(Debug)]
+pub struct TransactionDedup {
+    slots: BTreeMap<u64, SlotState>,
+    retain_slots: u64,
+    highest_slot: u64,
+}
+
+impl Default for TransactionDedup {
+    fn default() -> Self {
+        Self::new(Self::RETAIN_SLOTS_DEFAULT)
+    }
+}
+
+impl TransactionDedup {
+    pub const RETAIN_SLOTS_DEFAULT: u64 = 32;
+    /// Slots between the highest seen slot and the finalized slot kept on
+    /// pruning by the highest slot, finalized slot update is expected earlier
+    pub const FINALIZATION_LAG_MAX: u64 = 150;
+
+    pub const fn new(retain_slots: u64) -> Self {
+        Self {
+            slots: BTreeMap::new(),
+            retain_slots,
+            highest_slot: 0,
+        }
+    }
+
+    /// Number of tracked slots
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Add update, returns updates for processing
+    pub fn push(&mut self, update: SubscribeUpdate) -> Vec<DedupUpdate> {
+        let (slot, signature, is_status) = match &update.update_oneof {
+            Some(UpdateOneof::Transaction(msg)) => match &msg.transaction {
+                Some(tx) => (msg.slot, tx.signature.as_slice(), false),
+                None => return vec![DedupUpdate::Update(Box::new(update))],
+            },
+            Some(UpdateOneof::TransactionStatus(msg)) => (msg.slot, msg.signature.as_slice(), true),
+            Some(UpdateOneof::Slot(msg)) => {
+                let slot = msg.slot;
+                self.prune_highest(slot);
+                let mut updates = match SlotStatus::try_from(msg.status) {
+                    Ok(SlotStatus::SlotConfirmed) => self.upgrade(slot, CommitmentLevel::Confirmed),
+                    Ok(SlotStatus::SlotFinalized) => {
+                        let updates = self.upgrade(slot, CommitmentLevel::Finalized);
+                        self.slots = self
+                            .slots
+                            .split_off(&slot.saturating_sub(self.retain_slots));
+                        updates
+                    }
+                    Ok(SlotStatus::SlotDead) => {
+                        self.slots.remove(&slot);
+                        vec![]
+                    }
+                    _ => vec![],
+                };
+                updates.insert(0, DedupUpdate::Update(Box::new(update)));
+                return updates;
+            }
+            _ => return vec![DedupUpdate::Update(Box::new(update))],
+        };
+
+        self.prune_highest(slot);
+        if self
+            .slots
+            .entry(slot)
+            .or_default()
+            .insert(signature, is_status)
+        {
+            vec![DedupUpdate::Update(Box::new(update))]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Drop slots too far behind the highest seen slot
+    fn prune_highest(&mut self, slot: u64) {
+        if slot <= self.highest_slot {
+            return;
+        }
+        self.highest_slot = slot;
+        let min_slot = slot.saturating_sub(self.retain_slots + Self::FINALIZATION_LAG_MAX);
+        if self
+            .slots
+            .first_key_value()
+            .is_some_and(|(first, _state)| *first < min_slot)
+        {
+            self.slots = self.slots.split_off(&min_slot);
+        }
+    }
+
+    fn upgrade(&mut self, slot: u64, commitment: CommitmentLevel) -> Vec<DedupUpdate> {
+        let Some(state) = self.slots.get_mut(&slot) else {
+            return vec![];
+        };
+        if state.commitment >= commitment {
+            return vec![];
+        }
+        state.commitment = commitment;
+        state
+            .signatures
+            .iter()
+            .map(|signature| DedupUpdate::CommitmentUpgrade {
+                slot,
+                signature: signature.clone(),
+                commitment,
+            })
+            .collect()
+    }
+}
+
+/// Deliver every transaction once, with commitment upgrades on slot status updates
+pub fn dedup_transactions<S, E>(
+    stream: S,
+    retain_slots: u64,
+) -> impl Stream<Item = Result<DedupUpdate, E>>
+where
+    S: Stream<Item = Result<SubscribeUpdate, E>>,
+{
+    let mut dedup = TransactionDedup::new(retain_slots);
+    stream.flat_map(move |message| {
+        stream::iter(match message {
+            Ok(update) => dedup.push(update).into_iter().map(Ok).collect(),
+            Err(error) => vec![Err(error)],
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{dedup_transactions, DedupUpdate, TransactionDedup},
+        futures::{stream, StreamExt},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, CommitmentLevel, SlotStatus, SubscribeUpdate,
+            SubscribeUpdateSlot, SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo,
+            SubscribeUpdateTransactionStatus,
+        },
+    };
+
+    fn transaction(slot: u64, signature: u8) -> Result<SubscribeUpdate, ()> {
+        Ok(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                transaction: Some(SubscribeUpdateTransactionInfo {
+                    signature: vec![signature; 64],
+                    ..Default::default()
+                }),
+                slot,
+                invocations: vec![],
+            })),
+            ..Default::default()
+        })
+    }
+
+    fn tx_status(slot: u64, signature: u8) -> Result<SubscribeUpdate, ()> {
+        Ok(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::TransactionStatus(
+                SubscribeUpdateTransactionStatus {
+                    slot,
+                    signature: vec![signature; 64],
+                    ..Default::default()
+                },
+            )),
+            ..Default::default()
+        })
+    }
+
+    fn slot(slot: u64, status: SlotStatus) -> Result<SubscribeUpdate, ()> {
+        Ok(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                status: status as i32,
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+    }
+
+    fn upgrade(slot: u64, signature: u8, commitment: CommitmentLevel) -> DedupUpdate {
+        DedupUpdate::CommitmentUpgrade {
+            slot,
+            signature: vec![signature; 64],
+            commitment,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dedup() {
+        let updates = vec![
+            transaction(1, 1),
+            tx_status(1, 1),
+            transaction(1, 2),
+            transaction(2, 3),
+            slot(1, SlotStatus::SlotProcessed),
+            transaction(1, 1),
+            slot(1, SlotStatus::SlotConfirmed),
+            slot(1, SlotStatus::SlotConfirmed),
+            slot(2, SlotStatus::SlotDead),
+            transaction(1, 2),
+            slot(1, SlotStatus::SlotFinalized),
+            // late duplicate from finalized subscription
+            transaction(1, 1),
+        ];
+        let expected = vec![
+            DedupUpdate::Update(Box::new(transaction(1, 1).unwrap())),
+            DedupUpdate::Update(Box::new(tx_status(1, 1).unwrap())),
+            DedupUpdate::Update(Box::new(transaction(1, 2).unwrap())),
+            DedupUpdate::Update(Box::new(transaction(2, 3).unwrap())),
+            DedupUpdate::Update(Box::new(slot(1, SlotStatus::SlotProcessed).unwrap())),
+            DedupUpdate::Update(Box::new(slot(1, SlotStatus::SlotConfirmed).unwrap())),
+            upgrade(1, 1, CommitmentLevel::Confirmed),
+            upgrade(1, 2, CommitmentLevel::Confirmed),
+            DedupUpdate::Update(Box::new(slot(1, SlotStatus::SlotConfirmed).unwrap())),
+            DedupUpdate::Update(Box::new(slot(2, SlotStatus::SlotDead).unwrap())),
+            DedupUpdate::Update(Box::new(slot(1, SlotStatus::SlotFinalized).unwrap())),
+            upgrade(1, 1, CommitmentLevel::Finalized),
+            upgrade(1, 2, CommitmentLevel::Finalized),
+        ];
+        let received = dedup_transactions(
+            stream::iter(updates),
+            TransactionDedup::RETAIN_SLOTS_DEFAULT,
+        )
+        .map(Result::unwrap)
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn test_retain_slots() {
+        let mut dedup = TransactionDedup::new(2);
+        for slot in 1..=5 {
+            dedup.push(transaction(slot, slot as u8).unwrap());
+        }
+        dedup.push(slot(5, SlotStatus::SlotFinalized).unwrap());
+        assert_eq!(dedup.len(), 3);
+        assert_eq!(dedup.push(transaction(1, 1).unwrap()).len(), 1);
+        assert!(dedup.push(transaction(3, 3).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_retain_slots_highest() {
+        let lag = TransactionDedup::FINALIZATION_LAG_MAX;
+        let mut dedup = TransactionDedup::new(2);
+        for slot in 1..=5 {
+            dedup.push(transaction(slot, slot as u8).unwrap());
+        }
+        assert_eq!(dedup.len(), 5);
+
+        // without finalized slot updates
+        dedup.push(transaction(lag + 5, 6).unwrap());
+        assert_eq!(dedup.len(), 4);
+        assert!(dedup.push(transaction(3, 3).unwrap()).is_empty());
+        assert_eq!(dedup.push(transaction(2, 2).unwrap()).len(), 1);
+
+        // slot status updates move the highest slot too
+        dedup.push(slot(lag + 7, SlotStatus::SlotProcessed).unwrap());
+        assert_eq!(dedup.len(), 2);
+    }
+}
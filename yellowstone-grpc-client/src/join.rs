@@ -0,0 +1,255 @@
+//! Correlation of account updates with transactions caused them.
+//!
+//! Account updates and transactions are buffered by slot and released on the
+//! first slot status update (processed, confirmed or finalized) of the slot,
+//! dead slots are dropped. With confirmed and finalized commitment server sends
+//! only the write with the highest `write_version` of the account in the slot,
+//! so every account is joined with the last transaction modified it.
+
+use {
+    futures::{
+        future,
+        stream::{Stream, StreamExt},
+    },
+    std::collections::{BTreeMap, HashMap},
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, SlotStatus, SubscribeUpdate, SubscribeUpdateAccountInfo,
+        SubscribeUpdateTransactionInfo,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionAccounts {
+    pub signature: Vec<u8>,
+    /// `None` if transaction is not matched by the transactions filters
+    pub transaction: Option<SubscribeUpdateTransactionInfo>,
+    /// Ordered by `write_version`
+    pub accounts: Vec<SubscribeUpdateAccountInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotJoin {
+    pub slot: u64,
+    /// Ordered by transaction index, transactions without accounts are included,
+    /// accounts without received transaction are at the end
+    pub transactions: Vec<TransactionAccounts>,
+    /// Account updates without `txn_signature`
+    pub accounts: Vec<SubscribeUpdateAccountInfo>,
+}
+
+#[derive(Debug, Default)]
+struct SlotState {
+    transactions: HashMap<Vec<u8>, TransactionAccounts>,
+    accounts: Vec<SubscribeUpdateAccountInfo>,
+}
+
+impl SlotState {
+    fn entry(&mut self, signature: Vec<u8>) -> &mut TransactionAccounts {
+        self.transactions
+            .entry(signature)
+            .or_insert_with_key(|signature| TransactionAccounts {
+                signature: signature.clone(),
+                transaction: None,
+                accounts: Vec::new(),
+            })
+    }
+
+    fn into_join(self, slot: u64) -> SlotJoin {
+        let mut transactions = self
+            .transactions
+            .into_values()
+            .map(|mut tx| {
+                tx.accounts.sort_by_key(|account| account.write_version);
+                tx
+            })
+            .collect::<Vec<_>>();
+        transactions.sort_by(|a, b| {
+            let key = |tx: &TransactionAccounts| {
+                tx.transaction
+                    .as_ref()
+                    .map_or(u64::MAX, |transaction| transaction.index)
+            };
+            key(a)
+                .cmp(&key(b))
+                .then_with(|| a.signature.cmp(&b.signature))
+        });
+
+        let mut accounts = self.accounts;
+        accounts.sort_by_key(|account| account.write_version);
+
+        SlotJoin {
+            slot,
+            transactions,
+            accounts,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TransactionJoin {
+    slots: BTreeMap<u64, SlotState>,
+}
+
+impl TransactionJoin {
+    pub const fn new() -> Self {
+        Self {
+            slots: BTreeMap::new(),
+        }
+    }
+
+    /// Number of slots waiting for the status update
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Add update, returns joined slot on the slot status update
+    pub fn push(&mut self, update: SubscribeUpdate) -> Option<SlotJoin> {
+        match update.update_oneof? {
+            UpdateOneof::Account(msg) => {
+                let account = msg.account?;
+                let state = self.slots.entry(msg.slot).or_default();
+                match account.txn_signature.clone() {
+                    Some(signature) => state.entry(signature).accounts.push(account),
+                    None => state.accounts.push(account),
+                }
+                None
+            }
+            UpdateOneof::Transaction(msg) => {
+                let transaction = msg.transaction?;
+                let state = self.slots.entry(msg.slot).or_default();
+                let signature = transaction.signature.clone();
+                state.entry(signature).transaction = Some(transaction);
+                None
+            }
+            UpdateOneof::Slot(msg) => match SlotStatus::try_from(msg.status) {
+                Ok(
+                    SlotStatus::SlotProcessed
+                    | SlotStatus::SlotConfirmed
+                    | SlotStatus::SlotFinalized,
+                ) => self.flush(msg.slot),
+                Ok(SlotStatus::SlotDead) => {
+                    self.slots.remove(&msg.slot);
+                    None
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Release buffered updates of the slot
+    pub fn flush(&mut self, slot: u64) -> Option<SlotJoin> {
+        self.slots.remove(&slot).map(|state| state.into_join(slot))
+    }
+}
+
+/// Join account updates and transactions of the stream, other updates are dropped
+pub fn join_transactions<S, E>(stream: S) -> impl Stream<Item = Result<SlotJoin, E>>
+where
+    S: Stream<Item = Result<SubscribeUpdate, E>>,
+{
+    let mut join = TransactionJoin::new();
+    stream.filter_map(move |message| {
+        future::ready(match message {
+            Ok(update) => join.push(update).map(Ok),
+            Err(error) => Some(Err(error)),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::join_transactions,
+        futures::{stream, StreamExt},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SlotStatus, SubscribeUpdate, SubscribeUpdateAccount,
+            SubscribeUpdateAccountInfo, SubscribeUpdateSlot, SubscribeUpdateTransaction,
+            SubscribeUpdateTransactionInfo,
+        },
+    };
+
+    fn account(
+        slot: u64,
+        write_version: u64,
+        signature: Option<u8>,
+    ) -> Result<SubscribeUpdate, ()> {
+        Ok(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    write_version,
+                    txn_signature: signature.map(|value| vec![value; 64]),
+                    ..Default::default()
+                }),
+                slot,
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+    }
+
+    fn transaction(slot: u64, index: u64, signature: u8) -> Result<SubscribeUpdate, ()> {
+        Ok(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                transaction: Some(SubscribeUpdateTransactionInfo {
+                    signature: vec![signature; 64],
+                    index,
+                    ..Default::default()
+                }),
+                slot,
+                invocations: vec![],
+            })),
+            ..Default::default()
+        })
+    }
+
+    fn slot(slot: u64, status: SlotStatus) -> Result<SubscribeUpdate, ()> {
+        Ok(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                status: status as i32,
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_join() {
+        let updates = vec![
+            account(1, 3, Some(2)),
+            account(1, 1, Some(2)),
+            account(1, 2, None),
+            account(2, 4, Some(3)),
+            transaction(1, 5, 2),
+            transaction(1, 0, 1),
+            slot(2, SlotStatus::SlotDead),
+            slot(1, SlotStatus::SlotConfirmed),
+            slot(1, SlotStatus::SlotFinalized),
+        ];
+        let joins = join_transactions(stream::iter(updates))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(joins.len(), 1);
+
+        let join = joins[0].as_ref().unwrap();
+        assert_eq!(join.slot, 1);
+        assert_eq!(join.accounts.len(), 1);
+        assert_eq!(join.transactions.len(), 2);
+        assert_eq!(join.transactions[0].signature, vec![1; 64]);
+        assert!(join.transactions[0].accounts.is_empty());
+        assert_eq!(join.transactions[1].signature, vec![2; 64]);
+        assert_eq!(
+            join.transactions[1]
+                .accounts
+                .iter()
+                .map(|account| account.write_version)
+                .collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+}
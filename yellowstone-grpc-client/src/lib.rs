@@ -1,12 +1,52 @@
+#[cfg(feature = "alt")]
+pub mod alt;
+#[cfg(feature = "anchor")]
+pub mod anchor;
+#[cfg(feature = "backfill")]
+pub mod backfill;
+pub mod cache;
+pub mod dedup;
+pub mod join;
+#[cfg(feature = "managed")]
+pub mod managed;
+#[cfg(feature = "multi")]
+pub mod multi;
+#[cfg(feature = "oracle")]
+pub mod oracle;
+pub mod parallel;
+#[cfg(feature = "pda")]
+pub mod pda;
+pub mod presets;
+pub mod reorder;
+#[cfg(feature = "router")]
+pub mod router;
+pub mod sequence;
+#[cfg(feature = "shm")]
+pub mod shm;
+#[cfg(feature = "sink")]
+pub mod sink;
+pub mod split;
+#[cfg(feature = "spool")]
+pub mod spool;
+#[cfg(feature = "staking")]
+pub mod staking;
+pub mod subscription;
+#[cfg(feature = "transfers")]
+pub mod transfers;
+pub mod transform;
+#[cfg(feature = "wallets")]
+pub mod wallets;
+
 pub use tonic::{service::Interceptor, transport::ClientTlsConfig};
 use {
+    crate::{cache::ChainStateCache, subscription::Subscription},
     bytes::Bytes,
     futures::{
         channel::mpsc,
         sink::{Sink, SinkExt},
         stream::Stream,
     },
-    std::time::Duration,
+    std::{fmt, time::Duration},
     tonic::{
         codec::{CompressionEncoding, Streaming},
         metadata::{errors::InvalidMetadataValue, AsciiMetadataValue, MetadataValue},
@@ -15,24 +55,55 @@ use {
         Request, Response, Status,
     },
     tonic_health::pb::{health_client::HealthClient, HealthCheckRequest, HealthCheckResponse},
-    yellowstone_grpc_proto::prelude::{
-        geyser_client::GeyserClient, CommitmentLevel, GetBlockHeightRequest,
-        GetBlockHeightResponse, GetLatestBlockhashRequest, GetLatestBlockhashResponse,
-        GetSlotRequest, GetSlotResponse, GetVersionRequest, GetVersionResponse,
-        IsBlockhashValidRequest, IsBlockhashValidResponse, PingRequest, PongResponse,
-        SubscribeReplayInfoRequest, SubscribeReplayInfoResponse, SubscribeRequest, SubscribeUpdate,
+    yellowstone_grpc_proto::{
+        prelude::{
+            geyser_client::GeyserClient, CommitmentLevel, ExplainFiltersRequest,
+            ExplainFiltersResponse, GetAccountRequest, GetAccountResponse, GetBlockHeightRequest,
+            GetBlockHeightResponse, GetLatestBlockhashRequest, GetLatestBlockhashResponse,
+            GetSlotRequest, GetSlotResponse, GetSlotWatermarksRequest, GetSlotWatermarksResponse,
+            GetVersionCluster, GetVersionFeatures, GetVersionRequest, GetVersionResponse,
+            IsBlockhashValidRequest, IsBlockhashValidResponse, PingRequest, PongResponse,
+            SnapshotDownloadRequest, SnapshotDownloadResponse, SubscribeReplayInfoRequest,
+            SubscribeReplayInfoResponse, SubscribeRequest, SubscribeUpdate,
+        },
+        status::{parse_status, ErrorInfo, StatusReason},
     },
 };
 
+/// `x-token` value, never printed with `Debug`
+#[derive(Clone, PartialEq, Eq)]
+pub struct XToken(AsciiMetadataValue);
+
+impl fmt::Debug for XToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("XToken(<redacted>)")
+    }
+}
+
+impl XToken {
+    pub fn new<T>(value: T) -> Result<Self, InvalidMetadataValue>
+    where
+        T: TryInto<AsciiMetadataValue, Error = InvalidMetadataValue>,
+    {
+        value.try_into().map(Self)
+    }
+
+    /// Explicit access to the token for operational tooling, avoid logging it
+    pub fn expose_secret(&self) -> &str {
+        self.0.to_str().expect("ascii value")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct InterceptorXToken {
-    pub x_token: Option<AsciiMetadataValue>,
+    pub x_token: Option<XToken>,
     pub x_request_snapshot: bool,
+    pub x_subscription_id: Option<AsciiMetadataValue>,
 }
 
 impl Interceptor for InterceptorXToken {
     fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
-        if let Some(x_token) = self.x_token.clone() {
+        if let Some(XToken(x_token)) = self.x_token.clone() {
             request.metadata_mut().insert("x-token", x_token);
         }
         if self.x_request_snapshot {
@@ -40,6 +111,11 @@ impl Interceptor for InterceptorXToken {
                 .metadata_mut()
                 .insert("x-request-snapshot", MetadataValue::from_static("true"));
         }
+        if let Some(x_subscription_id) = self.x_subscription_id.clone() {
+            request
+                .metadata_mut()
+                .insert("x-subscription-id", x_subscription_id);
+        }
         Ok(request)
     }
 }
@@ -50,6 +126,22 @@ pub enum GeyserGrpcClientError {
     TonicStatus(#[from] Status),
     #[error("Failed to send subscribe request: {0}")]
     SubscribeSendError(#[from] mpsc::SendError),
+    #[error("Unexpected validator or cluster, expected: {expected:?}, received: {received:?}")]
+    ClusterMismatch {
+        expected: Box<GetVersionCluster>,
+        received: Option<Box<GetVersionCluster>>,
+    },
+}
+
+impl GeyserGrpcClientError {
+    /// Reason and error info if the error is a status with known reason, see
+    /// `yellowstone_grpc_proto::status` for the mapping to status codes
+    pub fn status_reason(&self) -> Option<(StatusReason, ErrorInfo)> {
+        match self {
+            Self::TonicStatus(status) => parse_status(status),
+            _ => None,
+        }
+    }
 }
 
 pub type GeyserGrpcClientResult<T> = Result<T, GeyserGrpcClientError>;
@@ -57,6 +149,10 @@ pub type GeyserGrpcClientResult<T> = Result<T, GeyserGrpcClientError>;
 pub struct GeyserGrpcClient<F> {
     pub health: HealthClient<InterceptedService<Channel, F>>,
     pub geyser: GeyserClient<InterceptedService<Channel, F>>,
+    /// Clients on dedicated connections for unary calls, used round-robin,
+    /// unary calls go through `geyser` if empty
+    pub unary_pool: Vec<GeyserClient<InterceptedService<Channel, F>>>,
+    unary_pool_next: usize,
 }
 
 impl GeyserGrpcClient<()> {
@@ -76,7 +172,34 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         health: HealthClient<InterceptedService<Channel, F>>,
         geyser: GeyserClient<InterceptedService<Channel, F>>,
     ) -> Self {
-        Self { health, geyser }
+        Self {
+            health,
+            geyser,
+            unary_pool: Vec::new(),
+            unary_pool_next: 0,
+        }
+    }
+
+    /// Send unary calls over the pool instead of the streaming connection
+    pub fn with_unary_pool(
+        self,
+        unary_pool: Vec<GeyserClient<InterceptedService<Channel, F>>>,
+    ) -> Self {
+        Self {
+            unary_pool,
+            unary_pool_next: 0,
+            ..self
+        }
+    }
+
+    // Next client from the pool or streaming client if pool is empty
+    fn unary(&mut self) -> &mut GeyserClient<InterceptedService<Channel, F>> {
+        if self.unary_pool.is_empty() {
+            return &mut self.geyser;
+        }
+        let index = self.unary_pool_next % self.unary_pool.len();
+        self.unary_pool_next = index + 1;
+        &mut self.unary_pool[index]
     }
 
     // Health
@@ -127,6 +250,51 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         Ok((subscribe_tx, response.into_inner()))
     }
 
+    /// Same as [`Self::subscribe_with_request`] with bounded channel of
+    /// requests: sink is not ready while `capacity` requests (plus one per
+    /// sender) are not sent to the server, so producer of requests is slowed
+    /// down by the connection instead of growing memory. `SendError` is
+    /// returned only when the subscription is closed. Buffering of updates is
+    /// tuned with `buffer_size` and `initial_stream_window_size` of the builder.
+    pub async fn subscribe_with_capacity(
+        &mut self,
+        capacity: usize,
+        request: Option<SubscribeRequest>,
+    ) -> GeyserGrpcClientResult<(
+        impl Sink<SubscribeRequest, Error = mpsc::SendError>,
+        impl Stream<Item = Result<SubscribeUpdate, Status>>,
+    )> {
+        let (mut subscribe_tx, subscribe_rx) = mpsc::channel(capacity);
+        if let Some(request) = request {
+            subscribe_tx
+                .send(request)
+                .await
+                .map_err(GeyserGrpcClientError::SubscribeSendError)?;
+        }
+        let response: Response<Streaming<SubscribeUpdate>> =
+            self.geyser.subscribe(subscribe_rx).await?;
+        Ok((subscribe_tx, response.into_inner()))
+    }
+
+    /// Subscribe with the handle to update filters with acknowledgement from
+    /// the server, see [`Subscription::update_filter`]. Initial request is sent
+    /// without waiting for acknowledgement.
+    pub async fn subscribe_with_handle(
+        &mut self,
+        request: SubscribeRequest,
+    ) -> GeyserGrpcClientResult<(
+        Subscription,
+        impl Stream<Item = Result<SubscribeUpdate, Status>>,
+    )> {
+        let (subscribe_tx, subscribe_rx) = mpsc::unbounded();
+        let response: Response<Streaming<SubscribeUpdate>> =
+            self.geyser.subscribe(subscribe_rx).await?;
+        let (mut subscription, stream) = Subscription::new(subscribe_tx, response.into_inner());
+        // acknowledgement of the initial request is delivered by the stream
+        drop(subscription.update_filter(request));
+        Ok((subscription, stream))
+    }
+
     pub async fn subscribe_once(
         &mut self,
         request: SubscribeRequest,
@@ -160,7 +328,7 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         let request = tonic::Request::new(GetLatestBlockhashRequest {
             commitment: commitment.map(|value| value as i32),
         });
-        let response = self.geyser.get_latest_blockhash(request).await?;
+        let response = self.unary().get_latest_blockhash(request).await?;
         Ok(response.into_inner())
     }
 
@@ -171,7 +339,7 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         let request = tonic::Request::new(GetBlockHeightRequest {
             commitment: commitment.map(|value| value as i32),
         });
-        let response = self.geyser.get_block_height(request).await?;
+        let response = self.unary().get_block_height(request).await?;
         Ok(response.into_inner())
     }
 
@@ -182,7 +350,47 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         let request = tonic::Request::new(GetSlotRequest {
             commitment: commitment.map(|value| value as i32),
         });
-        let response = self.geyser.get_slot(request).await?;
+        let response = self.unary().get_slot(request).await?;
+        Ok(response.into_inner())
+    }
+
+    /// Answer from the cache if possible, response of unary request is cached
+    pub async fn get_latest_blockhash_cached(
+        &mut self,
+        cache: &ChainStateCache,
+        commitment: Option<CommitmentLevel>,
+    ) -> GeyserGrpcClientResult<GetLatestBlockhashResponse> {
+        let commitment = commitment.unwrap_or(CommitmentLevel::Processed);
+        if let Some(response) = cache.get_latest_blockhash(commitment) {
+            return Ok(response);
+        }
+        let response = self.get_latest_blockhash(Some(commitment)).await?;
+        cache.set_latest_blockhash(commitment, &response);
+        Ok(response)
+    }
+
+    /// Answer from the cache if possible, response of unary request is cached
+    pub async fn get_slot_cached(
+        &mut self,
+        cache: &ChainStateCache,
+        commitment: Option<CommitmentLevel>,
+    ) -> GeyserGrpcClientResult<GetSlotResponse> {
+        let commitment = commitment.unwrap_or(CommitmentLevel::Processed);
+        if let Some(response) = cache.get_slot(commitment) {
+            return Ok(response);
+        }
+        let response = self.get_slot(Some(commitment)).await?;
+        cache.set_slot(commitment, &response);
+        Ok(response)
+    }
+
+    /// Latest processed, confirmed and finalized slots with the first slot
+    /// available for replay in one call
+    pub async fn get_slot_watermarks(
+        &mut self,
+    ) -> GeyserGrpcClientResult<GetSlotWatermarksResponse> {
+        let request = tonic::Request::new(GetSlotWatermarksRequest {});
+        let response = self.unary().get_slot_watermarks(request).await?;
         Ok(response.into_inner())
     }
 
@@ -195,7 +403,7 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
             blockhash,
             commitment: commitment.map(|value| value as i32),
         });
-        let response = self.geyser.is_blockhash_valid(request).await?;
+        let response = self.unary().is_blockhash_valid(request).await?;
         Ok(response.into_inner())
     }
 
@@ -204,6 +412,68 @@ impl<F: Interceptor> GeyserGrpcClient<F> {
         let response = self.geyser.get_version(request).await?;
         Ok(response.into_inner())
     }
+
+    /// Features supported by the server, empty if server does not report them
+    pub async fn get_features(&mut self) -> GeyserGrpcClientResult<GetVersionFeatures> {
+        let response = self.get_version().await?;
+        Ok(response.features.unwrap_or_default())
+    }
+
+    /// Check that server is connected to the expected validator and cluster,
+    /// only non-empty fields of `expected` are compared
+    pub async fn verify_cluster(
+        &mut self,
+        expected: GetVersionCluster,
+    ) -> GeyserGrpcClientResult<GetVersionCluster> {
+        let response = self.get_version().await?;
+        match response.cluster {
+            Some(cluster) if cluster.matches(&expected) => Ok(cluster),
+            received => Err(GeyserGrpcClientError::ClusterMismatch {
+                expected: Box::new(expected),
+                received: received.map(Box::new),
+            }),
+        }
+    }
+
+    /// Accounts state at the finalized slot in chunks, interrupted download can
+    /// be resumed with `slot` and `cursor` of the last received chunk
+    pub async fn snapshot_download(
+        &mut self,
+        request: SnapshotDownloadRequest,
+    ) -> GeyserGrpcClientResult<Streaming<SnapshotDownloadResponse>> {
+        let response = self.geyser.snapshot_download(request).await?;
+        Ok(response.into_inner())
+    }
+
+    /// Account from the server cache at the last slot with requested commitment,
+    /// only accounts configured on the server are available
+    pub async fn get_account(
+        &mut self,
+        pubkey: String,
+        commitment: Option<CommitmentLevel>,
+    ) -> GeyserGrpcClientResult<GetAccountResponse> {
+        let request = tonic::Request::new(GetAccountRequest {
+            pubkey,
+            commitment: commitment.map(|value| value as i32),
+        });
+        let response = self.geyser.get_account(request).await?;
+        Ok(response.into_inner())
+    }
+
+    /// Explain which filters of the request match the sample update and why
+    /// other filters do not
+    pub async fn explain_filters(
+        &mut self,
+        request: SubscribeRequest,
+        update: SubscribeUpdate,
+    ) -> GeyserGrpcClientResult<ExplainFiltersResponse> {
+        let request = tonic::Request::new(ExplainFiltersRequest {
+            request: Some(request),
+            update: Some(update),
+        });
+        let response = self.geyser.explain_filters(request).await?;
+        Ok(response.into_inner())
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -216,15 +486,17 @@ pub enum GeyserGrpcBuilderError {
 
 pub type GeyserGrpcBuilderResult<T> = Result<T, GeyserGrpcBuilderError>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GeyserGrpcBuilder {
     pub endpoint: Endpoint,
-    pub x_token: Option<AsciiMetadataValue>,
+    pub x_token: Option<XToken>,
     pub x_request_snapshot: bool,
+    pub x_subscription_id: Option<AsciiMetadataValue>,
     pub send_compressed: Option<CompressionEncoding>,
     pub accept_compressed: Option<CompressionEncoding>,
     pub max_decoding_message_size: Option<usize>,
     pub max_encoding_message_size: Option<usize>,
+    pub unary_pool_size: usize,
 }
 
 impl GeyserGrpcBuilder {
@@ -234,10 +506,12 @@ impl GeyserGrpcBuilder {
             endpoint,
             x_token: None,
             x_request_snapshot: false,
+            x_subscription_id: None,
             send_compressed: None,
             accept_compressed: None,
             max_decoding_message_size: None,
             max_encoding_message_size: None,
+            unary_pool_size: 0,
         }
     }
 
@@ -253,13 +527,32 @@ impl GeyserGrpcBuilder {
     fn build(
         self,
         channel: Channel,
+        unary_pool: Vec<Channel>,
     ) -> GeyserGrpcBuilderResult<GeyserGrpcClient<impl Interceptor>> {
         let interceptor = InterceptorXToken {
-            x_token: self.x_token,
+            x_token: self.x_token.clone(),
             x_request_snapshot: self.x_request_snapshot,
+            x_subscription_id: self.x_subscription_id.clone(),
         };
 
-        let mut geyser = GeyserClient::with_interceptor(channel.clone(), interceptor.clone());
+        let geyser = self.build_geyser(channel.clone(), interceptor.clone());
+        let unary_pool = unary_pool
+            .into_iter()
+            .map(|channel| self.build_geyser(channel, interceptor.clone()))
+            .collect();
+
+        Ok(
+            GeyserGrpcClient::new(HealthClient::with_interceptor(channel, interceptor), geyser)
+                .with_unary_pool(unary_pool),
+        )
+    }
+
+    fn build_geyser<F: Interceptor>(
+        &self,
+        channel: Channel,
+        interceptor: F,
+    ) -> GeyserClient<InterceptedService<Channel, F>> {
+        let mut geyser = GeyserClient::with_interceptor(channel, interceptor);
         if let Some(encoding) = self.send_compressed {
             geyser = geyser.send_compressed(encoding);
         }
@@ -272,21 +565,24 @@ impl GeyserGrpcBuilder {
         if let Some(limit) = self.max_encoding_message_size {
             geyser = geyser.max_encoding_message_size(limit);
         }
-
-        Ok(GeyserGrpcClient::new(
-            HealthClient::with_interceptor(channel, interceptor),
-            geyser,
-        ))
+        geyser
     }
 
     pub async fn connect(self) -> GeyserGrpcBuilderResult<GeyserGrpcClient<impl Interceptor>> {
         let channel = self.endpoint.connect().await?;
-        self.build(channel)
+        let mut unary_pool = Vec::with_capacity(self.unary_pool_size);
+        for _ in 0..self.unary_pool_size {
+            unary_pool.push(self.endpoint.connect().await?);
+        }
+        self.build(channel, unary_pool)
     }
 
     pub fn connect_lazy(self) -> GeyserGrpcBuilderResult<GeyserGrpcClient<impl Interceptor>> {
         let channel = self.endpoint.connect_lazy();
-        self.build(channel)
+        let unary_pool = (0..self.unary_pool_size)
+            .map(|_| self.endpoint.connect_lazy())
+            .collect();
+        self.build(channel, unary_pool)
     }
 
     // Set x-token
@@ -295,7 +591,7 @@ impl GeyserGrpcBuilder {
         T: TryInto<AsciiMetadataValue, Error = InvalidMetadataValue>,
     {
         Ok(Self {
-            x_token: x_token.map(|x_token| x_token.try_into()).transpose()?,
+            x_token: x_token.map(XToken::new).transpose()?,
             ..self
         })
     }
@@ -308,6 +604,17 @@ impl GeyserGrpcBuilder {
         }
     }
 
+    // Set `x-subscription-id`, label of the subscriptions in server metrics and logs
+    pub fn x_subscription_id<T>(self, x_subscription_id: Option<T>) -> GeyserGrpcBuilderResult<Self>
+    where
+        T: TryInto<AsciiMetadataValue, Error = InvalidMetadataValue>,
+    {
+        Ok(Self {
+            x_subscription_id: x_subscription_id.map(TryInto::try_into).transpose()?,
+            ..self
+        })
+    }
+
     // Endpoint options
     pub fn connect_timeout(self, dur: Duration) -> Self {
         Self {
@@ -421,11 +728,351 @@ impl GeyserGrpcBuilder {
             ..self
         }
     }
+
+    // Open `size` extra connections for `get_latest_blockhash`, `get_block_height`,
+    // `get_slot` and `is_blockhash_valid`, so bursts of unary calls are not queued
+    // behind the subscription on the same HTTP/2 connection
+    pub fn unary_pool_size(self, size: usize) -> Self {
+        Self {
+            unary_pool_size: size,
+            ..self
+        }
+    }
 }
 
 #[cfg(test)]
-mod tests {
-    use super::GeyserGrpcClient;
+pub(crate) mod tests {
+    use {
+        super::{GeyserGrpcClient, GeyserGrpcClientError, InterceptorXToken},
+        futures::stream::{self, BoxStream, StreamExt},
+        tokio::net::TcpListener,
+        tokio_stream::wrappers::TcpListenerStream,
+        tonic::{
+            codec::CompressionEncoding, service::Interceptor, transport::Server, Request, Response,
+            Status,
+        },
+        yellowstone_grpc_proto::{
+            fixtures::UpdateGenerator,
+            prelude::{
+                subscribe_update::UpdateOneof, ExplainFiltersRequest, ExplainFiltersResponse,
+                GetAccountRequest, GetAccountResponse, GetBlockHeightRequest,
+                GetBlockHeightResponse, GetLatestBlockhashRequest, GetLatestBlockhashResponse,
+                GetSlotRequest, GetSlotResponse, GetSlotWatermarksRequest,
+                GetSlotWatermarksResponse, GetVersionCluster, GetVersionFeatures,
+                GetVersionRequest, GetVersionResponse, Geyser, GeyserServer,
+                IsBlockhashValidRequest, IsBlockhashValidResponse, PingRequest, PongResponse,
+                SnapshotDownloadRequest, SnapshotDownloadResponse, SubscribeReplayInfoRequest,
+                SubscribeReplayInfoResponse, SubscribeRequest, SubscribeUpdate,
+                SubscribeUpdateFilterApplied,
+            },
+            status::StatusReason,
+        },
+    };
+
+    /// Server which sends predefined updates on subscribe
+    #[derive(Default)]
+    pub(crate) struct MockGeyser {
+        pub(crate) updates: Vec<SubscribeUpdate>,
+        pub(crate) cluster: Option<GetVersionCluster>,
+        pub(crate) slot: Option<u64>,
+        /// Acknowledge requests with `request_id` after predefined updates
+        pub(crate) filter_applied: bool,
+        /// Replay is supported from the slot, but every `from_slot` is
+        /// rejected with `SLOT_NOT_AVAILABLE`
+        pub(crate) replay_first_available: Option<u64>,
+    }
+
+    impl MockGeyser {
+        /// Serve on random port, returns endpoint
+        pub(crate) async fn spawn(self) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let endpoint = format!("http://{}", listener.local_addr().unwrap());
+            let service = GeyserServer::new(self)
+                .send_compressed(CompressionEncoding::Zstd)
+                .accept_compressed(CompressionEncoding::Zstd);
+            tokio::spawn(
+                Server::builder()
+                    .add_service(service)
+                    .serve_with_incoming(TcpListenerStream::new(listener)),
+            );
+            endpoint
+        }
+    }
+
+    /// Endpoint without listener, connection is refused
+    pub(crate) async fn closed_endpoint() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    #[tonic::async_trait]
+    impl Geyser for MockGeyser {
+        type SubscribeStream = BoxStream<'static, Result<SubscribeUpdate, Status>>;
+
+        async fn subscribe(
+            &self,
+            request: Request<tonic::Streaming<SubscribeRequest>>,
+        ) -> Result<Response<Self::SubscribeStream>, Status> {
+            let mut request = request.into_inner();
+            if self.replay_first_available.is_some() {
+                let first = request.message().await?.unwrap_or_default();
+                if first.from_slot.is_some() {
+                    let status = StatusReason::SlotNotAvailable.status("evicted");
+                    return Ok(Response::new(stream::iter([Err(status)]).boxed()));
+                }
+            }
+
+            let updates = stream::iter(self.updates.clone().into_iter().map(Ok));
+            if !self.filter_applied {
+                return Ok(Response::new(updates.boxed()));
+            }
+            let acks = request.filter_map(|request| async move {
+                let request_id = request.ok()?.request_id?;
+                Some(Ok(SubscribeUpdate {
+                    update_oneof: Some(UpdateOneof::FilterApplied(SubscribeUpdateFilterApplied {
+                        request_id,
+                    })),
+                    ..Default::default()
+                }))
+            });
+            Ok(Response::new(updates.chain(acks).boxed()))
+        }
+
+        async fn subscribe_replay_info(
+            &self,
+            _request: Request<SubscribeReplayInfoRequest>,
+        ) -> Result<Response<SubscribeReplayInfoResponse>, Status> {
+            match self.replay_first_available {
+                Some(slot) => Ok(Response::new(SubscribeReplayInfoResponse {
+                    first_available: Some(slot),
+                })),
+                None => Err(Status::unimplemented("")),
+            }
+        }
+
+        async fn ping(
+            &self,
+            _request: Request<PingRequest>,
+        ) -> Result<Response<PongResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn get_latest_blockhash(
+            &self,
+            _request: Request<GetLatestBlockhashRequest>,
+        ) -> Result<Response<GetLatestBlockhashResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn get_block_height(
+            &self,
+            _request: Request<GetBlockHeightRequest>,
+        ) -> Result<Response<GetBlockHeightResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn get_slot(
+            &self,
+            _request: Request<GetSlotRequest>,
+        ) -> Result<Response<GetSlotResponse>, Status> {
+            match self.slot {
+                Some(slot) => Ok(Response::new(GetSlotResponse { slot })),
+                None => Err(Status::unimplemented("")),
+            }
+        }
+
+        async fn get_slot_watermarks(
+            &self,
+            _request: Request<GetSlotWatermarksRequest>,
+        ) -> Result<Response<GetSlotWatermarksResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn is_blockhash_valid(
+            &self,
+            _request: Request<IsBlockhashValidRequest>,
+        ) -> Result<Response<IsBlockhashValidResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn get_version(
+            &self,
+            _request: Request<GetVersionRequest>,
+        ) -> Result<Response<GetVersionResponse>, Status> {
+            Ok(Response::new(GetVersionResponse {
+                version: String::new(),
+                features: self.replay_first_available.map(|_slot| GetVersionFeatures {
+                    replay_stored_slots: 100,
+                    ..Default::default()
+                }),
+                cluster: self.cluster.clone(),
+            }))
+        }
+
+        type SnapshotDownloadStream = BoxStream<'static, Result<SnapshotDownloadResponse, Status>>;
+
+        async fn snapshot_download(
+            &self,
+            _request: Request<SnapshotDownloadRequest>,
+        ) -> Result<Response<Self::SnapshotDownloadStream>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn explain_filters(
+            &self,
+            _request: Request<ExplainFiltersRequest>,
+        ) -> Result<Response<ExplainFiltersResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn get_account(
+            &self,
+            _request: Request<GetAccountRequest>,
+        ) -> Result<Response<GetAccountResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_roundtrip() {
+        let mut generator = UpdateGenerator::new(42);
+        let mut updates = (0..64).map(|_| generator.update()).collect::<Vec<_>>();
+        updates.push(SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Block(generator.block(16))),
+            created_at: None,
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        });
+
+        let endpoint = MockGeyser {
+            updates: updates.clone(),
+            ..Default::default()
+        }
+        .spawn()
+        .await;
+
+        let mut client = GeyserGrpcClient::build_from_shared(endpoint)
+            .unwrap()
+            .accept_compressed(CompressionEncoding::Zstd)
+            .connect()
+            .await
+            .unwrap();
+        let received = client
+            .subscribe_once(SubscribeRequest::default())
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(received, updates);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_with_capacity() {
+        let mut generator = UpdateGenerator::new(42);
+        let updates = (0..8).map(|_| generator.update()).collect::<Vec<_>>();
+        let endpoint = MockGeyser {
+            updates: updates.clone(),
+            ..Default::default()
+        }
+        .spawn()
+        .await;
+
+        let mut client = GeyserGrpcClient::build_from_shared(endpoint)
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let (_sink, stream) = client
+            .subscribe_with_capacity(1, Some(SubscribeRequest::default()))
+            .await
+            .unwrap();
+        let received = stream.map(Result::unwrap).collect::<Vec<_>>().await;
+        assert_eq!(received, updates);
+    }
+
+    #[tokio::test]
+    async fn test_verify_cluster() {
+        let cluster = GetVersionCluster {
+            identity: "Identity1111111111111111111111111111111111".to_owned(),
+            shred_version: 50093,
+            feature_set: 0,
+            cluster: "mainnet-beta".to_owned(),
+        };
+        let endpoint = MockGeyser {
+            cluster: Some(cluster.clone()),
+            ..Default::default()
+        }
+        .spawn()
+        .await;
+        let mut client = GeyserGrpcClient::build_from_shared(endpoint)
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+
+        let expected = GetVersionCluster {
+            shred_version: 50093,
+            ..Default::default()
+        };
+        assert_eq!(client.verify_cluster(expected).await.unwrap(), cluster);
+
+        let expected = GetVersionCluster {
+            cluster: "testnet".to_owned(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            client.verify_cluster(expected).await,
+            Err(GeyserGrpcClientError::ClusterMismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unary_pool() {
+        let endpoint = MockGeyser {
+            slot: Some(42),
+            ..Default::default()
+        }
+        .spawn()
+        .await;
+        let mut client = GeyserGrpcClient::build_from_shared(endpoint)
+            .unwrap()
+            .unary_pool_size(2)
+            .connect()
+            .await
+            .unwrap();
+        assert_eq!(client.unary_pool.len(), 2);
+
+        for _ in 0..3 {
+            assert_eq!(client.get_slot(None).await.unwrap().slot, 42);
+        }
+        assert_eq!(client.unary_pool_next, 1);
+    }
+
+    #[test]
+    fn test_interceptor_x_subscription_id() {
+        let builder = GeyserGrpcClient::build_from_static("http://127.0.0.1:10000")
+            .x_subscription_id(Some("team-a/indexer"))
+            .unwrap();
+        let mut interceptor = InterceptorXToken {
+            x_token: None,
+            x_request_snapshot: false,
+            x_subscription_id: builder.x_subscription_id,
+        };
+        let request = interceptor.call(Request::new(())).unwrap();
+        assert_eq!(
+            request.metadata().get("x-subscription-id").unwrap(),
+            "team-a/indexer"
+        );
+
+        assert!(
+            GeyserGrpcClient::build_from_static("http://127.0.0.1:10000")
+                .x_subscription_id(Some("invalid\n"))
+                .is_err()
+        );
+    }
 
     #[tokio::test]
     async fn test_channel_https_success() {
@@ -438,7 +1085,12 @@ mod tests {
         let res = res.unwrap().x_token(Some(x_token));
         assert!(res.is_ok());
 
-        let res = res.unwrap().connect_lazy();
+        let builder = res.unwrap();
+        let x_token_value = builder.x_token.as_ref().map(|value| value.expose_secret());
+        assert_eq!(x_token_value, Some(x_token));
+        assert!(!format!("{builder:?}").contains(x_token));
+
+        let res = builder.connect_lazy();
         assert!(res.is_ok());
     }
 
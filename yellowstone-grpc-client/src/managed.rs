@@ -0,0 +1,610 @@
+//! Subscription with automatic reconnect and connection lifecycle events.
+//!
+//! Connection is driven by the updates stream, events are sent to the side
+//! channel so UIs and alerting can show connection state. Reconnect delay is
+//! doubled on every failed attempt and reset after successful connect.
+//!
+//! On reconnect the client waits for the server health, detects server restart
+//! by changed version, slot regression or stream closed by the server, and
+//! resubscribes with `from_slot` if the server can replay missed updates.
+//!
+//! The request can be changed while the subscription is running, the latest
+//! request is sent to the current stream and used on every reconnect.
+
+use {
+    crate::{GeyserGrpcBuilder, GeyserGrpcClient, GeyserGrpcClientError, GeyserGrpcClientResult},
+    futures::{
+        channel::mpsc,
+        future::{self, Either},
+        sink::{Sink, SinkExt},
+        stream::{self, Stream, StreamExt},
+    },
+    std::{pin::Pin, time::Duration},
+    tonic::{service::Interceptor, Code, Status},
+    tonic_health::pb::health_check_response::ServingStatus,
+    yellowstone_grpc_proto::{
+        prelude::{CommitmentLevel, GetVersionFeatures, SubscribeRequest, SubscribeUpdate},
+        status::{parse_status, StatusReason},
+    },
+};
+
+type UpdatesStream = Pin<Box<dyn Stream<Item = Result<SubscribeUpdate, Status>> + Send>>;
+type RequestsSink = Pin<Box<dyn Sink<SubscribeRequest, Error = mpsc::SendError> + Send>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    Connecting {
+        attempt: u32,
+    },
+    Connected,
+    /// No messages from the server (including pings) within `ping_timeout`
+    PingTimeout,
+    ReconnectScheduled {
+        delay: Duration,
+        error: String,
+    },
+    GaveUp {
+        attempts: u32,
+        error: String,
+    },
+    /// Emitted after `Connected` if the server was restarted, `from_slot` is set
+    /// if missed updates are replayed by the server, otherwise slots after
+    /// `last_slot` should be backfilled
+    ServerRestarted {
+        reason: RestartReason,
+        last_slot: Option<u64>,
+        from_slot: Option<u64>,
+    },
+    /// Emitted after `Connected` if the stream was resubscribed without
+    /// `from_slot`, updates after `last_slot` could be missed
+    PossibleGap {
+        last_slot: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestartReason {
+    /// Stream was finished by the server without error, streams are closed on shutdown
+    StreamClosed,
+    /// Version reported in `GetVersion` was changed
+    VersionChanged { from: String, to: String },
+    /// Processed slot of the server is behind the last received slot
+    SlotRegression { last_slot: u64, server_slot: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ManagedError {
+    #[error("gave up after {attempts} attempts: {error}")]
+    GaveUp { attempts: u32, error: String },
+    /// Subscription was rejected with not retryable reason (limits, permissions)
+    #[error("rejected by the server ({}): {message}", reason.as_str())]
+    Rejected {
+        reason: StatusReason,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ManagedConfig {
+    pub reconnect_delay_min: Duration,
+    pub reconnect_delay_max: Duration,
+    /// Maximum number of consecutive failed attempts, `None` for unlimited
+    pub max_attempts: Option<u32>,
+    /// Server sends ping every 15 seconds
+    pub ping_timeout: Option<Duration>,
+    /// Subscribe only when health check reports `SERVING`, servers without
+    /// health service are considered healthy
+    pub wait_healthy: bool,
+    /// Resubscribe with `from_slot` set to the last received slot if the server
+    /// supports replay and the slot is available, updates of the last slot
+    /// can be received twice. If the slot is evicted before the subscription
+    /// is created (`SLOT_NOT_AVAILABLE`), the stream is resubscribed without
+    /// `from_slot` and `PossibleGap` is emitted
+    pub resubscribe_from_slot: bool,
+    /// Capacity of the events channel, events are dropped while the channel
+    /// is full
+    pub events_capacity: usize,
+}
+
+impl Default for ManagedConfig {
+    fn default() -> Self {
+        Self {
+            reconnect_delay_min: Duration::from_millis(100),
+            reconnect_delay_max: Duration::from_secs(10),
+            max_attempts: None,
+            ping_timeout: Some(Duration::from_secs(30)),
+            wait_healthy: true,
+            resubscribe_from_slot: true,
+            events_capacity: 1024,
+        }
+    }
+}
+
+impl ManagedConfig {
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.reconnect_delay_min
+            .saturating_mul(factor)
+            .min(self.reconnect_delay_max)
+    }
+}
+
+struct ManagedState {
+    builder: GeyserGrpcBuilder,
+    request: SubscribeRequest,
+    config: ManagedConfig,
+    events_tx: mpsc::Sender<ConnectionEvent>,
+    stream: Option<UpdatesStream>,
+    sink: Option<RequestsSink>,
+    /// New requests, `None` if the sender is dropped
+    requests_rx: Option<mpsc::UnboundedReceiver<SubscribeRequest>>,
+    /// Failed attempts since the last successful connect
+    attempt: u32,
+    error: Option<String>,
+    done: bool,
+    /// Highest slot of received updates
+    last_slot: Option<u64>,
+    /// Version of the server from the last connect
+    version: Option<String>,
+    stream_closed: bool,
+    /// `from_slot` was rejected with `SLOT_NOT_AVAILABLE`, not used on the
+    /// next subscribe
+    slot_not_available: bool,
+}
+
+impl ManagedState {
+    fn send_event(&mut self, event: ConnectionEvent) {
+        // receiver could be dropped or not polled if events are not needed
+        let _ = self.events_tx.try_send(event);
+    }
+
+    /// Methods not implemented by the server are ignored
+    fn optional<T>(result: GeyserGrpcClientResult<T>) -> Result<Option<T>, String> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(GeyserGrpcClientError::TonicStatus(status))
+                if status.code() == Code::Unimplemented =>
+            {
+                Ok(None)
+            }
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    async fn subscribe(
+        &mut self,
+        client: &mut GeyserGrpcClient<impl Interceptor + Send + 'static>,
+    ) -> Result<(UpdatesStream, RequestsSink, Vec<ConnectionEvent>), String> {
+        if self.config.wait_healthy {
+            if let Some(response) = Self::optional(client.health_check().await)? {
+                if response.status != ServingStatus::Serving as i32 {
+                    return Err("server is not serving".to_owned());
+                }
+            }
+        }
+
+        let response = Self::optional(client.get_version().await)?;
+        let mut reason = match (&self.version, &response) {
+            (Some(from), Some(to)) if *from != to.version => Some(RestartReason::VersionChanged {
+                from: from.clone(),
+                to: to.version.clone(),
+            }),
+            _ => None,
+        };
+        if let (None, Some(last_slot)) = (&reason, self.last_slot) {
+            let server_slot =
+                Self::optional(client.get_slot(Some(CommitmentLevel::Processed)).await)?;
+            if let Some(server_slot) = server_slot.map(|response| response.slot) {
+                if server_slot < last_slot {
+                    reason = Some(RestartReason::SlotRegression {
+                        last_slot,
+                        server_slot,
+                    });
+                }
+            }
+        }
+        if reason.is_none() && self.stream_closed {
+            reason = Some(RestartReason::StreamClosed);
+        }
+        let last_slot = self.last_slot;
+        if matches!(reason, Some(RestartReason::SlotRegression { .. })) {
+            self.last_slot = None;
+        }
+
+        let mut request = self.request.clone();
+        let supports_replay = response
+            .as_ref()
+            .and_then(|response| response.features.as_ref())
+            .is_some_and(GetVersionFeatures::supports_replay);
+        if let (true, true, false, Some(last_slot)) = (
+            self.config.resubscribe_from_slot,
+            supports_replay,
+            self.slot_not_available,
+            self.last_slot,
+        ) {
+            let first_available = Self::optional(client.subscribe_replay_info().await)?
+                .and_then(|response| response.first_available);
+            if first_available.is_some_and(|first_available| first_available <= last_slot) {
+                request.from_slot = Some(last_slot);
+            }
+        }
+
+        let (sink, stream) = client
+            .subscribe_with_request(Some(request.clone()))
+            .await
+            .map_err(|error| error.to_string())?;
+        self.version = response.map(|response| response.version);
+        self.stream_closed = false;
+        self.slot_not_available = false;
+        let mut events = vec![];
+        if let Some(reason) = reason {
+            events.push(ConnectionEvent::ServerRestarted {
+                reason,
+                last_slot,
+                from_slot: request.from_slot,
+            });
+        }
+        if let (Some(last_slot), None) = (last_slot, request.from_slot) {
+            events.push(ConnectionEvent::PossibleGap { last_slot });
+        }
+        Ok((Box::pin(stream), Box::pin(sink), events))
+    }
+
+    async fn connect(&mut self) -> Result<(), ManagedError> {
+        if let Some(error) = self.error.take() {
+            if self
+                .config
+                .max_attempts
+                .is_some_and(|max_attempts| self.attempt >= max_attempts)
+            {
+                self.send_event(ConnectionEvent::GaveUp {
+                    attempts: self.attempt,
+                    error: error.clone(),
+                });
+                return Err(ManagedError::GaveUp {
+                    attempts: self.attempt,
+                    error,
+                });
+            }
+
+            let delay = self.config.delay(self.attempt);
+            self.send_event(ConnectionEvent::ReconnectScheduled { delay, error });
+            tokio::time::sleep(delay).await;
+        }
+
+        self.attempt += 1;
+        self.send_event(ConnectionEvent::Connecting {
+            attempt: self.attempt,
+        });
+        let result = match self.builder.clone().connect().await {
+            Ok(mut client) => self.subscribe(&mut client).await,
+            Err(error) => Err(error.to_string()),
+        };
+        match result {
+            Ok((stream, sink, events)) => {
+                self.send_event(ConnectionEvent::Connected);
+                for event in events {
+                    self.send_event(event);
+                }
+                self.stream = Some(stream);
+                self.sink = Some(sink);
+                self.attempt = 0;
+            }
+            Err(error) => self.error = Some(error),
+        }
+        Ok(())
+    }
+
+    /// Remember the request and send it to the current stream, the stream is
+    /// reconnected if the request can not be sent
+    async fn update_request(&mut self, request: Option<SubscribeRequest>) {
+        let Some(request) = request else {
+            self.requests_rx = None;
+            return;
+        };
+        self.request = request.clone();
+        if let (Some(_), Some(sink)) = (&self.stream, self.sink.as_mut()) {
+            if sink.send(request).await.is_err() {
+                self.stream = None;
+                self.error = Some("failed to send request".to_owned());
+            }
+        }
+    }
+
+    async fn next(&mut self) -> Option<Result<SubscribeUpdate, ManagedError>> {
+        loop {
+            if self.done {
+                return None;
+            }
+
+            let Some(stream) = self.stream.as_mut() else {
+                if let Err(error) = self.connect().await {
+                    self.done = true;
+                    return Some(Err(error));
+                }
+                continue;
+            };
+
+            let ping_timeout = self.config.ping_timeout;
+            let message = async move {
+                match ping_timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, stream.next())
+                        .await
+                        .map_err(|_elapsed| ()),
+                    None => Ok(stream.next().await),
+                }
+            };
+            let next = match self.requests_rx.as_mut() {
+                Some(requests_rx) => {
+                    match future::select(std::pin::pin!(message), requests_rx.next()).await {
+                        Either::Left((message, _)) => Ok(message),
+                        Either::Right((request, _)) => Err(request),
+                    }
+                }
+                None => Ok(message.await),
+            };
+            let message = match next {
+                Ok(message) => message,
+                Err(request) => {
+                    self.update_request(request).await;
+                    continue;
+                }
+            };
+            let Ok(message) = message else {
+                self.send_event(ConnectionEvent::PingTimeout);
+                self.stream = None;
+                self.error = Some("ping timeout".to_owned());
+                continue;
+            };
+            match message {
+                Some(Ok(update)) => {
+                    if let Some(slot) = update.identity().map(|identity| identity.slot()) {
+                        self.last_slot = Some(self.last_slot.map_or(slot, |last| last.max(slot)));
+                    }
+                    return Some(Ok(update));
+                }
+                Some(Err(status)) => {
+                    self.stream = None;
+                    if let Some((reason, _details)) = parse_status(&status) {
+                        if !reason.is_retryable() {
+                            self.done = true;
+                            return Some(Err(ManagedError::Rejected {
+                                reason,
+                                message: status.message().to_owned(),
+                            }));
+                        }
+                        self.slot_not_available = reason == StatusReason::SlotNotAvailable;
+                    }
+                    self.error = Some(status.to_string());
+                }
+                None => {
+                    self.stream = None;
+                    self.stream_closed = true;
+                    self.error = Some("stream closed by the server".to_owned());
+                }
+            }
+        }
+    }
+}
+
+impl GeyserGrpcBuilder {
+    /// Subscribe with automatic reconnect, stream is finished only when
+    /// `max_attempts` is reached
+    pub fn subscribe_managed(
+        self,
+        request: SubscribeRequest,
+        config: ManagedConfig,
+    ) -> (
+        impl Stream<Item = Result<SubscribeUpdate, ManagedError>>,
+        mpsc::Receiver<ConnectionEvent>,
+    ) {
+        let (stream, events_rx) = self.subscribe_managed_inner(request, config, None);
+        (stream, events_rx)
+    }
+
+    /// Same as [`Self::subscribe_managed`], requests sent to the returned
+    /// sender replace the current request, including after reconnects
+    pub fn subscribe_managed_with_updates(
+        self,
+        request: SubscribeRequest,
+        config: ManagedConfig,
+    ) -> (
+        impl Stream<Item = Result<SubscribeUpdate, ManagedError>>,
+        mpsc::Receiver<ConnectionEvent>,
+        mpsc::UnboundedSender<SubscribeRequest>,
+    ) {
+        let (requests_tx, requests_rx) = mpsc::unbounded();
+        let (stream, events_rx) = self.subscribe_managed_inner(request, config, Some(requests_rx));
+        (stream, events_rx, requests_tx)
+    }
+
+    fn subscribe_managed_inner(
+        self,
+        request: SubscribeRequest,
+        config: ManagedConfig,
+        requests_rx: Option<mpsc::UnboundedReceiver<SubscribeRequest>>,
+    ) -> (
+        impl Stream<Item = Result<SubscribeUpdate, ManagedError>>,
+        mpsc::Receiver<ConnectionEvent>,
+    ) {
+        let (events_tx, events_rx) = mpsc::channel(config.events_capacity);
+        let state = ManagedState {
+            builder: self,
+            request,
+            config,
+            events_tx,
+            stream: None,
+            sink: None,
+            requests_rx,
+            attempt: 0,
+            error: None,
+            done: false,
+            last_slot: None,
+            version: None,
+            stream_closed: false,
+            slot_not_available: false,
+        };
+        let stream = stream::unfold(state, |mut state| async move {
+            state.next().await.map(|item| (item, state))
+        });
+        (stream, events_rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{ConnectionEvent, ManagedConfig, ManagedError, RestartReason},
+        crate::{
+            tests::{closed_endpoint, MockGeyser},
+            GeyserGrpcClient,
+        },
+        futures::StreamExt,
+        std::time::Duration,
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SubscribeRequest, SubscribeUpdate, SubscribeUpdateSlot,
+        },
+    };
+
+    #[test]
+    fn test_delay() {
+        let config = ManagedConfig::default();
+        assert_eq!(config.delay(1), Duration::from_millis(100));
+        assert_eq!(config.delay(3), Duration::from_millis(400));
+        assert_eq!(config.delay(100), Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_gave_up() {
+        let config = ManagedConfig {
+            reconnect_delay_min: Duration::from_millis(1),
+            max_attempts: Some(2),
+            ..Default::default()
+        };
+        let (stream, events) = GeyserGrpcClient::build_from_shared(closed_endpoint().await)
+            .unwrap()
+            .subscribe_managed(SubscribeRequest::default(), config);
+
+        let items = stream.collect::<Vec<_>>().await;
+        assert!(matches!(
+            items.as_slice(),
+            [Err(ManagedError::GaveUp { attempts: 2, .. })]
+        ));
+
+        let events = events.collect::<Vec<_>>().await;
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0], ConnectionEvent::Connecting { attempt: 1 });
+        assert!(matches!(
+            events[1],
+            ConnectionEvent::ReconnectScheduled { delay, .. } if delay == Duration::from_millis(1)
+        ));
+        assert_eq!(events[2], ConnectionEvent::Connecting { attempt: 2 });
+        assert!(matches!(
+            events[3],
+            ConnectionEvent::GaveUp { attempts: 2, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_server_restarted() {
+        let update = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot: 42,
+                ..Default::default()
+            })),
+            created_at: None,
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        };
+        // stream is closed by the server after every update
+        let endpoint = MockGeyser {
+            updates: vec![update.clone()],
+            ..Default::default()
+        }
+        .spawn()
+        .await;
+
+        let config = ManagedConfig {
+            reconnect_delay_min: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let (stream, events) = GeyserGrpcClient::build_from_shared(endpoint)
+            .unwrap()
+            .subscribe_managed(SubscribeRequest::default(), config);
+
+        let items = stream.take(2).collect::<Vec<_>>().await;
+        assert_eq!(items, vec![Ok(update.clone()), Ok(update)]);
+
+        let events = events.collect::<Vec<_>>().await;
+        assert_eq!(events[0], ConnectionEvent::Connecting { attempt: 1 });
+        assert_eq!(events[1], ConnectionEvent::Connected);
+        assert!(matches!(
+            events[2],
+            ConnectionEvent::ReconnectScheduled { .. }
+        ));
+        assert_eq!(events[3], ConnectionEvent::Connecting { attempt: 1 });
+        assert_eq!(events[4], ConnectionEvent::Connected);
+        assert_eq!(
+            events[5],
+            ConnectionEvent::ServerRestarted {
+                reason: RestartReason::StreamClosed,
+                last_slot: Some(42),
+                from_slot: None,
+            }
+        );
+        assert_eq!(events[6], ConnectionEvent::PossibleGap { last_slot: 42 });
+    }
+
+    #[tokio::test]
+    async fn test_slot_not_available() {
+        let update = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot: 42,
+                ..Default::default()
+            })),
+            created_at: None,
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        };
+        // replay is reported as available, but `from_slot` is rejected
+        let endpoint = MockGeyser {
+            updates: vec![update.clone()],
+            replay_first_available: Some(0),
+            ..Default::default()
+        }
+        .spawn()
+        .await;
+
+        let config = ManagedConfig {
+            reconnect_delay_min: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let (stream, events) = GeyserGrpcClient::build_from_shared(endpoint)
+            .unwrap()
+            .subscribe_managed(SubscribeRequest::default(), config);
+
+        let items = stream.take(2).collect::<Vec<_>>().await;
+        assert_eq!(items, vec![Ok(update.clone()), Ok(update)]);
+
+        let events = events.collect::<Vec<_>>().await;
+        assert_eq!(
+            events[5],
+            ConnectionEvent::ServerRestarted {
+                reason: RestartReason::StreamClosed,
+                last_slot: Some(42),
+                from_slot: Some(42),
+            }
+        );
+        assert!(matches!(
+            &events[6],
+            ConnectionEvent::ReconnectScheduled { error, .. } if error.contains("evicted")
+        ));
+        assert_eq!(events[7], ConnectionEvent::Connecting { attempt: 1 });
+        assert_eq!(events[8], ConnectionEvent::Connected);
+        assert_eq!(events[9], ConnectionEvent::PossibleGap { last_slot: 42 });
+    }
+}
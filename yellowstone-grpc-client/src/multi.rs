@@ -0,0 +1,536 @@
+//! Subscription to multiple endpoints with deduplication of updates.
+//!
+//! Same request is sent to every endpoint with managed subscription, so every
+//! endpoint reconnects independently and stalled endpoint is detected by ping
+//! timeout. Updates are merged in order of arrival and only the first copy of
+//! every update (by [`UpdateIdentity`]) is delivered, so the consumer receives
+//! every update from the fastest endpoint and a failure of one endpoint is not
+//! visible while others are connected.
+//!
+//! Identities are kept for `retain_slots` slots before the highest received
+//! slot, updates of older slots are dropped as late duplicates. Slot batches
+//! are deduplicated by slot and frame index, slot manifests by slot, so every
+//! endpoint should use the same batch config. Pings and filter
+//! acknowledgements are not delivered.
+//!
+//! With [`MultiGeyserGrpcClient::subscribe_with_gap_reports`] every slot
+//! leaving the window is reported with number of delivered updates and updates
+//! received from every endpoint by update type, so gaps of the endpoint are
+//! visible even if the consumer received every update. Only first 64 endpoints
+//! are counted.
+
+use {
+    crate::{
+        managed::{ConnectionEvent, ManagedConfig, ManagedError},
+        GeyserGrpcBuilder,
+    },
+    futures::{
+        channel::mpsc,
+        stream::{self, Stream, StreamExt},
+    },
+    std::{
+        collections::{hash_map::Entry as HashMapEntry, BTreeMap, HashMap, VecDeque},
+        mem,
+    },
+    yellowstone_grpc_proto::{
+        identity::UpdateIdentity,
+        prelude::{subscribe_update::UpdateOneof, SubscribeRequest, SubscribeUpdate},
+    },
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct MultiConfig {
+    /// Config of subscription to every endpoint
+    pub managed: ManagedConfig,
+    pub retain_slots: u64,
+}
+
+impl Default for MultiConfig {
+    fn default() -> Self {
+        Self {
+            managed: ManagedConfig::default(),
+            retain_slots: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiUpdate {
+    /// Index of the endpoint delivered the update first
+    pub endpoint: usize,
+    pub update: SubscribeUpdate,
+}
+
+/// Connection event of the endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiEvent {
+    pub endpoint: usize,
+    pub event: ConnectionEvent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MultiError {
+    #[error("all endpoints failed: {errors:?}")]
+    AllFailed { errors: Vec<(usize, ManagedError)> },
+}
+
+/// Completeness of the slot by update type (`accounts`, `slots`,
+/// `transactions`, `transactions_status`, `blocks`, `blocks_meta`, `entries`,
+/// `slot_batches`, `slot_manifests`)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SlotGapReport {
+    pub slot: u64,
+    /// Unique updates delivered to the consumer
+    pub delivered: BTreeMap<&'static str, usize>,
+    /// Unique updates received from the endpoint, by index of the endpoint
+    pub received: Vec<BTreeMap<&'static str, usize>>,
+}
+
+impl SlotGapReport {
+    /// Updates delivered from other endpoints and not received from the endpoint
+    pub fn missing(&self, endpoint: usize) -> BTreeMap<&'static str, usize> {
+        let received = self.received.get(endpoint);
+        self.delivered
+            .iter()
+            .filter_map(|(kind, delivered)| {
+                let received = received
+                    .and_then(|received| received.get(kind))
+                    .copied()
+                    .unwrap_or_default();
+                (received < *delivered).then(|| (*kind, delivered - received))
+            })
+            .collect()
+    }
+
+    /// `true` if every endpoint received every delivered update
+    pub fn is_complete(&self) -> bool {
+        (0..self.received.len()).all(|endpoint| self.missing(endpoint).is_empty())
+    }
+}
+
+const fn identity_kind(identity: &UpdateIdentity) -> &'static str {
+    match identity {
+        UpdateIdentity::Account { .. } => "accounts",
+        UpdateIdentity::Slot { .. } => "slots",
+        UpdateIdentity::Transaction { .. } => "transactions",
+        UpdateIdentity::TransactionStatus { .. } => "transactions_status",
+        UpdateIdentity::Block { .. } => "blocks",
+        UpdateIdentity::BlockMeta { .. } => "blocks_meta",
+        UpdateIdentity::Entry { .. } => "entries",
+        UpdateIdentity::SlotBatch { .. } => "slot_batches",
+        UpdateIdentity::SlotManifest { .. } => "slot_manifests",
+    }
+}
+
+/// Identities of delivered updates by slot with endpoints received them
+#[derive(Debug)]
+pub struct MultiDedup {
+    retain_slots: u64,
+    max_slot: u64,
+    /// Bit of the endpoint is set if the update is received from it
+    slots: BTreeMap<u64, HashMap<UpdateIdentity, u64>>,
+    /// Number of endpoints in gap reports, reports are disabled if zero
+    endpoints: usize,
+    reports: VecDeque<SlotGapReport>,
+}
+
+impl MultiDedup {
+    /// Max number of endpoints counted in gap reports
+    pub const ENDPOINTS_MAX: usize = u64::BITS as usize;
+
+    pub const fn new(retain_slots: u64) -> Self {
+        Self {
+            retain_slots,
+            max_slot: 0,
+            slots: BTreeMap::new(),
+            endpoints: 0,
+            reports: VecDeque::new(),
+        }
+    }
+
+    /// Create gap report of every slot leaving the window
+    pub fn with_gap_reports(mut self, endpoints: usize) -> Self {
+        self.endpoints = endpoints.min(Self::ENDPOINTS_MAX);
+        self
+    }
+
+    /// Report of the slot in the window
+    pub fn gap_report(&self, slot: u64) -> Option<SlotGapReport> {
+        self.slots
+            .get(&slot)
+            .map(|keys| Self::create_gap_report(slot, keys, self.endpoints))
+    }
+
+    /// Next report of the slot left the window
+    pub fn pop_gap_report(&mut self) -> Option<SlotGapReport> {
+        self.reports.pop_front()
+    }
+
+    fn create_gap_report(
+        slot: u64,
+        keys: &HashMap<UpdateIdentity, u64>,
+        endpoints: usize,
+    ) -> SlotGapReport {
+        let mut report = SlotGapReport {
+            slot,
+            delivered: BTreeMap::new(),
+            received: vec![BTreeMap::new(); endpoints],
+        };
+        for (identity, mask) in keys {
+            let kind = identity_kind(identity);
+            *report.delivered.entry(kind).or_default() += 1;
+            for (endpoint, received) in report.received.iter_mut().enumerate() {
+                if mask & (1 << endpoint) != 0 {
+                    *received.entry(kind).or_default() += 1;
+                }
+            }
+        }
+        report
+    }
+
+    /// `true` if the update received from the endpoint should be delivered
+    pub fn push(&mut self, endpoint: usize, update: &SubscribeUpdate) -> bool {
+        let identity = match &update.update_oneof {
+            Some(UpdateOneof::Ping(_) | UpdateOneof::Pong(_) | UpdateOneof::FilterApplied(_)) => {
+                return false
+            }
+            _ => match update.identity() {
+                Some(identity) => identity,
+                None => return true,
+            },
+        };
+        let slot = identity.slot();
+
+        let min_slot = self.max_slot.saturating_sub(self.retain_slots);
+        if slot < min_slot {
+            return false;
+        }
+        if slot > self.max_slot {
+            self.max_slot = slot;
+            let min_slot = slot.saturating_sub(self.retain_slots);
+            let retained = self.slots.split_off(&min_slot);
+            let pruned = mem::replace(&mut self.slots, retained);
+            if self.endpoints > 0 {
+                for (slot, keys) in pruned {
+                    self.reports
+                        .push_back(Self::create_gap_report(slot, &keys, self.endpoints));
+                }
+            }
+        }
+
+        let mask = if endpoint < Self::ENDPOINTS_MAX {
+            1 << endpoint
+        } else {
+            0
+        };
+        match self.slots.entry(slot).or_default().entry(identity) {
+            HashMapEntry::Occupied(mut entry) => {
+                *entry.get_mut() |= mask;
+                false
+            }
+            HashMapEntry::Vacant(entry) => {
+                entry.insert(mask);
+                true
+            }
+        }
+    }
+}
+
+enum EndpointItem {
+    Update(Result<SubscribeUpdate, ManagedError>),
+    Event(ConnectionEvent),
+}
+
+#[derive(Debug, Clone)]
+pub struct MultiGeyserGrpcClient {
+    builders: Vec<GeyserGrpcBuilder>,
+    config: MultiConfig,
+}
+
+impl MultiGeyserGrpcClient {
+    pub fn new(builders: impl IntoIterator<Item = GeyserGrpcBuilder>) -> Self {
+        Self {
+            builders: builders.into_iter().collect(),
+            config: MultiConfig::default(),
+        }
+    }
+
+    pub const fn with_config(mut self, config: MultiConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Subscribe to every endpoint, stream is finished with error when all
+    /// endpoints are failed (`max_attempts` reached or subscription rejected)
+    pub fn subscribe(
+        self,
+        request: SubscribeRequest,
+    ) -> (
+        impl Stream<Item = Result<MultiUpdate, MultiError>>,
+        mpsc::UnboundedReceiver<MultiEvent>,
+    ) {
+        let (stream, events, _reports) = self.subscribe_inner(request, false);
+        (stream, events)
+    }
+
+    /// Same as [`MultiGeyserGrpcClient::subscribe`] with gap report of every
+    /// slot leaving the deduplication window
+    pub fn subscribe_with_gap_reports(
+        self,
+        request: SubscribeRequest,
+    ) -> (
+        impl Stream<Item = Result<MultiUpdate, MultiError>>,
+        mpsc::UnboundedReceiver<MultiEvent>,
+        mpsc::UnboundedReceiver<SlotGapReport>,
+    ) {
+        self.subscribe_inner(request, true)
+    }
+
+    fn subscribe_inner(
+        self,
+        request: SubscribeRequest,
+        gap_reports: bool,
+    ) -> (
+        impl Stream<Item = Result<MultiUpdate, MultiError>>,
+        mpsc::UnboundedReceiver<MultiEvent>,
+        mpsc::UnboundedReceiver<SlotGapReport>,
+    ) {
+        let (events_tx, events_rx) = mpsc::unbounded();
+        let (reports_tx, reports_rx) = mpsc::unbounded();
+        let mut dedup = MultiDedup::new(self.config.retain_slots);
+        if gap_reports {
+            dedup = dedup.with_gap_reports(self.builders.len());
+        }
+        let managed = self.config.managed;
+        let endpoints = self
+            .builders
+            .into_iter()
+            .enumerate()
+            .map(|(endpoint, builder)| {
+                let (updates, events) = builder.subscribe_managed(request.clone(), managed);
+                // events are finished together with updates
+                Box::pin(
+                    stream::select(
+                        updates.map(EndpointItem::Update),
+                        events.map(EndpointItem::Event),
+                    )
+                    .map(move |item| (endpoint, item)),
+                )
+            });
+
+        let state = (
+            stream::select_all(endpoints),
+            dedup,
+            vec![],
+            events_tx,
+            reports_tx,
+        );
+        let stream = stream::unfold(Some(state), |state| async move {
+            let (mut endpoints, mut dedup, mut errors, events_tx, reports_tx) = state?;
+            loop {
+                match endpoints.next().await {
+                    Some((endpoint, EndpointItem::Update(Ok(update)))) => {
+                        let deliver = dedup.push(endpoint, &update);
+                        while let Some(report) = dedup.pop_gap_report() {
+                            // receiver could be dropped if reports are not needed
+                            let _ = reports_tx.unbounded_send(report);
+                        }
+                        if deliver {
+                            let item = Ok(MultiUpdate { endpoint, update });
+                            let state = (endpoints, dedup, errors, events_tx, reports_tx);
+                            return Some((item, Some(state)));
+                        }
+                    }
+                    Some((endpoint, EndpointItem::Update(Err(error)))) => {
+                        errors.push((endpoint, error));
+                    }
+                    Some((endpoint, EndpointItem::Event(event))) => {
+                        // receiver could be dropped if events are not needed
+                        let _ = events_tx.unbounded_send(MultiEvent { endpoint, event });
+                    }
+                    None if errors.is_empty() => return None,
+                    None => return Some((Err(MultiError::AllFailed { errors }), None)),
+                }
+            }
+        });
+        (stream, events_rx, reports_rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{MultiConfig, MultiDedup, MultiGeyserGrpcClient, SlotGapReport},
+        crate::{
+            managed::ManagedConfig,
+            tests::{closed_endpoint, MockGeyser},
+            GeyserGrpcClient,
+        },
+        futures::StreamExt,
+        std::{collections::BTreeMap, time::Duration},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SubscribeRequest, SubscribeUpdate,
+            SubscribeUpdateAccount, SubscribeUpdateAccountInfo, SubscribeUpdateFilterApplied,
+            SubscribeUpdatePing, SubscribeUpdateSlot, SubscribeUpdateSlotBatch,
+            SubscribeUpdateSlotManifest,
+        },
+    };
+
+    fn slot(slot: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut dedup = MultiDedup::new(10);
+        assert!(dedup.push(0, &slot(100)));
+        assert!(!dedup.push(0, &slot(100)));
+        assert!(dedup.push(0, &slot(95)));
+        assert!(dedup.push(0, &slot(120)));
+        // out of the window
+        assert!(!dedup.push(0, &slot(105)));
+        assert_eq!(dedup.slots.len(), 1);
+
+        let ping = SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Ping(SubscribeUpdatePing {})),
+            ..Default::default()
+        };
+        assert!(!dedup.push(0, &ping));
+    }
+
+    #[test]
+    fn test_dedup_batches() {
+        let batch = |slot, index| SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::SlotBatch(SubscribeUpdateSlotBatch {
+                slot,
+                index,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        let manifest = |slot| SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::SlotManifest(SubscribeUpdateSlotManifest {
+                slot,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut dedup = MultiDedup::new(10);
+        assert!(dedup.push(0, &batch(100, 0)));
+        assert!(dedup.push(0, &batch(100, 1)));
+        assert!(!dedup.push(0, &batch(100, 0)));
+        assert!(dedup.push(0, &batch(101, 0)));
+        assert!(dedup.push(0, &manifest(100)));
+        assert!(!dedup.push(0, &manifest(100)));
+        // slot update of the same slot is not a duplicate of the batch
+        assert!(dedup.push(0, &slot(100)));
+
+        let ack = SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::FilterApplied(SubscribeUpdateFilterApplied {
+                request_id: 1,
+            })),
+            ..Default::default()
+        };
+        assert!(!dedup.push(0, &ack));
+    }
+
+    #[test]
+    fn test_gap_reports() {
+        let account = |slot, write_version| SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: vec![1; 32],
+                    write_version,
+                    ..Default::default()
+                }),
+                slot,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let mut dedup = MultiDedup::new(10).with_gap_reports(3);
+        assert!(dedup.push(0, &account(100, 1)));
+        assert!(!dedup.push(1, &account(100, 1)));
+        assert!(!dedup.push(1, &account(100, 1)));
+        assert!(dedup.push(1, &account(100, 2)));
+        assert!(dedup.push(0, &slot(100)));
+        assert!(!dedup.push(2, &slot(100)));
+
+        let expected = SlotGapReport {
+            slot: 100,
+            delivered: BTreeMap::from([("accounts", 2), ("slots", 1)]),
+            received: vec![
+                BTreeMap::from([("accounts", 1), ("slots", 1)]),
+                BTreeMap::from([("accounts", 2)]),
+                BTreeMap::from([("slots", 1)]),
+            ],
+        };
+        assert_eq!(dedup.gap_report(100), Some(expected.clone()));
+        assert_eq!(expected.missing(0), BTreeMap::from([("accounts", 1)]));
+        assert_eq!(expected.missing(1), BTreeMap::from([("slots", 1)]));
+        assert_eq!(expected.missing(2), BTreeMap::from([("accounts", 2)]));
+        assert!(!expected.is_complete());
+        assert!(dedup.pop_gap_report().is_none());
+
+        // reported once slot leaves the window
+        assert!(dedup.push(0, &slot(111)));
+        assert_eq!(dedup.pop_gap_report(), Some(expected));
+        assert!(dedup.pop_gap_report().is_none());
+        assert!(dedup.gap_report(100).is_none());
+        assert!(dedup
+            .gap_report(111)
+            .is_some_and(|report| !report.is_complete()));
+
+        // disabled by default
+        let mut dedup = MultiDedup::new(10);
+        assert!(dedup.push(0, &slot(100)));
+        assert!(dedup.push(0, &slot(111)));
+        assert!(dedup.pop_gap_report().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() {
+        let mut builders = vec![];
+        for _ in 0..2 {
+            // stream is closed by the server after every update
+            let endpoint = MockGeyser {
+                updates: vec![slot(42)],
+                ..Default::default()
+            }
+            .spawn()
+            .await;
+            builders.push(GeyserGrpcClient::build_from_shared(endpoint).unwrap());
+        }
+        builders.push(GeyserGrpcClient::build_from_shared(closed_endpoint().await).unwrap());
+
+        let config = MultiConfig {
+            managed: ManagedConfig {
+                reconnect_delay_min: Duration::from_millis(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (stream, _events) = MultiGeyserGrpcClient::new(builders)
+            .with_config(config)
+            .subscribe(SubscribeRequest::default());
+        let mut stream = std::pin::pin!(stream);
+
+        let item = stream.next().await.unwrap().unwrap();
+        assert!(item.endpoint < 2);
+        assert_eq!(item.update, slot(42));
+        // updates of both endpoints after reconnects are duplicates
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), stream.next())
+                .await
+                .is_err()
+        );
+    }
+}
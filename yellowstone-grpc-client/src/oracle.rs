@@ -0,0 +1,471 @@
+//! Tracking of oracle price accounts from accounts updates.
+//!
+//! Supported layouts are Pyth `PriceUpdateV2` (pull oracle and price feed
+//! accounts) and Switchboard On-Demand `PullFeedAccountData`. Prices are kept as
+//! integers with exponent, staleness is based on the publish time reported by
+//! the oracle.
+
+use {
+    futures::stream::{self, Stream, StreamExt},
+    sha2::{Digest, Sha256},
+    std::{
+        collections::HashMap,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+    tonic::Status,
+    yellowstone_grpc_proto::{
+        base58,
+        prelude::{
+            subscribe_update::UpdateOneof, SubscribeRequestFilterAccounts, SubscribeUpdate,
+            SubscribeUpdateAccount,
+        },
+    },
+};
+
+/// `Partial` verification level in Pyth `PriceUpdateV2` has extra byte
+const PYTH_VERIFICATION_LEVEL_PARTIAL: u8 = 0;
+const PYTH_VERIFICATION_LEVEL_FULL: u8 = 1;
+
+/// Offset of `result` (`CurrentResult`) in Switchboard `PullFeedAccountData`
+const SWITCHBOARD_RESULT_OFFSET: usize = 2264;
+/// Offset of `last_update_timestamp` in Switchboard `PullFeedAccountData`
+const SWITCHBOARD_LAST_UPDATE_OFFSET: usize = 2216;
+/// Switchboard values are fixed-point numbers with 18 decimals
+const SWITCHBOARD_EXPO: i32 = -18;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum OracleError {
+    #[error("account data is too short: {0} bytes")]
+    InvalidSize(usize),
+    #[error("invalid account discriminator")]
+    InvalidDiscriminator,
+    #[error("invalid verification level: {0}")]
+    InvalidVerificationLevel(u8),
+}
+
+pub type OracleResult<T> = Result<T, OracleError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OracleLayout {
+    /// Pyth `PriceUpdateV2`
+    Pyth,
+    /// Switchboard On-Demand `PullFeedAccountData`
+    Switchboard,
+}
+
+impl OracleLayout {
+    fn discriminator(self) -> [u8; 8] {
+        let name = match self {
+            Self::Pyth => "PriceUpdateV2",
+            Self::Switchboard => "PullFeedAccountData",
+        };
+        let hash = Sha256::digest(format!("account:{name}"));
+        hash[..8].try_into().expect("valid size")
+    }
+
+    pub fn decode(self, data: &[u8]) -> OracleResult<OraclePrice> {
+        if data.len() < 8 {
+            return Err(OracleError::InvalidSize(data.len()));
+        }
+        if data[..8] != self.discriminator() {
+            return Err(OracleError::InvalidDiscriminator);
+        }
+
+        match self {
+            Self::Pyth => {
+                // discriminator, write_authority, verification_level
+                let offset = match data.get(40) {
+                    Some(&PYTH_VERIFICATION_LEVEL_PARTIAL) => 42,
+                    Some(&PYTH_VERIFICATION_LEVEL_FULL) => 41,
+                    Some(&level) => return Err(OracleError::InvalidVerificationLevel(level)),
+                    None => return Err(OracleError::InvalidSize(data.len())),
+                };
+                // feed_id, price, conf, exponent, publish_time
+                let data = data
+                    .get(offset..offset + 60)
+                    .ok_or(OracleError::InvalidSize(data.len()))?;
+                Ok(OraclePrice {
+                    price: read_i64(&data[32..40]) as i128,
+                    conf: read_u64(&data[40..48]) as u128,
+                    expo: read_i32(&data[48..52]),
+                    publish_time: read_i64(&data[52..60]),
+                })
+            }
+            Self::Switchboard => {
+                // value, std_dev
+                let result = data
+                    .get(SWITCHBOARD_RESULT_OFFSET..SWITCHBOARD_RESULT_OFFSET + 32)
+                    .ok_or(OracleError::InvalidSize(data.len()))?;
+                let last_update = &data[SWITCHBOARD_LAST_UPDATE_OFFSET..][..8];
+                Ok(OraclePrice {
+                    price: read_i128(&result[..16]),
+                    conf: read_i128(&result[16..32]).unsigned_abs(),
+                    expo: SWITCHBOARD_EXPO,
+                    publish_time: read_i64(last_update),
+                })
+            }
+        }
+    }
+}
+
+fn read_i32(data: &[u8]) -> i32 {
+    i32::from_le_bytes(data.try_into().expect("valid size"))
+}
+
+fn read_i64(data: &[u8]) -> i64 {
+    i64::from_le_bytes(data.try_into().expect("valid size"))
+}
+
+fn read_u64(data: &[u8]) -> u64 {
+    u64::from_le_bytes(data.try_into().expect("valid size"))
+}
+
+fn read_i128(data: &[u8]) -> i128 {
+    i128::from_le_bytes(data.try_into().expect("valid size"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: i128,
+    /// Confidence interval for Pyth, standard deviation for Switchboard
+    pub conf: u128,
+    pub expo: i32,
+    /// Unix timestamp in seconds
+    pub publish_time: i64,
+}
+
+impl OraclePrice {
+    pub fn price_f64(&self) -> f64 {
+        self.price as f64 * 10f64.powi(self.expo)
+    }
+
+    pub fn conf_f64(&self) -> f64 {
+        self.conf as f64 * 10f64.powi(self.expo)
+    }
+
+    pub fn age(&self, now: SystemTime) -> Duration {
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or_default();
+        Duration::from_secs(now.saturating_sub(self.publish_time).max(0) as u64)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct OracleFeed {
+    layout: OracleLayout,
+    price: Option<OraclePrice>,
+    slot: u64,
+    stale: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OracleEvent {
+    /// Price or confidence changed, `previous` is `None` for the first update
+    PriceChanged {
+        /// Account pubkey in base58
+        pubkey: String,
+        slot: u64,
+        previous: Option<OraclePrice>,
+        price: OraclePrice,
+    },
+    /// Publish time is older than max staleness, reported once until next update
+    Stale {
+        /// Account pubkey in base58
+        pubkey: String,
+        price: OraclePrice,
+    },
+    /// Failed to decode tracked account
+    Error {
+        /// Account pubkey in base58
+        pubkey: String,
+        error: OracleError,
+    },
+}
+
+/// Tracks a set of oracle accounts, feeds are identified by base58 pubkey
+#[derive(Debug, Clone)]
+pub struct OracleMonitor {
+    feeds: HashMap<String, OracleFeed>,
+    max_staleness: Duration,
+}
+
+impl OracleMonitor {
+    pub fn new(max_staleness: Duration) -> Self {
+        Self {
+            feeds: HashMap::new(),
+            max_staleness,
+        }
+    }
+
+    pub fn add_feed(&mut self, pubkey: impl Into<String>, layout: OracleLayout) {
+        self.feeds.insert(
+            pubkey.into(),
+            OracleFeed {
+                layout,
+                price: None,
+                slot: 0,
+                stale: false,
+            },
+        );
+    }
+
+    pub fn remove_feed(&mut self, pubkey: &str) -> bool {
+        self.feeds.remove(pubkey).is_some()
+    }
+
+    /// Accounts filter for all tracked feeds
+    pub fn filter(&self) -> SubscribeRequestFilterAccounts {
+        SubscribeRequestFilterAccounts::with_account(self.feeds.keys())
+    }
+
+    pub fn price(&self, pubkey: &str) -> Option<&OraclePrice> {
+        self.feeds.get(pubkey).and_then(|feed| feed.price.as_ref())
+    }
+
+    pub fn is_stale(&self, pubkey: &str, now: SystemTime) -> bool {
+        self.price(pubkey)
+            .is_none_or(|price| price.age(now) > self.max_staleness)
+    }
+
+    /// Apply account update, events are returned only for tracked accounts
+    pub fn update_account(&mut self, msg: &SubscribeUpdateAccount) -> Option<OracleEvent> {
+        let account = msg.account.as_ref()?;
+        let pubkey = base58::encode(&account.pubkey);
+        let feed = self.feeds.get_mut(&pubkey)?;
+        if msg.slot < feed.slot {
+            return None;
+        }
+
+        let price = match feed.layout.decode(&account.data) {
+            Ok(price) => price,
+            Err(error) => return Some(OracleEvent::Error { pubkey, error }),
+        };
+        let previous = feed.price.replace(price);
+        feed.slot = msg.slot;
+        feed.stale = false;
+
+        match previous {
+            Some(previous) if previous.price == price.price && previous.conf == price.conf => None,
+            previous => Some(OracleEvent::PriceChanged {
+                pubkey,
+                slot: msg.slot,
+                previous,
+                price,
+            }),
+        }
+    }
+
+    /// Mark feeds with outdated prices as stale, each feed is reported once
+    pub fn check_staleness(&mut self, now: SystemTime) -> Vec<OracleEvent> {
+        let mut events = vec![];
+        for (pubkey, feed) in self.feeds.iter_mut() {
+            let Some(price) = feed.price else {
+                continue;
+            };
+            if !feed.stale && price.age(now) > self.max_staleness {
+                feed.stale = true;
+                events.push(OracleEvent::Stale {
+                    pubkey: pubkey.clone(),
+                    price,
+                });
+            }
+        }
+        events
+    }
+
+    /// Convert stream of updates to stream of oracle events, staleness is
+    /// checked on every received message (including pings)
+    pub fn into_stream<S>(mut self, stream: S) -> impl Stream<Item = Result<OracleEvent, Status>>
+    where
+        S: Stream<Item = Result<SubscribeUpdate, Status>>,
+    {
+        stream.flat_map(move |message| {
+            let events = match message {
+                Ok(SubscribeUpdate {
+                    update_oneof: Some(UpdateOneof::Account(msg)),
+                    ..
+                }) => self
+                    .update_account(&msg)
+                    .into_iter()
+                    .chain(self.check_staleness(SystemTime::now()))
+                    .map(Ok)
+                    .collect(),
+                Ok(_) => self
+                    .check_staleness(SystemTime::now())
+                    .into_iter()
+                    .map(Ok)
+                    .collect(),
+                Err(status) => vec![Err(status)],
+            };
+            stream::iter(events)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            OracleError, OracleEvent, OracleLayout, OracleMonitor, OraclePrice,
+            SWITCHBOARD_LAST_UPDATE_OFFSET, SWITCHBOARD_RESULT_OFFSET,
+        },
+        futures::{stream, StreamExt},
+        std::time::{Duration, SystemTime, UNIX_EPOCH},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateAccount,
+            SubscribeUpdateAccountInfo,
+        },
+    };
+
+    const PYTH_FEED: &str = "7UVimffxr9ow1uXYxsr4LHAcV58mLzhmwaeKvJ1pjLiE";
+    const SWITCHBOARD_FEED: &str = "11111111111111111111111111111112";
+
+    fn pyth_data(price: i64, conf: u64, publish_time: i64) -> Vec<u8> {
+        let mut data = OracleLayout::Pyth.discriminator().to_vec();
+        data.extend_from_slice(&[0; 32]);
+        data.extend_from_slice(&[0, 3]);
+        data.extend_from_slice(&[1; 32]);
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&conf.to_le_bytes());
+        data.extend_from_slice(&(-8i32).to_le_bytes());
+        data.extend_from_slice(&publish_time.to_le_bytes());
+        data.extend_from_slice(&[0; 48]);
+        data
+    }
+
+    fn switchboard_data(value: i128, std_dev: i128, publish_time: i64) -> Vec<u8> {
+        let mut data = vec![0; 3208];
+        data[..8].copy_from_slice(&OracleLayout::Switchboard.discriminator());
+        data[SWITCHBOARD_LAST_UPDATE_OFFSET..][..8].copy_from_slice(&publish_time.to_le_bytes());
+        data[SWITCHBOARD_RESULT_OFFSET..][..16].copy_from_slice(&value.to_le_bytes());
+        data[SWITCHBOARD_RESULT_OFFSET + 16..][..16].copy_from_slice(&std_dev.to_le_bytes());
+        data
+    }
+
+    fn account_update(pubkey: &str, slot: u64, data: Vec<u8>) -> SubscribeUpdateAccount {
+        SubscribeUpdateAccount {
+            account: Some(SubscribeUpdateAccountInfo {
+                pubkey: bs58::decode(pubkey).into_vec().unwrap(),
+                data,
+                ..Default::default()
+            }),
+            slot,
+            is_startup: false,
+            previous_owner: None,
+            data_omitted: false,
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let price = OracleLayout::Pyth
+            .decode(&pyth_data(15_012_345_678, 2_000_000, 1_700_000_000))
+            .unwrap();
+        assert_eq!(
+            price,
+            OraclePrice {
+                price: 15_012_345_678,
+                conf: 2_000_000,
+                expo: -8,
+                publish_time: 1_700_000_000,
+            }
+        );
+        assert!((price.price_f64() - 150.12345678).abs() < 1e-9);
+
+        let price = OracleLayout::Switchboard
+            .decode(&switchboard_data(
+                1_500_000_000_000_000_000,
+                -1_000_000_000_000_000,
+                1_700_000_000,
+            ))
+            .unwrap();
+        assert!((price.price_f64() - 1.5).abs() < 1e-9);
+        assert_eq!(price.conf, 1_000_000_000_000_000);
+        assert_eq!(price.publish_time, 1_700_000_000);
+
+        assert_eq!(
+            OracleLayout::Switchboard.decode(&pyth_data(1, 1, 1)),
+            Err(OracleError::InvalidDiscriminator)
+        );
+        assert_eq!(
+            OracleLayout::Pyth.decode(&pyth_data(1, 1, 1)[..64]),
+            Err(OracleError::InvalidSize(64))
+        );
+    }
+
+    #[test]
+    fn test_monitor() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let mut monitor = OracleMonitor::new(Duration::from_secs(60));
+        monitor.add_feed(PYTH_FEED, OracleLayout::Pyth);
+        monitor.add_feed(SWITCHBOARD_FEED, OracleLayout::Switchboard);
+        assert_eq!(monitor.filter().account.len(), 2);
+
+        let event = monitor.update_account(&account_update(PYTH_FEED, 10, pyth_data(100, 1, now)));
+        assert!(matches!(
+            event,
+            Some(OracleEvent::PriceChanged { previous: None, .. })
+        ));
+        assert!(!monitor.is_stale(PYTH_FEED, SystemTime::now()));
+        assert!(monitor.is_stale(SWITCHBOARD_FEED, SystemTime::now()));
+
+        // same price with newer publish time
+        let event = monitor.update_account(&account_update(PYTH_FEED, 11, pyth_data(100, 1, now)));
+        assert_eq!(event, None);
+        // outdated slot
+        let event = monitor.update_account(&account_update(PYTH_FEED, 9, pyth_data(90, 1, now)));
+        assert_eq!(event, None);
+        assert_eq!(monitor.price(PYTH_FEED).unwrap().price, 100);
+
+        let event = monitor.update_account(&account_update(SWITCHBOARD_FEED, 12, vec![0; 8]));
+        assert!(matches!(event, Some(OracleEvent::Error { .. })));
+
+        let later = SystemTime::now() + Duration::from_secs(120);
+        let events = monitor.check_staleness(later);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(&events[0], OracleEvent::Stale { pubkey, .. } if pubkey == PYTH_FEED));
+        assert!(monitor.check_staleness(later).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+
+        let mut monitor = OracleMonitor::new(Duration::from_secs(60));
+        monitor.add_feed(PYTH_FEED, OracleLayout::Pyth);
+
+        let updates = [pyth_data(100, 1, now), pyth_data(101, 1, now)]
+            .into_iter()
+            .enumerate()
+            .map(|(slot, data)| {
+                Ok(SubscribeUpdate {
+                    update_oneof: Some(UpdateOneof::Account(account_update(
+                        PYTH_FEED,
+                        slot as u64,
+                        data,
+                    ))),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+        let events = monitor
+            .into_stream(stream::iter(updates))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            &events[1],
+            Ok(OracleEvent::PriceChanged { previous: Some(previous), price, .. })
+                if previous.price == 100 && price.price == 101
+        ));
+    }
+}
@@ -0,0 +1,217 @@
+//! Processing of updates by a pool of workers with ordering per key.
+//!
+//! Updates with the same key (account pubkey, transaction signature) are always
+//! handled by the same worker in the order of the stream, so the handler of the
+//! next update of an account is started only after the previous one is
+//! finished. Other updates (slots, blocks, entries) are keyed by update type,
+//! pings are not delivered. Workers are futures polled together with the
+//! stream, handlers with heavy CPU work should spawn it to the runtime.
+
+use {
+    futures::{
+        channel::mpsc,
+        future::{try_join, try_join_all, Future},
+        sink::SinkExt,
+        stream::{Stream, StreamExt},
+    },
+    std::hash::{DefaultHasher, Hash, Hasher},
+    yellowstone_grpc_proto::prelude::{subscribe_update::UpdateOneof, SubscribeUpdate},
+};
+
+/// Capacity of the queue of every worker, the stream is not polled while the
+/// queue of the next update is full
+pub const WORKER_QUEUE_CAPACITY: usize = 1_024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpdateKey<'a> {
+    Pubkey(&'a [u8]),
+    Signature(&'a [u8]),
+    Kind(&'static str),
+}
+
+impl<'a> UpdateKey<'a> {
+    /// Ordering key of the update, `None` for pings, pongs and acknowledgements
+    pub fn new(update: &'a SubscribeUpdate) -> Option<Self> {
+        Some(match update.update_oneof.as_ref()? {
+            UpdateOneof::Account(msg) => Self::Pubkey(&msg.account.as_ref()?.pubkey),
+            UpdateOneof::Transaction(msg) => Self::Signature(&msg.transaction.as_ref()?.signature),
+            UpdateOneof::TransactionStatus(msg) => Self::Signature(&msg.signature),
+            UpdateOneof::Slot(_) => Self::Kind("slot"),
+            UpdateOneof::Block(_) => Self::Kind("block"),
+            UpdateOneof::BlockMeta(_) => Self::Kind("block_meta"),
+            UpdateOneof::Entry(_) => Self::Kind("entry"),
+            UpdateOneof::SlotManifest(_) => Self::Kind("slot_manifest"),
+            UpdateOneof::SlotBatch(_) => Self::Kind("slot_batch"),
+            UpdateOneof::Ping(_) | UpdateOneof::Pong(_) | UpdateOneof::FilterApplied(_) => {
+                return None
+            }
+        })
+    }
+
+    fn worker(&self, concurrency: usize) -> usize {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        (hasher.finish() % concurrency as u64) as usize
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ParallelError<E, H> {
+    #[error("stream error: {0}")]
+    Stream(E),
+    #[error("handler error: {0}")]
+    Handler(H),
+}
+
+/// Handle updates of the stream by `concurrency` workers, finished when the
+/// stream is finished and all updates are handled or on the first error of the
+/// stream or the handler
+pub async fn process_parallel<S, E, F, Fut, H>(
+    stream: S,
+    handler: F,
+    concurrency: usize,
+) -> Result<(), ParallelError<E, H>>
+where
+    S: Stream<Item = Result<SubscribeUpdate, E>>,
+    F: Fn(SubscribeUpdate) -> Fut,
+    Fut: Future<Output = Result<(), H>>,
+{
+    let concurrency = concurrency.max(1);
+    let (mut senders, receivers): (Vec<_>, Vec<_>) = (0..concurrency)
+        .map(|_| mpsc::channel::<SubscribeUpdate>(WORKER_QUEUE_CAPACITY))
+        .unzip();
+
+    let handler = &handler;
+    let workers = try_join_all(receivers.into_iter().map(|mut rx| async move {
+        while let Some(update) = rx.next().await {
+            handler(update).await.map_err(ParallelError::Handler)?;
+        }
+        Ok(())
+    }));
+
+    let dispatcher = async move {
+        let mut stream = std::pin::pin!(stream);
+        while let Some(update) = stream.next().await {
+            let update = update.map_err(ParallelError::Stream)?;
+            let Some(worker) = UpdateKey::new(&update).map(|key| key.worker(concurrency)) else {
+                continue;
+            };
+            // receiver is dropped only on handler error, returned by the worker
+            if senders[worker].send(update).await.is_err() {
+                break;
+            }
+        }
+        // close queues, workers are finished after handling queued updates
+        drop(senders);
+        Ok(())
+    };
+
+    try_join(dispatcher, workers).await.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{process_parallel, ParallelError},
+        futures::stream,
+        std::{
+            collections::HashMap,
+            sync::{Arc, Mutex},
+        },
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateAccount,
+            SubscribeUpdateAccountInfo, SubscribeUpdatePing,
+        },
+    };
+
+    fn account(pubkey: u8, write_version: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: vec![pubkey; 32],
+                    write_version,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn write_version(update: &SubscribeUpdate) -> (u8, u64) {
+        match &update.update_oneof {
+            Some(UpdateOneof::Account(msg)) => {
+                let account = msg.account.as_ref().unwrap();
+                (account.pubkey[0], account.write_version)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_order_per_key() {
+        let mut updates = (0..100)
+            .map(|write_version| Ok::<_, ()>(account((write_version % 7) as u8, write_version)))
+            .collect::<Vec<_>>();
+        updates.push(Ok(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Ping(SubscribeUpdatePing {})),
+            ..Default::default()
+        }));
+
+        let handled = Arc::new(Mutex::new(HashMap::<u8, Vec<u64>>::new()));
+        process_parallel(
+            stream::iter(updates),
+            |update| {
+                let handled = Arc::clone(&handled);
+                async move {
+                    // let other workers run between updates
+                    tokio::task::yield_now().await;
+                    let (pubkey, write_version) = write_version(&update);
+                    handled
+                        .lock()
+                        .unwrap()
+                        .entry(pubkey)
+                        .or_default()
+                        .push(write_version);
+                    Ok::<_, ()>(())
+                }
+            },
+            4,
+        )
+        .await
+        .unwrap();
+
+        let handled = handled.lock().unwrap();
+        assert_eq!(handled.values().map(Vec::len).sum::<usize>(), 100);
+        for (pubkey, write_versions) in handled.iter() {
+            let expected = (0..100)
+                .filter(|write_version| write_version % 7 == *pubkey as u64)
+                .collect::<Vec<_>>();
+            assert_eq!(write_versions, &expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let result = process_parallel(
+            stream::iter([Ok(account(1, 1)), Err("stream"), Ok(account(1, 2))]),
+            |_update| async { Ok::<_, ()>(()) },
+            2,
+        )
+        .await;
+        assert!(matches!(result, Err(ParallelError::Stream("stream"))));
+
+        let result = process_parallel(
+            stream::iter((0..10).map(|write_version| Ok::<_, ()>(account(1, write_version)))),
+            |update| async move {
+                match write_version(&update) {
+                    (_, 5) => Err("handler"),
+                    _ => Ok(()),
+                }
+            },
+            2,
+        )
+        .await;
+        assert!(matches!(result, Err(ParallelError::Handler("handler"))));
+    }
+}
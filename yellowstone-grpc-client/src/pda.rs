@@ -0,0 +1,84 @@
+//! Expansion of `pda` in accounts filters into concrete pubkeys.
+//!
+//! Servers without `accounts_pda` in `GetVersionFeatures` ignore `pda`, so for
+//! them families of program derived addresses are expanded on the client and
+//! sent as `account`.
+
+use yellowstone_grpc_proto::{
+    pda::PdaResult,
+    prelude::{GetVersionFeatures, SubscribeRequest, SubscribeRequestFilterAccounts},
+};
+
+/// Move `pda` of the filter into `account`
+pub fn expand_pda(
+    mut filter: SubscribeRequestFilterAccounts,
+) -> PdaResult<SubscribeRequestFilterAccounts> {
+    for family in std::mem::take(&mut filter.pda) {
+        filter.account.extend(
+            family
+                .expand()?
+                .into_iter()
+                .map(|pubkey| pubkey.to_string()),
+        );
+    }
+    Ok(filter)
+}
+
+/// Expand `pda` of all accounts filters if the server does not support them
+pub fn resolve_pda(
+    mut request: SubscribeRequest,
+    features: &GetVersionFeatures,
+) -> PdaResult<SubscribeRequest> {
+    if !features.supports_filter(GetVersionFeatures::ACCOUNTS_PDA) {
+        for filter in request.accounts.values_mut() {
+            *filter = expand_pda(std::mem::take(filter))?;
+        }
+    }
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::resolve_pda,
+        yellowstone_grpc_proto::prelude::{
+            GetVersionFeatures, SubscribeRequest, SubscribeRequestFilterAccounts,
+            SubscribeRequestFilterAccountsPda,
+        },
+    };
+
+    const PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+    #[test]
+    fn test_resolve_pda() {
+        let pda = SubscribeRequestFilterAccountsPda::new(PROGRAM_ID, [b"vault".to_vec()])
+            .with_seeds_last([[1; 32], [2; 32]]);
+        let vaults = pda.expand().unwrap();
+        let request = SubscribeRequest::default().with_accounts(
+            "vaults",
+            SubscribeRequestFilterAccounts::with_account([PROGRAM_ID]).add_pda(pda),
+        );
+
+        // server expands `pda`
+        let features = GetVersionFeatures {
+            filters: vec![GetVersionFeatures::ACCOUNTS_PDA.to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve_pda(request.clone(), &features).unwrap(),
+            request.clone()
+        );
+
+        let resolved = resolve_pda(request, &GetVersionFeatures::default()).unwrap();
+        let filter = &resolved.accounts["vaults"];
+        assert_eq!(
+            filter.account,
+            [
+                PROGRAM_ID.to_owned(),
+                vaults[0].to_string(),
+                vaults[1].to_string()
+            ]
+        );
+        assert!(filter.pda.is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+//! Filters for common programs.
+//!
+//! Presets use `datasize` / `memcmp` filters where possible, so the server does
+//! not send accounts of other types owned by the same program.
+
+use {
+    std::fmt::Display,
+    yellowstone_grpc_proto::prelude::{
+        SubscribeRequestFilterAccounts, SubscribeRequestFilterAccountsFilter,
+        SubscribeRequestFilterTransactions,
+    },
+};
+
+pub const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+pub const SERUM_V3_PROGRAM_ID: &str = "9xQeWvG816bUx9EPjHmaT23yvVM2ZWbrrpZb9PusVFin";
+pub const OPENBOOK_V1_PROGRAM_ID: &str = "srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX";
+pub const OPENBOOK_V2_PROGRAM_ID: &str = "opnb2LAfJYbRMAHHvqjCwQxanZn7ReEHp1k81EohpZb";
+
+/// SPL Token `Account`
+const TOKEN_ACCOUNT_SIZE: u64 = 165;
+/// Offset of `mint` in token account
+const TOKEN_ACCOUNT_MINT_OFFSET: u64 = 0;
+/// Offset of `owner` in token account
+const TOKEN_ACCOUNT_OWNER_OFFSET: u64 = 32;
+/// Serum v3 / OpenBook v1 `MarketState` with padding
+const SERUM_MARKET_SIZE: u64 = 388;
+/// OpenBook v2 `Market` with discriminator
+const OPENBOOK_V2_MARKET_SIZE: u64 = 848;
+
+/// All SPL Token accounts (without mints and multisigs)
+pub fn token_accounts() -> SubscribeRequestFilterAccounts {
+    SubscribeRequestFilterAccounts::with_owner([TOKEN_PROGRAM_ID]).add_filter(
+        SubscribeRequestFilterAccountsFilter::datasize(TOKEN_ACCOUNT_SIZE),
+    )
+}
+
+/// All Token-2022 accounts, size varies with extensions
+pub fn token_2022_accounts() -> SubscribeRequestFilterAccounts {
+    SubscribeRequestFilterAccounts::with_owner([TOKEN_2022_PROGRAM_ID])
+        .add_filter(SubscribeRequestFilterAccountsFilter::token_account_state())
+}
+
+/// SPL Token accounts of the wallet
+pub fn token_accounts_by_owner(owner: impl Display) -> SubscribeRequestFilterAccounts {
+    token_accounts().add_filter(SubscribeRequestFilterAccountsFilter::memcmp_base58(
+        TOKEN_ACCOUNT_OWNER_OFFSET,
+        owner.to_string(),
+    ))
+}
+
+/// SPL Token accounts of the mint
+pub fn token_accounts_by_mint(mint: impl Display) -> SubscribeRequestFilterAccounts {
+    token_accounts().add_filter(SubscribeRequestFilterAccountsFilter::memcmp_base58(
+        TOKEN_ACCOUNT_MINT_OFFSET,
+        mint.to_string(),
+    ))
+}
+
+/// Serum v3 markets (without queues and open orders)
+pub fn serum_markets() -> SubscribeRequestFilterAccounts {
+    SubscribeRequestFilterAccounts::with_owner([SERUM_V3_PROGRAM_ID]).add_filter(
+        SubscribeRequestFilterAccountsFilter::datasize(SERUM_MARKET_SIZE),
+    )
+}
+
+/// OpenBook v1 (Serum v3 fork) markets
+pub fn openbook_v1_markets() -> SubscribeRequestFilterAccounts {
+    SubscribeRequestFilterAccounts::with_owner([OPENBOOK_V1_PROGRAM_ID]).add_filter(
+        SubscribeRequestFilterAccountsFilter::datasize(SERUM_MARKET_SIZE),
+    )
+}
+
+/// OpenBook v2 markets
+pub fn openbook_v2_markets() -> SubscribeRequestFilterAccounts {
+    SubscribeRequestFilterAccounts::with_owner([OPENBOOK_V2_PROGRAM_ID]).add_filter(
+        SubscribeRequestFilterAccountsFilter::datasize(OPENBOOK_V2_MARKET_SIZE),
+    )
+}
+
+/// Non-vote transactions with any of the programs, failed transactions included
+pub fn program_transactions<I, T>(programs: I) -> SubscribeRequestFilterTransactions
+where
+    I: IntoIterator<Item = T>,
+    T: Display,
+{
+    SubscribeRequestFilterTransactions::for_account(programs).with_vote(false)
+}
+
+/// Successful non-vote transactions with System program, server can't filter by
+/// instruction so other System program instructions (e.g. `CreateAccount`) are
+/// included too
+pub fn system_transfers() -> SubscribeRequestFilterTransactions {
+    program_transactions([SYSTEM_PROGRAM_ID]).with_failed(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            program_transactions, serum_markets, system_transfers, token_accounts_by_owner,
+            SERUM_MARKET_SIZE, SYSTEM_PROGRAM_ID, TOKEN_ACCOUNT_OWNER_OFFSET, TOKEN_PROGRAM_ID,
+        },
+        yellowstone_grpc_proto::prelude::{
+            subscribe_request_filter_accounts_filter::Filter as AccountsFilterOneof,
+            subscribe_request_filter_accounts_filter_memcmp::Data as AccountsFilterMemcmpOneof,
+            SubscribeRequestFilterAccountsFilter, SubscribeRequestFilterAccountsFilterMemcmp,
+        },
+    };
+
+    #[test]
+    fn test_accounts() {
+        let owner = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+        let filter = token_accounts_by_owner(owner);
+        assert_eq!(filter.owner, vec![TOKEN_PROGRAM_ID.to_owned()]);
+        assert_eq!(
+            filter.filters[1],
+            SubscribeRequestFilterAccountsFilter {
+                filter: Some(AccountsFilterOneof::Memcmp(
+                    SubscribeRequestFilterAccountsFilterMemcmp {
+                        offset: TOKEN_ACCOUNT_OWNER_OFFSET,
+                        data: Some(AccountsFilterMemcmpOneof::Base58(owner.to_owned())),
+                    }
+                )),
+            }
+        );
+
+        assert_eq!(
+            serum_markets().filters,
+            vec![SubscribeRequestFilterAccountsFilter::datasize(
+                SERUM_MARKET_SIZE
+            )]
+        );
+    }
+
+    #[test]
+    fn test_transactions() {
+        let filter = program_transactions([SYSTEM_PROGRAM_ID]);
+        assert_eq!(filter.vote, Some(false));
+        assert_eq!(filter.failed, None);
+
+        let filter = system_transfers();
+        assert_eq!(filter.account_include, vec![SYSTEM_PROGRAM_ID.to_owned()]);
+        assert_eq!(filter.failed, Some(false));
+    }
+}
@@ -0,0 +1,173 @@
+//! Transactions of the slot in block order.
+//!
+//! Transactions and transactions statuses are buffered by slot and released
+//! sorted by `index` before the first slot status update (processed, confirmed
+//! or finalized), block meta or block of the slot. Buffered transactions of
+//! dead slots are dropped, other updates are passed as is.
+
+use {
+    futures::stream::{self, Stream, StreamExt},
+    std::collections::BTreeMap,
+    yellowstone_grpc_proto::prelude::{subscribe_update::UpdateOneof, SlotStatus, SubscribeUpdate},
+};
+
+#[derive(Debug, Default)]
+pub struct TransactionReorder {
+    slots: BTreeMap<u64, Vec<(u64, SubscribeUpdate)>>,
+}
+
+impl TransactionReorder {
+    pub const fn new() -> Self {
+        Self {
+            slots: BTreeMap::new(),
+        }
+    }
+
+    /// Number of buffered transactions
+    pub fn len(&self) -> usize {
+        self.slots.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Add update, returns updates ready for processing
+    pub fn push(&mut self, update: SubscribeUpdate) -> Vec<SubscribeUpdate> {
+        let (slot, index) = match &update.update_oneof {
+            Some(UpdateOneof::Transaction(msg)) => match &msg.transaction {
+                Some(tx) => (msg.slot, tx.index),
+                None => return vec![update],
+            },
+            Some(UpdateOneof::TransactionStatus(msg)) => (msg.slot, msg.index),
+            Some(UpdateOneof::Slot(msg)) => {
+                match SlotStatus::try_from(msg.status) {
+                    Ok(
+                        SlotStatus::SlotProcessed
+                        | SlotStatus::SlotConfirmed
+                        | SlotStatus::SlotFinalized,
+                    ) => {
+                        let mut updates = self.flush(msg.slot);
+                        updates.push(update);
+                        return updates;
+                    }
+                    Ok(SlotStatus::SlotDead) => {
+                        self.slots.remove(&msg.slot);
+                    }
+                    _ => {}
+                }
+                return vec![update];
+            }
+            Some(UpdateOneof::BlockMeta(msg)) => {
+                let mut updates = self.flush(msg.slot);
+                updates.push(update);
+                return updates;
+            }
+            Some(UpdateOneof::Block(msg)) => {
+                let mut updates = self.flush(msg.slot);
+                updates.push(update);
+                return updates;
+            }
+            _ => return vec![update],
+        };
+
+        self.slots.entry(slot).or_default().push((index, update));
+        vec![]
+    }
+
+    /// Release buffered transactions of the slot sorted by index
+    pub fn flush(&mut self, slot: u64) -> Vec<SubscribeUpdate> {
+        let mut transactions = self.slots.remove(&slot).unwrap_or_default();
+        // stable, transaction is followed by its status
+        transactions.sort_by_key(|(index, _update)| *index);
+        transactions
+            .into_iter()
+            .map(|(_index, update)| update)
+            .collect()
+    }
+}
+
+/// Deliver transactions of every slot sorted by index
+pub fn reorder_transactions<S, E>(stream: S) -> impl Stream<Item = Result<SubscribeUpdate, E>>
+where
+    S: Stream<Item = Result<SubscribeUpdate, E>>,
+{
+    let mut reorder = TransactionReorder::new();
+    stream.flat_map(move |message| {
+        stream::iter(match message {
+            Ok(update) => reorder.push(update).into_iter().map(Ok).collect(),
+            Err(error) => vec![Err(error)],
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::reorder_transactions,
+        futures::{stream, StreamExt},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SlotStatus, SubscribeUpdate, SubscribeUpdateSlot,
+            SubscribeUpdateTransactionStatus,
+        },
+    };
+
+    fn tx_status(slot: u64, index: u64) -> Result<SubscribeUpdate, ()> {
+        Ok(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::TransactionStatus(
+                SubscribeUpdateTransactionStatus {
+                    slot,
+                    index,
+                    ..Default::default()
+                },
+            )),
+            ..Default::default()
+        })
+    }
+
+    fn slot(slot: u64, status: SlotStatus) -> Result<SubscribeUpdate, ()> {
+        Ok(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                status: status as i32,
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+    }
+
+    fn key(update: &SubscribeUpdate) -> (u64, Option<u64>) {
+        match &update.update_oneof {
+            Some(UpdateOneof::TransactionStatus(msg)) => (msg.slot, Some(msg.index)),
+            Some(UpdateOneof::Slot(msg)) => (msg.slot, None),
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reorder() {
+        let updates = vec![
+            tx_status(1, 2),
+            tx_status(2, 0),
+            tx_status(1, 0),
+            tx_status(1, 1),
+            slot(2, SlotStatus::SlotDead),
+            slot(1, SlotStatus::SlotProcessed),
+            tx_status(3, 0),
+        ];
+        let updates = reorder_transactions(stream::iter(updates))
+            .map(|update| key(&update.unwrap()))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            updates,
+            vec![
+                (2, None),
+                (1, Some(0)),
+                (1, Some(1)),
+                (1, Some(2)),
+                (1, None)
+            ]
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! Single upstream subscription shared by multiple in-process tenants.
+//!
+//! Filters of every tenant are merged into one request with names prefixed by
+//! tenant id (`{id}:{name}`), so matching is done by the server and updates are
+//! routed by `filters` field. Every tenant has own bounded channel, tenant with
+//! full channel is removed and its stream is finished without blocking others.
+
+use {
+    futures::{
+        channel::mpsc,
+        sink::{Sink, SinkExt},
+        stream::{Stream, StreamExt},
+    },
+    std::{
+        collections::{HashMap, HashSet},
+        sync::{Arc, Mutex},
+    },
+    tonic::Status,
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, SubscribeRequest, SubscribeRequestPing, SubscribeUpdate,
+    },
+};
+
+pub type TenantId = u64;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RouterError {
+    #[error("invalid tenant request: {0}")]
+    InvalidRequest(&'static str),
+    #[error("router is already running")]
+    AlreadyRunning,
+    #[error("gRPC status: {0}")]
+    Status(#[from] Status),
+    #[error("failed to send subscribe request: {0}")]
+    SubscribeSendError(#[from] mpsc::SendError),
+}
+
+pub type RouterResult<T> = Result<T, RouterError>;
+
+#[derive(Debug)]
+struct Tenant {
+    request: SubscribeRequest,
+    tx: mpsc::Sender<SubscribeUpdate>,
+    // own sequence, upstream one has gaps for every tenant
+    sequence: u64,
+}
+
+#[derive(Debug, Default)]
+struct RouterState {
+    next_id: TenantId,
+    tenants: HashMap<TenantId, Tenant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubscriptionRouter {
+    /// Commitment and accounts data slices of the upstream subscription
+    base: SubscribeRequest,
+    channel_capacity: usize,
+    state: Arc<Mutex<RouterState>>,
+    changed_tx: mpsc::UnboundedSender<()>,
+    changed_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<()>>>>,
+}
+
+impl SubscriptionRouter {
+    pub fn new(base: SubscribeRequest, channel_capacity: usize) -> Self {
+        let (changed_tx, changed_rx) = mpsc::unbounded();
+        Self {
+            base,
+            channel_capacity,
+            state: Arc::default(),
+            changed_tx,
+            changed_rx: Arc::new(Mutex::new(Some(changed_rx))),
+        }
+    }
+
+    /// Register tenant filters, commitment and accounts data slices are
+    /// defined by the base request and can't be set by tenant
+    pub fn register(
+        &self,
+        request: SubscribeRequest,
+    ) -> RouterResult<(TenantId, mpsc::Receiver<SubscribeUpdate>)> {
+        if request
+            .commitment
+            .is_some_and(|value| Some(value) != self.base.commitment)
+        {
+            return Err(RouterError::InvalidRequest(
+                "commitment differs from router",
+            ));
+        }
+        if !request.accounts_data_slice.is_empty() {
+            return Err(RouterError::InvalidRequest(
+                "accounts_data_slice is not supported",
+            ));
+        }
+        if request.from_slot.is_some() {
+            return Err(RouterError::InvalidRequest("from_slot is not supported"));
+        }
+
+        let (tx, rx) = mpsc::channel(self.channel_capacity);
+        let mut state = self.state.lock().expect("unpoisoned mutex");
+        let id = state.next_id;
+        state.next_id += 1;
+        state.tenants.insert(
+            id,
+            Tenant {
+                request,
+                tx,
+                sequence: 0,
+            },
+        );
+        drop(state);
+
+        let _ = self.changed_tx.unbounded_send(());
+        Ok((id, rx))
+    }
+
+    pub fn unregister(&self, id: TenantId) -> bool {
+        let removed = self.remove_tenants([id]);
+        if removed {
+            let _ = self.changed_tx.unbounded_send(());
+        }
+        removed
+    }
+
+    fn remove_tenants(&self, ids: impl IntoIterator<Item = TenantId>) -> bool {
+        let mut state = self.state.lock().expect("unpoisoned mutex");
+        ids.into_iter().fold(false, |removed, id| {
+            state.tenants.remove(&id).is_some() || removed
+        })
+    }
+
+    pub fn tenants(&self) -> usize {
+        self.state.lock().expect("unpoisoned mutex").tenants.len()
+    }
+
+    /// Merged request for the upstream subscription
+    pub fn request(&self) -> SubscribeRequest {
+        let state = self.state.lock().expect("unpoisoned mutex");
+        let mut request = SubscribeRequest {
+            commitment: self.base.commitment,
+            accounts_data_slice: self.base.accounts_data_slice.clone(),
+            ..Default::default()
+        };
+        for (id, tenant) in state.tenants.iter() {
+            let tenant = &tenant.request;
+            merge(&mut request.accounts, *id, &tenant.accounts);
+            merge(&mut request.slots, *id, &tenant.slots);
+            merge(&mut request.transactions, *id, &tenant.transactions);
+            merge(
+                &mut request.transactions_status,
+                *id,
+                &tenant.transactions_status,
+            );
+            merge(&mut request.blocks, *id, &tenant.blocks);
+            merge(&mut request.blocks_meta, *id, &tenant.blocks_meta);
+            merge(&mut request.entry, *id, &tenant.entry);
+        }
+        request
+    }
+
+    /// Deliver update to the tenants, returns `true` if some tenants were removed
+    pub fn route(&self, update: SubscribeUpdate) -> bool {
+        let mut filters = HashMap::<TenantId, Vec<String>>::new();
+        for filter in update.filters.iter() {
+            if let Some((id, name)) = filter.split_once(':') {
+                if let Ok(id) = id.parse() {
+                    filters.entry(id).or_default().push(name.to_owned());
+                }
+            }
+        }
+
+        let mut removed = HashSet::new();
+        let mut state = self.state.lock().expect("unpoisoned mutex");
+        for (id, filters) in filters {
+            let Some(tenant) = state.tenants.get_mut(&id) else {
+                continue;
+            };
+            tenant.sequence += 1;
+            let update = SubscribeUpdate {
+                filters,
+                update_oneof: update.update_oneof.clone(),
+                created_at: update.created_at,
+                sequence: tenant.sequence,
+                ingested_at: update.ingested_at,
+                replica: update.replica.clone(),
+            };
+            // full channel or dropped receiver
+            if tenant.tx.try_send(update).is_err() {
+                removed.insert(id);
+            }
+        }
+        drop(state);
+
+        self.remove_tenants(removed.iter().copied())
+    }
+
+    /// Drive upstream subscription: send merged request on every tenants change,
+    /// answer server pings and route updates
+    pub async fn run<S, T>(&self, mut subscribe_tx: S, stream: T) -> RouterResult<()>
+    where
+        S: Sink<SubscribeRequest, Error = mpsc::SendError> + Unpin,
+        T: Stream<Item = Result<SubscribeUpdate, Status>>,
+    {
+        let mut changed_rx = self
+            .changed_rx
+            .lock()
+            .expect("unpoisoned mutex")
+            .take()
+            .ok_or(RouterError::AlreadyRunning)?;
+        let result = self
+            .run_loop(&mut subscribe_tx, stream, &mut changed_rx)
+            .await;
+        *self.changed_rx.lock().expect("unpoisoned mutex") = Some(changed_rx);
+        result
+    }
+
+    async fn run_loop<S, T>(
+        &self,
+        subscribe_tx: &mut S,
+        stream: T,
+        changed_rx: &mut mpsc::UnboundedReceiver<()>,
+    ) -> RouterResult<()>
+    where
+        S: Sink<SubscribeRequest, Error = mpsc::SendError> + Unpin,
+        T: Stream<Item = Result<SubscribeUpdate, Status>>,
+    {
+        // drain notifications created before start
+        while let Ok(Some(())) = changed_rx.try_next() {}
+        subscribe_tx.send(self.request()).await?;
+
+        let mut stream = std::pin::pin!(stream.fuse());
+        loop {
+            futures::select! {
+                changed = changed_rx.next() => {
+                    if changed.is_some() {
+                        while let Ok(Some(())) = changed_rx.try_next() {}
+                        subscribe_tx.send(self.request()).await?;
+                    }
+                }
+                message = stream.next() => match message {
+                    Some(Ok(update)) => {
+                        if matches!(update.update_oneof, Some(UpdateOneof::Ping(_))) {
+                            subscribe_tx
+                                .send(SubscribeRequest {
+                                    ping: Some(SubscribeRequestPing { id: 1 }),
+                                    ..Default::default()
+                                })
+                                .await?;
+                        } else if self.route(update) {
+                            subscribe_tx.send(self.request()).await?;
+                        }
+                    }
+                    Some(Err(status)) => return Err(status.into()),
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+}
+
+fn merge<T: Clone>(target: &mut HashMap<String, T>, id: TenantId, filters: &HashMap<String, T>) {
+    for (name, filter) in filters.iter() {
+        target.insert(format!("{id}:{name}"), filter.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{RouterError, SubscriptionRouter},
+        futures::{channel::mpsc, stream, StreamExt},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
+            SubscribeRequestFilterSlots, SubscribeUpdate, SubscribeUpdatePing, SubscribeUpdateSlot,
+        },
+    };
+
+    fn slot(slot: u64, filters: &[&str]) -> SubscribeUpdate {
+        SubscribeUpdate {
+            filters: filters.iter().map(|name| name.to_string()).collect(),
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                ..Default::default()
+            })),
+            created_at: None,
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let router = SubscriptionRouter::new(
+            SubscribeRequest::default().with_commitment(CommitmentLevel::Confirmed),
+            1,
+        );
+        assert!(matches!(
+            router
+                .register(SubscribeRequest::default().with_commitment(CommitmentLevel::Processed)),
+            Err(RouterError::InvalidRequest(_))
+        ));
+
+        let filter = SubscribeRequestFilterSlots::default();
+        let (a, _rx_a) = router
+            .register(SubscribeRequest::default().with_slots("slots", filter))
+            .unwrap();
+        let (b, _rx_b) = router
+            .register(SubscribeRequest::default().with_slots("slots", filter))
+            .unwrap();
+        let request = router.request();
+        assert_eq!(request.commitment, Some(CommitmentLevel::Confirmed as i32));
+        assert!(request.slots.contains_key(&format!("{a}:slots")));
+        assert!(request.slots.contains_key(&format!("{b}:slots")));
+
+        assert!(router.unregister(a));
+        assert!(!router.unregister(a));
+        assert_eq!(router.request().slots.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let router = SubscriptionRouter::new(SubscribeRequest::default(), 0);
+        let filter = SubscribeRequestFilterSlots::default();
+        let (a, mut rx_a) = router
+            .register(SubscribeRequest::default().with_slots("a", filter))
+            .unwrap();
+        let (b, mut rx_b) = router
+            .register(SubscribeRequest::default().with_slots("b", filter))
+            .unwrap();
+
+        let (a_name, b_name) = (format!("{a}:a"), format!("{b}:b"));
+        let updates = vec![
+            Ok(slot(1, &[&a_name, &b_name])),
+            Ok(SubscribeUpdate {
+                update_oneof: Some(UpdateOneof::Ping(SubscribeUpdatePing {})),
+                ..Default::default()
+            }),
+            // channel of `b` is full
+            Ok(slot(2, &[&b_name])),
+        ];
+        let (subscribe_tx, subscribe_rx) = mpsc::unbounded();
+        router
+            .run(subscribe_tx, stream::iter(updates))
+            .await
+            .unwrap();
+
+        assert_eq!(rx_a.next().await.unwrap().filters, vec!["a".to_owned()]);
+        assert_eq!(rx_b.next().await.unwrap().filters, vec!["b".to_owned()]);
+        assert_eq!(rx_b.next().await, None);
+        assert_eq!(router.tenants(), 1);
+
+        let requests = subscribe_rx.collect::<Vec<_>>().await;
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].slots.len(), 2);
+        assert!(requests[1].ping.is_some());
+        assert_eq!(requests[2].slots.len(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+//! Gap detection by per-stream `sequence` of updates.
+//!
+//! Server stamps every update of the stream with sequence number increased by
+//! one, so missed updates are detected as range of skipped numbers. Updates
+//! with zero sequence (server without support) are passed as is, sequence equal
+//! or lower than the last one is treated as a new stream (reconnect).
+
+use {
+    futures::stream::{self, Stream, StreamExt},
+    std::ops::RangeInclusive,
+    yellowstone_grpc_proto::prelude::SubscribeUpdate,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SequenceUpdate {
+    Update(Box<SubscribeUpdate>),
+    /// Sequence numbers of missed updates
+    Gap(RangeInclusive<u64>),
+}
+
+#[derive(Debug, Default)]
+pub struct SequenceGapDetector {
+    last: Option<u64>,
+}
+
+impl SequenceGapDetector {
+    pub const fn new() -> Self {
+        Self { last: None }
+    }
+
+    /// Last received sequence
+    pub const fn last(&self) -> Option<u64> {
+        self.last
+    }
+
+    /// Forget last sequence, should be called on resubscribe
+    pub const fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// Add update, returns missed range before it
+    pub fn push(&mut self, sequence: u64) -> Option<RangeInclusive<u64>> {
+        if sequence == 0 {
+            return None;
+        }
+        // every stream starts from 1
+        let first = match self.last {
+            Some(last) if sequence > last => last + 1,
+            _ => 1,
+        };
+        let gap = (sequence > first).then(|| first..=sequence - 1);
+        self.last = Some(sequence);
+        gap
+    }
+}
+
+/// Emit `Gap` before the update with skipped sequence numbers
+pub fn detect_gaps<S, E>(stream: S) -> impl Stream<Item = Result<SequenceUpdate, E>>
+where
+    S: Stream<Item = Result<SubscribeUpdate, E>>,
+{
+    let mut detector = SequenceGapDetector::new();
+    stream.flat_map(move |message| {
+        stream::iter(match message {
+            Ok(update) => {
+                let gap = detector.push(update.sequence);
+                gap.map(|range| Ok(SequenceUpdate::Gap(range)))
+                    .into_iter()
+                    .chain([Ok(SequenceUpdate::Update(Box::new(update)))])
+                    .collect::<Vec<_>>()
+            }
+            Err(error) => vec![Err(error)],
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{detect_gaps, SequenceGapDetector, SequenceUpdate},
+        futures::{stream, StreamExt},
+        yellowstone_grpc_proto::prelude::SubscribeUpdate,
+    };
+
+    fn update(sequence: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            sequence,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_detector() {
+        let mut detector = SequenceGapDetector::new();
+        assert_eq!(detector.push(0), None);
+        assert_eq!(detector.push(1), None);
+        assert_eq!(detector.push(2), None);
+        assert_eq!(detector.push(5), Some(3..=4));
+        assert_eq!(detector.push(6), None);
+        // reconnect
+        assert_eq!(detector.push(1), None);
+        assert_eq!(detector.push(3), Some(2..=2));
+        detector.reset();
+        assert_eq!(detector.push(4), Some(1..=3));
+        assert_eq!(detector.last(), Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_detect_gaps() {
+        let updates = [1, 2, 4, 7].map(|sequence| Ok::<_, ()>(update(sequence)));
+        let received = detect_gaps(stream::iter(updates))
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            received,
+            vec![
+                SequenceUpdate::Update(Box::new(update(1))),
+                SequenceUpdate::Update(Box::new(update(2))),
+                SequenceUpdate::Gap(3..=3),
+                SequenceUpdate::Update(Box::new(update(4))),
+                SequenceUpdate::Gap(5..=6),
+                SequenceUpdate::Update(Box::new(update(7))),
+            ]
+        );
+    }
+}
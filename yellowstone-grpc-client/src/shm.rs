@@ -0,0 +1,179 @@
+//! Updates from the geyser plugin shared memory ring buffer.
+//!
+//! Plugin writes all processed updates without filters to the file configured
+//! with `grpc.shm`, consumers on the same host read them without gRPC and
+//! compression overhead. Filtering is done by the consumer. If the plugin
+//! compresses updates with zstd dictionaries (`grpc.shm.zstd_dictionary`),
+//! the subscriber needs the same file, see
+//! [`ShmSubscriber::with_zstd_dictionaries`].
+
+use {
+    std::{fmt, path::Path, thread, time::Duration},
+    yellowstone_grpc_proto::{
+        prelude::SubscribeUpdate,
+        prost::Message,
+        shm::{ShmError, ShmReader},
+        zstd_dict::{ZstdDictDecompressor, ZstdDictError, ZstdDictionaries},
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShmSubscriberError {
+    #[error(transparent)]
+    Shm(#[from] ShmError),
+    #[error("failed to decode update: {0}")]
+    Decode(#[from] yellowstone_grpc_proto::prost::DecodeError),
+    #[error("failed to decompress update: {0}")]
+    ZstdDict(#[from] ZstdDictError),
+}
+
+pub struct ShmSubscriber {
+    reader: ShmReader,
+    poll_interval: Duration,
+    decompressor: Option<ZstdDictDecompressor>,
+}
+
+impl fmt::Debug for ShmSubscriber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShmSubscriber")
+            .field("reader", &self.reader)
+            .field("poll_interval", &self.poll_interval)
+            .field("zstd_dictionaries", &self.decompressor.is_some())
+            .finish()
+    }
+}
+
+impl ShmSubscriber {
+    /// Open ring buffer, updates written before are skipped
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ShmSubscriberError> {
+        Ok(Self {
+            reader: ShmReader::open(path)?,
+            poll_interval: Duration::from_micros(50),
+            decompressor: None,
+        })
+    }
+
+    /// Decompress updates with dictionaries the plugin was configured with
+    pub fn with_zstd_dictionaries(
+        mut self,
+        dicts: &ZstdDictionaries,
+    ) -> Result<Self, ShmSubscriberError> {
+        self.decompressor = Some(dicts.decompressor()?);
+        Ok(self)
+    }
+
+    /// Sleep between checks in [`ShmSubscriber::recv`], default is 50µs
+    pub const fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Next update if available, on [`ShmError::Lagged`] subscriber continues
+    /// from the latest update on the next call
+    pub fn try_recv(&mut self) -> Result<Option<SubscribeUpdate>, ShmSubscriberError> {
+        match self.reader.try_read() {
+            Ok(Some(data)) => Ok(Some(match &mut self.decompressor {
+                Some(decompressor) => decompressor.decompress(&data)?,
+                None => SubscribeUpdate::decode(data.as_slice())?,
+            })),
+            Ok(None) => Ok(None),
+            Err(error) => {
+                if matches!(error, ShmError::Lagged(_)) {
+                    self.reader.skip_to_latest();
+                }
+                Err(error.into())
+            }
+        }
+    }
+
+    /// Block current thread until next update
+    pub fn recv(&mut self) -> Result<SubscribeUpdate, ShmSubscriberError> {
+        loop {
+            if let Some(update) = self.try_recv()? {
+                return Ok(update);
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{ShmSubscriber, ShmSubscriberError},
+        std::{env, fs, process},
+        yellowstone_grpc_proto::{
+            prelude::{subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdatePing},
+            prost::Message,
+            shm::{ShmError, ShmWriter},
+            zstd_dict::ZstdDictionaries,
+        },
+    };
+
+    #[test]
+    fn test_subscriber() {
+        let path = env::temp_dir().join(format!("yellowstone-grpc-client-shm-{}", process::id()));
+        let mut writer = ShmWriter::create(&path, 1024).unwrap();
+        let mut subscriber = ShmSubscriber::open(&path).unwrap();
+        assert!(subscriber.try_recv().unwrap().is_none());
+
+        let update = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Ping(SubscribeUpdatePing {})),
+            created_at: None,
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        };
+        writer.write(&update.encode_to_vec()).unwrap();
+        assert_eq!(subscriber.recv().unwrap(), update);
+
+        for _ in 0..200 {
+            writer.write(&update.encode_to_vec()).unwrap();
+        }
+        assert!(matches!(
+            subscriber.try_recv(),
+            Err(ShmSubscriberError::Shm(ShmError::Lagged(_)))
+        ));
+        assert!(subscriber.try_recv().unwrap().is_none());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_subscriber_zstd_dictionaries() {
+        let path = env::temp_dir().join(format!(
+            "yellowstone-grpc-client-shm-zstd-{}",
+            process::id()
+        ));
+        let dicts = ZstdDictionaries::default();
+        let mut compressor = dicts.compressor(3).unwrap();
+        let mut writer = ShmWriter::create(&path, 1024).unwrap();
+        let mut subscriber = ShmSubscriber::open(&path)
+            .unwrap()
+            .with_zstd_dictionaries(&dicts)
+            .unwrap();
+
+        let update = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Ping(SubscribeUpdatePing {})),
+            created_at: None,
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        };
+        writer
+            .write(&compressor.compress(&update).unwrap())
+            .unwrap();
+        assert_eq!(subscriber.recv().unwrap(), update);
+
+        // plain frames are rejected
+        writer.write(&update.encode_to_vec()).unwrap();
+        assert!(matches!(
+            subscriber.try_recv(),
+            Err(ShmSubscriberError::ZstdDict(_))
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+}
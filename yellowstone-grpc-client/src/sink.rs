@@ -0,0 +1,928 @@
+//! Staging of updates until slot is finalized for exactly-once delivery to sinks.
+//!
+//! Updates are received at `processed` / `confirmed` commitment and kept by slot.
+//! When slot is finalized the chain of its ancestors is committed in ascending
+//! order, all other staged slots below finalized slot are abandoned forks and
+//! discarded. Duplicates (e.g. replayed after reconnect with `from_slot`) are
+//! removed by [`UpdateIdentity`], slots at or below last committed slot are
+//! never committed again, so sink can persist last committed slot together with
+//! data and resume from it. Slot batches and manifests are staged like other
+//! updates, pings and filter acknowledgements are dropped.
+//!
+//! [`ordered_finalized_stream`] is stricter: every finalized slot should be a
+//! child of the previous one, otherwise [`FinalizedGap`] error is returned.
+//!
+//! [`run_finalized_sink_with_dlq`] retries failed commits with backoff and
+//! moves updates which can't be written at all (schema errors, oversized rows,
+//! see [`FinalizedSink::is_retryable`]) to a [`DeadLetterQueue`] instead of
+//! blocking the sink. Transient errors are never dead-lettered, the loop stops
+//! if the sink is still unavailable after all attempts.
+
+use {
+    futures::stream::{Stream, StreamExt},
+    std::{
+        collections::{BTreeMap, HashSet, VecDeque},
+        fmt,
+        fs::{File, OpenOptions},
+        future::Future,
+        io::{self, BufReader, Read, Write},
+        path::Path,
+        time::Duration,
+    },
+    tonic::Status,
+    yellowstone_grpc_proto::{
+        identity::UpdateIdentity,
+        prelude::{subscribe_update::UpdateOneof, SlotStatus, SubscribeUpdate},
+        prost::Message,
+    },
+};
+
+/// Downstream storage with commit of the whole finalized slot at once
+pub trait FinalizedSink {
+    type Error;
+
+    /// Called once per finalized slot in ascending order, slot without updates
+    /// is committed too so sink can record progress
+    fn commit(
+        &mut self,
+        slot: u64,
+        updates: Vec<SubscribeUpdate>,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Transient error (sink unavailable, timeout) is retried with backoff,
+    /// other errors are permanent and updates of the failed commit are moved
+    /// to the dead letter queue by [`run_finalized_sink_with_dlq`]. All errors
+    /// are transient by default.
+    fn is_retryable(&self, _error: &Self::Error) -> bool {
+        true
+    }
+}
+
+/// Retries of commits in [`run_finalized_sink_with_dlq`]
+#[derive(Debug, Clone, Copy)]
+pub struct SinkRetryConfig {
+    /// Attempts of every commit with transient errors
+    pub max_attempts: u32,
+    pub backoff_min: Duration,
+    pub backoff_max: Duration,
+}
+
+impl Default for SinkRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff_min: Duration::from_millis(100),
+            backoff_max: Duration::from_secs(10),
+        }
+    }
+}
+
+impl SinkRetryConfig {
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.backoff_min
+            .saturating_mul(factor)
+            .min(self.backoff_max)
+    }
+}
+
+/// Update which could not be written by the sink
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadLetter {
+    pub slot: u64,
+    pub update: SubscribeUpdate,
+    /// Last error of the sink, permanent according to [`FinalizedSink::is_retryable`]
+    pub error: String,
+    pub attempts: u32,
+}
+
+/// Destination of updates rejected by the sink
+pub trait DeadLetterQueue {
+    type Error;
+
+    fn push(&mut self, letter: DeadLetter) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError<E> {
+    #[error("gRPC status: {0}")]
+    Status(#[from] Status),
+    #[error("failed to commit slot {slot}: {error}")]
+    Commit { slot: u64, error: E },
+}
+
+/// Finalized slot is not connected to the last committed slot, updates of the
+/// missed slots can't be recovered from the stream
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "finalized slot {slot} with parent {parent:?} does not follow committed slot {last_committed}"
+)]
+pub struct FinalizedGap {
+    pub last_committed: u64,
+    pub slot: u64,
+    pub parent: Option<u64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FinalizedStreamError {
+    #[error("gRPC status: {0}")]
+    Status(#[from] Status),
+    #[error(transparent)]
+    Gap(#[from] FinalizedGap),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeadLetterSinkError<E, D> {
+    #[error("gRPC status: {0}")]
+    Status(#[from] Status),
+    #[error("failed to commit slot {slot}: {error}")]
+    Commit { slot: u64, error: E },
+    #[error("failed to write dead letter of slot {slot}: {error}")]
+    DeadLetter { slot: u64, error: D },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FinalizedSlot {
+    pub slot: u64,
+    pub updates: Vec<SubscribeUpdate>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct StagedSlot {
+    parent: Option<u64>,
+    identities: HashSet<UpdateIdentity>,
+    updates: Vec<SubscribeUpdate>,
+}
+
+/// Updates staged by slot until finalization
+#[derive(Debug, Default, Clone)]
+pub struct FinalizedStage {
+    slots: BTreeMap<u64, StagedSlot>,
+    last_committed: Option<u64>,
+}
+
+impl FinalizedStage {
+    /// `last_committed` is the slot recorded by the sink, older slots are ignored
+    pub const fn new(last_committed: Option<u64>) -> Self {
+        Self {
+            slots: BTreeMap::new(),
+            last_committed,
+        }
+    }
+
+    pub const fn last_committed(&self) -> Option<u64> {
+        self.last_committed
+    }
+
+    /// Number of staged slots
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    fn is_committed(&self, slot: u64) -> bool {
+        self.last_committed
+            .is_some_and(|committed| slot <= committed)
+    }
+
+    /// Stage update, returns slots ready for commit in ascending order
+    pub fn push(&mut self, update: SubscribeUpdate) -> Vec<(u64, Vec<SubscribeUpdate>)> {
+        let Some(identity) = update.identity() else {
+            return vec![];
+        };
+        let slot = identity.slot();
+        if self.is_committed(slot) {
+            return vec![];
+        }
+
+        if let Some(UpdateOneof::Slot(msg)) = &update.update_oneof {
+            if let Some(parent) = msg.parent {
+                self.slots.entry(slot).or_default().parent = Some(parent);
+            }
+            return match SlotStatus::try_from(msg.status) {
+                Ok(SlotStatus::SlotFinalized) => self.finalize(slot),
+                Ok(SlotStatus::SlotDead) => {
+                    self.slots.remove(&slot);
+                    vec![]
+                }
+                _ => vec![],
+            };
+        }
+
+        let staged = self.slots.entry(slot).or_default();
+        if staged.identities.insert(identity) {
+            staged.updates.push(update);
+        }
+        vec![]
+    }
+
+    /// Commit finalized slot with known ancestors and discard forks
+    pub fn finalize(&mut self, slot: u64) -> Vec<(u64, Vec<SubscribeUpdate>)> {
+        if self.is_committed(slot) {
+            return vec![];
+        }
+        let (_parent, chain) = self.resolve_chain(slot);
+        self.commit_chain(slot, chain)
+    }
+
+    /// Same as [`FinalizedStage::finalize`] but parent of the first committed
+    /// slot should be the last committed slot
+    pub fn finalize_strict(&mut self, slot: u64) -> Result<Vec<FinalizedSlot>, FinalizedGap> {
+        if self.is_committed(slot) {
+            return Ok(vec![]);
+        }
+        let (parent, chain) = self.resolve_chain(slot);
+        if let (Some(last_committed), Some(first)) = (self.last_committed, chain.last()) {
+            if parent != Some(last_committed) {
+                return Err(FinalizedGap {
+                    last_committed,
+                    slot: *first,
+                    parent,
+                });
+            }
+        }
+        Ok(self
+            .commit_chain(slot, chain)
+            .into_iter()
+            .map(|(slot, updates)| FinalizedSlot { slot, updates })
+            .collect())
+    }
+
+    /// Returns parent of the first slot in the chain, if known, and the chain
+    /// in descending order
+    fn resolve_chain(&self, slot: u64) -> (Option<u64>, Vec<u64>) {
+        let mut chain = vec![slot];
+        let mut current = slot;
+        let mut parent = None;
+        while let Some(slot_parent) = self.slots.get(&current).and_then(|staged| staged.parent) {
+            parent = Some(slot_parent);
+            if self.is_committed(slot_parent) || !self.slots.contains_key(&slot_parent) {
+                break;
+            }
+            chain.push(slot_parent);
+            current = slot_parent;
+            parent = None;
+        }
+        (parent, chain)
+    }
+
+    fn commit_chain(&mut self, slot: u64, chain: Vec<u64>) -> Vec<(u64, Vec<SubscribeUpdate>)> {
+        let mut commits = Vec::with_capacity(chain.len());
+        for slot in chain.into_iter().rev() {
+            let updates = self
+                .slots
+                .remove(&slot)
+                .map(|staged| staged.updates)
+                .unwrap_or_default();
+            commits.push((slot, updates));
+        }
+
+        // everything left below finalized slot is not on the rooted chain
+        self.slots = self.slots.split_off(&(slot + 1));
+        self.last_committed = Some(slot);
+        commits
+    }
+
+    /// Stage update, finalized slots are returned only if they continue the
+    /// chain of committed slots
+    pub fn push_strict(
+        &mut self,
+        update: SubscribeUpdate,
+    ) -> Result<Vec<FinalizedSlot>, FinalizedGap> {
+        if let Some(UpdateOneof::Slot(msg)) = &update.update_oneof {
+            if msg.status == SlotStatus::SlotFinalized as i32 && !self.is_committed(msg.slot) {
+                if let Some(parent) = msg.parent {
+                    self.slots.entry(msg.slot).or_default().parent = Some(parent);
+                }
+                return self.finalize_strict(msg.slot);
+            }
+        }
+        self.push(update);
+        Ok(vec![])
+    }
+}
+
+/// Feed updates from the stream to the sink, returns last committed slot when
+/// stream is finished
+pub async fn run_finalized_sink<S, K>(
+    stream: S,
+    sink: &mut K,
+    last_committed: Option<u64>,
+) -> Result<Option<u64>, SinkError<K::Error>>
+where
+    S: Stream<Item = Result<SubscribeUpdate, Status>>,
+    K: FinalizedSink,
+{
+    let mut stage = FinalizedStage::new(last_committed);
+    let mut stream = std::pin::pin!(stream);
+    while let Some(message) = stream.next().await {
+        for (slot, updates) in stage.push(message?) {
+            sink.commit(slot, updates)
+                .await
+                .map_err(|error| SinkError::Commit { slot, error })?;
+        }
+    }
+    Ok(stage.last_committed())
+}
+
+/// Same as [`run_finalized_sink`] but commit of the slot is retried with
+/// backoff up to `retry.max_attempts` times while the error is transient. If
+/// the slot fails with a permanent error, its updates are committed one by one
+/// and updates with permanent errors are pushed to the dead letter queue with
+/// the error. Slot without committed updates is committed empty to record
+/// progress. Transient error after all attempts means that the sink is
+/// unavailable and stops the loop, nothing is dead-lettered in that case.
+pub async fn run_finalized_sink_with_dlq<S, K, Q>(
+    stream: S,
+    sink: &mut K,
+    dlq: &mut Q,
+    last_committed: Option<u64>,
+    retry: SinkRetryConfig,
+) -> Result<Option<u64>, DeadLetterSinkError<K::Error, Q::Error>>
+where
+    S: Stream<Item = Result<SubscribeUpdate, Status>>,
+    K: FinalizedSink,
+    K::Error: fmt::Display,
+    Q: DeadLetterQueue,
+{
+    let mut stage = FinalizedStage::new(last_committed);
+    let mut stream = std::pin::pin!(stream);
+    while let Some(message) = stream.next().await {
+        for (slot, updates) in stage.push(message?) {
+            match commit_with_retries(sink, slot, &updates, retry).await {
+                Ok(()) => continue,
+                Err(failure) if failure.retryable => {
+                    return Err(DeadLetterSinkError::Commit {
+                        slot,
+                        error: failure.error,
+                    })
+                }
+                Err(_failure) => {}
+            }
+
+            let mut committed = false;
+            for update in updates {
+                match commit_with_retries(sink, slot, std::slice::from_ref(&update), retry).await {
+                    Ok(()) => committed = true,
+                    Err(failure) if failure.retryable => {
+                        return Err(DeadLetterSinkError::Commit {
+                            slot,
+                            error: failure.error,
+                        })
+                    }
+                    Err(failure) => {
+                        let letter = DeadLetter {
+                            slot,
+                            update,
+                            error: failure.error.to_string(),
+                            attempts: failure.attempts,
+                        };
+                        dlq.push(letter)
+                            .await
+                            .map_err(|error| DeadLetterSinkError::DeadLetter { slot, error })?;
+                    }
+                }
+            }
+            if !committed {
+                commit_with_retries(sink, slot, &[], retry)
+                    .await
+                    .map_err(|failure| DeadLetterSinkError::Commit {
+                        slot,
+                        error: failure.error,
+                    })?;
+            }
+        }
+    }
+    Ok(stage.last_committed())
+}
+
+struct CommitFailure<E> {
+    error: E,
+    attempts: u32,
+    retryable: bool,
+}
+
+async fn commit_with_retries<K: FinalizedSink>(
+    sink: &mut K,
+    slot: u64,
+    updates: &[SubscribeUpdate],
+    retry: SinkRetryConfig,
+) -> Result<(), CommitFailure<K::Error>> {
+    let mut attempts = 1;
+    loop {
+        let error = match sink.commit(slot, updates.to_vec()).await {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+        let retryable = sink.is_retryable(&error);
+        if !retryable || attempts >= retry.max_attempts {
+            return Err(CommitFailure {
+                error,
+                attempts,
+                retryable,
+            });
+        }
+        tokio::time::sleep(retry.delay(attempts)).await;
+        attempts += 1;
+    }
+}
+
+/// Dead letters appended to the file. Every record is slot (u64), attempts
+/// (u32), length-prefixed error and length-prefixed encoded `SubscribeUpdate`,
+/// integers are little endian. Writes are synchronous, dead letters are
+/// expected to be rare.
+#[derive(Debug)]
+pub struct DeadLetterFile {
+    file: File,
+}
+
+impl DeadLetterFile {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+
+    fn encode(letter: &DeadLetter) -> Vec<u8> {
+        let update = letter.update.encode_to_vec();
+        let mut data = Vec::with_capacity(20 + letter.error.len() + update.len());
+        data.extend_from_slice(&letter.slot.to_le_bytes());
+        data.extend_from_slice(&letter.attempts.to_le_bytes());
+        data.extend_from_slice(&(letter.error.len() as u32).to_le_bytes());
+        data.extend_from_slice(letter.error.as_bytes());
+        data.extend_from_slice(&(update.len() as u32).to_le_bytes());
+        data.extend_from_slice(&update);
+        data
+    }
+
+    /// Read all dead letters of the file, e.g. to replay them after the sink
+    /// is fixed
+    pub fn read_all(path: impl AsRef<Path>) -> io::Result<Vec<DeadLetter>> {
+        fn read_bytes<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+            let mut bytes = [0; N];
+            reader.read_exact(&mut bytes)?;
+            Ok(bytes)
+        }
+
+        fn read_vec<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+            let len = u32::from_le_bytes(read_bytes(reader)?);
+            let mut data = vec![0; len as usize];
+            reader.read_exact(&mut data)?;
+            Ok(data)
+        }
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut letters = vec![];
+        loop {
+            let slot = match read_bytes(&mut reader) {
+                Ok(bytes) => u64::from_le_bytes(bytes),
+                Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(error) => return Err(error),
+            };
+            let attempts = u32::from_le_bytes(read_bytes(&mut reader)?);
+            let error = String::from_utf8(read_vec(&mut reader)?)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            let update = SubscribeUpdate::decode(read_vec(&mut reader)?.as_slice())
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            letters.push(DeadLetter {
+                slot,
+                update,
+                error,
+                attempts,
+            });
+        }
+        Ok(letters)
+    }
+}
+
+impl DeadLetterQueue for DeadLetterFile {
+    type Error = io::Error;
+
+    fn push(&mut self, letter: DeadLetter) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        let result = self
+            .file
+            .write_all(&Self::encode(&letter))
+            .and_then(|()| self.file.flush());
+        async move { result }
+    }
+}
+
+/// Stream of finalized slots in strictly increasing order, subscription should
+/// include slots with `processed` or `confirmed` commitment to track parents.
+/// Stream is finished after the first error.
+pub fn ordered_finalized_stream<S>(
+    stream: S,
+    last_committed: Option<u64>,
+) -> impl Stream<Item = Result<FinalizedSlot, FinalizedStreamError>>
+where
+    S: Stream<Item = Result<SubscribeUpdate, Status>>,
+{
+    let state = (
+        Box::pin(stream),
+        FinalizedStage::new(last_committed),
+        VecDeque::new(),
+        false,
+    );
+    futures::stream::unfold(
+        state,
+        |(mut stream, mut stage, mut queue, mut failed)| async move {
+            loop {
+                if let Some(slot) = queue.pop_front() {
+                    return Some((Ok(slot), (stream, stage, queue, failed)));
+                }
+                if failed {
+                    return None;
+                }
+
+                let result = match stream.next().await? {
+                    Ok(update) => stage.push_strict(update).map_err(Into::into),
+                    Err(status) => Err(status.into()),
+                };
+                match result {
+                    Ok(slots) => queue.extend(slots),
+                    Err(error) => {
+                        failed = true;
+                        return Some((Err(error), (stream, stage, queue, failed)));
+                    }
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            ordered_finalized_stream, run_finalized_sink, run_finalized_sink_with_dlq, DeadLetter,
+            DeadLetterFile, DeadLetterQueue, DeadLetterSinkError, FinalizedGap, FinalizedSink,
+            FinalizedStage, FinalizedStreamError, SinkError, SinkRetryConfig,
+        },
+        futures::{stream, StreamExt},
+        std::{future::Future, time::Duration},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SlotStatus, SubscribeUpdate, SubscribeUpdateEntry,
+            SubscribeUpdateSlot,
+        },
+    };
+
+    fn slot(slot: u64, parent: Option<u64>, status: SlotStatus) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                parent,
+                status: status as i32,
+                dead_error: None,
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn entry(slot: u64, index: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Entry(SubscribeUpdateEntry {
+                slot,
+                index,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn committed(commits: Vec<(u64, Vec<SubscribeUpdate>)>) -> Vec<(u64, usize)> {
+        commits
+            .into_iter()
+            .map(|(slot, updates)| (slot, updates.len()))
+            .collect()
+    }
+
+    #[test]
+    fn test_forks() {
+        let mut stage = FinalizedStage::default();
+        for update in [
+            slot(10, Some(9), SlotStatus::SlotProcessed),
+            entry(10, 0),
+            entry(10, 1),
+            // fork from 10
+            slot(11, Some(10), SlotStatus::SlotProcessed),
+            entry(11, 0),
+            slot(12, Some(10), SlotStatus::SlotProcessed),
+            entry(12, 0),
+            // duplicate
+            entry(12, 0),
+            slot(13, Some(12), SlotStatus::SlotProcessed),
+            entry(13, 0),
+            slot(14, Some(13), SlotStatus::SlotProcessed),
+            entry(14, 0),
+        ] {
+            assert!(stage.push(update).is_empty());
+        }
+
+        let commits = stage.push(slot(13, None, SlotStatus::SlotFinalized));
+        assert_eq!(committed(commits), vec![(10, 2), (12, 1), (13, 1)]);
+        assert_eq!(stage.last_committed(), Some(13));
+        assert_eq!(stage.len(), 1);
+
+        // replayed updates are ignored
+        assert!(stage.push(entry(12, 1)).is_empty());
+        assert!(stage
+            .push(slot(12, None, SlotStatus::SlotFinalized))
+            .is_empty());
+        assert_eq!(stage.len(), 1);
+
+        // dead slot is discarded
+        stage.push(slot(15, Some(14), SlotStatus::SlotProcessed));
+        stage.push(entry(15, 0));
+        stage.push(slot(15, None, SlotStatus::SlotDead));
+        let commits = stage.push(slot(14, None, SlotStatus::SlotFinalized));
+        assert_eq!(committed(commits), vec![(14, 1)]);
+        assert!(stage.is_empty());
+    }
+
+    #[derive(Default)]
+    struct VecSink {
+        commits: Vec<(u64, usize)>,
+    }
+
+    impl FinalizedSink for VecSink {
+        type Error = &'static str;
+
+        fn commit(
+            &mut self,
+            slot: u64,
+            updates: Vec<SubscribeUpdate>,
+        ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+            let result = if slot == 7 {
+                Err("slot 7")
+            } else {
+                self.commits.push((slot, updates.len()));
+                Ok(())
+            };
+            async move { result }
+        }
+    }
+
+    #[test]
+    fn test_finalize_strict_gap() {
+        let mut stage = FinalizedStage::new(Some(10));
+        stage.push(slot(12, Some(11), SlotStatus::SlotProcessed));
+        stage.push(entry(12, 0));
+
+        // gap is reported without touching the stage
+        let result = stage.push_strict(slot(12, None, SlotStatus::SlotFinalized));
+        assert!(matches!(
+            result,
+            Err(FinalizedGap {
+                last_committed: 10,
+                slot: 12,
+                parent: Some(11),
+            })
+        ));
+        assert_eq!(stage.last_committed(), Some(10));
+        assert_eq!(stage.len(), 1);
+
+        // missed slot arrives, chain is committed
+        stage.push(slot(11, Some(10), SlotStatus::SlotProcessed));
+        stage.push(entry(11, 0));
+        let slots = stage
+            .push_strict(slot(12, None, SlotStatus::SlotFinalized))
+            .expect("no gap")
+            .into_iter()
+            .map(|item| (item.slot, item.updates.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(slots, vec![(11, 1), (12, 1)]);
+        assert_eq!(stage.last_committed(), Some(12));
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let mut sink = VecSink::default();
+        let updates = vec![
+            Ok(entry(4, 0)),
+            Ok(slot(5, Some(4), SlotStatus::SlotProcessed)),
+            Ok(entry(5, 0)),
+            Ok(slot(5, None, SlotStatus::SlotFinalized)),
+        ];
+        let last = run_finalized_sink(stream::iter(updates), &mut sink, Some(4))
+            .await
+            .unwrap();
+        assert_eq!(last, Some(5));
+        assert_eq!(sink.commits, vec![(5, 1)]);
+
+        let updates = vec![Ok(slot(7, None, SlotStatus::SlotFinalized))];
+        let error = run_finalized_sink(stream::iter(updates), &mut sink, last)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, SinkError::Commit { slot: 7, .. }));
+    }
+
+    /// Rejects entries with index 13, unavailable for slot 9 and for the next
+    /// `unavailable` commits
+    #[derive(Default)]
+    struct RejectingSink {
+        attempts: usize,
+        unavailable: usize,
+        commits: Vec<(u64, Vec<u64>)>,
+    }
+
+    impl FinalizedSink for RejectingSink {
+        type Error = &'static str;
+
+        fn commit(
+            &mut self,
+            slot: u64,
+            updates: Vec<SubscribeUpdate>,
+        ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+            self.attempts += 1;
+            let indices = updates
+                .iter()
+                .map(|update| match &update.update_oneof {
+                    Some(UpdateOneof::Entry(entry)) => entry.index,
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>();
+            let result = if slot == 9 || self.unavailable > 0 {
+                self.unavailable = self.unavailable.saturating_sub(1);
+                Err("unavailable")
+            } else if indices.contains(&13) {
+                Err("oversized row")
+            } else {
+                self.commits.push((slot, indices));
+                Ok(())
+            };
+            async move { result }
+        }
+
+        fn is_retryable(&self, error: &Self::Error) -> bool {
+            *error == "unavailable"
+        }
+    }
+
+    impl DeadLetterQueue for Vec<DeadLetter> {
+        type Error = ();
+
+        fn push(
+            &mut self,
+            letter: DeadLetter,
+        ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+            Vec::push(self, letter);
+            async { Ok(()) }
+        }
+    }
+
+    const RETRY: SinkRetryConfig = SinkRetryConfig {
+        max_attempts: 3,
+        backoff_min: Duration::from_millis(1),
+        backoff_max: Duration::from_millis(2),
+    };
+
+    #[test]
+    fn test_retry_delay() {
+        let retry = SinkRetryConfig::default();
+        assert_eq!(retry.delay(1), Duration::from_millis(100));
+        assert_eq!(retry.delay(3), Duration::from_millis(400));
+        assert_eq!(retry.delay(100), Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_run_with_dlq() {
+        let mut sink = RejectingSink::default();
+        let mut dlq = vec![];
+        let updates = vec![
+            Ok(slot(5, Some(4), SlotStatus::SlotProcessed)),
+            Ok(entry(5, 12)),
+            Ok(entry(5, 13)),
+            Ok(entry(5, 14)),
+            Ok(slot(5, None, SlotStatus::SlotFinalized)),
+            Ok(slot(6, Some(5), SlotStatus::SlotProcessed)),
+            Ok(entry(6, 13)),
+            Ok(slot(6, None, SlotStatus::SlotFinalized)),
+        ];
+        let last =
+            run_finalized_sink_with_dlq(stream::iter(updates), &mut sink, &mut dlq, None, RETRY)
+                .await
+                .unwrap();
+        assert_eq!(last, Some(6));
+        assert_eq!(
+            sink.commits,
+            vec![(5, vec![12]), (5, vec![14]), (6, vec![])]
+        );
+        // permanent errors are not retried, slot 5: 1 + 3, slot 6: 1 + 1 + 1
+        assert_eq!(sink.attempts, 7);
+        let rejected = dlq
+            .iter()
+            .map(|letter| (letter.slot, letter.error.as_str(), letter.attempts))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rejected,
+            vec![(5, "oversized row", 1), (6, "oversized row", 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_with_dlq_transient() {
+        // recovered before the last attempt
+        let mut sink = RejectingSink {
+            unavailable: 2,
+            ..Default::default()
+        };
+        let mut dlq = vec![];
+        let updates = vec![
+            Ok(slot(5, Some(4), SlotStatus::SlotProcessed)),
+            Ok(entry(5, 12)),
+            Ok(slot(5, None, SlotStatus::SlotFinalized)),
+        ];
+        let last =
+            run_finalized_sink_with_dlq(stream::iter(updates), &mut sink, &mut dlq, None, RETRY)
+                .await
+                .unwrap();
+        assert_eq!(last, Some(5));
+        assert_eq!(sink.commits, vec![(5, vec![12])]);
+        assert_eq!(sink.attempts, 3);
+        assert!(dlq.is_empty());
+
+        // unavailable sink stops the loop, updates are not dead-lettered
+        let updates = vec![
+            Ok(slot(9, Some(5), SlotStatus::SlotProcessed)),
+            Ok(entry(9, 0)),
+            Ok(entry(9, 13)),
+            Ok(slot(9, None, SlotStatus::SlotFinalized)),
+        ];
+        let error =
+            run_finalized_sink_with_dlq(stream::iter(updates), &mut sink, &mut dlq, last, RETRY)
+                .await
+                .unwrap_err();
+        assert!(matches!(
+            error,
+            DeadLetterSinkError::Commit {
+                slot: 9,
+                error: "unavailable"
+            }
+        ));
+        assert_eq!(sink.attempts, 6);
+        assert!(dlq.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_file() {
+        let path = std::env::temp_dir().join(format!("dlq-{}.bin", std::process::id()));
+        let letters = vec![
+            DeadLetter {
+                slot: 5,
+                update: entry(5, 13),
+                error: "oversized row".to_owned(),
+                attempts: 3,
+            },
+            DeadLetter {
+                slot: 6,
+                update: entry(6, 1),
+                error: String::new(),
+                attempts: 1,
+            },
+        ];
+        let mut file = DeadLetterFile::open(&path).unwrap();
+        for letter in letters.clone() {
+            DeadLetterQueue::push(&mut file, letter).await.unwrap();
+        }
+        assert_eq!(DeadLetterFile::read_all(&path).unwrap(), letters);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ordered_stream() {
+        let updates = vec![
+            Ok(slot(11, Some(10), SlotStatus::SlotProcessed)),
+            Ok(entry(11, 0)),
+            Ok(slot(12, Some(11), SlotStatus::SlotProcessed)),
+            Ok(entry(12, 0)),
+            Ok(slot(12, None, SlotStatus::SlotFinalized)),
+            Ok(slot(11, None, SlotStatus::SlotFinalized)),
+            // slot 13 is missed
+            Ok(slot(14, Some(13), SlotStatus::SlotProcessed)),
+            Ok(slot(14, None, SlotStatus::SlotFinalized)),
+            Ok(slot(15, Some(14), SlotStatus::SlotProcessed)),
+        ];
+        let items = ordered_finalized_stream(stream::iter(updates), Some(10))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(items.len(), 3);
+        let slots = items[..2]
+            .iter()
+            .map(|item| {
+                let item = item.as_ref().unwrap();
+                (item.slot, item.updates.len())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(slots, vec![(11, 1), (12, 1)]);
+        assert!(matches!(
+            &items[2],
+            Err(FinalizedStreamError::Gap(FinalizedGap {
+                last_committed: 12,
+                slot: 14,
+                parent: Some(13),
+            }))
+        ));
+    }
+}
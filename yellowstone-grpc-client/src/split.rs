@@ -0,0 +1,455 @@
+//! Splitting of subscription into channels by update type.
+//!
+//! Every channel has own capacity and policy for full channel, so slow
+//! processing of one type doesn't delay others (unless [`ChannelPolicy::Block`]
+//! is used). Transactions channel includes transactions statuses, blocks
+//! channel includes blocks meta, entries and slot batches, slots channel
+//! includes slot manifests, pings are not delivered. Filter update
+//! acknowledgements are sent to every channel, so updates after the
+//! acknowledgement in any channel are matched by the new filters.
+
+use {
+    futures::{
+        future::poll_fn,
+        stream::{Stream, StreamExt},
+        task::AtomicWaker,
+    },
+    std::{
+        collections::{HashMap, VecDeque},
+        future::Future,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+    },
+    tonic::Status,
+    yellowstone_grpc_proto::prelude::{subscribe_update::UpdateOneof, SubscribeUpdate},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelPolicy {
+    /// Wait for free space, delays all other channels
+    Block,
+    /// Drop the oldest update when channel is full
+    DropOldest,
+    /// Replace pending update of the same account, drop the oldest when full
+    Coalesce,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelConfig {
+    pub capacity: usize,
+    pub policy: ChannelPolicy,
+}
+
+impl ChannelConfig {
+    pub const fn new(capacity: usize, policy: ChannelPolicy) -> Self {
+        Self { capacity, policy }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SplitConfig {
+    pub accounts: ChannelConfig,
+    pub transactions: ChannelConfig,
+    pub slots: ChannelConfig,
+    pub blocks: ChannelConfig,
+}
+
+impl Default for SplitConfig {
+    /// Slots are never dropped, accounts are coalesced
+    fn default() -> Self {
+        Self {
+            accounts: ChannelConfig::new(100_000, ChannelPolicy::Coalesce),
+            transactions: ChannelConfig::new(100_000, ChannelPolicy::DropOldest),
+            slots: ChannelConfig::new(10_000, ChannelPolicy::Block),
+            blocks: ChannelConfig::new(1_000, ChannelPolicy::DropOldest),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChannelState {
+    queue: VecDeque<SubscribeUpdate>,
+    /// Sequence number of the first update in the queue
+    head: u64,
+    /// Pending account updates by pubkey, for coalescing
+    keys: HashMap<Vec<u8>, u64>,
+    dropped: u64,
+    closed: bool,
+    receiver_dropped: bool,
+}
+
+impl ChannelState {
+    fn account_key(update: &SubscribeUpdate) -> Option<&[u8]> {
+        match &update.update_oneof {
+            Some(UpdateOneof::Account(msg)) => msg.account.as_ref().map(|a| a.pubkey.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn pop(&mut self) -> Option<SubscribeUpdate> {
+        let update = self.queue.pop_front()?;
+        if let Some(key) = Self::account_key(&update) {
+            if self.keys.get(key) == Some(&self.head) {
+                self.keys.remove(key);
+            }
+        }
+        self.head += 1;
+        Some(update)
+    }
+
+    fn push(&mut self, update: SubscribeUpdate, config: ChannelConfig) {
+        if config.policy == ChannelPolicy::Coalesce {
+            if let Some(key) = Self::account_key(&update) {
+                if let Some(seq) = self.keys.get(key) {
+                    self.queue[(seq - self.head) as usize] = update;
+                    return;
+                }
+            }
+        }
+
+        if self.queue.len() >= config.capacity.max(1) {
+            self.pop();
+            self.dropped += 1;
+        }
+        if config.policy == ChannelPolicy::Coalesce {
+            if let Some(key) = Self::account_key(&update) {
+                let seq = self.head + self.queue.len() as u64;
+                self.keys.insert(key.to_vec(), seq);
+            }
+        }
+        self.queue.push_back(update);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Channel {
+    state: Mutex<ChannelState>,
+    rx_waker: AtomicWaker,
+    tx_waker: AtomicWaker,
+}
+
+impl Channel {
+    fn poll_send(
+        &self,
+        cx: &mut Context<'_>,
+        update: &mut Option<SubscribeUpdate>,
+        config: ChannelConfig,
+    ) -> Poll<()> {
+        if config.policy == ChannelPolicy::Block {
+            self.tx_waker.register(cx.waker());
+        }
+
+        let mut state = self.state.lock().expect("unpoisoned mutex");
+        if state.receiver_dropped {
+            return Poll::Ready(());
+        }
+        if config.policy == ChannelPolicy::Block && state.queue.len() >= config.capacity.max(1) {
+            return Poll::Pending;
+        }
+        if let Some(update) = update.take() {
+            state.push(update, config);
+        }
+        drop(state);
+
+        self.rx_waker.wake();
+        Poll::Ready(())
+    }
+
+    fn close(&self) {
+        self.state.lock().expect("unpoisoned mutex").closed = true;
+        self.rx_waker.wake();
+    }
+}
+
+/// Stream of updates of one type
+#[derive(Debug)]
+pub struct SplitReceiver {
+    channel: Arc<Channel>,
+}
+
+impl SplitReceiver {
+    /// Number of updates dropped because of full channel
+    pub fn dropped(&self) -> u64 {
+        self.channel.state.lock().expect("unpoisoned mutex").dropped
+    }
+
+    /// Number of pending updates
+    pub fn len(&self) -> usize {
+        self.channel
+            .state
+            .lock()
+            .expect("unpoisoned mutex")
+            .queue
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Stream for SplitReceiver {
+    type Item = SubscribeUpdate;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.channel.rx_waker.register(cx.waker());
+        let mut state = self.channel.state.lock().expect("unpoisoned mutex");
+        match state.pop() {
+            Some(update) => {
+                drop(state);
+                self.channel.tx_waker.wake();
+                Poll::Ready(Some(update))
+            }
+            None if state.closed => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for SplitReceiver {
+    fn drop(&mut self) {
+        let mut state = self.channel.state.lock().expect("unpoisoned mutex");
+        state.receiver_dropped = true;
+        state.queue.clear();
+        drop(state);
+        self.channel.tx_waker.wake();
+    }
+}
+
+#[derive(Debug)]
+pub struct SplitReceivers {
+    pub accounts: SplitReceiver,
+    pub transactions: SplitReceiver,
+    pub slots: SplitReceiver,
+    pub blocks: SplitReceiver,
+}
+
+/// Channels are closed when the driver is finished or dropped
+struct Senders {
+    config: SplitConfig,
+    accounts: Arc<Channel>,
+    transactions: Arc<Channel>,
+    slots: Arc<Channel>,
+    blocks: Arc<Channel>,
+}
+
+impl Senders {
+    fn channels(&self) -> [(&Channel, ChannelConfig); 4] {
+        [
+            (&self.accounts, self.config.accounts),
+            (&self.transactions, self.config.transactions),
+            (&self.slots, self.config.slots),
+            (&self.blocks, self.config.blocks),
+        ]
+    }
+
+    fn route(&self, update: &SubscribeUpdate) -> Option<(&Channel, ChannelConfig)> {
+        Some(match update.update_oneof.as_ref()? {
+            UpdateOneof::Account(_) => (&self.accounts, self.config.accounts),
+            UpdateOneof::Transaction(_) | UpdateOneof::TransactionStatus(_) => {
+                (&self.transactions, self.config.transactions)
+            }
+            UpdateOneof::Slot(_) | UpdateOneof::SlotManifest(_) => (&self.slots, self.config.slots),
+            UpdateOneof::Block(_)
+            | UpdateOneof::BlockMeta(_)
+            | UpdateOneof::Entry(_)
+            | UpdateOneof::SlotBatch(_) => (&self.blocks, self.config.blocks),
+            // acknowledgements are sent to every channel
+            UpdateOneof::Ping(_) | UpdateOneof::Pong(_) | UpdateOneof::FilterApplied(_) => {
+                return None
+            }
+        })
+    }
+
+    async fn send(&self, update: SubscribeUpdate) {
+        if matches!(update.update_oneof, Some(UpdateOneof::FilterApplied(_))) {
+            for (channel, config) in self.channels() {
+                Self::send_to(channel, config, update.clone()).await;
+            }
+            return;
+        }
+
+        if let Some((channel, config)) = self.route(&update) {
+            Self::send_to(channel, config, update).await;
+        }
+    }
+
+    async fn send_to(channel: &Channel, config: ChannelConfig, update: SubscribeUpdate) {
+        let mut update = Some(update);
+        poll_fn(|cx| channel.poll_send(cx, &mut update, config)).await
+    }
+}
+
+impl Drop for Senders {
+    fn drop(&mut self) {
+        for (channel, _config) in self.channels() {
+            channel.close();
+        }
+    }
+}
+
+/// Split stream into channels, returned future should be polled (e.g. spawned)
+/// to receive updates, it's finished on the first error
+pub fn split_updates<S>(
+    stream: S,
+    config: SplitConfig,
+) -> (SplitReceivers, impl Future<Output = Result<(), Status>>)
+where
+    S: Stream<Item = Result<SubscribeUpdate, Status>>,
+{
+    let senders = Senders {
+        config,
+        accounts: Arc::default(),
+        transactions: Arc::default(),
+        slots: Arc::default(),
+        blocks: Arc::default(),
+    };
+    let receivers = SplitReceivers {
+        accounts: SplitReceiver {
+            channel: Arc::clone(&senders.accounts),
+        },
+        transactions: SplitReceiver {
+            channel: Arc::clone(&senders.transactions),
+        },
+        slots: SplitReceiver {
+            channel: Arc::clone(&senders.slots),
+        },
+        blocks: SplitReceiver {
+            channel: Arc::clone(&senders.blocks),
+        },
+    };
+
+    let driver = async move {
+        let mut stream = std::pin::pin!(stream);
+        while let Some(message) = stream.next().await {
+            senders.send(message?).await;
+        }
+        Ok(())
+    };
+    (receivers, driver)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{split_updates, ChannelConfig, ChannelPolicy, SplitConfig},
+        futures::{stream, StreamExt},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateAccount,
+            SubscribeUpdateAccountInfo, SubscribeUpdateFilterApplied, SubscribeUpdateSlot,
+            SubscribeUpdateTransactionStatus,
+        },
+    };
+
+    fn account(pubkey: u8, write_version: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: vec![pubkey; 32],
+                    write_version,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn tx_status(slot: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::TransactionStatus(
+                SubscribeUpdateTransactionStatus {
+                    slot,
+                    ..Default::default()
+                },
+            )),
+            ..Default::default()
+        }
+    }
+
+    fn slot(slot: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn write_version(update: &SubscribeUpdate) -> (u8, u64) {
+        match &update.update_oneof {
+            Some(UpdateOneof::Account(msg)) => {
+                let account = msg.account.as_ref().unwrap();
+                (account.pubkey[0], account.write_version)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_split() {
+        let config = SplitConfig {
+            accounts: ChannelConfig::new(2, ChannelPolicy::Coalesce),
+            transactions: ChannelConfig::new(1, ChannelPolicy::DropOldest),
+            slots: ChannelConfig::new(1, ChannelPolicy::Block),
+            ..Default::default()
+        };
+        let updates = vec![
+            Ok(account(1, 1)),
+            Ok(account(2, 1)),
+            Ok(account(1, 2)),
+            Ok(tx_status(1)),
+            Ok(tx_status(2)),
+            Ok(slot(1)),
+            Ok(slot(2)),
+            Ok(account(3, 1)),
+        ];
+        let (receivers, driver) = split_updates(stream::iter(updates), config);
+        let mut accounts = receivers.accounts;
+        let transactions = receivers.transactions;
+
+        // driver is blocked by slots channel until slots are received
+        let (result, slots) = futures::join!(driver, receivers.slots.collect::<Vec<_>>());
+        result.unwrap();
+        assert_eq!(slots.len(), 2);
+
+        assert_eq!(accounts.dropped(), 1);
+        let accounts = (&mut accounts).collect::<Vec<_>>().await;
+        assert_eq!(
+            accounts.iter().map(write_version).collect::<Vec<_>>(),
+            vec![(2, 1), (3, 1)]
+        );
+
+        assert_eq!(transactions.dropped(), 1);
+        assert_eq!(transactions.collect::<Vec<_>>().await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_filter_applied() {
+        let ack = SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::FilterApplied(SubscribeUpdateFilterApplied {
+                request_id: 7,
+            })),
+            ..Default::default()
+        };
+        let updates = vec![Ok(account(1, 1)), Ok(ack.clone()), Ok(slot(1))];
+        let (receivers, driver) = split_updates(stream::iter(updates), SplitConfig::default());
+        driver.await.unwrap();
+
+        assert_eq!(
+            receivers.accounts.collect::<Vec<_>>().await,
+            vec![account(1, 1), ack.clone()]
+        );
+        assert_eq!(
+            receivers.transactions.collect::<Vec<_>>().await,
+            vec![ack.clone()]
+        );
+        assert_eq!(
+            receivers.slots.collect::<Vec<_>>().await,
+            vec![ack.clone(), slot(1)]
+        );
+        assert_eq!(receivers.blocks.collect::<Vec<_>>().await, vec![ack]);
+    }
+}
@@ -0,0 +1,377 @@
+//! Disk-backed queue between the subscription stream and the consumer.
+//!
+//! [`spool`] splits the subscription into a driver future and a stream for
+//! the consumer. Driver reads the subscription all the time, updates are kept
+//! in memory up to `memory_capacity` and appended to the file after that, so
+//! a short outage of the consumer (e.g. database failover) does not stall the
+//! subscription and the server does not drop the client as lagging. Updates
+//! are delivered in order of receiving. The file is truncated every time all
+//! spilled updates are delivered and removed when the stream and the driver
+//! are dropped.
+
+use {
+    futures::{
+        future::Future,
+        stream::{self, Stream, StreamExt},
+    },
+    std::{
+        collections::VecDeque,
+        fs::{self, File, OpenOptions},
+        io::{self, BufReader, Read, Seek, SeekFrom, Write},
+        path::PathBuf,
+        sync::{Arc, Mutex, MutexGuard},
+    },
+    tokio::{sync::Notify, task::spawn_blocking},
+    tonic::Status,
+    yellowstone_grpc_proto::{
+        prelude::SubscribeUpdate,
+        prost::{DecodeError, Message},
+    },
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpoolConfig {
+    /// Updates kept in memory before the file is used
+    pub memory_capacity: usize,
+    /// Maximum size of the file, subscription is finished with
+    /// [`SpoolError::Full`] when it's reached
+    pub max_bytes: u64,
+}
+
+impl Default for SpoolConfig {
+    fn default() -> Self {
+        Self {
+            memory_capacity: 10_000,
+            max_bytes: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpoolError {
+    #[error(transparent)]
+    Status(#[from] Status),
+    #[error("spool file is full, max {max} bytes")]
+    Full { max: u64 },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("failed to decode spooled update: {0}")]
+    Decode(#[from] DecodeError),
+}
+
+/// File of the queue, used only by blocking tasks
+#[derive(Debug)]
+struct SpoolFile {
+    path: PathBuf,
+    max_bytes: u64,
+    writer: File,
+    reader: BufReader<File>,
+    written: u64,
+    read: u64,
+}
+
+impl Drop for SpoolFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl SpoolFile {
+    fn create(path: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        let writer = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(&path)?;
+        let reader = BufReader::new(File::open(&path)?);
+        Ok(Self {
+            path,
+            max_bytes,
+            writer,
+            reader,
+            written: 0,
+            read: 0,
+        })
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), SpoolError> {
+        if self.written + data.len() as u64 > self.max_bytes {
+            return Err(SpoolError::Full {
+                max: self.max_bytes,
+            });
+        }
+        self.writer.write_all(data)?;
+        self.written += data.len() as u64;
+        Ok(())
+    }
+
+    /// File is truncated once all written updates are read
+    fn read(&mut self) -> Result<SubscribeUpdate, SpoolError> {
+        let mut len = [0; 4];
+        self.reader.read_exact(&mut len)?;
+        let mut data = vec![0; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut data)?;
+        self.read += 4 + data.len() as u64;
+
+        if self.read == self.written {
+            self.writer.set_len(0)?;
+            self.writer.seek(SeekFrom::Start(0))?;
+            self.reader.seek(SeekFrom::Start(0))?;
+            self.written = 0;
+            self.read = 0;
+        }
+
+        Ok(SubscribeUpdate::decode(data.as_slice())?)
+    }
+}
+
+#[derive(Debug, Default)]
+struct SpoolState {
+    memory: VecDeque<SubscribeUpdate>,
+    /// Updates written to the file and not read yet
+    spilled: usize,
+    /// Error or end of the subscription, delivered after queued updates
+    finished: Option<Option<SpoolError>>,
+}
+
+#[derive(Debug)]
+struct SpoolQueue {
+    memory_capacity: usize,
+    state: Mutex<SpoolState>,
+    file: Arc<Mutex<SpoolFile>>,
+    notify: Notify,
+}
+
+impl SpoolQueue {
+    fn lock(&self) -> MutexGuard<'_, SpoolState> {
+        self.state.lock().expect("unpoisoned mutex")
+    }
+
+    /// Updates go to the file once memory is full and until the file is empty,
+    /// so order is kept. Driver is the only writer, so an update is counted
+    /// as spilled only once it's written.
+    async fn push(&self, update: SubscribeUpdate) -> Result<(), SpoolError> {
+        {
+            let mut state = self.lock();
+            if state.spilled == 0 && state.memory.len() < self.memory_capacity {
+                state.memory.push_back(update);
+                self.notify.notify_one();
+                return Ok(());
+            }
+        }
+
+        let mut data = Vec::with_capacity(4 + update.encoded_len());
+        data.extend_from_slice(&(update.encoded_len() as u32).to_le_bytes());
+        update.encode(&mut data).expect("enough capacity");
+        let file = Arc::clone(&self.file);
+        spawn_blocking(move || file.lock().expect("unpoisoned mutex").write(&data))
+            .await
+            .map_err(io::Error::other)??;
+
+        self.lock().spilled += 1;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Next update, `None` if the subscription is finished and all updates
+    /// are delivered
+    async fn pop(&self) -> Option<Result<SubscribeUpdate, SpoolError>> {
+        loop {
+            match self.pop_memory() {
+                Ok(Some(update)) => return Some(Ok(update)),
+                Ok(None) => break,
+                Err(Some(finished)) => return finished.map(Err),
+                Err(None) => self.notify.notified().await,
+            }
+        }
+
+        let file = Arc::clone(&self.file);
+        let result = spawn_blocking(move || file.lock().expect("unpoisoned mutex").read())
+            .await
+            .map_err(|error| SpoolError::Io(io::Error::other(error)))
+            .and_then(|result| result);
+        self.lock().spilled -= 1;
+        Some(result)
+    }
+
+    /// Update from memory, `None` if the next update is in the file, `Err`
+    /// with the end of the subscription if nothing is queued
+    fn pop_memory(&self) -> Result<Option<SubscribeUpdate>, Option<Option<SpoolError>>> {
+        let mut state = self.lock();
+        if let Some(update) = state.memory.pop_front() {
+            return Ok(Some(update));
+        }
+        if state.spilled > 0 {
+            return Ok(None);
+        }
+        Err(state.finished.take())
+    }
+
+    fn finish(&self, error: Option<SpoolError>) {
+        self.lock().finished = Some(error);
+        self.notify.notify_one();
+    }
+}
+
+/// Queue the subscription through the file at `path`, returned future reads
+/// the subscription and should be spawned, it's finished when the stream is
+/// finished or failed, or when the consumer stream is dropped. File is
+/// written and read by blocking tasks of tokio.
+pub fn spool<S>(
+    mut stream: S,
+    path: impl Into<PathBuf>,
+    config: SpoolConfig,
+) -> io::Result<(
+    impl Future<Output = ()>,
+    impl Stream<Item = Result<SubscribeUpdate, SpoolError>>,
+)>
+where
+    S: Stream<Item = Result<SubscribeUpdate, Status>> + Unpin,
+{
+    let queue = Arc::new(SpoolQueue {
+        memory_capacity: config.memory_capacity,
+        state: Mutex::default(),
+        file: Arc::new(Mutex::new(SpoolFile::create(
+            path.into(),
+            config.max_bytes,
+        )?)),
+        notify: Notify::new(),
+    });
+
+    let driver_queue = Arc::clone(&queue);
+    let driver = async move {
+        loop {
+            let item = stream.next().await;
+            // consumer is dropped
+            if Arc::strong_count(&driver_queue) == 1 {
+                break;
+            }
+            match item {
+                Some(Ok(update)) => {
+                    if let Err(error) = driver_queue.push(update).await {
+                        driver_queue.finish(Some(error));
+                        break;
+                    }
+                }
+                Some(Err(status)) => {
+                    driver_queue.finish(Some(status.into()));
+                    break;
+                }
+                None => {
+                    driver_queue.finish(None);
+                    break;
+                }
+            }
+        }
+    };
+
+    let updates = Box::pin(stream::unfold(Some(queue), |queue| async move {
+        let queue = queue?;
+        match queue.pop().await {
+            Some(Ok(update)) => Some((Ok(update), Some(queue))),
+            // nothing is delivered after an error
+            Some(Err(error)) => Some((Err(error), None)),
+            None => None,
+        }
+    }));
+
+    Ok((driver, updates))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{spool, SpoolConfig, SpoolError},
+        futures::{stream, StreamExt},
+        std::{env, process},
+        tonic::Status,
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateSlot,
+        },
+    };
+
+    fn slot(slot: u64) -> Result<SubscribeUpdate, Status> {
+        Ok(SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot,
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+    }
+
+    fn get_slot(update: SubscribeUpdate) -> u64 {
+        match update.update_oneof {
+            Some(UpdateOneof::Slot(msg)) => msg.slot,
+            _ => panic!("expected slot update"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spool() {
+        let path = env::temp_dir().join(format!("yellowstone-grpc-client-spool-{}", process::id()));
+        let updates = stream::iter((0..10).map(slot).chain([Err(Status::internal("closed"))]));
+        let config = SpoolConfig {
+            memory_capacity: 3,
+            ..Default::default()
+        };
+        let (driver, stream) = spool(updates, &path, config).unwrap();
+
+        // consumer is not polled while the subscription is read
+        driver.await;
+        assert!(path.metadata().unwrap().len() > 0);
+
+        let items = stream.collect::<Vec<_>>().await;
+        assert_eq!(items.len(), 11);
+        let slots = items[..10]
+            .iter()
+            .map(|item| get_slot(item.as_ref().unwrap().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(slots, (0..10).collect::<Vec<_>>());
+        assert!(matches!(items[10], Err(SpoolError::Status(_))));
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_spool_concurrent() {
+        let path = env::temp_dir().join(format!(
+            "yellowstone-grpc-client-spool-concurrent-{}",
+            process::id()
+        ));
+        let updates = stream::iter((0..100).map(slot));
+        let config = SpoolConfig {
+            memory_capacity: 2,
+            ..Default::default()
+        };
+        let (driver, stream) = spool(updates, &path, config).unwrap();
+        tokio::spawn(driver);
+
+        // updates from memory and from the file are delivered in order
+        let slots = stream
+            .map(|item| get_slot(item.unwrap()))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(slots, (0..100).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_spool_full() {
+        let path = env::temp_dir().join(format!(
+            "yellowstone-grpc-client-spool-full-{}",
+            process::id()
+        ));
+        let updates = stream::iter((0..10).map(slot));
+        let config = SpoolConfig {
+            memory_capacity: 1,
+            max_bytes: 16,
+        };
+        let (driver, stream) = spool(updates, &path, config).unwrap();
+        driver.await;
+
+        let items = stream.collect::<Vec<_>>().await;
+        assert!(matches!(
+            items.last(),
+            Some(Err(SpoolError::Full { max: 16 }))
+        ));
+    }
+}
@@ -0,0 +1,521 @@
+//! Tracking of vote accounts and stake delegations from accounts updates.
+//!
+//! Vote accounts are decoded from `VoteStateVersions` (`V1_14_11` and `Current`
+//! layouts), stake accounts from `StakeStateV2`. Delegated stake is aggregated
+//! per vote account without warmup and cooldown, deactivating stake is not
+//! included.
+
+use {
+    futures::stream::{self, Stream, StreamExt},
+    std::collections::HashMap,
+    tonic::Status,
+    yellowstone_grpc_proto::{
+        base58,
+        prelude::{
+            subscribe_update::UpdateOneof, SubscribeRequestFilterAccounts,
+            SubscribeRequestFilterAccountsFilter, SubscribeUpdate, SubscribeUpdateAccount,
+        },
+    },
+};
+
+pub const VOTE_PROGRAM_ID: &str = "Vote111111111111111111111111111111111111111";
+pub const STAKE_PROGRAM_ID: &str = "Stake11111111111111111111111111111111111111";
+
+/// `StakeStateV2` size
+const STAKE_ACCOUNT_SIZE: u64 = 200;
+/// `VoteStateVersions` variants
+const VOTE_STATE_V1_14_11: u32 = 1;
+const VOTE_STATE_CURRENT: u32 = 2;
+/// `StakeStateV2::Stake` variant
+const STAKE_STATE_STAKE: u32 = 2;
+/// `prior_voters` is `CircBuf<(Pubkey, Epoch, Epoch)>` with 32 items, index and
+/// `is_empty` flag
+const VOTE_PRIOR_VOTERS_SIZE: usize = 32 * 48 + 8 + 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum StakingError {
+    #[error("account data is too short: {0} bytes")]
+    InvalidSize(usize),
+    #[error("unsupported vote state version: {0}")]
+    UnsupportedVoteState(u32),
+    #[error("invalid stake state: {0}")]
+    InvalidStakeState(u32),
+}
+
+pub type StakingResult<T> = Result<T, StakingError>;
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    fn take(&mut self, len: Option<usize>) -> StakingResult<&'a [u8]> {
+        let value = len
+            .and_then(|len| self.data.get(self.offset..self.offset.checked_add(len)?))
+            .ok_or(StakingError::InvalidSize(self.data.len()))?;
+        self.offset += value.len();
+        Ok(value)
+    }
+
+    fn read_u8(&mut self) -> StakingResult<u8> {
+        self.take(Some(1)).map(|data| data[0])
+    }
+
+    fn read_u32(&mut self) -> StakingResult<u32> {
+        let data = self.take(Some(4))?;
+        Ok(u32::from_le_bytes(data.try_into().expect("valid size")))
+    }
+
+    fn read_u64(&mut self) -> StakingResult<u64> {
+        let data = self.take(Some(8))?;
+        Ok(u64::from_le_bytes(data.try_into().expect("valid size")))
+    }
+
+    fn read_pubkey(&mut self) -> StakingResult<String> {
+        self.take(Some(32)).map(base58::encode)
+    }
+
+    /// Skip `len` items of `size` bytes
+    fn skip(&mut self, len: u64, size: usize) -> StakingResult<()> {
+        let len = usize::try_from(len)
+            .ok()
+            .and_then(|len| len.checked_mul(size));
+        self.take(len).map(|_| ())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EpochCredits {
+    pub epoch: u64,
+    pub credits: u64,
+    pub prev_credits: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoteAccountState {
+    /// Validator identity in base58
+    pub node_pubkey: String,
+    /// Withdraw authority in base58
+    pub authorized_withdrawer: String,
+    pub commission: u8,
+    /// Credits of the latest epoch
+    pub epoch_credits: Option<EpochCredits>,
+}
+
+impl VoteAccountState {
+    pub fn decode(data: &[u8]) -> StakingResult<Self> {
+        let mut reader = Reader::new(data);
+        let lockout_size = match reader.read_u32()? {
+            // `Lockout`
+            VOTE_STATE_V1_14_11 => 12,
+            // `LandedVote`
+            VOTE_STATE_CURRENT => 13,
+            version => return Err(StakingError::UnsupportedVoteState(version)),
+        };
+
+        let node_pubkey = reader.read_pubkey()?;
+        let authorized_withdrawer = reader.read_pubkey()?;
+        let commission = reader.read_u8()?;
+        let votes = reader.read_u64()?;
+        reader.skip(votes, lockout_size)?;
+        // root_slot
+        if reader.read_u8()? != 0 {
+            reader.read_u64()?;
+        }
+        // authorized_voters
+        let voters = reader.read_u64()?;
+        reader.skip(voters, 40)?;
+        reader.skip(1, VOTE_PRIOR_VOTERS_SIZE)?;
+        let epoch_credits = match reader.read_u64()? {
+            0 => None,
+            len => {
+                reader.skip(len - 1, 24)?;
+                Some(EpochCredits {
+                    epoch: reader.read_u64()?,
+                    credits: reader.read_u64()?,
+                    prev_credits: reader.read_u64()?,
+                })
+            }
+        };
+
+        Ok(Self {
+            node_pubkey,
+            authorized_withdrawer,
+            commission,
+            epoch_credits,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StakeDelegation {
+    /// Vote account in base58
+    pub voter: String,
+    pub stake: u64,
+    pub activation_epoch: u64,
+    /// `u64::MAX` if stake is not deactivating
+    pub deactivation_epoch: u64,
+}
+
+impl StakeDelegation {
+    /// Returns `None` for not delegated stake accounts
+    pub fn decode(data: &[u8]) -> StakingResult<Option<Self>> {
+        let mut reader = Reader::new(data);
+        match reader.read_u32()? {
+            STAKE_STATE_STAKE => {}
+            // `Uninitialized`, `Initialized` and `RewardsPool`
+            0 | 1 | 3 => return Ok(None),
+            state => return Err(StakingError::InvalidStakeState(state)),
+        }
+        // `Meta`: rent_exempt_reserve, authorized, lockup
+        reader.skip(1, 120)?;
+        Ok(Some(Self {
+            voter: reader.read_pubkey()?,
+            stake: reader.read_u64()?,
+            activation_epoch: reader.read_u64()?,
+            deactivation_epoch: reader.read_u64()?,
+        }))
+    }
+
+    pub const fn is_deactivating(&self) -> bool {
+        self.deactivation_epoch != u64::MAX
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StakingEvent {
+    /// Identity, withdraw authority, commission or credits changed, `previous`
+    /// is `None` for the first update
+    VoteAccountChanged {
+        /// Vote account in base58
+        vote_account: String,
+        slot: u64,
+        previous: Option<VoteAccountState>,
+        state: VoteAccountState,
+    },
+    /// Sum of delegations to the vote account changed
+    DelegatedStakeChanged {
+        /// Vote account in base58
+        vote_account: String,
+        slot: u64,
+        previous: u64,
+        delegated: u64,
+    },
+    /// Failed to decode vote or stake account
+    Error {
+        /// Account pubkey in base58
+        pubkey: String,
+        error: StakingError,
+    },
+}
+
+/// Tracks vote accounts and delegated stake per vote account, accounts are
+/// identified by base58 pubkey
+#[derive(Debug, Clone, Default)]
+pub struct StakingMonitor {
+    vote_accounts: HashMap<String, (u64, VoteAccountState)>,
+    /// Active delegations by stake account
+    delegations: HashMap<String, (u64, StakeDelegation)>,
+    delegated: HashMap<String, u64>,
+}
+
+impl StakingMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accounts filter for all vote accounts
+    pub fn vote_accounts_filter() -> SubscribeRequestFilterAccounts {
+        SubscribeRequestFilterAccounts::with_owner([VOTE_PROGRAM_ID])
+    }
+
+    /// Accounts filter for all stake accounts
+    pub fn stake_accounts_filter() -> SubscribeRequestFilterAccounts {
+        SubscribeRequestFilterAccounts::with_owner([STAKE_PROGRAM_ID]).add_filter(
+            SubscribeRequestFilterAccountsFilter::datasize(STAKE_ACCOUNT_SIZE),
+        )
+    }
+
+    pub fn vote_account(&self, pubkey: &str) -> Option<&VoteAccountState> {
+        self.vote_accounts.get(pubkey).map(|(_slot, state)| state)
+    }
+
+    /// Delegated stake to the vote account, without warmup and cooldown
+    pub fn delegated_stake(&self, vote_account: &str) -> u64 {
+        self.delegated
+            .get(vote_account)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Apply account update, accounts of other programs are ignored
+    pub fn update_account(&mut self, msg: &SubscribeUpdateAccount) -> Vec<StakingEvent> {
+        let Some(account) = msg.account.as_ref() else {
+            return vec![];
+        };
+        let pubkey = base58::encode(&account.pubkey);
+        let owner = base58::encode(&account.owner);
+        let closed = account.lamports == 0;
+
+        if owner == VOTE_PROGRAM_ID {
+            if self
+                .vote_accounts
+                .get(&pubkey)
+                .is_some_and(|(slot, _state)| msg.slot < *slot)
+            {
+                return vec![];
+            }
+            if closed {
+                self.vote_accounts.remove(&pubkey);
+                return vec![];
+            }
+
+            let state = match VoteAccountState::decode(&account.data) {
+                Ok(state) => state,
+                Err(error) => return vec![StakingEvent::Error { pubkey, error }],
+            };
+            let previous = self
+                .vote_accounts
+                .insert(pubkey.clone(), (msg.slot, state.clone()))
+                .map(|(_slot, state)| state);
+            if previous.as_ref() == Some(&state) {
+                return vec![];
+            }
+            vec![StakingEvent::VoteAccountChanged {
+                vote_account: pubkey,
+                slot: msg.slot,
+                previous,
+                state,
+            }]
+        } else if owner == STAKE_PROGRAM_ID {
+            if self
+                .delegations
+                .get(&pubkey)
+                .is_some_and(|(slot, _delegation)| msg.slot < *slot)
+            {
+                return vec![];
+            }
+
+            let delegation = if closed {
+                None
+            } else {
+                match StakeDelegation::decode(&account.data) {
+                    Ok(delegation) => delegation.filter(|delegation| !delegation.is_deactivating()),
+                    Err(error) => return vec![StakingEvent::Error { pubkey, error }],
+                }
+            };
+            let previous = match delegation.clone() {
+                Some(delegation) => self.delegations.insert(pubkey, (msg.slot, delegation)),
+                None => self.delegations.remove(&pubkey),
+            };
+
+            let mut changes = HashMap::<String, (u64, u64)>::new();
+            if let Some((_slot, delegation)) = previous {
+                self.change_delegated(&mut changes, delegation.voter, |value| {
+                    value.saturating_sub(delegation.stake)
+                });
+            }
+            if let Some(delegation) = delegation {
+                self.change_delegated(&mut changes, delegation.voter, |value| {
+                    value.saturating_add(delegation.stake)
+                });
+            }
+
+            changes
+                .into_iter()
+                .filter(|(_vote_account, (previous, delegated))| previous != delegated)
+                .map(
+                    |(vote_account, (previous, delegated))| StakingEvent::DelegatedStakeChanged {
+                        vote_account,
+                        slot: msg.slot,
+                        previous,
+                        delegated,
+                    },
+                )
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    fn change_delegated(
+        &mut self,
+        changes: &mut HashMap<String, (u64, u64)>,
+        vote_account: String,
+        f: impl FnOnce(u64) -> u64,
+    ) {
+        let value = self.delegated.entry(vote_account.clone()).or_default();
+        let previous = *value;
+        *value = f(previous);
+        let delegated = *value;
+        if delegated == 0 {
+            self.delegated.remove(&vote_account);
+        }
+        changes
+            .entry(vote_account)
+            .and_modify(|change| change.1 = delegated)
+            .or_insert((previous, delegated));
+    }
+
+    /// Convert stream of updates to stream of staking events
+    pub fn into_stream<S>(mut self, stream: S) -> impl Stream<Item = Result<StakingEvent, Status>>
+    where
+        S: Stream<Item = Result<SubscribeUpdate, Status>>,
+    {
+        stream.flat_map(move |message| {
+            let events = match message {
+                Ok(SubscribeUpdate {
+                    update_oneof: Some(UpdateOneof::Account(msg)),
+                    ..
+                }) => self.update_account(&msg).into_iter().map(Ok).collect(),
+                Ok(_) => vec![],
+                Err(status) => vec![Err(status)],
+            };
+            stream::iter(events)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            EpochCredits, StakeDelegation, StakingError, StakingEvent, StakingMonitor,
+            VoteAccountState, STAKE_PROGRAM_ID, VOTE_PRIOR_VOTERS_SIZE, VOTE_PROGRAM_ID,
+        },
+        yellowstone_grpc_proto::{
+            base58,
+            prelude::{SubscribeUpdateAccount, SubscribeUpdateAccountInfo},
+        },
+    };
+
+    const VOTE_ACCOUNT: &str = "3N7s9zXMZ4QqvHQR15t8GNHyqc89KduzPd3rhwYXBs4M";
+
+    fn vote_data(commission: u8, credits: u64) -> Vec<u8> {
+        let mut data = 2u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&[1; 32]);
+        data.extend_from_slice(&[2; 32]);
+        data.push(commission);
+        // votes
+        data.extend_from_slice(&2u64.to_le_bytes());
+        data.extend_from_slice(&[0; 26]);
+        // root_slot
+        data.push(1);
+        data.extend_from_slice(&100u64.to_le_bytes());
+        // authorized_voters
+        data.extend_from_slice(&1u64.to_le_bytes());
+        data.extend_from_slice(&[0; 40]);
+        data.extend_from_slice(&[0; VOTE_PRIOR_VOTERS_SIZE]);
+        // epoch_credits
+        data.extend_from_slice(&2u64.to_le_bytes());
+        data.extend_from_slice(&[0; 24]);
+        for value in [7u64, credits, 10] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        // last_timestamp
+        data.extend_from_slice(&[0; 16]);
+        data
+    }
+
+    fn stake_data(voter: &str, stake: u64, deactivation_epoch: u64) -> Vec<u8> {
+        let mut data = vec![0; 200];
+        data[..4].copy_from_slice(&2u32.to_le_bytes());
+        data[124..156].copy_from_slice(&bs58::decode(voter).into_vec().unwrap());
+        data[156..164].copy_from_slice(&stake.to_le_bytes());
+        data[172..180].copy_from_slice(&deactivation_epoch.to_le_bytes());
+        data
+    }
+
+    fn account(pubkey: u8, owner: &str, slot: u64, data: Vec<u8>) -> SubscribeUpdateAccount {
+        SubscribeUpdateAccount {
+            account: Some(SubscribeUpdateAccountInfo {
+                pubkey: vec![pubkey; 32],
+                lamports: 1,
+                owner: bs58::decode(owner).into_vec().unwrap(),
+                data,
+                ..Default::default()
+            }),
+            slot,
+            is_startup: false,
+            previous_owner: None,
+            data_omitted: false,
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let state = VoteAccountState::decode(&vote_data(5, 42)).unwrap();
+        assert_eq!(state.node_pubkey, base58::encode([1; 32]));
+        assert_eq!(state.commission, 5);
+        assert_eq!(
+            state.epoch_credits,
+            Some(EpochCredits {
+                epoch: 7,
+                credits: 42,
+                prev_credits: 10
+            })
+        );
+        assert_eq!(
+            VoteAccountState::decode(&vote_data(5, 42)[..100]),
+            Err(StakingError::InvalidSize(100))
+        );
+
+        let delegation = StakeDelegation::decode(&stake_data(VOTE_ACCOUNT, 10, u64::MAX))
+            .unwrap()
+            .unwrap();
+        assert_eq!(delegation.voter, VOTE_ACCOUNT);
+        assert_eq!(delegation.stake, 10);
+        assert!(!delegation.is_deactivating());
+        assert_eq!(StakeDelegation::decode(&[1, 0, 0, 0]), Ok(None));
+    }
+
+    #[test]
+    fn test_monitor() {
+        let mut monitor = StakingMonitor::new();
+
+        let events = monitor.update_account(&account(1, VOTE_PROGRAM_ID, 1, vote_data(5, 1)));
+        assert!(matches!(
+            events.as_slice(),
+            [StakingEvent::VoteAccountChanged { previous: None, .. }]
+        ));
+        // same state
+        let events = monitor.update_account(&account(1, VOTE_PROGRAM_ID, 2, vote_data(5, 1)));
+        assert!(events.is_empty());
+        let events = monitor.update_account(&account(1, VOTE_PROGRAM_ID, 3, vote_data(10, 1)));
+        assert!(matches!(
+            events.as_slice(),
+            [StakingEvent::VoteAccountChanged { state, .. }] if state.commission == 10
+        ));
+
+        let stake = |stake, deactivation_epoch| stake_data(VOTE_ACCOUNT, stake, deactivation_epoch);
+        let events = monitor.update_account(&account(2, STAKE_PROGRAM_ID, 1, stake(10, u64::MAX)));
+        assert_eq!(
+            events,
+            vec![StakingEvent::DelegatedStakeChanged {
+                vote_account: VOTE_ACCOUNT.to_owned(),
+                slot: 1,
+                previous: 0,
+                delegated: 10,
+            }]
+        );
+        monitor.update_account(&account(3, STAKE_PROGRAM_ID, 1, stake(5, u64::MAX)));
+        assert_eq!(monitor.delegated_stake(VOTE_ACCOUNT), 15);
+
+        // deactivation
+        let events = monitor.update_account(&account(2, STAKE_PROGRAM_ID, 2, stake(10, 8)));
+        assert_eq!(
+            events,
+            vec![StakingEvent::DelegatedStakeChanged {
+                vote_account: VOTE_ACCOUNT.to_owned(),
+                slot: 2,
+                previous: 15,
+                delegated: 5,
+            }]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Filter updates of the open subscription with acknowledgement.
+//!
+//! Every request sent by [`Subscription`] carries `request_id`, server sends
+//! `FilterApplied` with the same id once the new filter replaced the previous
+//! one: every update after the acknowledgement is matched by the new filter.
+//! Server applies only the latest of requests received together, so the
+//! acknowledgement resolves all earlier requests too. Servers without support
+//! never acknowledge requests, futures are resolved with an error only when
+//! the stream is finished or dropped.
+
+use {
+    futures::{
+        channel::{mpsc, oneshot},
+        future::Future,
+        stream::{self, Stream, StreamExt},
+    },
+    std::{
+        collections::BTreeMap,
+        sync::{Arc, Mutex, Weak},
+    },
+    tonic::Status,
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, SubscribeRequest, SubscribeUpdate,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SubscriptionError {
+    #[error("subscription is closed")]
+    Closed,
+}
+
+/// Requests waiting for acknowledgement by id, owned by the stream
+type Pending = Mutex<BTreeMap<u64, oneshot::Sender<()>>>;
+
+/// Handle of the subscription to update filters, created by
+/// [`crate::GeyserGrpcClient::subscribe_with_handle`]
+#[derive(Debug)]
+pub struct Subscription {
+    requests_tx: mpsc::UnboundedSender<SubscribeRequest>,
+    next_id: u64,
+    pending: Weak<Pending>,
+}
+
+impl Subscription {
+    pub(crate) fn new<S>(
+        requests_tx: mpsc::UnboundedSender<SubscribeRequest>,
+        stream: S,
+    ) -> (Self, impl Stream<Item = Result<SubscribeUpdate, Status>>)
+    where
+        S: Stream<Item = Result<SubscribeUpdate, Status>>,
+    {
+        let pending = Arc::new(Pending::default());
+        let subscription = Self {
+            requests_tx,
+            next_id: 0,
+            pending: Arc::downgrade(&pending),
+        };
+
+        // waiting futures are resolved with an error when the stream is finished or dropped
+        let stream = stream::unfold(
+            (Box::pin(stream), Some(pending)),
+            |(mut stream, pending)| async move {
+                let pending = pending?;
+                match stream.next().await {
+                    Some(item) => {
+                        match &item {
+                            Ok(SubscribeUpdate {
+                                update_oneof: Some(UpdateOneof::FilterApplied(msg)),
+                                ..
+                            }) => Self::acknowledge(&pending, msg.request_id),
+                            Ok(_) => {}
+                            Err(_) => return Some((item, (stream, None))),
+                        }
+                        Some((item, (stream, Some(pending))))
+                    }
+                    None => None,
+                }
+            },
+        );
+        (subscription, stream)
+    }
+
+    fn acknowledge(pending: &Pending, request_id: u64) {
+        let mut pending = pending.lock().expect("unpoisoned mutex");
+        let rest = pending.split_off(&request_id.saturating_add(1));
+        for tx in std::mem::replace(&mut *pending, rest).into_values() {
+            // future could be dropped
+            let _ = tx.send(());
+        }
+    }
+
+    /// Send the request, returned future is resolved once the server applied
+    /// the request (or a newer one). Acknowledgement is delivered by the
+    /// stream, so the stream should be polled while the future is awaited.
+    pub fn update_filter(
+        &mut self,
+        mut request: SubscribeRequest,
+    ) -> impl Future<Output = Result<(), SubscriptionError>> {
+        self.next_id += 1;
+        request.request_id = Some(self.next_id);
+
+        let (tx, rx) = oneshot::channel();
+        let sent = match self.pending.upgrade() {
+            Some(pending) => {
+                // registered before the request is sent, acknowledgement can not be missed
+                pending
+                    .lock()
+                    .expect("unpoisoned mutex")
+                    .insert(self.next_id, tx);
+                self.requests_tx.unbounded_send(request).is_ok()
+            }
+            None => false,
+        };
+        async move {
+            if !sent {
+                return Err(SubscriptionError::Closed);
+            }
+            rx.await.map_err(|_canceled| SubscriptionError::Closed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::SubscriptionError,
+        crate::{tests::MockGeyser, GeyserGrpcClient},
+        futures::{future, StreamExt},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SubscribeRequest, SubscribeUpdateFilterApplied,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_update_filter() {
+        let endpoint = MockGeyser {
+            filter_applied: true,
+            ..Default::default()
+        }
+        .spawn()
+        .await;
+        let mut client = GeyserGrpcClient::build_from_shared(endpoint)
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let (mut subscription, stream) = client
+            .subscribe_with_handle(SubscribeRequest::default())
+            .await
+            .unwrap();
+        let mut stream = Box::pin(stream);
+
+        // initial request
+        let update = stream.next().await.unwrap().unwrap();
+        assert_eq!(
+            update.update_oneof,
+            Some(UpdateOneof::FilterApplied(SubscribeUpdateFilterApplied {
+                request_id: 1
+            }))
+        );
+
+        let applied = subscription.update_filter(SubscribeRequest::default());
+        let (result, update) = future::join(applied, stream.next()).await;
+        assert_eq!(result, Ok(()));
+        assert!(update.unwrap().is_ok());
+
+        drop(stream);
+        let result = subscription
+            .update_filter(SubscribeRequest::default())
+            .await;
+        assert_eq!(result, Err(SubscriptionError::Closed));
+    }
+}
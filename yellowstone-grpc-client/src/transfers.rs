@@ -0,0 +1,207 @@
+//! Native SOL transfers decoded from System program instructions.
+//!
+//! `Transfer` and `TransferWithSeed` instructions are decoded from outer and
+//! inner instructions (CPI), account indexes are resolved with loaded addresses
+//! of v0 transactions from the transaction meta. Failed transactions have no
+//! transfers.
+
+use {
+    futures::{
+        future,
+        stream::{Stream, StreamExt},
+    },
+    yellowstone_grpc_proto::{
+        base58,
+        invocation::instructions,
+        prelude::{subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateTransactionInfo},
+    },
+};
+
+/// `SystemInstruction::Transfer`
+const SYSTEM_TRANSFER: u32 = 2;
+/// `SystemInstruction::TransferWithSeed`
+const SYSTEM_TRANSFER_WITH_SEED: u32 = 11;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeTransfer {
+    /// Source account in base58
+    pub from: String,
+    /// Destination account in base58
+    pub to: String,
+    pub lamports: u64,
+    /// Index of the outer instruction
+    pub instruction_index: usize,
+    /// Index in the inner instructions of the outer instruction, `None` for
+    /// outer instruction
+    pub inner_index: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionTransfers {
+    pub slot: u64,
+    /// Transaction signature in base58
+    pub signature: String,
+    /// In the order of execution
+    pub transfers: Vec<NativeTransfer>,
+}
+
+/// Decode System program instruction, returns `(from, to, lamports)` indexes
+/// in the account keys
+fn decode_instruction(accounts: &[u8], data: &[u8]) -> Option<(u8, u8, u64)> {
+    let tag = u32::from_le_bytes(data.get(0..4)?.try_into().ok()?);
+    let lamports = u64::from_le_bytes(data.get(4..12)?.try_into().ok()?);
+    match tag {
+        // [from, to]
+        SYSTEM_TRANSFER if data.len() == 12 => {
+            Some((*accounts.first()?, *accounts.get(1)?, lamports))
+        }
+        // [from, base, to]
+        SYSTEM_TRANSFER_WITH_SEED => Some((*accounts.first()?, *accounts.get(2)?, lamports)),
+        _ => None,
+    }
+}
+
+/// Decode native transfers of the successful transaction
+pub fn decode_transfers(transaction: &SubscribeUpdateTransactionInfo) -> Vec<NativeTransfer> {
+    let (Some(tx), Some(meta)) = (transaction.transaction.as_ref(), transaction.meta.as_ref())
+    else {
+        return vec![];
+    };
+    if meta.err.is_some() {
+        return vec![];
+    }
+
+    instructions(tx, meta)
+        .filter(|ix| {
+            ix.program_id()
+                .is_some_and(|pubkey| pubkey.iter().all(|byte| *byte == 0))
+        })
+        .filter_map(|ix| {
+            let (from, to, lamports) = decode_instruction(ix.accounts, ix.data)?;
+            Some(NativeTransfer {
+                from: base58::encode(ix.account_key(from as u32)?),
+                to: base58::encode(ix.account_key(to as u32)?),
+                lamports,
+                instruction_index: ix.instruction_index,
+                inner_index: ix.inner_index,
+            })
+        })
+        .collect()
+}
+
+/// Native transfers of transactions in the stream, transactions without
+/// transfers and other updates are dropped
+pub fn native_transfers<S, E>(stream: S) -> impl Stream<Item = Result<TransactionTransfers, E>>
+where
+    S: Stream<Item = Result<SubscribeUpdate, E>>,
+{
+    stream.filter_map(|message| {
+        future::ready(match message {
+            Ok(SubscribeUpdate {
+                update_oneof: Some(UpdateOneof::Transaction(msg)),
+                ..
+            }) => msg.transaction.and_then(|transaction| {
+                let transfers = decode_transfers(&transaction);
+                (!transfers.is_empty()).then(|| {
+                    Ok(TransactionTransfers {
+                        slot: msg.slot,
+                        signature: base58::encode(&transaction.signature),
+                        transfers,
+                    })
+                })
+            }),
+            Ok(_) => None,
+            Err(error) => Some(Err(error)),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{decode_transfers, NativeTransfer},
+        yellowstone_grpc_proto::{
+            base58,
+            prelude::{
+                CompiledInstruction, InnerInstruction, InnerInstructions, Message,
+                SubscribeUpdateTransactionInfo, Transaction, TransactionError,
+                TransactionStatusMeta,
+            },
+        },
+    };
+
+    fn transfer_data(tag: u32, lamports: u64) -> Vec<u8> {
+        let mut data = tag.to_le_bytes().to_vec();
+        data.extend_from_slice(&lamports.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_decode_transfers() {
+        let mut with_seed = transfer_data(11, 7);
+        with_seed.extend_from_slice(&[0; 8 + 32]);
+        let mut transaction = SubscribeUpdateTransactionInfo {
+            transaction: Some(Transaction {
+                message: Some(Message {
+                    // system program, from, program
+                    account_keys: vec![vec![0; 32], vec![1; 32], vec![2; 32]],
+                    instructions: vec![
+                        CompiledInstruction {
+                            program_id_index: 0,
+                            accounts: vec![1, 3],
+                            data: transfer_data(2, 42),
+                        },
+                        CompiledInstruction {
+                            program_id_index: 2,
+                            accounts: vec![],
+                            data: transfer_data(2, 1),
+                        },
+                    ],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            meta: Some(TransactionStatusMeta {
+                inner_instructions: vec![InnerInstructions {
+                    index: 1,
+                    instructions: vec![InnerInstruction {
+                        program_id_index: 0,
+                        accounts: vec![1, 4, 3],
+                        data: with_seed,
+                        stack_height: Some(2),
+                    }],
+                }],
+                // loaded destination and base
+                loaded_writable_addresses: vec![vec![3; 32]],
+                loaded_readonly_addresses: vec![vec![4; 32]],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let from = base58::encode([1; 32]);
+        let to = base58::encode([3; 32]);
+        assert_eq!(
+            decode_transfers(&transaction),
+            vec![
+                NativeTransfer {
+                    from: from.clone(),
+                    to: to.clone(),
+                    lamports: 42,
+                    instruction_index: 0,
+                    inner_index: None,
+                },
+                NativeTransfer {
+                    from,
+                    to,
+                    lamports: 7,
+                    instruction_index: 1,
+                    inner_index: Some(0),
+                },
+            ]
+        );
+
+        transaction.meta.as_mut().unwrap().err = Some(TransactionError { err: vec![1] });
+        assert!(decode_transfers(&transaction).is_empty());
+    }
+}
@@ -0,0 +1,333 @@
+//! Transform stage between the stream and the sink.
+//!
+//! [`Transform`] can drop, redact or enrich updates before they are written,
+//! so one sink can be reused for different destinations without forks. Any
+//! `Fn(SubscribeUpdate) -> Option<SubscribeUpdate>` is a transform, common
+//! ones are provided by [`BuiltinTransform`] which can be parsed from command
+//! line arguments.
+
+use {
+    futures::{future, stream::Stream, StreamExt},
+    std::{fmt, str::FromStr},
+    yellowstone_grpc_proto::prelude::{
+        subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateAccountInfo,
+        SubscribeUpdateTransactionInfo,
+    },
+};
+
+pub trait Transform {
+    /// Transformed update, `None` drops the update
+    fn apply(&self, update: SubscribeUpdate) -> Option<SubscribeUpdate>;
+}
+
+impl<F> Transform for F
+where
+    F: Fn(SubscribeUpdate) -> Option<SubscribeUpdate>,
+{
+    fn apply(&self, update: SubscribeUpdate) -> Option<SubscribeUpdate> {
+        self(update)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TransformParseError {
+    #[error("unknown transform: {0}")]
+    Unknown(String),
+    #[error("invalid argument of {name}: {value}")]
+    InvalidArgument { name: &'static str, value: String },
+}
+
+/// Transforms applied to accounts and transactions, including accounts and
+/// transactions of blocks. Other updates are not changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinTransform {
+    /// Drop vote transactions
+    DropVotes,
+    /// Drop failed transactions
+    DropFailed,
+    /// Clear data of accounts
+    DropAccountData,
+    /// Clear log messages of transactions
+    DropTransactionLogs,
+    /// Keep only the range of account data, e.g. amount of token account
+    AccountDataSlice { offset: usize, length: usize },
+}
+
+impl fmt::Display for BuiltinTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DropVotes => write!(f, "drop-votes"),
+            Self::DropFailed => write!(f, "drop-failed"),
+            Self::DropAccountData => write!(f, "drop-account-data"),
+            Self::DropTransactionLogs => write!(f, "drop-transaction-logs"),
+            Self::AccountDataSlice { offset, length } => {
+                write!(f, "account-data-slice={offset}:{length}")
+            }
+        }
+    }
+}
+
+impl FromStr for BuiltinTransform {
+    type Err = TransformParseError;
+
+    /// Parse transform in form of `name` or `name=argument`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            None => match s {
+                "drop-votes" => Ok(Self::DropVotes),
+                "drop-failed" => Ok(Self::DropFailed),
+                "drop-account-data" => Ok(Self::DropAccountData),
+                "drop-transaction-logs" => Ok(Self::DropTransactionLogs),
+                _ => Err(TransformParseError::Unknown(s.to_owned())),
+            },
+            Some(("account-data-slice", value)) => {
+                let invalid = || TransformParseError::InvalidArgument {
+                    name: "account-data-slice",
+                    value: value.to_owned(),
+                };
+                let (offset, length) = value.split_once(':').ok_or_else(invalid)?;
+                Ok(Self::AccountDataSlice {
+                    offset: offset.parse().map_err(|_| invalid())?,
+                    length: length.parse().map_err(|_| invalid())?,
+                })
+            }
+            Some(_) => Err(TransformParseError::Unknown(s.to_owned())),
+        }
+    }
+}
+
+impl BuiltinTransform {
+    /// `false` if the transaction should be dropped
+    fn transaction(self, tx: &mut SubscribeUpdateTransactionInfo) -> bool {
+        match self {
+            Self::DropVotes => !tx.is_vote,
+            Self::DropFailed => tx.meta.as_ref().is_none_or(|meta| meta.err.is_none()),
+            Self::DropTransactionLogs => {
+                if let Some(meta) = tx.meta.as_mut() {
+                    meta.log_messages = vec![];
+                    meta.log_messages_none = true;
+                }
+                true
+            }
+            Self::DropAccountData | Self::AccountDataSlice { .. } => true,
+        }
+    }
+
+    fn account(self, account: &mut SubscribeUpdateAccountInfo) {
+        match self {
+            Self::DropAccountData => account.data = vec![],
+            Self::AccountDataSlice { offset, length } => {
+                let start = offset.min(account.data.len());
+                let end = offset.saturating_add(length).min(account.data.len());
+                account.data = account.data[start..end].to_vec();
+            }
+            Self::DropVotes | Self::DropFailed | Self::DropTransactionLogs => {}
+        }
+    }
+}
+
+impl Transform for BuiltinTransform {
+    fn apply(&self, mut update: SubscribeUpdate) -> Option<SubscribeUpdate> {
+        match update.update_oneof.as_mut() {
+            Some(UpdateOneof::Account(msg)) => {
+                if let Some(account) = msg.account.as_mut() {
+                    self.account(account);
+                }
+            }
+            Some(UpdateOneof::Transaction(msg)) => {
+                if let Some(tx) = msg.transaction.as_mut() {
+                    if !self.transaction(tx) {
+                        return None;
+                    }
+                }
+            }
+            Some(UpdateOneof::TransactionStatus(msg)) => match self {
+                Self::DropVotes if msg.is_vote => return None,
+                Self::DropFailed if msg.err.is_some() => return None,
+                _ => {}
+            },
+            Some(UpdateOneof::Block(msg)) => {
+                msg.transactions.retain_mut(|tx| self.transaction(tx));
+                for account in msg.accounts.iter_mut() {
+                    self.account(account);
+                }
+            }
+            _ => {}
+        }
+        Some(update)
+    }
+}
+
+/// Transforms applied in order, stopped on the first dropped update
+#[derive(Default)]
+pub struct TransformChain {
+    transforms: Vec<Box<dyn Transform + Send + Sync>>,
+}
+
+impl fmt::Debug for TransformChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransformChain")
+            .field("transforms", &self.transforms.len())
+            .finish()
+    }
+}
+
+impl<T> FromIterator<T> for TransformChain
+where
+    T: Transform + Send + Sync + 'static,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::default(), |chain, transform| chain.with(transform))
+    }
+}
+
+impl TransformChain {
+    pub fn with(mut self, transform: impl Transform + Send + Sync + 'static) -> Self {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+}
+
+impl Transform for TransformChain {
+    fn apply(&self, update: SubscribeUpdate) -> Option<SubscribeUpdate> {
+        self.transforms
+            .iter()
+            .try_fold(update, |update, transform| transform.apply(update))
+    }
+}
+
+/// Apply the transform to every update of the stream, errors are passed as is
+pub fn transform_stream<S, E, T>(
+    stream: S,
+    transform: T,
+) -> impl Stream<Item = Result<SubscribeUpdate, E>>
+where
+    S: Stream<Item = Result<SubscribeUpdate, E>>,
+    T: Transform,
+{
+    stream.filter_map(move |message| {
+        future::ready(match message {
+            Ok(update) => transform.apply(update).map(Ok),
+            Err(error) => Some(Err(error)),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{transform_stream, BuiltinTransform, Transform, TransformChain},
+        futures::{stream, StreamExt},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateAccount,
+            SubscribeUpdateAccountInfo, SubscribeUpdateTransactionStatus,
+        },
+    };
+
+    fn account(data: Vec<u8>) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    data,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn account_data(update: &SubscribeUpdate) -> &[u8] {
+        match &update.update_oneof {
+            Some(UpdateOneof::Account(msg)) => &msg.account.as_ref().unwrap().data,
+            _ => unreachable!(),
+        }
+    }
+
+    fn transaction_status(is_vote: bool) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::TransactionStatus(
+                SubscribeUpdateTransactionStatus {
+                    is_vote,
+                    ..Default::default()
+                },
+            )),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        for transform in [
+            BuiltinTransform::DropVotes,
+            BuiltinTransform::DropFailed,
+            BuiltinTransform::DropAccountData,
+            BuiltinTransform::DropTransactionLogs,
+            BuiltinTransform::AccountDataSlice {
+                offset: 64,
+                length: 8,
+            },
+        ] {
+            assert_eq!(
+                transform.to_string().parse::<BuiltinTransform>(),
+                Ok(transform)
+            );
+        }
+        assert!("drop-all".parse::<BuiltinTransform>().is_err());
+        assert!("account-data-slice=64".parse::<BuiltinTransform>().is_err());
+    }
+
+    #[test]
+    fn test_builtin() {
+        let slice = BuiltinTransform::AccountDataSlice {
+            offset: 2,
+            length: 3,
+        };
+        let update = slice.apply(account(vec![0, 1, 2, 3, 4, 5])).unwrap();
+        assert_eq!(account_data(&update), [2, 3, 4]);
+        let update = slice.apply(account(vec![0, 1, 2])).unwrap();
+        assert_eq!(account_data(&update), [2]);
+        let update = slice.apply(account(vec![0])).unwrap();
+        assert!(account_data(&update).is_empty());
+
+        let update = BuiltinTransform::DropAccountData
+            .apply(account(vec![0, 1]))
+            .unwrap();
+        assert!(account_data(&update).is_empty());
+
+        assert!(BuiltinTransform::DropVotes
+            .apply(transaction_status(true))
+            .is_none());
+        assert!(BuiltinTransform::DropVotes
+            .apply(transaction_status(false))
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_chain_stream() {
+        let chain = [BuiltinTransform::DropVotes]
+            .into_iter()
+            .collect::<TransformChain>()
+            .with(|update: SubscribeUpdate| {
+                matches!(update.update_oneof, Some(UpdateOneof::TransactionStatus(_)))
+                    .then_some(update)
+            });
+        let updates = transform_stream(
+            stream::iter([
+                Ok(transaction_status(true)),
+                Ok(account(vec![1])),
+                Err("error"),
+                Ok(transaction_status(false)),
+            ]),
+            chain,
+        )
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(updates, vec![Err("error"), Ok(transaction_status(false))]);
+    }
+}
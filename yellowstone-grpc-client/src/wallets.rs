@@ -0,0 +1,299 @@
+//! Token accounts of tracked wallets.
+//!
+//! [`WalletTokenAccounts`] adds to the subscription request token accounts of
+//! wallets: associated token accounts of known mints (as `pda` of the accounts
+//! filter) and accounts discovered from token balances of wallet transactions.
+//! New accounts appear when transactions of wallets create them, so
+//! [`WalletTokenAccounts::observe`] reports when the request should be sent
+//! again with [`WalletTokenAccounts::apply`].
+
+use {
+    std::collections::{BTreeSet, HashSet},
+    yellowstone_grpc_proto::{
+        base58,
+        prelude::{
+            subscribe_update::UpdateOneof, SubscribeRequest, SubscribeRequestFilterAccounts,
+            SubscribeRequestFilterAccountsPda, SubscribeRequestFilterTransactions, SubscribeUpdate,
+            SubscribeUpdateTransactionInfo,
+        },
+    },
+};
+
+pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+
+/// Name of accounts and transactions filters added to the request
+pub const FILTER_NAME: &str = "wallet_token_accounts";
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum WalletsError {
+    #[error("invalid wallet pubkey: {0}")]
+    InvalidWallet(String),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WalletTokenAccounts {
+    wallets: BTreeSet<String>,
+    /// Wallet pubkeys decoded for seeds of associated token accounts
+    wallet_seeds: Vec<Vec<u8>>,
+    mints: Vec<Vec<u8>>,
+    accounts: BTreeSet<String>,
+}
+
+impl WalletTokenAccounts {
+    pub fn new<I, T>(wallets: I) -> Result<Self, WalletsError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        let wallets = wallets.into_iter().map(Into::into).collect::<BTreeSet<_>>();
+        let wallet_seeds = wallets
+            .iter()
+            .map(|wallet| match bs58::decode(wallet).into_vec() {
+                Ok(bytes) if bytes.len() == 32 => Ok(bytes),
+                _ => Err(WalletsError::InvalidWallet(wallet.clone())),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            wallets,
+            wallet_seeds,
+            mints: vec![],
+            accounts: BTreeSet::new(),
+        })
+    }
+
+    /// Subscribe to associated token accounts of the mints (Token and
+    /// Token-2022), mints are 32-byte pubkeys. Every mint adds two addresses
+    /// per wallet, see [`WalletTokenAccounts::pda_count`].
+    pub fn with_mints<I, T>(mut self, mints: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Vec<u8>>,
+    {
+        for mint in mints.into_iter().map(Into::into) {
+            if !self.mints.contains(&mint) {
+                self.mints.push(mint);
+            }
+        }
+        self
+    }
+
+    /// Number of associated token accounts derived by the server: wallets
+    /// multiplied by mints and two token programs. Servers limit it by
+    /// `accounts.pda_max` of filter limits (1000 by default).
+    pub fn pda_count(&self) -> usize {
+        self.wallet_seeds.len() * self.mints.len() * 2
+    }
+
+    /// Token accounts already known, e.g. loaded with `getTokenAccountsByOwner`
+    pub fn with_accounts<I, T>(mut self, accounts: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.accounts.extend(accounts.into_iter().map(Into::into));
+        self
+    }
+
+    pub const fn accounts(&self) -> &BTreeSet<String> {
+        &self.accounts
+    }
+
+    /// Families of associated token accounts, one per wallet and token program
+    fn pda(&self) -> Vec<SubscribeRequestFilterAccountsPda> {
+        if self.mints.is_empty() {
+            return vec![];
+        }
+        self.wallet_seeds
+            .iter()
+            .flat_map(|wallet| {
+                [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID].map(|program_id| {
+                    let program_id = bs58::decode(program_id)
+                        .into_vec()
+                        .expect("valid program id");
+                    SubscribeRequestFilterAccountsPda::new(
+                        ASSOCIATED_TOKEN_PROGRAM_ID,
+                        [wallet.clone(), program_id],
+                    )
+                    .with_seeds_last(self.mints.iter().cloned())
+                })
+            })
+            .collect()
+    }
+
+    /// Add filters of token accounts and transactions of wallets to the
+    /// request. Accounts filter is not added while there are no accounts,
+    /// empty filter would match all accounts.
+    pub fn apply(&self, mut request: SubscribeRequest) -> SubscribeRequest {
+        let pda = self.pda();
+        if !self.accounts.is_empty() || !pda.is_empty() {
+            request.accounts.insert(
+                FILTER_NAME.to_owned(),
+                SubscribeRequestFilterAccounts {
+                    account: self.accounts.iter().cloned().collect(),
+                    pda,
+                    ..Default::default()
+                },
+            );
+        }
+        if !self.wallets.is_empty() {
+            // failed transactions do not create accounts
+            request.transactions.insert(
+                FILTER_NAME.to_owned(),
+                SubscribeRequestFilterTransactions::for_account(&self.wallets)
+                    .with_vote(false)
+                    .with_failed(false),
+            );
+        }
+        request
+    }
+
+    /// Track token accounts of wallets from the transaction update, returns
+    /// `true` if the set of accounts is changed and the request should be
+    /// sent again
+    pub fn observe(&mut self, update: &SubscribeUpdate) -> bool {
+        match &update.update_oneof {
+            Some(UpdateOneof::Transaction(msg)) => msg
+                .transaction
+                .as_ref()
+                .is_some_and(|tx| self.observe_transaction(tx)),
+            Some(UpdateOneof::Block(msg)) => msg
+                .transactions
+                .iter()
+                .fold(false, |changed, tx| self.observe_transaction(tx) || changed),
+            _ => false,
+        }
+    }
+
+    fn observe_transaction(&mut self, tx: &SubscribeUpdateTransactionInfo) -> bool {
+        let (Some(transaction), Some(meta)) = (&tx.transaction, &tx.meta) else {
+            return false;
+        };
+        if meta.err.is_some() {
+            return false;
+        }
+        let Some(message) = &transaction.message else {
+            return false;
+        };
+        let account_keys = message
+            .account_keys
+            .iter()
+            .chain(meta.loaded_writable_addresses.iter())
+            .chain(meta.loaded_readonly_addresses.iter())
+            .collect::<Vec<_>>();
+        let account = |index: u32| account_keys.get(index as usize).map(base58::encode);
+
+        let mut changed = false;
+        let mut post = HashSet::new();
+        for balance in &meta.post_token_balances {
+            if self.wallets.contains(&balance.owner) {
+                if let Some(account) = account(balance.account_index) {
+                    post.insert(balance.account_index);
+                    changed |= self.accounts.insert(account);
+                }
+            }
+        }
+        // closed accounts or accounts with changed owner
+        for balance in &meta.pre_token_balances {
+            if self.wallets.contains(&balance.owner) && !post.contains(&balance.account_index) {
+                if let Some(account) = account(balance.account_index) {
+                    changed |= self.accounts.remove(&account);
+                }
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{WalletTokenAccounts, WalletsError, FILTER_NAME},
+        yellowstone_grpc_proto::prelude::{
+            subscribe_update::UpdateOneof, Message, SubscribeRequest, SubscribeUpdate,
+            SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo, TokenBalance, Transaction,
+            TransactionStatusMeta,
+        },
+    };
+
+    const WALLET: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+    fn token_balance(account_index: u32, owner: &str) -> TokenBalance {
+        TokenBalance {
+            account_index,
+            owner: owner.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn transaction(pre: Vec<TokenBalance>, post: Vec<TokenBalance>) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                transaction: Some(SubscribeUpdateTransactionInfo {
+                    transaction: Some(Transaction {
+                        message: Some(Message {
+                            account_keys: vec![vec![0; 32], vec![1; 32]],
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }),
+                    meta: Some(TransactionStatusMeta {
+                        loaded_writable_addresses: vec![vec![2; 32]],
+                        pre_token_balances: pre,
+                        post_token_balances: post,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(
+            WalletTokenAccounts::new(["invalid"]).unwrap_err(),
+            WalletsError::InvalidWallet("invalid".to_owned())
+        );
+
+        let tracker = WalletTokenAccounts::new([WALLET]).unwrap();
+        let request = tracker.apply(SubscribeRequest::default());
+        assert!(request.accounts.is_empty());
+        assert_eq!(request.transactions[FILTER_NAME].account_include, [WALLET]);
+
+        // duplicated mints are not derived twice
+        let tracker = tracker.with_mints([[7; 32], [7; 32]]);
+        assert_eq!(tracker.pda_count(), 2);
+        let request = tracker.apply(SubscribeRequest::default());
+        let filter = &request.accounts[FILTER_NAME];
+        assert!(filter.account.is_empty());
+        assert_eq!(filter.pda.len(), 2);
+        assert_eq!(filter.pda[0].seeds_last, [vec![7; 32]]);
+    }
+
+    #[test]
+    fn test_observe() {
+        let mut tracker = WalletTokenAccounts::new([WALLET]).unwrap();
+        let other = bs58::encode([9; 32]).into_string();
+
+        // new account of the wallet at index from loaded addresses
+        let update = transaction(
+            vec![],
+            vec![token_balance(2, WALLET), token_balance(1, &other)],
+        );
+        assert!(tracker.observe(&update));
+        assert!(!tracker.observe(&update));
+        let account = bs58::encode([2; 32]).into_string();
+        assert_eq!(tracker.accounts().iter().collect::<Vec<_>>(), [&account]);
+        let request = tracker.apply(SubscribeRequest::default());
+        assert_eq!(request.accounts[FILTER_NAME].account, [account]);
+
+        // account is closed
+        let update = transaction(vec![token_balance(2, WALLET)], vec![]);
+        assert!(tracker.observe(&update));
+        assert!(tracker.accounts().is_empty());
+    }
+}
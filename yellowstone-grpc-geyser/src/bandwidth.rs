@@ -0,0 +1,190 @@
+use {
+    crate::config::ConfigGrpcBandwidth,
+    std::{
+        ops::Range,
+        sync::{Arc, Mutex},
+    },
+    tokio::time::{sleep, Duration, Instant},
+    tonic::Result as TonicResult,
+    yellowstone_grpc_proto::{
+        plugin::filter::{
+            message::{FilteredUpdate, FilteredUpdateAccount, FilteredUpdateOneof},
+            FilterAccountsDataSlice,
+        },
+        prost::Message,
+    },
+};
+
+#[derive(Debug)]
+struct BandwidthState {
+    /// Available bytes, negative while sent updates are paid off
+    available: f64,
+    updated_at: Instant,
+}
+
+/// Token bucket of the token policy, shared by all subscriptions of the token.
+/// Updates are always sent, the subscription waits until sent bytes are paid
+/// off, so the rate is kept on average and bursts are limited by `burst`.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    rate: f64,
+    burst: f64,
+    degrade_accounts: bool,
+    state: Mutex<BandwidthState>,
+}
+
+impl BandwidthLimiter {
+    pub fn new(config: &ConfigGrpcBandwidth) -> Self {
+        let rate = config.bytes_per_sec.get() as f64;
+        let burst = config.burst.unwrap_or(config.bytes_per_sec).get() as f64;
+        Self {
+            rate,
+            burst,
+            degrade_accounts: config.degrade_accounts,
+            state: Mutex::new(BandwidthState {
+                available: burst,
+                updated_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Refill by elapsed time, returns available bytes after refill
+    fn refill(&self, state: &mut BandwidthState) -> f64 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.updated_at).as_secs_f64();
+        state.available = (state.available + elapsed * self.rate).min(self.burst);
+        state.updated_at = now;
+        state.available
+    }
+
+    /// Consume bytes under one lock, `degraded_bytes` are consumed instead if
+    /// the limit is already exceeded. Returns `true` if the update should be
+    /// degraded and delay if the limit is exceeded after the update.
+    fn consume(&self, bytes: usize, degraded_bytes: Option<usize>) -> (bool, Option<Duration>) {
+        let mut state = self.state.lock().expect("unpoisoned mutex");
+        let exceeded = self.refill(&mut state) <= 0.0;
+        let (degrade, bytes) = match degraded_bytes {
+            Some(degraded_bytes) if exceeded => (true, degraded_bytes),
+            _ => (false, bytes),
+        };
+        state.available -= bytes as f64;
+        let delay =
+            (state.available < 0.0).then(|| Duration::from_secs_f64(-state.available / self.rate));
+        (degrade, delay)
+    }
+
+    fn degrade(msg: &mut FilteredUpdateAccount) {
+        msg.data_slice =
+            FilterAccountsDataSlice::new_unchecked(Arc::new(vec![Range { start: 0, end: 0 }]));
+        msg.data_omitted = true;
+    }
+
+    pub async fn shape(&self, item: TonicResult<FilteredUpdate>) -> TonicResult<FilteredUpdate> {
+        let Ok(mut update) = item else {
+            return item;
+        };
+        let bytes = update.encoded_len();
+        let degraded_bytes = match &update.message {
+            FilteredUpdateOneof::Account(msg) if self.degrade_accounts => {
+                let mut degraded = msg.clone();
+                Self::degrade(&mut degraded);
+                let degraded = FilteredUpdateOneof::Account(degraded);
+                Some(bytes - update.message.encoded_len() + degraded.encoded_len())
+            }
+            _ => None,
+        };
+        let (degrade, delay) = self.consume(bytes, degraded_bytes);
+        if degrade {
+            if let FilteredUpdateOneof::Account(msg) = &mut update.message {
+                Self::degrade(msg);
+                update.encoded_message = None;
+            }
+        }
+        if let Some(delay) = delay {
+            sleep(delay).await;
+        }
+        Ok(update)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::BandwidthLimiter,
+        crate::config::ConfigGrpcBandwidth,
+        prost_types::Timestamp,
+        std::{num::NonZeroU64, sync::Arc, time::SystemTime},
+        yellowstone_grpc_proto::{
+            geyser::{subscribe_update::UpdateOneof, SubscribeUpdate},
+            plugin::{
+                filter::message::{FilteredUpdate, FilteredUpdateOneof},
+                message::{MessageAccount, MessageAccountInfo},
+            },
+            prost::Message,
+        },
+    };
+
+    fn limiter(bytes_per_sec: u64, burst: u64, degrade_accounts: bool) -> BandwidthLimiter {
+        BandwidthLimiter::new(&ConfigGrpcBandwidth {
+            bytes_per_sec: NonZeroU64::new(bytes_per_sec).unwrap(),
+            burst: NonZeroU64::new(burst),
+            degrade_accounts,
+        })
+    }
+
+    fn account(data: Vec<u8>) -> FilteredUpdate {
+        let message = MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey: Default::default(),
+                lamports: 0,
+                owner: Default::default(),
+                executable: false,
+                rent_epoch: 0,
+                data,
+                write_version: 0,
+                txn_signature: None,
+            }),
+            slot: 0,
+            is_startup: false,
+            previous_owner: None,
+            created_at: Timestamp::from(SystemTime::now()),
+        };
+        FilteredUpdate::new_empty(FilteredUpdateOneof::account(&message, Default::default()))
+    }
+
+    fn is_degraded(update: &FilteredUpdate) -> bool {
+        let update = SubscribeUpdate::decode(update.encode_to_vec().as_slice()).unwrap();
+        let Some(UpdateOneof::Account(msg)) = update.update_oneof else {
+            panic!("expected account");
+        };
+        assert_eq!(msg.data_omitted, msg.account.unwrap().data.is_empty());
+        msg.data_omitted
+    }
+
+    #[test]
+    fn test_consume() {
+        let limiter = limiter(1_000_000, 100, false);
+        assert_eq!(limiter.consume(100, None), (false, None));
+        let (degrade, delay) = limiter.consume(1_000, Some(10));
+        assert!(!degrade);
+        assert!(delay.unwrap().as_micros() >= 900);
+        // limit is exceeded, degraded size is consumed
+        let (degrade, delay) = limiter.consume(1_000_000, Some(10));
+        assert!(degrade);
+        assert!(delay.unwrap().as_millis() < 10);
+    }
+
+    #[tokio::test]
+    async fn test_degrade_accounts() {
+        let limiter = limiter(1_000_000, 1_000, true);
+        let update = limiter.shape(Ok(account(vec![42; 100]))).await.unwrap();
+        assert!(!is_degraded(&update));
+
+        limiter.consume(10_000, None);
+        let mut update = account(vec![42; 100]);
+        update.encode_message();
+        let update = limiter.shape(Ok(update)).await.unwrap();
+        assert!(update.encoded_message.is_none());
+        assert!(is_degraded(&update));
+    }
+}
@@ -0,0 +1,345 @@
+use {
+    anyhow::Context,
+    clap::Parser,
+    futures::{sink::SinkExt, stream::StreamExt},
+    log::{error, info, warn},
+    std::{path::Path, sync::Arc, time::Duration},
+    tokio::{
+        fs,
+        runtime::Builder,
+        sync::{mpsc, watch},
+        time::sleep,
+    },
+    tonic::transport::channel::ClientTlsConfig,
+    yellowstone_grpc_client::{multi::MultiDedup, GeyserGrpcClient},
+    yellowstone_grpc_geyser::{
+        config::{Config, ConfigRelay, ConfigRelayUpstream},
+        grpc::{GrpcService, SubscriptionEvent},
+        log_level::LogLevels,
+        metrics::{self, PrometheusService},
+        relay::{GapReports, SubscriptionRegistry},
+        replay,
+    },
+    yellowstone_grpc_proto::{
+        geyser::{subscribe_update::UpdateOneof, SlotStatus, SubscribeRequest, SubscribeUpdate},
+        plugin::message::Message,
+    },
+};
+
+/// Updates received from upstreams and not yet deduplicated
+const UPSTREAMS_CHANNEL_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Parser)]
+#[clap(
+    author,
+    version,
+    about = "Run gRPC service without validator, fed by upstream gRPC subscription"
+)]
+struct Args {
+    #[clap(short, long, default_value_t = String::from("config.json"))]
+    /// Path to config
+    config: String,
+}
+
+/// Latest finalized slot saved before restart
+async fn load_state(path: &Path) -> anyhow::Result<Option<u64>> {
+    match fs::read_to_string(path).await {
+        Ok(state) => {
+            Ok(Some(state.trim().parse().with_context(|| {
+                format!("invalid slot in relay state {path:?}")
+            })?))
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error).with_context(|| format!("failed to read relay state {path:?}")),
+    }
+}
+
+/// Keep the upstream request in sync with filters of relay clients, the
+/// registry is saved on every change of the request
+async fn track_subscriptions(
+    mut registry: SubscriptionRegistry,
+    base: SubscribeRequest,
+    restore_timeout: Duration,
+    mut subscriptions_rx: mpsc::UnboundedReceiver<SubscriptionEvent>,
+    request_tx: watch::Sender<SubscribeRequest>,
+) {
+    let restore_deadline = sleep(restore_timeout);
+    tokio::pin!(restore_deadline);
+    let mut restoring = registry.restored() > 0;
+    loop {
+        tokio::select! {
+            event = subscriptions_rx.recv() => match event {
+                Some(event) => registry.apply(event),
+                None => break,
+            },
+            () = &mut restore_deadline, if restoring => {
+                restoring = false;
+                if registry.restored() > 0 {
+                    info!("{} saved subscriptions were not restored by clients", registry.restored());
+                }
+                registry.expire_restored();
+            }
+        }
+
+        let request = registry.upstream_request(&base);
+        let changed = request_tx.send_if_modified(|current| {
+            let changed = *current != request;
+            if changed {
+                *current = request;
+            }
+            changed
+        });
+        if changed {
+            if let Err(error) = registry.save() {
+                error!("failed to save relay subscriptions: {error:?}");
+            }
+        }
+    }
+}
+
+/// Subscribe to the upstream and send updates with index of the upstream to
+/// the relay until the stream is finished, changed request is sent to the
+/// same stream
+async fn subscribe_upstream(
+    index: usize,
+    upstream: &ConfigRelayUpstream,
+    from_slot: Option<u64>,
+    requests: &mut watch::Receiver<SubscribeRequest>,
+    updates_tx: &mpsc::Sender<(usize, SubscribeUpdate)>,
+) -> anyhow::Result<()> {
+    let mut client = GeyserGrpcClient::build_from_shared(upstream.endpoint.clone())?
+        .x_token(upstream.x_token.clone())?
+        .tls_config(ClientTlsConfig::new().with_native_roots())?
+        .connect()
+        .await
+        .context("failed to connect to upstream")?;
+    let mut request = requests.borrow_and_update().clone();
+    request.from_slot = from_slot.or(request.from_slot);
+    let (mut subscribe_tx, stream) = client
+        .subscribe_with_request(Some(request))
+        .await
+        .context("failed to subscribe to upstream")?;
+    let mut stream = std::pin::pin!(stream);
+    info!("subscribed to upstream {}", upstream.endpoint);
+
+    loop {
+        tokio::select! {
+            update = stream.next() => match update {
+                Some(update) => {
+                    if updates_tx.send((index, update?)).await.is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            },
+            Ok(()) = requests.changed() => {
+                let request = SubscribeRequest {
+                    from_slot: None,
+                    ..requests.borrow_and_update().clone()
+                };
+                subscribe_tx
+                    .send(request)
+                    .await
+                    .context("failed to update upstream subscription")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resubscribe to the upstream after `reconnect_interval` until the relay is
+/// stopped, `from_slot` is used only by the first subscription, slot could be
+/// not available anymore
+async fn run_upstream(
+    index: usize,
+    upstream: ConfigRelayUpstream,
+    mut requests: watch::Receiver<SubscribeRequest>,
+    mut from_slot: Option<u64>,
+    reconnect_interval: Duration,
+    updates_tx: mpsc::Sender<(usize, SubscribeUpdate)>,
+) {
+    loop {
+        let from_slot = from_slot.take();
+        match subscribe_upstream(index, &upstream, from_slot, &mut requests, &updates_tx).await {
+            Ok(()) => warn!("upstream {} stream finished", upstream.endpoint),
+            Err(error) => error!("upstream {} error: {error:?}", upstream.endpoint),
+        }
+        if updates_tx.is_closed() {
+            break;
+        }
+        sleep(reconnect_interval).await;
+    }
+}
+
+/// Send updates of upstreams to the service, with mirrors every update is
+/// relayed once from the upstream delivered it first and completeness of
+/// slots left the deduplication window is saved to gap reports
+async fn relay(
+    config: &ConfigRelay,
+    endpoints: &[String],
+    mut updates_rx: mpsc::Receiver<(usize, SubscribeUpdate)>,
+    gap_reports: Option<&GapReports>,
+    grpc_channel: &mpsc::UnboundedSender<Message>,
+) {
+    let mut dedup = gap_reports
+        .map(|_| MultiDedup::new(config.dedup_retain_slots).with_gap_reports(endpoints.len()));
+    let mut messages = vec![];
+    while let Some((index, update)) = updates_rx.recv().await {
+        if let (Some(dedup), Some(gap_reports)) = (&mut dedup, gap_reports) {
+            let deliver = dedup.push(index, &update);
+            while let Some(report) = dedup.pop_gap_report() {
+                gap_reports.push(report);
+            }
+            if !deliver {
+                continue;
+            }
+        }
+
+        if let (Some(path), Some(UpdateOneof::Slot(msg))) =
+            (&config.state_path, &update.update_oneof)
+        {
+            if msg.status == SlotStatus::SlotFinalized as i32 {
+                if let Err(error) = fs::write(path, msg.slot.to_string()).await {
+                    error!("failed to save relay state to {path:?}: {error}");
+                }
+            }
+        }
+        if let Err(error) = replay::push_update(update, &endpoints[index], &mut messages) {
+            error!("failed to relay update: {error:?}");
+        }
+        for message in messages.drain(..) {
+            if grpc_channel.send(message).is_ok() {
+                metrics::message_queue_size_inc();
+            }
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let config = Config::load_from_file(&args.config)?;
+    let log_levels = Arc::new(LogLevels::new(&config.log.level));
+    log_levels.setup();
+
+    let config_relay = config
+        .relay
+        .clone()
+        .context("`relay` is not set in config")?;
+    let request = config_relay
+        .upstream_request()
+        .map_err(anyhow::Error::msg)?;
+    let upstreams = config_relay.upstreams();
+    anyhow::ensure!(
+        upstreams.len() <= MultiDedup::ENDPOINTS_MAX,
+        "max number of relay upstreams is {}",
+        MultiDedup::ENDPOINTS_MAX
+    );
+    let endpoints = upstreams
+        .iter()
+        .map(|upstream| upstream.endpoint.clone())
+        .collect::<Vec<_>>();
+    let gap_reports = (upstreams.len() > 1).then(|| {
+        Arc::new(GapReports::new(
+            endpoints.clone(),
+            config_relay.gap_reports_max,
+        ))
+    });
+    let registry = config_relay
+        .subscriptions_path
+        .clone()
+        .map(SubscriptionRegistry::load)
+        .transpose()?;
+
+    let mut builder = Builder::new_multi_thread();
+    if let Some(worker_threads) = config.tokio.worker_threads {
+        builder.worker_threads(worker_threads);
+    }
+    let runtime = builder
+        .thread_name_fn(yellowstone_grpc_geyser::get_thread_name)
+        .enable_all()
+        .build()?;
+
+    runtime.block_on(async move {
+        let (debug_client_tx, debug_client_rx) = mpsc::unbounded_channel();
+        let (subscriptions_tx, subscriptions_rx) = mpsc::unbounded_channel();
+        let (_snapshot_channel, grpc_channel, grpc_shutdown) = GrpcService::create(
+            config.tokio,
+            config.grpc,
+            config.debug_clients_http.then_some(debug_client_tx),
+            registry.is_some().then_some(subscriptions_tx),
+            false,
+        )
+        .await?;
+        let prometheus = PrometheusService::new(
+            config.prometheus,
+            config.debug_clients_http.then_some(debug_client_rx),
+            config.log.http_control.then_some(log_levels),
+            gap_reports.clone(),
+        )
+        .await?;
+
+        // updates of the restarted relay are replayed by the upstream, so
+        // clients of the relay can resume with `from_slot` too
+        let from_slot = match &config_relay.state_path {
+            Some(path) => load_state(path).await?,
+            None => None,
+        };
+        if let Some(slot) = from_slot {
+            info!("resume upstream subscription from slot {slot}");
+        }
+        // upstream request is extended with filters of relay clients, saved
+        // filters are subscribed until clients resubscribe after restart
+        let request_rx = match registry {
+            Some(registry) => {
+                info!("loaded {} saved subscriptions", registry.restored());
+                let (request_tx, request_rx) = watch::channel(registry.upstream_request(&request));
+                tokio::spawn(track_subscriptions(
+                    registry,
+                    request,
+                    config_relay.subscriptions_restore_timeout,
+                    subscriptions_rx,
+                    request_tx,
+                ));
+                request_rx
+            }
+            None => watch::channel(request).1,
+        };
+        let (updates_tx, updates_rx) = mpsc::channel(UPSTREAMS_CHANNEL_CAPACITY);
+        let upstreams = upstreams
+            .into_iter()
+            .enumerate()
+            .map(|(index, upstream)| {
+                tokio::spawn(run_upstream(
+                    index,
+                    upstream,
+                    request_rx.clone(),
+                    from_slot,
+                    config_relay.reconnect_interval,
+                    updates_tx.clone(),
+                ))
+            })
+            .collect::<Vec<_>>();
+        drop(updates_tx);
+        tokio::select! {
+            () = relay(
+                &config_relay,
+                &endpoints,
+                updates_rx,
+                gap_reports.as_deref(),
+                &grpc_channel,
+            ) => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+        for upstream in upstreams {
+            upstream.abort();
+        }
+        info!("shutting down");
+
+        grpc_shutdown.notify_one();
+        prometheus.shutdown();
+        Ok::<_, anyhow::Error>(())
+    })?;
+
+    runtime.shutdown_timeout(Duration::from_secs(30));
+    Ok(())
+}
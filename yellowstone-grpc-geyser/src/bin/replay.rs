@@ -0,0 +1,103 @@
+use {
+    clap::Parser,
+    log::info,
+    std::{path::PathBuf, sync::Arc, time::Duration},
+    tokio::{runtime::Builder, sync::mpsc, time::sleep},
+    yellowstone_grpc_geyser::{
+        config::Config,
+        grpc::GrpcService,
+        log_level::LogLevels,
+        metrics::{self, PrometheusService},
+        replay,
+    },
+};
+
+#[derive(Debug, Parser)]
+#[clap(
+    author,
+    version,
+    about = "Run gRPC service without validator, fed by capture files"
+)]
+struct Args {
+    #[clap(short, long, default_value_t = String::from("config.json"))]
+    /// Path to config
+    config: String,
+
+    /// Delay before replay, gives clients time to connect
+    #[clap(long, default_value_t = 0)]
+    start_delay_ms: u64,
+
+    /// Delay between slots
+    #[clap(long, default_value_t = 400)]
+    slot_interval_ms: u64,
+
+    /// Stop service with delay after replay, otherwise keep serving until killed
+    #[clap(long)]
+    exit_after_ms: Option<u64>,
+
+    /// Capture files (length-delimited `SubscribeUpdate`) or directories with `*.bin` captures
+    #[clap(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let config = Config::load_from_file(&args.config)?;
+    let log_levels = Arc::new(LogLevels::new(&config.log.level));
+    log_levels.setup();
+
+    let messages = replay::load_messages(&args.paths)?;
+    info!("loaded {} messages", messages.len());
+
+    let mut builder = Builder::new_multi_thread();
+    if let Some(worker_threads) = config.tokio.worker_threads {
+        builder.worker_threads(worker_threads);
+    }
+    let runtime = builder
+        .thread_name_fn(yellowstone_grpc_geyser::get_thread_name)
+        .enable_all()
+        .build()?;
+
+    runtime.block_on(async move {
+        let (debug_client_tx, debug_client_rx) = mpsc::unbounded_channel();
+        let (_snapshot_channel, grpc_channel, grpc_shutdown) = GrpcService::create(
+            config.tokio,
+            config.grpc,
+            config.debug_clients_http.then_some(debug_client_tx),
+            None,
+            false,
+        )
+        .await?;
+        let prometheus = PrometheusService::new(
+            config.prometheus,
+            config.debug_clients_http.then_some(debug_client_rx),
+            config.log.http_control.then_some(log_levels),
+            None,
+        )
+        .await?;
+
+        sleep(Duration::from_millis(args.start_delay_ms)).await;
+        let mut slot = None;
+        for message in messages {
+            if slot.is_some_and(|slot| slot != message.get_slot()) {
+                sleep(Duration::from_millis(args.slot_interval_ms)).await;
+            }
+            slot = Some(message.get_slot());
+            if grpc_channel.send(message).is_ok() {
+                metrics::message_queue_size_inc();
+            }
+        }
+        info!("replay finished");
+
+        match args.exit_after_ms {
+            Some(exit_after_ms) => sleep(Duration::from_millis(exit_after_ms)).await,
+            None => std::future::pending().await,
+        }
+        grpc_shutdown.notify_one();
+        prometheus.shutdown();
+        Ok::<_, anyhow::Error>(())
+    })?;
+
+    runtime.shutdown_timeout(Duration::from_secs(30));
+    Ok(())
+}
@@ -3,13 +3,30 @@ use {
         GeyserPluginError, Result as PluginResult,
     },
     serde::{de, Deserialize, Deserializer},
+    serde_json::Value,
+    solana_sdk::pubkey::Pubkey,
     std::{
-        collections::HashSet, fmt, fs::read_to_string, net::SocketAddr, path::Path, str::FromStr,
+        collections::{HashMap, HashSet},
+        env, fmt,
+        fs::read_to_string,
+        net::SocketAddr,
+        num::NonZeroU64,
+        path::{Path, PathBuf},
+        str::FromStr,
         time::Duration,
     },
     tokio::sync::Semaphore,
     tonic::codec::CompressionEncoding,
-    yellowstone_grpc_proto::plugin::filter::limits::FilterLimits,
+    yellowstone_grpc_proto::{
+        geyser::{
+            CommitmentLevel as CommitmentLevelProto, GetVersionFeatures, SubscribeRequest,
+            SubscribeRequestFilterSlots,
+        },
+        plugin::{
+            filter::{limits::FilterLimits, Filter},
+            message::CommitmentLevel,
+        },
+    },
 };
 
 #[derive(Debug, Clone, Deserialize)]
@@ -26,18 +43,131 @@ pub struct Config {
     /// Collect client filters, processed slot and make it available on prometheus port `/debug_clients`
     #[serde(default)]
     pub debug_clients_http: bool,
+    /// Upstream of the `relay` binary
+    #[serde(default)]
+    pub relay: Option<ConfigRelay>,
 }
 
 impl Config {
-    fn load_from_str(config: &str) -> PluginResult<Self> {
-        serde_json::from_str(config).map_err(|error| GeyserPluginError::ConfigFileReadError {
-            msg: error.to_string(),
-        })
+    /// Max depth of nested `$include`
+    const INCLUDE_DEPTH_MAX: usize = 8;
+
+    fn load_from_str(config: &str, dir: &Path) -> PluginResult<Self> {
+        let value = Self::load_value(config, dir, 0)?;
+        let config: Self = serde_json::from_value(value).map_err(Self::read_error)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks of options which depend on each other
+    fn validate(&self) -> PluginResult<()> {
+        if let Some(snapshot_download) = &self.grpc.snapshot_download {
+            if snapshot_download.accounts.is_empty() && snapshot_download.owners.is_empty() {
+                return Err(Self::read_error(
+                    "`grpc.snapshot_download` requires `accounts` or `owners`, the whole accounts state can't be kept in memory",
+                ));
+            }
+        }
+        Ok(())
     }
 
     pub fn load_from_file<P: AsRef<Path>>(file: P) -> PluginResult<Self> {
+        let file = file.as_ref();
         let config = read_to_string(file).map_err(GeyserPluginError::ConfigFileOpenError)?;
-        Self::load_from_str(&config)
+        Self::load_from_str(&config, file.parent().unwrap_or(Path::new(".")))
+    }
+
+    fn read_error(error: impl fmt::Display) -> GeyserPluginError {
+        GeyserPluginError::ConfigFileReadError {
+            msg: error.to_string(),
+        }
+    }
+
+    fn load_value(config: &str, dir: &Path, depth: usize) -> PluginResult<Value> {
+        let mut value = serde_json::from_str(config).map_err(Self::read_error)?;
+        Self::interpolate(&mut value, dir, depth)?;
+        Ok(value)
+    }
+
+    /// Replace `${ENV_VAR}` / `${ENV_VAR:-default}` in strings (`$${` for literal
+    /// `${`) and merge objects with `"$include": "path"` into the object, path is
+    /// relative to the file directory, keys of the object take precedence
+    fn interpolate(value: &mut Value, dir: &Path, depth: usize) -> PluginResult<()> {
+        match value {
+            Value::String(string) => *string = Self::interpolate_env(string)?,
+            Value::Array(array) => {
+                for value in array.iter_mut() {
+                    Self::interpolate(value, dir, depth)?;
+                }
+            }
+            Value::Object(object) => {
+                for value in object.values_mut() {
+                    Self::interpolate(value, dir, depth)?;
+                }
+                if let Some(include) = object.remove("$include") {
+                    let Value::String(path) = include else {
+                        return Err(Self::read_error("`$include` should be a string"));
+                    };
+                    if depth >= Self::INCLUDE_DEPTH_MAX {
+                        return Err(Self::read_error(format!(
+                            "`$include` depth is limited to {}",
+                            Self::INCLUDE_DEPTH_MAX
+                        )));
+                    }
+                    let path = dir.join(path);
+                    let config = read_to_string(&path).map_err(|error| {
+                        Self::read_error(format!("failed to read {path:?}: {error}"))
+                    })?;
+                    let dir = path.parent().unwrap_or(Path::new("."));
+                    let Value::Object(mut included) = Self::load_value(&config, dir, depth + 1)?
+                    else {
+                        return Err(Self::read_error(format!(
+                            "included {path:?} should be an object"
+                        )));
+                    };
+                    included.append(object);
+                    *object = included;
+                }
+            }
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+        Ok(())
+    }
+
+    fn interpolate_env(value: &str) -> PluginResult<String> {
+        let mut result = String::with_capacity(value.len());
+        let mut rest = value;
+        while let Some(start) = rest.find('$') {
+            result.push_str(&rest[..start]);
+            rest = &rest[start..];
+            if let Some(escaped) = rest.strip_prefix("$${") {
+                result.push_str("${");
+                rest = escaped;
+            } else if let Some(expr) = rest.strip_prefix("${") {
+                let end = expr
+                    .find('}')
+                    .ok_or_else(|| Self::read_error(format!("unclosed `${{` in {value:?}")))?;
+                let (name, default) = match expr[..end].split_once(":-") {
+                    Some((name, default)) => (name, Some(default)),
+                    None => (&expr[..end], None),
+                };
+                match (env::var(name), default) {
+                    (Ok(var), _) => result.push_str(&var),
+                    (Err(_), Some(default)) => result.push_str(default),
+                    (Err(error), None) => {
+                        return Err(Self::read_error(format!(
+                            "failed to read env variable {name:?}: {error}"
+                        )))
+                    }
+                }
+                rest = &expr[end + 1..];
+            } else {
+                result.push('$');
+                rest = &rest[1..];
+            }
+        }
+        result.push_str(rest);
+        Ok(result)
     }
 }
 
@@ -47,12 +177,16 @@ pub struct ConfigLog {
     /// Log level.
     #[serde(default = "ConfigLog::default_level")]
     pub level: String,
+    /// Change log level per module at runtime on prometheus port `/log_level`
+    #[serde(default)]
+    pub http_control: bool,
 }
 
 impl Default for ConfigLog {
     fn default() -> Self {
         Self {
             level: Self::default_level(),
+            http_control: false,
         }
     }
 }
@@ -66,9 +200,11 @@ impl ConfigLog {
 #[derive(Debug, Clone, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigTokio {
-    /// Number of worker threads in Tokio runtime
+    /// Number of worker threads in Tokio runtimes of the plugin and of the
+    /// geyser loop, with `grpc.turbo` only in the plugin runtime
     pub worker_threads: Option<usize>,
-    /// Threads affinity
+    /// Threads affinity, with `grpc.turbo` the geyser loop thread uses
+    /// `grpc.turbo.affinity` instead
     #[serde(deserialize_with = "ConfigTokio::deserialize_affinity")]
     pub affinity: Option<Vec<usize>>,
 }
@@ -180,6 +316,13 @@ pub struct ConfigGrpc {
     pub filter_limits: FilterLimits,
     /// x_token to enforce on connections
     pub x_token: Option<String>,
+    /// Additional tokens with subscription policy, accepted together with `x_token`
+    #[serde(default)]
+    pub x_tokens: HashMap<String, ConfigGrpcTokenPolicy>,
+    /// Named subscriptions in the JSON format of `SubscribeRequest`, activated
+    /// with `template` in `SubscribeRequest`
+    #[serde(default)]
+    pub subscription_templates: HashMap<String, SubscribeRequest>,
     /// Filter name size limit
     #[serde(default = "ConfigGrpc::default_filter_name_size_limit")]
     pub filter_name_size_limit: usize,
@@ -198,6 +341,74 @@ pub struct ConfigGrpc {
         deserialize_with = "deserialize_int_str"
     )]
     pub replay_stored_slots: u64,
+    /// Save stored slots to the file on unload and load them on start, so
+    /// clients can replay slots received before the restart, disabled if not set
+    #[serde(default)]
+    pub replay_persist: Option<ConfigGrpcReplayPersist>,
+    /// Finalized accounts state for `SnapshotDownload`, disabled if not set.
+    /// Configured accounts and accounts of configured owners updated since
+    /// start are kept in memory with versions of the stored finalized slots
+    #[serde(default)]
+    pub snapshot_download: Option<ConfigGrpcSnapshotDownload>,
+    /// Latency-optimized mode, disabled if not set
+    #[serde(default)]
+    pub turbo: Option<ConfigGrpcTurbo>,
+    /// Processed messages in shared memory for consumers on the same host,
+    /// disabled if not set
+    #[serde(default)]
+    pub shm: Option<ConfigGrpcShm>,
+    /// Validator and cluster reported in `GetVersion`, not reported if not set
+    #[serde(default)]
+    pub cluster: Option<ConfigGrpcCluster>,
+    /// Track owners of updated accounts and set `previous_owner` on owner change,
+    /// `owner_changed` filters are rejected if disabled
+    #[serde(default)]
+    pub owner_change_tracking: bool,
+    /// Max number of accounts with known owner, least recently updated accounts
+    /// are forgotten, default 10M (~100 bytes per account)
+    #[serde(
+        default = "ConfigGrpc::default_owner_change_tracking_max_accounts",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub owner_change_tracking_max_accounts: usize,
+    /// Max number of distinct `x-subscription-id` labels in metrics of
+    /// connected clients, other labels are reported as `other`
+    #[serde(
+        default = "ConfigGrpc::default_subscription_label_metrics_max",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub subscription_label_metrics_max: usize,
+    /// Close subscriptions without pings, filter updates and sent updates for
+    /// this duration, disabled if not set
+    #[serde(default, with = "humantime_serde")]
+    pub idle_timeout: Option<Duration>,
+    /// Train zstd dictionaries per update type on sampled updates and save
+    /// them to the file, disabled if not set
+    #[serde(default)]
+    pub zstd_dictionary: Option<ConfigGrpcZstdDictionary>,
+    /// Recent state of configured accounts for `GetAccount`, disabled if not set
+    #[serde(default)]
+    pub account_cache: Option<ConfigGrpcAccountCache>,
+    /// Write updates which do not fit into the client channel to disk instead
+    /// of dropping the client, disabled if not set
+    #[serde(default)]
+    pub spill: Option<ConfigGrpcSpill>,
+    /// Per-client queues by type of updates, updates of higher priority are
+    /// sent first when the client is congested, disabled if not set
+    #[serde(default)]
+    pub priority: Option<ConfigGrpcPriority>,
+    /// Encode large updates of every client by the pool of blocking workers
+    /// instead of the connection task, disabled if not set
+    #[serde(default)]
+    pub encode: Option<ConfigGrpcEncode>,
+    /// Moment stamped as `created_at` of updates: geyser notification or send
+    /// to the client, with `send` notification time is set to `ingested_at`
+    #[serde(default)]
+    pub created_at: ConfigGrpcCreatedAt,
+    /// Replica (region) of the server in `x-replica` header of `Subscribe` and
+    /// `GetVersion` responses and optionally in updates, disabled if not set
+    #[serde(default)]
+    pub replica: Option<ConfigGrpcReplica>,
     #[serde(default)]
     pub server_http2_adaptive_window: Option<bool>,
     #[serde(default, with = "humantime_serde")]
@@ -246,6 +457,500 @@ impl ConfigGrpc {
     const fn default_replay_stored_slots() -> u64 {
         0
     }
+
+    const fn default_owner_change_tracking_max_accounts() -> usize {
+        10_000_000
+    }
+
+    const fn default_subscription_label_metrics_max() -> usize {
+        100
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcSnapshotDownload {
+    /// Stored accounts
+    #[serde(
+        default,
+        deserialize_with = "ConfigGrpcAccountCache::deserialize_pubkeys"
+    )]
+    pub accounts: HashSet<Pubkey>,
+    /// Stored accounts of these owners, memory grows with the number of owned accounts
+    #[serde(
+        default,
+        deserialize_with = "ConfigGrpcAccountCache::deserialize_pubkeys"
+    )]
+    pub owners: HashSet<Pubkey>,
+    /// Number of the last finalized slots available for download (and resume)
+    #[serde(
+        default = "ConfigGrpcSnapshotDownload::keep_snapshots_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub keep_snapshots: usize,
+    /// Maximum number of accounts in one response
+    #[serde(
+        default = "ConfigGrpcSnapshotDownload::chunk_size_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub chunk_size: usize,
+    /// Capacity of the channel of stored accounts and slot messages, the geyser
+    /// loop never waits: once it's full the storage is disabled
+    #[serde(
+        default = "ConfigGrpcSnapshotDownload::channel_capacity_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub channel_capacity: usize,
+}
+
+impl ConfigGrpcSnapshotDownload {
+    const fn keep_snapshots_default() -> usize {
+        32
+    }
+
+    const fn chunk_size_default() -> usize {
+        1_000
+    }
+
+    const fn channel_capacity_default() -> usize {
+        100_000
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcAccountCache {
+    /// Cached accounts
+    #[serde(
+        default,
+        deserialize_with = "ConfigGrpcAccountCache::deserialize_pubkeys"
+    )]
+    pub accounts: HashSet<Pubkey>,
+    /// Cached accounts of these owners, memory grows with the number of owned accounts
+    #[serde(
+        default,
+        deserialize_with = "ConfigGrpcAccountCache::deserialize_pubkeys"
+    )]
+    pub owners: HashSet<Pubkey>,
+    /// Capacity of the channel of cached accounts and slot messages, the
+    /// geyser loop never waits: once it's full the cache is disabled
+    #[serde(
+        default = "ConfigGrpcAccountCache::channel_capacity_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub channel_capacity: usize,
+}
+
+impl ConfigGrpcAccountCache {
+    const fn channel_capacity_default() -> usize {
+        100_000
+    }
+
+    fn deserialize_pubkeys<'de, D>(deserializer: D) -> Result<HashSet<Pubkey>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|value| value.parse().map_err(de::Error::custom))
+            .collect()
+    }
+}
+
+/// Geyser loop runs on a dedicated thread with raised priority, processed
+/// slots and transactions are sent to clients without batching
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcTurbo {
+    /// Affinity of the geyser loop thread
+    #[serde(default, deserialize_with = "ConfigTokio::deserialize_affinity")]
+    pub affinity: Option<Vec<usize>>,
+    /// Nice value of the geyser loop thread, values below zero require `CAP_SYS_NICE`
+    #[serde(default = "ConfigGrpcTurbo::default_nice")]
+    pub nice: i32,
+    /// Subscriptions on this address are served without send compression,
+    /// compression of the main address is not changed
+    #[serde(default)]
+    pub address: Option<SocketAddr>,
+}
+
+impl Default for ConfigGrpcTurbo {
+    fn default() -> Self {
+        Self {
+            affinity: None,
+            nice: Self::default_nice(),
+            address: None,
+        }
+    }
+}
+
+impl ConfigGrpcTurbo {
+    const fn default_nice() -> i32 {
+        -10
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcCluster {
+    /// Validator identity pubkey
+    #[serde(default, deserialize_with = "ConfigGrpcCluster::deserialize_identity")]
+    pub identity: Option<Pubkey>,
+    #[serde(default)]
+    pub shred_version: Option<u16>,
+    /// Feature set id of the validator software
+    #[serde(default)]
+    pub feature_set: Option<u32>,
+    /// Cluster name: `mainnet-beta`, `testnet`, `devnet` or custom
+    #[serde(default)]
+    pub cluster: Option<String>,
+}
+
+impl ConfigGrpcCluster {
+    fn deserialize_identity<'de, D>(deserializer: D) -> Result<Option<Pubkey>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| value.parse().map_err(de::Error::custom))
+            .transpose()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcTokenPolicy {
+    /// Minimum commitment level of subscriptions: `processed`, `confirmed` or `finalized`
+    #[serde(
+        default,
+        deserialize_with = "ConfigGrpcTokenPolicy::deserialize_commitment"
+    )]
+    pub commitment_min: Option<CommitmentLevel>,
+    /// Allowed update types (`accounts`, `slots`, `transactions`, `transactions_status`,
+    /// `blocks`, `blocks_meta`, `entry`), everything is allowed if not set
+    #[serde(
+        default,
+        deserialize_with = "ConfigGrpcTokenPolicy::deserialize_filters"
+    )]
+    pub filters: Option<HashSet<String>>,
+    /// Allowed subscription templates, subscriptions without template are
+    /// rejected if set (requests with only ping are accepted)
+    #[serde(default)]
+    pub templates: Option<HashSet<String>>,
+    /// Bandwidth shared by all subscriptions of the token, not limited if not set
+    #[serde(default)]
+    pub bandwidth: Option<ConfigGrpcBandwidth>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcBandwidth {
+    /// Should be greater than zero
+    #[serde(deserialize_with = "deserialize_int_str")]
+    pub bytes_per_sec: NonZeroU64,
+    /// Bytes which can be sent above the rate at once, default is one second
+    /// of the rate, should be greater than zero
+    #[serde(default, deserialize_with = "deserialize_int_str_maybe")]
+    pub burst: Option<NonZeroU64>,
+    /// Send account updates without data and with `data_omitted` while the
+    /// limit is exceeded, other updates are delayed
+    #[serde(default)]
+    pub degrade_accounts: bool,
+}
+
+impl ConfigGrpcTokenPolicy {
+    fn deserialize_commitment<'de, D>(deserializer: D) -> Result<Option<CommitmentLevel>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| match value.as_str() {
+                "processed" => Ok(CommitmentLevel::Processed),
+                "confirmed" => Ok(CommitmentLevel::Confirmed),
+                "finalized" => Ok(CommitmentLevel::Finalized),
+                value => Err(de::Error::custom(format!(
+                    "unknown commitment level: {value}"
+                ))),
+            })
+            .transpose()
+    }
+
+    fn deserialize_filters<'de, D>(deserializer: D) -> Result<Option<HashSet<String>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let filters = Option::<HashSet<String>>::deserialize(deserializer)?;
+        for filter in filters.iter().flatten() {
+            if !GetVersionFeatures::FILTERS.contains(&filter.as_str()) {
+                return Err(de::Error::custom(format!("unknown filter: {filter}")));
+            }
+        }
+        Ok(filters)
+    }
+
+    /// Verify that template of the subscription request is allowed for the token
+    pub fn check_template(&self, request: &SubscribeRequest) -> Result<(), String> {
+        let Some(templates) = &self.templates else {
+            return Ok(());
+        };
+        match &request.template {
+            Some(name) if templates.contains(name) => Ok(()),
+            Some(name) => Err(format!("template {name} is not allowed")),
+            // ping without any filters or options
+            None if request.ping.is_some()
+                && *request
+                    == (SubscribeRequest {
+                        ping: request.ping,
+                        ..Default::default()
+                    }) =>
+            {
+                Ok(())
+            }
+            None => Err("subscriptions without template are not allowed".to_owned()),
+        }
+    }
+
+    /// Verify that subscription is allowed for the token
+    pub fn check(&self, filter: &Filter) -> Result<(), String> {
+        if let Some(commitment_min) = self.commitment_min {
+            // accounts filters can have own commitment
+            let commitment = filter.get_commitment_level_min();
+            if commitment < commitment_min {
+                return Err(format!(
+                    "commitment level {commitment:?} is not allowed, minimum is {commitment_min:?}"
+                ));
+            }
+        }
+
+        if let Some(filters) = &self.filters {
+            for (name, count) in filter.get_metrics() {
+                let name = match name {
+                    "all" => continue,
+                    "entries" => "entry",
+                    name => name,
+                };
+                if count > 0 && !filters.contains(name) {
+                    return Err(format!("filter {name} is not allowed"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcShm {
+    /// Path of the ring buffer file, `/dev/shm` is backed by memory
+    pub path: PathBuf,
+    /// Size of the ring buffer, default is 1GiB
+    #[serde(
+        default = "ConfigGrpcShm::capacity_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub capacity: usize,
+    /// Number of blocking workers encoding messages, messages are never
+    /// encoded by the geyser loop. Order of messages in the ring buffer is
+    /// preserved. Default is 1.
+    #[serde(
+        default = "ConfigGrpcShm::workers_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub workers: usize,
+    /// Dictionaries trained with `grpc.zstd_dictionary`, if set updates are
+    /// written as zstd frames compressed with them, consumers need the same
+    /// file. Disabled by default.
+    #[serde(default)]
+    pub zstd_dictionary: Option<PathBuf>,
+}
+
+impl ConfigGrpcShm {
+    const fn capacity_default() -> usize {
+        1024 * 1024 * 1024
+    }
+
+    const fn workers_default() -> usize {
+        1
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcReplayPersist {
+    /// Path of the file, written in the capture format of `replay` on a clean
+    /// unload of the plugin, removed once loaded
+    pub path: PathBuf,
+    /// Maximum size of the file, the latest slots are saved, default is 256MiB
+    #[serde(
+        default = "ConfigGrpcReplayPersist::max_size_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub max_size: usize,
+    /// File older than `max_age` is not loaded, default is 10 minutes
+    #[serde(
+        default = "ConfigGrpcReplayPersist::max_age_default",
+        with = "humantime_serde"
+    )]
+    pub max_age: Duration,
+}
+
+impl ConfigGrpcReplayPersist {
+    const fn max_size_default() -> usize {
+        256 * 1024 * 1024
+    }
+
+    const fn max_age_default() -> Duration {
+        Duration::from_secs(600)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcReplica {
+    /// Identifier of the replica, e.g. `eu-1`, should be a valid header value
+    pub id: String,
+    /// Set `replica` of every update, increases size of every update
+    #[serde(default)]
+    pub updates: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigGrpcCreatedAt {
+    /// Update received from geyser, latency on the client includes queueing
+    /// delay of the plugin
+    #[default]
+    Notification,
+    /// Update sent to the client stream, latency on the client is network
+    /// delay and `created_at - ingested_at` is queueing delay
+    Send,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcSpill {
+    /// Directory for spill files, one file per client
+    pub path: PathBuf,
+    /// Maximum size of the spill file per client, client is dropped as lagging
+    /// on reaching the limit, default is 256MiB
+    #[serde(
+        default = "ConfigGrpcSpill::max_bytes_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub max_bytes: u64,
+}
+
+impl ConfigGrpcSpill {
+    const fn max_bytes_default() -> u64 {
+        256 * 1024 * 1024
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigGrpcPriorityClass {
+    Slot,
+    BlockMeta,
+    Transaction,
+    TransactionStatus,
+    Account,
+    Entry,
+    Block,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcPriority {
+    /// Classes from the highest priority, updates of other types have the
+    /// lowest priority, pings, pongs, errors and acknowledgements of filters
+    /// are sent before all updates
+    #[serde(default = "ConfigGrpcPriority::classes_default")]
+    pub classes: Vec<ConfigGrpcPriorityClass>,
+    /// Capacity shared by queues of all classes, `channel_capacity` by default
+    #[serde(default, deserialize_with = "deserialize_int_str_maybe")]
+    pub capacity: Option<usize>,
+}
+
+impl ConfigGrpcPriority {
+    fn classes_default() -> Vec<ConfigGrpcPriorityClass> {
+        vec![
+            ConfigGrpcPriorityClass::Slot,
+            ConfigGrpcPriorityClass::BlockMeta,
+            ConfigGrpcPriorityClass::Transaction,
+            ConfigGrpcPriorityClass::Account,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcEncode {
+    /// Max number of updates of one client encoded at a time, order of
+    /// updates is preserved, default is 4
+    #[serde(
+        default = "ConfigGrpcEncode::workers_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub workers: usize,
+    /// Updates with smaller encoded size are encoded by the connection task,
+    /// default is 64KiB
+    #[serde(
+        default = "ConfigGrpcEncode::min_size_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub min_size: usize,
+}
+
+impl ConfigGrpcEncode {
+    const fn workers_default() -> usize {
+        4
+    }
+
+    const fn min_size_default() -> usize {
+        64 * 1024
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigGrpcZstdDictionary {
+    /// Path of the dictionaries file
+    pub path: PathBuf,
+    /// Number of collected updates, update types with too few samples are
+    /// saved without dictionary, default is 10k
+    #[serde(
+        default = "ConfigGrpcZstdDictionary::samples_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub samples: usize,
+    /// Only every `sample_interval` update is encoded and collected, blocks
+    /// are never collected. Default is 100.
+    #[serde(
+        default = "ConfigGrpcZstdDictionary::sample_interval_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub sample_interval: usize,
+    /// Max size of dictionary per update type, default is 110KiB
+    #[serde(
+        default = "ConfigGrpcZstdDictionary::size_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub size: usize,
+}
+
+impl ConfigGrpcZstdDictionary {
+    const fn samples_default() -> usize {
+        10_000
+    }
+
+    const fn sample_interval_default() -> usize {
+        100
+    }
+
+    const fn size_default() -> usize {
+        110 * 1024
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -303,11 +1008,171 @@ impl ConfigGrpcCompression {
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConfigPrometheus {
     /// Address of Prometheus service.
     pub address: SocketAddr,
+    /// CPU profiles on `/debug/pprof/profile` (requires `profiling` feature)
+    /// and heap profiles on `/debug/pprof/heap` (requires `jemalloc` feature)
+    #[serde(default)]
+    pub profiling: Option<ConfigProfiling>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigProfiling {
+    /// Value of `x-token` header required for profiling requests, can't be empty
+    #[serde(deserialize_with = "ConfigProfiling::deserialize_admin_token")]
+    pub admin_token: String,
+    /// Max duration of one profile
+    #[serde(
+        default = "ConfigProfiling::max_duration_default",
+        with = "humantime_serde"
+    )]
+    pub max_duration: Duration,
+    /// Sampling frequency, Hz
+    #[serde(default = "ConfigProfiling::frequency_default")]
+    pub frequency: i32,
+}
+
+impl ConfigProfiling {
+    const fn max_duration_default() -> Duration {
+        Duration::from_secs(120)
+    }
+
+    const fn frequency_default() -> i32 {
+        99
+    }
+
+    fn deserialize_admin_token<'de, D>(deserializer: D) -> Result<String, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let token = String::deserialize(deserializer)?;
+        if token.is_empty() {
+            return Err(de::Error::custom("`admin_token` can't be empty"));
+        }
+        Ok(token)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigRelay {
+    /// Upstream gRPC endpoint
+    pub endpoint: String,
+    /// x_token of the upstream, not related to tokens of the relay clients
+    #[serde(default)]
+    pub x_token: Option<String>,
+    /// Broad upstream subscription in the JSON format of `SubscribeRequest`,
+    /// clients of the relay are matched against received updates
+    pub request: SubscribeRequest,
+    /// Delay before reconnect to the upstream
+    #[serde(
+        default = "ConfigRelay::reconnect_interval_default",
+        with = "humantime_serde"
+    )]
+    pub reconnect_interval: Duration,
+    /// File with the latest finalized slot, written on every finalized slot
+    /// and used as `from_slot` of the first upstream subscription after
+    /// restart, disabled if not set
+    #[serde(default)]
+    pub state_path: Option<PathBuf>,
+    /// Additional upstreams subscribed with the same request, updates are
+    /// deduplicated and relayed from whichever upstream delivers first
+    #[serde(default)]
+    pub mirrors: Vec<ConfigRelayUpstream>,
+    /// Slots kept for deduplication of updates from upstreams, completeness of
+    /// the slot is reported when it leaves the window
+    #[serde(
+        default = "ConfigRelay::dedup_retain_slots_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub dedup_retain_slots: u64,
+    /// Max number of slot gap reports served on `/gap_reports` of the
+    /// prometheus address, older reports are dropped
+    #[serde(
+        default = "ConfigRelay::gap_reports_max_default",
+        deserialize_with = "deserialize_int_str"
+    )]
+    pub gap_reports_max: usize,
+    /// File with filters of relay clients, rewritten when the upstream
+    /// subscription changes. Upstream request is extended with filters of
+    /// clients, after restart saved filters are subscribed until clients
+    /// resubscribe or `subscriptions_restore_timeout` is passed. Upstream
+    /// request is not extended if not set
+    #[serde(default)]
+    pub subscriptions_path: Option<PathBuf>,
+    /// Time for clients to resubscribe after restart
+    #[serde(
+        default = "ConfigRelay::subscriptions_restore_timeout_default",
+        with = "humantime_serde"
+    )]
+    pub subscriptions_restore_timeout: Duration,
+}
+
+impl ConfigRelay {
+    const SLOTS_FILTER: &'static str = "_relay_slots";
+
+    const fn reconnect_interval_default() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    const fn dedup_retain_slots_default() -> u64 {
+        150
+    }
+
+    const fn gap_reports_max_default() -> usize {
+        10_000
+    }
+
+    const fn subscriptions_restore_timeout_default() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    /// Primary upstream followed by mirrors
+    pub fn upstreams(&self) -> Vec<ConfigRelayUpstream> {
+        let primary = ConfigRelayUpstream {
+            endpoint: self.endpoint.clone(),
+            x_token: self.x_token.clone(),
+        };
+        std::iter::once(primary)
+            .chain(self.mirrors.iter().cloned())
+            .collect()
+    }
+
+    /// Upstream request at `processed` with all slot statuses, so the relay
+    /// tracks commitment of slots for its clients like the plugin
+    pub fn upstream_request(&self) -> Result<SubscribeRequest, String> {
+        if !self.request.blocks.is_empty() {
+            return Err(
+                "blocks are not supported in relay request, blocks are reconstructed from entries, transactions, accounts and blocks meta"
+                    .to_owned(),
+            );
+        }
+
+        let mut request = self.request.clone();
+        request.commitment = Some(CommitmentLevelProto::Processed as i32);
+        request.slots.insert(
+            Self::SLOTS_FILTER.to_owned(),
+            SubscribeRequestFilterSlots {
+                filter_by_commitment: Some(false),
+                interslot_updates: Some(false),
+            },
+        );
+        Ok(request)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigRelayUpstream {
+    /// Upstream gRPC endpoint
+    pub endpoint: String,
+    /// x_token of the upstream
+    #[serde(default)]
+    pub x_token: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -348,3 +1213,307 @@ where
         None => Ok(None),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{Config, ConfigGrpcBandwidth, ConfigGrpcTokenPolicy, ConfigProfiling},
+        serde_json::json,
+        std::{
+            collections::HashSet,
+            env, fs,
+            path::{Path, PathBuf},
+            process,
+            time::Duration,
+        },
+        yellowstone_grpc_proto::{
+            plugin::{
+                filter::{limits::FilterLimits, name::FilterNames, Filter},
+                message::CommitmentLevel,
+            },
+            prelude::{
+                CommitmentLevel as CommitmentLevelProto, SubscribeRequest,
+                SubscribeRequestFilterAccounts, SubscribeRequestFilterBlocksMeta,
+                SubscribeRequestFilterEntry, SubscribeRequestFilterSlots,
+                SubscribeRequestFilterTransactions, SubscribeRequestPing,
+            },
+        },
+    };
+
+    #[test]
+    fn test_check_template_ping() {
+        let policy = ConfigGrpcTokenPolicy {
+            templates: Some(HashSet::from(["slots".to_owned()])),
+            ..Default::default()
+        };
+        let ping = Some(SubscribeRequestPing { id: 1 });
+
+        let request = SubscribeRequest {
+            ping,
+            ..Default::default()
+        };
+        assert!(policy.check_template(&request).is_ok());
+
+        let request = SubscribeRequest {
+            template: Some("slots".to_owned()),
+            ping,
+            ..Default::default()
+        };
+        assert!(policy.check_template(&request).is_ok());
+
+        // filters can not be sent together with ping
+        let request = SubscribeRequest {
+            slots: [("slots".to_owned(), SubscribeRequestFilterSlots::default())].into(),
+            ping,
+            ..Default::default()
+        };
+        assert!(policy.check_template(&request).is_err());
+
+        let request = SubscribeRequest {
+            from_slot: Some(42),
+            ping,
+            ..Default::default()
+        };
+        assert!(policy.check_template(&request).is_err());
+    }
+
+    fn create_filter(request: &SubscribeRequest) -> Filter {
+        let mut names = FilterNames::new(128, 1024, Duration::from_secs(1));
+        Filter::new(request, &FilterLimits::default(), &mut names).unwrap()
+    }
+
+    #[test]
+    fn test_deserialize_token_policy() {
+        let policy: ConfigGrpcTokenPolicy = serde_json::from_str(
+            r#"{"commitment_min": "confirmed", "filters": ["blocks_meta", "entry"]}"#,
+        )
+        .unwrap();
+        assert_eq!(policy.commitment_min, Some(CommitmentLevel::Confirmed));
+        assert_eq!(
+            policy.filters,
+            Some(HashSet::from([
+                "blocks_meta".to_owned(),
+                "entry".to_owned()
+            ]))
+        );
+
+        let policy: ConfigGrpcTokenPolicy = serde_json::from_str("{}").unwrap();
+        assert_eq!(policy.commitment_min, None);
+        assert_eq!(policy.filters, None);
+
+        for config in [
+            r#"{"commitment_min": "rooted"}"#,
+            r#"{"filters": ["blocks_meta", "unknown"]}"#,
+            // name of the metric, not of the request field
+            r#"{"filters": ["entries"]}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<ConfigGrpcTokenPolicy>(config).is_err(),
+                "{config}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let policy = ConfigGrpcTokenPolicy {
+            commitment_min: Some(CommitmentLevel::Finalized),
+            filters: Some(HashSet::from([
+                "blocks_meta".to_owned(),
+                "entry".to_owned(),
+            ])),
+            ..Default::default()
+        };
+
+        let request = SubscribeRequest {
+            blocks_meta: [(
+                "meta".to_owned(),
+                SubscribeRequestFilterBlocksMeta::default(),
+            )]
+            .into(),
+            entry: [("entry".to_owned(), SubscribeRequestFilterEntry::default())].into(),
+            commitment: Some(CommitmentLevelProto::Finalized as i32),
+            ..Default::default()
+        };
+        assert_eq!(policy.check(&create_filter(&request)), Ok(()));
+
+        // request commitment below the minimum
+        let mut request_confirmed = request.clone();
+        request_confirmed.commitment = Some(CommitmentLevelProto::Confirmed as i32);
+        assert!(policy.check(&create_filter(&request_confirmed)).is_err());
+
+        // not allowed update type
+        let mut request_transactions = request.clone();
+        request_transactions.transactions = [(
+            "transactions".to_owned(),
+            SubscribeRequestFilterTransactions::default(),
+        )]
+        .into();
+        assert_eq!(
+            policy.check(&create_filter(&request_transactions)),
+            Err("filter transactions is not allowed".to_owned())
+        );
+
+        // accounts filter with own commitment below the minimum
+        let policy = ConfigGrpcTokenPolicy {
+            commitment_min: Some(CommitmentLevel::Finalized),
+            ..Default::default()
+        };
+        let request = SubscribeRequest {
+            accounts: [(
+                "accounts".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    commitment: Some(CommitmentLevelProto::Processed as i32),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            commitment: Some(CommitmentLevelProto::Finalized as i32),
+            ..Default::default()
+        };
+        assert!(policy.check(&create_filter(&request)).is_err());
+
+        // everything is allowed without policy
+        assert_eq!(
+            ConfigGrpcTokenPolicy::default().check(&create_filter(&request)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_bandwidth_non_zero() {
+        let config: ConfigGrpcBandwidth =
+            serde_json::from_str(r#"{"bytes_per_sec": "1_000", "burst": 10}"#).unwrap();
+        assert_eq!(config.bytes_per_sec.get(), 1_000);
+        assert_eq!(config.burst.map(|burst| burst.get()), Some(10));
+
+        for config in [
+            r#"{"bytes_per_sec": 0}"#,
+            r#"{"bytes_per_sec": "0"}"#,
+            r#"{"bytes_per_sec": 1, "burst": 0}"#,
+        ] {
+            assert!(serde_json::from_str::<ConfigGrpcBandwidth>(config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_profiling_admin_token() {
+        let config: ConfigProfiling = serde_json::from_str(r#"{"admin_token": "secret"}"#).unwrap();
+        assert_eq!(config.admin_token, "secret");
+        assert!(serde_json::from_str::<ConfigProfiling>(r#"{"admin_token": ""}"#).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_download_scope() {
+        let mut value: serde_json::Value =
+            serde_json::from_str(include_str!("../config.json")).unwrap();
+        value["grpc"]["snapshot_download"] = json!({"keep_snapshots": 4});
+        assert!(Config::load_from_str(&value.to_string(), Path::new(".")).is_err());
+
+        value["grpc"]["snapshot_download"] =
+            json!({"owners": ["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"]});
+        let config = Config::load_from_str(&value.to_string(), Path::new(".")).unwrap();
+        assert_eq!(config.grpc.snapshot_download.unwrap().owners.len(), 1);
+    }
+
+    fn create_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "yellowstone-grpc-geyser-config-{name}-{}",
+            process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_interpolate_env() {
+        env::set_var("YELLOWSTONE_TEST_INTERPOLATE", "secret");
+        env::remove_var("YELLOWSTONE_TEST_INTERPOLATE_MISSING");
+
+        for (value, expected) in [
+            ("${YELLOWSTONE_TEST_INTERPOLATE}", "secret"),
+            ("a-${YELLOWSTONE_TEST_INTERPOLATE}-b", "a-secret-b"),
+            ("${YELLOWSTONE_TEST_INTERPOLATE:-default}", "secret"),
+            (
+                "${YELLOWSTONE_TEST_INTERPOLATE_MISSING:-default}",
+                "default",
+            ),
+            ("${YELLOWSTONE_TEST_INTERPOLATE_MISSING:-}", ""),
+            (
+                "$${YELLOWSTONE_TEST_INTERPOLATE}",
+                "${YELLOWSTONE_TEST_INTERPOLATE}",
+            ),
+            ("$1 and $", "$1 and $"),
+        ] {
+            assert_eq!(Config::interpolate_env(value).unwrap(), expected, "{value}");
+        }
+
+        for value in [
+            "${YELLOWSTONE_TEST_INTERPOLATE_MISSING}",
+            "${YELLOWSTONE_TEST_INTERPOLATE",
+        ] {
+            assert!(Config::interpolate_env(value).is_err(), "{value}");
+        }
+
+        // strings are replaced at any depth, keys are not
+        let value = Config::load_value(
+            r#"{"${YELLOWSTONE_TEST_INTERPOLATE}": ["${YELLOWSTONE_TEST_INTERPOLATE}", 1, null]}"#,
+            &env::temp_dir(),
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            value,
+            json!({"${YELLOWSTONE_TEST_INTERPOLATE}": ["secret", 1, null]})
+        );
+    }
+
+    #[test]
+    fn test_include_nested() {
+        let dir = create_dir("nested");
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(
+            dir.join("a.json"),
+            r#"{"$include": "nested/b.json", "a": 1, "shared": "a"}"#,
+        )
+        .unwrap();
+        // path is relative to the directory of the including file
+        fs::write(
+            dir.join("nested/b.json"),
+            r#"{"$include": "c.json", "b": 2, "shared": "b"}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("nested/c.json"), r#"{"c": 3, "shared": "c"}"#).unwrap();
+
+        let value = Config::load_value(
+            r#"{"grpc": {"$include": "a.json", "shared": "root"}}"#,
+            &dir,
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            value,
+            json!({"grpc": {"a": 1, "b": 2, "c": 3, "shared": "root"}})
+        );
+
+        // missing file and not an object
+        assert!(Config::load_value(r#"{"$include": "missing.json"}"#, &dir, 0).is_err());
+        fs::write(dir.join("array.json"), "[]").unwrap();
+        assert!(Config::load_value(r#"{"$include": "array.json"}"#, &dir, 0).is_err());
+        assert!(Config::load_value(r#"{"$include": 1}"#, &dir, 0).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let dir = create_dir("cycle");
+        fs::write(dir.join("a.json"), r#"{"$include": "b.json"}"#).unwrap();
+        fs::write(dir.join("b.json"), r#"{"$include": "a.json"}"#).unwrap();
+
+        let error = Config::load_value(r#"{"$include": "a.json"}"#, &dir, 0).unwrap_err();
+        assert!(error.to_string().contains("depth is limited"), "{error}");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
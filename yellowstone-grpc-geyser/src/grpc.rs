@@ -1,33 +1,52 @@
 use {
     crate::{
-        config::{ConfigGrpc, ConfigTokio},
-        metrics::{self, DebugClientMessage},
+        bandwidth::BandwidthLimiter,
+        config::{
+            ConfigGrpc, ConfigGrpcAccountCache, ConfigGrpcCreatedAt, ConfigGrpcEncode,
+            ConfigGrpcPriority, ConfigGrpcReplayPersist, ConfigGrpcReplica, ConfigGrpcShm,
+            ConfigGrpcSnapshotDownload, ConfigGrpcSpill, ConfigGrpcTokenPolicy,
+            ConfigGrpcZstdDictionary, ConfigTokio,
+        },
+        log_level::LOG_TARGET_BROADCAST,
+        metrics::{self, DebugClientMessage, SubscriptionLabelMetrics},
+        owners::OwnerTracker,
+        priority::{self, ClientSender},
+        replay,
+        spill::ClientSpill,
         version::GrpcVersionInfo,
     },
     anyhow::Context,
-    log::{error, info},
+    futures::{
+        future::{self, FutureExt},
+        stream::{BoxStream, StreamExt},
+    },
+    log::{debug, error, info, warn},
     prost_types::Timestamp,
     solana_sdk::{
         clock::{Slot, MAX_RECENT_BLOCKHASHES},
         pubkey::Pubkey,
     },
     std::{
-        collections::{BTreeMap, HashMap},
+        collections::{BTreeMap, HashMap, VecDeque},
+        iter,
+        mem::size_of,
         sync::{
-            atomic::{AtomicU64, AtomicUsize, Ordering},
+            atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
             Arc,
         },
+        thread,
         time::SystemTime,
     },
     tokio::{
         fs,
-        runtime::Builder,
+        runtime::{Builder, Handle},
         sync::{broadcast, mpsc, oneshot, Mutex, Notify, RwLock, Semaphore},
         task::spawn_blocking,
         time::{sleep, Duration, Instant},
     },
     tokio_stream::wrappers::ReceiverStream,
     tonic::{
+        metadata::AsciiMetadataValue,
         service::interceptor::interceptor,
         transport::{
             server::{Server, TcpIncoming},
@@ -37,26 +56,36 @@ use {
     },
     tonic_health::server::health_reporter,
     yellowstone_grpc_proto::{
+        created_at::to_prost,
         plugin::{
+            account_cache::{AccountCache, AccountCacheError, AccountCacheFilter},
             filter::{
-                limits::FilterLimits,
+                limits::{FilterLimits, FilterLimitsAccounts, FilterLimitsCheckError},
                 message::{FilteredUpdate, FilteredUpdateOneof},
                 name::FilterNames,
-                Filter,
+                Filter, FilterAccountsDataSlice, FilterError,
             },
             message::{
-                CommitmentLevel, Message, MessageBlock, MessageBlockMeta, MessageEntry,
-                MessageSlot, MessageTransactionInfo, SlotStatus,
+                CommitmentLevel, Message, MessageAccountInfo, MessageBlock, MessageBlockMeta,
+                MessageEntry, MessageSlot, MessageTransactionInfo, SlotStatus,
             },
             proto::geyser_server::{Geyser, GeyserServer},
+            snapshot::{AccountsStore, SnapshotError},
         },
         prelude::{
-            CommitmentLevel as CommitmentLevelProto, GetBlockHeightRequest, GetBlockHeightResponse,
-            GetLatestBlockhashRequest, GetLatestBlockhashResponse, GetSlotRequest, GetSlotResponse,
-            GetVersionRequest, GetVersionResponse, IsBlockhashValidRequest,
-            IsBlockhashValidResponse, PingRequest, PongResponse, SubscribeReplayInfoRequest,
-            SubscribeReplayInfoResponse, SubscribeRequest,
+            CommitmentLevel as CommitmentLevelProto, CompiledInstruction, ExplainFiltersRequest,
+            ExplainFiltersResponse, ExplainFiltersResult, GetAccountRequest, GetAccountResponse,
+            GetBlockHeightRequest, GetBlockHeightResponse, GetLatestBlockhashRequest,
+            GetLatestBlockhashResponse, GetSlotRequest, GetSlotResponse, GetSlotWatermarksRequest,
+            GetSlotWatermarksResponse, GetVersionCluster, GetVersionFeatures, GetVersionRequest,
+            GetVersionResponse, IsBlockhashValidRequest, IsBlockhashValidResponse, PingRequest,
+            PongResponse, Reward, SnapshotDownloadRequest, SnapshotDownloadResponse,
+            SubscribeReplayInfoRequest, SubscribeReplayInfoResponse, SubscribeRequest,
         },
+        prost::Message as _,
+        shm::ShmWriter,
+        status::StatusReason,
+        zstd_dict::{UpdateKind, ZstdDictionaries},
     },
 };
 
@@ -214,6 +243,17 @@ impl BlockMetaStorage {
         }
     }
 
+    async fn get_slot_watermarks(&self, first_available: Option<u64>) -> GetSlotWatermarksResponse {
+        let _permit = self.read_sem.acquire().await;
+        let storage = self.inner.read().await;
+        GetSlotWatermarksResponse {
+            processed: storage.processed,
+            confirmed: storage.confirmed,
+            finalized: storage.finalized,
+            first_available,
+        }
+    }
+
     async fn is_blockhash_valid(
         &self,
         blockhash: &str,
@@ -248,6 +288,154 @@ impl BlockMetaStorage {
     }
 }
 
+/// Apply messages to the storage, the write lock is released after every
+/// `UPDATE_LOCK_MAX` messages, so readers are not blocked by a large batch.
+fn spawn_storage_updates<T: Send + Sync + 'static>(
+    storage: Arc<RwLock<T>>,
+    capacity: usize,
+    update: fn(&mut T, &Message),
+) -> mpsc::Sender<Message> {
+    const UPDATE_BATCH_MAX: usize = 1_024;
+    const UPDATE_LOCK_MAX: usize = 64;
+
+    let (tx, mut rx) = mpsc::channel(capacity.max(1));
+    tokio::spawn(async move {
+        let mut messages = Vec::with_capacity(UPDATE_BATCH_MAX);
+        while rx.recv_many(&mut messages, UPDATE_BATCH_MAX).await > 0 {
+            for chunk in messages.chunks(UPDATE_LOCK_MAX) {
+                let mut storage = storage.write().await;
+                for message in chunk {
+                    update(&mut storage, message);
+                }
+            }
+            messages.clear();
+        }
+    });
+    tx
+}
+
+/// Messages selected by the filter are sent to the storage, the geyser loop
+/// never waits for it: once the channel is full the update is lost, so the
+/// storage is marked as inconsistent and is not fed anymore.
+#[derive(Debug)]
+struct StorageFeed {
+    name: &'static str,
+    tx: mpsc::Sender<Message>,
+    filter: AccountCacheFilter,
+    inconsistent: Arc<AtomicBool>,
+}
+
+impl StorageFeed {
+    /// Returns `false` once the storage is disabled
+    fn send(&mut self, message: &Message) -> bool {
+        if !self.filter.is_selected(message) || self.tx.try_send(message.clone()).is_ok() {
+            return true;
+        }
+        error!("{} storage is behind, disabled until restart", self.name);
+        self.inconsistent.store(true, Ordering::Relaxed);
+        metrics::storage_disabled_inc(self.name);
+        false
+    }
+}
+
+/// Storage which missed updates can't be read
+fn check_storage_consistent(inconsistent: &AtomicBool) -> TonicResult<()> {
+    if inconsistent.load(Ordering::Relaxed) {
+        Err(Status::unavailable(
+            "storage missed updates, disabled until restart",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct SnapshotDownloadStorage {
+    chunk_size: usize,
+    inner: Arc<RwLock<AccountsStore>>,
+    inconsistent: Arc<AtomicBool>,
+}
+
+impl SnapshotDownloadStorage {
+    /// Only configured accounts and accounts of configured owners are stored
+    fn new(config: ConfigGrpcSnapshotDownload) -> (Self, StorageFeed) {
+        let inner = Arc::new(RwLock::new(AccountsStore::new(config.keep_snapshots)));
+        let inconsistent = Arc::new(AtomicBool::new(false));
+        let feed = StorageFeed {
+            name: "snapshot_download",
+            tx: spawn_storage_updates(
+                Arc::clone(&inner),
+                config.channel_capacity,
+                AccountsStore::update,
+            ),
+            filter: AccountCacheFilter::new(config.accounts, config.owners),
+            inconsistent: Arc::clone(&inconsistent),
+        };
+
+        (
+            Self {
+                chunk_size: config.chunk_size,
+                inner,
+                inconsistent,
+            },
+            feed,
+        )
+    }
+
+    fn error_to_status(error: SnapshotError) -> Status {
+        match error {
+            SnapshotError::NotAvailable { .. } => Status::failed_precondition(error.to_string()),
+            SnapshotError::Empty => Status::unavailable(error.to_string()),
+            SnapshotError::CursorWithoutSlot | SnapshotError::InvalidCursor => {
+                Status::invalid_argument(error.to_string())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AccountCacheStorage {
+    inner: Arc<RwLock<AccountCache>>,
+    inconsistent: Arc<AtomicBool>,
+}
+
+impl AccountCacheStorage {
+    fn new(config: ConfigGrpcAccountCache) -> (Self, StorageFeed) {
+        let filter = AccountCacheFilter::new(config.accounts.clone(), config.owners.clone());
+        let inner = Arc::new(RwLock::new(AccountCache::new(
+            config.accounts,
+            config.owners,
+        )));
+        let inconsistent = Arc::new(AtomicBool::new(false));
+        let feed = StorageFeed {
+            name: "account_cache",
+            tx: spawn_storage_updates(
+                Arc::clone(&inner),
+                config.channel_capacity,
+                AccountCache::update,
+            ),
+            filter,
+            inconsistent: Arc::clone(&inconsistent),
+        };
+        (
+            Self {
+                inner,
+                inconsistent,
+            },
+            feed,
+        )
+    }
+
+    fn error_to_status(error: AccountCacheError) -> Status {
+        match error {
+            AccountCacheError::InvalidPubkey(_) | AccountCacheError::InvalidCommitment(_) => {
+                Status::invalid_argument(error.to_string())
+            }
+            AccountCacheError::NotFound(_) => Status::not_found(error.to_string()),
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 struct MessageId {
     id: u64,
@@ -275,9 +463,70 @@ struct SlotMessages {
     parent_slot: Option<Slot>,
     confirmed: bool,
     finalized: bool,
+    /// Approximate size of stored messages
+    memory: usize,
 }
 
 impl SlotMessages {
+    /// Approximate heap size of the message, parts shared with other messages
+    /// (accounts, transactions and entries of the block) are not counted.
+    /// Called for every message, so only lengths of the largest fields are used.
+    fn message_memory(message: &Message) -> usize {
+        size_of::<(u64, Message)>()
+            + match message {
+                Message::Slot(_) => 0,
+                Message::Account(msg) => size_of::<MessageAccountInfo>() + msg.account.data.len(),
+                Message::Transaction(msg) => {
+                    let tx = &msg.transaction;
+                    let (account_keys, instructions) =
+                        tx.transaction.message.as_ref().map_or((0, 0), |message| {
+                            (message.account_keys.len(), message.instructions.len())
+                        });
+                    size_of::<MessageTransactionInfo>()
+                        + tx.transaction.signatures.len() * 64
+                        + account_keys * size_of::<Pubkey>()
+                        + instructions * size_of::<CompiledInstruction>()
+                        + tx.meta.log_messages.len() * size_of::<String>()
+                        + tx.account_keys.len() * size_of::<Pubkey>()
+                }
+                Message::Entry(_) => size_of::<MessageEntry>(),
+                Message::BlockMeta(msg) => {
+                    size_of::<MessageBlockMeta>()
+                        + msg
+                            .block_meta
+                            .rewards
+                            .as_ref()
+                            .map_or(0, |rewards| rewards.rewards.len() * size_of::<Reward>())
+                }
+                Message::Block(msg) => {
+                    size_of::<MessageBlock>()
+                        + (msg.transactions.len() + msg.accounts.len() + msg.entries.len())
+                            * size_of::<usize>()
+                }
+            }
+    }
+
+    /// Last pushed message is the account write, only the write with the highest
+    /// `write_version` is kept (with signature of the transaction made it), late
+    /// writes with lower `write_version` are dropped too
+    fn dedup_account(&mut self, pubkey: Pubkey, write_version: u64) {
+        let msg_index = self.messages.len() - 1;
+        let Some(entry) = self.accounts_dedup.get_mut(&pubkey) else {
+            self.accounts_dedup
+                .insert(pubkey, (write_version, msg_index));
+            return;
+        };
+        // We can replace the message, but in this case we will lose the order
+        let index = if entry.0 < write_version {
+            std::mem::replace(entry, (write_version, msg_index)).1
+        } else {
+            msg_index
+        };
+        if let Some((_msgid, message)) = self.messages[index].take() {
+            self.memory -= Self::message_memory(&message);
+        }
+    }
+
     pub fn try_seal(&mut self, msgid_gen: &mut MessageId) -> Option<(u64, Message)> {
         if !self.sealed {
             if let Some(block_meta) = &self.block_meta {
@@ -309,6 +558,7 @@ impl SlotMessages {
                         entries,
                     )));
                     let message = (msgid_gen.next(), message_block);
+                    self.memory += Self::message_memory(&message.1);
                     self.messages.push(Some(message.clone()));
 
                     self.sealed = true;
@@ -331,19 +581,48 @@ enum ReplayedResponse {
 
 type ReplayStoredSlotsRequest = (CommitmentLevel, Slot, oneshot::Sender<ReplayedResponse>);
 
+/// Accepted filters of subscriptions, used by the relay to keep the upstream
+/// subscription in sync with its clients
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscriptionEvent {
+    /// Request with applied template, sent on every filter update
+    Update {
+        id: usize,
+        request: SubscribeRequest,
+    },
+    Removed {
+        id: usize,
+    },
+}
+
 #[derive(Debug)]
 pub struct GrpcService {
     config_snapshot_client_channel_capacity: usize,
     config_channel_capacity: usize,
     config_filter_limits: Arc<FilterLimits>,
     blocks_meta: Option<BlockMetaStorage>,
+    snapshot_download: Option<SnapshotDownloadStorage>,
+    account_cache: Option<AccountCacheStorage>,
     subscribe_id: AtomicUsize,
     snapshot_rx: Mutex<Option<crossbeam_channel::Receiver<Box<Message>>>>,
     broadcast_tx: broadcast::Sender<BroadcastedMessage>,
     replay_stored_slots_tx: Option<mpsc::Sender<ReplayStoredSlotsRequest>>,
     replay_first_available_slot: Option<Arc<AtomicU64>>,
     debug_clients_tx: Option<mpsc::UnboundedSender<DebugClientMessage>>,
+    subscriptions_tx: Option<mpsc::UnboundedSender<SubscriptionEvent>>,
     filter_names: Arc<Mutex<FilterNames>>,
+    subscription_templates: Arc<HashMap<String, SubscribeRequest>>,
+    features: GetVersionFeatures,
+    cluster: Option<GetVersionCluster>,
+    idle_timeout: Option<Duration>,
+    subscription_label_metrics_max: usize,
+    turbo: bool,
+    spill: Option<ConfigGrpcSpill>,
+    priority: Option<ConfigGrpcPriority>,
+    encode: Option<ConfigGrpcEncode>,
+    created_at: ConfigGrpcCreatedAt,
+    replica: Option<AsciiMetadataValue>,
+    replica_updates: Option<Arc<str>>,
 }
 
 impl GrpcService {
@@ -352,6 +631,7 @@ impl GrpcService {
         config_tokio: ConfigTokio,
         config: ConfigGrpc,
         debug_clients_tx: Option<mpsc::UnboundedSender<DebugClientMessage>>,
+        subscriptions_tx: Option<mpsc::UnboundedSender<SubscriptionEvent>>,
         is_reload: bool,
     ) -> anyhow::Result<(
         Option<crossbeam_channel::Sender<Box<Message>>>,
@@ -365,6 +645,14 @@ impl GrpcService {
             Some(Duration::from_secs(20)), // tcp_keepalive
         )
         .map_err(|error| anyhow::anyhow!(error))?;
+        // Turbo subscriptions are served on own address without send compression
+        let turbo_incoming = config
+            .turbo
+            .as_ref()
+            .and_then(|turbo| turbo.address)
+            .map(|address| TcpIncoming::new(address, true, Some(Duration::from_secs(20))))
+            .transpose()
+            .map_err(|error| anyhow::anyhow!(error))?;
 
         // Snapshot channel
         let (snapshot_tx, snapshot_rx) = match config.snapshot_plugin_channel_capacity {
@@ -384,6 +672,51 @@ impl GrpcService {
             (Some(blocks_meta), Some(blocks_meta_tx))
         };
 
+        // Finalized accounts state storage
+        let (snapshot_download, snapshot_download_feed) = match config.snapshot_download.clone() {
+            Some(config) => {
+                let (snapshot_download, snapshot_download_feed) =
+                    SnapshotDownloadStorage::new(config);
+                (Some(snapshot_download), Some(snapshot_download_feed))
+            }
+            None => (None, None),
+        };
+
+        // Recent accounts state storage, used only by unary `GetAccount`
+        let account_cache_config = config
+            .account_cache
+            .clone()
+            .filter(|_config| !config.unary_disabled);
+        let (account_cache, account_cache_feed) = match account_cache_config {
+            Some(config) => {
+                let (account_cache, account_cache_feed) = AccountCacheStorage::new(config);
+                (Some(account_cache), Some(account_cache_feed))
+            }
+            None => (None, None),
+        };
+
+        // Processed messages for consumers on the same host
+        let shm_writer =
+            match &config.shm {
+                Some(ConfigGrpcShm {
+                    path,
+                    capacity,
+                    workers,
+                    zstd_dictionary,
+                }) => Some((
+                    ShmWriter::create(path, *capacity)
+                        .with_context(|| format!("failed to create shm file {path:?}"))?,
+                    *workers,
+                    match zstd_dictionary {
+                        Some(path) => Some(ZstdDictionaries::load(path).with_context(|| {
+                            format!("failed to load zstd dictionaries {path:?}")
+                        })?),
+                        None => None,
+                    },
+                )),
+                None => None,
+            };
+
         // Messages to clients combined by commitment
         let (broadcast_tx, _) = broadcast::channel(config.channel_capacity);
         // attempt to prevent spam of geyser loop with capacity eq 1
@@ -430,55 +763,247 @@ impl GrpcService {
             config.filter_names_cleanup_interval,
         )));
 
+        // Subscription templates are checked on startup, not on subscribe
+        {
+            let mut filter_names = filter_names.lock().await;
+            for (name, template) in config.subscription_templates.iter() {
+                anyhow::ensure!(
+                    template.template.is_none(),
+                    "subscription template {name:?} can not refer to another template"
+                );
+                Filter::new(template, &FilterLimits::default(), &mut filter_names)
+                    .with_context(|| format!("invalid subscription template {name:?}"))?;
+            }
+            for (token, policy) in config.x_tokens.iter() {
+                for name in policy.templates.iter().flatten() {
+                    anyhow::ensure!(
+                        config.subscription_templates.contains_key(name),
+                        "unknown subscription template {name:?} in policy of token {token:?}"
+                    );
+                }
+            }
+        }
+
+        let turbo = config.turbo.is_some();
+
+        // Features reported in GetVersion
+        let limits = &config.filter_limits;
+        let features = GetVersionFeatures {
+            filters: [
+                ("accounts", limits.accounts.max),
+                ("slots", limits.slots.max),
+                ("transactions", limits.transactions.max),
+                ("transactions_status", limits.transactions_status.max),
+                ("blocks", limits.blocks.max),
+                ("blocks_meta", limits.blocks_meta.max),
+                ("entry", limits.entries.max),
+            ]
+            .into_iter()
+            .filter(|(_name, max)| *max > 0)
+            .map(|(name, _max)| name.to_owned())
+            .chain(
+                (limits.transactions.max > 0 && limits.transactions.log_contains_max > 0)
+                    .then(|| GetVersionFeatures::TRANSACTIONS_LOG_CONTAINS.to_owned()),
+            )
+            .chain(
+                (limits.transactions.max > 0)
+                    .then(|| GetVersionFeatures::TRANSACTIONS_FEE.to_owned()),
+            )
+            .chain(
+                (limits.transactions.max > 0 && limits.transactions.program_invoked_max > 0)
+                    .then(|| GetVersionFeatures::TRANSACTIONS_PROGRAM_INVOKED.to_owned()),
+            )
+            .chain(
+                (limits.accounts.max > 0)
+                    .then(|| GetVersionFeatures::ACCOUNTS_NONEMPTY_DATA.to_owned()),
+            )
+            .chain(
+                (limits.accounts.max > 0 && config.owner_change_tracking)
+                    .then(|| GetVersionFeatures::ACCOUNTS_OWNER_CHANGED.to_owned()),
+            )
+            .chain((limits.accounts.max > 0).then(|| GetVersionFeatures::ACCOUNTS_PDA.to_owned()))
+            .chain(
+                (limits.accounts.max > 0)
+                    .then(|| GetVersionFeatures::ACCOUNTS_TOKEN_EXTENSION.to_owned()),
+            )
+            .collect(),
+            replay_stored_slots: config.replay_stored_slots,
+            compression_accept: config
+                .compression
+                .accept
+                .iter()
+                .map(|encoding| encoding.to_string())
+                .collect(),
+            compression_send: config
+                .compression
+                .send
+                .iter()
+                .map(|encoding| encoding.to_string())
+                .collect(),
+            snapshot: snapshot_rx.is_some(),
+            unary: !config.unary_disabled,
+            slot_manifest: !limits.slot_manifest_disabled,
+            slot_batch: true,
+            snapshot_download: config
+                .snapshot_download
+                .as_ref()
+                .map(|config| config.keep_snapshots as u64)
+                .unwrap_or_default(),
+            subscription_templates: config.subscription_templates.keys().cloned().collect(),
+        };
+
+        // Validator and cluster reported in GetVersion
+        let cluster = config.cluster.map(|cluster| GetVersionCluster {
+            identity: cluster
+                .identity
+                .map(|pubkey| pubkey.to_string())
+                .unwrap_or_default(),
+            shred_version: cluster.shred_version.unwrap_or_default() as u32,
+            feature_set: cluster.feature_set.unwrap_or_default(),
+            cluster: cluster.cluster.unwrap_or_default(),
+        });
+
+        // Replica in response headers and updates
+        let replica = config
+            .replica
+            .as_ref()
+            .map(|ConfigGrpcReplica { id, .. }| {
+                AsciiMetadataValue::try_from(id.as_str())
+                    .with_context(|| format!("invalid replica id: {id:?}"))
+            })
+            .transpose()?;
+        let replica_updates = config
+            .replica
+            .as_ref()
+            .filter(|replica| replica.updates)
+            .map(|replica| Arc::from(replica.id.as_str()));
+
         // Create Server
         let max_decoding_message_size = config.max_decoding_message_size;
         let mut service = GeyserServer::new(Self {
             config_snapshot_client_channel_capacity: config.snapshot_client_channel_capacity,
             config_channel_capacity: config.channel_capacity,
-            config_filter_limits: Arc::new(config.filter_limits),
+            config_filter_limits: Arc::new(FilterLimits {
+                accounts: FilterLimitsAccounts {
+                    owner_changed: config.owner_change_tracking,
+                    ..config.filter_limits.accounts
+                },
+                ..config.filter_limits
+            }),
             blocks_meta,
+            snapshot_download,
+            account_cache,
             subscribe_id: AtomicUsize::new(0),
             snapshot_rx: Mutex::new(snapshot_rx),
             broadcast_tx: broadcast_tx.clone(),
             replay_stored_slots_tx,
             replay_first_available_slot: replay_first_available_slot.clone(),
             debug_clients_tx,
+            subscriptions_tx,
             filter_names,
+            subscription_templates: Arc::new(config.subscription_templates.clone()),
+            features,
+            cluster,
+            idle_timeout: config.idle_timeout,
+            subscription_label_metrics_max: config.subscription_label_metrics_max,
+            turbo,
+            spill: config.spill.clone(),
+            priority: config.priority.clone(),
+            encode: config.encode,
+            created_at: config.created_at,
+            replica,
+            replica_updates,
         })
         .max_decoding_message_size(max_decoding_message_size);
         for encoding in config.compression.accept {
             service = service.accept_compressed(encoding);
         }
+        // Compression adds latency, tonic applies it to the whole stream
+        let turbo_service = turbo_incoming.is_some().then(|| service.clone());
         for encoding in config.compression.send {
             service = service.send_compressed(encoding);
         }
 
         // Run geyser message loop
         let (messages_tx, messages_rx) = mpsc::unbounded_channel();
-        spawn_blocking(move || {
-            let mut builder = Builder::new_multi_thread();
-            if let Some(worker_threads) = config_tokio.worker_threads {
-                builder.worker_threads(worker_threads);
+        // Stored slots saved on unload are processed before the first live slot
+        let replay_persist = config
+            .replay_persist
+            .clone()
+            .filter(|_| config.replay_stored_slots > 0);
+        let replay_loaded = replay_persist
+            .as_ref()
+            .map(replay::load_persisted)
+            .unwrap_or_default();
+        if !replay_loaded.is_empty() {
+            info!("loaded {} stored messages", replay_loaded.len());
+        }
+        let run_geyser_loop = move |handle: Handle| {
+            handle.block_on(Self::geyser_loop(
+                messages_rx,
+                blocks_meta_tx,
+                snapshot_download_feed,
+                account_cache_feed,
+                shm_writer,
+                broadcast_tx,
+                replay_stored_slots_rx,
+                replay_first_available_slot,
+                config.replay_stored_slots,
+                replay_persist,
+                replay_loaded,
+                config
+                    .owner_change_tracking
+                    .then_some(config.owner_change_tracking_max_accounts),
+                config.zstd_dictionary.clone(),
+                turbo,
+            ))
+        };
+        match config.turbo {
+            // turbo: dedicated thread without work stealing, timers and
+            // spawned tasks are driven by the plugin runtime
+            Some(turbo) => {
+                if config_tokio.worker_threads.is_some() || config_tokio.affinity.is_some() {
+                    warn!("turbo mode: `tokio.worker_threads` and `tokio.affinity` are not applied to the geyser loop, use `grpc.turbo.affinity`");
+                }
+                let handle = Handle::current();
+                thread::Builder::new()
+                    .name("solGeyserTurbo".to_owned())
+                    .spawn(move || {
+                        if let Some(cpus) = turbo.affinity {
+                            affinity::set_thread_affinity(&cpus).expect("failed to set affinity");
+                        }
+                        // SAFETY: changes priority of the calling thread only
+                        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, turbo.nice) } != 0 {
+                            warn!(
+                                "turbo mode: failed to set nice {} of geyser loop thread: {}",
+                                turbo.nice,
+                                std::io::Error::last_os_error()
+                            );
+                        }
+                        run_geyser_loop(handle)
+                    })
+                    .context("failed to spawn geyser loop thread")?;
             }
-            if let Some(tokio_cpus) = config_tokio.affinity.clone() {
-                builder.on_thread_start(move || {
-                    affinity::set_thread_affinity(&tokio_cpus).expect("failed to set affinity")
+            None => {
+                spawn_blocking(move || {
+                    let mut builder = Builder::new_multi_thread();
+                    if let Some(worker_threads) = config_tokio.worker_threads {
+                        builder.worker_threads(worker_threads);
+                    }
+                    if let Some(cpus) = config_tokio.affinity.clone() {
+                        builder.on_thread_start(move || {
+                            affinity::set_thread_affinity(&cpus).expect("failed to set affinity")
+                        });
+                    }
+                    let runtime = builder
+                        .thread_name_fn(crate::get_thread_name)
+                        .enable_all()
+                        .build()
+                        .expect("Failed to create a new runtime for geyser loop");
+                    run_geyser_loop(runtime.handle().clone())
                 });
             }
-            builder
-                .thread_name_fn(crate::get_thread_name)
-                .enable_all()
-                .build()
-                .expect("Failed to create a new runtime for geyser loop")
-                .block_on(Self::geyser_loop(
-                    messages_rx,
-                    blocks_meta_tx,
-                    broadcast_tx,
-                    replay_stored_slots_rx,
-                    replay_first_available_slot,
-                    config.replay_stored_slots,
-                ));
-        });
+        }
 
         // Run Server
         let shutdown = Arc::new(Notify::new());
@@ -488,64 +1013,193 @@ impl GrpcService {
             let (mut health_reporter, health_service) = health_reporter();
             health_reporter.set_serving::<GeyserServer<Self>>().await;
 
-            server_builder
-                .layer(interceptor(move |request: Request<()>| {
-                    if let Some(x_token) = &config.x_token {
-                        match request.metadata().get("x-token") {
-                            Some(token) if x_token == token => Ok(request),
-                            _ => Err(Status::unauthenticated("No valid auth token")),
+            let x_tokens = config
+                .x_tokens
+                .iter()
+                .map(|(token, policy)| {
+                    // one limiter for all subscriptions of the token
+                    let bandwidth = policy
+                        .bandwidth
+                        .as_ref()
+                        .map(|config| Arc::new(BandwidthLimiter::new(config)));
+                    (token.clone(), (Arc::new(policy.clone()), bandwidth))
+                })
+                .collect::<HashMap<_, _>>();
+            let auth = move |mut request: Request<()>| {
+                if config.x_token.is_none() && x_tokens.is_empty() {
+                    return Ok(request);
+                }
+
+                let token = request
+                    .metadata()
+                    .get("x-token")
+                    .and_then(|token| token.to_str().ok());
+                if token.is_some() && token == config.x_token.as_deref() {
+                    return Ok(request);
+                }
+                match token.and_then(|token| x_tokens.get(token)) {
+                    Some((policy, bandwidth)) => {
+                        let policy = Arc::clone(policy);
+                        request.extensions_mut().insert(policy);
+                        if let Some(bandwidth) = bandwidth {
+                            request.extensions_mut().insert(Arc::clone(bandwidth));
                         }
-                    } else {
                         Ok(request)
                     }
-                }))
+                    None => Err(Status::unauthenticated("No valid auth token")),
+                }
+            };
+
+            // Turbo server shares service state, only send compression differs
+            let shutdown_turbo = Arc::new(Notify::new());
+            if let (Some(turbo_service), Some(turbo_incoming)) = (turbo_service, turbo_incoming) {
+                let router = server_builder
+                    .clone()
+                    .layer(interceptor(auth.clone()))
+                    .add_service(health_service.clone())
+                    .add_service(turbo_service);
+                let shutdown_turbo = Arc::clone(&shutdown_turbo);
+                tokio::spawn(async move {
+                    if let Err(error) = router
+                        .serve_with_incoming_shutdown(turbo_incoming, shutdown_turbo.notified())
+                        .await
+                    {
+                        error!("turbo server failed: {error}");
+                    }
+                });
+            }
+
+            let result = server_builder
+                .layer(interceptor(auth))
                 .add_service(health_service)
                 .add_service(service)
                 .serve_with_incoming_shutdown(incoming, shutdown_grpc.notified())
-                .await
+                .await;
+            shutdown_turbo.notify_one();
+            result
         });
 
         Ok((snapshot_tx, messages_tx, shutdown))
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn geyser_loop(
         mut messages_rx: mpsc::UnboundedReceiver<Message>,
         blocks_meta_tx: Option<mpsc::UnboundedSender<Message>>,
+        mut snapshot_download_feed: Option<StorageFeed>,
+        mut account_cache_feed: Option<StorageFeed>,
+        shm_writer: Option<(ShmWriter, usize, Option<ZstdDictionaries>)>,
         broadcast_tx: broadcast::Sender<BroadcastedMessage>,
         replay_stored_slots_rx: Option<mpsc::Receiver<ReplayStoredSlotsRequest>>,
         replay_first_available_slot: Option<Arc<AtomicU64>>,
         replay_stored_slots: u64,
+        replay_persist: Option<ConfigGrpcReplayPersist>,
+        replay_loaded: Vec<Message>,
+        owner_change_tracking: Option<usize>,
+        zstd_dictionary: Option<ConfigGrpcZstdDictionary>,
+        turbo: bool,
     ) {
         const PROCESSED_MESSAGES_MAX: usize = 31;
         const PROCESSED_MESSAGES_SLEEP: Duration = Duration::from_millis(10);
 
+        // messages are encoded by workers, never by the geyser loop
+        let mut shm_tx = shm_writer
+            .map(|(writer, workers, dicts)| Self::spawn_shm_workers(writer, workers.max(1), dicts));
         let mut msgid_gen = MessageId::default();
         let mut messages: BTreeMap<u64, SlotMessages> = Default::default();
         let mut processed_messages = Vec::with_capacity(PROCESSED_MESSAGES_MAX);
         let mut processed_first_slot = None;
+        let mut owners = owner_change_tracking.map(OwnerTracker::new);
+        let mut zstd_samples = zstd_dictionary.map(|config| (config, 0usize, Vec::new()));
         let processed_sleep = sleep(PROCESSED_MESSAGES_SLEEP);
         tokio::pin!(processed_sleep);
         let (_tx, rx) = mpsc::channel(1);
         let mut replay_stored_slots_rx = replay_stored_slots_rx.unwrap_or(rx);
+        let mut replay_loaded = Some(replay_loaded).filter(|messages| !messages.is_empty());
+        let mut pending = VecDeque::new();
 
         loop {
             tokio::select! {
-                Some(message) = messages_rx.recv() => {
+                message = async {
+                    match pending.pop_front() {
+                        Some(message) => Some(message),
+                        None => messages_rx.recv().await.map(|message| (message, false)),
+                    }
+                } => {
+                    // plugin is unloaded
+                    let Some((mut message, is_loaded)) = message else {
+                        break;
+                    };
                     metrics::message_queue_size_dec();
+
+                    // Loaded slots go before the first live slot, slots received
+                    // from the validator again are dropped from loaded slots
+                    let is_startup = matches!(&message, Message::Account(msg) if msg.is_startup);
+                    if let Some(loaded) = replay_loaded.take_if(|_| !is_startup) {
+                        let first_slot = message.get_slot();
+                        let loaded = loaded
+                            .into_iter()
+                            .filter(|message| message.get_slot() < first_slot)
+                            .map(|message| (message, true));
+                        for message in loaded.chain(iter::once((message, false))) {
+                            metrics::message_queue_size_inc();
+                            pending.push_back(message);
+                        }
+                        continue;
+                    }
                     let msgid = msgid_gen.next();
 
                     // Update metrics
                     if let Message::Slot(slot_message) = &message {
-                        metrics::update_slot_plugin_status(slot_message.status, slot_message.slot);
+                        if !is_loaded {
+                            metrics::update_slot_plugin_status(slot_message.status, slot_message.slot);
+                        }
+                    }
+
+                    // Count ticks, entries are received before block meta
+                    if let Message::BlockMeta(msg) = &mut message {
+                        if let Some(slot_messages) = messages.get(&msg.slot) {
+                            if slot_messages.entries.len() == msg.entries_count as usize {
+                                let ticks_count = slot_messages
+                                    .entries
+                                    .iter()
+                                    .filter(|entry| entry.executed_transaction_count == 0)
+                                    .count();
+                                Arc::make_mut(msg).ticks_count = Some(ticks_count as u64);
+                            }
+                        }
+                    }
+
+                    // Set previous owner, startup accounts only fill owners
+                    if let Some(owners) = owners.as_mut().filter(|_| !is_loaded) {
+                        match &mut message {
+                            Message::Account(msg) => {
+                                msg.previous_owner = owners.update_account(msg);
+                            }
+                            Message::Slot(msg) => owners.update_slot(msg),
+                            _ => {}
+                        }
                     }
 
                     // Update blocks info
-                    if let Some(blocks_meta_tx) = &blocks_meta_tx {
+                    if let Some(blocks_meta_tx) = blocks_meta_tx.as_ref().filter(|_| !is_loaded) {
                         if matches!(&message, Message::Slot(_) | Message::BlockMeta(_)) {
                             let _ = blocks_meta_tx.send(message.clone());
                         }
                     }
 
+                    // Update finalized and recent accounts state, storage is disabled once it's behind
+                    for feed in [&mut snapshot_download_feed, &mut account_cache_feed] {
+                        if !is_loaded && feed.as_mut().is_some_and(|feed| !feed.send(&message)) {
+                            *feed = None;
+                        }
+                    }
+
+                    // Startup accounts are sent to the loop only for the accounts state
+                    if matches!(&message, Message::Account(msg) if msg.is_startup) {
+                        continue;
+                    }
+
                     // Remove outdated block reconstruction info
                     match &message {
                         // On startup we can receive multiple Confirmed/Finalized slots without BlockMeta message
@@ -573,13 +1227,13 @@ impl GrpcService {
                                                         let msg_txn_count = slot_messages.transactions.len();
                                                         if block_txn_count != msg_txn_count {
                                                             reasons.push("InvalidTxnCount");
-                                                            error!("failed to reconstruct #{slot} -- tx count: {block_txn_count} vs {msg_txn_count}");
+                                                            error!(target: LOG_TARGET_BROADCAST, "failed to reconstruct #{slot} -- tx count: {block_txn_count} vs {msg_txn_count}");
                                                         }
                                                         let block_entries_count = block_meta.entries_count as usize;
                                                         let msg_entries_count = slot_messages.entries.len();
                                                         if block_entries_count != msg_entries_count {
                                                             reasons.push("InvalidEntriesCount");
-                                                            error!("failed to reconstruct #{slot} -- entries count: {block_entries_count} vs {msg_entries_count}");
+                                                            error!(target: LOG_TARGET_BROADCAST, "failed to reconstruct #{slot} -- entries count: {block_entries_count} vs {msg_entries_count}");
                                                         }
                                                     } else {
                                                         reasons.push("NoBlockMeta");
@@ -619,6 +1273,7 @@ impl GrpcService {
                             _ => {}
                         }
                     }
+                    slot_messages.memory += SlotMessages::message_memory(&message);
                     if matches!(&message, Message::Slot(_)) {
                         slot_messages.messages_slots.push((msgid, message.clone()));
                     } else {
@@ -652,17 +1307,7 @@ impl GrpcService {
                         }
                         // Dedup accounts by max write_version
                         Message::Account(msg) => {
-                            let write_version = msg.account.write_version;
-                            let msg_index = slot_messages.messages.len() - 1;
-                            if let Some(entry) = slot_messages.accounts_dedup.get_mut(&msg.account.pubkey) {
-                                if entry.0 < write_version {
-                                    // We can replace the message, but in this case we will lose the order
-                                    slot_messages.messages[entry.1] = None;
-                                    *entry = (write_version, msg_index);
-                                }
-                            } else {
-                                slot_messages.accounts_dedup.insert(msg.account.pubkey, (write_version, msg_index));
-                            }
+                            slot_messages.dedup_account(msg.account.pubkey, msg.account.write_version);
                         }
                         Message::Entry(msg) => {
                             slot_messages.entries.push(Arc::clone(msg));
@@ -707,7 +1352,7 @@ impl GrpcService {
                                     parent: entry.parent_slot,
                                     status,
                                     dead_error: None,
-                                    created_at: Timestamp::from(SystemTime::now())
+                                    created_at: Timestamp::from(SystemTime::now()),
                                 });
                                 messages_vec.push((msgid_gen.next(), message_slot));
                                 metrics::missed_status_message_inc(status);
@@ -715,6 +1360,49 @@ impl GrpcService {
                         }
                     }
 
+                    // Loaded messages only fill stored slots for replay, they
+                    // were sent to clients and storages before restart
+                    if is_loaded {
+                        continue;
+                    }
+
+                    // Full messages without filters for consumers on the same host
+                    if let Some(tx) = &shm_tx {
+                        // geyser loop never waits for workers, batch is dropped if they are behind
+                        let batch = messages_vec.iter().rev().map(|(_msgid, message)| message.clone()).collect();
+                        match tx.try_send(batch) {
+                            Ok(()) => {}
+                            Err(mpsc::error::TrySendError::Full(_)) => {
+                                metrics::shm_dropped_batches_inc();
+                            }
+                            Err(mpsc::error::TrySendError::Closed(_)) => {
+                                error!(target: LOG_TARGET_BROADCAST, "shm writer is stopped");
+                                shm_tx = None;
+                            }
+                        }
+                    }
+
+                    // Samples for zstd dictionaries, trained once in the blocking pool
+                    if let Some((config, seen, samples)) = &mut zstd_samples {
+                        for (_msgid, message) in messages_vec.iter().rev() {
+                            // blocks are large and repeat other updates
+                            if matches!(message, Message::Block(_)) {
+                                continue;
+                            }
+                            *seen += 1;
+                            if *seen % config.sample_interval.max(1) != 0 || samples.len() >= config.samples {
+                                continue;
+                            }
+                            if let Some(update) = Self::create_shm_update(message) {
+                                samples.push((Self::get_update_kind(message), update.encode_to_vec()));
+                            }
+                        }
+                        if samples.len() >= config.samples {
+                            let (config, _seen, samples) = zstd_samples.take().expect("defined");
+                            spawn_blocking(move || Self::train_zstd_dictionaries(config, samples));
+                        }
+                    }
+
                     for message in messages_vec.into_iter().rev() {
                         if let Message::Slot(slot) = &message.1 {
                             let (mut confirmed_messages, mut finalized_messages) = match slot.status {
@@ -785,8 +1473,10 @@ impl GrpcService {
                                 }
                             }
 
+                            let is_transaction = matches!(&message.1, Message::Transaction(_));
                             processed_messages.push(message);
                             if processed_messages.len() >= PROCESSED_MESSAGES_MAX
+                                || (turbo && is_transaction)
                                 || !confirmed_messages.is_empty()
                                 || !finalized_messages.is_empty()
                             {
@@ -809,6 +1499,11 @@ impl GrpcService {
                             }
                         }
                     }
+
+                    if let Some((slot, status)) = slot_status {
+                        Self::update_commitment_queue_size(&messages, processed_messages.len());
+                        debug!(target: LOG_TARGET_BROADCAST, "slot {slot} {status:?}, stored slots: {}, queued: {}", messages.len(), processed_messages.len());
+                    }
                 }
                 () = &mut processed_sleep => {
                     if !processed_messages.is_empty() {
@@ -842,29 +1537,329 @@ impl GrpcService {
                 else => break,
             }
         }
+
+        if let Some(config) = replay_persist {
+            match Self::save_stored_slots(&messages, &config) {
+                Ok(slots) => info!("saved {slots} stored slots to {:?}", config.path),
+                Err(error) => error!("failed to save stored slots: {error:?}"),
+            }
+        }
+    }
+
+    /// Save the latest slots which fit into `max_size`, messages are written
+    /// in order of receiving. Blocks are not saved, they are reconstructed
+    /// again on load.
+    fn save_stored_slots(
+        messages: &BTreeMap<u64, SlotMessages>,
+        config: &ConfigGrpcReplayPersist,
+    ) -> anyhow::Result<usize> {
+        let mut updates = vec![];
+        let mut size = 0;
+        let mut slots = 0;
+        for slot_messages in messages.values().rev() {
+            let slot_updates = slot_messages
+                .messages_slots
+                .iter()
+                .chain(slot_messages.messages.iter().flatten())
+                .filter_map(|(msgid, message)| {
+                    Self::create_shm_update(message).map(|update| (*msgid, update.encode_to_vec()))
+                })
+                .collect::<Vec<_>>();
+            let slot_size = slot_updates
+                .iter()
+                .map(|(_msgid, update)| update.len())
+                .sum::<usize>();
+            if size + slot_size > config.max_size {
+                break;
+            }
+            size += slot_size;
+            slots += 1;
+            updates.extend(slot_updates);
+        }
+        updates.sort_unstable_by_key(|(msgid, _update)| *msgid);
+        let updates = updates
+            .into_iter()
+            .map(|(_msgid, update)| update)
+            .collect::<Vec<_>>();
+        replay::save_capture(&config.path, &updates)?;
+        Ok(slots)
+    }
+
+    /// Messages after the latest slot with commitment, processed messages are
+    /// waiting for the batch. Memory of stored slots is updated too.
+    fn update_commitment_queue_size(messages: &BTreeMap<u64, SlotMessages>, processed: usize) {
+        metrics::update_commitment_queue_size(CommitmentLevel::Processed, processed);
+        for commitment in [CommitmentLevel::Confirmed, CommitmentLevel::Finalized] {
+            let size = messages
+                .values()
+                .rev()
+                .take_while(|slot_messages| match commitment {
+                    CommitmentLevel::Confirmed => !slot_messages.confirmed,
+                    _ => !slot_messages.finalized,
+                })
+                .map(|slot_messages| slot_messages.messages.iter().flatten().count())
+                .sum();
+            metrics::update_commitment_queue_size(commitment, size);
+        }
+        metrics::update_memory_estimate(
+            "stored_slots",
+            messages
+                .values()
+                .map(|slot_messages| slot_messages.memory)
+                .sum(),
+        );
+    }
+
+    /// Label from `x-subscription-id`, limited to safe characters and 64 bytes
+    /// because it's used in metrics labels and logs
+    fn parse_subscription_label(value: &str) -> Option<String> {
+        const MAX_LEN: usize = 64;
+        let label = value
+            .chars()
+            .filter(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.' | ':' | '/'))
+            .take(MAX_LEN)
+            .collect::<String>();
+        (!label.is_empty()).then_some(label)
+    }
+
+    /// Batches are encoded in the blocking pool, up to `workers` at a time, and
+    /// written in the order of receiving. With dictionaries every update is
+    /// written as zstd frame, compressors are reused between batches.
+    fn spawn_shm_workers(
+        mut writer: ShmWriter,
+        workers: usize,
+        dicts: Option<ZstdDictionaries>,
+    ) -> mpsc::Sender<Vec<Message>> {
+        const ZSTD_LEVEL: i32 = 3;
+
+        let (tx, rx) = mpsc::channel(workers * 4);
+        let dicts = dicts.map(Arc::new);
+        let compressors = Arc::new(std::sync::Mutex::new(Vec::new()));
+        tokio::spawn(async move {
+            let mut batches = ReceiverStream::new(rx)
+                .map(|batch: Vec<Message>| {
+                    let dicts = dicts.clone();
+                    let compressors = Arc::clone(&compressors);
+                    spawn_blocking(move || {
+                        let updates = batch.iter().filter_map(|message| {
+                            Self::create_shm_update(message).map(|update| {
+                                (Self::get_update_kind(message), update.encode_to_vec())
+                            })
+                        });
+                        let Some(dicts) = dicts else {
+                            return Ok(updates.map(|(_kind, update)| update).collect::<Vec<_>>());
+                        };
+                        let compressor = compressors.lock().expect("unpoisoned").pop();
+                        let mut compressor = match compressor {
+                            Some(compressor) => compressor,
+                            None => dicts.compressor(ZSTD_LEVEL)?,
+                        };
+                        let frames = updates
+                            .map(|(kind, update)| compressor.compress_encoded(kind, &update))
+                            .collect::<Result<Vec<_>, _>>();
+                        compressors.lock().expect("unpoisoned").push(compressor);
+                        frames
+                    })
+                })
+                .buffered(workers);
+            while let Some(result) = batches.next().await {
+                match result {
+                    Ok(Ok(updates)) => {
+                        for update in updates {
+                            if let Err(error) = writer.write(&update) {
+                                error!(target: LOG_TARGET_BROADCAST, "failed to write message to shm: {error}");
+                            }
+                        }
+                    }
+                    Ok(Err(error)) => {
+                        error!(target: LOG_TARGET_BROADCAST, "failed to compress messages for shm: {error}")
+                    }
+                    Err(error) => {
+                        error!(target: LOG_TARGET_BROADCAST, "failed to encode messages for shm: {error}")
+                    }
+                }
+            }
+        });
+        tx
+    }
+
+    /// Add `x-replica` header to the response if the replica is configured
+    fn with_replica<T>(&self, mut response: Response<T>) -> Response<T> {
+        if let Some(replica) = &self.replica {
+            response.metadata_mut().insert("x-replica", replica.clone());
+        }
+        response
+    }
+
+    fn create_shm_update(message: &Message) -> Option<FilteredUpdate> {
+        let message = match message {
+            Message::Slot(msg) => FilteredUpdateOneof::slot(msg.clone()),
+            Message::Account(msg) => {
+                FilteredUpdateOneof::account(msg, FilterAccountsDataSlice::default())
+            }
+            Message::Transaction(msg) => FilteredUpdateOneof::transaction(msg),
+            Message::Entry(msg) => FilteredUpdateOneof::entry(Arc::clone(msg)),
+            Message::BlockMeta(msg) => FilteredUpdateOneof::block_meta(Arc::clone(msg)),
+            // can be reconstructed from other messages
+            Message::Block(_) => return None,
+        };
+        Some(FilteredUpdate::new_empty(message))
+    }
+
+    const fn get_update_kind(message: &Message) -> UpdateKind {
+        match message {
+            Message::Slot(_) => UpdateKind::Slot,
+            Message::Account(_) => UpdateKind::Account,
+            Message::Transaction(_) => UpdateKind::Transaction,
+            Message::Entry(_) => UpdateKind::Entry,
+            Message::BlockMeta(_) => UpdateKind::BlockMeta,
+            Message::Block(_) => UpdateKind::Block,
+        }
+    }
+
+    fn train_zstd_dictionaries(
+        config: ConfigGrpcZstdDictionary,
+        samples: Vec<(UpdateKind, Vec<u8>)>,
+    ) {
+        let dicts = ZstdDictionaries::train(
+            samples
+                .iter()
+                .map(|(kind, sample)| (*kind, sample.as_slice())),
+            config.size,
+        );
+        match dicts.save(&config.path) {
+            Ok(()) => info!(
+                "zstd dictionaries trained on {} samples saved to {:?}",
+                samples.len(),
+                config.path
+            ),
+            Err(error) => error!(
+                "failed to save zstd dictionaries to {:?}: {error}",
+                config.path
+            ),
+        }
+    }
+
+    /// Replace filters of the request with the subscription template, pings and
+    /// `from_slot` of the request are kept
+    fn apply_template(
+        templates: &HashMap<String, SubscribeRequest>,
+        request: SubscribeRequest,
+    ) -> Result<SubscribeRequest, Status> {
+        let Some(name) = &request.template else {
+            return Ok(request);
+        };
+        let Some(template) = templates.get(name) else {
+            return Err(Status::invalid_argument(format!(
+                "unknown subscription template: {name}"
+            )));
+        };
+
+        let filters = SubscribeRequest {
+            ping: None,
+            from_slot: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
+            ..request.clone()
+        };
+        if filters != SubscribeRequest::default() {
+            return Err(Status::invalid_argument(
+                "subscription template can not be combined with filters",
+            ));
+        }
+
+        Ok(SubscribeRequest {
+            ping: request.ping,
+            from_slot: request.from_slot,
+            template: request.template,
+            slot_batch: request.slot_batch.or(template.slot_batch),
+            request_id: request.request_id,
+            ..template.clone()
+        })
+    }
+
+    /// Addresses of `pda` are derived in the blocking pool, filter names are
+    /// locked only while the filter is created
+    async fn create_filter(
+        request: SubscribeRequest,
+        limits: Arc<FilterLimits>,
+        filter_names: Arc<Mutex<FilterNames>>,
+    ) -> TonicResult<(SubscribeRequest, Filter)> {
+        let has_pda = request
+            .accounts
+            .values()
+            .any(|filter| !filter.pda.is_empty());
+        let create = move |filter_names: &mut FilterNames| {
+            filter_names.try_clean();
+            Filter::new(&request, &limits, filter_names)
+                .map(|filter| (request, filter))
+                .map_err(Self::filter_error_to_status)
+        };
+
+        if !has_pda {
+            let mut filter_names = filter_names.lock().await;
+            return create(&mut filter_names);
+        }
+        spawn_blocking(move || create(&mut filter_names.blocking_lock()))
+            .await
+            .map_err(|error| Status::internal(format!("failed to create filter: {error}")))?
+    }
+
+    fn filter_error_to_status(error: FilterError) -> Status {
+        let message = format!("failed to create filter: {error}");
+        match error {
+            FilterError::LimitsCheck(FilterLimitsCheckError::PubkeyReject { .. }) => {
+                StatusReason::UnauthorizedFilter.status(message)
+            }
+            FilterError::LimitsCheck(
+                FilterLimitsCheckError::Max { max } | FilterLimitsCheckError::MaxPubkey { max },
+            )
+            | FilterError::CreateAccountStateMaxFilters { max } => {
+                let metadata = HashMap::from([("limit".to_owned(), max.to_string())]);
+                StatusReason::LimitExceeded.status_with_metadata(message, metadata)
+            }
+            FilterError::LimitsCheck(FilterLimitsCheckError::Any)
+            | FilterError::CreateBlocksNotAllowed(_) => StatusReason::LimitExceeded.status(message),
+            FilterError::OwnerChangedNotSupported => Status::unimplemented(message),
+            _ => Status::invalid_argument(message),
+        }
     }
 
     #[allow(clippy::too_many_arguments)]
     async fn client_loop(
         id: usize,
         endpoint: String,
-        stream_tx: mpsc::Sender<TonicResult<FilteredUpdate>>,
+        label: Option<String>,
+        label_metrics_max: usize,
+        stream_tx: ClientSender,
         mut client_rx: mpsc::UnboundedReceiver<Option<(Option<u64>, Filter)>>,
         mut snapshot_rx: Option<crossbeam_channel::Receiver<Box<Message>>>,
         mut messages_rx: broadcast::Receiver<BroadcastedMessage>,
         replay_stored_slots_tx: Option<mpsc::Sender<ReplayStoredSlotsRequest>>,
         debug_client_tx: Option<mpsc::UnboundedSender<DebugClientMessage>>,
+        idle_timeout: Option<Duration>,
+        is_active: Arc<AtomicBool>,
+        turbo: bool,
+        mut spill: Option<ClientSpill>,
         drop_client: impl FnOnce(),
     ) {
         let mut filter = Filter::default();
         metrics::update_subscriptions(&endpoint, None, Some(&filter));
 
         metrics::connections_total_inc();
+        let label_metrics = label
+            .as_deref()
+            .map(|label| SubscriptionLabelMetrics::new(label, label_metrics_max));
         DebugClientMessage::maybe_send(&debug_client_tx, || DebugClientMessage::UpdateFilter {
             id,
+            label: label.clone(),
             filter: Box::new(filter.clone()),
         });
-        info!("client #{id}: new");
+        match &label {
+            Some(label) => info!("client #{id}: new, subscription id: {label}"),
+            None => info!("client #{id}: new"),
+        }
 
         let mut is_alive = true;
         if let Some(snapshot_rx) = snapshot_rx.take() {
@@ -880,9 +1875,54 @@ impl GrpcService {
             .await;
         }
 
+        let mut slot_manifests = filter.get_slot_manifests();
+        let mut slot_batches = filter.get_slot_batches();
+        // own part of `client_queues` memory estimate
+        let mut queues_memory = 0;
+        // activity is checked once per timeout, subscription is closed after 1-2 timeouts without it
+        let idle_sleep = sleep(idle_timeout.unwrap_or_default());
+        tokio::pin!(idle_sleep);
         if is_alive {
             'outer: loop {
                 tokio::select! {
+                    () = &mut idle_sleep, if idle_timeout.is_some() => {
+                        let timeout = idle_timeout.unwrap_or_default();
+                        if is_active.swap(false, Ordering::Relaxed) {
+                            idle_sleep.as_mut().reset(Instant::now() + timeout);
+                            continue;
+                        }
+
+                        info!("client #{id}: idle for {timeout:?}, closing");
+                        metrics::subscriptions_idle_closed_inc();
+                        let message = format!("subscription is idle: no pings and no updates for {timeout:?}");
+                        tokio::spawn(async move {
+                            let _ = stream_tx.send(Err(Status::deadline_exceeded(message))).await;
+                        });
+                        break 'outer;
+                    }
+                    result = stream_tx.ready(), if spill.as_ref().is_some_and(|spill| !spill.is_empty()) => {
+                        let (Ok(()), Some(spill)) = (result, spill.as_mut()) else {
+                            error!("client #{id}: stream closed");
+                            break 'outer;
+                        };
+                        match spill.drain(&stream_tx).await {
+                            Ok(count) => {
+                                if idle_timeout.is_some() {
+                                    is_active.store(true, Ordering::Relaxed);
+                                }
+                                if let Some(label_metrics) = &label_metrics {
+                                    label_metrics.messages_sent_inc_by(count as u64);
+                                }
+                            }
+                            Err(error) => {
+                                error!("client #{id}: failed to read spilled updates: {error}");
+                                tokio::spawn(async move {
+                                    let _ = stream_tx.send(Err(StatusReason::LaggingClientDropped.status("failed to read spilled updates"))).await;
+                                });
+                                break 'outer;
+                            }
+                        }
+                    }
                     mut message = client_rx.recv() => {
                         // forward to latest filter
                         loop {
@@ -902,9 +1942,34 @@ impl GrpcService {
                             Some(Some((from_slot, filter_new))) => {
                                 metrics::update_subscriptions(&endpoint, Some(&filter), Some(&filter_new));
                                 filter = filter_new;
-                                DebugClientMessage::maybe_send(&debug_client_tx, || DebugClientMessage::UpdateFilter { id, filter: Box::new(filter.clone()) });
+                                slot_manifests.update_filter(filter.get_slot_manifests());
+                                let pending = slot_batches.update_filter(filter.get_slot_batches());
+                                DebugClientMessage::maybe_send(&debug_client_tx, || DebugClientMessage::UpdateFilter { id, label: label.clone(), filter: Box::new(filter.clone()) });
                                 info!("client #{id}: filter updated");
 
+                                // replayed and following messages are matched by the new filter,
+                                // updates waiting for batches are matched by the previous one,
+                                // acknowledgement is spilled to stay behind spilled updates
+                                for msg in pending.into_iter().chain(filter.get_filter_applied_msg()) {
+                                    match spill.as_mut() {
+                                        Some(spill) if !spill.is_empty() => {
+                                            if let Err(error) = spill.push(&msg).await {
+                                                error!("client #{id}: failed to spill an update: {error}");
+                                                tokio::spawn(async move {
+                                                    let _ = stream_tx.send(Err(StatusReason::LaggingClientDropped.status("lagged to send an update"))).await;
+                                                });
+                                                break 'outer;
+                                            }
+                                        }
+                                        _ => {
+                                            if stream_tx.send(Ok(msg)).await.is_err() {
+                                                error!("client #{id}: stream closed");
+                                                break 'outer;
+                                            }
+                                        }
+                                    }
+                                }
+
                                 if let Some(from_slot) = from_slot {
                                     let Some(replay_stored_slots_tx) = &replay_stored_slots_tx else {
                                         info!("client #{id}: from_slot is not supported");
@@ -930,9 +1995,11 @@ impl GrpcService {
                                             info!("client #{id}: broadcast from {from_slot} is not available");
                                             tokio::spawn(async move {
                                                 let message = format!(
-                                                    "broadcast from {from_slot} is not available, last available: {slot}"
+                                                    "broadcast from {from_slot} is not available, first available: {slot}"
                                                 );
-                                                let _ = stream_tx.send(Err(Status::internal(message))).await;
+                                                let metadata = HashMap::from([("first_available".to_owned(), slot.to_string())]);
+                                                let status = StatusReason::SlotNotAvailable.status_with_metadata(message, metadata);
+                                                let _ = stream_tx.send(Err(status)).await;
                                             });
                                             break 'outer;
                                         },
@@ -947,7 +2014,9 @@ impl GrpcService {
 
                                     messages.sort_by_key(|msg| msg.0);
                                     for (_msgid, message) in messages.iter() {
-                                        for message in filter.get_updates(message, Some(commitment)) {
+                                        let updates = filter.get_updates(message, Some(commitment));
+                                        let updates = slot_batches.process(message, updates);
+                                        for message in slot_manifests.process(message, updates) {
                                             match stream_tx.send(Ok(message)).await {
                                                 Ok(()) => {}
                                                 Err(mpsc::error::SendError(_)) => {
@@ -971,30 +2040,77 @@ impl GrpcService {
                         let (commitment, messages) = match message {
                             Ok((commitment, messages)) => (commitment, messages),
                             Err(broadcast::error::RecvError::Closed) => {
+                                tokio::spawn(async move {
+                                    let _ = stream_tx.send(Err(StatusReason::ShuttingDown.status("server is shutting down"))).await;
+                                });
                                 break 'outer;
                             },
                             Err(broadcast::error::RecvError::Lagged(_)) => {
                                 info!("client #{id}: lagged to receive geyser messages");
                                 tokio::spawn(async move {
-                                    let _ = stream_tx.send(Err(Status::internal("lagged to receive geyser messages"))).await;
+                                    let _ = stream_tx.send(Err(StatusReason::LaggingClientDropped.status("lagged to receive geyser messages"))).await;
                                 });
                                 break 'outer;
                             }
                         };
 
-                        if commitment == filter.get_commitment_level() {
+                        if filter.is_commitment_used(commitment) {
+                            // other commitments deliver only accounts of filters with own commitment
+                            let is_request_commitment = commitment == filter.get_commitment_level();
                             for (_msgid, message) in messages.iter() {
-                                for message in filter.get_updates(message, Some(commitment)) {
-                                    match stream_tx.try_send(Ok(message)) {
-                                        Ok(()) => {}
-                                        Err(mpsc::error::TrySendError::Full(_)) => {
+                                if !is_request_commitment && !matches!(message, Message::Account(_)) {
+                                    continue;
+                                }
+                                // turbo: latency of processed slots and transactions
+                                let turbo_kind = match message {
+                                    Message::Slot(_) if turbo && commitment == CommitmentLevel::Processed => Some("slot"),
+                                    Message::Transaction(_) if turbo && commitment == CommitmentLevel::Processed => Some("transaction"),
+                                    _ => None,
+                                };
+                                let updates = filter.get_updates(message, Some(commitment));
+                                let updates = if is_request_commitment {
+                                    let updates = slot_batches.process(message, updates);
+                                    slot_manifests.process(message, updates)
+                                } else {
+                                    updates
+                                };
+                                for message in updates {
+                                    let created_at = message.created_at;
+                                    // keep order, nothing is sent directly until spilled updates are delivered
+                                    let result = match &spill {
+                                        Some(spill) if !spill.is_empty() => Err(mpsc::error::TrySendError::Full(Ok(message))),
+                                        _ => stream_tx.try_send(Ok(message)),
+                                    };
+                                    match (result, spill.as_mut()) {
+                                        (Ok(()), _) => {
+                                            if idle_timeout.is_some() {
+                                                is_active.store(true, Ordering::Relaxed);
+                                            }
+                                            if let Some(kind) = turbo_kind {
+                                                metrics::turbo_send_latency_observe(kind, &created_at);
+                                            }
+                                            if let Some(label_metrics) = &label_metrics {
+                                                label_metrics.messages_sent_inc_by(1);
+                                            }
+                                        }
+                                        (Err(mpsc::error::TrySendError::Full(Ok(message))), Some(spill)) => {
+                                            if let Err(error) = spill.push(&message).await {
+                                                error!("client #{id}: failed to spill an update: {error}");
+                                                tokio::spawn(async move {
+                                                    let _ = stream_tx.send(Err(StatusReason::LaggingClientDropped.status("lagged to send an update"))).await;
+                                                });
+                                                break 'outer;
+                                            }
+                                            metrics::client_spilled_updates_inc();
+                                        }
+                                        (Err(mpsc::error::TrySendError::Full(_)), _) => {
                                             error!("client #{id}: lagged to send an update");
                                             tokio::spawn(async move {
-                                                let _ = stream_tx.send(Err(Status::internal("lagged to send an update"))).await;
+                                                let _ = stream_tx.send(Err(StatusReason::LaggingClientDropped.status("lagged to send an update"))).await;
                                             });
                                             break 'outer;
                                         }
-                                        Err(mpsc::error::TrySendError::Closed(_)) => {
+                                        (Err(mpsc::error::TrySendError::Closed(_)), _) => {
                                             error!("client #{id}: stream closed");
                                             break 'outer;
                                         }
@@ -1003,6 +2119,11 @@ impl GrpcService {
                             }
                         }
 
+                        // queued updates share messages with stored slots, only own size is counted
+                        let memory = stream_tx.queued_len() * size_of::<TonicResult<FilteredUpdate>>();
+                        metrics::client_queues_memory_add(memory as i64 - queues_memory as i64);
+                        queues_memory = memory;
+
                         if commitment == CommitmentLevel::Processed && debug_client_tx.is_some() {
                             for message in messages.iter() {
                                 if let Message::Slot(slot_message) = &message.1 {
@@ -1015,17 +2136,22 @@ impl GrpcService {
             }
         }
 
+        metrics::client_queues_memory_add(-(queues_memory as i64));
         metrics::connections_total_dec();
+        drop(label_metrics);
         DebugClientMessage::maybe_send(&debug_client_tx, || DebugClientMessage::Removed { id });
         metrics::update_subscriptions(&endpoint, Some(&filter), None);
-        info!("client #{id}: removed");
+        match &label {
+            Some(label) => info!("client #{id}: removed, subscription id: {label}"),
+            None => info!("client #{id}: removed"),
+        }
         drop_client();
     }
 
     async fn client_loop_snapshot(
         id: usize,
         endpoint: &str,
-        stream_tx: &mpsc::Sender<TonicResult<FilteredUpdate>>,
+        stream_tx: &ClientSender,
         client_rx: &mut mpsc::UnboundedReceiver<Option<(Option<u64>, Filter)>>,
         snapshot_rx: crossbeam_channel::Receiver<Box<Message>>,
         is_alive: &mut bool,
@@ -1048,6 +2174,12 @@ impl GrpcService {
                     metrics::update_subscriptions(endpoint, Some(filter), Some(&filter_new));
                     *filter = filter_new;
                     info!("client #{id}: filter updated");
+                    if let Some(msg) = filter.get_filter_applied_msg() {
+                        if stream_tx.send(Ok(msg)).await.is_err() {
+                            error!("client #{id}: stream closed");
+                            *is_alive = false;
+                        }
+                    }
                     break;
                 }
                 Some(None) => {
@@ -1088,7 +2220,7 @@ impl GrpcService {
 
 #[tonic::async_trait]
 impl Geyser for GrpcService {
-    type SubscribeStream = ReceiverStream<TonicResult<FilteredUpdate>>;
+    type SubscribeStream = BoxStream<'static, TonicResult<FilteredUpdate>>;
 
     async fn subscribe(
         &self,
@@ -1102,11 +2234,12 @@ impl Geyser for GrpcService {
         } else {
             None
         };
-        let (stream_tx, stream_rx) = mpsc::channel(if snapshot_rx.is_some() {
+        let capacity = if snapshot_rx.is_some() {
             self.config_snapshot_client_channel_capacity
         } else {
             self.config_channel_capacity
-        });
+        };
+        let (stream_tx, updates) = priority::channel(self.priority.as_ref(), capacity);
         let (client_tx, client_rx) = mpsc::unbounded_channel();
         let notify_exit1 = Arc::new(Notify::new());
         let notify_exit2 = Arc::new(Notify::new());
@@ -1143,15 +2276,40 @@ impl Geyser for GrpcService {
             .get("x-endpoint")
             .and_then(|h| h.to_str().ok().map(|s| s.to_string()))
             .unwrap_or_else(|| "".to_owned());
+        let label = request
+            .metadata()
+            .get("x-subscription-id")
+            .and_then(|value| value.to_str().ok())
+            .and_then(Self::parse_subscription_label);
+
+        let policy = request
+            .extensions()
+            .get::<Arc<ConfigGrpcTokenPolicy>>()
+            .cloned();
+        let bandwidth = request.extensions().get::<Arc<BandwidthLimiter>>().cloned();
 
         let config_filter_limits = Arc::clone(&self.config_filter_limits);
         let filter_names = Arc::clone(&self.filter_names);
+        let subscription_templates = Arc::clone(&self.subscription_templates);
         let incoming_stream_tx = stream_tx.clone();
         let incoming_client_tx = client_tx;
+        let incoming_subscriptions_tx = self.subscriptions_tx.clone();
         let incoming_exit = Arc::clone(&notify_exit2);
+        let is_active = Arc::new(AtomicBool::new(false));
+        let incoming_is_active = Arc::clone(&is_active);
         tokio::spawn(async move {
             let exit = incoming_exit.notified();
             tokio::pin!(exit);
+            // blocklist is applied to templates too
+            let template_filter_limits = Arc::new(FilterLimits {
+                accounts: FilterLimitsAccounts {
+                    owner_changed: config_filter_limits.accounts.owner_changed,
+                    ..Default::default()
+                },
+                blocklist: Arc::clone(&config_filter_limits.blocklist),
+                slot_manifest_disabled: config_filter_limits.slot_manifest_disabled,
+                ..Default::default()
+            });
 
             loop {
                 tokio::select! {
@@ -1160,31 +2318,62 @@ impl Geyser for GrpcService {
                     }
                     message = request.get_mut().message() => match message {
                         Ok(Some(request)) => {
-                            let mut filter_names = filter_names.lock().await;
-                            filter_names.try_clean();
+                            // pings and filter updates
+                            incoming_is_active.store(true, Ordering::Relaxed);
+
+                            let request = policy
+                                .as_ref()
+                                .map_or(Ok(()), |policy| policy.check_template(&request))
+                                .map_err(|error| StatusReason::UnauthorizedFilter.status(format!(
+                                    "subscription is not allowed for the token: {error}"
+                                )))
+                                .and_then(|()| Self::apply_template(&subscription_templates, request));
+                            let request = match request {
+                                Ok(request) => request,
+                                Err(status) => {
+                                    if incoming_stream_tx.send(Err(status)).await.is_err() {
+                                        let _ = incoming_client_tx.send(None);
+                                    }
+                                    continue;
+                                }
+                            };
+                            // templates are defined by the operator and not restricted by filter limits
+                            let limits = if request.template.is_some() {
+                                Arc::clone(&template_filter_limits)
+                            } else {
+                                Arc::clone(&config_filter_limits)
+                            };
 
-                            if let Err(error) = match Filter::new(&request, &config_filter_limits, &mut filter_names) {
-                                Ok(filter) => {
-                                    if let Some(msg) = filter.get_pong_msg() {
+                            if let Err(status) = match Self::create_filter(request, limits, Arc::clone(&filter_names)).await {
+                                Ok((request, filter)) => {
+                                    if let Some(Err(error)) = policy.as_ref().map(|policy| policy.check(&filter)) {
+                                        Err(StatusReason::UnauthorizedFilter.status(format!(
+                                            "subscription is not allowed for the token: {error}"
+                                        )))
+                                    } else if let Some(msg) = filter.get_pong_msg() {
                                         if incoming_stream_tx.send(Ok(msg)).await.is_err() {
                                             error!("client #{id}: stream closed");
                                             let _ = incoming_client_tx.send(None);
                                             break;
                                         }
                                         continue;
-                                    }
-
-                                    match incoming_client_tx.send(Some((request.from_slot, filter))) {
-                                        Ok(()) => Ok(()),
-                                        Err(error) => Err(error.to_string()),
+                                    } else {
+                                        match incoming_client_tx.send(Some((request.from_slot, filter))) {
+                                            Ok(()) => {
+                                                if let Some(tx) = &incoming_subscriptions_tx {
+                                                    let _ = tx.send(SubscriptionEvent::Update { id, request });
+                                                }
+                                                Ok(())
+                                            }
+                                            Err(error) => Err(Status::invalid_argument(format!(
+                                                "failed to create filter: {error}"
+                                            ))),
+                                        }
                                     }
                                 },
-                                Err(error) => Err(error.to_string()),
+                                Err(status) => Err(status),
                             } {
-                                let err = Err(Status::invalid_argument(format!(
-                                    "failed to create filter: {error}"
-                                )));
-                                if incoming_stream_tx.send(err).await.is_err() {
+                                if incoming_stream_tx.send(Err(status)).await.is_err() {
                                     let _ = incoming_client_tx.send(None);
                                 }
                             }
@@ -1201,22 +2390,92 @@ impl Geyser for GrpcService {
             }
         });
 
+        let subscriptions_tx = self.subscriptions_tx.clone();
+        // updates which do not fit into the channel are written to disk
+        let spill = match &self.spill {
+            Some(config) => ClientSpill::create(&config.path, id, config.max_bytes)
+                .await
+                .map_err(|error| error!("client #{id}: failed to create spill file: {error}"))
+                .ok(),
+            None => None,
+        };
+
         tokio::spawn(Self::client_loop(
             id,
             endpoint,
+            label,
+            self.subscription_label_metrics_max,
             stream_tx,
             client_rx,
             snapshot_rx,
             self.broadcast_tx.subscribe(),
             self.replay_stored_slots_tx.clone(),
             self.debug_clients_tx.clone(),
+            self.idle_timeout,
+            is_active,
+            self.turbo,
+            spill,
             move || {
                 notify_exit1.notify_one();
                 notify_exit2.notify_one();
+                // request stream can be closed while the client receives updates
+                if let Some(tx) = subscriptions_tx {
+                    let _ = tx.send(SubscriptionEvent::Removed { id });
+                }
             },
         ));
 
-        Ok(Response::new(ReceiverStream::new(stream_rx)))
+        let created_at = self.created_at;
+        let replica = self.replica_updates.clone();
+        let updates = match bandwidth {
+            Some(bandwidth) => updates
+                .then(move |item| {
+                    let bandwidth = Arc::clone(&bandwidth);
+                    async move { bandwidth.shape(item).await }
+                })
+                .boxed(),
+            None => updates.boxed(),
+        };
+        // large updates are encoded by blocking workers, `buffered` keeps the order
+        let updates = match self.encode {
+            Some(ConfigGrpcEncode { workers, min_size }) => updates
+                .map(move |item| match item {
+                    Ok(mut update)
+                        if update.encoded_message.is_none() && update.encoded_len() >= min_size =>
+                    {
+                        spawn_blocking(move || {
+                            update.encode_message();
+                            Ok(update)
+                        })
+                        .map(|result| {
+                            result.unwrap_or_else(|error| {
+                                Err(Status::internal(format!(
+                                    "failed to encode update: {error}"
+                                )))
+                            })
+                        })
+                        .left_future()
+                    }
+                    item => future::ready(item).right_future(),
+                })
+                .buffered(workers.max(1))
+                .boxed(),
+            None => updates,
+        };
+        Ok(self.with_replica(Response::new(
+            updates
+                .map(move |item| {
+                    item.map(|mut update| {
+                        if created_at == ConfigGrpcCreatedAt::Send {
+                            update.ingested_at = Some(update.created_at);
+                            update.created_at = Timestamp::from(SystemTime::now());
+                        }
+                        update.replica.clone_from(&replica);
+                        update
+                    })
+                })
+                .boxed(),
+        )))
     }
 
     async fn subscribe_first_available_slot(
@@ -1297,6 +2556,23 @@ impl Geyser for GrpcService {
         }
     }
 
+    async fn get_slot_watermarks(
+        &self,
+        _request: Request<GetSlotWatermarksRequest>,
+    ) -> Result<Response<GetSlotWatermarksResponse>, Status> {
+        if let Some(blocks_meta) = &self.blocks_meta {
+            let first_available = self
+                .replay_first_available_slot
+                .as_ref()
+                .map(|stored| stored.load(Ordering::Relaxed));
+            Ok(Response::new(
+                blocks_meta.get_slot_watermarks(first_available).await,
+            ))
+        } else {
+            Err(Status::unimplemented("method disabled"))
+        }
+    }
+
     async fn is_blockhash_valid(
         &self,
         request: Request<IsBlockhashValidRequest>,
@@ -1315,8 +2591,352 @@ impl Geyser for GrpcService {
         &self,
         _request: Request<GetVersionRequest>,
     ) -> Result<Response<GetVersionResponse>, Status> {
-        Ok(Response::new(GetVersionResponse {
+        Ok(self.with_replica(Response::new(GetVersionResponse {
             version: serde_json::to_string(&GrpcVersionInfo::default()).unwrap(),
-        }))
+            features: Some(self.features.clone()),
+            cluster: self.cluster.clone(),
+        })))
+    }
+
+    type SnapshotDownloadStream = ReceiverStream<TonicResult<SnapshotDownloadResponse>>;
+
+    async fn snapshot_download(
+        &self,
+        request: Request<SnapshotDownloadRequest>,
+    ) -> TonicResult<Response<Self::SnapshotDownloadStream>> {
+        let Some(snapshot_download) = &self.snapshot_download else {
+            return Err(Status::unimplemented("method disabled"));
+        };
+        check_storage_consistent(&snapshot_download.inconsistent)?;
+        let request = request.into_inner();
+
+        let filter = {
+            let mut filter_names = self.filter_names.lock().await;
+            filter_names.try_clean();
+            AccountsStore::create_filter(&request, &self.config_filter_limits, &mut filter_names)
+                .map_err(Self::filter_error_to_status)?
+        };
+        let (slot, mut cursor) = snapshot_download
+            .inner
+            .read()
+            .await
+            .get_position(&request)
+            .map_err(SnapshotDownloadStorage::error_to_status)?;
+
+        // chunks are read only when previous one is sent
+        let (tx, rx) = mpsc::channel(1);
+        let storage = Arc::clone(&snapshot_download.inner);
+        let inconsistent = Arc::clone(&snapshot_download.inconsistent);
+        let chunk_size = snapshot_download.chunk_size;
+        tokio::spawn(async move {
+            loop {
+                if let Err(status) = check_storage_consistent(&inconsistent) {
+                    let _ = tx.send(Err(status)).await;
+                    break;
+                }
+                let chunk = storage
+                    .read()
+                    .await
+                    .get_chunk(slot, cursor, &filter, chunk_size);
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(error) => {
+                        let status = SnapshotDownloadStorage::error_to_status(error);
+                        let _ = tx.send(Err(status)).await;
+                        break;
+                    }
+                };
+
+                let finished = chunk.finished;
+                cursor = Pubkey::try_from(chunk.cursor.as_slice()).ok();
+                if tx.send(Ok(chunk)).await.is_err() || finished {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn get_account(
+        &self,
+        request: Request<GetAccountRequest>,
+    ) -> TonicResult<Response<GetAccountResponse>> {
+        let Some(account_cache) = &self.account_cache else {
+            return Err(Status::unimplemented("method disabled"));
+        };
+        check_storage_consistent(&account_cache.inconsistent)?;
+        account_cache
+            .inner
+            .read()
+            .await
+            .get_account(request.get_ref(), &self.config_filter_limits.blocklist)
+            .map(Response::new)
+            .map_err(AccountCacheStorage::error_to_status)
+    }
+
+    async fn explain_filters(
+        &self,
+        request: Request<ExplainFiltersRequest>,
+    ) -> TonicResult<Response<ExplainFiltersResponse>> {
+        if !self.features.unary {
+            return Err(Status::unimplemented("method disabled"));
+        }
+        let request = request.into_inner();
+
+        let (_request, filter) = Self::create_filter(
+            request.request.unwrap_or_default(),
+            Arc::clone(&self.config_filter_limits),
+            Arc::clone(&self.filter_names),
+        )
+        .await?;
+
+        let update = request.update.unwrap_or_default();
+        let Some(update_oneof) = update.update_oneof else {
+            return Err(Status::invalid_argument("update is not defined"));
+        };
+        let created_at = update
+            .created_at
+            .map(to_prost)
+            .unwrap_or_else(|| Timestamp::from(SystemTime::now()));
+        let message = Message::from_update_oneof(update_oneof, created_at)
+            .map_err(|error| Status::invalid_argument(format!("invalid update: {error}")))?;
+
+        let filters = filter
+            .explain(&message)
+            .into_iter()
+            .map(|explain| ExplainFiltersResult {
+                kind: explain.kind.to_owned(),
+                name: explain.name,
+                matched: explain.reason.is_none(),
+                reason: explain.reason.map(ToOwned::to_owned),
+            })
+            .collect();
+        Ok(Response::new(ExplainFiltersResponse { filters }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            check_storage_consistent, GrpcService, ReplayedResponse, SlotMessages, StorageFeed,
+        },
+        crate::{config::ConfigGrpcReplayPersist, replay},
+        prost_types::Timestamp,
+        solana_sdk::pubkey::Pubkey,
+        std::{
+            collections::{BTreeMap, HashSet},
+            env, fs, process,
+            sync::{atomic::AtomicBool, Arc},
+            time::Duration,
+        },
+        tokio::sync::{broadcast, mpsc, oneshot},
+        yellowstone_grpc_proto::plugin::{
+            account_cache::AccountCacheFilter,
+            message::{
+                CommitmentLevel, Message, MessageAccount, MessageAccountInfo, MessageSlot,
+                SlotStatus,
+            },
+        },
+    };
+
+    fn create_message_slot(slot: u64, status: SlotStatus) -> Message {
+        Message::Slot(MessageSlot {
+            slot,
+            parent: slot.checked_sub(1),
+            status,
+            dead_error: None,
+            created_at: Timestamp::default(),
+        })
+    }
+
+    #[test]
+    fn test_storage_feed_disabled_when_full() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let inconsistent = Arc::new(AtomicBool::new(false));
+        let mut feed = StorageFeed {
+            name: "test",
+            tx,
+            filter: AccountCacheFilter::new(HashSet::new(), HashSet::new()),
+            inconsistent: Arc::clone(&inconsistent),
+        };
+
+        // not selected messages are skipped
+        let account = Message::Account(MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey: Pubkey::new_unique(),
+                lamports: 0,
+                owner: Pubkey::new_unique(),
+                executable: false,
+                rent_epoch: 0,
+                data: vec![],
+                write_version: 0,
+                txn_signature: None,
+            }),
+            slot: 1,
+            is_startup: false,
+            previous_owner: None,
+            created_at: Timestamp::default(),
+        });
+        assert!(feed.send(&account));
+        assert!(feed.send(&create_message_slot(1, SlotStatus::Processed)));
+        assert!(check_storage_consistent(&inconsistent).is_ok());
+
+        // the loop does not wait, the storage is disabled
+        assert!(!feed.send(&create_message_slot(2, SlotStatus::Processed)));
+        assert!(check_storage_consistent(&inconsistent).is_err());
+        assert!(matches!(rx.try_recv(), Ok(Message::Slot(msg)) if msg.slot == 1));
+    }
+
+    #[test]
+    fn test_dedup_account() {
+        let pubkey = Pubkey::new_unique();
+        let mut slot_messages = SlotMessages::default();
+        for (msgid, write_version) in [(1, 2), (2, 5), (3, 3)] {
+            let message = Message::Account(MessageAccount {
+                account: Arc::new(MessageAccountInfo {
+                    pubkey,
+                    lamports: 0,
+                    owner: Pubkey::default(),
+                    executable: false,
+                    rent_epoch: 0,
+                    data: vec![],
+                    write_version,
+                    txn_signature: None,
+                }),
+                slot: 1,
+                is_startup: false,
+                previous_owner: None,
+                created_at: Timestamp::default(),
+            });
+            slot_messages.memory += SlotMessages::message_memory(&message);
+            slot_messages.messages.push(Some((msgid, message)));
+            slot_messages.dedup_account(pubkey, write_version);
+        }
+
+        // late write with lower write_version is dropped too
+        let kept = slot_messages
+            .messages
+            .iter()
+            .flatten()
+            .map(|(msgid, _message)| *msgid)
+            .collect::<Vec<_>>();
+        assert_eq!(kept, vec![2]);
+        assert_eq!(slot_messages.accounts_dedup[&pubkey], (5, 1));
+    }
+
+    #[test]
+    fn test_save_stored_slots() {
+        let path = env::temp_dir().join(format!("yellowstone-grpc-stored-{}.bin", process::id()));
+        let mut messages = BTreeMap::new();
+        let mut msgid = 0;
+        for slot in 1..=3 {
+            let slot_messages: &mut SlotMessages = messages.entry(slot).or_default();
+            for status in [SlotStatus::Processed, SlotStatus::Confirmed] {
+                msgid += 1;
+                slot_messages
+                    .messages_slots
+                    .push((msgid, create_message_slot(slot, status)));
+            }
+        }
+        let size = GrpcService::create_shm_update(&create_message_slot(3, SlotStatus::Processed))
+            .unwrap()
+            .encoded_len();
+        let mut config = ConfigGrpcReplayPersist {
+            path: path.clone(),
+            max_size: usize::MAX,
+            max_age: Duration::from_secs(600),
+        };
+
+        // all slots in order of receiving
+        assert_eq!(
+            GrpcService::save_stored_slots(&messages, &config).unwrap(),
+            3
+        );
+        let loaded = replay::load_messages(&[path.clone()]).unwrap();
+        assert_eq!(
+            loaded.iter().map(Message::get_slot).collect::<Vec<_>>(),
+            vec![1, 1, 2, 2, 3, 3]
+        );
+
+        // only the latest slot fits
+        config.max_size = size * 3;
+        assert_eq!(
+            GrpcService::save_stored_slots(&messages, &config).unwrap(),
+            1
+        );
+        let loaded = replay::load_messages(&[path.clone()])
+            .unwrap()
+            .into_iter()
+            .map(|message| match message {
+                Message::Slot(msg) => (msg.slot, msg.status),
+                message => panic!("unexpected message: {message:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            loaded,
+            vec![(3, SlotStatus::Processed), (3, SlotStatus::Confirmed)]
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_loaded_messages_only_replayed() {
+        let (messages_tx, messages_rx) = mpsc::unbounded_channel();
+        let (broadcast_tx, mut broadcast_rx) = broadcast::channel(16);
+        let (replay_tx, replay_rx) = mpsc::channel(1);
+        let loaded = (1..=2)
+            .map(|slot| create_message_slot(slot, SlotStatus::Processed))
+            .collect();
+        let geyser_loop = GrpcService::geyser_loop(
+            messages_rx,
+            None,
+            None,
+            None,
+            None,
+            broadcast_tx,
+            Some(replay_rx),
+            None,
+            100,
+            None,
+            loaded,
+            None,
+            None,
+            false,
+        );
+        let client = async move {
+            // only the live slot is sent to clients
+            messages_tx
+                .send(create_message_slot(3, SlotStatus::Processed))
+                .unwrap();
+            let (commitment, messages) = broadcast_rx.recv().await.unwrap();
+            assert!(matches!(commitment, CommitmentLevel::Processed));
+            assert_eq!(
+                messages
+                    .iter()
+                    .map(|(_msgid, message)| message.get_slot())
+                    .collect::<Vec<_>>(),
+                vec![3]
+            );
+
+            // loaded slots are stored for replay
+            let (tx, rx) = oneshot::channel();
+            replay_tx
+                .send((CommitmentLevel::Processed, 1, tx))
+                .await
+                .unwrap();
+            let Ok(ReplayedResponse::Messages(replayed)) = rx.await else {
+                panic!("stored slots are not replayed");
+            };
+            assert_eq!(
+                replayed
+                    .iter()
+                    .map(|(_msgid, message)| message.get_slot())
+                    .collect::<Vec<_>>(),
+                vec![1, 2, 3]
+            );
+        };
+        tokio::join!(geyser_loop, client);
     }
 }
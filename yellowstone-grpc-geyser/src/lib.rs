@@ -1,7 +1,15 @@
+pub mod bandwidth;
 pub mod config;
 pub mod grpc;
+pub mod log_level;
 pub mod metrics;
+pub mod owners;
 pub mod plugin;
+pub mod priority;
+pub mod profiling;
+pub mod relay;
+pub mod replay;
+pub mod spill;
 pub mod version;
 
 pub fn get_thread_name() -> String {
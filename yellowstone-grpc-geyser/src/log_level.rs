@@ -0,0 +1,80 @@
+use {
+    log::LevelFilter,
+    std::{collections::BTreeMap, env, str::FromStr, sync::Mutex},
+};
+
+/// Log target of messages from the geyser loop (block reconstruction and broadcast)
+pub const LOG_TARGET_BROADCAST: &str = "yellowstone_grpc_geyser::broadcast";
+
+/// Short module names and their log targets
+const MODULES: [(&str, &str); 5] = [
+    ("plugin", "yellowstone_grpc_geyser::plugin"),
+    ("grpc", "yellowstone_grpc_geyser::grpc"),
+    ("broadcast", LOG_TARGET_BROADCAST),
+    ("metrics", "yellowstone_grpc_geyser::metrics"),
+    ("filters", "yellowstone_grpc_proto::plugin::filter"),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum LogLevelError {
+    #[error("unknown module: {0}, expected one of: plugin, grpc, broadcast, metrics, filters")]
+    UnknownModule(String),
+    #[error("invalid level: {0}")]
+    InvalidLevel(String),
+}
+
+/// Plugin log filter: level from the config (or `RUST_LOG`) with per-module
+/// overrides, which can be changed at runtime without restart
+#[derive(Debug)]
+pub struct LogLevels {
+    base: String,
+    modules: Mutex<BTreeMap<&'static str, LevelFilter>>,
+}
+
+impl LogLevels {
+    pub fn new(level: &str) -> Self {
+        Self {
+            base: env::var("RUST_LOG").unwrap_or_else(|_| level.to_owned()),
+            modules: Mutex::default(),
+        }
+    }
+
+    /// Install logger with current filter, can be called any number of times
+    pub fn setup(&self) {
+        solana_logger::setup_with(&self.filter());
+    }
+
+    pub fn filter(&self) -> String {
+        let modules = self.modules.lock().expect("unpoisoned mutex");
+        let mut filter = self.base.clone();
+        for (target, level) in modules.iter() {
+            filter.push_str(&format!(",{target}={level}"));
+        }
+        filter
+    }
+
+    /// Set level of the module, `default` removes override
+    pub fn set(&self, module: &str, level: &str) -> Result<(), LogLevelError> {
+        let target = MODULES
+            .iter()
+            .find_map(|(name, target)| (*name == module).then_some(*target))
+            .ok_or_else(|| LogLevelError::UnknownModule(module.to_owned()))?;
+        let level = match level {
+            "default" => None,
+            level => Some(
+                LevelFilter::from_str(level)
+                    .map_err(|_error| LogLevelError::InvalidLevel(level.to_owned()))?,
+            ),
+        };
+
+        let mut modules = self.modules.lock().expect("unpoisoned mutex");
+        match level {
+            Some(level) => modules.insert(target, level),
+            None => modules.remove(target),
+        };
+        drop(modules);
+
+        self.setup();
+        Ok(())
+    }
+}
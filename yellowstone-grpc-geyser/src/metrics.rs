@@ -1,30 +1,44 @@
 use {
-    crate::{config::ConfigPrometheus, version::VERSION as VERSION_INFO},
+    crate::{
+        config::ConfigPrometheus,
+        log_level::LogLevels,
+        profiling::{ProfileError, ProfileFormat, Profiler},
+        relay::GapReports,
+        version::VERSION as VERSION_INFO,
+    },
     agave_geyser_plugin_interface::geyser_plugin_interface::SlotStatus as GeyserSlosStatus,
     http_body_util::{combinators::BoxBody, BodyExt, Empty as BodyEmpty, Full as BodyFull},
     hyper::{
         body::{Bytes, Incoming as BodyIncoming},
         service::service_fn,
-        Request, Response, StatusCode,
+        Method, Request, Response, StatusCode,
     },
     hyper_util::{
         rt::tokio::{TokioExecutor, TokioIo},
         server::conn::auto::Builder as ServerBuilder,
     },
     log::{error, info},
-    prometheus::{IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder},
+    prometheus::{
+        HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+        Registry, TextEncoder,
+    },
+    prost_types::Timestamp,
     solana_sdk::clock::Slot,
     std::{
         collections::{hash_map::Entry as HashMapEntry, HashMap},
         convert::Infallible,
-        sync::{Arc, Once},
+        sync::{Arc, Mutex, Once},
+        time::SystemTime,
     },
     tokio::{
         net::TcpListener,
         sync::{mpsc, oneshot, Notify},
         task::JoinHandle,
     },
-    yellowstone_grpc_proto::plugin::{filter::Filter, message::SlotStatus},
+    yellowstone_grpc_proto::plugin::{
+        filter::Filter,
+        message::{CommitmentLevel, SlotStatus},
+    },
 };
 
 lazy_static::lazy_static! {
@@ -45,6 +59,16 @@ lazy_static::lazy_static! {
         &["status"]
     ).unwrap();
 
+    static ref COMMITMENT_SLOT_LAG: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("commitment_slot_lag", "Slots between the latest slot status from Geyser and the latest processed in the plugin"),
+        &["commitment"]
+    ).unwrap();
+
+    static ref COMMITMENT_QUEUE_SIZE: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("commitment_queue_size", "Number of messages in the plugin waiting for commitment"),
+        &["commitment"]
+    ).unwrap();
+
     static ref INVALID_FULL_BLOCKS: IntGaugeVec = IntGaugeVec::new(
         Opts::new("invalid_full_blocks_total", "Total number of fails on constructin full blocks"),
         &["reason"]
@@ -63,17 +87,80 @@ lazy_static::lazy_static! {
         &["endpoint", "subscription"]
     ).unwrap();
 
+    static ref SUBSCRIPTION_LABEL_CONNECTIONS: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("subscription_label_connections_total", "Number of connections by x-subscription-id"),
+        &["label"]
+    ).unwrap();
+
+    static ref SUBSCRIPTION_LABEL_MESSAGES_SENT: IntCounterVec = IntCounterVec::new(
+        Opts::new("subscription_label_messages_sent_total", "Number of sent messages by x-subscription-id"),
+        &["label"]
+    ).unwrap();
+
+    static ref SUBSCRIPTIONS_IDLE_CLOSED: IntCounter = IntCounter::new(
+        "subscriptions_idle_closed_total", "Number of subscriptions closed by idle timeout"
+    ).unwrap();
+
+    static ref CLIENT_SPILLED_UPDATES: IntCounter = IntCounter::new(
+        "client_spilled_updates_total", "Number of updates written to the client spill file"
+    ).unwrap();
+
+    static ref SHM_DROPPED_BATCHES: IntCounter = IntCounter::new(
+        "shm_dropped_batches_total", "Number of batches of messages not written to shm because encoding workers are behind"
+    ).unwrap();
+
+    static ref STORAGE_DISABLED: IntCounterVec = IntCounterVec::new(
+        Opts::new("storage_disabled_total", "Number of accounts storages disabled because updates channel was full"),
+        &["storage"]
+    ).unwrap();
+
+    /// Connected clients by label of `SUBSCRIPTION_LABEL_*` metrics
+    static ref SUBSCRIPTION_LABELS: Mutex<HashMap<String, usize>> = Mutex::default();
+
     static ref MISSED_STATUS_MESSAGE: IntCounterVec = IntCounterVec::new(
         Opts::new("missed_status_message_total", "Number of missed messages by commitment"),
         &["status"]
     ).unwrap();
+
+    static ref TURBO_SEND_LATENCY: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "turbo_send_latency_seconds",
+            "Latency from geyser notification to client queue of processed slots and transactions in turbo mode"
+        ).buckets(vec![0.000_1, 0.000_25, 0.000_5, 0.001, 0.002_5, 0.005, 0.01, 0.025, 0.05, 0.1]),
+        &["kind"]
+    ).unwrap();
+
+    static ref MEMORY_ESTIMATE: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("memory_estimate_bytes", "Approximate memory used by subsystem"),
+        &["subsystem"]
+    ).unwrap();
+
+    static ref MEMORY_ESTIMATE_FILTERS: IntGauge =
+        MEMORY_ESTIMATE.with_label_values(&["filters"]);
+
+    static ref MEMORY_ESTIMATE_CLIENT_QUEUES: IntGauge =
+        MEMORY_ESTIMATE.with_label_values(&["client_queues"]);
+
+    static ref JEMALLOC_STATS: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("jemalloc_bytes", "Allocator statistics of the validator, requires `jemalloc` feature"),
+        &["stat"]
+    ).unwrap();
 }
 
 #[derive(Debug)]
 pub enum DebugClientMessage {
-    UpdateFilter { id: usize, filter: Box<Filter> },
-    UpdateSlot { id: usize, slot: Slot },
-    Removed { id: usize },
+    UpdateFilter {
+        id: usize,
+        label: Option<String>,
+        filter: Box<Filter>,
+    },
+    UpdateSlot {
+        id: usize,
+        slot: Slot,
+    },
+    Removed {
+        id: usize,
+    },
 }
 
 impl DebugClientMessage {
@@ -86,6 +173,7 @@ impl DebugClientMessage {
 
 #[derive(Debug)]
 struct DebugClientStatus {
+    label: Option<String>,
     filter: Box<Filter>,
     processed_slot: Slot,
 }
@@ -117,13 +205,14 @@ impl DebugClientStatuses {
         loop {
             tokio::select! {
                 Some(message) = clients_rx.recv() => match message {
-                    DebugClientMessage::UpdateFilter { id, filter } => {
+                    DebugClientMessage::UpdateFilter { id, label, filter } => {
                         match clients.entry(id) {
                             HashMapEntry::Occupied(mut entry) => {
                                 entry.get_mut().filter = filter;
                             }
                             HashMapEntry::Vacant(entry) => {
                                 entry.insert(DebugClientStatus {
+                                    label,
                                     filter,
                                     processed_slot: 0,
                                 });
@@ -141,7 +230,8 @@ impl DebugClientStatuses {
                 },
                 Some(tx) = requests_rx.recv() => {
                     let mut statuses: Vec<(usize, String)> = clients.iter().map(|(id, status)| {
-                        (*id, format!("client#{id:06}, {}, {:?}", status.processed_slot, status.filter))
+                        let label = status.label.as_deref().unwrap_or_default();
+                        (*id, format!("client#{id:06}, {label}, {}, {:?}", status.processed_slot, status.filter))
                     }).collect();
                     statuses.sort();
 
@@ -181,6 +271,8 @@ impl PrometheusService {
     pub async fn new(
         config: Option<ConfigPrometheus>,
         debug_clients_rx: Option<mpsc::UnboundedReceiver<DebugClientMessage>>,
+        log_levels: Option<Arc<LogLevels>>,
+        gap_reports: Option<Arc<GapReports>>,
     ) -> std::io::Result<Self> {
         static REGISTER: Once = Once::new();
         REGISTER.call_once(|| {
@@ -194,11 +286,22 @@ impl PrometheusService {
             register!(VERSION);
             register!(SLOT_STATUS);
             register!(SLOT_STATUS_PLUGIN);
+            register!(COMMITMENT_SLOT_LAG);
+            register!(COMMITMENT_QUEUE_SIZE);
             register!(INVALID_FULL_BLOCKS);
             register!(MESSAGE_QUEUE_SIZE);
             register!(CONNECTIONS_TOTAL);
             register!(SUBSCRIPTIONS_TOTAL);
+            register!(SUBSCRIPTION_LABEL_CONNECTIONS);
+            register!(SUBSCRIPTION_LABEL_MESSAGES_SENT);
+            register!(SUBSCRIPTIONS_IDLE_CLOSED);
+            register!(CLIENT_SPILLED_UPDATES);
+            register!(SHM_DROPPED_BATCHES);
+            register!(STORAGE_DISABLED);
             register!(MISSED_STATUS_MESSAGE);
+            register!(TURBO_SEND_LATENCY);
+            register!(MEMORY_ESTIMATE);
+            register!(JEMALLOC_STATS);
 
             VERSION
                 .with_label_values(&[
@@ -215,7 +318,8 @@ impl PrometheusService {
 
         let shutdown = Arc::new(Notify::new());
         let mut debug_clients_statuses = None;
-        if let Some(ConfigPrometheus { address }) = config {
+        if let Some(ConfigPrometheus { address, profiling }) = config {
+            let profiler = profiling.map(|config| Arc::new(Profiler::new(config)));
             if let Some(debug_clients_rx) = debug_clients_rx {
                 debug_clients_statuses = Some(DebugClientStatuses::new(debug_clients_rx));
             }
@@ -239,12 +343,18 @@ impl PrometheusService {
                         }
                     };
                     let debug_clients_statuses = debug_clients_statuses2.clone();
+                    let log_levels = log_levels.clone();
+                    let gap_reports = gap_reports.clone();
+                    let profiler = profiler.clone();
                     tokio::spawn(async move {
                         if let Err(error) = ServerBuilder::new(TokioExecutor::new())
                             .serve_connection(
                                 TokioIo::new(stream),
                                 service_fn(move |req: Request<BodyIncoming>| {
                                     let debug_clients_statuses = debug_clients_statuses.clone();
+                                    let log_levels = log_levels.clone();
+                                    let gap_reports = gap_reports.clone();
+                                    let profiler = profiler.clone();
                                     async move {
                                         match req.uri().path() {
                                             "/metrics" => metrics_handler(),
@@ -270,6 +380,30 @@ impl PrometheusService {
                                                     not_found_handler()
                                                 }
                                             }
+                                            "/log_level" => match &log_levels {
+                                                Some(log_levels) => {
+                                                    log_level_handler(&req, log_levels)
+                                                }
+                                                None => not_found_handler(),
+                                            },
+                                            "/gap_reports" => match &gap_reports {
+                                                Some(gap_reports) => {
+                                                    gap_reports_handler(&req, gap_reports)
+                                                }
+                                                None => not_found_handler(),
+                                            },
+                                            "/debug/pprof/profile" => match &profiler {
+                                                Some(profiler) => {
+                                                    profile_handler(&req, profiler).await
+                                                }
+                                                None => not_found_handler(),
+                                            },
+                                            "/debug/pprof/heap" => match &profiler {
+                                                Some(profiler) => {
+                                                    heap_handler(&req, profiler).await
+                                                }
+                                                None => not_found_handler(),
+                                            },
                                             _ => not_found_handler(),
                                         }
                                     }
@@ -297,6 +431,8 @@ impl PrometheusService {
 }
 
 fn metrics_handler() -> http::Result<Response<BoxBody<Bytes, Infallible>>> {
+    #[cfg(feature = "jemalloc")]
+    update_jemalloc_stats();
     let metrics = TextEncoder::new()
         .encode_to_string(&REGISTRY.gather())
         .unwrap_or_else(|error| {
@@ -308,6 +444,203 @@ fn metrics_handler() -> http::Result<Response<BoxBody<Bytes, Infallible>>> {
         .body(BodyFull::new(Bytes::from(metrics)).boxed())
 }
 
+/// `GET` returns current filter, `POST` with `module` and `level` in the query
+/// changes level of the module (`level=default` removes override)
+fn log_level_handler(
+    req: &Request<BodyIncoming>,
+    log_levels: &LogLevels,
+) -> http::Result<Response<BoxBody<Bytes, Infallible>>> {
+    let (status, body) = match *req.method() {
+        Method::GET => (StatusCode::OK, log_levels.filter()),
+        Method::POST => {
+            let mut module = None;
+            let mut level = None;
+            for (key, value) in req
+                .uri()
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+            {
+                match key {
+                    "module" => module = Some(value),
+                    "level" => level = Some(value),
+                    _ => {}
+                }
+            }
+            match (module, level) {
+                (Some(module), Some(level)) => match log_levels.set(module, level) {
+                    Ok(()) => {
+                        info!("log level of {module} changed to {level}");
+                        (StatusCode::OK, log_levels.filter())
+                    }
+                    Err(error) => (StatusCode::BAD_REQUEST, error.to_string()),
+                },
+                _ => (
+                    StatusCode::BAD_REQUEST,
+                    "`module` and `level` are required".to_owned(),
+                ),
+            }
+        }
+        _ => (StatusCode::METHOD_NOT_ALLOWED, String::new()),
+    };
+    Response::builder()
+        .status(status)
+        .body(BodyFull::new(Bytes::from(body + "\n")).boxed())
+}
+
+/// `GET` with optional `from_slot` and `incomplete=true` in the query returns
+/// completeness of slots by upstream of the relay
+fn gap_reports_handler(
+    req: &Request<BodyIncoming>,
+    gap_reports: &GapReports,
+) -> http::Result<Response<BoxBody<Bytes, Infallible>>> {
+    if *req.method() != Method::GET {
+        return Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .body(BodyEmpty::new().boxed());
+    }
+
+    let mut from_slot = None;
+    let mut incomplete = false;
+    for (key, value) in req
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+    {
+        match key {
+            "from_slot" => match value.parse() {
+                Ok(slot) => from_slot = Some(slot),
+                Err(_) => {
+                    return Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .body(BodyFull::new(Bytes::from("invalid `from_slot`\n")).boxed())
+                }
+            },
+            "incomplete" => incomplete = value == "true",
+            _ => {}
+        }
+    }
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(
+            BodyFull::new(Bytes::from(
+                gap_reports.to_json(from_slot, incomplete).to_string(),
+            ))
+            .boxed(),
+        )
+}
+
+/// `GET` with optional `seconds` and `format` (`pprof` or `flamegraph`) in the
+/// query returns CPU profile, `x-token` header is required
+async fn profile_handler(
+    req: &Request<BodyIncoming>,
+    profiler: &Profiler,
+) -> http::Result<Response<BoxBody<Bytes, Infallible>>> {
+    if let Some(response) = profile_check_request(req, profiler) {
+        return response;
+    }
+
+    let mut seconds = None;
+    let mut format = Ok(ProfileFormat::Pprof);
+    for (key, value) in req
+        .uri()
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+    {
+        match key {
+            "seconds" => seconds = Some(value),
+            "format" => format = value.parse(),
+            _ => {}
+        }
+    }
+    let result = match (format, profiler.duration(seconds)) {
+        (Ok(format), Ok(duration)) => {
+            info!("start CPU profile for {duration:?}");
+            profiler
+                .profile(duration, format)
+                .await
+                .map(|body| (format.content_type(), body))
+        }
+        (Err(error), _) | (_, Err(error)) => Err(error),
+    };
+    profile_response(result)
+}
+
+/// `GET` returns heap profile of the validator jemalloc, `x-token` header is
+/// required
+async fn heap_handler(
+    req: &Request<BodyIncoming>,
+    profiler: &Profiler,
+) -> http::Result<Response<BoxBody<Bytes, Infallible>>> {
+    if let Some(response) = profile_check_request(req, profiler) {
+        return response;
+    }
+
+    info!("dump heap profile");
+    let result = profiler
+        .heap()
+        .await
+        .map(|body| (ProfileFormat::Pprof.content_type(), body));
+    profile_response(result)
+}
+
+fn profile_check_request(
+    req: &Request<BodyIncoming>,
+    profiler: &Profiler,
+) -> Option<http::Result<Response<BoxBody<Bytes, Infallible>>>> {
+    if *req.method() != Method::GET {
+        return Some(
+            Response::builder()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .body(BodyEmpty::new().boxed()),
+        );
+    }
+    let token = req
+        .headers()
+        .get("x-token")
+        .and_then(|value| value.to_str().ok());
+    if !profiler.is_authorized(token) {
+        return Some(
+            Response::builder()
+                .status(StatusCode::UNAUTHORIZED)
+                .body(BodyEmpty::new().boxed()),
+        );
+    }
+    None
+}
+
+fn profile_response(
+    result: Result<(&'static str, Vec<u8>), ProfileError>,
+) -> http::Result<Response<BoxBody<Bytes, Infallible>>> {
+    match result {
+        Ok((content_type, body)) => Response::builder()
+            .status(StatusCode::OK)
+            .header(http::header::CONTENT_TYPE, content_type)
+            .body(BodyFull::new(Bytes::from(body)).boxed()),
+        Err(error) => {
+            let status = match error {
+                ProfileError::InvalidFormat(_) | ProfileError::InvalidDuration(_) => {
+                    StatusCode::BAD_REQUEST
+                }
+                ProfileError::Busy => StatusCode::CONFLICT,
+                ProfileError::Unsupported | ProfileError::HeapUnavailable(_) => {
+                    StatusCode::NOT_IMPLEMENTED
+                }
+                ProfileError::Profiler(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Response::builder()
+                .status(status)
+                .body(BodyFull::new(Bytes::from(format!("{error}\n"))).boxed())
+        }
+    }
+}
+
 fn not_found_handler() -> http::Result<Response<BoxBody<Bytes, Infallible>>> {
     Response::builder()
         .status(StatusCode::NOT_FOUND)
@@ -318,12 +651,149 @@ pub fn update_slot_status(status: &GeyserSlosStatus, slot: u64) {
     SLOT_STATUS
         .with_label_values(&[status.as_str()])
         .set(slot as i64);
+    match status {
+        GeyserSlosStatus::Processed => update_commitment_slot_lag(CommitmentLevel::Processed),
+        GeyserSlosStatus::Confirmed => update_commitment_slot_lag(CommitmentLevel::Confirmed),
+        GeyserSlosStatus::Rooted => update_commitment_slot_lag(CommitmentLevel::Finalized),
+        _ => {}
+    }
 }
 
 pub fn update_slot_plugin_status(status: SlotStatus, slot: u64) {
     SLOT_STATUS_PLUGIN
         .with_label_values(&[status.as_str()])
         .set(slot as i64);
+    match status {
+        SlotStatus::Processed => update_commitment_slot_lag(CommitmentLevel::Processed),
+        SlotStatus::Confirmed => update_commitment_slot_lag(CommitmentLevel::Confirmed),
+        SlotStatus::Finalized => update_commitment_slot_lag(CommitmentLevel::Finalized),
+        _ => {}
+    }
+}
+
+// updated from both sides, so lag grows if the plugin is stuck
+fn update_commitment_slot_lag(commitment: CommitmentLevel) {
+    let (geyser, plugin) = match commitment {
+        CommitmentLevel::Processed => ("processed", "processed"),
+        CommitmentLevel::Confirmed => ("confirmed", "confirmed"),
+        CommitmentLevel::Finalized => ("rooted", "finalized"),
+    };
+    let geyser = SLOT_STATUS.with_label_values(&[geyser]).get();
+    let plugin = SLOT_STATUS_PLUGIN.with_label_values(&[plugin]).get();
+    if geyser > 0 && plugin > 0 {
+        COMMITMENT_SLOT_LAG
+            .with_label_values(&[commitment.as_str()])
+            .set((geyser - plugin).max(0));
+    }
+}
+
+pub fn update_commitment_queue_size(commitment: CommitmentLevel, size: usize) {
+    COMMITMENT_QUEUE_SIZE
+        .with_label_values(&[commitment.as_str()])
+        .set(size as i64);
+}
+
+/// Change of the estimate shared by all clients, every client adds the
+/// difference of own estimate
+pub fn client_queues_memory_add(bytes: i64) {
+    MEMORY_ESTIMATE_CLIENT_QUEUES.add(bytes);
+}
+
+pub fn update_memory_estimate(subsystem: &str, bytes: usize) {
+    MEMORY_ESTIMATE
+        .with_label_values(&[subsystem])
+        .set(bytes as i64);
+}
+
+#[cfg(feature = "jemalloc")]
+pub(crate) type Mallctl = unsafe extern "C" fn(
+    *const std::ffi::c_char,
+    *mut std::ffi::c_void,
+    *mut usize,
+    *mut std::ffi::c_void,
+    usize,
+) -> std::ffi::c_int;
+
+/// `mallctl` of jemalloc used by the validator, plugin does not own the
+/// allocator: memory is allocated and freed across the plugin boundary, so
+/// the symbol is resolved at runtime. `None` if the validator is built without
+/// jemalloc or does not export `mallctl`.
+#[cfg(feature = "jemalloc")]
+pub(crate) fn jemalloc_mallctl() -> Option<Mallctl> {
+    use std::{ffi::c_void, sync::OnceLock};
+
+    static MALLCTL: OnceLock<Option<Mallctl>> = OnceLock::new();
+    *MALLCTL.get_or_init(|| {
+        // `tikv-jemallocator` prefixes symbols on Linux
+        let mallctl = [c"_rjem_mallctl", c"mallctl"].into_iter().find_map(|name| {
+            // SAFETY: name is nul-terminated, symbol has `mallctl` signature
+            unsafe {
+                let symbol = libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr());
+                (!symbol.is_null()).then(|| std::mem::transmute::<*mut c_void, Mallctl>(symbol))
+            }
+        });
+        if mallctl.is_none() {
+            info!("jemalloc of the validator is not found, jemalloc stats and heap profiles are not available");
+        }
+        mallctl
+    })
+}
+
+/// Statistics of jemalloc used by the validator, not exported if `mallctl` of
+/// the validator is not found
+#[cfg(feature = "jemalloc")]
+fn update_jemalloc_stats() {
+    use std::{ffi::CStr, mem::size_of, ptr};
+
+    let Some(mallctl) = jemalloc_mallctl() else {
+        return;
+    };
+
+    // statistics are cached by jemalloc, refreshed on every scrape
+    let mut epoch = 1u64;
+    let mut len = size_of::<u64>();
+    // SAFETY: `epoch` is read and written as `u64`
+    let code = unsafe {
+        mallctl(
+            c"epoch".as_ptr(),
+            ptr::addr_of_mut!(epoch).cast(),
+            &mut len,
+            ptr::addr_of_mut!(epoch).cast(),
+            size_of::<u64>(),
+        )
+    };
+    if code != 0 {
+        error!("failed to refresh jemalloc stats: {code}");
+        return;
+    }
+
+    let stats: [(&str, &CStr); 6] = [
+        ("allocated", c"stats.allocated"),
+        ("active", c"stats.active"),
+        ("metadata", c"stats.metadata"),
+        ("resident", c"stats.resident"),
+        ("mapped", c"stats.mapped"),
+        ("retained", c"stats.retained"),
+    ];
+    for (stat, name) in stats {
+        let mut value = 0usize;
+        let mut len = size_of::<usize>();
+        // SAFETY: stats are `size_t`
+        let code = unsafe {
+            mallctl(
+                name.as_ptr(),
+                ptr::addr_of_mut!(value).cast(),
+                &mut len,
+                ptr::null_mut(),
+                0,
+            )
+        };
+        if code == 0 {
+            JEMALLOC_STATS.with_label_values(&[stat]).set(value as i64);
+        } else {
+            error!("failed to read jemalloc stat {stat}: {code}");
+        }
+    }
 }
 
 pub fn update_invalid_blocks(reason: impl AsRef<str>) {
@@ -361,12 +831,142 @@ pub fn update_subscriptions(endpoint: &str, old: Option<&Filter>, new: Option<&F
                     .with_label_values(&[endpoint, name])
                     .add((value as i64) * multiplier);
             }
+            MEMORY_ESTIMATE_FILTERS.add((filter.get_memory_estimate() as i64) * multiplier);
         }
     }
 }
 
+/// Metrics of the connection with `x-subscription-id`, series of the label are
+/// removed when the last connection with the label is closed
+#[derive(Debug)]
+pub struct SubscriptionLabelMetrics {
+    label: String,
+    messages_sent: IntCounter,
+}
+
+impl SubscriptionLabelMetrics {
+    const OTHER: &'static str = "other";
+
+    /// Labels above `max_labels` distinct labels of connected clients are
+    /// reported as `other`
+    pub fn new(label: &str, max_labels: usize) -> Self {
+        let mut labels = SUBSCRIPTION_LABELS.lock().expect("unpoisoned mutex");
+        let label = if labels.contains_key(label) || labels.len() < max_labels {
+            label
+        } else {
+            Self::OTHER
+        };
+        *labels.entry(label.to_owned()).or_default() += 1;
+        SUBSCRIPTION_LABEL_CONNECTIONS
+            .with_label_values(&[label])
+            .inc();
+        Self {
+            label: label.to_owned(),
+            messages_sent: SUBSCRIPTION_LABEL_MESSAGES_SENT.with_label_values(&[label]),
+        }
+    }
+
+    pub fn messages_sent_inc_by(&self, count: u64) {
+        self.messages_sent.inc_by(count);
+    }
+}
+
+impl Drop for SubscriptionLabelMetrics {
+    fn drop(&mut self) {
+        let mut labels = SUBSCRIPTION_LABELS.lock().expect("unpoisoned mutex");
+        let Some(connections) = labels.get_mut(&self.label) else {
+            return;
+        };
+        *connections -= 1;
+        if *connections == 0 {
+            labels.remove(&self.label);
+            let _ = SUBSCRIPTION_LABEL_CONNECTIONS.remove_label_values(&[&self.label]);
+            let _ = SUBSCRIPTION_LABEL_MESSAGES_SENT.remove_label_values(&[&self.label]);
+        } else {
+            SUBSCRIPTION_LABEL_CONNECTIONS
+                .with_label_values(&[&self.label])
+                .dec();
+        }
+    }
+}
+
+pub fn subscriptions_idle_closed_inc() {
+    SUBSCRIPTIONS_IDLE_CLOSED.inc()
+}
+
+pub fn client_spilled_updates_inc() {
+    CLIENT_SPILLED_UPDATES.inc()
+}
+
+pub fn shm_dropped_batches_inc() {
+    SHM_DROPPED_BATCHES.inc()
+}
+
+pub fn storage_disabled_inc(storage: &str) {
+    STORAGE_DISABLED.with_label_values(&[storage]).inc()
+}
+
 pub fn missed_status_message_inc(status: SlotStatus) {
     MISSED_STATUS_MESSAGE
         .with_label_values(&[status.as_str()])
         .inc()
 }
+
+pub fn turbo_send_latency_observe(kind: &str, created_at: &Timestamp) {
+    if let Ok(created_at) = SystemTime::try_from(*created_at) {
+        if let Ok(latency) = SystemTime::now().duration_since(created_at) {
+            TURBO_SEND_LATENCY
+                .with_label_values(&[kind])
+                .observe(latency.as_secs_f64());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{SubscriptionLabelMetrics, SUBSCRIPTION_LABELS, SUBSCRIPTION_LABEL_CONNECTIONS},
+        prometheus::core::Collector,
+    };
+
+    fn labels() -> Vec<(String, i64)> {
+        let mut labels = SUBSCRIPTION_LABEL_CONNECTIONS.collect()[0]
+            .get_metric()
+            .iter()
+            .map(|metric| {
+                let label = metric.get_label()[0].get_value().to_owned();
+                (label, metric.get_gauge().get_value() as i64)
+            })
+            .collect::<Vec<_>>();
+        labels.sort();
+        labels
+    }
+
+    #[test]
+    fn test_subscription_label_metrics() {
+        let a1 = SubscriptionLabelMetrics::new("a", 2);
+        let a2 = SubscriptionLabelMetrics::new("a", 2);
+        let b = SubscriptionLabelMetrics::new("b", 2);
+        let c = SubscriptionLabelMetrics::new("c", 2);
+        let d = SubscriptionLabelMetrics::new("d", 2);
+        assert_eq!(c.label, "other");
+        assert_eq!(
+            labels(),
+            vec![
+                ("a".to_owned(), 2),
+                ("b".to_owned(), 1),
+                ("other".to_owned(), 2)
+            ]
+        );
+
+        drop(a1);
+        drop(b);
+        drop(c);
+        assert_eq!(labels(), vec![("a".to_owned(), 1), ("other".to_owned(), 1)]);
+
+        drop(a2);
+        drop(d);
+        assert!(labels().is_empty());
+        assert!(SUBSCRIPTION_LABELS.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+use {
+    solana_sdk::{clock::Slot, pubkey::Pubkey},
+    std::{
+        collections::{BTreeMap, HashMap},
+        mem,
+    },
+    yellowstone_grpc_proto::plugin::message::{MessageAccount, MessageSlot, SlotStatus},
+};
+
+#[derive(Debug, Default)]
+struct SlotOwners {
+    parent: Option<Slot>,
+    owners: HashMap<Pubkey, Pubkey>,
+}
+
+/// Owners of updated accounts, used to set `previous_owner`. Owners set in
+/// not finalized slots are kept by slot and used only for descendants of the
+/// slot, so owner changes on abandoned forks are never reported. Owners of
+/// finalized slots are limited by `max_accounts`, accounts updated least
+/// recently are forgotten.
+#[derive(Debug)]
+pub struct OwnerTracker {
+    max_accounts: usize,
+    /// Owner and slot of the latest update
+    finalized: HashMap<Pubkey, (Pubkey, Slot)>,
+    finalized_slot: Option<Slot>,
+    slots: BTreeMap<Slot, SlotOwners>,
+    /// Number of not finalized slots with updates of the account
+    pending: HashMap<Pubkey, usize>,
+}
+
+impl OwnerTracker {
+    pub fn new(max_accounts: usize) -> Self {
+        Self {
+            max_accounts,
+            finalized: HashMap::new(),
+            finalized_slot: None,
+            slots: BTreeMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Owner before the update, `None` if owner is not changed or not known,
+    /// startup accounts only set owners
+    pub fn update_account(&mut self, msg: &MessageAccount) -> Option<Pubkey> {
+        let pubkey = msg.account.pubkey;
+        let owner = msg.account.owner;
+        if msg.is_startup || self.is_finalized(msg.slot) {
+            self.finalized.insert(pubkey, (owner, msg.slot));
+            self.evict();
+            return None;
+        }
+
+        let previous_owner = self.get(&pubkey, msg.slot);
+        let slot_owners = self.slots.entry(msg.slot).or_default();
+        if slot_owners.owners.insert(pubkey, owner).is_none() {
+            *self.pending.entry(pubkey).or_default() += 1;
+        }
+        previous_owner.filter(|previous_owner| *previous_owner != owner)
+    }
+
+    pub fn update_slot(&mut self, msg: &MessageSlot) {
+        if self.is_finalized(msg.slot) {
+            return;
+        }
+        if let Some(parent) = msg.parent {
+            self.slots.entry(msg.slot).or_default().parent = Some(parent);
+        }
+        match msg.status {
+            SlotStatus::Finalized => self.finalize(msg.slot),
+            SlotStatus::Dead => {
+                if let Some(slot_owners) = self.slots.remove(&msg.slot) {
+                    self.release(slot_owners.owners);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn is_finalized(&self, slot: Slot) -> bool {
+        self.finalized_slot
+            .is_some_and(|finalized| slot <= finalized)
+    }
+
+    /// Owner in the slot or in the nearest ancestor
+    fn get(&self, pubkey: &Pubkey, slot: Slot) -> Option<Pubkey> {
+        if self.pending.contains_key(pubkey) {
+            let mut slot = Some(slot);
+            while let Some(slot_owners) = slot.and_then(|slot| self.slots.get(&slot)) {
+                if let Some(owner) = slot_owners.owners.get(pubkey) {
+                    return Some(*owner);
+                }
+                slot = slot_owners.parent;
+            }
+        }
+        self.finalized.get(pubkey).map(|(owner, _slot)| *owner)
+    }
+
+    /// Owners of the finalized slot and its ancestors are applied from the
+    /// oldest slot, all other slots before it are abandoned forks
+    fn finalize(&mut self, slot: Slot) {
+        let mut chain = vec![];
+        let mut current = Some(slot);
+        while let Some((slot, slot_owners)) =
+            current.and_then(|slot| self.slots.remove(&slot).map(|value| (slot, value)))
+        {
+            current = slot_owners.parent;
+            chain.push((slot, slot_owners.owners));
+        }
+        for (slot, owners) in chain.into_iter().rev() {
+            for (pubkey, owner) in owners.iter() {
+                self.finalized.insert(*pubkey, (*owner, slot));
+            }
+            self.release(owners);
+        }
+
+        let slots = self.slots.split_off(&(slot + 1));
+        for (_slot, slot_owners) in mem::replace(&mut self.slots, slots) {
+            self.release(slot_owners.owners);
+        }
+        self.finalized_slot = Some(slot);
+        self.evict();
+    }
+
+    fn release(&mut self, owners: HashMap<Pubkey, Pubkey>) {
+        for pubkey in owners.into_keys() {
+            if let Some(count) = self.pending.get_mut(&pubkey) {
+                *count -= 1;
+                if *count == 0 {
+                    self.pending.remove(&pubkey);
+                }
+            }
+        }
+    }
+
+    /// Remove accounts updated least recently, 10% below the limit, so the map
+    /// is not scanned on every update
+    fn evict(&mut self) {
+        if self.finalized.len() <= self.max_accounts {
+            return;
+        }
+        let count = self.finalized.len() - self.max_accounts / 10 * 9;
+        if count >= self.finalized.len() {
+            self.finalized.clear();
+            return;
+        }
+
+        let mut slots = self
+            .finalized
+            .values()
+            .map(|(_owner, slot)| *slot)
+            .collect::<Vec<_>>();
+        let (below, cutoff, _above) = slots.select_nth_unstable(count);
+        let cutoff = *cutoff;
+        let mut cutoff_count = count - below.iter().filter(|slot| **slot < cutoff).count();
+        self.finalized.retain(|_pubkey, (_owner, slot)| {
+            if *slot < cutoff {
+                false
+            } else if *slot == cutoff && cutoff_count > 0 {
+                cutoff_count -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::OwnerTracker,
+        prost_types::Timestamp,
+        solana_sdk::pubkey::Pubkey,
+        std::sync::Arc,
+        yellowstone_grpc_proto::plugin::message::{
+            MessageAccount, MessageAccountInfo, MessageSlot, SlotStatus,
+        },
+    };
+
+    fn account(pubkey: Pubkey, owner: Pubkey, slot: u64) -> MessageAccount {
+        MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey,
+                lamports: 0,
+                owner,
+                executable: false,
+                rent_epoch: 0,
+                data: vec![],
+                write_version: 0,
+                txn_signature: None,
+            }),
+            slot,
+            is_startup: false,
+            previous_owner: None,
+            created_at: Timestamp::default(),
+        }
+    }
+
+    fn slot(slot: u64, parent: u64, status: SlotStatus) -> MessageSlot {
+        MessageSlot {
+            slot,
+            parent: Some(parent),
+            status,
+            dead_error: None,
+            created_at: Timestamp::default(),
+        }
+    }
+
+    #[test]
+    fn test_owner_changed() {
+        let mut tracker = OwnerTracker::new(100);
+        let pubkey = Pubkey::new_unique();
+        let (owner1, owner2) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        tracker.update_slot(&slot(10, 9, SlotStatus::Processed));
+        assert_eq!(tracker.update_account(&account(pubkey, owner1, 10)), None);
+        tracker.update_slot(&slot(11, 10, SlotStatus::Processed));
+        assert_eq!(tracker.update_account(&account(pubkey, owner1, 11)), None);
+        assert_eq!(
+            tracker.update_account(&account(pubkey, owner2, 11)),
+            Some(owner1)
+        );
+
+        tracker.update_slot(&slot(11, 10, SlotStatus::Finalized));
+        tracker.update_slot(&slot(12, 11, SlotStatus::Processed));
+        assert!(tracker.pending.is_empty());
+        assert_eq!(
+            tracker.update_account(&account(pubkey, owner1, 12)),
+            Some(owner2)
+        );
+    }
+
+    #[test]
+    fn test_owner_changed_forks() {
+        let mut tracker = OwnerTracker::new(100);
+        let pubkey = Pubkey::new_unique();
+        let (owner1, owner2) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        tracker.update_slot(&slot(10, 9, SlotStatus::Processed));
+        tracker.update_account(&account(pubkey, owner1, 10));
+        // owner is changed only on the fork
+        tracker.update_slot(&slot(11, 10, SlotStatus::Processed));
+        tracker.update_account(&account(pubkey, owner2, 11));
+        tracker.update_slot(&slot(12, 10, SlotStatus::Processed));
+        assert_eq!(tracker.update_account(&account(pubkey, owner1, 12)), None);
+
+        // abandoned fork is removed on finalization
+        tracker.update_slot(&slot(12, 10, SlotStatus::Finalized));
+        assert!(tracker.slots.is_empty());
+        assert!(tracker.pending.is_empty());
+        assert_eq!(tracker.finalized.get(&pubkey), Some(&(owner1, 12)));
+
+        // updates of the dead slot are removed
+        tracker.update_slot(&slot(13, 12, SlotStatus::Processed));
+        tracker.update_account(&account(pubkey, owner2, 13));
+        tracker.update_slot(&slot(13, 12, SlotStatus::Dead));
+        tracker.update_slot(&slot(14, 12, SlotStatus::Processed));
+        assert_eq!(tracker.update_account(&account(pubkey, owner1, 14)), None);
+    }
+
+    #[test]
+    fn test_owner_evict() {
+        let mut tracker = OwnerTracker::new(10);
+        let owner = Pubkey::new_unique();
+        let pubkeys = (0..11).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+        for (index, pubkey) in pubkeys.iter().enumerate() {
+            let mut msg = account(*pubkey, owner, index as u64);
+            msg.is_startup = true;
+            tracker.update_account(&msg);
+        }
+
+        // least recently updated accounts are forgotten
+        assert_eq!(tracker.finalized.len(), 9);
+        assert!(!tracker.finalized.contains_key(&pubkeys[0]));
+        assert!(!tracker.finalized.contains_key(&pubkeys[1]));
+        assert!(tracker.finalized.contains_key(&pubkeys[10]));
+    }
+}
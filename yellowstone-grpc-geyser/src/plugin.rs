@@ -2,6 +2,7 @@ use {
     crate::{
         config::Config,
         grpc::GrpcService,
+        log_level::LogLevels,
         metrics::{self, PrometheusService},
     },
     agave_geyser_plugin_interface::geyser_plugin_interface::{
@@ -31,6 +32,7 @@ pub struct PluginInner {
     runtime: Runtime,
     snapshot_channel: Mutex<Option<crossbeam_channel::Sender<Box<Message>>>>,
     snapshot_channel_closed: AtomicBool,
+    snapshot_download: bool,
     grpc_channel: mpsc::UnboundedSender<Message>,
     grpc_shutdown: Arc<Notify>,
     prometheus: PrometheusService,
@@ -68,7 +70,8 @@ impl GeyserPlugin for Plugin {
         let config = Config::load_from_file(config_file)?;
 
         // Setup logger
-        solana_logger::setup_with_default(&config.log.level);
+        let log_levels = Arc::new(LogLevels::new(&config.log.level));
+        log_levels.setup();
 
         // Create inner
         let mut builder = Builder::new_multi_thread();
@@ -86,6 +89,7 @@ impl GeyserPlugin for Plugin {
             .build()
             .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
 
+        let snapshot_download = config.grpc.snapshot_download.is_some();
         let (snapshot_channel, grpc_channel, grpc_shutdown, prometheus) =
             runtime.block_on(async move {
                 let (debug_client_tx, debug_client_rx) = mpsc::unbounded_channel();
@@ -93,6 +97,7 @@ impl GeyserPlugin for Plugin {
                     config.tokio,
                     config.grpc,
                     config.debug_clients_http.then_some(debug_client_tx),
+                    None,
                     is_reload,
                 )
                 .await
@@ -100,6 +105,8 @@ impl GeyserPlugin for Plugin {
                 let prometheus = PrometheusService::new(
                     config.prometheus,
                     config.debug_clients_http.then_some(debug_client_rx),
+                    config.log.http_control.then_some(log_levels),
+                    None,
                 )
                 .await
                 .map_err(|error| GeyserPluginError::Custom(Box::new(error)))?;
@@ -115,6 +122,7 @@ impl GeyserPlugin for Plugin {
             runtime,
             snapshot_channel: Mutex::new(snapshot_channel),
             snapshot_channel_closed: AtomicBool::new(false),
+            snapshot_download,
             grpc_channel,
             grpc_shutdown,
             prometheus,
@@ -149,10 +157,12 @@ impl GeyserPlugin for Plugin {
                 ReplicaAccountInfoVersions::V0_0_3(info) => info,
             };
 
+            let message = Message::Account(MessageAccount::from_geyser(account, slot, is_startup));
             if is_startup {
+                if inner.snapshot_download {
+                    inner.send_message(message.clone());
+                }
                 if let Some(channel) = inner.snapshot_channel.lock().unwrap().as_ref() {
-                    let message =
-                        Message::Account(MessageAccount::from_geyser(account, slot, is_startup));
                     match channel.send(Box::new(message)) {
                         Ok(()) => metrics::message_queue_size_inc(),
                         Err(_) => {
@@ -165,8 +175,6 @@ impl GeyserPlugin for Plugin {
                     }
                 }
             } else {
-                let message =
-                    Message::Account(MessageAccount::from_geyser(account, slot, is_startup));
                 inner.send_message(message);
             }
 
@@ -259,7 +267,10 @@ impl GeyserPlugin for Plugin {
     }
 
     fn account_data_snapshot_notifications_enabled(&self) -> bool {
-        false
+        // startup accounts are required for the accounts state of `SnapshotDownload`
+        self.inner
+            .as_ref()
+            .is_some_and(|inner| inner.snapshot_download)
     }
 
     fn transaction_notifications_enabled(&self) -> bool {
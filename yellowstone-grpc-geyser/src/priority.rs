@@ -0,0 +1,532 @@
+use {
+    crate::config::{ConfigGrpcPriority, ConfigGrpcPriorityClass},
+    futures::{stream::Stream, task::AtomicWaker},
+    std::{
+        collections::{BTreeMap, BTreeSet, VecDeque},
+        pin::Pin,
+        sync::{Arc, Mutex, MutexGuard},
+        task::{Context, Poll},
+    },
+    tokio::sync::{
+        mpsc::error::{SendError, TrySendError},
+        Notify,
+    },
+    tonic::Result as TonicResult,
+    yellowstone_grpc_proto::plugin::filter::message::{FilteredUpdate, FilteredUpdateOneof},
+};
+
+pub type ClientItem = TonicResult<FilteredUpdate>;
+
+#[derive(Debug)]
+struct Entry {
+    sequence: u64,
+    /// Slot of data update, used to keep slot statuses behind the data
+    slot: Option<u64>,
+    item: ClientItem,
+}
+
+#[derive(Debug)]
+struct State {
+    /// Without classes only one queue, otherwise the control queue (pings,
+    /// pongs, errors, acknowledgements of filters), queues of classes and the
+    /// queue of other updates
+    queues: Vec<VecDeque<Entry>>,
+    /// Number of queued updates, the control queue is not counted
+    len: usize,
+    sequence: u64,
+    /// Sequences of queued updates by slot, slot statuses are not included
+    slots: BTreeMap<u64, BTreeSet<u64>>,
+    senders: usize,
+    is_closed: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    classes: Vec<ConfigGrpcPriorityClass>,
+    capacity: usize,
+    rx_waker: AtomicWaker,
+    tx_notify: Notify,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("poisoned priority queues")
+    }
+
+    const fn get_class(message: &FilteredUpdateOneof) -> Option<ConfigGrpcPriorityClass> {
+        Some(match message {
+            FilteredUpdateOneof::Slot(_) => ConfigGrpcPriorityClass::Slot,
+            FilteredUpdateOneof::BlockMeta(_) => ConfigGrpcPriorityClass::BlockMeta,
+            FilteredUpdateOneof::Transaction(_) => ConfigGrpcPriorityClass::Transaction,
+            FilteredUpdateOneof::TransactionStatus(_) => ConfigGrpcPriorityClass::TransactionStatus,
+            FilteredUpdateOneof::Account(_) => ConfigGrpcPriorityClass::Account,
+            FilteredUpdateOneof::Entry(_) => ConfigGrpcPriorityClass::Entry,
+            FilteredUpdateOneof::Block(_) | FilteredUpdateOneof::SlotBatch(_) => {
+                ConfigGrpcPriorityClass::Block
+            }
+            _ => return None,
+        })
+    }
+
+    const fn is_control(&self, index: usize) -> bool {
+        !self.classes.is_empty() && index == 0
+    }
+
+    fn get_queue(&self, item: &ClientItem) -> usize {
+        if self.classes.is_empty() {
+            return 0;
+        }
+        let Ok(update) = item else {
+            return 0;
+        };
+        match &update.message {
+            FilteredUpdateOneof::Ping
+            | FilteredUpdateOneof::Pong(_)
+            | FilteredUpdateOneof::FilterApplied(_) => 0,
+            message => Self::get_class(message)
+                .and_then(|class| self.classes.iter().position(|value| *value == class))
+                .map_or(self.classes.len() + 1, |index| index + 1),
+        }
+    }
+
+    /// Updates are stamped with the per-stream sequence when queued, so
+    /// updates which were not queued are never counted
+    fn try_push(&self, mut item: ClientItem) -> Result<(), TrySendError<ClientItem>> {
+        let mut state = self.lock();
+        if state.is_closed {
+            return Err(TrySendError::Closed(item));
+        }
+
+        let index = self.get_queue(&item);
+        let is_control = self.is_control(index);
+        let len = if is_control {
+            state.queues[index].len()
+        } else {
+            state.len
+        };
+        if len >= self.capacity {
+            return Err(TrySendError::Full(item));
+        }
+
+        state.sequence += 1;
+        let sequence = state.sequence;
+        if let Ok(update) = &mut item {
+            update.sequence = sequence;
+        }
+        let slot = match &item {
+            Ok(update) if !self.classes.is_empty() && !is_control => match &update.message {
+                FilteredUpdateOneof::Slot(_) => None,
+                message => message.get_slot(),
+            },
+            _ => None,
+        };
+        if let Some(slot) = slot {
+            state.slots.entry(slot).or_default().insert(sequence);
+        }
+        if !is_control {
+            state.len += 1;
+        }
+        state.queues[index].push_back(Entry {
+            sequence,
+            slot,
+            item,
+        });
+        drop(state);
+
+        self.rx_waker.wake();
+        Ok(())
+    }
+
+    /// Acknowledgement of the filter is sent only after updates queued before
+    /// it, slot status only after queued updates of the same or previous slots
+    fn is_ready(&self, state: &State, entry: &Entry) -> bool {
+        if self.classes.is_empty() {
+            return true;
+        }
+        match &entry.item {
+            Ok(update) => match &update.message {
+                FilteredUpdateOneof::FilterApplied(_) => state.queues[1..].iter().all(|queue| {
+                    queue
+                        .front()
+                        .is_none_or(|head| head.sequence > entry.sequence)
+                }),
+                FilteredUpdateOneof::Slot(message) => {
+                    state
+                        .slots
+                        .range(..=message.slot)
+                        .all(|(_slot, sequences)| {
+                            sequences
+                                .first()
+                                .is_none_or(|sequence| *sequence > entry.sequence)
+                        })
+                }
+                _ => true,
+            },
+            Err(_) => true,
+        }
+    }
+
+    /// Queued update with the lowest sequence is always ready, so queues never
+    /// stuck
+    fn pop(&self, state: &mut State) -> Option<ClientItem> {
+        let index = (0..state.queues.len()).find(|index| {
+            state.queues[*index]
+                .front()
+                .is_some_and(|entry| self.is_ready(&*state, entry))
+        })?;
+        let entry = state.queues[index].pop_front()?;
+        if !self.is_control(index) {
+            state.len -= 1;
+        }
+        if let Some(slot) = entry.slot {
+            if let Some(sequences) = state.slots.get_mut(&slot) {
+                sequences.remove(&entry.sequence);
+                if sequences.is_empty() {
+                    state.slots.remove(&slot);
+                }
+            }
+        }
+        Some(entry.item)
+    }
+}
+
+/// Channel of the client. With `priority` config updates are queued by class
+/// and sent in order of classes, pings, pongs, errors and acknowledgements of
+/// filters are sent before all updates. Order is kept within one class, while
+/// acknowledgement of the filter is never sent before updates queued before it
+/// and slot status before queued updates of the slot. Capacity is shared by
+/// queues of all classes. Sequence is stamped in the order of queuing, so it's
+/// increasing in the stream only without classes.
+pub fn channel(
+    config: Option<&ConfigGrpcPriority>,
+    capacity: usize,
+) -> (ClientSender, ClientReceiver) {
+    let classes = config
+        .map(|config| config.classes.clone())
+        .unwrap_or_default();
+    let queues = if classes.is_empty() {
+        1
+    } else {
+        classes.len() + 2
+    };
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queues: (0..queues).map(|_| VecDeque::new()).collect(),
+            len: 0,
+            sequence: 0,
+            slots: BTreeMap::new(),
+            senders: 1,
+            is_closed: false,
+        }),
+        classes,
+        capacity: config
+            .and_then(|config| config.capacity)
+            .unwrap_or(capacity),
+        rx_waker: AtomicWaker::new(),
+        tx_notify: Notify::new(),
+    });
+    (
+        ClientSender {
+            shared: Arc::clone(&shared),
+        },
+        ClientReceiver { shared },
+    )
+}
+
+#[derive(Debug)]
+pub struct ClientSender {
+    shared: Arc<Shared>,
+}
+
+impl Clone for ClientSender {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for ClientSender {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.shared.rx_waker.wake();
+        }
+    }
+}
+
+impl ClientSender {
+    pub fn try_send(&self, item: ClientItem) -> Result<(), TrySendError<ClientItem>> {
+        self.shared.try_push(item)
+    }
+
+    pub async fn send(&self, mut item: ClientItem) -> Result<(), SendError<ClientItem>> {
+        loop {
+            let notified = self.shared.tx_notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            match self.shared.try_push(item) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Full(value)) => item = value,
+                Err(TrySendError::Closed(value)) => return Err(SendError(value)),
+            }
+            notified.await;
+        }
+    }
+
+    /// Wait until queues have capacity for at least one update
+    pub async fn ready(&self) -> Result<(), SendError<()>> {
+        loop {
+            let notified = self.shared.tx_notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if !self.has_capacity()? {
+                notified.await;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn has_capacity(&self) -> Result<bool, SendError<()>> {
+        let state = self.shared.lock();
+        if state.is_closed {
+            Err(SendError(()))
+        } else {
+            Ok(state.len < self.shared.capacity)
+        }
+    }
+
+    /// Number of queued updates, including pings, pongs and errors
+    pub fn queued_len(&self) -> usize {
+        self.shared.lock().queues.iter().map(VecDeque::len).sum()
+    }
+}
+
+#[derive(Debug)]
+pub struct ClientReceiver {
+    shared: Arc<Shared>,
+}
+
+impl Drop for ClientReceiver {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.is_closed = true;
+        state.queues.iter_mut().for_each(VecDeque::clear);
+        state.slots.clear();
+        state.len = 0;
+        drop(state);
+        self.shared.tx_notify.notify_waiters();
+    }
+}
+
+impl Stream for ClientReceiver {
+    type Item = ClientItem;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.shared.rx_waker.register(cx.waker());
+        let mut state = self.shared.lock();
+        if let Some(item) = self.shared.pop(&mut state) {
+            drop(state);
+            self.shared.tx_notify.notify_waiters();
+            return Poll::Ready(Some(item));
+        }
+        if state.senders == 0 {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{channel, ClientItem, ClientReceiver},
+        crate::config::{ConfigGrpcPriority, ConfigGrpcPriorityClass},
+        futures::stream::StreamExt,
+        prost_types::Timestamp,
+        std::{sync::Arc, time::SystemTime},
+        tokio::sync::mpsc::error::TrySendError,
+        tonic::Status,
+        yellowstone_grpc_proto::plugin::{
+            filter::message::{FilteredUpdate, FilteredUpdateOneof},
+            message::{MessageAccount, MessageAccountInfo, MessageSlot, SlotStatus},
+        },
+    };
+
+    fn config(classes: Vec<ConfigGrpcPriorityClass>) -> ConfigGrpcPriority {
+        ConfigGrpcPriority {
+            classes,
+            capacity: None,
+        }
+    }
+
+    fn slot(slot: u64) -> ClientItem {
+        Ok(FilteredUpdate::new_empty(FilteredUpdateOneof::slot(
+            MessageSlot {
+                slot,
+                parent: None,
+                status: SlotStatus::Processed,
+                dead_error: None,
+                created_at: Timestamp::from(SystemTime::now()),
+            },
+        )))
+    }
+
+    fn account(slot: u64) -> ClientItem {
+        let message = MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey: Default::default(),
+                lamports: 0,
+                owner: Default::default(),
+                executable: false,
+                rent_epoch: 0,
+                data: vec![],
+                write_version: 0,
+                txn_signature: None,
+            }),
+            slot,
+            is_startup: false,
+            previous_owner: None,
+            created_at: Timestamp::from(SystemTime::now()),
+        };
+        Ok(FilteredUpdate::new_empty(FilteredUpdateOneof::account(
+            &message,
+            Default::default(),
+        )))
+    }
+
+    fn filter_applied() -> ClientItem {
+        Ok(FilteredUpdate::new_empty(
+            FilteredUpdateOneof::filter_applied(0),
+        ))
+    }
+
+    fn describe(item: &ClientItem) -> String {
+        match item {
+            Ok(update) => match &update.message {
+                FilteredUpdateOneof::Slot(message) => format!("slot {}", message.slot),
+                FilteredUpdateOneof::Account(message) => format!("account {}", message.slot),
+                FilteredUpdateOneof::Ping => "ping".to_owned(),
+                FilteredUpdateOneof::FilterApplied(_) => "ack".to_owned(),
+                _ => "other".to_owned(),
+            },
+            Err(_) => "error".to_owned(),
+        }
+    }
+
+    async fn collect(rx: ClientReceiver) -> Vec<String> {
+        rx.map(|item| describe(&item)).collect().await
+    }
+
+    #[tokio::test]
+    async fn test_fifo_without_classes() {
+        let (tx, rx) = channel(None, 10);
+        tx.try_send(account(1)).unwrap();
+        tx.try_send(Ok(FilteredUpdate::new_empty(FilteredUpdateOneof::ping())))
+            .unwrap();
+        tx.try_send(slot(1)).unwrap();
+        drop(tx);
+        assert_eq!(collect(rx).await, ["account 1", "ping", "slot 1"]);
+    }
+
+    #[tokio::test]
+    async fn test_control_first() {
+        let config = config(vec![ConfigGrpcPriorityClass::Slot]);
+        let (tx, rx) = channel(Some(&config), 10);
+        tx.try_send(account(1)).unwrap();
+        tx.try_send(Ok(FilteredUpdate::new_empty(FilteredUpdateOneof::ping())))
+            .unwrap();
+        tx.try_send(Err(Status::internal("error"))).unwrap();
+        drop(tx);
+        assert_eq!(collect(rx).await, ["ping", "error", "account 1"]);
+    }
+
+    #[tokio::test]
+    async fn test_slot_behind_own_data() {
+        let config = config(vec![ConfigGrpcPriorityClass::Slot]);
+        let (tx, rx) = channel(Some(&config), 10);
+        tx.try_send(account(2)).unwrap();
+        tx.try_send(slot(1)).unwrap();
+        tx.try_send(slot(2)).unwrap();
+        tx.try_send(account(2)).unwrap();
+        drop(tx);
+        // status of slot 2 overtakes only the account queued after it
+        assert_eq!(
+            collect(rx).await,
+            ["slot 1", "account 2", "slot 2", "account 2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_filter_applied_behind_queued() {
+        let config = config(vec![ConfigGrpcPriorityClass::Slot]);
+        let (tx, rx) = channel(Some(&config), 10);
+        tx.try_send(account(2)).unwrap();
+        tx.try_send(filter_applied()).unwrap();
+        tx.try_send(Ok(FilteredUpdate::new_empty(FilteredUpdateOneof::ping())))
+            .unwrap();
+        tx.try_send(slot(1)).unwrap();
+        drop(tx);
+        // updates of the old filter are sent before the acknowledgement
+        assert_eq!(collect(rx).await, ["slot 1", "account 2", "ack", "ping"]);
+    }
+
+    #[tokio::test]
+    async fn test_shared_capacity() {
+        let config = config(vec![
+            ConfigGrpcPriorityClass::Slot,
+            ConfigGrpcPriorityClass::Account,
+        ]);
+        let (tx, mut rx) = channel(Some(&config), 2);
+        tx.try_send(account(1)).unwrap();
+        tx.try_send(slot(1)).unwrap();
+        assert!(matches!(
+            tx.try_send(account(2)),
+            Err(TrySendError::Full(_))
+        ));
+        assert!(!tx.has_capacity().unwrap());
+        // control messages have own capacity
+        tx.try_send(filter_applied()).unwrap();
+        assert_eq!(tx.queued_len(), 3);
+
+        assert_eq!(describe(&rx.next().await.unwrap()), "account 1");
+        assert!(tx.has_capacity().unwrap());
+        tx.send(account(2)).await.unwrap();
+
+        drop(rx);
+        assert!(matches!(
+            tx.try_send(account(3)),
+            Err(TrySendError::Closed(_))
+        ));
+        assert!(tx.ready().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sequence_on_enqueue() {
+        let sequences = |rx: ClientReceiver| {
+            rx.map(|item| item.map(|update| update.sequence).unwrap_or_default())
+                .collect::<Vec<_>>()
+        };
+
+        let (tx, rx) = channel(None, 2);
+        tx.try_send(account(1)).unwrap();
+        tx.try_send(slot(1)).unwrap();
+        // not queued update is not counted
+        assert!(tx.try_send(account(2)).is_err());
+        drop(tx);
+        assert_eq!(sequences(rx).await, [1, 2]);
+
+        let config = config(vec![ConfigGrpcPriorityClass::Slot]);
+        let (tx, rx) = channel(Some(&config), 10);
+        tx.try_send(account(2)).unwrap();
+        tx.try_send(slot(1)).unwrap();
+        drop(tx);
+        assert_eq!(sequences(rx).await, [2, 1]);
+    }
+}
@@ -0,0 +1,305 @@
+use {
+    crate::config::ConfigProfiling,
+    std::{
+        str::FromStr,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileFormat {
+    /// `pprof` protobuf, can be opened by `go tool pprof`
+    Pprof,
+    /// SVG flamegraph
+    Flamegraph,
+}
+
+impl FromStr for ProfileFormat {
+    type Err = ProfileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pprof" => Ok(Self::Pprof),
+            "flamegraph" => Ok(Self::Flamegraph),
+            _ => Err(ProfileError::InvalidFormat(s.to_owned())),
+        }
+    }
+}
+
+impl ProfileFormat {
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Self::Pprof => "application/octet-stream",
+            Self::Flamegraph => "image/svg+xml",
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProfileError {
+    #[error("invalid format: {0}, expected `pprof` or `flamegraph`")]
+    InvalidFormat(String),
+    #[error("invalid duration: {0}, expected seconds")]
+    InvalidDuration(String),
+    #[error("profile is already running")]
+    Busy,
+    #[error("plugin is built without `profiling` feature")]
+    Unsupported,
+    #[error("heap profile is not available: {0}")]
+    HeapUnavailable(&'static str),
+    #[error("failed to build profile: {0}")]
+    Profiler(String),
+}
+
+/// Resets `running` on drop, including panic of the profiling task
+#[derive(Debug)]
+struct RunningGuard(Arc<AtomicBool>);
+
+impl RunningGuard {
+    fn acquire(running: &Arc<AtomicBool>) -> Result<Self, ProfileError> {
+        if running.swap(true, Ordering::SeqCst) {
+            return Err(ProfileError::Busy);
+        }
+        Ok(Self(Arc::clone(running)))
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// CPU profiles of the process and heap profiles of the validator jemalloc,
+/// only one profile can run at a time
+#[derive(Debug)]
+pub struct Profiler {
+    config: ConfigProfiling,
+    running: Arc<AtomicBool>,
+}
+
+impl Profiler {
+    const DURATION_DEFAULT: Duration = Duration::from_secs(30);
+
+    pub fn new(config: ConfigProfiling) -> Self {
+        Self {
+            config,
+            running: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Empty token is never authorized, even if config check is bypassed
+    pub fn is_authorized(&self, token: Option<&str>) -> bool {
+        !self.config.admin_token.is_empty() && token == Some(self.config.admin_token.as_str())
+    }
+
+    /// Requested duration in seconds limited by `max_duration`, 30s if not set
+    pub fn duration(&self, seconds: Option<&str>) -> Result<Duration, ProfileError> {
+        let duration = match seconds {
+            Some(seconds) => seconds
+                .parse()
+                .map(Duration::from_secs)
+                .map_err(|_error| ProfileError::InvalidDuration(seconds.to_owned()))?,
+            None => Self::DURATION_DEFAULT,
+        };
+        Ok(duration.min(self.config.max_duration))
+    }
+
+    /// Sample CPU for `duration`, sampling is done on the blocking pool
+    pub async fn profile(
+        &self,
+        duration: Duration,
+        format: ProfileFormat,
+    ) -> Result<Vec<u8>, ProfileError> {
+        let guard = RunningGuard::acquire(&self.running)?;
+        let frequency = self.config.frequency;
+        let result = tokio::task::spawn_blocking(move || {
+            let _guard = guard;
+            Self::sample(frequency, duration, format)
+        })
+        .await;
+        result.map_err(|error| ProfileError::Profiler(error.to_string()))?
+    }
+
+    /// Dump heap profile of the validator jemalloc (`heap_v2` format of
+    /// `jeprof`), requires validator started with `prof:true` in jemalloc
+    /// options
+    pub async fn heap(&self) -> Result<Vec<u8>, ProfileError> {
+        let guard = RunningGuard::acquire(&self.running)?;
+        let result = tokio::task::spawn_blocking(move || {
+            let _guard = guard;
+            Self::dump_heap()
+        })
+        .await;
+        result.map_err(|error| ProfileError::Profiler(error.to_string()))?
+    }
+
+    #[cfg(feature = "jemalloc")]
+    fn dump_heap() -> Result<Vec<u8>, ProfileError> {
+        use std::{ffi::CString, fs, mem::size_of, ptr};
+
+        let mallctl = crate::metrics::jemalloc_mallctl().ok_or(ProfileError::HeapUnavailable(
+            "jemalloc of the validator is not found",
+        ))?;
+
+        let mut enabled = false;
+        let mut len = size_of::<bool>();
+        // SAFETY: `opt.prof` is `bool`
+        let code = unsafe {
+            mallctl(
+                c"opt.prof".as_ptr(),
+                ptr::addr_of_mut!(enabled).cast(),
+                &mut len,
+                ptr::null_mut(),
+                0,
+            )
+        };
+        if code != 0 || !enabled {
+            return Err(ProfileError::HeapUnavailable(
+                "jemalloc of the validator is started without `prof:true`",
+            ));
+        }
+
+        let path =
+            std::env::temp_dir().join(format!("yellowstone-grpc-heap-{}.prof", std::process::id()));
+        let filename = CString::new(path.to_string_lossy().as_bytes())
+            .map_err(|error| ProfileError::Profiler(error.to_string()))?;
+        let mut filename_ptr = filename.as_ptr();
+        // SAFETY: `prof.dump` takes `const char *`, string outlives the call
+        let code = unsafe {
+            mallctl(
+                c"prof.dump".as_ptr(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::addr_of_mut!(filename_ptr).cast(),
+                size_of::<*const std::ffi::c_char>(),
+            )
+        };
+        if code != 0 {
+            return Err(ProfileError::Profiler(format!(
+                "failed to dump heap profile: {code}"
+            )));
+        }
+        let body = fs::read(&path).map_err(|error| ProfileError::Profiler(error.to_string()));
+        let _ = fs::remove_file(&path);
+        body
+    }
+
+    #[cfg(not(feature = "jemalloc"))]
+    const fn dump_heap() -> Result<Vec<u8>, ProfileError> {
+        Err(ProfileError::HeapUnavailable(
+            "plugin is built without `jemalloc` feature",
+        ))
+    }
+
+    #[cfg(feature = "profiling")]
+    fn sample(
+        frequency: i32,
+        duration: Duration,
+        format: ProfileFormat,
+    ) -> Result<Vec<u8>, ProfileError> {
+        use pprof::protos::Message;
+
+        let map_err = |error: pprof::Error| ProfileError::Profiler(error.to_string());
+        let guard = pprof::ProfilerGuardBuilder::default()
+            .frequency(frequency)
+            .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+            .build()
+            .map_err(map_err)?;
+        std::thread::sleep(duration);
+        let report = guard.report().build().map_err(map_err)?;
+
+        let mut body = Vec::new();
+        match format {
+            ProfileFormat::Pprof => {
+                let profile = report.pprof().map_err(map_err)?;
+                profile
+                    .encode(&mut body)
+                    .map_err(|error| ProfileError::Profiler(error.to_string()))?;
+            }
+            ProfileFormat::Flamegraph => report.flamegraph(&mut body).map_err(map_err)?,
+        }
+        Ok(body)
+    }
+
+    #[cfg(not(feature = "profiling"))]
+    const fn sample(
+        _frequency: i32,
+        _duration: Duration,
+        _format: ProfileFormat,
+    ) -> Result<Vec<u8>, ProfileError> {
+        Err(ProfileError::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{ProfileError, Profiler, RunningGuard},
+        crate::config::ConfigProfiling,
+        std::{sync::atomic::Ordering, time::Duration},
+    };
+
+    fn create_profiler(admin_token: &str) -> Profiler {
+        Profiler::new(ConfigProfiling {
+            admin_token: admin_token.to_owned(),
+            max_duration: Duration::from_secs(120),
+            frequency: 99,
+        })
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        let profiler = create_profiler("secret");
+        assert!(profiler.is_authorized(Some("secret")));
+        assert!(!profiler.is_authorized(Some("secret2")));
+        assert!(!profiler.is_authorized(Some("")));
+        assert!(!profiler.is_authorized(None));
+
+        let profiler = create_profiler("");
+        assert!(!profiler.is_authorized(Some("")));
+        assert!(!profiler.is_authorized(None));
+    }
+
+    #[test]
+    fn test_duration() {
+        let profiler = create_profiler("secret");
+        assert_eq!(profiler.duration(None).unwrap(), Duration::from_secs(30));
+        assert_eq!(
+            profiler.duration(Some("5")).unwrap(),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            profiler.duration(Some("600")).unwrap(),
+            Duration::from_secs(120)
+        );
+        for seconds in ["abc", "", "-1", "1.5"] {
+            assert!(matches!(
+                profiler.duration(Some(seconds)),
+                Err(ProfileError::InvalidDuration(value)) if value == seconds
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_running_guard_panic() {
+        let profiler = create_profiler("secret");
+        let guard = RunningGuard::acquire(&profiler.running).unwrap();
+        assert!(matches!(
+            RunningGuard::acquire(&profiler.running),
+            Err(ProfileError::Busy)
+        ));
+        let result = tokio::task::spawn_blocking(move || {
+            let _guard = guard;
+            panic!("profiler failed");
+        })
+        .await;
+        assert!(result.is_err());
+        assert!(!profiler.running.load(Ordering::SeqCst));
+        assert!(RunningGuard::acquire(&profiler.running).is_ok());
+    }
+}
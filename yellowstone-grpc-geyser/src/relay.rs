@@ -0,0 +1,337 @@
+use {
+    crate::grpc::SubscriptionEvent,
+    anyhow::Context,
+    serde_json::{json, Value},
+    std::{
+        collections::{BTreeMap, HashMap, VecDeque},
+        fs, io,
+        path::PathBuf,
+        sync::Mutex,
+    },
+    yellowstone_grpc_client::multi::SlotGapReport,
+    yellowstone_grpc_proto::geyser::SubscribeRequest,
+};
+
+/// Completeness of slots left the deduplication window of the relay, by
+/// upstream, served on `/gap_reports` of the prometheus address. Only the
+/// latest `max_reports` are kept.
+#[derive(Debug)]
+pub struct GapReports {
+    endpoints: Vec<String>,
+    max_reports: usize,
+    reports: Mutex<VecDeque<SlotGapReport>>,
+}
+
+impl GapReports {
+    pub fn new(endpoints: Vec<String>, max_reports: usize) -> Self {
+        Self {
+            endpoints,
+            max_reports,
+            reports: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn push(&self, report: SlotGapReport) {
+        let mut reports = self.reports.lock().expect("unpoisoned");
+        while reports.len() >= self.max_reports.max(1) {
+            reports.pop_front();
+        }
+        reports.push_back(report);
+    }
+
+    /// Reports of slots starting from `from_slot`, only slots missed by some
+    /// upstream with `incomplete`
+    pub fn to_json(&self, from_slot: Option<u64>, incomplete: bool) -> Value {
+        let reports = self.reports.lock().expect("unpoisoned");
+        reports
+            .iter()
+            .filter(|report| from_slot.is_none_or(|slot| report.slot >= slot))
+            .filter(|report| !incomplete || !report.is_complete())
+            .map(|report| {
+                let upstreams = self
+                    .endpoints
+                    .iter()
+                    .enumerate()
+                    .map(|(index, endpoint)| {
+                        json!({
+                            "endpoint": endpoint,
+                            "received": report.received.get(index).cloned().unwrap_or_default(),
+                            "missing": report.missing(index),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                json!({
+                    "slot": report.slot,
+                    "complete": report.is_complete(),
+                    "delivered": report.delivered,
+                    "upstreams": upstreams,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Filters of relay clients, saved to the file so the upstream subscription
+/// after restart covers clients resuming with `from_slot`. Saved filters are
+/// subscribed until the same filters are received from a client or they are
+/// expired.
+#[derive(Debug)]
+pub struct SubscriptionRegistry {
+    path: PathBuf,
+    /// Filters of connected clients by subscription id
+    clients: BTreeMap<usize, SubscribeRequest>,
+    /// Filters loaded from the file and not resubscribed yet
+    restored: Vec<SubscribeRequest>,
+}
+
+impl SubscriptionRegistry {
+    const FILTER_PREFIX: &'static str = "_relay_client";
+
+    /// Registry with filters saved before restart, empty if file not exists
+    pub fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let restored = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .with_context(|| format!("invalid relay subscriptions {path:?}"))?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => vec![],
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed to read relay subscriptions {path:?}"))
+            }
+        };
+        Ok(Self {
+            path,
+            clients: BTreeMap::new(),
+            restored,
+        })
+    }
+
+    /// Replace the file with unique filters of the registry
+    pub fn save(&self) -> anyhow::Result<()> {
+        let data = serde_json::to_vec(&self.filters())?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, data).with_context(|| format!("failed to write {tmp:?}"))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to rename {tmp:?} to {:?}", self.path))
+    }
+
+    /// Number of saved filters not resubscribed yet
+    pub fn restored(&self) -> usize {
+        self.restored.len()
+    }
+
+    pub fn apply(&mut self, event: SubscriptionEvent) {
+        match event {
+            SubscriptionEvent::Update { id, request } => {
+                let filters = Self::upstream_filters(request);
+                if let Some(index) = self.restored.iter().position(|saved| *saved == filters) {
+                    self.restored.remove(index);
+                }
+                self.clients.insert(id, filters);
+            }
+            SubscriptionEvent::Removed { id } => {
+                self.clients.remove(&id);
+            }
+        }
+    }
+
+    /// Forget saved filters of clients which did not resubscribe
+    pub fn expire_restored(&mut self) {
+        self.restored.clear();
+    }
+
+    /// Filters of the client subscribed upstream: blocks are reconstructed by
+    /// the relay, slots are always subscribed, data slices and commitment are
+    /// applied by the relay
+    fn upstream_filters(request: SubscribeRequest) -> SubscribeRequest {
+        SubscribeRequest {
+            accounts: request.accounts,
+            transactions: request.transactions,
+            transactions_status: request.transactions_status,
+            blocks_meta: request.blocks_meta,
+            entry: request.entry,
+            ..Default::default()
+        }
+    }
+
+    /// Saved filters followed by filters of clients, identical filters once
+    fn filters(&self) -> Vec<&SubscribeRequest> {
+        let mut filters = Vec::<&SubscribeRequest>::new();
+        for request in self.restored.iter().chain(self.clients.values()) {
+            if !filters.contains(&request) {
+                filters.push(request);
+            }
+        }
+        filters
+    }
+
+    /// `base` request extended with filters of the registry
+    pub fn upstream_request(&self, base: &SubscribeRequest) -> SubscribeRequest {
+        fn extend<T: Clone>(
+            target: &mut HashMap<String, T>,
+            source: &HashMap<String, T>,
+            index: usize,
+        ) {
+            target.extend(source.iter().map(|(name, filter)| {
+                let name = format!("{}{index}_{name}", SubscriptionRegistry::FILTER_PREFIX);
+                (name, filter.clone())
+            }));
+        }
+
+        let mut request = base.clone();
+        for (index, filters) in self.filters().into_iter().enumerate() {
+            extend(&mut request.accounts, &filters.accounts, index);
+            extend(&mut request.transactions, &filters.transactions, index);
+            extend(
+                &mut request.transactions_status,
+                &filters.transactions_status,
+                index,
+            );
+            extend(&mut request.blocks_meta, &filters.blocks_meta, index);
+            extend(&mut request.entry, &filters.entry, index);
+        }
+        request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{GapReports, SubscriptionRegistry},
+        crate::grpc::SubscriptionEvent,
+        serde_json::json,
+        std::{collections::BTreeMap, env, fs, process},
+        yellowstone_grpc_client::multi::SlotGapReport,
+        yellowstone_grpc_proto::geyser::{
+            CommitmentLevel, SubscribeRequest, SubscribeRequestAccountsDataSlice,
+            SubscribeRequestFilterAccounts, SubscribeRequestFilterBlocksMeta,
+            SubscribeRequestFilterTransactions,
+        },
+    };
+
+    fn report(slot: u64, received: [usize; 2]) -> SlotGapReport {
+        SlotGapReport {
+            slot,
+            delivered: BTreeMap::from([("transactions", 2)]),
+            received: received
+                .into_iter()
+                .map(|count| BTreeMap::from([("transactions", count)]))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_gap_reports() {
+        let reports = GapReports::new(vec!["a".to_owned(), "b".to_owned()], 2);
+        reports.push(report(10, [2, 2]));
+        reports.push(report(11, [2, 1]));
+        reports.push(report(12, [2, 2]));
+
+        // oldest report is dropped
+        let slots = |value: serde_json::Value| {
+            value
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|report| report["slot"].as_u64().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(slots(reports.to_json(None, false)), [11, 12]);
+        assert_eq!(slots(reports.to_json(Some(12), false)), [12]);
+
+        let incomplete = reports.to_json(None, true);
+        assert_eq!(slots(incomplete.clone()), [11]);
+        assert_eq!(incomplete[0]["complete"], json!(false));
+        assert_eq!(
+            incomplete[0]["upstreams"][1],
+            json!({
+                "endpoint": "b",
+                "received": {"transactions": 1},
+                "missing": {"transactions": 1},
+            })
+        );
+        assert_eq!(incomplete[0]["upstreams"][0]["missing"], json!({}));
+    }
+
+    fn create_request(owner: &str, vote: bool) -> SubscribeRequest {
+        SubscribeRequest {
+            accounts: [(
+                "client".to_owned(),
+                SubscribeRequestFilterAccounts {
+                    owner: vec![owner.to_owned()],
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            transactions: [(
+                "client".to_owned(),
+                SubscribeRequestFilterTransactions {
+                    vote: Some(vote),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            commitment: Some(CommitmentLevel::Finalized as i32),
+            accounts_data_slice: vec![SubscribeRequestAccountsDataSlice {
+                offset: 0,
+                length: 32,
+            }],
+            from_slot: Some(100),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_subscription_registry_restart() {
+        let path = env::temp_dir().join(format!(
+            "yellowstone-grpc-relay-subscriptions-{}.json",
+            process::id()
+        ));
+        let base = SubscribeRequest {
+            blocks_meta: [("all".to_owned(), SubscribeRequestFilterBlocksMeta {})].into(),
+            ..Default::default()
+        };
+
+        let mut registry = SubscriptionRegistry::load(path.clone()).unwrap();
+        assert_eq!(registry.upstream_request(&base), base);
+        for (id, request) in [
+            (0, create_request("owner1", false)),
+            (1, create_request("owner2", true)),
+            (2, create_request("owner1", false)),
+        ] {
+            registry.apply(SubscriptionEvent::Update { id, request });
+        }
+        let request = registry.upstream_request(&base);
+        // identical filters are subscribed once, relay applies data slices and commitment
+        assert_eq!(request.accounts.len(), 2);
+        assert_eq!(request.transactions.len(), 2);
+        assert_eq!(request.blocks_meta, base.blocks_meta);
+        assert_eq!(request.commitment, None);
+        assert!(request.accounts_data_slice.is_empty());
+        assert_eq!(request.from_slot, None);
+        registry.save().unwrap();
+
+        // same upstream request after restart
+        let mut registry = SubscriptionRegistry::load(path.clone()).unwrap();
+        assert_eq!(registry.restored(), 2);
+        assert_eq!(registry.upstream_request(&base), request);
+
+        // saved filters are replaced by resubscribed clients
+        registry.apply(SubscriptionEvent::Update {
+            id: 0,
+            request: create_request("owner2", true),
+        });
+        assert_eq!(registry.restored(), 1);
+        assert_eq!(registry.upstream_request(&base).accounts.len(), 2);
+        registry.expire_restored();
+        let request = registry.upstream_request(&base);
+        assert_eq!(request.accounts.len(), 1);
+        assert_eq!(
+            request.accounts.values().next().unwrap().owner,
+            vec!["owner2".to_owned()]
+        );
+
+        registry.apply(SubscriptionEvent::Removed { id: 0 });
+        assert_eq!(registry.upstream_request(&base), base);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,266 @@
+use {
+    crate::config::ConfigGrpcReplayPersist,
+    anyhow::Context,
+    log::{info, warn},
+    prost_types::Timestamp,
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+    yellowstone_grpc_proto::{
+        created_at::to_prost,
+        geyser::{subscribe_update::UpdateOneof, SubscribeUpdate},
+        plugin::message::{
+            Message, MessageAccount, MessageBlock, MessageSlot, MessageTransaction, SlotStatus,
+        },
+        prost::{self, Message as _},
+    },
+};
+
+/// Load messages from capture files, directories are expanded into `*.bin` files sorted by name
+pub fn load_messages(paths: &[PathBuf]) -> anyhow::Result<Vec<Message>> {
+    let mut messages = vec![];
+    for path in paths {
+        if path.is_dir() {
+            let mut files = fs::read_dir(path)
+                .with_context(|| format!("failed to read directory {path:?}"))?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()
+                .with_context(|| format!("failed to read directory {path:?}"))?;
+            files.retain(|path| path.extension().is_some_and(|ext| ext == "bin"));
+            files.sort();
+            for path in files {
+                load_capture(&path, &mut messages)?;
+            }
+        } else {
+            load_capture(path, &mut messages)?;
+        }
+    }
+    Ok(messages)
+}
+
+/// Capture file is a sequence of length-delimited `SubscribeUpdate` messages
+fn load_capture(path: &Path, messages: &mut Vec<Message>) -> anyhow::Result<()> {
+    let data = fs::read(path).with_context(|| format!("failed to read {path:?}"))?;
+    let mut buf = data.as_slice();
+    while !buf.is_empty() {
+        let update = SubscribeUpdate::decode_length_delimited(&mut buf)
+            .with_context(|| format!("failed to decode {path:?}"))?;
+        push_update(update, &format!("{path:?}"), messages)?;
+    }
+    Ok(())
+}
+
+/// Load stored slots saved on the previous unload. The file is removed, so
+/// slots are never loaded twice, a file which can't be loaded is kept with
+/// the `invalid` extension. File older than `max_age` is ignored.
+pub fn load_persisted(config: &ConfigGrpcReplayPersist) -> Vec<Message> {
+    let path = &config.path;
+    let age = match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+        Ok(modified) => modified.elapsed().unwrap_or_default(),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return vec![],
+        Err(error) => {
+            warn!("failed to read metadata of {path:?}: {error}");
+            return vec![];
+        }
+    };
+
+    let messages = if age > config.max_age {
+        info!("stored slots in {path:?} are too old: {age:?}");
+        Ok(vec![])
+    } else {
+        load_messages(&[path.clone()])
+    };
+    match messages {
+        Ok(messages) => {
+            if let Err(error) = fs::remove_file(path) {
+                warn!("failed to remove {path:?}: {error}");
+            }
+            messages
+        }
+        Err(error) => {
+            warn!("failed to load stored slots: {error:?}");
+            let invalid = path.with_extension("invalid");
+            if let Err(error) = fs::rename(path, &invalid) {
+                warn!("failed to rename {path:?} to {invalid:?}: {error}");
+            }
+            vec![]
+        }
+    }
+}
+
+/// Write encoded updates as a capture file, the file is replaced only after
+/// all updates are written
+pub fn save_capture(path: &Path, updates: &[Vec<u8>]) -> anyhow::Result<()> {
+    let mut data = Vec::with_capacity(updates.iter().map(|update| update.len() + 10).sum());
+    for update in updates {
+        prost::encoding::encode_varint(update.len() as u64, &mut data);
+        data.extend_from_slice(update);
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, data).with_context(|| format!("failed to write {tmp:?}"))?;
+    fs::rename(&tmp, path).with_context(|| format!("failed to rename {tmp:?} to {path:?}"))
+}
+
+/// Convert update into plugin messages, `source` is used in warnings about
+/// skipped updates. Block is an error if it can't be converted, it's split
+/// into entries, transactions, accounts, block meta and slot statuses.
+pub fn push_update(
+    update: SubscribeUpdate,
+    source: &str,
+    messages: &mut Vec<Message>,
+) -> anyhow::Result<()> {
+    let Some(oneof) = update.update_oneof else {
+        return Ok(());
+    };
+    let created_at = update
+        .created_at
+        .map(to_prost)
+        .unwrap_or_else(|| Timestamp::from(SystemTime::now()));
+    match oneof {
+        // plugin reconstructs blocks from other messages, split them back
+        UpdateOneof::Block(msg) => {
+            let block = MessageBlock::from_update_oneof(msg, created_at)
+                .map_err(|error| anyhow::anyhow!("{source}: {error}"))?;
+            push_block(block, messages);
+        }
+        // batched updates are replayed one by one
+        UpdateOneof::SlotBatch(msg) => {
+            let accounts = msg.accounts.into_iter().map(UpdateOneof::Account);
+            let transactions = msg.transactions.into_iter().map(UpdateOneof::Transaction);
+            for oneof in accounts.chain(transactions) {
+                match Message::from_update_oneof(oneof, created_at) {
+                    Ok(message) => messages.push(message),
+                    Err(error) => warn!("{source}: skip update: {error}"),
+                }
+            }
+        }
+        UpdateOneof::TransactionStatus(_)
+        | UpdateOneof::Ping(_)
+        | UpdateOneof::Pong(_)
+        | UpdateOneof::SlotManifest(_)
+        | UpdateOneof::FilterApplied(_) => {}
+        oneof => match Message::from_update_oneof(oneof, created_at) {
+            Ok(message) => messages.push(message),
+            Err(error) => warn!("{source}: skip update: {error}"),
+        },
+    }
+    Ok(())
+}
+
+fn push_block(block: MessageBlock, messages: &mut Vec<Message>) {
+    let slot = block.meta.slot;
+    let created_at = block.created_at;
+    messages.extend(block.entries.into_iter().map(Message::Entry));
+    messages.extend(block.transactions.into_iter().map(|transaction| {
+        Message::Transaction(MessageTransaction {
+            transaction,
+            slot,
+            created_at,
+        })
+    }));
+    messages.extend(block.accounts.into_iter().map(|account| {
+        Message::Account(MessageAccount {
+            account,
+            slot,
+            is_startup: false,
+            previous_owner: None,
+            created_at,
+        })
+    }));
+    let parent = Some(block.meta.parent_slot);
+    messages.push(Message::BlockMeta(block.meta));
+    for status in [
+        SlotStatus::Processed,
+        SlotStatus::Confirmed,
+        SlotStatus::Finalized,
+    ] {
+        messages.push(Message::Slot(MessageSlot {
+            slot,
+            parent,
+            status,
+            dead_error: None,
+            created_at,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{load_messages, load_persisted, save_capture},
+        crate::config::ConfigGrpcReplayPersist,
+        prost_types::Timestamp,
+        std::{env, fs, process, thread, time::Duration},
+        yellowstone_grpc_proto::{
+            plugin::{
+                filter::message::{FilteredUpdate, FilteredUpdateOneof},
+                message::{Message, MessageSlot, SlotStatus},
+            },
+            prost::Message as _,
+        },
+    };
+
+    fn create_slot_update(slot: u64) -> Vec<u8> {
+        FilteredUpdate::new_empty(FilteredUpdateOneof::slot(MessageSlot {
+            slot,
+            parent: slot.checked_sub(1),
+            status: SlotStatus::Processed,
+            dead_error: None,
+            created_at: Timestamp::default(),
+        }))
+        .encode_to_vec()
+    }
+
+    fn get_slots(messages: &[Message]) -> Vec<u64> {
+        messages.iter().map(Message::get_slot).collect()
+    }
+
+    #[test]
+    fn test_save_load_capture() {
+        let path = env::temp_dir().join(format!("yellowstone-grpc-capture-{}.bin", process::id()));
+        let updates = (10..13).map(create_slot_update).collect::<Vec<_>>();
+        save_capture(&path, &updates).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+
+        let messages = load_messages(&[path.clone()]).unwrap();
+        assert!(messages
+            .iter()
+            .all(|message| matches!(message, Message::Slot(_))));
+        assert_eq!(get_slots(&messages), vec![10, 11, 12]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_load_persisted() {
+        let path = env::temp_dir().join(format!("yellowstone-grpc-persist-{}.bin", process::id()));
+        let mut config = ConfigGrpcReplayPersist {
+            path: path.clone(),
+            max_size: 1024,
+            max_age: Duration::from_secs(600),
+        };
+
+        // loaded once
+        save_capture(&path, &[create_slot_update(5)]).unwrap();
+        assert_eq!(get_slots(&load_persisted(&config)), vec![5]);
+        assert!(!path.exists());
+        assert!(load_persisted(&config).is_empty());
+
+        // too old file is removed without loading
+        save_capture(&path, &[create_slot_update(5)]).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        config.max_age = Duration::from_millis(10);
+        assert!(load_persisted(&config).is_empty());
+        assert!(!path.exists());
+
+        // invalid file is kept for inspection
+        config.max_age = Duration::from_secs(600);
+        fs::write(&path, [0xff; 4]).unwrap();
+        assert!(load_persisted(&config).is_empty());
+        assert!(!path.exists());
+        let invalid = path.with_extension("invalid");
+        assert!(invalid.exists());
+        fs::remove_file(invalid).unwrap();
+    }
+}
@@ -0,0 +1,303 @@
+use {
+    crate::priority::ClientSender,
+    std::{
+        fs, io,
+        path::{Path, PathBuf},
+    },
+    tokio::{
+        fs::{File, OpenOptions},
+        io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, BufWriter, SeekFrom},
+        sync::mpsc::error::TrySendError,
+    },
+    yellowstone_grpc_proto::{
+        created_at::from_prost,
+        plugin::filter::message::FilteredUpdate,
+        prelude::SubscribeUpdate,
+        prost::{bytes::Bytes, Message},
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpillError {
+    #[error("spill file is full, max {max} bytes")]
+    Full { max: u64 },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("failed to decode spilled update: {0}")]
+    Decode(String),
+}
+
+pub type SpillResult<T> = Result<T, SpillError>;
+
+/// Updates which do not fit into the client channel, written to the file in
+/// the same order and moved back to the channel once it has capacity. File is
+/// truncated every time all updates are delivered and removed on drop.
+#[derive(Debug)]
+pub struct ClientSpill {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    reader: BufReader<File>,
+    max_bytes: u64,
+    written: u64,
+    read: u64,
+    /// Update which did not fit into the channel on drain
+    head: Option<FilteredUpdate>,
+}
+
+impl Drop for ClientSpill {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl ClientSpill {
+    pub async fn create(dir: &Path, id: usize, max_bytes: u64) -> io::Result<Self> {
+        tokio::fs::create_dir_all(dir).await?;
+        let path = dir.join(format!("client-{id}.spill"));
+        let writer = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(&path)
+            .await?;
+        let reader = File::open(&path).await?;
+        Ok(Self {
+            path,
+            writer: BufWriter::new(writer),
+            reader: BufReader::new(reader),
+            max_bytes,
+            written: 0,
+            read: 0,
+            head: None,
+        })
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.head.is_none() && self.read == self.written
+    }
+
+    /// Every update is stored as the length of the record, the length of
+    /// filters and `created_at` and the encoded update, the message is written
+    /// last, so it's sent as is once read. Sequence is stamped once the update
+    /// is queued to the channel, so it's not stored.
+    pub async fn push(&mut self, update: &FilteredUpdate) -> SpillResult<()> {
+        let meta = SubscribeUpdate {
+            filters: update
+                .filters
+                .iter()
+                .map(|filter| filter.as_ref().to_owned())
+                .collect(),
+            created_at: Some(from_prost(update.created_at)),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let message = match &update.encoded_message {
+            Some(encoded) => encoded.clone(),
+            None => {
+                let mut buf = Vec::with_capacity(update.message.encoded_len());
+                update.message.encode_raw(&mut buf);
+                buf.into()
+            }
+        };
+
+        let size = 8 + (meta.len() + message.len()) as u64;
+        if self.written + size > self.max_bytes {
+            return Err(SpillError::Full {
+                max: self.max_bytes,
+            });
+        }
+        self.writer
+            .write_all(&((meta.len() + message.len()) as u32).to_le_bytes())
+            .await?;
+        self.writer
+            .write_all(&(meta.len() as u32).to_le_bytes())
+            .await?;
+        self.writer.write_all(&meta).await?;
+        self.writer.write_all(&message).await?;
+        self.written += size;
+        Ok(())
+    }
+
+    /// Update is decoded for routing by class and slot, encoded message is
+    /// kept, so it's not encoded again
+    async fn pop(&mut self) -> SpillResult<Option<FilteredUpdate>> {
+        if let Some(update) = self.head.take() {
+            return Ok(Some(update));
+        }
+        if self.read == self.written {
+            return Ok(None);
+        }
+        self.writer.flush().await?;
+
+        let mut len = [0; 4];
+        self.reader.read_exact(&mut len).await?;
+        let mut meta_len = [0; 4];
+        self.reader.read_exact(&mut meta_len).await?;
+        let mut data = vec![0; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut data).await?;
+        self.read += 8 + data.len() as u64;
+
+        if self.read == self.written {
+            self.writer.get_mut().set_len(0).await?;
+            self.writer.seek(SeekFrom::Start(0)).await?;
+            self.reader.seek(SeekFrom::Start(0)).await?;
+            self.written = 0;
+            self.read = 0;
+        }
+
+        let data = Bytes::from(data);
+        let meta_len = (u32::from_le_bytes(meta_len) as usize).min(data.len());
+        let update = SubscribeUpdate::decode(data.clone())
+            .map_err(|error| SpillError::Decode(error.to_string()))?;
+        let mut update = FilteredUpdate::from_subscribe_update(update)
+            .map_err(|error| SpillError::Decode(error.to_owned()))?;
+        update.encoded_message = Some(data.slice(meta_len..));
+        Ok(Some(update))
+    }
+
+    /// Move spilled updates to the channel while it has capacity, updates are
+    /// queued by class as any other update
+    pub async fn drain(&mut self, tx: &ClientSender) -> SpillResult<usize> {
+        let mut count = 0;
+        while tx.has_capacity().unwrap_or(false) {
+            let Some(update) = self.pop().await? else {
+                break;
+            };
+            match tx.try_send(Ok(update)) {
+                Ok(()) => count += 1,
+                Err(TrySendError::Full(item)) => {
+                    self.head = item.ok();
+                    break;
+                }
+                Err(TrySendError::Closed(_)) => break,
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{ClientSpill, SpillError},
+        crate::{
+            config::{ConfigGrpcPriority, ConfigGrpcPriorityClass},
+            priority::channel,
+        },
+        futures::stream::StreamExt,
+        prost_types::Timestamp,
+        std::{env, process, sync::Arc, time::SystemTime},
+        yellowstone_grpc_proto::{
+            plugin::{
+                filter::{
+                    message::{FilteredUpdate, FilteredUpdateOneof},
+                    name::FilterName,
+                },
+                message::{MessageAccount, MessageAccountInfo, MessageSlot, SlotStatus},
+            },
+            prost::Message,
+        },
+    };
+
+    fn slot(slot: u64) -> FilteredUpdate {
+        let mut update = FilteredUpdate::new_empty(FilteredUpdateOneof::slot(MessageSlot {
+            slot,
+            parent: None,
+            status: SlotStatus::Confirmed,
+            dead_error: None,
+            created_at: Timestamp::from(SystemTime::now()),
+        }));
+        update.filters.push(FilterName::new("slots"));
+        update
+    }
+
+    fn account(slot: u64) -> FilteredUpdate {
+        let message = MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey: Default::default(),
+                lamports: 42,
+                owner: Default::default(),
+                executable: false,
+                rent_epoch: 0,
+                data: vec![1, 2, 3],
+                write_version: 7,
+                txn_signature: None,
+            }),
+            slot,
+            is_startup: false,
+            previous_owner: None,
+            created_at: Timestamp::from(SystemTime::now()),
+        };
+        let mut update =
+            FilteredUpdate::new_empty(FilteredUpdateOneof::account(&message, Default::default()));
+        update.filters.push(FilterName::new("accounts"));
+        update
+    }
+
+    #[tokio::test]
+    async fn test_spill_drain() {
+        let dir = env::temp_dir().join(format!("yellowstone-grpc-spill-{}", process::id()));
+        let mut spill = ClientSpill::create(&dir, 0, 1024 * 1024).await.unwrap();
+        let config = ConfigGrpcPriority {
+            classes: vec![ConfigGrpcPriorityClass::Slot],
+            capacity: None,
+        };
+        let (tx, rx) = channel(Some(&config), 2);
+
+        let updates = vec![account(6), account(6), slot(5)];
+        for update in updates.iter() {
+            spill.push(update).await.unwrap();
+        }
+        assert!(!spill.is_empty());
+
+        // the third update does not fit and is kept
+        assert_eq!(spill.drain(&tx).await.unwrap(), 2);
+        assert!(!spill.is_empty());
+        let mut rx = rx.boxed();
+        let first = rx.next().await.unwrap().unwrap();
+        assert_eq!(spill.drain(&tx).await.unwrap(), 1);
+        assert!(spill.is_empty());
+        drop(tx);
+        let rest = rx.map(Result::unwrap).collect::<Vec<_>>().await;
+
+        // slot status of the previous slot goes ahead of accounts, spilled
+        // updates are sent as they were encoded and stamped once queued
+        let received = [first].into_iter().chain(rest).collect::<Vec<_>>();
+        assert!(matches!(
+            received[0].message,
+            FilteredUpdateOneof::Account(_)
+        ));
+        assert!(matches!(received[1].message, FilteredUpdateOneof::Slot(_)));
+        assert!(matches!(
+            received[2].message,
+            FilteredUpdateOneof::Account(_)
+        ));
+        for (update, expected) in received.iter().zip([&updates[0], &updates[2], &updates[1]]) {
+            assert!(update.encoded_message.is_some());
+            let mut expected = expected.clone();
+            expected.sequence = update.sequence;
+            assert_eq!(update.encode_to_vec(), expected.encode_to_vec());
+        }
+
+        // file is reused once all updates are delivered
+        spill.push(&slot(7)).await.unwrap();
+        let (tx, rx) = channel(None, 1);
+        assert_eq!(spill.drain(&tx).await.unwrap(), 1);
+        drop(tx);
+        assert_eq!(rx.count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_spill_full() {
+        let dir = env::temp_dir().join(format!("yellowstone-grpc-spill-full-{}", process::id()));
+        let update = account(1);
+        let mut spill = ClientSpill::create(&dir, 1, 8 + update.encoded_len() as u64 + 64)
+            .await
+            .unwrap();
+        spill.push(&update).await.unwrap();
+        assert!(matches!(
+            spill.push(&update).await,
+            Err(SpillError::Full { .. })
+        ));
+    }
+}
@@ -0,0 +1,35 @@
+use {
+    criterion::{criterion_group, criterion_main, BenchmarkId, Criterion},
+    std::hint::black_box,
+    yellowstone_grpc_proto::base58,
+};
+
+fn bench_encode(c: &mut Criterion) {
+    for (kind, len) in [("pubkey", 32), ("signature", 64)] {
+        let values = (0..1_000u32)
+            .map(|index| {
+                (0..len)
+                    .map(|offset| index.wrapping_mul(31).wrapping_add(offset) as u8)
+                    .collect::<Vec<u8>>()
+            })
+            .collect::<Vec<_>>();
+
+        c.bench_with_input(BenchmarkId::new(kind, "bs58"), &values, |b, values| {
+            b.iter(|| {
+                for value in values.iter() {
+                    black_box(bs58::encode(value).into_string());
+                }
+            })
+        });
+        c.bench_with_input(BenchmarkId::new(kind, "base58"), &values, |b, values| {
+            b.iter(|| {
+                for value in values.iter() {
+                    black_box(base58::encode(value));
+                }
+            })
+        });
+    }
+}
+
+criterion_group!(benches, bench_encode);
+criterion_main!(benches);
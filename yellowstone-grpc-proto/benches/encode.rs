@@ -43,6 +43,10 @@ fn bench_account(c: &mut Criterion) {
             filters: filters.clone(),
             message: FilteredUpdateOneof::account(&msg, data_slice),
             created_at: Timestamp::from(SystemTime::now()),
+            sequence: 0,
+            ingested_at: None,
+            replica: None,
+            encoded_message: None,
         })
         .collect::<Vec<_>>();
     bench!(&updates, "accounts");
@@ -57,6 +61,10 @@ fn bench_account(c: &mut Criterion) {
                 created_at: Timestamp::from(SystemTime::now()),
             }),
             created_at: Timestamp::from(SystemTime::now()),
+            sequence: 0,
+            ingested_at: None,
+            replica: None,
+            encoded_message: None,
         })
         .collect::<Vec<_>>();
     bench!(&updates, "transactions");
@@ -67,6 +75,10 @@ fn bench_account(c: &mut Criterion) {
             filters: filters.clone(),
             message: FilteredUpdateOneof::block(Box::new(block)),
             created_at: Timestamp::from(SystemTime::now()),
+            sequence: 0,
+            ingested_at: None,
+            replica: None,
+            encoded_message: None,
         })
         .collect::<Vec<_>>();
     bench!(&updates, "blocks");
@@ -0,0 +1,186 @@
+use {
+    criterion::{criterion_group, criterion_main, BenchmarkId, Criterion},
+    prost::Message as _,
+    prost_types::Timestamp,
+    std::{
+        collections::HashMap,
+        hint::black_box,
+        sync::Arc,
+        time::{Duration, SystemTime},
+    },
+    yellowstone_grpc_proto::{
+        geyser::{
+            SubscribeRequest, SubscribeRequestFilterAccounts, SubscribeRequestFilterTransactions,
+            SubscribeUpdate,
+        },
+        plugin::{
+            filter::{
+                limits::FilterLimits,
+                message::tests::{create_accounts_raw, load_predefined_transactions},
+                name::FilterNames,
+                Filter,
+            },
+            message::{Message, MessageAccount, MessageTransaction},
+        },
+    },
+};
+
+fn create_filter(request: &SubscribeRequest) -> Filter {
+    let mut names = FilterNames::new(64, 1024, Duration::from_secs(1));
+    Filter::new(request, &FilterLimits::default(), &mut names).expect("valid filter")
+}
+
+fn create_messages() -> (Vec<Message>, Vec<Message>) {
+    let created_at = Timestamp::from(SystemTime::now());
+    let accounts = create_accounts_raw()
+        .into_iter()
+        .map(|account| {
+            Message::Account(MessageAccount {
+                account,
+                slot: 42,
+                is_startup: false,
+                previous_owner: None,
+                created_at,
+            })
+        })
+        .collect();
+    let transactions = load_predefined_transactions()
+        .into_iter()
+        .map(|transaction| {
+            Message::Transaction(MessageTransaction {
+                transaction,
+                slot: 42,
+                created_at,
+            })
+        })
+        .collect();
+    (accounts, transactions)
+}
+
+fn create_request(accounts: &[Message], transactions: &[Message]) -> SubscribeRequest {
+    let owner = match &accounts[0] {
+        Message::Account(msg) => msg.account.owner.to_string(),
+        _ => unreachable!(),
+    };
+    let account_include = match &transactions[0] {
+        Message::Transaction(msg) => msg
+            .transaction
+            .account_keys
+            .iter()
+            .take(2)
+            .map(ToString::to_string)
+            .collect(),
+        _ => unreachable!(),
+    };
+    SubscribeRequest {
+        accounts: HashMap::from([(
+            "accounts".to_owned(),
+            SubscribeRequestFilterAccounts {
+                owner: vec![owner],
+                ..Default::default()
+            },
+        )]),
+        transactions: HashMap::from([(
+            "transactions".to_owned(),
+            SubscribeRequestFilterTransactions {
+                account_include,
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    }
+}
+
+fn bench_filter(c: &mut Criterion) {
+    let (accounts, transactions) = create_messages();
+    let filter = create_filter(&create_request(&accounts, &transactions));
+
+    for (kind, messages) in [("accounts", &accounts), ("transactions", &transactions)] {
+        c.bench_with_input(BenchmarkId::new("filter", kind), messages, |b, messages| {
+            b.iter(|| {
+                for message in messages.iter() {
+                    black_box(filter.get_updates(message, None));
+                }
+            })
+        });
+    }
+}
+
+fn bench_fanout(c: &mut Criterion) {
+    let (accounts, transactions) = create_messages();
+    let request = create_request(&accounts, &transactions);
+    let messages = Arc::new(
+        accounts
+            .into_iter()
+            .chain(transactions)
+            .enumerate()
+            .map(|(msgid, message)| (msgid as u64, message))
+            .collect::<Vec<_>>(),
+    );
+
+    // same path as broadcasted messages: shared batch, every client filters
+    // and encodes matched updates
+    for clients in [1, 10, 100] {
+        let filters = (0..clients)
+            .map(|_| create_filter(&request))
+            .collect::<Vec<_>>();
+        c.bench_with_input(
+            BenchmarkId::new("fanout", clients),
+            &filters,
+            |b, filters| {
+                b.iter(|| {
+                    for filter in filters.iter() {
+                        let messages = Arc::clone(&messages);
+                        for (_msgid, message) in messages.iter() {
+                            for update in filter.get_updates(message, None) {
+                                black_box(update.encode_to_vec());
+                            }
+                        }
+                    }
+                })
+            },
+        );
+    }
+
+    c.bench_with_input(
+        BenchmarkId::new("clone", "messages"),
+        &messages,
+        |b, messages| {
+            b.iter(|| {
+                for (_msgid, message) in messages.iter() {
+                    black_box(message.clone());
+                }
+            })
+        },
+    );
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let (accounts, transactions) = create_messages();
+    let filter = create_filter(&create_request(&accounts, &transactions));
+
+    for (kind, messages) in [("accounts", &accounts), ("transactions", &transactions)] {
+        let encoded = messages
+            .iter()
+            .flat_map(|message| filter.get_updates(message, None))
+            .map(|update| update.encode_to_vec())
+            .collect::<Vec<_>>();
+        assert!(!encoded.is_empty(), "no {kind} matched");
+        c.bench_with_input(BenchmarkId::new("decode", kind), &encoded, |b, encoded| {
+            b.iter(|| {
+                for data in encoded.iter() {
+                    black_box(SubscribeUpdate::decode(data.as_slice()).expect("valid update"));
+                }
+            })
+        });
+    }
+}
+
+criterion_group!(
+    name = benches;
+    config = Criterion::default()
+        .warm_up_time(Duration::from_secs(3)) // default 3
+        .measurement_time(Duration::from_secs(5)); // default 5
+    targets = bench_filter, bench_fanout, bench_decode
+);
+criterion_main!(benches);
@@ -1,25 +1,67 @@
-use {
-    std::{env, fs, path::Path},
-    tonic_build::manual::{Builder, Method, Service},
-};
+use std::{env, path::Path};
+#[cfg(feature = "plugin")]
+use tonic_build::manual::{Builder, Method, Service};
 
 fn main() -> anyhow::Result<()> {
     std::env::set_var("PROTOC", protobuf_src::protoc());
 
-    // build protos
-    tonic_build::configure().compile_protos(&["proto/geyser.proto"], &["proto"])?;
-
-    // build protos without tonic (wasm)
     let out_dir = env::var("OUT_DIR").expect("OUT_DIR not found");
-    let out_dir_path = Path::new(&out_dir).join("no-tonic");
-    fs::create_dir_all(&out_dir_path).expect("failed to create out no-tonic directory");
+
+    // build protos, well-known types are from `pbjson-types` with serde support
+    // gRPC client and server are generated only with `tonic`, otherwise messages are prost-only
+    let descriptor_path = Path::new(&out_dir).join("proto_descriptor.bin");
     tonic_build::configure()
-        .build_client(false)
-        .build_server(false)
-        .out_dir(out_dir_path)
+        .build_client(cfg!(feature = "tonic"))
+        .build_server(cfg!(feature = "tonic"))
+        .file_descriptor_set_path(&descriptor_path)
+        .compile_well_known_types(true)
+        .extern_path(".google.protobuf", "::pbjson_types")
         .compile_protos(&["proto/geyser.proto"], &["proto"])?;
 
-    // build with accepting our custom struct
+    // build serde implementations (pbjson)
+    #[cfg(feature = "serde")]
+    {
+        let descriptor_set = std::fs::read(&descriptor_path)?;
+        pbjson_build::Builder::new()
+            .register_descriptors(&descriptor_set)?
+            .extern_path(".google.protobuf", "::pbjson_types")
+            .build(&[".geyser", ".solana"])?;
+    }
+
+    // build protos with `Bytes` instead of `Vec<u8>` (zero-copy decoding)
+    #[cfg(feature = "zero-copy")]
+    {
+        let out_dir_path = Path::new(&out_dir).join("zero-copy");
+        std::fs::create_dir_all(&out_dir_path).expect("failed to create out zero-copy directory");
+        tonic_build::configure()
+            .build_client(false)
+            .build_server(false)
+            .bytes(["."])
+            .compile_well_known_types(true)
+            .extern_path(".google.protobuf", "::pbjson_types")
+            .out_dir(out_dir_path)
+            .compile_protos(&["proto/geyser.proto"], &["proto"])?;
+    }
+
+    // build with accepting our custom struct (used by the plugin server)
+    #[cfg(feature = "plugin")]
+    build_plugin_service();
+
+    // patching generated custom struct (if custom Codec is used)
+    // let mut location = std::path::PathBuf::from(std::env::var("OUT_DIR")?);
+    // location.push("geyser.Geyser.rs");
+    // let geyser_rs = std::fs::read_to_string(location.clone())?;
+    // let geyser_rs = geyser_rs.replace(
+    //     "let codec = crate::plugin::codec::SubscribeCodec::default();",
+    //     "let codec = crate::plugin::codec::SubscribeCodec::<crate::plugin::filter::Message, _>::default();",
+    // );
+    // std::fs::write(location, geyser_rs)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "plugin")]
+fn build_plugin_service() {
     let geyser_service = Service::builder()
         .name("Geyser")
         .package("geyser")
@@ -81,6 +123,15 @@ fn main() -> anyhow::Result<()> {
                 .codec_path("tonic::codec::ProstCodec")
                 .build(),
         )
+        .method(
+            Method::builder()
+                .name("get_slot_watermarks")
+                .route_name("GetSlotWatermarks")
+                .input_type("crate::geyser::GetSlotWatermarksRequest")
+                .output_type("crate::geyser::GetSlotWatermarksResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
         .method(
             Method::builder()
                 .name("is_blockhash_valid")
@@ -99,20 +150,36 @@ fn main() -> anyhow::Result<()> {
                 .codec_path("tonic::codec::ProstCodec")
                 .build(),
         )
+        .method(
+            Method::builder()
+                .name("snapshot_download")
+                .route_name("SnapshotDownload")
+                .input_type("crate::geyser::SnapshotDownloadRequest")
+                .output_type("crate::geyser::SnapshotDownloadResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .server_streaming()
+                .build(),
+        )
+        .method(
+            Method::builder()
+                .name("explain_filters")
+                .route_name("ExplainFilters")
+                .input_type("crate::geyser::ExplainFiltersRequest")
+                .output_type("crate::geyser::ExplainFiltersResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
+        .method(
+            Method::builder()
+                .name("get_account")
+                .route_name("GetAccount")
+                .input_type("crate::geyser::GetAccountRequest")
+                .output_type("crate::geyser::GetAccountResponse")
+                .codec_path("tonic::codec::ProstCodec")
+                .build(),
+        )
         .build();
     Builder::new()
         .build_client(false)
         .compile(&[geyser_service]);
-
-    // patching generated custom struct (if custom Codec is used)
-    // let mut location = std::path::PathBuf::from(std::env::var("OUT_DIR")?);
-    // location.push("geyser.Geyser.rs");
-    // let geyser_rs = std::fs::read_to_string(location.clone())?;
-    // let geyser_rs = geyser_rs.replace(
-    //     "let codec = crate::plugin::codec::SubscribeCodec::default();",
-    //     "let codec = crate::plugin::codec::SubscribeCodec::<crate::plugin::filter::Message, _>::default();",
-    // );
-    // std::fs::write(location, geyser_rs)?;
-
-    Ok(())
 }
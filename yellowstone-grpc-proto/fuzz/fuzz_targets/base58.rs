@@ -0,0 +1,9 @@
+#![no_main]
+
+use {libfuzzer_sys::fuzz_target, yellowstone_grpc_proto::base58};
+
+fuzz_target!(|data: &[u8]| {
+    let encoded = base58::encode(data);
+    assert_eq!(encoded, bs58::encode(data).into_string());
+    assert_eq!(bs58::decode(&encoded).into_vec().unwrap(), data);
+});
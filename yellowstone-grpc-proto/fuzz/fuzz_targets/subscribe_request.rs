@@ -0,0 +1,85 @@
+//! `SubscribeRequest` from untrusted client: decode, create filter with
+//! permissive and strict limits, match created filter against messages.
+
+#![no_main]
+
+use {
+    libfuzzer_sys::fuzz_target,
+    prost::Message as _,
+    std::time::Duration,
+    yellowstone_grpc_proto::{
+        geyser::SubscribeRequest,
+        plugin::{
+            filter::{limits::FilterLimits, name::FilterNames, Filter},
+            message::{Message, MessageAccount, MessageAccountInfo, MessageSlot, SlotStatus},
+        },
+        prost_types::Timestamp,
+    },
+};
+
+fn strict_limits() -> FilterLimits {
+    serde_json::from_str(
+        r#"{
+            "accounts": {"max": 1, "any": false, "account_max": 10, "owner_max": 10, "data_slice_max": 2},
+            "slots": {"max": 1},
+            "transactions": {"max": 1, "any": false, "account_include_max": 10, "account_exclude_max": 10, "account_required_max": 10},
+            "transactions_status": {"max": 1, "any": false, "account_include_max": 10, "account_exclude_max": 10, "account_required_max": 10},
+            "blocks": {"max": 1, "account_include_max": 10, "account_include_any": false, "include_transactions": true, "include_accounts": false, "include_entries": false},
+            "blocks_meta": {"max": 1},
+            "entries": {"max": 1}
+        }"#,
+    )
+    .expect("valid limits")
+}
+
+fn messages() -> Vec<Message> {
+    let account = MessageAccount {
+        account: MessageAccountInfo {
+            pubkey: Default::default(),
+            lamports: 1,
+            owner: Default::default(),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![1; 256],
+            write_version: 0,
+            txn_signature: None,
+        }
+        .into(),
+        slot: 42,
+        is_startup: false,
+        previous_owner: None,
+        created_at: Timestamp::default(),
+    };
+    vec![
+        Message::Slot(MessageSlot {
+            slot: 42,
+            parent: Some(41),
+            status: SlotStatus::Processed,
+            dead_error: None,
+            created_at: Timestamp::default(),
+        }),
+        Message::Account(account.clone()),
+        // owner changed
+        Message::Account(MessageAccount {
+            previous_owner: Some([1; 32].into()),
+            ..account
+        }),
+    ]
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(request) = SubscribeRequest::decode(data) else {
+        return;
+    };
+
+    for limits in [FilterLimits::default(), strict_limits()] {
+        let mut names = FilterNames::new(64, 1024, Duration::from_secs(1));
+        if let Ok(filter) = Filter::new(&request, &limits, &mut names) {
+            for message in messages().iter() {
+                for update in filter.get_updates(message, None) {
+                    update.encode_to_vec();
+                }
+            }
+        }
+    }
+});
@@ -0,0 +1,115 @@
+use {
+    crate::geyser::SubscribeUpdate,
+    prost_types::Timestamp,
+    std::time::{Duration, SystemTime},
+};
+
+/// Allowed difference between server and client clocks when `created_at` is in the future
+pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_millis(100);
+
+/// `created_at` and `ingested_at` of generated messages are `pbjson_types::Timestamp`
+/// (serde support), plugin messages and helpers of this module use `prost_types`
+pub const fn from_prost(timestamp: Timestamp) -> pbjson_types::Timestamp {
+    pbjson_types::Timestamp {
+        seconds: timestamp.seconds,
+        nanos: timestamp.nanos,
+    }
+}
+
+pub const fn to_prost(timestamp: pbjson_types::Timestamp) -> Timestamp {
+    Timestamp {
+        seconds: timestamp.seconds,
+        nanos: timestamp.nanos,
+    }
+}
+
+pub fn to_system_time(created_at: Timestamp) -> Option<SystemTime> {
+    SystemTime::try_from(created_at).ok()
+}
+
+#[cfg(feature = "chrono")]
+pub fn to_chrono(created_at: Timestamp) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::from_timestamp(created_at.seconds, created_at.nanos.try_into().ok()?)
+}
+
+/// Latency between `created_at` and `now`.
+///
+/// If `created_at` is ahead of `now` by no more than `max_skew` then zero is returned,
+/// otherwise `None` because clocks are not synchronized and value is meaningless.
+pub fn latency(created_at: Timestamp, now: SystemTime, max_skew: Duration) -> Option<Duration> {
+    match now.duration_since(to_system_time(created_at)?) {
+        Ok(latency) => Some(latency),
+        Err(error) if error.duration() <= max_skew => Some(Duration::ZERO),
+        Err(_) => None,
+    }
+}
+
+impl SubscribeUpdate {
+    pub fn created_at_system_time(&self) -> Option<SystemTime> {
+        self.created_at.map(to_prost).and_then(to_system_time)
+    }
+
+    #[cfg(feature = "chrono")]
+    pub fn created_at_chrono(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.created_at.map(to_prost).and_then(to_chrono)
+    }
+
+    /// Latency from `created_at` to now, see [`latency`]
+    pub fn latency(&self) -> Option<Duration> {
+        latency(
+            to_prost(self.created_at?),
+            SystemTime::now(),
+            DEFAULT_MAX_CLOCK_SKEW,
+        )
+    }
+
+    /// Time between receiving the update from geyser and sending it, measured
+    /// by the server clock. `None` if the server stamps `created_at` on notification
+    pub fn queue_delay(&self) -> Option<Duration> {
+        to_system_time(to_prost(self.created_at?))?
+            .duration_since(to_system_time(to_prost(self.ingested_at?))?)
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{from_prost, latency},
+        crate::geyser::SubscribeUpdate,
+        prost_types::Timestamp,
+        std::time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn test_latency() {
+        let now = SystemTime::now();
+        let max_skew = Duration::from_millis(100);
+
+        let created_at = Timestamp::from(now - Duration::from_millis(50));
+        assert_eq!(
+            latency(created_at, now, max_skew),
+            Some(Duration::from_millis(50))
+        );
+
+        let created_at = Timestamp::from(now + Duration::from_millis(50));
+        assert_eq!(latency(created_at, now, max_skew), Some(Duration::ZERO));
+
+        let created_at = Timestamp::from(now + Duration::from_secs(1));
+        assert_eq!(latency(created_at, now, max_skew), None);
+    }
+
+    #[test]
+    fn test_queue_delay() {
+        let mut update = SubscribeUpdate {
+            created_at: Some(from_prost(Timestamp::from(SystemTime::now()))),
+            ..Default::default()
+        };
+        assert_eq!(update.queue_delay(), None);
+
+        let now = SystemTime::now();
+        update.ingested_at = Some(from_prost(Timestamp::from(now - Duration::from_millis(5))));
+        update.created_at = Some(from_prost(Timestamp::from(now)));
+        assert_eq!(update.queue_delay(), Some(Duration::from_millis(5)));
+    }
+}
@@ -0,0 +1,159 @@
+use {
+    crate::geyser::{subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateBlock},
+    prost::encoding::{encoded_len_varint, key_len, message, string, uint64},
+};
+
+/// Encoded size of `SubscribeUpdate` split by fields, sum is equal to `Message::encoded_len`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SubscribeUpdateEncodedLen {
+    pub filters: usize,
+    pub update_oneof: usize,
+    /// `created_at` and `ingested_at`
+    pub created_at: usize,
+    /// `sequence` and `replica`
+    pub other: usize,
+}
+
+impl SubscribeUpdateEncodedLen {
+    pub const fn total(&self) -> usize {
+        self.filters + self.update_oneof + self.created_at + self.other
+    }
+}
+
+/// Encoded size of `SubscribeUpdateBlock` heavy fields, `other` includes everything else
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SubscribeUpdateBlockEncodedLen {
+    pub transactions: usize,
+    pub accounts: usize,
+    pub entries: usize,
+    pub other: usize,
+}
+
+impl SubscribeUpdateBlockEncodedLen {
+    pub const fn total(&self) -> usize {
+        self.transactions + self.accounts + self.entries + self.other
+    }
+}
+
+impl SubscribeUpdate {
+    pub fn encoded_len_breakdown(&self) -> SubscribeUpdateEncodedLen {
+        SubscribeUpdateEncodedLen {
+            filters: string::encoded_len_repeated(1, &self.filters),
+            update_oneof: self
+                .update_oneof
+                .as_ref()
+                .map_or(0, UpdateOneof::encoded_len),
+            created_at: self
+                .created_at
+                .as_ref()
+                .map_or(0, |created_at| message::encoded_len(11, created_at))
+                + self
+                    .ingested_at
+                    .as_ref()
+                    .map_or(0, |ingested_at| message::encoded_len(15, ingested_at)),
+            other: if self.sequence != 0 {
+                uint64::encoded_len(13, &self.sequence)
+            } else {
+                0
+            } + if self.replica.is_empty() {
+                0
+            } else {
+                string::encoded_len(17, &self.replica)
+            },
+        }
+    }
+
+    /// Size of the message in the stream including length-delimiter
+    pub fn encoded_len_delimited(&self) -> usize {
+        let len = prost::Message::encoded_len(self);
+        encoded_len_varint(len as u64) + len
+    }
+}
+
+impl SubscribeUpdateBlock {
+    pub fn encoded_len_breakdown(&self) -> SubscribeUpdateBlockEncodedLen {
+        let transactions = message::encoded_len_repeated(6, &self.transactions);
+        let accounts = message::encoded_len_repeated(11, &self.accounts);
+        let entries = message::encoded_len_repeated(13, &self.entries);
+        let total = prost::Message::encoded_len(self);
+        SubscribeUpdateBlockEncodedLen {
+            transactions,
+            accounts,
+            entries,
+            other: total - transactions - accounts - entries,
+        }
+    }
+}
+
+/// Encoded size of `SubscribeUpdate` with `update` only, useful when update is not wrapped yet
+pub fn encoded_len_with_update(
+    filters: &[String],
+    update: &UpdateOneof,
+    created_at: bool,
+) -> usize {
+    // len + (key + seconds) + (key + nanos)
+    const TIMESTAMP_MAX_LEN: usize = 1 + (1 + 10) + (1 + 5);
+    string::encoded_len_repeated(1, filters)
+        + update.encoded_len()
+        + if created_at {
+            key_len(11) + TIMESTAMP_MAX_LEN
+        } else {
+            0
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::encoded_len_with_update,
+        crate::geyser::{
+            subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateAccountInfo,
+            SubscribeUpdateBlock, SubscribeUpdateSlot,
+        },
+        pbjson_types::Timestamp,
+        prost::Message,
+    };
+
+    #[test]
+    fn test_encoded_len_breakdown() {
+        let update = SubscribeUpdate {
+            filters: vec!["client".to_owned(), "client2".to_owned()],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot: 42,
+                parent: Some(41),
+                status: 1,
+                dead_error: None,
+            })),
+            created_at: Some(Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 123_456_789,
+            }),
+            sequence: 42,
+            ingested_at: None,
+            replica: "eu".to_owned(),
+        };
+        let breakdown = update.encoded_len_breakdown();
+        assert_eq!(breakdown.total(), update.encoded_len());
+        assert_eq!(
+            update.encoded_len_delimited(),
+            update.encode_length_delimited_to_vec().len()
+        );
+        assert!(
+            encoded_len_with_update(&update.filters, update.update_oneof.as_ref().unwrap(), true)
+                >= update.encoded_len()
+        );
+
+        let block = SubscribeUpdateBlock {
+            slot: 42,
+            accounts: vec![SubscribeUpdateAccountInfo {
+                data: vec![42; 100],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let breakdown = block.encoded_len_breakdown();
+        assert_eq!(breakdown.total(), block.encoded_len());
+        assert!(breakdown.accounts > 100);
+        assert_eq!(breakdown.transactions, 0);
+    }
+}
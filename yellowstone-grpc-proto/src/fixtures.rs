@@ -0,0 +1,338 @@
+use {
+    crate::{
+        convert_to,
+        created_at::from_prost,
+        geyser::{
+            subscribe_update::UpdateOneof, SlotStatus, SubscribeUpdate, SubscribeUpdateAccount,
+            SubscribeUpdateBlock, SubscribeUpdateSlot, SubscribeUpdateTransaction,
+        },
+    },
+    prost_types::Timestamp,
+    rand::{rngs::StdRng, Rng, SeedableRng},
+    solana_sdk::{
+        account::Account,
+        clock::Slot,
+        hash::Hash,
+        message::v0::LoadedAddresses,
+        pubkey::Pubkey,
+        signer::keypair::{keypair_from_seed, Keypair},
+        system_program, system_transaction,
+        transaction::VersionedTransaction,
+    },
+    solana_transaction_status::{
+        ConfirmedBlock, TransactionStatusMeta, TransactionWithStatusMeta,
+        VersionedTransactionWithStatusMeta,
+    },
+    std::time::SystemTime,
+};
+
+const FEE: u64 = 5_000;
+
+/// Generator of realistic random updates: valid pubkeys, signed transactions and
+/// balances in meta that match transferred lamports and fee.
+#[derive(Debug)]
+pub struct UpdateGenerator<R = StdRng> {
+    rng: R,
+    slot: Slot,
+    write_version: u64,
+}
+
+impl UpdateGenerator<StdRng> {
+    pub fn new(seed: u64) -> Self {
+        Self::from_rng(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<R: Rng> UpdateGenerator<R> {
+    pub fn from_rng(mut rng: R) -> Self {
+        Self {
+            slot: rng.gen_range(100_000_000..400_000_000),
+            rng,
+            write_version: 0,
+        }
+    }
+
+    pub const fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    pub fn next_slot(&mut self) -> Slot {
+        self.slot += 1;
+        self.slot
+    }
+
+    pub fn pubkey(&mut self) -> Pubkey {
+        Pubkey::new_from_array(self.rng.gen())
+    }
+
+    pub fn hash(&mut self) -> Hash {
+        Hash::new_from_array(self.rng.gen())
+    }
+
+    pub fn keypair(&mut self) -> Keypair {
+        let seed: [u8; 32] = self.rng.gen();
+        keypair_from_seed(&seed).expect("valid seed length")
+    }
+
+    pub fn account(&mut self) -> SubscribeUpdateAccount {
+        let pubkey = self.pubkey();
+        let data_len = self.rng.gen_range(0..=512);
+        let account = Account {
+            lamports: self.rng.gen_range(890_880..1_000_000_000_000),
+            data: (0..data_len).map(|_| self.rng.gen()).collect(),
+            owner: if data_len == 0 {
+                system_program::ID
+            } else {
+                self.pubkey()
+            },
+            executable: false,
+            rent_epoch: u64::MAX,
+        };
+        self.write_version += 1;
+
+        SubscribeUpdateAccount {
+            account: Some(convert_to::create_account(
+                &pubkey,
+                &account,
+                self.write_version,
+            )),
+            slot: self.slot,
+            is_startup: false,
+            previous_owner: None,
+            data_omitted: false,
+        }
+    }
+
+    pub fn slot_update(&mut self, status: SlotStatus) -> SubscribeUpdateSlot {
+        SubscribeUpdateSlot {
+            slot: self.slot,
+            parent: self.slot.checked_sub(1),
+            status: status as i32,
+            dead_error: None,
+        }
+    }
+
+    /// System transfer signed by random keypair with balanced meta
+    pub fn transaction_with_meta(&mut self) -> TransactionWithStatusMeta {
+        let payer = self.keypair();
+        let to = self.pubkey();
+        let lamports = self.rng.gen_range(1..1_000_000_000);
+        let transaction = VersionedTransaction::from(system_transaction::transfer(
+            &payer,
+            &to,
+            lamports,
+            self.hash(),
+        ));
+
+        let payer_balance = self
+            .rng
+            .gen_range(lamports + FEE..lamports + FEE + 1_000_000_000);
+        let to_balance = self.rng.gen_range(0..1_000_000_000);
+        let meta = TransactionStatusMeta {
+            status: Ok(()),
+            fee: FEE,
+            pre_balances: vec![payer_balance, to_balance, 1],
+            post_balances: vec![payer_balance - lamports - FEE, to_balance + lamports, 1],
+            inner_instructions: Some(vec![]),
+            log_messages: Some(vec![
+                format!("Program {} invoke [1]", system_program::ID),
+                format!("Program {} success", system_program::ID),
+            ]),
+            pre_token_balances: Some(vec![]),
+            post_token_balances: Some(vec![]),
+            rewards: Some(vec![]),
+            loaded_addresses: LoadedAddresses::default(),
+            return_data: None,
+            compute_units_consumed: Some(150),
+        };
+
+        TransactionWithStatusMeta::Complete(VersionedTransactionWithStatusMeta {
+            transaction,
+            meta,
+        })
+    }
+
+    pub fn transaction(&mut self) -> SubscribeUpdateTransaction {
+        let index = self.rng.gen_range(0..2_000);
+        SubscribeUpdateTransaction {
+            transaction: Some(convert_to::create_tx_with_meta(
+                &self.transaction_with_meta(),
+                index,
+            )),
+            slot: self.slot,
+            invocations: vec![],
+        }
+    }
+
+    pub fn confirmed_block(&mut self, transactions: usize) -> ConfirmedBlock {
+        ConfirmedBlock {
+            previous_blockhash: self.hash().to_string(),
+            blockhash: self.hash().to_string(),
+            parent_slot: self.slot.saturating_sub(1),
+            transactions: (0..transactions)
+                .map(|_| self.transaction_with_meta())
+                .collect(),
+            rewards: vec![],
+            num_partitions: None,
+            block_time: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .ok()
+                .map(|duration| duration.as_secs() as i64),
+            block_height: Some(self.slot - self.rng.gen_range(10_000_000..20_000_000)),
+        }
+    }
+
+    pub fn block(&mut self, transactions: usize) -> SubscribeUpdateBlock {
+        let block = self.confirmed_block(transactions);
+        convert_to::create_block(self.slot, &block)
+    }
+
+    /// Random account, slot or transaction update
+    pub fn update(&mut self) -> SubscribeUpdate {
+        let update_oneof = match self.rng.gen_range(0..3) {
+            0 => UpdateOneof::Account(self.account()),
+            1 => UpdateOneof::Slot(self.slot_update(SlotStatus::SlotProcessed)),
+            _ => UpdateOneof::Transaction(self.transaction()),
+        };
+        SubscribeUpdate {
+            filters: vec!["fixtures".to_owned()],
+            update_oneof: Some(update_oneof),
+            created_at: Some(from_prost(Timestamp::from(SystemTime::now()))),
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::UpdateGenerator,
+        crate::{
+            convert_from,
+            geyser::{subscribe_update::UpdateOneof, SubscribeUpdate},
+        },
+        pbjson_types::Timestamp,
+        prost::Message,
+        solana_sdk::signature::Signature,
+        std::{env, fs, path::Path},
+    };
+
+    /// Golden captures of encoded updates, one file per released proto version
+    const WIRE_CAPTURES: &str = "./fixtures/wire";
+
+    #[test]
+    fn test_generator_deterministic() {
+        let mut gen1 = UpdateGenerator::new(42);
+        let mut gen2 = UpdateGenerator::new(42);
+        assert_eq!(gen1.account(), gen2.account());
+        assert_eq!(gen1.transaction(), gen2.transaction());
+    }
+
+    #[test]
+    fn test_generator_transaction_valid() {
+        let mut generator = UpdateGenerator::new(42);
+        let tx = generator.transaction().transaction.unwrap();
+        let signature = Signature::try_from(tx.signature.as_slice()).unwrap();
+
+        let tx = convert_from::create_tx_with_meta(tx).unwrap();
+        assert_eq!(tx.transaction_signature(), &signature);
+
+        let versioned = tx.get_transaction();
+        assert!(versioned.verify_with_results().into_iter().all(|ok| ok));
+
+        let meta = tx.get_status_meta().unwrap();
+        assert_eq!(
+            meta.pre_balances.iter().sum::<u64>(),
+            meta.post_balances.iter().sum::<u64>() + meta.fee
+        );
+    }
+
+    #[test]
+    fn test_generator_block() {
+        let mut generator = UpdateGenerator::new(42);
+        let block = generator.block(10);
+        assert_eq!(block.transactions.len(), 10);
+        assert_eq!(block.executed_transaction_count, 10);
+        convert_from::create_block(block).unwrap();
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for seed in 0..64 {
+            let mut generator = UpdateGenerator::new(seed);
+            let mut updates = (0..16).map(|_| generator.update()).collect::<Vec<_>>();
+            updates.push(SubscribeUpdate {
+                filters: vec![],
+                update_oneof: Some(UpdateOneof::Block(generator.block(seed as usize % 4))),
+                created_at: None,
+                sequence: 0,
+                ingested_at: None,
+                replica: String::new(),
+            });
+
+            for update in updates {
+                let encoded = update.encode_to_vec();
+                assert_eq!(encoded.len(), update.encoded_len());
+                let decoded = SubscribeUpdate::decode(encoded.as_slice()).unwrap();
+                assert_eq!(decoded, update, "seed {seed}");
+                assert_eq!(decoded.encode_to_vec(), encoded, "seed {seed}");
+            }
+        }
+    }
+
+    fn create_wire_capture() -> Vec<u8> {
+        let mut generator = UpdateGenerator::new(42);
+        let mut updates = (0..32).map(|_| generator.update()).collect::<Vec<_>>();
+        updates.push(SubscribeUpdate {
+            filters: vec!["fixtures".to_owned()],
+            update_oneof: Some(UpdateOneof::Block(generator.block(3))),
+            created_at: None,
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        });
+
+        let mut buf = vec![];
+        for mut update in updates {
+            update.created_at = Some(Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 0,
+            });
+            update.encode_length_delimited(&mut buf).unwrap();
+        }
+        buf
+    }
+
+    /// Set `YELLOWSTONE_GRPC_WIRE_CAPTURE=1` on the release commit to record
+    /// capture of the released version, captures of previous versions should
+    /// never be changed. `v6.1.0.bin` predates this test, it was encoded with
+    /// types generated by `prost-build` 0.13.5 from the protos of the `v6.1.0` tag
+    #[test]
+    fn test_wire_compat() {
+        let path = Path::new(WIRE_CAPTURES).join(format!("v{}.bin", env!("CARGO_PKG_VERSION")));
+        if env::var_os("YELLOWSTONE_GRPC_WIRE_CAPTURE").is_some() && !path.exists() {
+            fs::write(&path, create_wire_capture()).unwrap();
+        }
+
+        let mut captures = 0;
+        for entry in fs::read_dir(WIRE_CAPTURES).unwrap() {
+            let path = entry.unwrap().path();
+            let data = fs::read(&path).unwrap();
+
+            let mut buf = data.as_slice();
+            let mut encoded = Vec::with_capacity(data.len());
+            while !buf.is_empty() {
+                let update = SubscribeUpdate::decode_length_delimited(&mut buf)
+                    .unwrap_or_else(|error| panic!("{path:?}: {error}"));
+                assert!(update.update_oneof.is_some(), "{path:?}: unknown update");
+                update.encode_length_delimited(&mut encoded).unwrap();
+            }
+            // unknown fields are dropped on decode
+            assert!(encoded == data, "{path:?}: re-encoded update is different");
+            captures += 1;
+        }
+        assert!(captures > 0);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::geyser::{subscribe_update::UpdateOneof, SubscribeUpdate};
+
+/// Canonical identity of an update, two updates with the same identity are duplicates.
+///
+/// Account is identified by `slot` + `pubkey` + `write_version`, transaction by `slot` + `signature`,
+/// slot batch frame by `slot` + `index`. Pings, pongs and filter acknowledgements are not updates of
+/// the stream and don't have identity.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum UpdateIdentity {
+    Account {
+        slot: u64,
+        pubkey: Vec<u8>,
+        write_version: u64,
+    },
+    Slot {
+        slot: u64,
+        status: i32,
+    },
+    Transaction {
+        slot: u64,
+        signature: Vec<u8>,
+    },
+    TransactionStatus {
+        slot: u64,
+        signature: Vec<u8>,
+    },
+    Block {
+        slot: u64,
+        blockhash: String,
+    },
+    BlockMeta {
+        slot: u64,
+        blockhash: String,
+    },
+    Entry {
+        slot: u64,
+        index: u64,
+    },
+    SlotManifest {
+        slot: u64,
+    },
+    SlotBatch {
+        slot: u64,
+        index: u32,
+    },
+}
+
+impl UpdateIdentity {
+    pub fn from_update_oneof(update: &UpdateOneof) -> Option<Self> {
+        Some(match update {
+            UpdateOneof::Account(msg) => {
+                let account = msg.account.as_ref()?;
+                Self::Account {
+                    slot: msg.slot,
+                    pubkey: account.pubkey.clone(),
+                    write_version: account.write_version,
+                }
+            }
+            UpdateOneof::Slot(msg) => Self::Slot {
+                slot: msg.slot,
+                status: msg.status,
+            },
+            UpdateOneof::Transaction(msg) => Self::Transaction {
+                slot: msg.slot,
+                signature: msg.transaction.as_ref()?.signature.clone(),
+            },
+            UpdateOneof::TransactionStatus(msg) => Self::TransactionStatus {
+                slot: msg.slot,
+                signature: msg.signature.clone(),
+            },
+            UpdateOneof::Block(msg) => Self::Block {
+                slot: msg.slot,
+                blockhash: msg.blockhash.clone(),
+            },
+            UpdateOneof::BlockMeta(msg) => Self::BlockMeta {
+                slot: msg.slot,
+                blockhash: msg.blockhash.clone(),
+            },
+            UpdateOneof::Entry(msg) => Self::Entry {
+                slot: msg.slot,
+                index: msg.index,
+            },
+            UpdateOneof::SlotManifest(msg) => Self::SlotManifest { slot: msg.slot },
+            UpdateOneof::SlotBatch(msg) => Self::SlotBatch {
+                slot: msg.slot,
+                index: msg.index,
+            },
+            UpdateOneof::Ping(_) | UpdateOneof::Pong(_) | UpdateOneof::FilterApplied(_) => {
+                return None
+            }
+        })
+    }
+
+    pub const fn slot(&self) -> u64 {
+        match self {
+            Self::Account { slot, .. } => *slot,
+            Self::Slot { slot, .. } => *slot,
+            Self::Transaction { slot, .. } => *slot,
+            Self::TransactionStatus { slot, .. } => *slot,
+            Self::Block { slot, .. } => *slot,
+            Self::BlockMeta { slot, .. } => *slot,
+            Self::Entry { slot, .. } => *slot,
+            Self::SlotManifest { slot } => *slot,
+            Self::SlotBatch { slot, .. } => *slot,
+        }
+    }
+
+    /// FNV-1a hash of the identity, unlike `std::hash::Hash` the value is stable
+    /// across processes, platforms and Rust versions and can be stored or shared.
+    pub fn stable_hash(&self) -> u64 {
+        let mut hasher = StableHasher::default();
+        match self {
+            Self::Account {
+                slot,
+                pubkey,
+                write_version,
+            } => {
+                hasher.write(&[0]);
+                hasher.write(&slot.to_le_bytes());
+                hasher.write_bytes(pubkey);
+                hasher.write(&write_version.to_le_bytes());
+            }
+            Self::Slot { slot, status } => {
+                hasher.write(&[1]);
+                hasher.write(&slot.to_le_bytes());
+                hasher.write(&status.to_le_bytes());
+            }
+            Self::Transaction { slot, signature } => {
+                hasher.write(&[2]);
+                hasher.write(&slot.to_le_bytes());
+                hasher.write_bytes(signature);
+            }
+            Self::TransactionStatus { slot, signature } => {
+                hasher.write(&[3]);
+                hasher.write(&slot.to_le_bytes());
+                hasher.write_bytes(signature);
+            }
+            Self::Block { slot, blockhash } => {
+                hasher.write(&[4]);
+                hasher.write(&slot.to_le_bytes());
+                hasher.write_bytes(blockhash.as_bytes());
+            }
+            Self::BlockMeta { slot, blockhash } => {
+                hasher.write(&[5]);
+                hasher.write(&slot.to_le_bytes());
+                hasher.write_bytes(blockhash.as_bytes());
+            }
+            Self::Entry { slot, index } => {
+                hasher.write(&[6]);
+                hasher.write(&slot.to_le_bytes());
+                hasher.write(&index.to_le_bytes());
+            }
+            Self::SlotManifest { slot } => {
+                hasher.write(&[7]);
+                hasher.write(&slot.to_le_bytes());
+            }
+            Self::SlotBatch { slot, index } => {
+                hasher.write(&[8]);
+                hasher.write(&slot.to_le_bytes());
+                hasher.write(&index.to_le_bytes());
+            }
+        }
+        hasher.0
+    }
+}
+
+#[derive(Debug)]
+struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    // length prefix to avoid collisions between variable-size fields
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+}
+
+impl SubscribeUpdate {
+    pub fn identity(&self) -> Option<UpdateIdentity> {
+        self.update_oneof
+            .as_ref()
+            .and_then(UpdateIdentity::from_update_oneof)
+    }
+
+    /// Same update received with different `filters` or `created_at` (e.g. from
+    /// multiple endpoints) is considered equal
+    pub fn is_same_update(&self, other: &Self) -> bool {
+        match (self.identity(), other.identity()) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::UpdateIdentity,
+        crate::geyser::{
+            subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateAccount,
+            SubscribeUpdateAccountInfo, SubscribeUpdateFilterApplied, SubscribeUpdatePing,
+            SubscribeUpdateSlotBatch, SubscribeUpdateSlotManifest,
+        },
+    };
+
+    fn account_update(filter: &str, write_version: u64) -> SubscribeUpdate {
+        SubscribeUpdate {
+            filters: vec![filter.to_owned()],
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: vec![1; 32],
+                    write_version,
+                    ..Default::default()
+                }),
+                slot: 42,
+                is_startup: false,
+                previous_owner: None,
+                data_omitted: false,
+            })),
+            created_at: None,
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_identity() {
+        let update1 = account_update("a", 1);
+        let update2 = account_update("b", 1);
+        let update3 = account_update("a", 2);
+        assert!(update1.is_same_update(&update2));
+        assert!(!update1.is_same_update(&update3));
+
+        let identity = update1.identity().unwrap();
+        assert_eq!(identity.slot(), 42);
+        assert_eq!(
+            identity.stable_hash(),
+            update2.identity().unwrap().stable_hash()
+        );
+        assert_ne!(
+            identity.stable_hash(),
+            update3.identity().unwrap().stable_hash()
+        );
+
+        let ping = SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Ping(SubscribeUpdatePing {})),
+            ..Default::default()
+        };
+        assert_eq!(ping.identity(), None);
+        let ack = SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::FilterApplied(SubscribeUpdateFilterApplied {
+                request_id: 1,
+            })),
+            ..Default::default()
+        };
+        assert_eq!(ack.identity(), None);
+
+        let batch = |index| SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::SlotBatch(SubscribeUpdateSlotBatch {
+                slot: 42,
+                index,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        assert_eq!(
+            batch(1).identity(),
+            Some(UpdateIdentity::SlotBatch { slot: 42, index: 1 })
+        );
+        assert!(!batch(1).is_same_update(&batch(2)));
+
+        let manifest = SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::SlotManifest(SubscribeUpdateSlotManifest {
+                slot: 42,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        assert_eq!(
+            manifest.identity(),
+            Some(UpdateIdentity::SlotManifest { slot: 42 })
+        );
+    }
+
+    #[test]
+    fn test_stable_hash_value() {
+        // value must never change, it can be persisted by consumers
+        let identity = UpdateIdentity::Entry { slot: 1, index: 2 };
+        assert_eq!(identity.stable_hash(), 0x9f433d58fb10a6ba);
+    }
+}
@@ -0,0 +1,263 @@
+use {
+    crate::{
+        geyser::SubscribeUpdateTransactionInvocation,
+        solana::storage::confirmed_block::{Message, Transaction, TransactionStatusMeta},
+    },
+    std::iter,
+};
+
+/// Outer or inner (CPI) instruction of the transaction.
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionRef<'a> {
+    /// Index of the outer instruction
+    pub instruction_index: usize,
+    /// Index in the inner instructions of the outer instruction, `None` for
+    /// outer instruction
+    pub inner_index: Option<usize>,
+    /// Outer instruction has stack height 1, `None` for inner instructions of
+    /// transactions executed before `stack_height` was introduced
+    pub stack_height: Option<u32>,
+    pub program_id_index: u32,
+    pub accounts: &'a [u8],
+    pub data: &'a [u8],
+    message: &'a Message,
+    meta: &'a TransactionStatusMeta,
+}
+
+impl<'a> InstructionRef<'a> {
+    /// Account key by index in the message keys followed by loaded addresses
+    pub fn account_key(&self, index: u32) -> Option<&'a [u8]> {
+        let mut index = index as usize;
+        for keys in [
+            &self.message.account_keys,
+            &self.meta.loaded_writable_addresses,
+            &self.meta.loaded_readonly_addresses,
+        ] {
+            match keys.get(index) {
+                Some(key) => return Some(key),
+                None => index -= keys.len(),
+            }
+        }
+        None
+    }
+
+    pub fn program_id(&self) -> Option<&'a [u8]> {
+        self.account_key(self.program_id_index)
+    }
+}
+
+/// Outer and inner instructions of the transaction in the order of execution
+pub fn instructions<'a>(
+    transaction: &'a Transaction,
+    meta: &'a TransactionStatusMeta,
+) -> impl Iterator<Item = InstructionRef<'a>> + 'a {
+    transaction.message.iter().flat_map(move |message| {
+        message
+            .instructions
+            .iter()
+            .enumerate()
+            .flat_map(move |(index, ix)| {
+                let outer = InstructionRef {
+                    instruction_index: index,
+                    inner_index: None,
+                    stack_height: Some(1),
+                    program_id_index: ix.program_id_index,
+                    accounts: &ix.accounts,
+                    data: &ix.data,
+                    message,
+                    meta,
+                };
+                let inner = meta
+                    .inner_instructions
+                    .iter()
+                    .filter(move |inner| inner.index as usize == index)
+                    .flat_map(|inner| inner.instructions.iter())
+                    .enumerate()
+                    .map(move |(inner_index, ix)| InstructionRef {
+                        instruction_index: index,
+                        inner_index: Some(inner_index),
+                        stack_height: ix.stack_height,
+                        program_id_index: ix.program_id_index,
+                        accounts: &ix.accounts,
+                        data: &ix.data,
+                        message,
+                        meta,
+                    });
+                iter::once(outer).chain(inner)
+            })
+    })
+}
+
+/// Program invocation by outer or inner (CPI) instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation<'a> {
+    /// Index of the outer instruction
+    pub instruction_index: usize,
+    /// Index in the inner instructions of the outer instruction, `None` for
+    /// outer instruction
+    pub inner_index: Option<usize>,
+    /// Program ids from the outer instruction to the invoked program, for
+    /// transactions executed before `stack_height` was introduced path is
+    /// outer program and invoked program
+    pub path: Vec<&'a [u8]>,
+}
+
+impl Invocation<'_> {
+    /// Invoked program
+    pub fn program_id(&self) -> &[u8] {
+        self.path.last().copied().unwrap_or_default()
+    }
+}
+
+impl From<Invocation<'_>> for SubscribeUpdateTransactionInvocation {
+    fn from(invocation: Invocation<'_>) -> Self {
+        Self {
+            instruction_index: invocation.instruction_index as u32,
+            inner_index: invocation.inner_index.map(|index| index as u32),
+            path: invocation.path.into_iter().map(<[u8]>::to_vec).collect(),
+        }
+    }
+}
+
+/// Programs invoked by outer and inner instructions without paths, programs
+/// can be repeated. Unlike [`invocations`] paths are not collected, so it's
+/// used on the hot path to match programs.
+pub fn invoked_programs<'a>(
+    transaction: &'a Transaction,
+    meta: &'a TransactionStatusMeta,
+) -> impl Iterator<Item = &'a [u8]> + 'a {
+    instructions(transaction, meta).filter_map(|ix| ix.program_id())
+}
+
+/// Program invocations of the transaction in the order of execution, path of
+/// the invocation is collected only when the iterator is advanced
+pub fn invocations<'a>(
+    transaction: &'a Transaction,
+    meta: &'a TransactionStatusMeta,
+) -> impl Iterator<Item = Invocation<'a>> + 'a {
+    let mut path = vec![];
+    instructions(transaction, meta).map(move |ix| {
+        let depth = match ix.inner_index {
+            None => 0,
+            Some(_) => ix
+                .stack_height
+                .map_or(1, |height| height.max(2) as usize - 1),
+        };
+        path.truncate(depth);
+        path.push(ix.program_id().unwrap_or_default());
+        Invocation {
+            instruction_index: ix.instruction_index,
+            inner_index: ix.inner_index,
+            path: path.clone(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{invocations, invoked_programs},
+        crate::solana::storage::confirmed_block::{
+            CompiledInstruction, InnerInstruction, InnerInstructions, Message, Transaction,
+            TransactionStatusMeta,
+        },
+    };
+
+    const fn inner(program_id_index: u32, stack_height: Option<u32>) -> InnerInstruction {
+        InnerInstruction {
+            program_id_index,
+            accounts: vec![],
+            data: vec![],
+            stack_height,
+        }
+    }
+
+    #[test]
+    fn test_invocations() {
+        let transaction = Transaction {
+            message: Some(Message {
+                account_keys: vec![vec![0; 32], vec![1; 32]],
+                instructions: vec![CompiledInstruction {
+                    program_id_index: 0,
+                    accounts: vec![],
+                    data: vec![],
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let meta = TransactionStatusMeta {
+            inner_instructions: vec![InnerInstructions {
+                index: 0,
+                instructions: vec![inner(1, Some(2)), inner(2, Some(3)), inner(2, Some(2))],
+            }],
+            loaded_readonly_addresses: vec![vec![2; 32]],
+            ..Default::default()
+        };
+
+        let paths = invocations(&transaction, &meta)
+            .map(|invocation| {
+                let path = invocation.path.iter().map(|key| key[0]).collect::<Vec<_>>();
+                (invocation.inner_index, path)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            vec![
+                (None, vec![0]),
+                (Some(0), vec![0, 1]),
+                (Some(1), vec![0, 1, 2]),
+                (Some(2), vec![0, 2]),
+            ]
+        );
+
+        // without stack height
+        let meta = TransactionStatusMeta {
+            inner_instructions: vec![InnerInstructions {
+                index: 0,
+                instructions: vec![inner(1, None), inner(1, None)],
+            }],
+            ..Default::default()
+        };
+        let invocations = invocations(&transaction, &meta).collect::<Vec<_>>();
+        assert_eq!(
+            invocations[2].path,
+            vec![[0; 32].as_slice(), [1; 32].as_slice()]
+        );
+        assert_eq!(invocations[2].program_id(), [1; 32].as_slice());
+    }
+
+    #[test]
+    fn test_invoked_programs() {
+        let transaction = Transaction {
+            message: Some(Message {
+                account_keys: vec![vec![0; 32], vec![1; 32]],
+                instructions: vec![CompiledInstruction {
+                    program_id_index: 0,
+                    accounts: vec![],
+                    data: vec![],
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let meta = TransactionStatusMeta {
+            inner_instructions: vec![InnerInstructions {
+                index: 0,
+                // index 3 is out of range
+                instructions: vec![inner(1, Some(2)), inner(2, Some(3)), inner(3, Some(2))],
+            }],
+            loaded_writable_addresses: vec![vec![2; 32]],
+            ..Default::default()
+        };
+
+        let programs = invoked_programs(&transaction, &meta)
+            .map(|key| key[0])
+            .collect::<Vec<_>>();
+        assert_eq!(programs, vec![0, 1, 2]);
+        let expected = invocations(&transaction, &meta)
+            .map(|invocation| invocation.program_id().first().copied())
+            .collect::<Vec<_>>();
+        assert_eq!(expected, vec![Some(0), Some(1), Some(2), None]);
+    }
+}
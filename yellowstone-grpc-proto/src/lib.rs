@@ -4,10 +4,74 @@ pub mod geyser {
     #![allow(clippy::clone_on_ref_ptr)]
     #![allow(clippy::missing_const_for_fn)]
 
-    #[cfg(feature = "tonic")]
     include!(concat!(env!("OUT_DIR"), "/geyser.rs"));
-    #[cfg(not(feature = "tonic"))]
-    include!(concat!(env!("OUT_DIR"), "/no-tonic/geyser.rs"));
+
+    #[cfg(feature = "serde")]
+    include!(concat!(env!("OUT_DIR"), "/geyser.serde.rs"));
+
+    #[cfg(all(test, feature = "serde"))]
+    mod tests {
+        use {
+            super::{
+                subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateAccount,
+                SubscribeUpdateAccountInfo,
+            },
+            pbjson_types::Timestamp,
+        };
+
+        #[test]
+        fn test_subscribe_update_json_roundtrip() {
+            let update = SubscribeUpdate {
+                filters: vec!["client".to_owned()],
+                update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                    account: Some(SubscribeUpdateAccountInfo {
+                        pubkey: vec![1; 32],
+                        lamports: u64::MAX,
+                        owner: vec![2; 32],
+                        executable: false,
+                        rent_epoch: u64::MAX,
+                        data: vec![42; 100],
+                        write_version: 1,
+                        txn_signature: Some(vec![3; 64]),
+                    }),
+                    slot: 42,
+                    is_startup: false,
+                    previous_owner: None,
+                    data_omitted: false,
+                })),
+                created_at: Some(Timestamp {
+                    seconds: 1_700_000_000,
+                    nanos: 123_456_789,
+                }),
+                sequence: 7,
+                ingested_at: Some(Timestamp {
+                    seconds: 1_700_000_000,
+                    nanos: 123_000_000,
+                }),
+                replica: "eu-1".to_owned(),
+            };
+
+            let json = serde_json::to_value(&update).unwrap();
+            // u64 as string, bytes as base64, timestamps as RFC 3339
+            assert_eq!(json["account"]["account"]["lamports"], u64::MAX.to_string());
+            assert_eq!(json["account"]["slot"], "42");
+            assert!(json["account"]["account"]["data"].is_string());
+            assert_eq!(json["sequence"], "7");
+            assert!(json["createdAt"].is_string());
+
+            let decoded: SubscribeUpdate = serde_json::from_value(json).unwrap();
+            assert_eq!(decoded, update);
+        }
+
+        #[test]
+        fn test_subscribe_update_json_multiple_oneof() {
+            let json = serde_json::json!({
+                "ping": {},
+                "pong": { "id": 1 },
+            });
+            assert!(serde_json::from_value::<SubscribeUpdate>(json).is_err());
+        }
+    }
 }
 
 pub mod solana {
@@ -15,53 +79,260 @@ pub mod solana {
 
     pub mod storage {
         pub mod confirmed_block {
-            #[cfg(feature = "tonic")]
             include!(concat!(
                 env!("OUT_DIR"),
                 "/solana.storage.confirmed_block.rs"
             ));
-            #[cfg(not(feature = "tonic"))]
+
+            #[cfg(feature = "serde")]
             include!(concat!(
                 env!("OUT_DIR"),
-                "/no-tonic/solana.storage.confirmed_block.rs"
+                "/solana.storage.confirmed_block.serde.rs"
             ));
         }
     }
 }
 
+/// Messages with `Bytes` instead of `Vec<u8>` for all bytes fields, decoding from
+/// `Bytes` buffer does not copy account data, signatures and other payloads.
+/// Wire format is the same, so messages can be decoded from the regular stream.
+#[cfg(feature = "zero-copy")]
+pub mod zero_copy {
+    pub mod geyser {
+        #![allow(clippy::clone_on_ref_ptr)]
+        #![allow(clippy::missing_const_for_fn)]
+
+        include!(concat!(env!("OUT_DIR"), "/zero-copy/geyser.rs"));
+    }
+
+    pub mod solana {
+        #![allow(clippy::missing_const_for_fn)]
+
+        pub mod storage {
+            pub mod confirmed_block {
+                include!(concat!(
+                    env!("OUT_DIR"),
+                    "/zero-copy/solana.storage.confirmed_block.rs"
+                ));
+            }
+        }
+    }
+
+    pub mod prelude {
+        pub use super::{geyser::*, solana::storage::confirmed_block::*};
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use {
+            super::prelude::{subscribe_update::UpdateOneof, SubscribeUpdate},
+            crate::prelude as proto,
+            prost::{bytes::Bytes, Message},
+        };
+
+        #[test]
+        fn test_decode_without_copy() {
+            let update = proto::SubscribeUpdate {
+                filters: vec!["client".to_owned()],
+                update_oneof: Some(proto::subscribe_update::UpdateOneof::Account(
+                    proto::SubscribeUpdateAccount {
+                        account: Some(proto::SubscribeUpdateAccountInfo {
+                            pubkey: vec![1; 32],
+                            data: vec![42; 1024],
+                            ..Default::default()
+                        }),
+                        slot: 42,
+                        is_startup: false,
+                        previous_owner: None,
+                        data_omitted: false,
+                    },
+                )),
+                created_at: None,
+                sequence: 0,
+                ingested_at: None,
+                replica: String::new(),
+            };
+            let buf = Bytes::from(update.encode_to_vec());
+
+            let decoded = SubscribeUpdate::decode(buf.clone()).unwrap();
+            let Some(UpdateOneof::Account(msg)) = decoded.update_oneof else {
+                panic!("expected account");
+            };
+            let data = msg.account.unwrap().data;
+            assert_eq!(data.as_ref(), [42; 1024].as_slice());
+            assert!(buf.as_ptr_range().contains(&data.as_ptr()));
+        }
+    }
+}
+
 pub mod prelude {
+    // generated service, can be used to implement Geyser-compatible servers (proxy, mock, etc)
+    #[cfg(feature = "tonic")]
+    pub use super::geyser::{
+        geyser_client::GeyserClient,
+        geyser_server::{Geyser, GeyserServer, SERVICE_NAME as GEYSER_SERVICE_NAME},
+    };
     pub use super::{geyser::*, solana::storage::confirmed_block::*};
 }
 
 #[cfg(feature = "tonic")]
 pub use tonic;
-pub use {prost, prost_types};
+pub use {pbjson_types, prost, prost_types};
 
 #[cfg(feature = "plugin")]
 pub mod plugin;
 
+pub mod base58;
+
+pub mod created_at;
+pub mod encoded_len;
+
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+
+pub mod identity;
+
+pub mod invocation;
+
+pub mod manifest;
+
+#[cfg(feature = "convert")]
+pub mod pda;
+
+mod request;
+
+#[cfg(feature = "shm")]
+pub mod shm;
+
+#[cfg(feature = "tonic")]
+pub mod status;
+
+#[cfg(feature = "zstd-dict")]
+pub mod zstd_dict;
+
 #[cfg(feature = "convert")]
 pub mod convert_to {
     use {
         super::prelude as proto,
         solana_sdk::{
-            clock::UnixTimestamp,
+            account::Account,
+            clock::{Slot, UnixTimestamp},
             instruction::CompiledInstruction,
             message::{
                 v0::{LoadedMessage, MessageAddressTableLookup},
-                LegacyMessage, MessageHeader, SanitizedMessage,
+                LegacyMessage, MessageHeader, SanitizedMessage, VersionedMessage,
             },
             pubkey::Pubkey,
             signature::Signature,
-            transaction::{SanitizedTransaction, TransactionError},
+            transaction::{SanitizedTransaction, TransactionError, VersionedTransaction},
             transaction_context::TransactionReturnData,
+            vote,
         },
         solana_transaction_status::{
-            InnerInstruction, InnerInstructions, Reward, RewardType, TransactionStatusMeta,
-            TransactionTokenBalance,
+            ConfirmedBlock, InnerInstruction, InnerInstructions, Reward, RewardType,
+            TransactionStatusMeta, TransactionTokenBalance, TransactionWithStatusMeta,
         },
     };
 
+    pub fn create_block(slot: Slot, block: &ConfirmedBlock) -> proto::SubscribeUpdateBlock {
+        proto::SubscribeUpdateBlock {
+            slot,
+            blockhash: block.blockhash.clone(),
+            rewards: Some(create_rewards_obj(&block.rewards, block.num_partitions)),
+            block_time: block.block_time.map(create_timestamp),
+            block_height: block.block_height.map(create_block_height),
+            parent_slot: block.parent_slot,
+            parent_blockhash: block.previous_blockhash.clone(),
+            executed_transaction_count: block.transactions.len() as u64,
+            transactions: block
+                .transactions
+                .iter()
+                .enumerate()
+                .map(|(index, tx)| create_tx_with_meta(tx, index))
+                .collect(),
+            updated_account_count: 0,
+            accounts: vec![],
+            entries_count: 0,
+            entries: vec![],
+            ticks_count: None,
+        }
+    }
+
+    pub fn create_tx_with_meta(
+        tx: &TransactionWithStatusMeta,
+        index: usize,
+    ) -> proto::SubscribeUpdateTransactionInfo {
+        let (transaction, meta) = match tx {
+            TransactionWithStatusMeta::MissingMetadata(tx) => {
+                (VersionedTransaction::from(tx.clone()), None)
+            }
+            TransactionWithStatusMeta::Complete(tx) => (
+                tx.transaction.clone(),
+                Some(create_transaction_meta(&tx.meta)),
+            ),
+        };
+
+        proto::SubscribeUpdateTransactionInfo {
+            signature: transaction
+                .signatures
+                .first()
+                .map(|signature| <Signature as AsRef<[u8]>>::as_ref(signature).into())
+                .unwrap_or_default(),
+            is_vote: is_simple_vote_transaction(&transaction),
+            transaction: Some(create_transaction_versioned(&transaction)),
+            meta,
+            index: index as u64,
+        }
+    }
+
+    // same rules as `is_simple_vote_transaction` in agave
+    fn is_simple_vote_transaction(tx: &VersionedTransaction) -> bool {
+        if tx.signatures.len() >= 3 {
+            return false;
+        }
+        let VersionedMessage::Legacy(message) = &tx.message else {
+            return false;
+        };
+        match message.instructions.as_slice() {
+            [ix] => {
+                message.account_keys.get(ix.program_id_index as usize) == Some(&vote::program::ID)
+            }
+            _ => false,
+        }
+    }
+
+    pub fn create_transaction_versioned(tx: &VersionedTransaction) -> proto::Transaction {
+        proto::Transaction {
+            signatures: tx
+                .signatures
+                .iter()
+                .map(|signature| <Signature as AsRef<[u8]>>::as_ref(signature).into())
+                .collect(),
+            message: Some(create_message_versioned(&tx.message)),
+        }
+    }
+
+    pub fn create_message_versioned(message: &VersionedMessage) -> proto::Message {
+        match message {
+            VersionedMessage::Legacy(message) => proto::Message {
+                header: Some(create_header(&message.header)),
+                account_keys: create_pubkeys(&message.account_keys),
+                recent_blockhash: message.recent_blockhash.to_bytes().into(),
+                instructions: create_instructions(&message.instructions),
+                versioned: false,
+                address_table_lookups: vec![],
+            },
+            VersionedMessage::V0(message) => proto::Message {
+                header: Some(create_header(&message.header)),
+                account_keys: create_pubkeys(&message.account_keys),
+                recent_blockhash: message.recent_blockhash.to_bytes().into(),
+                instructions: create_instructions(&message.instructions),
+                versioned: true,
+                address_table_lookups: create_lookups(&message.address_table_lookups),
+            },
+        }
+    }
+
     pub fn create_transaction(tx: &SanitizedTransaction) -> proto::Transaction {
         proto::Transaction {
             signatures: tx
@@ -295,6 +566,23 @@ pub mod convert_to {
     pub const fn create_timestamp(timestamp: UnixTimestamp) -> proto::UnixTimestamp {
         proto::UnixTimestamp { timestamp }
     }
+
+    pub fn create_account(
+        pubkey: &Pubkey,
+        account: &Account,
+        write_version: u64,
+    ) -> proto::SubscribeUpdateAccountInfo {
+        proto::SubscribeUpdateAccountInfo {
+            pubkey: pubkey.as_ref().into(),
+            lamports: account.lamports,
+            owner: account.owner.as_ref().into(),
+            executable: account.executable,
+            rent_epoch: account.rent_epoch,
+            data: account.data.clone(),
+            write_version,
+            txn_signature: None,
+        }
+    }
 }
 
 #[cfg(feature = "convert")]
@@ -316,14 +604,42 @@ pub mod convert_from {
             transaction_context::TransactionReturnData,
         },
         solana_transaction_status::{
-            ConfirmedBlock, InnerInstruction, InnerInstructions, Reward, RewardType,
-            RewardsAndNumPartitions, TransactionStatusMeta, TransactionTokenBalance,
-            TransactionWithStatusMeta, VersionedTransactionWithStatusMeta,
+            BlockEncodingOptions, ConfirmedBlock, EncodedConfirmedBlock, InnerInstruction,
+            InnerInstructions, Reward, RewardType, RewardsAndNumPartitions, TransactionDetails,
+            TransactionStatusMeta, TransactionTokenBalance, TransactionWithStatusMeta,
+            UiConfirmedBlock, UiTransactionEncoding, VersionedTransactionWithStatusMeta,
         },
     };
 
     type CreateResult<T> = Result<T, &'static str>;
 
+    pub fn create_block_ui(
+        block: proto::SubscribeUpdateBlock,
+        encoding: UiTransactionEncoding,
+        options: BlockEncodingOptions,
+    ) -> CreateResult<UiConfirmedBlock> {
+        create_block(block)?
+            .encode_with_options(encoding, options)
+            .map_err(|_| "failed to encode block")
+    }
+
+    pub fn create_block_encoded(
+        block: proto::SubscribeUpdateBlock,
+        encoding: UiTransactionEncoding,
+        max_supported_transaction_version: Option<u8>,
+    ) -> CreateResult<EncodedConfirmedBlock> {
+        create_block_ui(
+            block,
+            encoding,
+            BlockEncodingOptions {
+                transaction_details: TransactionDetails::Full,
+                show_rewards: true,
+                max_supported_transaction_version,
+            },
+        )
+        .map(Into::into)
+    }
+
     pub fn create_block(block: proto::SubscribeUpdateBlock) -> CreateResult<ConfirmedBlock> {
         let mut transactions = vec![];
         for tx in block.transactions {
@@ -645,3 +961,99 @@ pub mod convert_from {
         Ok((pubkey, account))
     }
 }
+
+#[cfg(all(test, feature = "convert"))]
+mod tests {
+    use {
+        super::{
+            convert_from, convert_to,
+            geyser::{SubscribeUpdateAccountInfo, SubscribeUpdateBlock},
+        },
+        prost::Message as _,
+        prost_011::Message as _,
+        solana_sdk::{account::Account, pubkey::Pubkey},
+        solana_storage_proto::convert::generated,
+        solana_transaction_status::{ConfirmedBlock, UiTransactionEncoding},
+        std::fs,
+    };
+
+    fn load_predefined() -> Vec<ConfirmedBlock> {
+        fs::read_dir("./fixtures/blocks")
+            .expect("failed to read `blocks` dir")
+            .map(|entry| {
+                let path = entry.expect("failed to read `blocks` dir entry").path();
+                let data = fs::read(path).expect("failed to read block");
+                generated::ConfirmedBlock::decode(data.as_slice())
+                    .expect("failed to decode block")
+                    .try_into()
+                    .expect("failed to convert decoded block")
+            })
+            .collect()
+    }
+
+    fn create_accounts() -> Vec<(Pubkey, Account, u64)> {
+        let mut accounts = vec![];
+        for lamports in [0, 8123] {
+            for executable in [true, false] {
+                for rent_epoch in [0, 4242] {
+                    for data_size in [0, 1024] {
+                        for write_version in [0, 1] {
+                            let account = Account {
+                                lamports,
+                                data: vec![42; data_size],
+                                owner: Pubkey::new_unique(),
+                                executable,
+                                rent_epoch,
+                            };
+                            accounts.push((Pubkey::new_unique(), account, write_version));
+                        }
+                    }
+                }
+            }
+        }
+        accounts
+    }
+
+    #[test]
+    fn test_convert_block() {
+        for mut block in load_predefined() {
+            let slot = block.parent_slot + 1;
+            // old blocks in fixtures have no `block_time` / `block_height`
+            block.block_time.get_or_insert(1_700_000_000);
+            block.block_height.get_or_insert(slot);
+
+            let update = convert_to::create_block(slot, &block);
+            assert_eq!(
+                SubscribeUpdateBlock::decode(update.encode_to_vec().as_slice())
+                    .expect("failed to decode"),
+                update
+            );
+            assert!(convert_from::create_block_encoded(
+                update.clone(),
+                UiTransactionEncoding::Base64,
+                Some(0)
+            )
+            .is_ok());
+
+            // first conversion fills optional meta fields, after that it should be lossless
+            let block = convert_from::create_block(update).expect("failed to convert block");
+            assert_eq!(
+                convert_from::create_block(convert_to::create_block(slot, &block)),
+                Ok(block)
+            );
+        }
+    }
+
+    #[test]
+    fn test_convert_account() {
+        for (pubkey, account, write_version) in create_accounts() {
+            let update = convert_to::create_account(&pubkey, &account, write_version);
+            assert_eq!(
+                SubscribeUpdateAccountInfo::decode(update.encode_to_vec().as_slice())
+                    .expect("failed to decode"),
+                update
+            );
+            assert_eq!(convert_from::create_account(update), Ok((pubkey, account)));
+        }
+    }
+}
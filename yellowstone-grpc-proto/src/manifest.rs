@@ -0,0 +1,189 @@
+use {
+    crate::{
+        geyser::{subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateSlotManifest},
+        identity::UpdateIdentity,
+    },
+    std::collections::BTreeMap,
+};
+
+/// Counters and content hash of updates delivered for a slot, same builder is
+/// used by the server to create the manifest and by the client to verify it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SlotManifestBuilder {
+    accounts: u64,
+    transactions: u64,
+    transactions_status: u64,
+    entries: u64,
+    blocks_meta: u64,
+    blocks: u64,
+    content_hash: u64,
+}
+
+impl SlotManifestBuilder {
+    /// Slot updates, manifests and batches are not part of the manifest
+    pub fn add(&mut self, identity: &UpdateIdentity) {
+        let counter = match identity {
+            UpdateIdentity::Account { .. } => &mut self.accounts,
+            UpdateIdentity::Slot { .. }
+            | UpdateIdentity::SlotManifest { .. }
+            | UpdateIdentity::SlotBatch { .. } => return,
+            UpdateIdentity::Transaction { .. } => &mut self.transactions,
+            UpdateIdentity::TransactionStatus { .. } => &mut self.transactions_status,
+            UpdateIdentity::Block { .. } => &mut self.blocks,
+            UpdateIdentity::BlockMeta { .. } => &mut self.blocks_meta,
+            UpdateIdentity::Entry { .. } => &mut self.entries,
+        };
+        *counter += 1;
+        // sum is used to make hash independent of the order
+        self.content_hash = self.content_hash.wrapping_add(identity.stable_hash());
+    }
+
+    pub fn build(&self, slot: u64, content_hash: bool) -> SubscribeUpdateSlotManifest {
+        SubscribeUpdateSlotManifest {
+            slot,
+            accounts: self.accounts,
+            transactions: self.transactions,
+            transactions_status: self.transactions_status,
+            entries: self.entries,
+            blocks_meta: self.blocks_meta,
+            blocks: self.blocks,
+            content_hash: content_hash.then_some(self.content_hash),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlotManifestCheck {
+    Complete,
+    Mismatch {
+        expected: SubscribeUpdateSlotManifest,
+        received: SubscribeUpdateSlotManifest,
+    },
+}
+
+/// Client side verification of received updates against slot manifests
+#[derive(Debug, Default, Clone)]
+pub struct SlotManifestTracker {
+    slots: BTreeMap<u64, SlotManifestBuilder>,
+}
+
+impl SlotManifestTracker {
+    /// Slots older than the oldest slot with manifest are not removed
+    /// automatically, see [`SlotManifestTracker::remove_before`]
+    pub fn add(&mut self, update: &SubscribeUpdate) -> Option<(u64, SlotManifestCheck)> {
+        let update = update.update_oneof.as_ref()?;
+        if let UpdateOneof::SlotManifest(received) = update {
+            let builder = self.slots.remove(&received.slot).unwrap_or_default();
+            let expected = builder.build(received.slot, received.content_hash.is_some());
+            let check = if expected == *received {
+                SlotManifestCheck::Complete
+            } else {
+                SlotManifestCheck::Mismatch {
+                    expected,
+                    received: *received,
+                }
+            };
+            return Some((received.slot, check));
+        }
+
+        if let Some(identity) = UpdateIdentity::from_update_oneof(update)
+            .filter(|identity| !matches!(identity, UpdateIdentity::Slot { .. }))
+        {
+            self.slots
+                .entry(identity.slot())
+                .or_default()
+                .add(&identity);
+        }
+        None
+    }
+
+    /// Remove state of slots without manifest (e.g. skipped on fork)
+    pub fn remove_before(&mut self, slot: u64) {
+        self.slots = self.slots.split_off(&slot);
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{SlotManifestBuilder, SlotManifestCheck, SlotManifestTracker},
+        crate::geyser::{
+            subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateEntry,
+            SubscribeUpdateSlot, SubscribeUpdateTransactionStatus,
+        },
+    };
+
+    fn update(update: UpdateOneof) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(update),
+            ..Default::default()
+        }
+    }
+
+    fn entry(slot: u64, index: u64) -> SubscribeUpdate {
+        update(UpdateOneof::Entry(SubscribeUpdateEntry {
+            slot,
+            index,
+            ..Default::default()
+        }))
+    }
+
+    fn tx_status(slot: u64, signature: u8) -> SubscribeUpdate {
+        update(UpdateOneof::TransactionStatus(
+            SubscribeUpdateTransactionStatus {
+                slot,
+                signature: vec![signature; 64],
+                ..Default::default()
+            },
+        ))
+    }
+
+    #[test]
+    fn test_tracker() {
+        let updates = [entry(10, 0), tx_status(10, 1), entry(10, 1)];
+
+        let mut builder = SlotManifestBuilder::default();
+        for update in updates.iter().rev() {
+            builder.add(&update.identity().unwrap());
+        }
+        let manifest = builder.build(10, true);
+        assert_eq!(manifest.entries, 2);
+        assert_eq!(manifest.transactions_status, 1);
+        assert!(manifest.content_hash.is_some());
+
+        let mut tracker = SlotManifestTracker::default();
+        for update in updates.iter() {
+            assert_eq!(tracker.add(update), None);
+        }
+        tracker.add(&update(UpdateOneof::Slot(SubscribeUpdateSlot {
+            slot: 10,
+            ..Default::default()
+        })));
+        assert_eq!(
+            tracker.add(&update(UpdateOneof::SlotManifest(manifest))),
+            Some((10, SlotManifestCheck::Complete))
+        );
+        assert!(tracker.is_empty());
+
+        // missed update
+        tracker.add(&entry(11, 0));
+        let check = tracker.add(&update(UpdateOneof::SlotManifest(builder.build(11, false))));
+        assert!(matches!(
+            check,
+            Some((11, SlotManifestCheck::Mismatch { expected, .. })) if expected.entries == 1
+        ));
+
+        tracker.add(&entry(12, 0));
+        tracker.add(&entry(13, 0));
+        tracker.remove_before(13);
+        assert_eq!(tracker.len(), 1);
+    }
+}
@@ -0,0 +1,131 @@
+//! Program derived addresses of `pda` in accounts filters. Same expansion is
+//! used by the server and by clients which subscribe to servers without
+//! `accounts_pda` support.
+
+use {
+    crate::geyser::SubscribeRequestFilterAccountsPda,
+    solana_sdk::pubkey::{ParsePubkeyError, Pubkey, MAX_SEEDS, MAX_SEED_LEN},
+    std::str::FromStr,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PdaError {
+    #[error("invalid program id: {0}")]
+    InvalidProgramId(#[from] ParsePubkeyError),
+    #[error("too many seeds: {count}, max {max} (without bump)")]
+    TooManySeeds { count: usize, max: usize },
+    #[error("seed is too long: {len} bytes, max {max}")]
+    SeedTooLong { len: usize, max: usize },
+    #[error("failed to find a viable bump seed")]
+    NoBumpSeed,
+}
+
+pub type PdaResult<T> = Result<T, PdaError>;
+
+impl SubscribeRequestFilterAccountsPda {
+    fn validate(&self) -> PdaResult<Pubkey> {
+        let program_id = Pubkey::from_str(&self.program_id)?;
+
+        // bump seed is appended on derivation
+        let count = self.seeds_prefix.len() + usize::from(!self.seeds_last.is_empty());
+        if count > MAX_SEEDS - 1 {
+            return Err(PdaError::TooManySeeds {
+                count,
+                max: MAX_SEEDS - 1,
+            });
+        }
+        if let Some(seed) = self
+            .seeds_prefix
+            .iter()
+            .chain(self.seeds_last.iter())
+            .find(|seed| seed.len() > MAX_SEED_LEN)
+        {
+            return Err(PdaError::SeedTooLong {
+                len: seed.len(),
+                max: MAX_SEED_LEN,
+            });
+        }
+
+        Ok(program_id)
+    }
+
+    /// Addresses of the family in order of `seeds_last`
+    pub fn expand(&self) -> PdaResult<Vec<Pubkey>> {
+        let program_id = self.validate()?;
+
+        let mut seeds = self
+            .seeds_prefix
+            .iter()
+            .map(Vec::as_slice)
+            .collect::<Vec<_>>();
+        if self.seeds_last.is_empty() {
+            return Ok(vec![Self::find(&seeds, &program_id)?]);
+        }
+
+        seeds.push(&[]);
+        self.seeds_last
+            .iter()
+            .map(|seed| {
+                *seeds.last_mut().expect("not empty") = seed.as_slice();
+                Self::find(&seeds, &program_id)
+            })
+            .collect()
+    }
+
+    fn find(seeds: &[&[u8]], program_id: &Pubkey) -> PdaResult<Pubkey> {
+        Pubkey::try_find_program_address(seeds, program_id)
+            .map(|(pubkey, _bump)| pubkey)
+            .ok_or(PdaError::NoBumpSeed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::PdaError, crate::geyser::SubscribeRequestFilterAccountsPda,
+        solana_sdk::pubkey::Pubkey,
+    };
+
+    #[test]
+    fn test_expand() {
+        let program_id = Pubkey::new_unique();
+        let mints = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let pda = SubscribeRequestFilterAccountsPda::new(program_id, [b"vault".to_vec()])
+            .with_seeds_last(mints.map(|mint| mint.to_bytes()));
+        assert_eq!(pda.addresses_count(), 2);
+        let expected = mints
+            .iter()
+            .map(|mint| Pubkey::find_program_address(&[b"vault", mint.as_ref()], &program_id).0)
+            .collect::<Vec<_>>();
+        assert_eq!(pda.expand().unwrap(), expected);
+
+        let pda = SubscribeRequestFilterAccountsPda::new(program_id, [b"config".to_vec()]);
+        assert_eq!(pda.addresses_count(), 1);
+        assert_eq!(
+            pda.expand().unwrap(),
+            vec![Pubkey::find_program_address(&[b"config"], &program_id).0]
+        );
+    }
+
+    #[test]
+    fn test_expand_invalid() {
+        let program_id = Pubkey::new_unique();
+
+        let pda = SubscribeRequestFilterAccountsPda::new("invalid", [b"vault".to_vec()]);
+        assert!(matches!(pda.expand(), Err(PdaError::InvalidProgramId(_))));
+
+        let pda = SubscribeRequestFilterAccountsPda::new(program_id, [vec![0; 33]]);
+        assert_eq!(
+            pda.expand(),
+            Err(PdaError::SeedTooLong { len: 33, max: 32 })
+        );
+
+        let pda = SubscribeRequestFilterAccountsPda::new(program_id, vec![vec![0]; 15])
+            .with_seeds_last([vec![1]]);
+        assert_eq!(
+            pda.expand(),
+            Err(PdaError::TooManySeeds { count: 16, max: 15 })
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use {
+    crate::{
+        geyser::{CommitmentLevel as CommitmentLevelProto, GetAccountRequest, GetAccountResponse},
+        plugin::{
+            filter::{limits::FilterBlocklist, message::FilteredUpdate, FilterAccountsDataSlice},
+            message::{CommitmentLevel, Message, MessageAccountInfo, SlotStatus},
+        },
+    },
+    solana_sdk::{clock::Slot, pubkey::Pubkey},
+    std::{
+        collections::{BTreeMap, HashMap, HashSet},
+        sync::Arc,
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum AccountCacheError {
+    #[error("invalid pubkey: {0}")]
+    InvalidPubkey(String),
+    #[error("invalid commitment: {0}")]
+    InvalidCommitment(i32),
+    #[error("account {0} is not found in the cache")]
+    NotFound(Pubkey),
+}
+
+pub type AccountCacheResult<T> = Result<T, AccountCacheError>;
+
+#[derive(Debug, Default)]
+struct PendingSlot {
+    parent: Option<Slot>,
+    accounts: HashMap<Pubkey, Arc<MessageAccountInfo>>,
+}
+
+/// Messages required by the cache, checked before messages are sent to the
+/// cache, so other accounts are never cloned. Selected accounts are remembered
+/// to pass their updates after owner change.
+#[derive(Debug, Clone)]
+pub struct AccountCacheFilter {
+    accounts: HashSet<Pubkey>,
+    owners: HashSet<Pubkey>,
+    selected: HashSet<Pubkey>,
+}
+
+impl AccountCacheFilter {
+    pub fn new(accounts: HashSet<Pubkey>, owners: HashSet<Pubkey>) -> Self {
+        Self {
+            accounts,
+            owners,
+            selected: HashSet::new(),
+        }
+    }
+
+    pub fn is_selected(&mut self, message: &Message) -> bool {
+        match message {
+            Message::Slot(_) => true,
+            Message::Account(msg) => {
+                let pubkey = msg.account.pubkey;
+                if self.accounts.contains(&pubkey) || self.owners.contains(&msg.account.owner) {
+                    self.selected.insert(pubkey);
+                    true
+                } else {
+                    self.selected.contains(&pubkey)
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Recent state of configured accounts and accounts of configured owners for
+/// point lookups. Updates of not finalized slots are kept by slot and read along
+/// the parent chain of the last processed or confirmed slot, so updates on forks
+/// are never returned.
+#[derive(Debug)]
+pub struct AccountCache {
+    accounts: HashSet<Pubkey>,
+    owners: HashSet<Pubkey>,
+    finalized: HashMap<Pubkey, (Slot, Arc<MessageAccountInfo>)>,
+    pending: BTreeMap<Slot, PendingSlot>,
+    processed_slot: Option<Slot>,
+    confirmed_slot: Option<Slot>,
+    finalized_slot: Option<Slot>,
+}
+
+impl AccountCache {
+    pub fn new(accounts: HashSet<Pubkey>, owners: HashSet<Pubkey>) -> Self {
+        Self {
+            accounts,
+            owners,
+            finalized: HashMap::new(),
+            pending: BTreeMap::new(),
+            processed_slot: None,
+            confirmed_slot: None,
+            finalized_slot: None,
+        }
+    }
+
+    /// Number of accounts in the finalized state
+    pub fn len(&self) -> usize {
+        self.finalized.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.finalized.is_empty()
+    }
+
+    /// Account is cached if it is configured, owned by configured owner or was
+    /// owned before (to see owner change)
+    fn is_cached(&self, account: &MessageAccountInfo) -> bool {
+        self.accounts.contains(&account.pubkey)
+            || self.owners.contains(&account.owner)
+            || self.finalized.contains_key(&account.pubkey)
+    }
+
+    fn is_finalized(&self, slot: Slot) -> bool {
+        self.finalized_slot.is_some_and(|last| slot <= last)
+    }
+
+    /// Apply account or slot message, other messages are ignored
+    pub fn update(&mut self, message: &Message) {
+        match message {
+            Message::Account(msg) if !self.is_cached(&msg.account) => {}
+            // startup accounts are already rooted
+            Message::Account(msg) if msg.is_startup => {
+                self.apply(msg.slot, Arc::clone(&msg.account));
+            }
+            Message::Account(msg) => {
+                if self.is_finalized(msg.slot) {
+                    return;
+                }
+                let accounts = &mut self.pending.entry(msg.slot).or_default().accounts;
+                match accounts.get(&msg.account.pubkey) {
+                    Some(account) if account.write_version > msg.account.write_version => {}
+                    _ => {
+                        accounts.insert(msg.account.pubkey, Arc::clone(&msg.account));
+                    }
+                }
+            }
+            Message::Slot(msg) => {
+                if msg.parent.is_some() && !self.is_finalized(msg.slot) {
+                    self.pending.entry(msg.slot).or_default().parent = msg.parent;
+                }
+                match msg.status {
+                    SlotStatus::Processed => {
+                        self.processed_slot = Some(msg.slot);
+                    }
+                    SlotStatus::Confirmed => {
+                        self.confirmed_slot = Some(msg.slot);
+                    }
+                    SlotStatus::Finalized => self.finalize(msg.slot),
+                    SlotStatus::Dead => {
+                        self.pending.remove(&msg.slot);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finalize(&mut self, slot: Slot) {
+        if self.is_finalized(slot) {
+            return;
+        }
+
+        // sometimes we do not receive finalized status for every slot
+        let mut chain = vec![slot];
+        while let Some(parent) = chain
+            .last()
+            .and_then(|slot| self.pending.get(slot))
+            .and_then(|pending| pending.parent)
+        {
+            if self.is_finalized(parent) || !self.pending.contains_key(&parent) {
+                break;
+            }
+            chain.push(parent);
+        }
+
+        self.finalized_slot = Some(slot);
+        for slot in chain.into_iter().rev() {
+            if let Some(pending) = self.pending.remove(&slot) {
+                for account in pending.accounts.into_values() {
+                    self.apply(slot, account);
+                }
+            }
+        }
+
+        // forks
+        self.pending = self.pending.split_off(&(slot + 1));
+    }
+
+    fn apply(&mut self, slot: Slot, account: Arc<MessageAccountInfo>) {
+        match self.finalized.get(&account.pubkey) {
+            Some((last_slot, last))
+                if (*last_slot, last.write_version) > (slot, account.write_version) => {}
+            _ if account.lamports == 0 => {
+                self.finalized.remove(&account.pubkey);
+            }
+            _ => {
+                self.finalized.insert(account.pubkey, (slot, account));
+            }
+        }
+    }
+
+    /// Latest version of the account visible at the last slot with requested commitment
+    pub fn get(
+        &self,
+        pubkey: &Pubkey,
+        commitment: CommitmentLevel,
+    ) -> Option<(Slot, &Arc<MessageAccountInfo>)> {
+        let head = match commitment {
+            CommitmentLevel::Processed => self.processed_slot,
+            CommitmentLevel::Confirmed => self.confirmed_slot,
+            CommitmentLevel::Finalized => None,
+        };
+
+        let mut slot = head.filter(|slot| !self.is_finalized(*slot));
+        while let Some(pending) = slot.and_then(|slot| self.pending.get(&slot)) {
+            let current = slot.expect("defined");
+            if let Some(account) = pending.accounts.get(pubkey) {
+                return (account.lamports > 0).then_some((current, account));
+            }
+            slot = pending.parent.filter(|slot| !self.is_finalized(*slot));
+        }
+
+        self.finalized
+            .get(pubkey)
+            .map(|(slot, account)| (*slot, account))
+    }
+
+    /// Accounts denied by the blocklist are reported as not found
+    pub fn get_account(
+        &self,
+        request: &GetAccountRequest,
+        blocklist: &FilterBlocklist,
+    ) -> AccountCacheResult<GetAccountResponse> {
+        let pubkey = request
+            .pubkey
+            .parse()
+            .map_err(|_error| AccountCacheError::InvalidPubkey(request.pubkey.clone()))?;
+        let commitment = request
+            .commitment
+            .unwrap_or(CommitmentLevelProto::Processed as i32);
+        let commitment = CommitmentLevelProto::try_from(commitment)
+            .map(Into::into)
+            .map_err(|_error| AccountCacheError::InvalidCommitment(commitment))?;
+
+        let (slot, account) = self
+            .get(&pubkey, commitment)
+            .filter(|(_slot, account)| !blocklist.is_account_blocked(account))
+            .ok_or(AccountCacheError::NotFound(pubkey))?;
+        Ok(GetAccountResponse {
+            slot,
+            account: Some(FilteredUpdate::as_subscribe_update_account(
+                account,
+                &FilterAccountsDataSlice::default(),
+            )),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{AccountCache, AccountCacheError, AccountCacheFilter},
+        crate::{
+            geyser::GetAccountRequest,
+            plugin::{
+                filter::limits::FilterBlocklist,
+                message::{
+                    CommitmentLevel, Message, MessageAccount, MessageAccountInfo, MessageSlot,
+                    SlotStatus,
+                },
+            },
+        },
+        prost_types::Timestamp,
+        solana_sdk::{clock::Slot, pubkey::Pubkey},
+        std::{collections::HashSet, sync::Arc},
+    };
+
+    fn account(pubkey: Pubkey, owner: Pubkey, slot: Slot, lamports: u64) -> Message {
+        Message::Account(MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey,
+                lamports,
+                owner,
+                executable: false,
+                rent_epoch: 0,
+                data: vec![],
+                write_version: slot,
+                txn_signature: None,
+            }),
+            slot,
+            is_startup: false,
+            previous_owner: None,
+            created_at: Timestamp::default(),
+        })
+    }
+
+    fn slot(slot: Slot, parent: Slot, status: SlotStatus) -> Message {
+        Message::Slot(MessageSlot {
+            slot,
+            parent: Some(parent),
+            status,
+            dead_error: None,
+            created_at: Timestamp::default(),
+        })
+    }
+
+    #[test]
+    fn test_commitment_and_forks() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let mut cache = AccountCache::new(HashSet::new(), HashSet::from([owner]));
+
+        let lamports = |cache: &AccountCache, commitment| {
+            cache
+                .get(&pubkey, commitment)
+                .map(|(slot, account)| (slot, account.lamports))
+        };
+
+        for message in [
+            account(pubkey, owner, 1, 10),
+            account(other, Pubkey::new_unique(), 1, 10),
+            slot(1, 0, SlotStatus::Processed),
+            slot(1, 0, SlotStatus::Confirmed),
+            slot(1, 0, SlotStatus::Finalized),
+            account(pubkey, owner, 2, 20),
+            slot(2, 1, SlotStatus::Processed),
+            slot(2, 1, SlotStatus::Confirmed),
+            // fork
+            account(pubkey, owner, 3, 30),
+            slot(3, 1, SlotStatus::Processed),
+        ] {
+            cache.update(&message);
+        }
+        assert!(cache.get(&other, CommitmentLevel::Processed).is_none());
+        assert_eq!(lamports(&cache, CommitmentLevel::Processed), Some((3, 30)));
+        assert_eq!(lamports(&cache, CommitmentLevel::Confirmed), Some((2, 20)));
+        assert_eq!(lamports(&cache, CommitmentLevel::Finalized), Some((1, 10)));
+
+        // fork is dropped on finalization, account is closed
+        for message in [
+            account(pubkey, owner, 4, 0),
+            slot(4, 2, SlotStatus::Processed),
+            slot(4, 2, SlotStatus::Confirmed),
+            slot(2, 1, SlotStatus::Finalized),
+        ] {
+            cache.update(&message);
+        }
+        assert_eq!(lamports(&cache, CommitmentLevel::Processed), None);
+        assert_eq!(lamports(&cache, CommitmentLevel::Finalized), Some((2, 20)));
+
+        cache.update(&slot(4, 2, SlotStatus::Finalized));
+        assert_eq!(lamports(&cache, CommitmentLevel::Finalized), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_filter() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let mut filter = AccountCacheFilter::new(HashSet::new(), HashSet::from([owner]));
+
+        assert!(filter.is_selected(&slot(1, 0, SlotStatus::Processed)));
+        assert!(!filter.is_selected(&account(pubkey, other, 1, 10)));
+        assert!(filter.is_selected(&account(pubkey, owner, 1, 10)));
+        // owner is changed
+        assert!(filter.is_selected(&account(pubkey, other, 2, 10)));
+        assert!(!filter.is_selected(&account(other, other, 2, 10)));
+    }
+
+    #[test]
+    fn test_get_account_blocklist() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut cache = AccountCache::new(HashSet::from([pubkey]), HashSet::new());
+        cache.update(&account(pubkey, owner, 1, 10));
+
+        let request = GetAccountRequest {
+            pubkey: pubkey.to_string(),
+            commitment: None,
+        };
+        let response = cache
+            .get_account(&request, &FilterBlocklist::default())
+            .unwrap();
+        assert_eq!(response.account.unwrap().lamports, 10);
+
+        for blocklist in [
+            FilterBlocklist {
+                accounts: HashSet::from([pubkey]),
+                ..Default::default()
+            },
+            FilterBlocklist {
+                owners: HashSet::from([owner]),
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                cache.get_account(&request, &blocklist),
+                Err(AccountCacheError::NotFound(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use {
+    crate::plugin::{
+        filter::message::{
+            FilteredUpdate, FilteredUpdateFilters, FilteredUpdateOneof, FilteredUpdateSlotBatch,
+            FilteredUpdates,
+        },
+        message::{Message, SlotStatus},
+    },
+    prost::Message as _,
+    prost_types::Timestamp,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        time::SystemTime,
+    },
+};
+
+/// Matched accounts and transactions by slot, sent as `SlotBatch` frames after
+/// `Block` message of the slot (block is sealed only when all messages are
+/// received). Updates received after the batch was sent are passed as is.
+#[derive(Debug, Default)]
+pub struct SlotBatches {
+    /// `None` if batches are not requested, otherwise max frame size
+    max_frame_size: Option<usize>,
+    slots: BTreeMap<u64, Vec<FilteredUpdate>>,
+    sent: BTreeSet<u64>,
+}
+
+impl SlotBatches {
+    pub const MAX_FRAME_SIZE_DEFAULT: usize = 4 * 1024 * 1024;
+
+    /// Slots without `Block` message (dead or skipped) are removed after this distance
+    const KEEP_SLOTS: u64 = 64;
+
+    pub const fn new(max_frame_size: Option<usize>) -> Self {
+        Self {
+            max_frame_size,
+            slots: BTreeMap::new(),
+            sent: BTreeSet::new(),
+        }
+    }
+
+    pub fn process(&mut self, message: &Message, updates: FilteredUpdates) -> FilteredUpdates {
+        let Some(max_frame_size) = self.max_frame_size else {
+            return updates;
+        };
+
+        let mut passed = FilteredUpdates::new();
+        for update in updates {
+            let slot = match &update.message {
+                FilteredUpdateOneof::Account(msg) if !msg.is_startup => msg.slot,
+                FilteredUpdateOneof::Transaction(msg) => msg.slot,
+                _ => {
+                    passed.push(update);
+                    continue;
+                }
+            };
+            if self.sent.contains(&slot) {
+                passed.push(update);
+            } else {
+                self.slots.entry(slot).or_default().push(update);
+            }
+        }
+
+        match message {
+            Message::Block(block) => {
+                let slot = block.meta.slot;
+                let mut frames = match self.slots.remove(&slot) {
+                    Some(updates) => Self::build_frames(slot, updates, max_frame_size),
+                    None => vec![],
+                };
+                self.sent.insert(slot);
+
+                // updates of old slots without `Block` message are passed as is
+                if let Some(keep_slot) = slot.checked_sub(Self::KEEP_SLOTS) {
+                    let slots = self.slots.split_off(&keep_slot);
+                    let expired = std::mem::replace(&mut self.slots, slots);
+                    frames.splice(0..0, expired.into_values().flatten());
+                    self.sent = self.sent.split_off(&keep_slot);
+                }
+                passed.extend(frames);
+            }
+            // updates of the dead slot are passed before the status
+            Message::Slot(msg) if msg.status == SlotStatus::Dead => {
+                if let Some(updates) = self.slots.remove(&msg.slot) {
+                    passed.insert_many(0, updates);
+                }
+                self.sent.insert(msg.slot);
+            }
+            _ => {}
+        }
+
+        passed
+    }
+
+    /// Replace options by options of the new filter, pending updates are
+    /// matched by the previous filter, so they are returned as is and
+    /// following updates of these slots are not batched
+    pub fn update_filter(&mut self, batches: Self) -> Vec<FilteredUpdate> {
+        self.max_frame_size = batches.max_frame_size;
+        let slots = std::mem::take(&mut self.slots);
+        self.sent.extend(slots.keys().copied());
+        slots.into_values().flatten().collect()
+    }
+
+    fn build_frames(
+        slot: u64,
+        updates: Vec<FilteredUpdate>,
+        max_frame_size: usize,
+    ) -> Vec<FilteredUpdate> {
+        let mut frames: Vec<(FilteredUpdateFilters, FilteredUpdateSlotBatch)> = vec![];
+        let mut frame_size = 0;
+        for update in updates {
+            let size = update.message.encoded_len();
+            // update larger than the limit is sent in own frame
+            if frames.is_empty() || (frame_size > 0 && frame_size + size > max_frame_size) {
+                frames.push((
+                    FilteredUpdateFilters::new(),
+                    FilteredUpdateSlotBatch {
+                        slot,
+                        index: frames.len() as u32,
+                        is_last: false,
+                        accounts: vec![],
+                        transactions: vec![],
+                    },
+                ));
+                frame_size = 0;
+            }
+
+            let (filters, batch) = frames.last_mut().expect("at least one frame");
+            for name in update.filters {
+                if !filters.contains(&name) {
+                    frame_size += name.as_ref().len();
+                    filters.push(name);
+                }
+            }
+            frame_size += size;
+            match update.message {
+                FilteredUpdateOneof::Account(msg) => batch.accounts.push(msg),
+                FilteredUpdateOneof::Transaction(msg) => batch.transactions.push(msg),
+                _ => unreachable!("only accounts and transactions are batched"),
+            }
+        }
+        if let Some((_filters, batch)) = frames.last_mut() {
+            batch.is_last = true;
+        }
+
+        frames
+            .into_iter()
+            .map(|(filters, batch)| {
+                FilteredUpdate::new(
+                    filters,
+                    FilteredUpdateOneof::slot_batch(Box::new(batch)),
+                    Timestamp::from(SystemTime::now()),
+                )
+            })
+            .collect()
+    }
+}
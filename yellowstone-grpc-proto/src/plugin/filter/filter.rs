@@ -6,18 +6,24 @@ use {
             subscribe_request_filter_accounts_filter_memcmp::Data as AccountsFilterMemcmpOneof,
             CommitmentLevel as CommitmentLevelProto, SubscribeRequest,
             SubscribeRequestAccountsDataSlice, SubscribeRequestFilterAccounts,
-            SubscribeRequestFilterAccountsFilter, SubscribeRequestFilterAccountsFilterLamports,
+            SubscribeRequestFilterAccountsFilterLamports,
+            SubscribeRequestFilterAccountsFilterMemcmp,
+            SubscribeRequestFilterAccountsFilterTokenExtension, SubscribeRequestFilterAccountsPda,
             SubscribeRequestFilterBlocks, SubscribeRequestFilterBlocksMeta,
             SubscribeRequestFilterEntry, SubscribeRequestFilterSlots,
-            SubscribeRequestFilterTransactions,
+            SubscribeRequestFilterTransactions, SubscribeUpdateTransactionInvocation,
         },
+        invocation::{invocations, invoked_programs},
+        pda::PdaError,
         plugin::{
             filter::{
+                batch::SlotBatches,
                 limits::{
-                    FilterLimits, FilterLimitsAccounts, FilterLimitsBlocks, FilterLimitsBlocksMeta,
-                    FilterLimitsCheckError, FilterLimitsEntries, FilterLimitsSlots,
-                    FilterLimitsTransactions,
+                    FilterBlocklist, FilterLimits, FilterLimitsAccounts, FilterLimitsBlocks,
+                    FilterLimitsBlocksMeta, FilterLimitsCheckError, FilterLimitsEntries,
+                    FilterLimitsSlots, FilterLimitsTransactions,
                 },
+                manifest::SlotManifests,
                 message::{
                     FilteredUpdate, FilteredUpdateBlock, FilteredUpdateFilters,
                     FilteredUpdateOneof, FilteredUpdates,
@@ -40,6 +46,7 @@ use {
     spl_token_2022::{generic_token_account::GenericTokenAccount, state::Account as TokenAccount},
     std::{
         collections::{HashMap, HashSet},
+        mem::size_of,
         ops::Range,
         str::FromStr,
         sync::Arc,
@@ -59,6 +66,8 @@ pub enum FilterError {
     InvalidPubkey(#[from] ParsePubkeyError),
     #[error(transparent)]
     InvalidSignature(#[from] ParseSignatureError),
+    #[error("invalid pda: {0}")]
+    InvalidPda(#[from] PdaError),
 
     #[error("Too many filters provided; max {max}")]
     CreateAccountStateMaxFilters { max: usize },
@@ -70,6 +79,14 @@ pub enum FilterError {
     CreateDataSliceOutOfOrder,
     #[error("failed to create filter: data slices overlapped")]
     CreateDataSliceOverlap,
+    #[error("failed to create filter: data slice is out of account data, max: {max}")]
+    CreateDataSliceTooLarge { max: u64 },
+    #[error("`slot_batch` can not be combined with `slot_manifest`")]
+    SlotBatchWithManifest,
+    #[error("`slot_manifest` is disabled by the server")]
+    SlotManifestDisabled,
+    #[error("`owner_changed` is not supported, owners are not tracked by the server")]
+    OwnerChangedNotSupported,
 }
 
 pub type FilterResult<T> = Result<T, FilterError>;
@@ -110,6 +127,10 @@ pub struct Filter {
     commitment: CommitmentLevel,
     accounts_data_slice: FilterAccountsDataSlice,
     ping: Option<i32>,
+    slot_manifest: Option<bool>,
+    slot_batch: Option<usize>,
+    request_id: Option<u64>,
+    blocklist: Arc<FilterBlocklist>,
 }
 
 impl Default for Filter {
@@ -131,6 +152,10 @@ impl Default for Filter {
             commitment: CommitmentLevel::Processed,
             accounts_data_slice: FilterAccountsDataSlice::default(),
             ping: None,
+            slot_manifest: None,
+            slot_batch: None,
+            request_id: None,
+            blocklist: Arc::default(),
         }
     }
 }
@@ -141,6 +166,13 @@ impl Filter {
         limits: &FilterLimits,
         names: &mut FilterNames,
     ) -> FilterResult<Self> {
+        if config.slot_batch.is_some() && config.slot_manifest.is_some() {
+            return Err(FilterError::SlotBatchWithManifest);
+        }
+        if config.slot_manifest.is_some() && limits.slot_manifest_disabled {
+            return Err(FilterError::SlotManifestDisabled);
+        }
+
         Ok(Self {
             accounts: FilterAccounts::new(&config.accounts, &limits.accounts, names)?,
             slots: FilterSlots::new(&config.slots, &limits.slots, names)?,
@@ -165,6 +197,14 @@ impl Filter {
                 limits.accounts.data_slice_max,
             )?,
             ping: config.ping.as_ref().map(|msg| msg.id),
+            slot_manifest: config.slot_manifest.as_ref().map(|msg| msg.content_hash),
+            slot_batch: config.slot_batch.as_ref().map(|msg| {
+                msg.max_frame_size
+                    .filter(|size| *size > 0)
+                    .map_or(SlotBatches::MAX_FRAME_SIZE_DEFAULT, |size| size as usize)
+            }),
+            request_id: config.request_id,
+            blocklist: Arc::clone(&limits.blocklist),
         })
     }
 
@@ -228,19 +268,77 @@ impl Filter {
         ]
     }
 
+    /// Approximate heap size of the pubkey index of the filter, other fields
+    /// are small and not counted
+    pub fn get_memory_estimate(&self) -> usize {
+        let pubkey_set = size_of::<Pubkey>() + size_of::<HashSet<FilterName>>();
+        let transactions = [&self.transactions, &self.transactions_status]
+            .into_iter()
+            .flat_map(|filter| filter.filters.values())
+            .map(|filter| {
+                filter.account_include.len()
+                    + filter.account_exclude.len()
+                    + filter.account_required.len()
+                    + filter.fee_payer.len()
+                    + filter.program_invoked.len()
+            })
+            .sum::<usize>();
+        let blocks = self
+            .blocks
+            .filters
+            .values()
+            .map(|filter| filter.account_include.len())
+            .sum::<usize>();
+        (self.accounts.account.len() + self.accounts.owner.len()) * pubkey_set
+            + (transactions + blocks) * size_of::<Pubkey>()
+    }
+
     pub const fn get_commitment_level(&self) -> CommitmentLevel {
         self.commitment
     }
 
+    /// Lowest commitment of the request and accounts filters with own commitment
+    pub fn get_commitment_level_min(&self) -> CommitmentLevel {
+        self.accounts
+            .commitments
+            .values()
+            .copied()
+            .fold(self.commitment, CommitmentLevel::min)
+    }
+
+    /// `true` if updates of the commitment are used by the request commitment
+    /// or by accounts filters with own commitment
+    pub fn is_commitment_used(&self, commitment: CommitmentLevel) -> bool {
+        commitment == self.commitment
+            || self
+                .accounts
+                .commitments
+                .values()
+                .any(|value| *value == commitment)
+    }
+
     pub fn get_updates(
         &self,
         message: &Message,
         commitment: Option<CommitmentLevel>,
     ) -> FilteredUpdates {
+        if self.is_blocked(message) {
+            return FilteredUpdates::new();
+        }
+        // other commitments are used only by accounts filters with own commitment
+        if commitment.is_some_and(|commitment| commitment != self.commitment)
+            && !matches!(message, Message::Account(_))
+        {
+            return FilteredUpdates::new();
+        }
+
         match message {
-            Message::Account(message) => self
-                .accounts
-                .get_updates(message, &self.accounts_data_slice),
+            Message::Account(message) => self.accounts.get_updates(
+                message,
+                &self.accounts_data_slice,
+                commitment.unwrap_or(self.commitment),
+                self.commitment,
+            ),
             Message::Slot(message) => self.slots.get_updates(message, commitment),
             Message::Transaction(message) => {
                 let mut updates = self.transactions.get_updates(message);
@@ -248,15 +346,102 @@ impl Filter {
                 updates
             }
             Message::Entry(message) => self.entries.get_updates(message),
-            Message::Block(message) => self.blocks.get_updates(message, &self.accounts_data_slice),
+            Message::Block(message) => {
+                self.blocks
+                    .get_updates(message, &self.accounts_data_slice, &self.blocklist)
+            }
             Message::BlockMeta(message) => self.blocks_meta.get_updates(message),
         }
     }
 
+    /// Explain which filters match the message and the first failed condition of
+    /// filters that do not, message is checked with filter commitment
+    pub fn explain(&self, message: &Message) -> Vec<FilterExplain> {
+        let mut explains = match message {
+            Message::Account(message) => self.accounts.explain(message),
+            Message::Slot(message) => self.slots.explain(message, Some(self.commitment)),
+            Message::Transaction(message) => {
+                let mut explains = self.transactions.explain(message);
+                explains.append(&mut self.transactions_status.explain(message));
+                explains
+            }
+            Message::Entry(_) => FilterExplain::matched_all("entries", &self.entries.filters),
+            Message::Block(message) => self.blocks.explain(message, &self.blocklist),
+            Message::BlockMeta(_) => {
+                FilterExplain::matched_all("blocks_meta", &self.blocks_meta.filters)
+            }
+        };
+        if self.is_blocked(message) {
+            for explain in explains.iter_mut() {
+                explain.reason = Some("blocklist");
+            }
+        }
+        explains.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name)));
+        explains
+    }
+
+    /// Account and transaction updates denied by the operator, blocks are
+    /// filtered per transaction and account
+    fn is_blocked(&self, message: &Message) -> bool {
+        match message {
+            Message::Account(message) => self.blocklist.is_account_blocked(&message.account),
+            Message::Transaction(message) => {
+                self.blocklist.is_transaction_blocked(&message.transaction)
+            }
+            _ => false,
+        }
+    }
+
+    /// Per-subscriber state for `slot_manifest`, counts are carried over on
+    /// filter update with [`SlotManifests::update_filter`]
+    pub const fn get_slot_manifests(&self) -> SlotManifests {
+        SlotManifests::new(self.slot_manifest)
+    }
+
+    /// Per-subscriber state for `slot_batch`, should be re-created on filter update
+    pub const fn get_slot_batches(&self) -> SlotBatches {
+        SlotBatches::new(self.slot_batch)
+    }
+
     pub fn get_pong_msg(&self) -> Option<FilteredUpdate> {
         self.ping
             .map(|id| FilteredUpdate::new_empty(FilteredUpdateOneof::pong(id)))
     }
+
+    /// Acknowledgement of the request, should be sent once the filter replaced
+    /// the previous one
+    pub fn get_filter_applied_msg(&self) -> Option<FilteredUpdate> {
+        self.request_id
+            .map(|id| FilteredUpdate::new_empty(FilteredUpdateOneof::filter_applied(id)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterExplain {
+    pub kind: &'static str,
+    pub name: String,
+    /// First failed condition, `None` if filter matched
+    pub reason: Option<&'static str>,
+}
+
+impl FilterExplain {
+    fn new(kind: &'static str, name: &FilterName, result: Result<(), &'static str>) -> Self {
+        Self {
+            kind,
+            name: name.as_ref().to_owned(),
+            reason: result.err(),
+        }
+    }
+
+    fn matched_all<'a>(
+        kind: &'static str,
+        names: impl IntoIterator<Item = &'a FilterName>,
+    ) -> Vec<Self> {
+        names
+            .into_iter()
+            .map(|name| Self::new(kind, name, Ok(())))
+            .collect()
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -268,6 +453,10 @@ struct FilterAccounts {
     owner: HashMap<Pubkey, HashSet<FilterName>>,
     owner_required: HashSet<FilterName>,
     filters: Vec<(FilterName, FilterAccountsState)>,
+    /// Own data slices of filters, global slice is used for other filters
+    data_slices: HashMap<FilterName, FilterAccountsDataSlice>,
+    /// Own commitment of filters, commitment of the request is used for other filters
+    commitments: HashMap<FilterName, CommitmentLevel>,
 }
 
 impl FilterAccounts {
@@ -277,9 +466,21 @@ impl FilterAccounts {
         names: &mut FilterNames,
     ) -> FilterResult<Self> {
         FilterLimits::check_max(configs.len(), limits.max)?;
+        FilterLimits::check_pda_max(
+            configs
+                .values()
+                .flat_map(|filter| filter.pda.iter())
+                .map(SubscribeRequestFilterAccountsPda::addresses_count)
+                .sum(),
+            limits.pda_max,
+        )?;
 
         let mut this = Self::default();
         for (name, filter) in configs {
+            if filter.owner_changed == Some(true) && !limits.owner_changed {
+                return Err(FilterError::OwnerChangedNotSupported);
+            }
+
             this.nonempty_txn_signature
                 .push((names.get(name)?, filter.nonempty_txn_signature));
             if filter.nonempty_txn_signature.is_some() {
@@ -288,18 +489,32 @@ impl FilterAccounts {
             }
 
             FilterLimits::check_any(
-                filter.account.is_empty() && filter.owner.is_empty(),
+                filter.account.is_empty() && filter.owner.is_empty() && filter.pda.is_empty(),
                 limits.any,
             )?;
-            FilterLimits::check_pubkey_max(filter.account.len(), limits.account_max)?;
+            // derived addresses are counted as accounts, checked before expansion
+            let pda_count = filter
+                .pda
+                .iter()
+                .map(SubscribeRequestFilterAccountsPda::addresses_count)
+                .sum::<usize>();
+            FilterLimits::check_pubkey_max(filter.account.len() + pda_count, limits.account_max)?;
             FilterLimits::check_pubkey_max(filter.owner.len(), limits.owner_max)?;
 
+            let mut pda = Vec::with_capacity(pda_count);
+            for family in filter.pda.iter() {
+                pda.extend(family.expand()?);
+            }
+            let pda = pda.into_iter().map(|pubkey| -> FilterResult<Pubkey> {
+                FilterLimits::check_pubkey_reject(&pubkey, &limits.account_reject)?;
+                Ok(pubkey)
+            });
             Self::set(
                 &mut this.account,
                 &mut this.account_required,
                 name,
                 names,
-                Filter::decode_pubkeys(&filter.account, &limits.account_reject),
+                Filter::decode_pubkeys(&filter.account, &limits.account_reject).chain(pda),
             )?;
 
             Self::set(
@@ -311,7 +526,19 @@ impl FilterAccounts {
             )?;
 
             this.filters
-                .push((names.get(name)?, FilterAccountsState::new(&filter.filters)?));
+                .push((names.get(name)?, FilterAccountsState::new(filter)?));
+            if !filter.data_slice.is_empty() {
+                this.data_slices.insert(
+                    names.get(name)?,
+                    FilterAccountsDataSlice::new(&filter.data_slice, limits.data_slice_max)?,
+                );
+            }
+            if filter.commitment.is_some() {
+                this.commitments.insert(
+                    names.get(name)?,
+                    Filter::decode_commitment(filter.commitment)?,
+                );
+            }
         }
         Ok(this)
     }
@@ -336,23 +563,70 @@ impl FilterAccounts {
         Ok(required)
     }
 
+    fn get_match(&self, message: &MessageAccount) -> FilterAccountsMatch<'_> {
+        let mut filter = FilterAccountsMatch::new(self);
+        filter.match_txn_signature(&message.account.txn_signature);
+        filter.match_account(&message.account.pubkey);
+        filter.match_owner(&message.account.owner);
+        filter.match_state(message);
+        filter
+    }
+
     fn get_updates(
         &self,
         message: &MessageAccount,
         accounts_data_slice: &FilterAccountsDataSlice,
+        commitment: CommitmentLevel,
+        commitment_default: CommitmentLevel,
     ) -> FilteredUpdates {
-        let mut filter = FilterAccountsMatch::new(self);
-        filter.match_txn_signature(&message.account.txn_signature);
-        filter.match_account(&message.account.pubkey);
-        filter.match_owner(&message.account.owner);
-        filter.match_data_lamports(&message.account.data, message.account.lamports);
-        let filters = filter.get_filters();
+        let mut filters = self.get_match(message).get_filters();
+        if !self.commitments.is_empty() {
+            filters.retain(|name| {
+                self.commitments
+                    .get(name)
+                    .copied()
+                    .unwrap_or(commitment_default)
+                    == commitment
+            });
+        }
+        if self.data_slices.is_empty() {
+            return filtered_updates_once_owned!(
+                filters,
+                FilteredUpdateOneof::account(message, accounts_data_slice.clone()),
+                message.created_at
+            );
+        }
+
+        // one update per account, so identity of the update is unique: account
+        // matched by filters with different slices is sent with full data
+        let mut data_slices = filters
+            .iter()
+            .map(|name| self.data_slices.get(name).unwrap_or(accounts_data_slice));
+        let data_slice = match data_slices.next() {
+            Some(data_slice) if data_slices.all(|value| value == data_slice) => data_slice.clone(),
+            _ => FilterAccountsDataSlice::default(),
+        };
         filtered_updates_once_owned!(
             filters,
-            FilteredUpdateOneof::account(message, accounts_data_slice.clone()),
+            FilteredUpdateOneof::account(message, data_slice),
             message.created_at
         )
     }
+
+    fn explain(&self, message: &MessageAccount) -> Vec<FilterExplain> {
+        let filter = self.get_match(message);
+        self.filters
+            .iter()
+            .map(|(name, state)| {
+                let result = match filter.check(name.as_ref(), state) {
+                    // detailed reason of the state mismatch
+                    Err("filters") => state.check(message),
+                    result => result,
+                };
+                FilterExplain::new("accounts", name, result)
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -361,51 +635,29 @@ struct FilterAccountsState {
     datasize: Option<usize>,
     token_account_state: bool,
     lamports: Vec<FilterAccountsLamports>,
+    token_extensions: Vec<FilterAccountsTokenExtension>,
+    nonempty_data: bool,
+    owner_changed: bool,
 }
 
 impl FilterAccountsState {
-    fn new(filters: &[SubscribeRequestFilterAccountsFilter]) -> FilterResult<Self> {
+    fn new(filter: &SubscribeRequestFilterAccounts) -> FilterResult<Self> {
         const MAX_FILTERS: usize = 4;
-        const MAX_DATA_SIZE: usize = 128;
-        const MAX_DATA_BASE58_SIZE: usize = 175;
-        const MAX_DATA_BASE64_SIZE: usize = 172;
 
+        let filters = &filter.filters;
         if filters.len() > MAX_FILTERS {
             return Err(FilterError::CreateAccountStateMaxFilters { max: MAX_FILTERS });
         }
 
-        let mut this = Self::default();
+        let mut this = Self {
+            nonempty_data: filter.nonempty_data.unwrap_or_default(),
+            owner_changed: filter.owner_changed.unwrap_or_default(),
+            ..Default::default()
+        };
         for filter in filters {
             match &filter.filter {
                 Some(AccountsFilterDataOneof::Memcmp(memcmp)) => {
-                    let data = match &memcmp.data {
-                        Some(AccountsFilterMemcmpOneof::Bytes(data)) => data.clone(),
-                        Some(AccountsFilterMemcmpOneof::Base58(data)) => {
-                            if data.len() > MAX_DATA_BASE58_SIZE {
-                                return Err(FilterError::CreateAccountState("data too large"));
-                            }
-                            bs58::decode(data)
-                                .into_vec()
-                                .map_err(|_| FilterError::CreateAccountState("invalid base58"))?
-                        }
-                        Some(AccountsFilterMemcmpOneof::Base64(data)) => {
-                            if data.len() > MAX_DATA_BASE64_SIZE {
-                                return Err(FilterError::CreateAccountState("data too large"));
-                            }
-                            base64_engine
-                                .decode(data)
-                                .map_err(|_| FilterError::CreateAccountState("invalid base64"))?
-                        }
-                        None => {
-                            return Err(FilterError::CreateAccountState(
-                                "data for memcmp should be defined",
-                            ))
-                        }
-                    };
-                    if data.len() > MAX_DATA_SIZE {
-                        return Err(FilterError::CreateAccountState("data too large"));
-                    }
-                    this.memcmp.push((memcmp.offset as usize, data));
+                    this.memcmp.push(Self::decode_memcmp(memcmp)?);
                 }
                 Some(AccountsFilterDataOneof::Datasize(datasize)) => {
                     if this.datasize.replace(*datasize as usize).is_some() {
@@ -432,6 +684,20 @@ impl FilterAccountsState {
                     };
                     this.lamports.push(cmp.into());
                 }
+                Some(AccountsFilterDataOneof::TokenExtension(
+                    SubscribeRequestFilterAccountsFilterTokenExtension { extension, memcmp },
+                )) => {
+                    let extension = u16::try_from(*extension)
+                        .ok()
+                        .filter(|extension| *extension != 0)
+                        .ok_or(FilterError::CreateAccountState(
+                            "token extension should be a non-zero ExtensionType",
+                        ))?;
+                    this.token_extensions.push(FilterAccountsTokenExtension {
+                        extension,
+                        memcmp: memcmp.as_ref().map(Self::decode_memcmp).transpose()?,
+                    });
+                }
                 None => {
                     return Err(FilterError::CreateAccountState("filter should be defined"));
                 }
@@ -440,33 +706,144 @@ impl FilterAccountsState {
         Ok(this)
     }
 
+    fn decode_memcmp(
+        memcmp: &SubscribeRequestFilterAccountsFilterMemcmp,
+    ) -> FilterResult<(usize, Vec<u8>)> {
+        const MAX_DATA_SIZE: usize = 128;
+        const MAX_DATA_BASE58_SIZE: usize = 175;
+        const MAX_DATA_BASE64_SIZE: usize = 172;
+
+        let data = match &memcmp.data {
+            Some(AccountsFilterMemcmpOneof::Bytes(data)) => data.clone(),
+            Some(AccountsFilterMemcmpOneof::Base58(data)) => {
+                if data.len() > MAX_DATA_BASE58_SIZE {
+                    return Err(FilterError::CreateAccountState("data too large"));
+                }
+                bs58::decode(data)
+                    .into_vec()
+                    .map_err(|_| FilterError::CreateAccountState("invalid base58"))?
+            }
+            Some(AccountsFilterMemcmpOneof::Base64(data)) => {
+                if data.len() > MAX_DATA_BASE64_SIZE {
+                    return Err(FilterError::CreateAccountState("data too large"));
+                }
+                base64_engine
+                    .decode(data)
+                    .map_err(|_| FilterError::CreateAccountState("invalid base64"))?
+            }
+            None => {
+                return Err(FilterError::CreateAccountState(
+                    "data for memcmp should be defined",
+                ))
+            }
+        };
+        if data.len() > MAX_DATA_SIZE {
+            return Err(FilterError::CreateAccountState("data too large"));
+        }
+        Ok((memcmp.offset as usize, data))
+    }
+
     fn is_empty(&self) -> bool {
         self.memcmp.is_empty()
             && self.datasize.is_none()
             && !self.token_account_state
             && self.lamports.is_empty()
+            && self.token_extensions.is_empty()
+            && !self.nonempty_data
+            && !self.owner_changed
+    }
+
+    fn is_match(&self, message: &MessageAccount) -> bool {
+        self.check(message).is_ok()
     }
 
-    fn is_match(&self, data: &[u8], lamports: u64) -> bool {
+    /// Returns the first failed condition
+    fn check(&self, message: &MessageAccount) -> Result<(), &'static str> {
+        let data = message.account.data.as_slice();
+        let lamports = message.account.lamports;
+        if self.nonempty_data && data.is_empty() {
+            return Err("nonempty_data");
+        }
+        if self.owner_changed && message.previous_owner.is_none() {
+            return Err("owner_changed");
+        }
         if matches!(self.datasize, Some(datasize) if data.len() != datasize) {
-            return false;
+            return Err("datasize");
         }
         if self.token_account_state && !TokenAccount::valid_account_data(data) {
-            return false;
+            return Err("token_account_state");
         }
         if self.lamports.iter().any(|f| !f.is_match(lamports)) {
-            return false;
+            return Err("lamports");
+        }
+        if !self.token_extensions.is_empty()
+            && (message.account.owner != spl_token_2022::id()
+                || !self
+                    .token_extensions
+                    .iter()
+                    .all(|extension| extension.is_match(data)))
+        {
+            return Err("token_extension");
+        }
+        if !self
+            .memcmp
+            .iter()
+            .all(|(offset, bytes)| Self::is_match_memcmp(data, *offset, bytes))
+        {
+            return Err("memcmp");
+        }
+        Ok(())
+    }
+
+    fn is_match_memcmp(data: &[u8], offset: usize, bytes: &[u8]) -> bool {
+        data.get(offset..offset + bytes.len()) == Some(bytes)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FilterAccountsTokenExtension {
+    extension: u16,
+    memcmp: Option<(usize, Vec<u8>)>,
+}
+
+impl FilterAccountsTokenExtension {
+    // mint with extensions is padded to the size of token account, both are
+    // followed by account type and TLV encoded extensions
+    const ACCOUNT_TYPE_OFFSET: usize = 165;
+    const MULTISIG_LEN: usize = 355;
+
+    fn is_match(&self, data: &[u8]) -> bool {
+        Self::extension_data(data, self.extension).is_some_and(|extension| {
+            self.memcmp.as_ref().is_none_or(|(offset, bytes)| {
+                FilterAccountsState::is_match_memcmp(extension, *offset, bytes)
+            })
+        })
+    }
+
+    fn extension_data(data: &[u8], extension: u16) -> Option<&[u8]> {
+        if data.len() == Self::MULTISIG_LEN {
+            return None;
+        }
+        // 1 is `Mint`, 2 is `Account`
+        if !matches!(data.get(Self::ACCOUNT_TYPE_OFFSET), Some(1 | 2)) {
+            return None;
         }
-        for (offset, bytes) in self.memcmp.iter() {
-            if data.len() < *offset + bytes.len() {
-                return false;
+
+        let mut tlv = &data[Self::ACCOUNT_TYPE_OFFSET + 1..];
+        while tlv.len() >= 4 {
+            let kind = u16::from_le_bytes([tlv[0], tlv[1]]);
+            let len = u16::from_le_bytes([tlv[2], tlv[3]]) as usize;
+            // `Uninitialized`, rest of the data is not used
+            if kind == 0 {
+                return None;
             }
-            let data = &data[*offset..*offset + bytes.len()];
-            if data != bytes {
-                return false;
+            let value = tlv.get(4..4 + len)?;
+            if kind == extension {
+                return Some(value);
             }
+            tlv = &tlv[4 + len..];
         }
-        true
+        None
     }
 }
 
@@ -550,40 +927,42 @@ impl<'a> FilterAccountsMatch<'a> {
         Self::extend(&mut self.owner, &self.filter.owner, pubkey)
     }
 
-    fn match_data_lamports(&mut self, data: &[u8], lamports: u64) {
+    fn match_state(&mut self, message: &MessageAccount) {
         for (name, filter) in self.filter.filters.iter() {
-            if filter.is_match(data, lamports) {
+            if filter.is_match(message) {
                 self.data.insert(name.as_ref());
             }
         }
     }
 
+    /// Returns the first failed condition of the filter
+    fn check(&self, name: &str, filter: &FilterAccountsState) -> Result<(), &'static str> {
+        let af = &self.filter;
+
+        // If filter name in required but not in matched => return `false`
+        if af.nonempty_txn_signature_required.contains(name)
+            && !self.nonempty_txn_signature.contains(name)
+        {
+            return Err("nonempty_txn_signature");
+        }
+        if af.account_required.contains(name) && !self.account.contains(name) {
+            return Err("account");
+        }
+        if af.owner_required.contains(name) && !self.owner.contains(name) {
+            return Err("owner");
+        }
+        if !filter.is_empty() && !self.data.contains(name) {
+            return Err("filters");
+        }
+        Ok(())
+    }
+
     fn get_filters(&self) -> FilteredUpdateFilters {
         self.filter
             .filters
             .iter()
-            .filter_map(|(filter_name, filter)| {
-                let name = filter_name.as_ref();
-                let af = &self.filter;
-
-                // If filter name in required but not in matched => return `false`
-                if af.nonempty_txn_signature_required.contains(name)
-                    && !self.nonempty_txn_signature.contains(name)
-                {
-                    return None;
-                }
-                if af.account_required.contains(name) && !self.account.contains(name) {
-                    return None;
-                }
-                if af.owner_required.contains(name) && !self.owner.contains(name) {
-                    return None;
-                }
-                if !filter.is_empty() && !self.data.contains(name) {
-                    return None;
-                }
-
-                Some(filter_name.clone())
-            })
+            .filter(|(filter_name, filter)| self.check(filter_name.as_ref(), filter).is_ok())
+            .map(|(filter_name, _filter)| filter_name.clone())
             .collect()
     }
 }
@@ -601,6 +980,28 @@ impl FilterSlotsInner {
             interslot_updates: filter.interslot_updates.unwrap_or_default(),
         }
     }
+
+    /// Returns the first failed condition
+    fn check(
+        self,
+        message: &MessageSlot,
+        commitment: Option<CommitmentLevel>,
+    ) -> Result<(), &'static str> {
+        if self.filter_by_commitment
+            && commitment.is_none_or(|commitment| commitment != message.status)
+        {
+            return Err("filter_by_commitment");
+        }
+        if !self.interslot_updates
+            && !matches!(
+                message.status,
+                SlotStatus::Processed | SlotStatus::Confirmed | SlotStatus::Finalized
+            )
+        {
+            return Err("interslot_updates");
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Default, Clone)]
@@ -636,22 +1037,8 @@ impl FilterSlots {
         let filters = self
             .filters
             .iter()
-            .filter_map(|(name, inner)| {
-                if (!inner.filter_by_commitment
-                    || commitment
-                        .map(|commitment| commitment == message.status)
-                        .unwrap_or(false))
-                    && (inner.interslot_updates
-                        || matches!(
-                            message.status,
-                            SlotStatus::Processed | SlotStatus::Confirmed | SlotStatus::Finalized
-                        ))
-                {
-                    Some(name.clone())
-                } else {
-                    None
-                }
-            })
+            .filter(|(_name, inner)| inner.check(message, commitment).is_ok())
+            .map(|(name, _inner)| name.clone())
             .collect::<FilteredUpdateFilters>();
         filtered_updates_once_owned!(
             filters,
@@ -659,6 +1046,19 @@ impl FilterSlots {
             message.created_at
         )
     }
+
+    fn explain(
+        &self,
+        message: &MessageSlot,
+        commitment: Option<CommitmentLevel>,
+    ) -> Vec<FilterExplain> {
+        self.filters
+            .iter()
+            .map(|(name, inner)| {
+                FilterExplain::new("slots", name, inner.check(message, commitment))
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -675,6 +1075,108 @@ struct FilterTransactionsInner {
     account_include: HashSet<Pubkey>,
     account_exclude: HashSet<Pubkey>,
     account_required: HashSet<Pubkey>,
+    log_contains: Vec<String>,
+    fee_payer: HashSet<Pubkey>,
+    fee_min: Option<u64>,
+    compute_unit_price_min: Option<u64>,
+    program_invoked: HashSet<Pubkey>,
+}
+
+impl FilterTransactionsInner {
+    /// Returns the first failed condition
+    fn check(&self, message: &MessageTransaction) -> Result<(), &'static str> {
+        if let Some(is_vote) = self.vote {
+            if is_vote != message.transaction.is_vote {
+                return Err("vote");
+            }
+        }
+
+        if let Some(is_failed) = self.failed {
+            if is_failed != message.transaction.meta.err.is_some() {
+                return Err("failed");
+            }
+        }
+
+        if let Some(signature) = &self.signature {
+            let tx_sig = message.transaction.transaction.signatures.first();
+            if Some(signature.as_ref()) != tx_sig.map(|sig| sig.as_ref()) {
+                return Err("signature");
+            }
+        }
+
+        if !self.account_include.is_empty()
+            && self
+                .account_include
+                .intersection(&message.transaction.account_keys)
+                .next()
+                .is_none()
+        {
+            return Err("account_include");
+        }
+
+        if !self.account_exclude.is_empty()
+            && self
+                .account_exclude
+                .intersection(&message.transaction.account_keys)
+                .next()
+                .is_some()
+        {
+            return Err("account_exclude");
+        }
+
+        if !self.account_required.is_empty()
+            && !self
+                .account_required
+                .is_subset(&message.transaction.account_keys)
+        {
+            return Err("account_required");
+        }
+
+        if !self.log_contains.is_empty()
+            && !message.transaction.meta.log_messages.iter().any(|log| {
+                self.log_contains
+                    .iter()
+                    .any(|pattern| log.contains(pattern.as_str()))
+            })
+        {
+            return Err("log_contains");
+        }
+
+        if !self.fee_payer.is_empty()
+            && !message
+                .transaction
+                .transaction
+                .fee_payer()
+                .and_then(|pubkey| Pubkey::try_from(pubkey).ok())
+                .is_some_and(|pubkey| self.fee_payer.contains(&pubkey))
+        {
+            return Err("fee_payer");
+        }
+
+        if self
+            .fee_min
+            .is_some_and(|fee_min| message.transaction.meta.fee < fee_min)
+        {
+            return Err("fee_min");
+        }
+
+        if let Some(price_min) = self.compute_unit_price_min {
+            let price = message.transaction.transaction.compute_unit_price();
+            if price.unwrap_or_default() < price_min {
+                return Err("compute_unit_price_min");
+            }
+        }
+
+        if !self.program_invoked.is_empty()
+            && !invoked_programs(&message.transaction.transaction, &message.transaction.meta)
+                .filter_map(|program_id| Pubkey::try_from(program_id).ok())
+                .any(|program_id| self.program_invoked.contains(&program_id))
+        {
+            return Err("program_invoked");
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -699,7 +1201,12 @@ impl FilterTransactions {
                     && filter.failed.is_none()
                     && filter.account_include.is_empty()
                     && filter.account_exclude.is_empty()
-                    && filter.account_required.is_empty(),
+                    && filter.account_required.is_empty()
+                    && filter.log_contains.is_empty()
+                    && filter.fee_payer.is_empty()
+                    && filter.fee_min.is_none()
+                    && filter.compute_unit_price_min.is_none()
+                    && filter.program_invoked.is_empty(),
                 limits.any,
             )?;
             FilterLimits::check_pubkey_max(
@@ -714,6 +1221,12 @@ impl FilterTransactions {
                 filter.account_required.len(),
                 limits.account_required_max,
             )?;
+            FilterLimits::check_max(filter.log_contains.len(), limits.log_contains_max)?;
+            FilterLimits::check_pubkey_max(filter.fee_payer.len(), limits.fee_payer_max)?;
+            FilterLimits::check_pubkey_max(
+                filter.program_invoked.len(),
+                limits.program_invoked_max,
+            )?;
 
             filters.insert(
                 names.get(name)?,
@@ -739,6 +1252,14 @@ impl FilterTransactions {
                         &filter.account_required,
                         &HashSet::new(),
                     )?,
+                    log_contains: filter.log_contains.clone(),
+                    fee_payer: Filter::decode_pubkeys_into_set(&filter.fee_payer, &HashSet::new())?,
+                    fee_min: filter.fee_min,
+                    compute_unit_price_min: filter.compute_unit_price_min,
+                    program_invoked: Filter::decode_pubkeys_into_set(
+                        &filter.program_invoked,
+                        &HashSet::new(),
+                    )?,
                 },
             );
         }
@@ -749,71 +1270,58 @@ impl FilterTransactions {
     }
 
     pub fn get_updates(&self, message: &MessageTransaction) -> FilteredUpdates {
+        let mut program_invoked = false;
         let filters = self
             .filters
             .iter()
-            .filter_map(|(name, inner)| {
-                if let Some(is_vote) = inner.vote {
-                    if is_vote != message.transaction.is_vote {
-                        return None;
-                    }
-                }
-
-                if let Some(is_failed) = inner.failed {
-                    if is_failed != message.transaction.meta.err.is_some() {
-                        return None;
-                    }
-                }
-
-                if let Some(signature) = &inner.signature {
-                    let tx_sig = message.transaction.transaction.signatures.first();
-                    if Some(signature.as_ref()) != tx_sig.map(|sig| sig.as_ref()) {
-                        return None;
-                    }
-                }
-
-                if !inner.account_include.is_empty()
-                    && inner
-                        .account_include
-                        .intersection(&message.transaction.account_keys)
-                        .next()
-                        .is_none()
-                {
-                    return None;
-                }
-
-                if !inner.account_exclude.is_empty()
-                    && inner
-                        .account_exclude
-                        .intersection(&message.transaction.account_keys)
-                        .next()
-                        .is_some()
-                {
-                    return None;
-                }
+            .filter(|(_name, inner)| inner.check(message).is_ok())
+            .map(|(name, inner)| {
+                program_invoked |= !inner.program_invoked.is_empty();
+                name.clone()
+            })
+            .collect::<FilteredUpdateFilters>();
 
-                if !inner.account_required.is_empty()
-                    && !inner
-                        .account_required
-                        .is_subset(&message.transaction.account_keys)
-                {
-                    return None;
-                }
+        let update = match self.filter_type {
+            FilterTransactionsType::Transaction if program_invoked => {
+                let invocations = self.get_invocations(&filters, message);
+                FilteredUpdateOneof::transaction_with_invocations(message, invocations)
+            }
+            FilterTransactionsType::Transaction => FilteredUpdateOneof::transaction(message),
+            FilterTransactionsType::TransactionStatus => {
+                FilteredUpdateOneof::transaction_status(message)
+            }
+        };
+        filtered_updates_once_owned!(filters, update, message.created_at)
+    }
 
-                Some(name.clone())
+    /// Invocations of programs from `program_invoked` of matched filters
+    fn get_invocations(
+        &self,
+        filters: &FilteredUpdateFilters,
+        message: &MessageTransaction,
+    ) -> Vec<SubscribeUpdateTransactionInvocation> {
+        let is_invoked = |program_id: &[u8]| {
+            Pubkey::try_from(program_id).is_ok_and(|program_id| {
+                self.filters.iter().any(|(name, inner)| {
+                    inner.program_invoked.contains(&program_id) && filters.contains(name)
+                })
             })
-            .collect::<FilteredUpdateFilters>();
+        };
+        invocations(&message.transaction.transaction, &message.transaction.meta)
+            .filter(|invocation| is_invoked(invocation.program_id()))
+            .map(Into::into)
+            .collect()
+    }
 
-        filtered_updates_once_owned!(
-            filters,
-            match self.filter_type {
-                FilterTransactionsType::Transaction => FilteredUpdateOneof::transaction(message),
-                FilterTransactionsType::TransactionStatus => {
-                    FilteredUpdateOneof::transaction_status(message)
-                }
-            },
-            message.created_at
-        )
+    fn explain(&self, message: &MessageTransaction) -> Vec<FilterExplain> {
+        let kind = match self.filter_type {
+            FilterTransactionsType::Transaction => "transactions",
+            FilterTransactionsType::TransactionStatus => "transactions_status",
+        };
+        self.filters
+            .iter()
+            .map(|(name, inner)| FilterExplain::new(kind, name, inner.check(message)))
+            .collect()
     }
 }
 
@@ -905,10 +1413,45 @@ impl FilterBlocks {
         Ok(this)
     }
 
+    /// Block is sent to every filter, filter with `account_include` is matched
+    /// only if the block is sent with at least one transaction or account
+    fn explain(&self, message: &MessageBlock, blocklist: &FilterBlocklist) -> Vec<FilterExplain> {
+        self.filters
+            .iter()
+            .map(|(name, inner)| {
+                let result = if inner.account_include.is_empty() {
+                    Ok(())
+                } else {
+                    let transactions = matches!(inner.include_transactions, None | Some(true))
+                        && message.transactions.iter().any(|tx| {
+                            !blocklist.is_transaction_blocked(tx)
+                                && inner
+                                    .account_include
+                                    .intersection(&tx.account_keys)
+                                    .next()
+                                    .is_some()
+                        });
+                    let accounts = inner.include_accounts == Some(true)
+                        && message.accounts.iter().any(|account| {
+                            !blocklist.is_account_blocked(account)
+                                && inner.account_include.contains(&account.pubkey)
+                        });
+                    if transactions || accounts {
+                        Ok(())
+                    } else {
+                        Err("account_include")
+                    }
+                };
+                FilterExplain::new("blocks", name, result)
+            })
+            .collect()
+    }
+
     fn get_updates(
         &self,
         message: &Arc<MessageBlock>,
         accounts_data_slice: &FilterAccountsDataSlice,
+        blocklist: &FilterBlocklist,
     ) -> FilteredUpdates {
         let mut updates = FilteredUpdates::new();
         for (filter, inner) in self.filters.iter() {
@@ -918,12 +1461,13 @@ impl FilterBlocks {
                     .transactions
                     .iter()
                     .filter_map(|tx| {
-                        if !inner.account_include.is_empty()
-                            && inner
-                                .account_include
-                                .intersection(&tx.account_keys)
-                                .next()
-                                .is_none()
+                        if blocklist.is_transaction_blocked(tx)
+                            || (!inner.account_include.is_empty()
+                                && inner
+                                    .account_include
+                                    .intersection(&tx.account_keys)
+                                    .next()
+                                    .is_none())
                         {
                             None
                         } else {
@@ -941,8 +1485,9 @@ impl FilterBlocks {
                     .accounts
                     .iter()
                     .filter_map(|account| {
-                        if !inner.account_include.is_empty()
-                            && !inner.account_include.contains(&account.pubkey)
+                        if blocklist.is_account_blocked(account)
+                            || (!inner.account_include.is_empty()
+                                && !inner.account_include.contains(&account.pubkey))
                         {
                             None
                         } else {
@@ -1022,15 +1567,23 @@ impl AsRef<[Range<usize>]> for FilterAccountsDataSlice {
 
 impl FilterAccountsDataSlice {
     pub fn new(slices: &[SubscribeRequestAccountsDataSlice], limits: usize) -> FilterResult<Self> {
+        // `MAX_PERMITTED_DATA_LENGTH`
+        const MAX_DATA_LENGTH: u64 = 10 * 1024 * 1024;
+
         FilterLimits::check_max(slices.len(), limits)?;
 
         let slices = slices
             .iter()
-            .map(|s| Range {
-                start: s.offset as usize,
-                end: (s.offset + s.length) as usize,
+            .map(|s| match s.offset.checked_add(s.length) {
+                Some(end) if end <= MAX_DATA_LENGTH => Ok(Range {
+                    start: s.offset as usize,
+                    end: end as usize,
+                }),
+                _ => Err(FilterError::CreateDataSliceTooLarge {
+                    max: MAX_DATA_LENGTH,
+                }),
             })
-            .collect::<Vec<_>>();
+            .collect::<FilterResult<Vec<_>>>()?;
 
         for (i, slice_a) in slices.iter().enumerate() {
             // check order
@@ -1105,20 +1658,37 @@ impl FilterAccountsDataSlice {
 #[cfg(test)]
 mod tests {
     use {
-        super::Filter,
+        super::{Filter, FilterAccountsDataSlice, FilterError, FilterExplain},
         crate::{
             convert_to,
             geyser::{
-                SubscribeRequest, SubscribeRequestFilterAccounts,
-                SubscribeRequestFilterTransactions,
+                subscribe_update::UpdateOneof, CommitmentLevel as CommitmentLevelProto,
+                SubscribeRequest, SubscribeRequestAccountsDataSlice,
+                SubscribeRequestFilterAccounts, SubscribeRequestFilterAccountsFilter,
+                SubscribeRequestFilterAccountsPda, SubscribeRequestFilterBlocks,
+                SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterSlots,
+                SubscribeRequestFilterTransactions, SubscribeRequestSlotBatch,
+                SubscribeRequestSlotManifest, SubscribeUpdateBlockMeta,
+                SubscribeUpdateTransactionInvocation,
             },
+            manifest::{SlotManifestCheck, SlotManifestTracker},
             plugin::{
                 filter::{
-                    limits::FilterLimits,
+                    batch::SlotBatches,
+                    limits::{
+                        FilterBlocklist, FilterLimits, FilterLimitsAccounts, FilterLimitsCheckError,
+                    },
                     message::{FilteredUpdateFilters, FilteredUpdateOneof},
                     name::{FilterName, FilterNames},
                 },
-                message::{Message, MessageTransaction, MessageTransactionInfo},
+                message::{
+                    CommitmentLevel, Message, MessageAccount, MessageAccountInfo, MessageBlock,
+                    MessageBlockMeta, MessageSlot, MessageTransaction, MessageTransactionInfo,
+                    SlotStatus,
+                },
+            },
+            solana::storage::confirmed_block::{
+                CompiledInstruction, InnerInstruction, InnerInstructions,
             },
         },
         prost_types::Timestamp,
@@ -1192,6 +1762,25 @@ mod tests {
         }
     }
 
+    fn create_message_account(pubkey: Pubkey, owner: Pubkey, data: Vec<u8>) -> Message {
+        Message::Account(MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey,
+                lamports: 1_000_000,
+                owner,
+                executable: false,
+                rent_epoch: 0,
+                data,
+                write_version: 1,
+                txn_signature: None,
+            }),
+            slot: 100,
+            is_startup: false,
+            previous_owner: None,
+            created_at: Timestamp::from(SystemTime::now()),
+        })
+    }
+
     #[test]
     fn test_filters_all_empty() {
         // ensure Filter can be created with empty values
@@ -1207,6 +1796,10 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names());
@@ -1224,6 +1817,11 @@ mod tests {
                 account: vec![],
                 owner: vec![],
                 filters: vec![],
+                nonempty_data: None,
+                owner_changed: None,
+                pda: vec![],
+                data_slice: vec![],
+                commitment: None,
             },
         );
 
@@ -1239,6 +1837,10 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
         };
         let mut limit = FilterLimits::default();
         limit.accounts.any = false;
@@ -1260,6 +1862,11 @@ mod tests {
                 account_include: vec![],
                 account_exclude: vec![],
                 account_required: vec![],
+                log_contains: vec![],
+                fee_payer: vec![],
+                fee_min: None,
+                compute_unit_price_min: None,
+                program_invoked: vec![],
             },
         );
 
@@ -1275,6 +1882,10 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
         };
         let mut limit = FilterLimits::default();
         limit.transactions.any = false;
@@ -1295,6 +1906,11 @@ mod tests {
                 account_include: vec![],
                 account_exclude: vec![],
                 account_required: vec![],
+                log_contains: vec![],
+                fee_payer: vec![],
+                fee_min: None,
+                compute_unit_price_min: None,
+                program_invoked: vec![],
             },
         );
 
@@ -1310,6 +1926,10 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
         };
         let mut limit = FilterLimits::default();
         limit.transactions.any = false;
@@ -1336,6 +1956,11 @@ mod tests {
                 account_include,
                 account_exclude: vec![],
                 account_required: vec![],
+                log_contains: vec![],
+                fee_payer: vec![],
+                fee_min: None,
+                compute_unit_price_min: None,
+                program_invoked: vec![],
             },
         );
 
@@ -1351,6 +1976,10 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
@@ -1401,6 +2030,11 @@ mod tests {
                 account_include,
                 account_exclude: vec![],
                 account_required: vec![],
+                log_contains: vec![],
+                fee_payer: vec![],
+                fee_min: None,
+                compute_unit_price_min: None,
+                program_invoked: vec![],
             },
         );
 
@@ -1416,6 +2050,10 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
@@ -1466,6 +2104,11 @@ mod tests {
                 account_include: vec![],
                 account_exclude,
                 account_required: vec![],
+                log_contains: vec![],
+                fee_payer: vec![],
+                fee_min: None,
+                compute_unit_price_min: None,
+                program_invoked: vec![],
             },
         );
 
@@ -1481,6 +2124,10 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
@@ -1517,6 +2164,11 @@ mod tests {
                 account_include,
                 account_exclude: vec![],
                 account_required,
+                log_contains: vec![],
+                fee_payer: vec![],
+                fee_min: None,
+                compute_unit_price_min: None,
+                program_invoked: vec![],
             },
         );
 
@@ -1532,6 +2184,10 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
@@ -1590,6 +2246,11 @@ mod tests {
                 account_include,
                 account_exclude: vec![],
                 account_required,
+                log_contains: vec![],
+                fee_payer: vec![],
+                fee_min: None,
+                compute_unit_price_min: None,
+                program_invoked: vec![],
             },
         );
 
@@ -1605,6 +2266,10 @@ mod tests {
             accounts_data_slice: Vec::new(),
             ping: None,
             from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
         };
         let limit = FilterLimits::default();
         let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
@@ -1616,4 +2281,1001 @@ mod tests {
             assert!(message.filters.is_empty());
         }
     }
+
+    #[test]
+    fn test_transaction_log_contains() {
+        let mut transactions = HashMap::new();
+        transactions.insert(
+            "logs".to_string(),
+            SubscribeRequestFilterTransactions {
+                vote: None,
+                failed: None,
+                signature: None,
+                account_include: vec![],
+                account_exclude: vec![],
+                account_required: vec![],
+                log_contains: vec!["Program log: Instruction: Swap".to_owned()],
+                fee_payer: vec![],
+                fee_min: None,
+                compute_unit_price_min: None,
+                program_invoked: vec![],
+            },
+        );
+
+        let config = SubscribeRequest {
+            accounts: HashMap::new(),
+            slots: HashMap::new(),
+            transactions,
+            transactions_status: HashMap::new(),
+            blocks: HashMap::new(),
+            blocks_meta: HashMap::new(),
+            entry: HashMap::new(),
+            commitment: None,
+            accounts_data_slice: Vec::new(),
+            ping: None,
+            from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
+        };
+        let limit = FilterLimits::default();
+        let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
+
+        let keypair = Keypair::new();
+        let message_transaction = create_message_transaction(&keypair, vec![keypair.pubkey()]);
+        let message = Message::Transaction(message_transaction);
+        assert!(filter.get_updates(&message, None).is_empty());
+
+        let mut message_transaction = create_message_transaction(&keypair, vec![keypair.pubkey()]);
+        Arc::get_mut(&mut message_transaction.transaction)
+            .unwrap()
+            .meta
+            .log_messages = vec!["Program log: Instruction: Swap".to_owned()];
+        let message = Message::Transaction(message_transaction);
+        let updates = filter.get_updates(&message, None);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(
+            updates[0].filters,
+            FilteredUpdateFilters::from_vec(vec![FilterName::new("logs")])
+        );
+    }
+
+    #[test]
+    fn test_transaction_fee() {
+        let keypair = Keypair::new();
+        let mut transactions = HashMap::new();
+        transactions.insert(
+            "fee".to_string(),
+            SubscribeRequestFilterTransactions {
+                vote: None,
+                failed: None,
+                signature: None,
+                account_include: vec![],
+                account_exclude: vec![],
+                account_required: vec![],
+                log_contains: vec![],
+                fee_payer: vec![keypair.pubkey().to_string()],
+                fee_min: Some(10_000),
+                compute_unit_price_min: None,
+                program_invoked: vec![],
+            },
+        );
+
+        let config = SubscribeRequest {
+            accounts: HashMap::new(),
+            slots: HashMap::new(),
+            transactions,
+            transactions_status: HashMap::new(),
+            blocks: HashMap::new(),
+            blocks_meta: HashMap::new(),
+            entry: HashMap::new(),
+            commitment: None,
+            accounts_data_slice: Vec::new(),
+            ping: None,
+            from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
+        };
+        let limit = FilterLimits::default();
+        let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
+
+        let create_message = |keypair: &Keypair, fee: u64| {
+            let mut message_transaction =
+                create_message_transaction(keypair, vec![keypair.pubkey()]);
+            Arc::get_mut(&mut message_transaction.transaction)
+                .unwrap()
+                .meta
+                .fee = fee;
+            Message::Transaction(message_transaction)
+        };
+        assert!(filter
+            .get_updates(&create_message(&keypair, 5_000), None)
+            .is_empty());
+        assert!(filter
+            .get_updates(&create_message(&Keypair::new(), 10_000), None)
+            .is_empty());
+
+        let updates = filter.get_updates(&create_message(&keypair, 10_000), None);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(
+            updates[0].filters,
+            FilteredUpdateFilters::from_vec(vec![FilterName::new("fee")])
+        );
+    }
+
+    #[test]
+    fn test_transaction_program_invoked() {
+        let program_id = Pubkey::new_unique();
+        let mut transactions = HashMap::new();
+        transactions.insert(
+            "cpi".to_string(),
+            SubscribeRequestFilterTransactions {
+                vote: None,
+                failed: None,
+                signature: None,
+                account_include: vec![],
+                account_exclude: vec![],
+                account_required: vec![],
+                log_contains: vec![],
+                fee_payer: vec![],
+                fee_min: None,
+                compute_unit_price_min: None,
+                program_invoked: vec![program_id.to_string()],
+            },
+        );
+
+        let config = SubscribeRequest {
+            accounts: HashMap::new(),
+            slots: HashMap::new(),
+            transactions,
+            transactions_status: HashMap::new(),
+            blocks: HashMap::new(),
+            blocks_meta: HashMap::new(),
+            entry: HashMap::new(),
+            commitment: None,
+            accounts_data_slice: Vec::new(),
+            ping: None,
+            from_slot: None,
+            slot_manifest: None,
+            template: None,
+            slot_batch: None,
+            request_id: None,
+        };
+        let limit = FilterLimits::default();
+        let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
+
+        let keypair = Keypair::new();
+        let outer_program_id = Pubkey::new_unique();
+        let create_message = |invoked: bool| {
+            let mut message_transaction = create_message_transaction(
+                &keypair,
+                vec![keypair.pubkey(), outer_program_id, program_id],
+            );
+            let info = Arc::get_mut(&mut message_transaction.transaction).unwrap();
+            info.transaction.message.as_mut().unwrap().instructions = vec![CompiledInstruction {
+                program_id_index: 1,
+                accounts: vec![2],
+                data: vec![],
+            }];
+            if invoked {
+                info.meta.inner_instructions = vec![InnerInstructions {
+                    index: 0,
+                    instructions: vec![InnerInstruction {
+                        program_id_index: 2,
+                        accounts: vec![],
+                        data: vec![],
+                        stack_height: Some(2),
+                    }],
+                }];
+            }
+            Message::Transaction(message_transaction)
+        };
+        // program passed as account only
+        assert!(filter.get_updates(&create_message(false), None).is_empty());
+
+        let updates = filter.get_updates(&create_message(true), None);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(
+            updates[0].filters,
+            FilteredUpdateFilters::from_vec(vec![FilterName::new("cpi")])
+        );
+        let FilteredUpdateOneof::Transaction(transaction) = &updates[0].message else {
+            panic!("expected transaction update");
+        };
+        assert_eq!(
+            transaction.invocations,
+            vec![SubscribeUpdateTransactionInvocation {
+                instruction_index: 0,
+                inner_index: Some(0),
+                path: vec![
+                    outer_program_id.to_bytes().to_vec(),
+                    program_id.to_bytes().to_vec()
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_slot_manifest() {
+        let mut transactions = HashMap::new();
+        transactions.insert(
+            "txs".to_string(),
+            SubscribeRequestFilterTransactions::default(),
+        );
+        let mut blocks_meta = HashMap::new();
+        blocks_meta.insert("meta".to_string(), SubscribeRequestFilterBlocksMeta {});
+
+        let config = SubscribeRequest {
+            transactions,
+            blocks_meta,
+            slot_manifest: Some(SubscribeRequestSlotManifest { content_hash: true }),
+            ..Default::default()
+        };
+        let limit = FilterLimits::default();
+        let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
+        let mut manifests = filter.get_slot_manifests();
+
+        let keypair = Keypair::new();
+        let message_transaction = create_message_transaction(&keypair, vec![keypair.pubkey()]);
+        let block_meta = Arc::new(MessageBlockMeta {
+            block_meta: SubscribeUpdateBlockMeta {
+                slot: message_transaction.slot,
+                blockhash: Hash::new_unique().to_string(),
+                ..Default::default()
+            },
+            created_at: Timestamp::from(SystemTime::now()),
+        });
+        let slot = message_transaction.slot;
+        let messages = [
+            Message::Transaction(message_transaction),
+            Message::BlockMeta(Arc::clone(&block_meta)),
+            Message::Block(Arc::new(MessageBlock::new(
+                block_meta,
+                vec![],
+                vec![],
+                vec![],
+            ))),
+            Message::Slot(MessageSlot {
+                slot,
+                parent: None,
+                status: SlotStatus::Finalized,
+                dead_error: None,
+                created_at: Timestamp::from(SystemTime::now()),
+            }),
+        ];
+
+        let mut tracker = SlotManifestTracker::default();
+        let mut checks = vec![];
+        for message in messages.iter() {
+            let updates = manifests.process(message, filter.get_updates(message, None));
+            for update in updates {
+                let update = update.as_subscribe_update();
+                if let Some(UpdateOneof::SlotManifest(manifest)) = &update.update_oneof {
+                    assert_eq!(manifest.transactions, 1);
+                    assert_eq!(manifest.blocks_meta, 1);
+                    assert_eq!(manifest.blocks, 0);
+                    assert!(manifest.content_hash.is_some());
+                }
+                checks.extend(tracker.add(&update));
+            }
+        }
+        assert_eq!(checks, vec![(100, SlotManifestCheck::Complete)]);
+
+        // counts are carried over on filter update
+        let mut manifests = filter.get_slot_manifests();
+        for message in messages.iter().take(2) {
+            manifests.process(message, filter.get_updates(message, None));
+        }
+        manifests.update_filter(filter.get_slot_manifests());
+        let updates = manifests.process(&messages[3], filter.get_updates(&messages[3], None));
+        let update = updates.last().unwrap().as_subscribe_update();
+        let Some(UpdateOneof::SlotManifest(manifest)) = update.update_oneof else {
+            panic!("expected slot manifest");
+        };
+        assert_eq!((manifest.transactions, manifest.blocks_meta), (1, 1));
+
+        // manifest is not sent if not requested
+        let filter = Filter::new(
+            &SubscribeRequest::default(),
+            &limit,
+            &mut create_filter_names(),
+        )
+        .unwrap();
+        let mut manifests = filter.get_slot_manifests();
+        assert!(manifests
+            .process(&messages[3], filter.get_updates(&messages[3], None))
+            .is_empty());
+
+        // manifest is rejected if disabled by the server
+        let limit = FilterLimits {
+            slot_manifest_disabled: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Filter::new(&config, &limit, &mut create_filter_names()),
+            Err(FilterError::SlotManifestDisabled)
+        ));
+    }
+
+    #[test]
+    fn test_slot_batch() {
+        let mut transactions = HashMap::new();
+        transactions.insert(
+            "txs".to_string(),
+            SubscribeRequestFilterTransactions::default(),
+        );
+        let mut blocks_meta = HashMap::new();
+        blocks_meta.insert("meta".to_string(), SubscribeRequestFilterBlocksMeta {});
+
+        let mut config = SubscribeRequest {
+            transactions,
+            blocks_meta,
+            slot_batch: Some(SubscribeRequestSlotBatch {
+                max_frame_size: Some(1),
+            }),
+            ..Default::default()
+        };
+        let limit = FilterLimits::default();
+        let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
+        let mut batches = filter.get_slot_batches();
+
+        let keypair = Keypair::new();
+        let message_transaction = create_message_transaction(&keypair, vec![keypair.pubkey()]);
+        let block_meta = Arc::new(MessageBlockMeta {
+            block_meta: SubscribeUpdateBlockMeta {
+                slot: message_transaction.slot,
+                blockhash: Hash::new_unique().to_string(),
+                ..Default::default()
+            },
+            created_at: Timestamp::from(SystemTime::now()),
+        });
+        let messages = [
+            Message::Transaction(message_transaction.clone()),
+            Message::Transaction(message_transaction.clone()),
+            Message::BlockMeta(Arc::clone(&block_meta)),
+            Message::Block(Arc::new(MessageBlock::new(
+                block_meta,
+                vec![],
+                vec![],
+                vec![],
+            ))),
+            // late update is sent as is
+            Message::Transaction(message_transaction),
+        ];
+
+        let mut received = vec![];
+        for message in messages.iter() {
+            for update in batches.process(message, filter.get_updates(message, None)) {
+                received.push(match update.as_subscribe_update().update_oneof {
+                    Some(UpdateOneof::SlotBatch(batch)) => {
+                        assert_eq!(batch.transactions.len(), 1);
+                        assert_eq!(update.filters.as_slice(), [FilterName::new("txs")]);
+                        format!("batch {} {} {}", batch.slot, batch.index, batch.is_last)
+                    }
+                    Some(UpdateOneof::BlockMeta(_)) => "meta".to_owned(),
+                    Some(UpdateOneof::Transaction(_)) => "tx".to_owned(),
+                    update => panic!("unexpected update: {update:?}"),
+                });
+            }
+        }
+        assert_eq!(
+            received,
+            ["meta", "batch 100 0 false", "batch 100 1 true", "tx"]
+        );
+
+        // can not be combined with manifest
+        config.slot_manifest = Some(SubscribeRequestSlotManifest::default());
+        assert!(matches!(
+            Filter::new(&config, &limit, &mut create_filter_names()),
+            Err(FilterError::SlotBatchWithManifest)
+        ));
+    }
+
+    #[test]
+    fn test_slot_batch_pending() {
+        let config = SubscribeRequest {
+            transactions: HashMap::from([(
+                "txs".to_owned(),
+                SubscribeRequestFilterTransactions::default(),
+            )]),
+            slot_batch: Some(SubscribeRequestSlotBatch::default()),
+            ..Default::default()
+        };
+        let limit = FilterLimits::default();
+        let filter = Filter::new(&config, &limit, &mut create_filter_names()).unwrap();
+        let keypair = Keypair::new();
+        let message_transaction = create_message_transaction(&keypair, vec![keypair.pubkey()]);
+        let message = Message::Transaction(message_transaction.clone());
+        let slot = message_transaction.slot;
+        let message_slot = |slot, status| {
+            Message::Slot(MessageSlot {
+                slot,
+                parent: None,
+                status,
+                dead_error: None,
+                created_at: Timestamp::from(SystemTime::now()),
+            })
+        };
+        let message_block = |slot| {
+            let block_meta = Arc::new(MessageBlockMeta {
+                block_meta: SubscribeUpdateBlockMeta {
+                    slot,
+                    ..Default::default()
+                },
+                created_at: Timestamp::from(SystemTime::now()),
+            });
+            Message::Block(Arc::new(MessageBlock::new(
+                block_meta,
+                vec![],
+                vec![],
+                vec![],
+            )))
+        };
+        let process = |batches: &mut SlotBatches, message: &Message| {
+            batches
+                .process(message, filter.get_updates(message, None))
+                .len()
+        };
+
+        // updates of the dead slot are passed as is
+        let mut batches = filter.get_slot_batches();
+        assert_eq!(process(&mut batches, &message), 0);
+        assert_eq!(
+            process(&mut batches, &message_slot(slot, SlotStatus::Dead)),
+            1
+        );
+        assert_eq!(process(&mut batches, &message), 1);
+
+        // updates of old slots without block are passed as is
+        let mut batches = filter.get_slot_batches();
+        assert_eq!(process(&mut batches, &message), 0);
+        assert_eq!(process(&mut batches, &message_block(slot + 1)), 0);
+        assert_eq!(process(&mut batches, &message_block(slot + 100)), 1);
+
+        // pending updates are returned on filter update
+        let mut batches = filter.get_slot_batches();
+        assert_eq!(process(&mut batches, &message), 0);
+        assert_eq!(batches.update_filter(filter.get_slot_batches()).len(), 1);
+        assert_eq!(process(&mut batches, &message), 1);
+    }
+
+    #[test]
+    fn test_accounts_nonempty_data() {
+        let owner = Pubkey::new_unique();
+        let config = SubscribeRequest {
+            accounts: HashMap::from([(
+                "owner".to_owned(),
+                SubscribeRequestFilterAccounts::with_owner([owner]).with_nonempty_data(),
+            )]),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        for (data, matched) in [(vec![], false), (vec![1], true)] {
+            let message = create_message_account(Pubkey::new_unique(), owner, data);
+            assert_eq!(filter.get_updates(&message, None).len(), matched as usize);
+        }
+    }
+
+    #[test]
+    fn test_accounts_token_extension() {
+        // `MetadataPointer`: authority and metadata address
+        const METADATA_POINTER: u16 = 18;
+        let metadata = Pubkey::new_unique();
+        let mut data = vec![0; 165];
+        data.push(1);
+        data.extend_from_slice(&METADATA_POINTER.to_le_bytes());
+        data.extend_from_slice(&64u16.to_le_bytes());
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(metadata.as_ref());
+
+        let create_filter = |filter| {
+            let config = SubscribeRequest {
+                accounts: HashMap::from([(
+                    "token_2022".to_owned(),
+                    SubscribeRequestFilterAccounts::with_owner([spl_token_2022::id()])
+                        .add_filter(filter),
+                )]),
+                ..Default::default()
+            };
+            Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names(),
+            )
+            .unwrap()
+        };
+        let create_message =
+            |owner, data| create_message_account(Pubkey::new_unique(), owner, data);
+
+        for (filter, matched) in [
+            (
+                SubscribeRequestFilterAccountsFilter::token_extension(METADATA_POINTER),
+                true,
+            ),
+            (
+                SubscribeRequestFilterAccountsFilter::token_extension_memcmp(
+                    METADATA_POINTER,
+                    32,
+                    metadata.to_bytes(),
+                ),
+                true,
+            ),
+            (
+                SubscribeRequestFilterAccountsFilter::token_extension_memcmp(
+                    METADATA_POINTER,
+                    32,
+                    Pubkey::new_unique().to_bytes(),
+                ),
+                false,
+            ),
+            // `TransferFeeConfig`
+            (
+                SubscribeRequestFilterAccountsFilter::token_extension(1),
+                false,
+            ),
+        ] {
+            let filter = create_filter(filter);
+            let message = create_message(spl_token_2022::id(), data.clone());
+            assert_eq!(filter.get_updates(&message, None).len(), matched as usize);
+        }
+
+        // base state without extensions
+        let filter = create_filter(SubscribeRequestFilterAccountsFilter::token_extension(
+            METADATA_POINTER,
+        ));
+        let message = create_message(spl_token_2022::id(), vec![0; 165]);
+        assert!(filter.get_updates(&message, None).is_empty());
+
+        let config = SubscribeRequest {
+            accounts: HashMap::from([(
+                "token_2022".to_owned(),
+                SubscribeRequestFilterAccounts::default()
+                    .add_filter(SubscribeRequestFilterAccountsFilter::token_extension(0)),
+            )]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names()
+            ),
+            Err(FilterError::CreateAccountState(_))
+        ));
+    }
+
+    #[test]
+    fn test_accounts_pda() {
+        let program_id = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let pda = SubscribeRequestFilterAccountsPda::new(program_id, [b"vault".to_vec()])
+            .with_seeds_last([mint.to_bytes()]);
+        let config = SubscribeRequest {
+            accounts: HashMap::from([(
+                "vaults".to_owned(),
+                SubscribeRequestFilterAccounts::default().add_pda(pda),
+            )]),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        let (vault, _bump) = Pubkey::find_program_address(&[b"vault", mint.as_ref()], &program_id);
+        for (pubkey, matched) in [(vault, true), (Pubkey::new_unique(), false)] {
+            let message = create_message_account(pubkey, program_id, vec![]);
+            assert_eq!(filter.get_updates(&message, None).len(), matched as usize);
+        }
+
+        // derived addresses are counted in `account_max`
+        let limits = FilterLimits {
+            accounts: FilterLimitsAccounts {
+                account_max: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            Filter::new(&config, &limits, &mut create_filter_names()),
+            Err(FilterError::LimitsCheck(
+                FilterLimitsCheckError::MaxPubkey { max: 0 }
+            ))
+        ));
+
+        // derived addresses of all filters are limited before expansion
+        let pda = SubscribeRequestFilterAccountsPda::new(program_id, [b"vault".to_vec()])
+            .with_seeds_last((0..600u32).map(|index| index.to_le_bytes()));
+        let config = SubscribeRequest {
+            accounts: HashMap::from([
+                (
+                    "vaults1".to_owned(),
+                    SubscribeRequestFilterAccounts::default().add_pda(pda.clone()),
+                ),
+                (
+                    "vaults2".to_owned(),
+                    SubscribeRequestFilterAccounts::default().add_pda(pda),
+                ),
+            ]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Filter::new(
+                &config,
+                &FilterLimits::default(),
+                &mut create_filter_names()
+            ),
+            Err(FilterError::LimitsCheck(FilterLimitsCheckError::MaxPda {
+                max: 1_000
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_blocklist() {
+        let blocked_account = Pubkey::new_unique();
+        let blocked_owner = Pubkey::new_unique();
+        let config = SubscribeRequest {
+            accounts: HashMap::from([(
+                "all".to_owned(),
+                SubscribeRequestFilterAccounts::default(),
+            )]),
+            transactions: HashMap::from([(
+                "all".to_owned(),
+                SubscribeRequestFilterTransactions::default(),
+            )]),
+            ..Default::default()
+        };
+        let limits = FilterLimits {
+            blocklist: Arc::new(FilterBlocklist {
+                accounts: [blocked_account].into(),
+                owners: [blocked_owner].into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let filter = Filter::new(&config, &limits, &mut create_filter_names()).unwrap();
+
+        for (owner, matched) in [(Pubkey::new_unique(), true), (blocked_owner, false)] {
+            let message = create_message_account(Pubkey::new_unique(), owner, vec![]);
+            assert_eq!(filter.get_updates(&message, None).len(), matched as usize);
+        }
+
+        let keypair = Keypair::new();
+        for (account, matched) in [(Pubkey::new_unique(), true), (blocked_account, false)] {
+            let message = Message::Transaction(create_message_transaction(
+                &keypair,
+                vec![keypair.pubkey(), account],
+            ));
+            assert_eq!(filter.get_updates(&message, None).len(), matched as usize);
+            let reason = (!matched).then_some("blocklist");
+            assert!(filter
+                .explain(&message)
+                .iter()
+                .all(|explain| explain.reason == reason));
+        }
+    }
+
+    #[test]
+    fn test_accounts_owner_changed() {
+        let config = SubscribeRequest {
+            accounts: HashMap::from([(
+                "owner_changed".to_owned(),
+                SubscribeRequestFilterAccounts::default().with_owner_changed(),
+            )]),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        let account = Arc::new(MessageAccountInfo {
+            pubkey: Pubkey::new_unique(),
+            lamports: 0,
+            owner: Pubkey::default(),
+            executable: false,
+            rent_epoch: 0,
+            data: vec![],
+            write_version: 1,
+            txn_signature: None,
+        });
+        for (previous_owner, matched) in [(None, false), (Some(Pubkey::new_unique()), true)] {
+            let message = Message::Account(MessageAccount {
+                account: Arc::clone(&account),
+                slot: 100,
+                is_startup: false,
+                previous_owner,
+                created_at: Timestamp::from(SystemTime::now()),
+            });
+            let updates = filter.get_updates(&message, None);
+            assert_eq!(updates.len(), matched as usize);
+            for update in updates {
+                let UpdateOneof::Account(msg) = update.as_subscribe_update().update_oneof.unwrap()
+                else {
+                    panic!("expected account");
+                };
+                assert_eq!(
+                    msg.previous_owner,
+                    previous_owner.map(|owner| owner.to_bytes().to_vec())
+                );
+            }
+        }
+
+        // rejected if owners are not tracked
+        let limits = FilterLimits {
+            accounts: FilterLimitsAccounts {
+                owner_changed: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            Filter::new(&config, &limits, &mut create_filter_names()),
+            Err(FilterError::OwnerChangedNotSupported)
+        ));
+    }
+
+    #[test]
+    fn test_explain() {
+        let account = Pubkey::new_unique();
+        let config = SubscribeRequest {
+            accounts: HashMap::from([
+                ("any".to_owned(), SubscribeRequestFilterAccounts::default()),
+                (
+                    "nonempty".to_owned(),
+                    SubscribeRequestFilterAccounts::default().with_nonempty_data(),
+                ),
+                (
+                    "owner".to_owned(),
+                    SubscribeRequestFilterAccounts {
+                        owner: vec![Pubkey::new_unique().to_string()],
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            transactions: HashMap::from([
+                (
+                    "all".to_owned(),
+                    SubscribeRequestFilterTransactions::default(),
+                ),
+                (
+                    "fee".to_owned(),
+                    SubscribeRequestFilterTransactions {
+                        fee_min: Some(10_000),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            blocks: HashMap::from([
+                ("all".to_owned(), SubscribeRequestFilterBlocks::default()),
+                (
+                    "account".to_owned(),
+                    SubscribeRequestFilterBlocks {
+                        account_include: vec![account.to_string()],
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "no_transactions".to_owned(),
+                    SubscribeRequestFilterBlocks {
+                        account_include: vec![account.to_string()],
+                        include_transactions: Some(false),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+        let explain = |kind, name: &str, reason| FilterExplain {
+            kind,
+            name: name.to_owned(),
+            reason,
+        };
+
+        let message = create_message_account(Pubkey::new_unique(), Pubkey::default(), vec![]);
+        assert_eq!(
+            filter.explain(&message),
+            vec![
+                explain("accounts", "any", None),
+                explain("accounts", "nonempty", Some("nonempty_data")),
+                explain("accounts", "owner", Some("owner")),
+            ]
+        );
+
+        let keypair = Keypair::new();
+        let message =
+            Message::Transaction(create_message_transaction(&keypair, vec![keypair.pubkey()]));
+        assert_eq!(
+            filter.explain(&message),
+            vec![
+                explain("transactions", "all", None),
+                explain("transactions", "fee", Some("fee_min")),
+            ]
+        );
+
+        let create_block = |transactions| {
+            let block_meta = Arc::new(MessageBlockMeta {
+                block_meta: SubscribeUpdateBlockMeta {
+                    slot: 100,
+                    ..Default::default()
+                },
+                created_at: Timestamp::from(SystemTime::now()),
+            });
+            Message::Block(Arc::new(MessageBlock::new(
+                block_meta,
+                transactions,
+                vec![],
+                vec![],
+            )))
+        };
+        let transaction = create_message_transaction(&keypair, vec![keypair.pubkey(), account]);
+        assert_eq!(
+            filter.explain(&create_block(vec![transaction.transaction])),
+            vec![
+                explain("blocks", "account", None),
+                explain("blocks", "all", None),
+                explain("blocks", "no_transactions", Some("account_include")),
+            ]
+        );
+        assert_eq!(
+            filter.explain(&create_block(vec![])),
+            vec![
+                explain("blocks", "account", Some("account_include")),
+                explain("blocks", "all", None),
+                explain("blocks", "no_transactions", Some("account_include")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_accounts_filter_data_slice() {
+        let owner = Pubkey::new_unique();
+        let pubkey = Pubkey::new_unique();
+        let config = SubscribeRequest {
+            accounts: HashMap::from([
+                (
+                    "global".to_owned(),
+                    SubscribeRequestFilterAccounts::with_owner([owner]),
+                ),
+                (
+                    "amount".to_owned(),
+                    SubscribeRequestFilterAccounts::with_account([pubkey]).with_data_slice(64, 8),
+                ),
+            ]),
+            ..Default::default()
+        }
+        .with_accounts_data_slice(0, 32);
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+
+        let create_message = |pubkey| create_message_account(pubkey, owner, (0..165).collect());
+        let get_updates = |message| {
+            filter
+                .get_updates(&message, None)
+                .into_iter()
+                .map(|update| {
+                    let FilteredUpdateOneof::Account(msg) = update.message else {
+                        panic!("expected account update");
+                    };
+                    let mut filters = update
+                        .filters
+                        .iter()
+                        .map(|name| name.as_ref().to_owned())
+                        .collect::<Vec<_>>();
+                    filters.sort();
+                    (filters, msg.data_slice.get_slice(&msg.account.data))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // slice of the filter
+        assert_eq!(
+            get_updates(create_message(Pubkey::new_unique())),
+            vec![(vec!["global".to_owned()], (0..32).collect())]
+        );
+        // account is sent once, with full data if slices are different
+        assert_eq!(
+            get_updates(create_message(pubkey)),
+            vec![(
+                vec!["amount".to_owned(), "global".to_owned()],
+                (0..165).collect()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_accounts_filter_commitment() {
+        let owner = Pubkey::new_unique();
+        let config = SubscribeRequest {
+            accounts: HashMap::from([
+                (
+                    "processed".to_owned(),
+                    SubscribeRequestFilterAccounts::with_owner([owner]),
+                ),
+                (
+                    "finalized".to_owned(),
+                    SubscribeRequestFilterAccounts::with_owner([owner])
+                        .with_commitment(CommitmentLevelProto::Finalized),
+                ),
+            ]),
+            slots: HashMap::from([("slots".to_owned(), SubscribeRequestFilterSlots::default())]),
+            ..Default::default()
+        };
+        let filter = Filter::new(
+            &config,
+            &FilterLimits::default(),
+            &mut create_filter_names(),
+        )
+        .unwrap();
+        assert!(filter.is_commitment_used(CommitmentLevel::Processed));
+        assert!(!filter.is_commitment_used(CommitmentLevel::Confirmed));
+        assert!(filter.is_commitment_used(CommitmentLevel::Finalized));
+
+        let message = create_message_account(Pubkey::new_unique(), owner, vec![]);
+        for (commitment, expected) in [
+            (None, "processed"),
+            (Some(CommitmentLevel::Processed), "processed"),
+            (Some(CommitmentLevel::Finalized), "finalized"),
+        ] {
+            let updates = filter.get_updates(&message, commitment);
+            assert_eq!(updates.len(), 1);
+            assert_eq!(updates[0].filters.len(), 1);
+            assert_eq!(updates[0].filters[0].as_ref(), expected);
+        }
+
+        // only accounts are delivered at the commitment of accounts filters
+        let message = Message::Slot(MessageSlot {
+            slot: 100,
+            parent: None,
+            status: SlotStatus::Finalized,
+            dead_error: None,
+            created_at: Timestamp::from(SystemTime::now()),
+        });
+        assert_eq!(filter.get_updates(&message, None).len(), 1);
+        assert!(filter
+            .get_updates(&message, Some(CommitmentLevel::Finalized))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_accounts_data_slice_too_large() {
+        for (offset, length) in [(u64::MAX, 1), (0, u64::MAX / 2), (10 * 1024 * 1024, 1)] {
+            let slices = [SubscribeRequestAccountsDataSlice { offset, length }];
+            assert!(matches!(
+                FilterAccountsDataSlice::new(&slices, 1),
+                Err(FilterError::CreateDataSliceTooLarge { .. })
+            ));
+        }
+
+        let slices = [SubscribeRequestAccountsDataSlice {
+            offset: 10 * 1024 * 1024 - 1,
+            length: 1,
+        }];
+        let data_slice = FilterAccountsDataSlice::new(&slices, 1).unwrap();
+        assert!(data_slice.get_slice(&[0; 32]).is_empty());
+    }
 }
@@ -1,7 +1,11 @@
 use {
+    crate::{
+        invocation::invoked_programs,
+        plugin::message::{MessageAccountInfo, MessageTransactionInfo},
+    },
     serde::{de, Deserialize, Deserializer},
     solana_sdk::pubkey::Pubkey,
-    std::collections::HashSet,
+    std::{collections::HashSet, sync::Arc},
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -12,6 +16,8 @@ pub enum FilterLimitsCheckError {
     Any,
     #[error("Max amount of Pubkeys reached, only {max} allowed")]
     MaxPubkey { max: usize },
+    #[error("Max amount of PDA addresses reached, only {max} allowed")]
+    MaxPda { max: usize },
     #[error("Pubkey {pubkey} in filters is not allowed")]
     PubkeyReject { pubkey: Pubkey },
 }
@@ -28,6 +34,11 @@ pub struct FilterLimits {
     pub blocks: FilterLimitsBlocks,
     pub blocks_meta: FilterLimitsBlocksMeta,
     pub entries: FilterLimitsEntries,
+    #[serde(deserialize_with = "FilterBlocklist::deserialize_arc")]
+    pub blocklist: Arc<FilterBlocklist>,
+    /// Reject `slot_manifest` in requests, per-slot counters are kept for
+    /// every subscription with manifest until the slot is finalized
+    pub slot_manifest_disabled: bool,
 }
 
 impl FilterLimits {
@@ -55,6 +66,14 @@ impl FilterLimits {
         }
     }
 
+    pub const fn check_pda_max(len: usize, max: usize) -> FilterLimitsCheckResult {
+        if len <= max {
+            Ok(())
+        } else {
+            Err(FilterLimitsCheckError::MaxPda { max })
+        }
+    }
+
     pub fn check_pubkey_reject(pubkey: &Pubkey, set: &HashSet<Pubkey>) -> FilterLimitsCheckResult {
         if !set.contains(pubkey) {
             Ok(())
@@ -64,6 +83,54 @@ impl FilterLimits {
     }
 }
 
+/// Operator deny rules, matched updates are never sent regardless of filters
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterBlocklist {
+    /// Updates of these accounts and transactions with them
+    #[serde(deserialize_with = "deserialize_pubkey_set")]
+    pub accounts: HashSet<Pubkey>,
+    /// Updates of accounts owned by these programs
+    #[serde(deserialize_with = "deserialize_pubkey_set")]
+    pub owners: HashSet<Pubkey>,
+    /// Transactions invoking these programs (including inner instructions)
+    #[serde(deserialize_with = "deserialize_pubkey_set")]
+    pub programs: HashSet<Pubkey>,
+}
+
+impl FilterBlocklist {
+    fn deserialize_arc<'de, D>(deserializer: D) -> Result<Arc<Self>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Self::deserialize(deserializer).map(Arc::new)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.owners.is_empty() && self.programs.is_empty()
+    }
+
+    pub fn is_account_blocked(&self, account: &MessageAccountInfo) -> bool {
+        self.accounts.contains(&account.pubkey) || self.owners.contains(&account.owner)
+    }
+
+    pub fn is_transaction_blocked(&self, transaction: &MessageTransactionInfo) -> bool {
+        if !self.accounts.is_empty()
+            && transaction
+                .account_keys
+                .iter()
+                .any(|pubkey| self.accounts.contains(pubkey))
+        {
+            return true;
+        }
+
+        !self.programs.is_empty()
+            && invoked_programs(&transaction.transaction, &transaction.meta)
+                .filter_map(|program_id| Pubkey::try_from(program_id).ok())
+                .any(|program_id| self.programs.contains(&program_id))
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 #[serde(default, deny_unknown_fields)]
 pub struct FilterLimitsAccounts {
@@ -76,6 +143,13 @@ pub struct FilterLimitsAccounts {
     #[serde(deserialize_with = "deserialize_pubkey_set")]
     pub owner_reject: HashSet<Pubkey>,
     pub data_slice_max: usize,
+    /// Addresses derived from `pda` of all filters of the request, every
+    /// address costs a few hashes on filter creation, so it's always limited
+    pub pda_max: usize,
+    /// `owner_changed` is allowed only if the server tracks owners, set by the
+    /// server and not by the config
+    #[serde(skip)]
+    pub owner_changed: bool,
 }
 
 impl Default for FilterLimitsAccounts {
@@ -88,6 +162,8 @@ impl Default for FilterLimitsAccounts {
             owner_max: usize::MAX,
             owner_reject: HashSet::new(),
             data_slice_max: usize::MAX,
+            pda_max: 1_000,
+            owner_changed: true,
         }
     }
 }
@@ -119,6 +195,12 @@ pub struct FilterLimitsTransactions {
     pub account_exclude_max: usize,
     #[serde(deserialize_with = "deserialize_usize_str")]
     pub account_required_max: usize,
+    #[serde(deserialize_with = "deserialize_usize_str")]
+    pub log_contains_max: usize,
+    #[serde(deserialize_with = "deserialize_usize_str")]
+    pub fee_payer_max: usize,
+    #[serde(deserialize_with = "deserialize_usize_str")]
+    pub program_invoked_max: usize,
 }
 
 impl Default for FilterLimitsTransactions {
@@ -130,6 +212,9 @@ impl Default for FilterLimitsTransactions {
             account_include_reject: HashSet::new(),
             account_exclude_max: usize::MAX,
             account_required_max: usize::MAX,
+            log_contains_max: usize::MAX,
+            fee_payer_max: usize::MAX,
+            program_invoked_max: usize::MAX,
         }
     }
 }
@@ -213,7 +298,7 @@ fn deserialize_pubkey_set<'de, D>(deserializer: D) -> Result<HashSet<Pubkey>, D:
 where
     D: Deserializer<'de>,
 {
-    Vec::<&str>::deserialize(deserializer)?
+    Vec::<String>::deserialize(deserializer)?
         .into_iter()
         .map(|value| {
             value
@@ -0,0 +1,80 @@
+use {
+    crate::{
+        identity::UpdateIdentity,
+        manifest::SlotManifestBuilder,
+        plugin::{
+            filter::message::{FilteredUpdate, FilteredUpdateOneof, FilteredUpdates},
+            message::{Message, SlotStatus},
+        },
+    },
+    std::collections::BTreeMap,
+};
+
+/// Updates delivered to the subscriber by slot, manifest is added after
+/// finalized `Slot` message of the slot (all messages of the slot are sent
+/// before it with any commitment)
+#[derive(Debug, Default)]
+pub struct SlotManifests {
+    /// `None` if manifest is not requested, otherwise `content_hash` flag
+    content_hash: Option<bool>,
+    slots: BTreeMap<u64, SlotManifestBuilder>,
+}
+
+impl SlotManifests {
+    /// Slots which are never finalized (dead or forked off) are removed after
+    /// this distance
+    const KEEP_SLOTS: u64 = 64;
+
+    pub const fn new(content_hash: Option<bool>) -> Self {
+        Self {
+            content_hash,
+            slots: BTreeMap::new(),
+        }
+    }
+
+    /// Counts of not finalized slots are kept if the new filter requests
+    /// manifest too, updates matched by the previous filter stay counted
+    pub fn update_filter(&mut self, new: Self) {
+        self.content_hash = new.content_hash;
+        if self.content_hash.is_none() {
+            self.slots.clear();
+        }
+    }
+
+    pub fn process(&mut self, message: &Message, mut updates: FilteredUpdates) -> FilteredUpdates {
+        let Some(content_hash) = self.content_hash else {
+            return updates;
+        };
+
+        for identity in updates
+            .iter()
+            .filter_map(|update| update.message.identity())
+            .filter(|identity| !matches!(identity, UpdateIdentity::Slot { .. }))
+        {
+            self.slots
+                .entry(identity.slot())
+                .or_default()
+                .add(&identity);
+        }
+
+        match message {
+            Message::Slot(msg) if msg.status == SlotStatus::Finalized => {
+                let manifest = self
+                    .slots
+                    .remove(&msg.slot)
+                    .unwrap_or_default()
+                    .build(msg.slot, content_hash);
+                updates.push(FilteredUpdate::new_empty(
+                    FilteredUpdateOneof::slot_manifest(manifest),
+                ));
+
+                if let Some(keep_slot) = msg.slot.checked_sub(Self::KEEP_SLOTS) {
+                    self.slots = self.slots.split_off(&keep_slot);
+                }
+            }
+            _ => {}
+        }
+
+        updates
+    }
+}
@@ -1,12 +1,16 @@
 use {
     crate::{
+        created_at::{from_prost, to_prost},
         geyser::{
             subscribe_update::UpdateOneof, SlotStatus as SlotStatusProto, SubscribeUpdate,
             SubscribeUpdateAccount, SubscribeUpdateAccountInfo, SubscribeUpdateBlock,
-            SubscribeUpdateEntry, SubscribeUpdatePing, SubscribeUpdatePong, SubscribeUpdateSlot,
-            SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo,
+            SubscribeUpdateEntry, SubscribeUpdateFilterApplied, SubscribeUpdatePing,
+            SubscribeUpdatePong, SubscribeUpdateSlot, SubscribeUpdateSlotBatch,
+            SubscribeUpdateSlotManifest, SubscribeUpdateTransaction,
+            SubscribeUpdateTransactionInfo, SubscribeUpdateTransactionInvocation,
             SubscribeUpdateTransactionStatus,
         },
+        identity::UpdateIdentity,
         plugin::{
             filter::{name::FilterName, FilterAccountsDataSlice},
             message::{
@@ -16,7 +20,10 @@ use {
         },
         solana::storage::confirmed_block,
     },
-    bytes::buf::{Buf, BufMut},
+    bytes::{
+        buf::{Buf, BufMut},
+        Bytes,
+    },
     prost::{
         encoding::{
             encode_key, encode_varint, encoded_len_varint, key_len, message, DecodeContext,
@@ -26,9 +33,10 @@ use {
     },
     prost_types::Timestamp,
     smallvec::SmallVec,
-    solana_sdk::signature::Signature,
+    solana_sdk::{pubkey::Pubkey, signature::Signature},
     std::{
         collections::HashSet,
+        mem,
         ops::{Deref, DerefMut},
         sync::Arc,
         time::SystemTime,
@@ -70,6 +78,14 @@ pub struct FilteredUpdate {
     pub filters: FilteredUpdateFilters,
     pub message: FilteredUpdateOneof,
     pub created_at: Timestamp,
+    /// Per-stream sequence number, stamped when queued to the client channel
+    pub sequence: u64,
+    /// Notification time, set only when `created_at` is stamped on send
+    pub ingested_at: Option<Timestamp>,
+    /// Replica of the server, set only if the server is configured to stamp it
+    pub replica: Option<Arc<str>>,
+    /// Encoded `message`, see [`FilteredUpdate::encode_message`]
+    pub encoded_message: Option<Bytes>,
 }
 
 impl prost::Message for FilteredUpdate {
@@ -79,14 +95,43 @@ impl prost::Message for FilteredUpdate {
             encode_varint(name.len() as u64, buf);
             buf.put_slice(name.as_bytes());
         }
-        self.message.encode_raw(buf);
+        match &self.encoded_message {
+            Some(encoded) => buf.put_slice(encoded),
+            None => self.message.encode_raw(buf),
+        }
         message::encode(11u32, &self.created_at, buf);
+        if self.sequence != 0 {
+            ::prost::encoding::uint64::encode(13u32, &self.sequence, buf);
+        }
+        if let Some(ingested_at) = &self.ingested_at {
+            message::encode(15u32, ingested_at, buf);
+        }
+        if let Some(replica) = &self.replica {
+            encode_key(17u32, WireType::LengthDelimited, buf);
+            encode_varint(replica.len() as u64, buf);
+            buf.put_slice(replica.as_bytes());
+        }
     }
 
     fn encoded_len(&self) -> usize {
         prost_repeated_encoded_len_map!(1u32, self.filters, |filter| filter.as_ref().len())
-            + self.message.encoded_len()
+            + self
+                .encoded_message
+                .as_ref()
+                .map_or_else(|| self.message.encoded_len(), Bytes::len)
             + message::encoded_len(11u32, &self.created_at)
+            + if self.sequence != 0 {
+                ::prost::encoding::uint64::encoded_len(13u32, &self.sequence)
+            } else {
+                0
+            }
+            + self
+                .ingested_at
+                .as_ref()
+                .map_or(0, |ingested_at| message::encoded_len(15u32, ingested_at))
+            + self.replica.as_ref().map_or(0, |replica| {
+                key_len(17u32) + encoded_len_varint(replica.len() as u64) + replica.len()
+            })
     }
 
     fn merge_field(
@@ -114,6 +159,10 @@ impl FilteredUpdate {
             filters,
             message,
             created_at,
+            sequence: 0,
+            ingested_at: None,
+            replica: None,
+            encoded_message: None,
         }
     }
 
@@ -125,7 +174,15 @@ impl FilteredUpdate {
         )
     }
 
-    fn as_subscribe_update_account(
+    /// Encode `message` in advance, so the stream only copies the bytes.
+    /// Changes of `message` after this are not encoded.
+    pub fn encode_message(&mut self) {
+        let mut buf = Vec::with_capacity(self.message.encoded_len());
+        self.message.encode_raw(&mut buf);
+        self.encoded_message = Some(buf.into());
+    }
+
+    pub(crate) fn as_subscribe_update_account(
         message: &MessageAccountInfo,
         data_slice: &FilterAccountsDataSlice,
     ) -> SubscribeUpdateAccountInfo {
@@ -164,16 +221,36 @@ impl FilteredUpdate {
         }
     }
 
+    fn as_subscribe_update_account_message(msg: &FilteredUpdateAccount) -> SubscribeUpdateAccount {
+        SubscribeUpdateAccount {
+            account: Some(Self::as_subscribe_update_account(
+                msg.account.as_ref(),
+                &msg.data_slice,
+            )),
+            slot: msg.slot,
+            is_startup: msg.is_startup,
+            previous_owner: msg.previous_owner.map(|owner| owner.as_ref().into()),
+            data_omitted: msg.data_omitted,
+        }
+    }
+
+    fn as_subscribe_update_transaction_message(
+        msg: &FilteredUpdateTransaction,
+    ) -> SubscribeUpdateTransaction {
+        SubscribeUpdateTransaction {
+            transaction: Some(Self::as_subscribe_update_transaction(
+                msg.transaction.as_ref(),
+            )),
+            slot: msg.slot,
+            invocations: msg.invocations.clone(),
+        }
+    }
+
     pub fn as_subscribe_update(&self) -> SubscribeUpdate {
         let message = match &self.message {
-            FilteredUpdateOneof::Account(msg) => UpdateOneof::Account(SubscribeUpdateAccount {
-                account: Some(Self::as_subscribe_update_account(
-                    msg.account.as_ref(),
-                    &msg.data_slice,
-                )),
-                slot: msg.slot,
-                is_startup: msg.is_startup,
-            }),
+            FilteredUpdateOneof::Account(msg) => {
+                UpdateOneof::Account(Self::as_subscribe_update_account_message(msg))
+            }
             FilteredUpdateOneof::Slot(msg) => UpdateOneof::Slot(SubscribeUpdateSlot {
                 slot: msg.slot,
                 parent: msg.parent,
@@ -181,12 +258,7 @@ impl FilteredUpdate {
                 dead_error: msg.dead_error.clone(),
             }),
             FilteredUpdateOneof::Transaction(msg) => {
-                UpdateOneof::Transaction(SubscribeUpdateTransaction {
-                    transaction: Some(Self::as_subscribe_update_transaction(
-                        msg.transaction.as_ref(),
-                    )),
-                    slot: msg.slot,
-                })
+                UpdateOneof::Transaction(Self::as_subscribe_update_transaction_message(msg))
             }
             FilteredUpdateOneof::TransactionStatus(msg) => {
                 UpdateOneof::TransactionStatus(SubscribeUpdateTransactionStatus {
@@ -225,6 +297,7 @@ impl FilteredUpdate {
                     .iter()
                     .map(|entry| Self::as_subscribe_update_entry(entry.as_ref()))
                     .collect(),
+                ticks_count: msg.meta.ticks_count,
             }),
             FilteredUpdateOneof::Ping => UpdateOneof::Ping(SubscribeUpdatePing {}),
             FilteredUpdateOneof::Pong(msg) => UpdateOneof::Pong(*msg),
@@ -232,6 +305,25 @@ impl FilteredUpdate {
             FilteredUpdateOneof::Entry(msg) => {
                 UpdateOneof::Entry(Self::as_subscribe_update_entry(&msg.0))
             }
+            FilteredUpdateOneof::SlotManifest(msg) => UpdateOneof::SlotManifest(*msg),
+            FilteredUpdateOneof::FilterApplied(msg) => UpdateOneof::FilterApplied(*msg),
+            FilteredUpdateOneof::SlotBatch(msg) => {
+                UpdateOneof::SlotBatch(SubscribeUpdateSlotBatch {
+                    slot: msg.slot,
+                    index: msg.index,
+                    is_last: msg.is_last,
+                    accounts: msg
+                        .accounts
+                        .iter()
+                        .map(Self::as_subscribe_update_account_message)
+                        .collect(),
+                    transactions: msg
+                        .transactions
+                        .iter()
+                        .map(Self::as_subscribe_update_transaction_message)
+                        .collect(),
+                })
+            }
         };
 
         SubscribeUpdate {
@@ -241,34 +333,49 @@ impl FilteredUpdate {
                 .map(|name| name.as_ref().to_string())
                 .collect(),
             update_oneof: Some(message),
-            created_at: Some(self.created_at),
+            created_at: Some(from_prost(self.created_at)),
+            sequence: self.sequence,
+            ingested_at: self.ingested_at.map(from_prost),
+            replica: self.replica.as_deref().unwrap_or_default().to_owned(),
         }
     }
 
     pub fn from_subscribe_update(update: SubscribeUpdate) -> Result<Self, &'static str> {
-        let created_at = update.created_at.ok_or("create_at should be defined")?;
-
-        let message = match update.update_oneof.ok_or("update should be defined")? {
-            UpdateOneof::Account(msg) => {
-                let account = MessageAccount::from_update_oneof(msg, created_at)?;
-                FilteredUpdateOneof::Account(FilteredUpdateAccount {
+        let created_at = update
+            .created_at
+            .map(to_prost)
+            .ok_or("create_at should be defined")?;
+
+        let account = |msg| {
+            MessageAccount::from_update_oneof(msg, created_at).map(|account| {
+                FilteredUpdateAccount {
                     account: account.account,
                     slot: account.slot,
                     is_startup: account.is_startup,
+                    previous_owner: account.previous_owner,
                     data_slice: FilterAccountsDataSlice::default(),
-                })
-            }
+                    data_omitted: false,
+                }
+            })
+        };
+        let transaction = |mut msg: SubscribeUpdateTransaction| {
+            let invocations = mem::take(&mut msg.invocations);
+            MessageTransaction::from_update_oneof(msg, created_at).map(|tx| {
+                FilteredUpdateTransaction {
+                    transaction: tx.transaction,
+                    slot: tx.slot,
+                    invocations,
+                }
+            })
+        };
+
+        let message = match update.update_oneof.ok_or("update should be defined")? {
+            UpdateOneof::Account(msg) => FilteredUpdateOneof::Account(account(msg)?),
             UpdateOneof::Slot(msg) => {
                 let slot = MessageSlot::from_update_oneof(&msg, created_at)?;
                 FilteredUpdateOneof::Slot(FilteredUpdateSlot(slot))
             }
-            UpdateOneof::Transaction(msg) => {
-                let tx = MessageTransaction::from_update_oneof(msg, created_at)?;
-                FilteredUpdateOneof::Transaction(FilteredUpdateTransaction {
-                    transaction: tx.transaction,
-                    slot: tx.slot,
-                })
-            }
+            UpdateOneof::Transaction(msg) => FilteredUpdateOneof::Transaction(transaction(msg)?),
             UpdateOneof::TransactionStatus(msg) => {
                 FilteredUpdateOneof::TransactionStatus(FilteredUpdateTransactionStatus {
                     transaction: Arc::new(MessageTransactionInfo {
@@ -307,12 +414,35 @@ impl FilteredUpdate {
                 let entry = MessageEntry::from_update_oneof(&msg, created_at)?;
                 FilteredUpdateOneof::Entry(FilteredUpdateEntry(Arc::new(entry)))
             }
+            UpdateOneof::SlotManifest(msg) => FilteredUpdateOneof::SlotManifest(msg),
+            UpdateOneof::FilterApplied(msg) => FilteredUpdateOneof::FilterApplied(msg),
+            UpdateOneof::SlotBatch(msg) => {
+                FilteredUpdateOneof::SlotBatch(Box::new(FilteredUpdateSlotBatch {
+                    slot: msg.slot,
+                    index: msg.index,
+                    is_last: msg.is_last,
+                    accounts: msg
+                        .accounts
+                        .into_iter()
+                        .map(account)
+                        .collect::<Result<_, _>>()?,
+                    transactions: msg
+                        .transactions
+                        .into_iter()
+                        .map(transaction)
+                        .collect::<Result<_, _>>()?,
+                }))
+            }
         };
 
         Ok(Self {
             filters: update.filters.into_iter().map(FilterName::new).collect(),
             message,
             created_at,
+            sequence: update.sequence,
+            ingested_at: update.ingested_at.map(to_prost),
+            replica: (!update.replica.is_empty()).then(|| update.replica.into()),
+            encoded_message: None,
         })
     }
 }
@@ -330,6 +460,9 @@ pub enum FilteredUpdateOneof {
     Pong(SubscribeUpdatePong),                          // 9
     BlockMeta(Arc<MessageBlockMeta>),                   // 7
     Entry(FilteredUpdateEntry),                         // 8
+    SlotManifest(SubscribeUpdateSlotManifest),          // 12
+    SlotBatch(Box<FilteredUpdateSlotBatch>),            // 14
+    FilterApplied(SubscribeUpdateFilterApplied),        // 16
 }
 
 impl FilteredUpdateOneof {
@@ -338,7 +471,9 @@ impl FilteredUpdateOneof {
             slot: message.slot,
             account: Arc::clone(&message.account),
             is_startup: message.is_startup,
+            previous_owner: message.previous_owner,
             data_slice,
+            data_omitted: false,
         })
     }
 
@@ -347,9 +482,17 @@ impl FilteredUpdateOneof {
     }
 
     pub fn transaction(message: &MessageTransaction) -> Self {
+        Self::transaction_with_invocations(message, vec![])
+    }
+
+    pub fn transaction_with_invocations(
+        message: &MessageTransaction,
+        invocations: Vec<SubscribeUpdateTransactionInvocation>,
+    ) -> Self {
         Self::Transaction(FilteredUpdateTransaction {
             transaction: Arc::clone(&message.transaction),
             slot: message.slot,
+            invocations,
         })
     }
 
@@ -379,6 +522,75 @@ impl FilteredUpdateOneof {
     pub const fn entry(message: Arc<MessageEntry>) -> Self {
         Self::Entry(FilteredUpdateEntry(message))
     }
+
+    pub const fn slot_manifest(message: SubscribeUpdateSlotManifest) -> Self {
+        Self::SlotManifest(message)
+    }
+
+    pub const fn slot_batch(message: Box<FilteredUpdateSlotBatch>) -> Self {
+        Self::SlotBatch(message)
+    }
+
+    pub const fn filter_applied(request_id: u64) -> Self {
+        Self::FilterApplied(SubscribeUpdateFilterApplied { request_id })
+    }
+
+    /// Slot of the update, not set for pings, pongs and acknowledgements
+    pub fn get_slot(&self) -> Option<u64> {
+        Some(match self {
+            Self::Account(msg) => msg.slot,
+            Self::Slot(msg) => msg.slot,
+            Self::Transaction(msg) => msg.slot,
+            Self::TransactionStatus(msg) => msg.slot,
+            Self::Block(msg) => msg.meta.slot,
+            Self::BlockMeta(msg) => msg.slot,
+            Self::Entry(msg) => msg.0.slot,
+            Self::SlotManifest(msg) => msg.slot,
+            Self::SlotBatch(msg) => msg.slot,
+            Self::Ping | Self::Pong(_) | Self::FilterApplied(_) => return None,
+        })
+    }
+
+    /// Same identity as for decoded `SubscribeUpdate`, see [`UpdateIdentity::from_update_oneof`]
+    pub fn identity(&self) -> Option<UpdateIdentity> {
+        Some(match self {
+            Self::Account(msg) => UpdateIdentity::Account {
+                slot: msg.slot,
+                pubkey: msg.account.pubkey.to_bytes().to_vec(),
+                write_version: msg.account.write_version,
+            },
+            Self::Slot(msg) => UpdateIdentity::Slot {
+                slot: msg.slot,
+                status: msg.status as i32,
+            },
+            Self::Transaction(msg) => UpdateIdentity::Transaction {
+                slot: msg.slot,
+                signature: msg.transaction.signature.as_ref().to_vec(),
+            },
+            Self::TransactionStatus(msg) => UpdateIdentity::TransactionStatus {
+                slot: msg.slot,
+                signature: msg.transaction.signature.as_ref().to_vec(),
+            },
+            Self::Block(msg) => UpdateIdentity::Block {
+                slot: msg.meta.slot,
+                blockhash: msg.meta.blockhash.clone(),
+            },
+            Self::BlockMeta(msg) => UpdateIdentity::BlockMeta {
+                slot: msg.slot,
+                blockhash: msg.blockhash.clone(),
+            },
+            Self::Entry(msg) => UpdateIdentity::Entry {
+                slot: msg.0.slot,
+                index: msg.0.index as u64,
+            },
+            Self::SlotManifest(msg) => UpdateIdentity::SlotManifest { slot: msg.slot },
+            Self::SlotBatch(msg) => UpdateIdentity::SlotBatch {
+                slot: msg.slot,
+                index: msg.index,
+            },
+            Self::Ping | Self::Pong(_) | Self::FilterApplied(_) => return None,
+        })
+    }
 }
 
 impl prost::Message for FilteredUpdateOneof {
@@ -396,6 +608,9 @@ impl prost::Message for FilteredUpdateOneof {
             Self::Pong(msg) => message::encode(9u32, msg, buf),
             Self::BlockMeta(msg) => message::encode(7u32, &msg.block_meta, buf),
             Self::Entry(msg) => message::encode(8u32, msg, buf),
+            Self::SlotManifest(msg) => message::encode(12u32, msg, buf),
+            Self::SlotBatch(msg) => message::encode(14u32, msg, buf),
+            Self::FilterApplied(msg) => message::encode(16u32, msg, buf),
         }
     }
 
@@ -410,6 +625,9 @@ impl prost::Message for FilteredUpdateOneof {
             Self::Pong(msg) => message::encoded_len(9u32, msg),
             Self::BlockMeta(msg) => message::encoded_len(7u32, &msg.block_meta),
             Self::Entry(msg) => message::encoded_len(8u32, msg),
+            Self::SlotManifest(msg) => message::encoded_len(12u32, msg),
+            Self::SlotBatch(msg) => message::encoded_len(14u32, msg),
+            Self::FilterApplied(msg) => message::encoded_len(16u32, msg),
         }
     }
 
@@ -433,7 +651,9 @@ pub struct FilteredUpdateAccount {
     pub account: Arc<MessageAccountInfo>,
     pub slot: u64,
     pub is_startup: bool,
+    pub previous_owner: Option<Pubkey>,
     pub data_slice: FilterAccountsDataSlice,
+    pub data_omitted: bool,
 }
 
 impl prost::Message for FilteredUpdateAccount {
@@ -445,6 +665,12 @@ impl prost::Message for FilteredUpdateAccount {
         if self.is_startup {
             ::prost::encoding::bool::encode(3u32, &self.is_startup, buf);
         }
+        if let Some(value) = &self.previous_owner {
+            prost_bytes_encode_raw(4u32, value.as_ref(), buf);
+        }
+        if self.data_omitted {
+            ::prost::encoding::bool::encode(5u32, &self.data_omitted, buf);
+        }
     }
 
     fn encoded_len(&self) -> usize {
@@ -459,7 +685,14 @@ impl prost::Message for FilteredUpdateAccount {
             ::prost::encoding::bool::encoded_len(3u32, &self.is_startup)
         } else {
             0
-        }
+        } + self
+            .previous_owner
+            .map_or(0, |owner| prost_bytes_encoded_len(4u32, owner.as_ref()))
+            + if self.data_omitted {
+                ::prost::encoding::bool::encoded_len(5u32, &self.data_omitted)
+            } else {
+                0
+            }
     }
 
     fn merge_field(
@@ -619,6 +852,8 @@ impl prost::Message for FilteredUpdateSlot {
 pub struct FilteredUpdateTransaction {
     pub transaction: Arc<MessageTransactionInfo>,
     pub slot: u64,
+    /// Invocations of programs from `program_invoked` of matched filters
+    pub invocations: Vec<SubscribeUpdateTransactionInvocation>,
 }
 
 impl prost::Message for FilteredUpdateTransaction {
@@ -627,6 +862,7 @@ impl prost::Message for FilteredUpdateTransaction {
         if self.slot != 0u64 {
             ::prost::encoding::uint64::encode(2u32, &self.slot, buf);
         }
+        message::encode_repeated(3u32, &self.invocations, buf);
     }
 
     fn encoded_len(&self) -> usize {
@@ -636,6 +872,7 @@ impl prost::Message for FilteredUpdateTransaction {
             } else {
                 0
             }
+            + message::encoded_len_repeated(3u32, &self.invocations)
     }
 
     fn merge_field(
@@ -755,6 +992,62 @@ impl prost::Message for FilteredUpdateTransactionStatus {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilteredUpdateSlotBatch {
+    pub slot: u64,
+    pub index: u32,
+    pub is_last: bool,
+    pub accounts: Vec<FilteredUpdateAccount>,
+    pub transactions: Vec<FilteredUpdateTransaction>,
+}
+
+impl prost::Message for FilteredUpdateSlotBatch {
+    fn encode_raw(&self, buf: &mut impl BufMut) {
+        if self.slot != 0u64 {
+            ::prost::encoding::uint64::encode(1u32, &self.slot, buf);
+        }
+        if self.index != 0u32 {
+            ::prost::encoding::uint32::encode(2u32, &self.index, buf);
+        }
+        if self.is_last {
+            ::prost::encoding::bool::encode(3u32, &self.is_last, buf);
+        }
+        message::encode_repeated(4u32, &self.accounts, buf);
+        message::encode_repeated(5u32, &self.transactions, buf);
+    }
+
+    fn encoded_len(&self) -> usize {
+        (if self.slot != 0u64 {
+            ::prost::encoding::uint64::encoded_len(1u32, &self.slot)
+        } else {
+            0
+        }) + if self.index != 0u32 {
+            ::prost::encoding::uint32::encoded_len(2u32, &self.index)
+        } else {
+            0
+        } + if self.is_last {
+            ::prost::encoding::bool::encoded_len(3u32, &self.is_last)
+        } else {
+            0
+        } + message::encoded_len_repeated(4u32, &self.accounts)
+            + message::encoded_len_repeated(5u32, &self.transactions)
+    }
+
+    fn merge_field(
+        &mut self,
+        _tag: u32,
+        _wire_type: WireType,
+        _buf: &mut impl Buf,
+        _ctx: DecodeContext,
+    ) -> Result<(), DecodeError> {
+        unimplemented!()
+    }
+
+    fn clear(&mut self) {
+        unimplemented!()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FilteredUpdateBlock {
     pub meta: Arc<MessageBlockMeta>,
@@ -816,6 +1109,9 @@ impl prost::Message for FilteredUpdateBlock {
             );
             FilteredUpdateEntry::entry_encode_raw(entry, buf);
         }
+        if let Some(value) = &self.meta.ticks_count {
+            ::prost::encoding::uint64::encode(14u32, value, buf);
+        }
     }
 
     fn encoded_len(&self) -> usize {
@@ -879,6 +1175,9 @@ impl prost::Message for FilteredUpdateBlock {
             + prost_repeated_encoded_len_map!(13u32, self.entries, |entry| {
                 FilteredUpdateEntry::entry_encoded_len(entry)
             })
+            + self.meta.ticks_count.as_ref().map_or(0, |value| {
+                ::prost::encoding::uint64::encoded_len(14u32, value)
+            })
     }
 
     fn merge_field(
@@ -979,10 +1278,16 @@ pub mod tests {
     #![cfg_attr(feature = "plugin-bench", allow(dead_code))]
     #![cfg_attr(feature = "plugin-bench", allow(unused_imports))]
     use {
-        super::{FilteredUpdate, FilteredUpdateBlock, FilteredUpdateFilters, FilteredUpdateOneof},
+        super::{
+            FilteredUpdate, FilteredUpdateBlock, FilteredUpdateFilters, FilteredUpdateOneof,
+            FilteredUpdateSlotBatch,
+        },
         crate::{
             convert_to,
-            geyser::{SubscribeUpdate, SubscribeUpdateBlockMeta},
+            geyser::{
+                SubscribeUpdate, SubscribeUpdateBlockMeta, SubscribeUpdateSlotManifest,
+                SubscribeUpdateTransactionInvocation,
+            },
             plugin::{
                 filter::{name::FilterName, FilterAccountsDataSlice},
                 message::{
@@ -1080,6 +1385,7 @@ pub mod tests {
                             account: Arc::clone(&account),
                             slot,
                             is_startup,
+                            previous_owner: (!is_startup).then(Pubkey::new_unique),
                             created_at: Timestamp::from(SystemTime::now()),
                         };
                         vec.push((msg, data_slice));
@@ -1197,6 +1503,12 @@ pub mod tests {
                         block_height: block.block_height.map(convert_to::create_block_height),
                         executed_transaction_count: transactions.len() as u64,
                         entries_count: entries.len() as u64,
+                        ticks_count: Some(
+                            entries
+                                .iter()
+                                .filter(|entry| entry.executed_transaction_count == 0)
+                                .count() as u64,
+                        ),
                     },
                     created_at: Timestamp::from(SystemTime::now()),
                 };
@@ -1239,6 +1551,11 @@ pub mod tests {
             filters: create_message_filters(filters),
             message,
             created_at: Timestamp::from(SystemTime::now()),
+            // with and without sequence, ingested_at and replica
+            sequence: filters.len() as u64,
+            ingested_at: (!filters.is_empty()).then(|| Timestamp::from(SystemTime::now())),
+            replica: (!filters.is_empty()).then(|| "eu-1".into()),
+            encoded_message: None,
         };
         let update = msg.as_subscribe_update();
         assert_eq!(msg.encoded_len(), update.encoded_len());
@@ -1246,6 +1563,10 @@ pub mod tests {
             SubscribeUpdate::decode(msg.encode_to_vec().as_slice()).expect("failed to decode"),
             update
         );
+        let mut encoded = msg.clone();
+        encoded.encode_message();
+        assert_eq!(encoded.encoded_len(), msg.encoded_len());
+        assert_eq!(encoded.encode_to_vec(), msg.encode_to_vec());
         assert_eq!(
             FilteredUpdate::from_subscribe_update(update.clone())
                 .map(|msg| msg.as_subscribe_update()),
@@ -1307,6 +1628,17 @@ pub mod tests {
                 created_at: Timestamp::from(SystemTime::now()),
             };
             encode_decode_cmp(&["123"], FilteredUpdateOneof::transaction(&msg));
+            encode_decode_cmp(
+                &["123"],
+                FilteredUpdateOneof::transaction_with_invocations(
+                    &msg,
+                    vec![SubscribeUpdateTransactionInvocation {
+                        instruction_index: 1,
+                        inner_index: Some(0),
+                        path: vec![vec![1; 32], vec![2; 32]],
+                    }],
+                ),
+            );
             encode_decode_cmp(&["123"], FilteredUpdateOneof::transaction_status(&msg));
         }
     }
@@ -1329,6 +1661,12 @@ pub mod tests {
         encode_decode_cmp(&["123"], FilteredUpdateOneof::pong(42));
     }
 
+    #[test]
+    fn test_message_filter_applied() {
+        encode_decode_cmp(&["123"], FilteredUpdateOneof::filter_applied(0));
+        encode_decode_cmp(&["123"], FilteredUpdateOneof::filter_applied(42));
+    }
+
     #[test]
     fn test_message_blockmeta() {
         for block_meta in load_predefined_blockmeta() {
@@ -1342,4 +1680,73 @@ pub mod tests {
             encode_decode_cmp(&["123"], FilteredUpdateOneof::entry(entry));
         }
     }
+
+    #[test]
+    fn test_message_slot_batch() {
+        let accounts = create_accounts()
+            .into_iter()
+            .step_by(7)
+            .map(
+                |(msg, data_slice)| match FilteredUpdateOneof::account(&msg, data_slice) {
+                    FilteredUpdateOneof::Account(msg) => msg,
+                    _ => unreachable!(),
+                },
+            )
+            .collect::<Vec<_>>();
+        let transactions = load_predefined_transactions()
+            .into_iter()
+            .map(|transaction| {
+                match FilteredUpdateOneof::transaction(&MessageTransaction {
+                    transaction,
+                    slot: 42,
+                    created_at: Timestamp::from(SystemTime::now()),
+                }) {
+                    FilteredUpdateOneof::Transaction(msg) => msg,
+                    _ => unreachable!(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for (index, is_last) in [(0, false), (3, true)] {
+            encode_decode_cmp(
+                &["123", "456"],
+                FilteredUpdateOneof::slot_batch(Box::new(FilteredUpdateSlotBatch {
+                    slot: 42,
+                    index,
+                    is_last,
+                    accounts: accounts.clone(),
+                    transactions: transactions.clone(),
+                })),
+            );
+        }
+        encode_decode_cmp(
+            &["123"],
+            FilteredUpdateOneof::slot_batch(Box::new(FilteredUpdateSlotBatch {
+                slot: 42,
+                index: 0,
+                is_last: true,
+                accounts: vec![],
+                transactions: vec![],
+            })),
+        );
+    }
+
+    #[test]
+    fn test_message_slot_manifest() {
+        for content_hash in [None, Some(0), Some(u64::MAX)] {
+            encode_decode_cmp(
+                &["123"],
+                FilteredUpdateOneof::slot_manifest(SubscribeUpdateSlotManifest {
+                    slot: 42,
+                    accounts: 1,
+                    transactions: 2,
+                    transactions_status: 0,
+                    entries: 3,
+                    blocks_meta: 1,
+                    blocks: 0,
+                    content_hash,
+                }),
+            );
+        }
+    }
 }
@@ -1,7 +1,9 @@
+pub mod batch;
 #[allow(clippy::module_inception)]
 mod filter;
 pub mod limits;
+pub mod manifest;
 pub mod message;
 pub mod name;
 
-pub use filter::{Filter, FilterAccountsDataSlice, FilterError, FilterResult};
+pub use filter::{Filter, FilterAccountsDataSlice, FilterError, FilterExplain, FilterResult};
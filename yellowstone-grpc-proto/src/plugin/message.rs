@@ -235,6 +235,7 @@ pub struct MessageAccount {
     pub account: Arc<MessageAccountInfo>,
     pub slot: Slot,
     pub is_startup: bool,
+    pub previous_owner: Option<Pubkey>,
     pub created_at: Timestamp,
 }
 
@@ -244,6 +245,7 @@ impl MessageAccount {
             account: Arc::new(MessageAccountInfo::from_geyser(info)),
             slot,
             is_startup,
+            previous_owner: None,
             created_at: Timestamp::from(SystemTime::now()),
         }
     }
@@ -258,6 +260,11 @@ impl MessageAccount {
             )?),
             slot: msg.slot,
             is_startup: msg.is_startup,
+            previous_owner: msg
+                .previous_owner
+                .map(|owner| Pubkey::try_from(owner.as_slice()))
+                .transpose()
+                .map_err(|_| "invalid previous owner length")?,
             created_at,
         })
     }
@@ -452,6 +459,7 @@ impl MessageBlockMeta {
                 block_height: info.block_height.map(convert_to::create_block_height),
                 executed_transaction_count: info.executed_transaction_count,
                 entries_count: info.entry_count,
+                ticks_count: None,
             },
             created_at: Timestamp::from(SystemTime::now()),
         }
@@ -511,6 +519,7 @@ impl MessageBlock {
                     parent_blockhash: msg.parent_blockhash,
                     executed_transaction_count: msg.executed_transaction_count,
                     entries_count: msg.entries_count,
+                    ticks_count: msg.ticks_count,
                 },
                 created_at,
             }),
@@ -577,6 +586,9 @@ impl Message {
             }
             UpdateOneof::Ping(_) => return Err("Ping message is not supported"),
             UpdateOneof::Pong(_) => return Err("Pong message is not supported"),
+            UpdateOneof::SlotManifest(_) => return Err("SlotManifest message is not supported"),
+            UpdateOneof::SlotBatch(_) => return Err("SlotBatch message is not supported"),
+            UpdateOneof::FilterApplied(_) => return Err("FilterApplied message is not supported"),
             UpdateOneof::BlockMeta(msg) => Self::BlockMeta(Arc::new(
                 MessageBlockMeta::from_update_oneof(msg, created_at),
             )),
@@ -1,5 +1,7 @@
+pub mod account_cache;
 pub mod filter;
 pub mod message;
+pub mod snapshot;
 
 pub mod proto {
     #![allow(clippy::clone_on_ref_ptr)]
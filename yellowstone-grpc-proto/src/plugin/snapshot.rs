@@ -0,0 +1,401 @@
+use {
+    crate::{
+        geyser::{SnapshotDownloadRequest, SnapshotDownloadResponse, SubscribeRequest},
+        plugin::{
+            filter::{
+                limits::FilterLimits,
+                message::{FilteredUpdate, FilteredUpdateOneof},
+                name::FilterNames,
+                Filter, FilterResult,
+            },
+            message::{Message, MessageAccount, MessageAccountInfo, SlotStatus},
+        },
+    },
+    prost_types::Timestamp,
+    solana_sdk::{clock::Slot, pubkey::Pubkey},
+    std::{
+        collections::{BTreeMap, HashMap, VecDeque},
+        ops::Bound,
+        sync::Arc,
+    },
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("snapshot at slot {slot} is not available, first available: {first_available:?}")]
+    NotAvailable {
+        slot: Slot,
+        first_available: Option<Slot>,
+    },
+    #[error("no finalized slots available")]
+    Empty,
+    #[error("`cursor` requires `slot`")]
+    CursorWithoutSlot,
+    #[error("invalid `cursor`")]
+    InvalidCursor,
+}
+
+pub type SnapshotResult<T> = Result<T, SnapshotError>;
+
+#[derive(Debug, Default)]
+struct PendingSlot {
+    parent: Option<Slot>,
+    accounts: HashMap<Pubkey, Arc<MessageAccountInfo>>,
+}
+
+/// Accounts state at the last finalized slots, updates of not finalized slots
+/// are kept aside and applied only for the finalized chain (forks are dropped).
+/// Every account keeps versions required to read state at any of the stored
+/// finalized slots, so download in chunks is consistent and can be resumed.
+#[derive(Debug)]
+pub struct AccountsStore {
+    keep_snapshots: usize,
+    /// Account versions ordered by slot
+    accounts: BTreeMap<Pubkey, Vec<(Slot, Arc<MessageAccountInfo>)>>,
+    pending: BTreeMap<Slot, PendingSlot>,
+    /// Finalized slots available for reading
+    finalized: VecDeque<Slot>,
+}
+
+impl AccountsStore {
+    /// Maximum number of accounts checked by filter for one chunk
+    pub const SCAN_LIMIT: usize = 65_536;
+
+    pub fn new(keep_snapshots: usize) -> Self {
+        Self {
+            keep_snapshots: keep_snapshots.max(1),
+            accounts: BTreeMap::new(),
+            pending: BTreeMap::new(),
+            finalized: VecDeque::new(),
+        }
+    }
+
+    pub fn first_available(&self) -> Option<Slot> {
+        self.finalized.front().copied()
+    }
+
+    pub fn last_finalized(&self) -> Option<Slot> {
+        self.finalized.back().copied()
+    }
+
+    /// Apply account or slot message, other messages are ignored
+    pub fn update(&mut self, message: &Message) {
+        match message {
+            // startup accounts are already rooted
+            Message::Account(msg) if msg.is_startup => {
+                self.apply(msg.slot, Arc::clone(&msg.account));
+            }
+            Message::Account(msg) => {
+                if self.is_finalized(msg.slot) {
+                    return;
+                }
+                let accounts = &mut self.pending.entry(msg.slot).or_default().accounts;
+                match accounts.get(&msg.account.pubkey) {
+                    Some(account) if account.write_version > msg.account.write_version => {}
+                    _ => {
+                        accounts.insert(msg.account.pubkey, Arc::clone(&msg.account));
+                    }
+                }
+            }
+            Message::Slot(msg) => match msg.status {
+                SlotStatus::Finalized => self.finalize(msg.slot),
+                SlotStatus::Dead => {
+                    self.pending.remove(&msg.slot);
+                }
+                _ => {
+                    if msg.parent.is_some() && !self.is_finalized(msg.slot) {
+                        self.pending.entry(msg.slot).or_default().parent = msg.parent;
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn is_finalized(&self, slot: Slot) -> bool {
+        self.last_finalized().is_some_and(|last| slot <= last)
+    }
+
+    fn finalize(&mut self, slot: Slot) {
+        if self.is_finalized(slot) {
+            return;
+        }
+
+        // sometimes we do not receive finalized status for every slot
+        let mut chain = vec![slot];
+        while let Some(parent) = chain
+            .last()
+            .and_then(|slot| self.pending.get(slot))
+            .and_then(|pending| pending.parent)
+        {
+            if self.is_finalized(parent) || !self.pending.contains_key(&parent) {
+                break;
+            }
+            chain.push(parent);
+        }
+
+        self.finalized.push_back(slot);
+        if self.finalized.len() > self.keep_snapshots {
+            self.finalized.pop_front();
+        }
+
+        for slot in chain.into_iter().rev() {
+            if let Some(pending) = self.pending.remove(&slot) {
+                for account in pending.accounts.into_values() {
+                    self.apply(slot, account);
+                }
+            }
+        }
+
+        // forks
+        self.pending = self.pending.split_off(&(slot + 1));
+    }
+
+    fn apply(&mut self, slot: Slot, account: Arc<MessageAccountInfo>) {
+        let pubkey = account.pubkey;
+        let versions = self.accounts.entry(pubkey).or_default();
+        match versions.last_mut() {
+            Some((last_slot, last)) if *last_slot == slot => {
+                if last.write_version <= account.write_version {
+                    *last = account;
+                }
+            }
+            _ => versions.push((slot, account)),
+        }
+
+        // versions before the one visible at the first available slot are not needed
+        if let Some(first) = self.finalized.front().copied() {
+            if let Some(index) = versions.iter().rposition(|(slot, _)| *slot <= first) {
+                versions.drain(..index);
+            }
+            if matches!(versions.as_slice(), [(slot, account)] if *slot <= first && account.lamports == 0)
+            {
+                self.accounts.remove(&pubkey);
+            }
+        }
+    }
+
+    /// Filter with accounts filter and data slices of the request
+    pub fn create_filter(
+        request: &SnapshotDownloadRequest,
+        limits: &FilterLimits,
+        names: &mut FilterNames,
+    ) -> FilterResult<Filter> {
+        let request = SubscribeRequest {
+            accounts: HashMap::from([(
+                "snapshot".to_owned(),
+                request.accounts.clone().unwrap_or_default(),
+            )]),
+            accounts_data_slice: request.accounts_data_slice.clone(),
+            ..Default::default()
+        };
+        Filter::new(&request, limits, names)
+    }
+
+    /// Slot and cursor of the request
+    pub fn get_position(
+        &self,
+        request: &SnapshotDownloadRequest,
+    ) -> SnapshotResult<(Slot, Option<Pubkey>)> {
+        let cursor = match request.cursor.as_deref() {
+            Some(_) if request.slot.is_none() => return Err(SnapshotError::CursorWithoutSlot),
+            Some(cursor) => {
+                Some(Pubkey::try_from(cursor).map_err(|_error| SnapshotError::InvalidCursor)?)
+            }
+            None => None,
+        };
+        let slot = match request.slot {
+            Some(slot) => slot,
+            None => self.last_finalized().ok_or(SnapshotError::Empty)?,
+        };
+        Ok((slot, cursor))
+    }
+
+    /// Up to `limit` accounts after `cursor` in the state at `slot`, chunk could
+    /// be empty if no accounts matched in [`Self::SCAN_LIMIT`] checked accounts
+    pub fn get_chunk(
+        &self,
+        slot: Slot,
+        cursor: Option<Pubkey>,
+        filter: &Filter,
+        limit: usize,
+    ) -> SnapshotResult<SnapshotDownloadResponse> {
+        if !self.finalized.contains(&slot) {
+            return Err(SnapshotError::NotAvailable {
+                slot,
+                first_available: self.first_available(),
+            });
+        }
+
+        let start = cursor.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut response = SnapshotDownloadResponse {
+            slot,
+            accounts: Vec::new(),
+            cursor: cursor
+                .map(|pubkey| pubkey.to_bytes().to_vec())
+                .unwrap_or_default(),
+            finished: true,
+        };
+        for (scanned, (pubkey, versions)) in
+            self.accounts.range((start, Bound::Unbounded)).enumerate()
+        {
+            if response.accounts.len() >= limit.max(1) || scanned >= Self::SCAN_LIMIT {
+                response.finished = false;
+                break;
+            }
+            response.cursor = pubkey.to_bytes().to_vec();
+
+            let Some((account_slot, account)) = versions.iter().rev().find(|(s, _)| *s <= slot)
+            else {
+                continue;
+            };
+            if account.lamports == 0 {
+                continue;
+            }
+
+            let message = Message::Account(MessageAccount {
+                account: Arc::clone(account),
+                slot: *account_slot,
+                is_startup: false,
+                previous_owner: None,
+                created_at: Timestamp::default(),
+            });
+            for update in filter.get_updates(&message, None) {
+                if let FilteredUpdateOneof::Account(msg) = &update.message {
+                    response
+                        .accounts
+                        .push(FilteredUpdate::as_subscribe_update_account(
+                            &msg.account,
+                            &msg.data_slice,
+                        ));
+                }
+            }
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{AccountsStore, SnapshotError},
+        crate::{
+            geyser::{SnapshotDownloadRequest, SubscribeRequestFilterAccounts},
+            plugin::{
+                filter::{limits::FilterLimits, name::FilterNames, Filter},
+                message::{Message, MessageAccount, MessageAccountInfo, MessageSlot, SlotStatus},
+            },
+        },
+        prost_types::Timestamp,
+        solana_sdk::{clock::Slot, pubkey::Pubkey},
+        std::{sync::Arc, time::Duration},
+    };
+
+    fn account(pubkey: Pubkey, slot: Slot, lamports: u64, is_startup: bool) -> Message {
+        Message::Account(MessageAccount {
+            account: Arc::new(MessageAccountInfo {
+                pubkey,
+                lamports,
+                owner: Pubkey::default(),
+                executable: false,
+                rent_epoch: 0,
+                data: vec![],
+                write_version: slot,
+                txn_signature: None,
+            }),
+            slot,
+            is_startup,
+            previous_owner: None,
+            created_at: Timestamp::default(),
+        })
+    }
+
+    fn slot(slot: Slot, parent: Option<Slot>, status: SlotStatus) -> Message {
+        Message::Slot(MessageSlot {
+            slot,
+            parent,
+            status,
+            dead_error: None,
+            created_at: Timestamp::default(),
+        })
+    }
+
+    fn create_filter() -> Filter {
+        let request = SnapshotDownloadRequest {
+            accounts: Some(SubscribeRequestFilterAccounts::default()),
+            ..Default::default()
+        };
+        let mut names = FilterNames::new(128, 128, Duration::from_secs(1));
+        AccountsStore::create_filter(&request, &FilterLimits::default(), &mut names).unwrap()
+    }
+
+    fn lamports(store: &AccountsStore, slot: Slot, filter: &Filter) -> Vec<u64> {
+        let chunk = store.get_chunk(slot, None, filter, 100).unwrap();
+        assert!(chunk.finished);
+        chunk.accounts.iter().map(|a| a.lamports).collect()
+    }
+
+    #[test]
+    fn test_finalized_chain() {
+        let filter = create_filter();
+        let (a, b) = (
+            Pubkey::new_from_array([1; 32]),
+            Pubkey::new_from_array([2; 32]),
+        );
+        let mut store = AccountsStore::new(2);
+        for message in [
+            account(a, 1, 10, true),
+            slot(2, Some(1), SlotStatus::Processed),
+            account(a, 2, 20, false),
+            account(b, 2, 5, false),
+            // fork
+            slot(3, Some(1), SlotStatus::Processed),
+            account(a, 3, 30, false),
+            slot(4, Some(2), SlotStatus::Processed),
+            account(b, 4, 0, false),
+            slot(2, None, SlotStatus::Finalized),
+        ] {
+            store.update(&message);
+        }
+        assert_eq!(lamports(&store, 2, &filter), vec![20, 5]);
+
+        // account `b` is removed in slot 4
+        store.update(&slot(4, None, SlotStatus::Finalized));
+        assert_eq!(lamports(&store, 2, &filter), vec![20, 5]);
+        assert_eq!(lamports(&store, 4, &filter), vec![20]);
+
+        store.update(&slot(5, Some(4), SlotStatus::Finalized));
+        assert!(matches!(
+            store.get_chunk(2, None, &filter, 100),
+            Err(SnapshotError::NotAvailable {
+                slot: 2,
+                first_available: Some(4)
+            })
+        ));
+    }
+
+    #[test]
+    fn test_chunks() {
+        let filter = create_filter();
+        let mut store = AccountsStore::new(1);
+        for i in 1..=5 {
+            store.update(&account(Pubkey::new_from_array([i; 32]), 1, 1, true));
+        }
+        store.update(&slot(1, None, SlotStatus::Finalized));
+
+        let chunk = store.get_chunk(1, None, &filter, 2).unwrap();
+        assert_eq!(chunk.accounts.len(), 2);
+        assert!(!chunk.finished);
+
+        let request = SnapshotDownloadRequest {
+            slot: Some(chunk.slot),
+            cursor: Some(chunk.cursor),
+            ..Default::default()
+        };
+        let (slot, cursor) = store.get_position(&request).unwrap();
+        let chunk = store.get_chunk(slot, cursor, &filter, 10).unwrap();
+        assert_eq!(chunk.accounts.len(), 3);
+        assert!(chunk.finished);
+        assert_eq!(chunk.accounts[0].pubkey, vec![3; 32]);
+    }
+}
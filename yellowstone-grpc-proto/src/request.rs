@@ -0,0 +1,637 @@
+use {
+    crate::{
+        geyser::{
+            subscribe_request_filter_accounts_filter::Filter as AccountsFilterOneof,
+            subscribe_request_filter_accounts_filter_memcmp::Data as AccountsFilterMemcmpOneof,
+            CommitmentLevel, GetVersionCluster, GetVersionFeatures, SnapshotDownloadRequest,
+            SnapshotDownloadResponse, SubscribeRequest, SubscribeRequestAccountsDataSlice,
+            SubscribeRequestFilterAccounts, SubscribeRequestFilterAccountsFilter,
+            SubscribeRequestFilterAccountsFilterMemcmp,
+            SubscribeRequestFilterAccountsFilterTokenExtension, SubscribeRequestFilterAccountsPda,
+            SubscribeRequestFilterBlocks, SubscribeRequestFilterBlocksMeta,
+            SubscribeRequestFilterEntry, SubscribeRequestFilterSlots,
+            SubscribeRequestFilterTransactions, SubscribeRequestSlotBatch,
+            SubscribeRequestSlotManifest,
+        },
+        solana::storage::confirmed_block::Transaction,
+    },
+    std::fmt::Display,
+};
+
+/// `ComputeBudget111111111111111111111111111111`
+const COMPUTE_BUDGET_PROGRAM_ID: [u8; 32] = [
+    3, 6, 70, 111, 229, 33, 23, 50, 255, 236, 173, 186, 114, 195, 155, 231, 188, 140, 229, 187,
+    197, 247, 18, 107, 44, 67, 155, 58, 64, 0, 0, 0,
+];
+/// `ComputeBudgetInstruction::SetComputeUnitPrice`
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+fn to_strings<I, T>(values: I) -> Vec<String>
+where
+    I: IntoIterator<Item = T>,
+    T: Display,
+{
+    values.into_iter().map(|value| value.to_string()).collect()
+}
+
+impl SubscribeRequest {
+    pub fn with_accounts(
+        mut self,
+        name: impl Into<String>,
+        filter: SubscribeRequestFilterAccounts,
+    ) -> Self {
+        self.accounts.insert(name.into(), filter);
+        self
+    }
+
+    pub fn with_slots(
+        mut self,
+        name: impl Into<String>,
+        filter: SubscribeRequestFilterSlots,
+    ) -> Self {
+        self.slots.insert(name.into(), filter);
+        self
+    }
+
+    pub fn with_transactions(
+        mut self,
+        name: impl Into<String>,
+        filter: SubscribeRequestFilterTransactions,
+    ) -> Self {
+        self.transactions.insert(name.into(), filter);
+        self
+    }
+
+    pub fn with_transactions_status(
+        mut self,
+        name: impl Into<String>,
+        filter: SubscribeRequestFilterTransactions,
+    ) -> Self {
+        self.transactions_status.insert(name.into(), filter);
+        self
+    }
+
+    pub fn with_blocks(
+        mut self,
+        name: impl Into<String>,
+        filter: SubscribeRequestFilterBlocks,
+    ) -> Self {
+        self.blocks.insert(name.into(), filter);
+        self
+    }
+
+    pub fn with_blocks_meta(mut self, name: impl Into<String>) -> Self {
+        self.blocks_meta
+            .insert(name.into(), SubscribeRequestFilterBlocksMeta::default());
+        self
+    }
+
+    pub fn with_entry(mut self, name: impl Into<String>) -> Self {
+        self.entry
+            .insert(name.into(), SubscribeRequestFilterEntry::default());
+        self
+    }
+
+    pub fn with_commitment(self, commitment: CommitmentLevel) -> Self {
+        Self {
+            commitment: Some(commitment as i32),
+            ..self
+        }
+    }
+
+    pub fn with_accounts_data_slice(mut self, offset: u64, length: u64) -> Self {
+        self.accounts_data_slice
+            .push(SubscribeRequestAccountsDataSlice { offset, length });
+        self
+    }
+
+    pub fn with_from_slot(self, from_slot: u64) -> Self {
+        Self {
+            from_slot: Some(from_slot),
+            ..self
+        }
+    }
+
+    pub fn with_slot_manifest(self, content_hash: bool) -> Self {
+        Self {
+            slot_manifest: Some(SubscribeRequestSlotManifest { content_hash }),
+            ..self
+        }
+    }
+
+    /// Receive matched accounts and transactions in one batch per slot,
+    /// frame size is limited by server default if `max_frame_size` is `None`
+    pub fn with_slot_batch(self, max_frame_size: Option<u64>) -> Self {
+        Self {
+            slot_batch: Some(SubscribeRequestSlotBatch { max_frame_size }),
+            ..self
+        }
+    }
+}
+
+impl SnapshotDownloadRequest {
+    pub const fn new(accounts: SubscribeRequestFilterAccounts) -> Self {
+        Self {
+            accounts: Some(accounts),
+            accounts_data_slice: Vec::new(),
+            slot: None,
+            cursor: None,
+        }
+    }
+
+    /// Continue interrupted download after the last received chunk
+    pub fn resume_after(self, chunk: &SnapshotDownloadResponse) -> Self {
+        Self {
+            slot: Some(chunk.slot),
+            cursor: Some(chunk.cursor.clone()),
+            ..self
+        }
+    }
+}
+
+impl GetVersionFeatures {
+    pub const FILTERS: [&'static str; 7] = [
+        "accounts",
+        "slots",
+        "transactions",
+        "transactions_status",
+        "blocks",
+        "blocks_meta",
+        "entry",
+    ];
+
+    /// Reported in `filters` when server applies `log_contains` in transactions filters
+    pub const TRANSACTIONS_LOG_CONTAINS: &'static str = "transactions_log_contains";
+    /// Reported in `filters` when server applies `fee_payer`, `fee_min` and
+    /// `compute_unit_price_min` in transactions filters
+    pub const TRANSACTIONS_FEE: &'static str = "transactions_fee";
+    /// Reported in `filters` when server applies `program_invoked` in transactions filters
+    pub const TRANSACTIONS_PROGRAM_INVOKED: &'static str = "transactions_program_invoked";
+    /// Reported in `filters` when server applies `nonempty_data` in accounts filters
+    pub const ACCOUNTS_NONEMPTY_DATA: &'static str = "accounts_nonempty_data";
+    /// Reported in `filters` when server tracks owners and sets `previous_owner` in account
+    /// updates, `owner_changed` in accounts filters is applied
+    pub const ACCOUNTS_OWNER_CHANGED: &'static str = "accounts_owner_changed";
+    /// Reported in `filters` when server expands `pda` in accounts filters
+    pub const ACCOUNTS_PDA: &'static str = "accounts_pda";
+    /// Reported in `filters` when server applies `token_extension` in accounts filters
+    pub const ACCOUNTS_TOKEN_EXTENSION: &'static str = "accounts_token_extension";
+
+    pub fn supports_filter(&self, name: &str) -> bool {
+        self.filters.iter().any(|filter| filter == name)
+    }
+
+    pub fn supports_compression(&self, encoding: &str) -> bool {
+        self.compression_send.iter().any(|value| value == encoding)
+    }
+
+    pub const fn supports_replay(&self) -> bool {
+        self.replay_stored_slots > 0
+    }
+
+    pub const fn supports_snapshot_download(&self) -> bool {
+        self.snapshot_download > 0
+    }
+}
+
+impl GetVersionCluster {
+    /// Every non-empty field of `expected` is equal to the field of `self`
+    pub fn matches(&self, expected: &Self) -> bool {
+        (expected.identity.is_empty() || expected.identity == self.identity)
+            && (expected.shred_version == 0 || expected.shred_version == self.shred_version)
+            && (expected.feature_set == 0 || expected.feature_set == self.feature_set)
+            && (expected.cluster.is_empty() || expected.cluster == self.cluster)
+    }
+}
+
+impl SubscribeRequestFilterAccounts {
+    pub fn with_account<I, T>(accounts: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Display,
+    {
+        Self {
+            account: to_strings(accounts),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_owner<I, T>(owners: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Display,
+    {
+        Self {
+            owner: to_strings(owners),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_filter(mut self, filter: SubscribeRequestFilterAccountsFilter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub const fn with_nonempty_data(mut self) -> Self {
+        self.nonempty_data = Some(true);
+        self
+    }
+
+    pub const fn with_owner_changed(mut self) -> Self {
+        self.owner_changed = Some(true);
+        self
+    }
+
+    /// Commitment of this filter, overrides commitment of the request
+    pub const fn with_commitment(mut self, commitment: CommitmentLevel) -> Self {
+        self.commitment = Some(commitment as i32);
+        self
+    }
+
+    pub fn add_pda(mut self, pda: SubscribeRequestFilterAccountsPda) -> Self {
+        self.pda.push(pda);
+        self
+    }
+
+    pub fn with_data_slice(mut self, offset: u64, length: u64) -> Self {
+        self.data_slice
+            .push(SubscribeRequestAccountsDataSlice { offset, length });
+        self
+    }
+}
+
+impl SubscribeRequestFilterAccountsPda {
+    pub fn new<I, T>(program_id: impl Display, seeds_prefix: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Vec<u8>>,
+    {
+        Self {
+            program_id: program_id.to_string(),
+            seeds_prefix: seeds_prefix.into_iter().map(Into::into).collect(),
+            seeds_last: vec![],
+        }
+    }
+
+    pub fn with_seeds_last<I, T>(mut self, seeds_last: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Vec<u8>>,
+    {
+        self.seeds_last = seeds_last.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Number of addresses in the family
+    pub fn addresses_count(&self) -> usize {
+        self.seeds_last.len().max(1)
+    }
+}
+
+impl SubscribeRequestFilterAccountsFilter {
+    pub const fn datasize(size: u64) -> Self {
+        Self {
+            filter: Some(AccountsFilterOneof::Datasize(size)),
+        }
+    }
+
+    pub const fn token_account_state() -> Self {
+        Self {
+            filter: Some(AccountsFilterOneof::TokenAccountState(true)),
+        }
+    }
+
+    pub fn memcmp(offset: u64, bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            filter: Some(AccountsFilterOneof::Memcmp(
+                SubscribeRequestFilterAccountsFilterMemcmp {
+                    offset,
+                    data: Some(AccountsFilterMemcmpOneof::Bytes(bytes.into())),
+                },
+            )),
+        }
+    }
+
+    pub fn memcmp_base58(offset: u64, data: impl Into<String>) -> Self {
+        Self {
+            filter: Some(AccountsFilterOneof::Memcmp(
+                SubscribeRequestFilterAccountsFilterMemcmp {
+                    offset,
+                    data: Some(AccountsFilterMemcmpOneof::Base58(data.into())),
+                },
+            )),
+        }
+    }
+
+    /// Token-2022 account or mint with the extension (`ExtensionType` as number)
+    pub const fn token_extension(extension: u16) -> Self {
+        Self {
+            filter: Some(AccountsFilterOneof::TokenExtension(
+                SubscribeRequestFilterAccountsFilterTokenExtension {
+                    extension: extension as u32,
+                    memcmp: None,
+                },
+            )),
+        }
+    }
+
+    /// Token-2022 account or mint with the extension and `bytes` at `offset`
+    /// of the extension data, e.g. a pubkey stored in the extension
+    pub fn token_extension_memcmp(extension: u16, offset: u64, bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            filter: Some(AccountsFilterOneof::TokenExtension(
+                SubscribeRequestFilterAccountsFilterTokenExtension {
+                    extension: extension as u32,
+                    memcmp: Some(SubscribeRequestFilterAccountsFilterMemcmp {
+                        offset,
+                        data: Some(AccountsFilterMemcmpOneof::Bytes(bytes.into())),
+                    }),
+                },
+            )),
+        }
+    }
+}
+
+impl SubscribeRequestFilterSlots {
+    pub const fn with_filter_by_commitment(filter_by_commitment: bool) -> Self {
+        Self {
+            filter_by_commitment: Some(filter_by_commitment),
+            interslot_updates: None,
+        }
+    }
+}
+
+impl SubscribeRequestFilterTransactions {
+    pub fn for_account<I, T>(accounts: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Display,
+    {
+        Self {
+            account_include: to_strings(accounts),
+            ..Default::default()
+        }
+    }
+
+    pub fn for_signature(signature: impl Display) -> Self {
+        Self {
+            signature: Some(signature.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_vote(self, vote: bool) -> Self {
+        Self {
+            vote: Some(vote),
+            ..self
+        }
+    }
+
+    pub fn with_failed(self, failed: bool) -> Self {
+        Self {
+            failed: Some(failed),
+            ..self
+        }
+    }
+
+    pub fn with_account_exclude<I, T>(self, accounts: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Display,
+    {
+        Self {
+            account_exclude: to_strings(accounts),
+            ..self
+        }
+    }
+
+    pub fn with_account_required<I, T>(self, accounts: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Display,
+    {
+        Self {
+            account_required: to_strings(accounts),
+            ..self
+        }
+    }
+
+    pub fn with_log_contains<I, T>(self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Display,
+    {
+        Self {
+            log_contains: to_strings(patterns),
+            ..self
+        }
+    }
+
+    pub fn with_fee_payer<I, T>(self, accounts: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Display,
+    {
+        Self {
+            fee_payer: to_strings(accounts),
+            ..self
+        }
+    }
+
+    pub fn with_program_invoked<I, T>(self, programs: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Display,
+    {
+        Self {
+            program_invoked: to_strings(programs),
+            ..self
+        }
+    }
+
+    pub fn with_fee_min(self, lamports: u64) -> Self {
+        Self {
+            fee_min: Some(lamports),
+            ..self
+        }
+    }
+
+    pub fn with_compute_unit_price_min(self, micro_lamports: u64) -> Self {
+        Self {
+            compute_unit_price_min: Some(micro_lamports),
+            ..self
+        }
+    }
+
+    /// Client-side check of `fee_min` and `compute_unit_price_min`, for servers
+    /// without [`GetVersionFeatures::TRANSACTIONS_FEE`] support
+    pub fn matches_fee(&self, fee: u64, transaction: &Transaction) -> bool {
+        self.fee_min.is_none_or(|fee_min| fee >= fee_min)
+            && self.compute_unit_price_min.is_none_or(|price_min| {
+                transaction.compute_unit_price().unwrap_or_default() >= price_min
+            })
+    }
+
+    /// Client-side check of `log_contains`, for servers without
+    /// [`GetVersionFeatures::TRANSACTIONS_LOG_CONTAINS`] support
+    pub fn matches_logs(&self, log_messages: &[String]) -> bool {
+        self.log_contains.is_empty()
+            || log_messages.iter().any(|log| {
+                self.log_contains
+                    .iter()
+                    .any(|pattern| log.contains(pattern.as_str()))
+            })
+    }
+}
+
+impl Transaction {
+    /// First account key
+    pub fn fee_payer(&self) -> Option<&[u8]> {
+        self.message
+            .as_ref()
+            .and_then(|message| message.account_keys.first())
+            .map(Vec::as_slice)
+    }
+
+    /// Micro-lamports per compute unit from `SetComputeUnitPrice` instruction
+    pub fn compute_unit_price(&self) -> Option<u64> {
+        let message = self.message.as_ref()?;
+        message.instructions.iter().find_map(|ix| {
+            let program_id = message.account_keys.get(ix.program_id_index as usize)?;
+            match ix.data.split_first() {
+                Some((&SET_COMPUTE_UNIT_PRICE, data))
+                    if program_id.as_slice() == COMPUTE_BUDGET_PROGRAM_ID =>
+                {
+                    Some(u64::from_le_bytes(data.try_into().ok()?))
+                }
+                _ => None,
+            }
+        })
+    }
+}
+
+impl SubscribeRequestFilterBlocks {
+    pub fn for_account<I, T>(accounts: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Display,
+    {
+        Self {
+            account_include: to_strings(accounts),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::COMPUTE_BUDGET_PROGRAM_ID,
+        crate::{
+            geyser::{
+                CommitmentLevel, GetVersionCluster, SubscribeRequest,
+                SubscribeRequestFilterAccounts, SubscribeRequestFilterAccountsFilter,
+                SubscribeRequestFilterTransactions,
+            },
+            solana::storage::confirmed_block::{CompiledInstruction, Message, Transaction},
+        },
+    };
+
+    #[test]
+    fn test_request_helpers() {
+        let owner = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+        let request = SubscribeRequest::default()
+            .with_accounts(
+                "client",
+                SubscribeRequestFilterAccounts::with_owner([owner])
+                    .add_filter(SubscribeRequestFilterAccountsFilter::datasize(165)),
+            )
+            .with_transactions(
+                "client",
+                SubscribeRequestFilterTransactions::for_account([owner])
+                    .with_vote(false)
+                    .with_failed(false),
+            )
+            .with_commitment(CommitmentLevel::Confirmed);
+
+        assert_eq!(request.accounts["client"].owner, vec![owner.to_owned()]);
+        assert_eq!(request.accounts["client"].filters.len(), 1);
+        assert_eq!(
+            request.transactions["client"].account_include,
+            vec![owner.to_owned()]
+        );
+        assert_eq!(request.transactions["client"].vote, Some(false));
+        assert_eq!(request.transactions["client"].failed, Some(false));
+        assert_eq!(request.commitment, Some(CommitmentLevel::Confirmed as i32));
+        assert!(request.slots.is_empty());
+    }
+
+    #[test]
+    fn test_transactions_log_contains() {
+        let filter = SubscribeRequestFilterTransactions::default();
+        assert!(filter.matches_logs(&[]));
+
+        let filter = filter.with_log_contains(["Program data: 4bt"]);
+        assert!(!filter.matches_logs(&[]));
+        assert!(!filter.matches_logs(&["Program log: Instruction: Swap".to_owned()]));
+        assert!(filter.matches_logs(&[
+            "Program log: Instruction: Swap".to_owned(),
+            "Program data: 4bt9fzGJ1Vd".to_owned(),
+        ]));
+    }
+
+    #[test]
+    fn test_transactions_fee() {
+        let mut data = vec![3];
+        data.extend_from_slice(&1_000u64.to_le_bytes());
+        let transaction = Transaction {
+            message: Some(Message {
+                account_keys: vec![vec![1; 32], COMPUTE_BUDGET_PROGRAM_ID.to_vec()],
+                instructions: vec![CompiledInstruction {
+                    program_id_index: 1,
+                    accounts: vec![],
+                    data,
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(transaction.fee_payer(), Some([1; 32].as_slice()));
+        assert_eq!(transaction.compute_unit_price(), Some(1_000));
+        assert_eq!(Transaction::default().compute_unit_price(), None);
+
+        let filter = SubscribeRequestFilterTransactions::default();
+        assert!(filter.matches_fee(0, &Transaction::default()));
+
+        let filter = filter.with_fee_min(10_000);
+        assert!(!filter.matches_fee(5_000, &transaction));
+        assert!(filter.matches_fee(10_000, &transaction));
+
+        let filter = filter.with_compute_unit_price_min(1_001);
+        assert!(!filter.matches_fee(10_000, &transaction));
+        let filter = filter.with_compute_unit_price_min(1_000);
+        assert!(filter.matches_fee(10_000, &transaction));
+    }
+
+    #[test]
+    fn test_version_cluster_matches() {
+        let cluster = GetVersionCluster {
+            identity: "Identity1111111111111111111111111111111111".to_owned(),
+            shred_version: 50093,
+            feature_set: 1_000_000,
+            cluster: "mainnet-beta".to_owned(),
+        };
+        assert!(cluster.matches(&GetVersionCluster::default()));
+        assert!(cluster.matches(&GetVersionCluster {
+            shred_version: 50093,
+            cluster: "mainnet-beta".to_owned(),
+            ..Default::default()
+        }));
+        assert!(!cluster.matches(&GetVersionCluster {
+            shred_version: 50093,
+            cluster: "testnet".to_owned(),
+            ..Default::default()
+        }));
+        assert!(!GetVersionCluster::default().matches(&cluster));
+    }
+}
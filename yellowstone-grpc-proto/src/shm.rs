@@ -0,0 +1,309 @@
+//! Shared memory ring buffer for consumers on the same host.
+//!
+//! Single writer appends length-prefixed records to a memory-mapped file (e.g.
+//! in `/dev/shm`), any number of readers follow the writer without coordination.
+//! Positions are monotonic byte offsets, reader detects overwritten records and
+//! returns [`ShmError::Lagged`].
+//!
+//! Layout: header of [`HEADER_SIZE`] bytes (magic, capacity, reserved and
+//! committed positions) and data area of `capacity` bytes. Record is `u32` length
+//! and payload, records never wrap: tail of the data area is skipped with the
+//! padding marker.
+
+use {
+    memmap2::{Mmap, MmapMut},
+    std::{
+        fs::{File, OpenOptions},
+        io,
+        path::Path,
+        ptr,
+        sync::atomic::{fence, AtomicU64, Ordering},
+    },
+};
+
+pub const HEADER_SIZE: usize = 64;
+
+const MAGIC: u64 = u64::from_le_bytes(*b"YSGRPCSH");
+const OFFSET_MAGIC: usize = 0;
+const OFFSET_CAPACITY: usize = 8;
+/// End of the record which is written now
+const OFFSET_RESERVED: usize = 16;
+/// End of the last written record
+const OFFSET_COMMITTED: usize = 24;
+const LEN_SIZE: u64 = 4;
+const PADDING: u32 = u32::MAX;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShmError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("invalid shared memory header")]
+    InvalidHeader,
+    #[error("record is too large: {0} bytes")]
+    TooLarge(usize),
+    #[error("reader lagged, record at {0} is overwritten")]
+    Lagged(u64),
+}
+
+pub type ShmResult<T> = Result<T, ShmError>;
+
+/// # Safety
+///
+/// `ptr` should point to the mapped header
+const unsafe fn header_position<'a>(ptr: *const u8, offset: usize) -> &'a AtomicU64 {
+    &*(ptr.add(offset) as *const AtomicU64)
+}
+
+#[derive(Debug)]
+pub struct ShmWriter {
+    mmap: MmapMut,
+    capacity: u64,
+    position: u64,
+}
+
+impl ShmWriter {
+    /// Create file with data area of `capacity` bytes. Existing ring of the
+    /// same capacity is never truncated: writing continues from its committed
+    /// position, so readers of the previous writer keep reading. Readers
+    /// should reopen the file if capacity is changed.
+    pub fn create(path: impl AsRef<Path>, capacity: usize) -> ShmResult<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let size = (HEADER_SIZE + capacity) as u64;
+        if file.metadata()?.len() != size {
+            file.set_len(size)?;
+        }
+        // SAFETY: file is owned by the writer, readers map it read-only
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+
+        let ptr = mmap.as_ptr();
+        // SAFETY: header is mapped
+        let (reserved, committed) = unsafe {
+            (
+                header_position(ptr, OFFSET_RESERVED),
+                header_position(ptr, OFFSET_COMMITTED),
+            )
+        };
+        let is_same = mmap[OFFSET_MAGIC..OFFSET_MAGIC + 8] == MAGIC.to_le_bytes()
+            && mmap[OFFSET_CAPACITY..OFFSET_CAPACITY + 8] == (capacity as u64).to_le_bytes();
+        let position = if is_same {
+            committed.load(Ordering::Acquire)
+        } else {
+            reserved.store(0, Ordering::Relaxed);
+            committed.store(0, Ordering::Release);
+            mmap[OFFSET_CAPACITY..OFFSET_CAPACITY + 8]
+                .copy_from_slice(&(capacity as u64).to_le_bytes());
+            mmap[OFFSET_MAGIC..OFFSET_MAGIC + 8].copy_from_slice(&MAGIC.to_le_bytes());
+            0
+        };
+
+        Ok(Self {
+            mmap,
+            capacity: capacity as u64,
+            position,
+        })
+    }
+
+    /// Position after the last written record
+    pub const fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Append record, record can't be larger than half of the data area
+    pub fn write(&mut self, payload: &[u8]) -> ShmResult<()> {
+        let size = LEN_SIZE + payload.len() as u64;
+        if size > self.capacity / 2 || payload.len() >= PADDING as usize {
+            return Err(ShmError::TooLarge(payload.len()));
+        }
+
+        let mut start = self.position;
+        let tail = self.capacity - start % self.capacity;
+        let padding = tail < size;
+        if padding {
+            start += tail;
+        }
+        let end = start + size;
+
+        // reserved position covers padding too, readers detect overwritten
+        // records before any byte is changed
+        let ptr = self.mmap.as_ptr();
+        // SAFETY: header is mapped
+        let reserved = unsafe { header_position(ptr, OFFSET_RESERVED) };
+        reserved.store(end, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        if padding && tail >= LEN_SIZE {
+            self.write_at(self.position, &PADDING.to_le_bytes());
+        }
+        self.write_at(start, &(payload.len() as u32).to_le_bytes());
+        self.write_at(start + LEN_SIZE, payload);
+        self.position = end;
+
+        // SAFETY: header is mapped
+        let committed = unsafe { header_position(ptr, OFFSET_COMMITTED) };
+        committed.store(end, Ordering::Release);
+        Ok(())
+    }
+
+    fn write_at(&mut self, position: u64, data: &[u8]) {
+        let offset = HEADER_SIZE + (position % self.capacity) as usize;
+        self.mmap[offset..offset + data.len()].copy_from_slice(data);
+    }
+}
+
+#[derive(Debug)]
+pub struct ShmReader {
+    mmap: Mmap,
+    capacity: u64,
+    position: u64,
+}
+
+impl ShmReader {
+    /// Open file created by [`ShmWriter`], reading starts from the next record
+    pub fn open(path: impl AsRef<Path>) -> ShmResult<Self> {
+        let file = File::open(path)?;
+        // SAFETY: memory is accessed only with atomics or validated after copy
+        let mmap = unsafe { Mmap::map(&file)? };
+        if mmap.len() < HEADER_SIZE || mmap[OFFSET_MAGIC..OFFSET_MAGIC + 8] != MAGIC.to_le_bytes() {
+            return Err(ShmError::InvalidHeader);
+        }
+        let capacity = u64::from_le_bytes(
+            mmap[OFFSET_CAPACITY..OFFSET_CAPACITY + 8]
+                .try_into()
+                .expect("valid size"),
+        );
+        if capacity == 0 || mmap.len() as u64 != HEADER_SIZE as u64 + capacity {
+            return Err(ShmError::InvalidHeader);
+        }
+
+        let mut reader = Self {
+            mmap,
+            capacity,
+            position: 0,
+        };
+        reader.skip_to_latest();
+        Ok(reader)
+    }
+
+    pub const fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Skip all written records, required after [`ShmError::Lagged`]
+    pub fn skip_to_latest(&mut self) {
+        self.position = self.committed();
+    }
+
+    fn committed(&self) -> u64 {
+        // SAFETY: header is mapped
+        unsafe { header_position(self.mmap.as_ptr(), OFFSET_COMMITTED) }.load(Ordering::Acquire)
+    }
+
+    fn check_overwritten(&self, position: u64) -> ShmResult<()> {
+        fence(Ordering::Acquire);
+        // SAFETY: header is mapped
+        let reserved = unsafe { header_position(self.mmap.as_ptr(), OFFSET_RESERVED) };
+        if reserved.load(Ordering::Relaxed) > position + self.capacity {
+            Err(ShmError::Lagged(position))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn read_at(&self, position: u64, len: usize) -> Vec<u8> {
+        let offset = HEADER_SIZE + (position % self.capacity) as usize;
+        let mut data = vec![0; len];
+        // SAFETY: range is inside of the data area, writer can modify it
+        // concurrently so data is validated with reserved position after copy
+        unsafe {
+            ptr::copy_nonoverlapping(self.mmap.as_ptr().add(offset), data.as_mut_ptr(), len);
+        }
+        data
+    }
+
+    /// Next record if available
+    pub fn try_read(&mut self) -> ShmResult<Option<Vec<u8>>> {
+        loop {
+            let committed = self.committed();
+            if self.position >= committed {
+                return Ok(None);
+            }
+            if committed - self.position > self.capacity {
+                return Err(ShmError::Lagged(self.position));
+            }
+
+            let tail = self.capacity - self.position % self.capacity;
+            if tail < LEN_SIZE {
+                self.position += tail;
+                continue;
+            }
+
+            let len = self.read_at(self.position, LEN_SIZE as usize);
+            let len = u32::from_le_bytes(len.try_into().expect("valid size"));
+            self.check_overwritten(self.position)?;
+            if len == PADDING {
+                self.position += tail;
+                continue;
+            }
+            let size = LEN_SIZE + len as u64;
+            if size > tail {
+                return Err(ShmError::InvalidHeader);
+            }
+
+            let payload = self.read_at(self.position + LEN_SIZE, len as usize);
+            self.check_overwritten(self.position)?;
+            self.position += size;
+            return Ok(Some(payload));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{ShmError, ShmReader, ShmWriter},
+        std::{env, fs, process},
+    };
+
+    #[test]
+    fn test_ring() {
+        let path = env::temp_dir().join(format!("yellowstone-grpc-shm-{}", process::id()));
+        let mut writer = ShmWriter::create(&path, 64).unwrap();
+        let mut reader = ShmReader::open(&path).unwrap();
+        assert!(reader.try_read().unwrap().is_none());
+
+        // wrap with padding
+        for value in 0..10u8 {
+            writer.write(&[value; 10]).unwrap();
+            assert_eq!(reader.try_read().unwrap(), Some(vec![value; 10]));
+        }
+        assert!(reader.try_read().unwrap().is_none());
+        assert!(matches!(
+            writer.write(&[0; 40]),
+            Err(ShmError::TooLarge(40))
+        ));
+
+        // overwritten
+        for value in 0..10u8 {
+            writer.write(&[value; 10]).unwrap();
+        }
+        assert!(matches!(reader.try_read(), Err(ShmError::Lagged(_))));
+        reader.skip_to_latest();
+        writer.write(&[42; 3]).unwrap();
+        assert_eq!(reader.try_read().unwrap(), Some(vec![42; 3]));
+
+        // restarted writer continues the ring of the same capacity
+        let position = writer.position();
+        drop(writer);
+        let mut writer = ShmWriter::create(&path, 64).unwrap();
+        assert_eq!(writer.position(), position);
+        writer.write(&[43; 3]).unwrap();
+        assert_eq!(reader.try_read().unwrap(), Some(vec![43; 3]));
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+//! Stable mapping of server conditions to gRPC status codes. Every condition
+//! has a fixed code and `google.rpc.ErrorInfo` with the reason in the rich
+//! error details of the status (`grpc-status-details-bin`), so clients can
+//! react without parsing the human readable message.
+//!
+//! | reason                    | code                 | client action                        |
+//! |---------------------------|----------------------|--------------------------------------|
+//! | `LIMIT_EXCEEDED`          | `RESOURCE_EXHAUSTED` | reduce filters, do not retry as is   |
+//! | `LAGGING_CLIENT_DROPPED`  | `ABORTED`            | resubscribe, consume updates faster  |
+//! | `SHUTTING_DOWN`           | `UNAVAILABLE`        | reconnect with backoff               |
+//! | `UNAUTHORIZED_FILTER`     | `PERMISSION_DENIED`  | change filters or token              |
+//! | `SLOT_NOT_AVAILABLE`      | `OUT_OF_RANGE`       | resubscribe from a later `from_slot` |
+
+use {
+    prost::Message,
+    prost_types::Any,
+    std::collections::HashMap,
+    tonic::{Code, Status},
+};
+
+/// `domain` of `ErrorInfo` in statuses of the server
+pub const ERROR_DOMAIN: &str = "yellowstone-grpc";
+
+/// `type_url` of `ErrorInfo` in `details` of `google.rpc.Status`
+pub const ERROR_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+
+/// `google.rpc.ErrorInfo` from `google/rpc/error_details.proto`
+#[derive(Clone, PartialEq, Message)]
+pub struct ErrorInfo {
+    #[prost(string, tag = "1")]
+    pub reason: String,
+    #[prost(string, tag = "2")]
+    pub domain: String,
+    #[prost(map = "string, string", tag = "3")]
+    pub metadata: HashMap<String, String>,
+}
+
+/// `google.rpc.Status` from `google/rpc/status.proto`, encoded to the rich
+/// error details of gRPC status
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<Any>,
+}
+
+impl ErrorInfo {
+    /// Status with this error info in details
+    pub fn to_status(&self, code: Code, message: impl Into<String>) -> Status {
+        let message = message.into();
+        let details = RpcStatus {
+            code: code as i32,
+            message: message.clone(),
+            details: vec![Any {
+                type_url: ERROR_INFO_TYPE_URL.to_owned(),
+                value: self.encode_to_vec(),
+            }],
+        };
+        Status::with_details(code, message, details.encode_to_vec().into())
+    }
+
+    /// First error info in details of the status
+    pub fn from_status(status: &Status) -> Option<Self> {
+        RpcStatus::decode(status.details())
+            .ok()?
+            .details
+            .into_iter()
+            .find(|any| any.type_url == ERROR_INFO_TYPE_URL)
+            .and_then(|any| Self::decode(any.value.as_slice()).ok())
+    }
+}
+
+pub const REASON_LIMIT_EXCEEDED: &str = "LIMIT_EXCEEDED";
+pub const REASON_LAGGING_CLIENT_DROPPED: &str = "LAGGING_CLIENT_DROPPED";
+pub const REASON_SHUTTING_DOWN: &str = "SHUTTING_DOWN";
+pub const REASON_UNAUTHORIZED_FILTER: &str = "UNAUTHORIZED_FILTER";
+pub const REASON_SLOT_NOT_AVAILABLE: &str = "SLOT_NOT_AVAILABLE";
+
+pub const CODE_LIMIT_EXCEEDED: Code = Code::ResourceExhausted;
+pub const CODE_LAGGING_CLIENT_DROPPED: Code = Code::Aborted;
+pub const CODE_SHUTTING_DOWN: Code = Code::Unavailable;
+pub const CODE_UNAUTHORIZED_FILTER: Code = Code::PermissionDenied;
+pub const CODE_SLOT_NOT_AVAILABLE: Code = Code::OutOfRange;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatusReason {
+    LimitExceeded,
+    LaggingClientDropped,
+    ShuttingDown,
+    UnauthorizedFilter,
+    /// `from_slot` is evicted from stored slots, `first_available` in metadata
+    SlotNotAvailable,
+}
+
+impl StatusReason {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::LimitExceeded => REASON_LIMIT_EXCEEDED,
+            Self::LaggingClientDropped => REASON_LAGGING_CLIENT_DROPPED,
+            Self::ShuttingDown => REASON_SHUTTING_DOWN,
+            Self::UnauthorizedFilter => REASON_UNAUTHORIZED_FILTER,
+            Self::SlotNotAvailable => REASON_SLOT_NOT_AVAILABLE,
+        }
+    }
+
+    pub const fn code(self) -> Code {
+        match self {
+            Self::LimitExceeded => CODE_LIMIT_EXCEEDED,
+            Self::LaggingClientDropped => CODE_LAGGING_CLIENT_DROPPED,
+            Self::ShuttingDown => CODE_SHUTTING_DOWN,
+            Self::UnauthorizedFilter => CODE_UNAUTHORIZED_FILTER,
+            Self::SlotNotAvailable => CODE_SLOT_NOT_AVAILABLE,
+        }
+    }
+
+    pub fn from_str_name(value: &str) -> Option<Self> {
+        match value {
+            REASON_LIMIT_EXCEEDED => Some(Self::LimitExceeded),
+            REASON_LAGGING_CLIENT_DROPPED => Some(Self::LaggingClientDropped),
+            REASON_SHUTTING_DOWN => Some(Self::ShuttingDown),
+            REASON_UNAUTHORIZED_FILTER => Some(Self::UnauthorizedFilter),
+            REASON_SLOT_NOT_AVAILABLE => Some(Self::SlotNotAvailable),
+            _ => None,
+        }
+    }
+
+    /// Client should reconnect / resubscribe, with the same request or, for
+    /// `SlotNotAvailable`, without `from_slot`
+    pub const fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            Self::LaggingClientDropped | Self::ShuttingDown | Self::SlotNotAvailable
+        )
+    }
+
+    pub fn status(self, message: impl Into<String>) -> Status {
+        self.status_with_metadata(message, HashMap::new())
+    }
+
+    pub fn status_with_metadata(
+        self,
+        message: impl Into<String>,
+        metadata: HashMap<String, String>,
+    ) -> Status {
+        let info = ErrorInfo {
+            reason: self.as_str().to_owned(),
+            domain: ERROR_DOMAIN.to_owned(),
+            metadata,
+        };
+        info.to_status(self.code(), message)
+    }
+}
+
+/// Reason and error info of the status created with [`StatusReason::status`],
+/// `None` for statuses without error info or with unknown reason
+pub fn parse_status(status: &Status) -> Option<(StatusReason, ErrorInfo)> {
+    let info = ErrorInfo::from_status(status)?;
+    let reason = StatusReason::from_str_name(&info.reason)?;
+    (info.domain == ERROR_DOMAIN && reason.code() == status.code()).then_some((reason, info))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{parse_status, ErrorInfo, StatusReason, ERROR_DOMAIN},
+        std::collections::HashMap,
+        tonic::{Code, Status},
+    };
+
+    #[test]
+    fn test_roundtrip() {
+        for reason in [
+            StatusReason::LimitExceeded,
+            StatusReason::LaggingClientDropped,
+            StatusReason::ShuttingDown,
+            StatusReason::UnauthorizedFilter,
+            StatusReason::SlotNotAvailable,
+        ] {
+            assert_eq!(StatusReason::from_str_name(reason.as_str()), Some(reason));
+
+            let metadata = HashMap::from([("limit".to_owned(), "10".to_owned())]);
+            let status = reason.status_with_metadata("test", metadata);
+            assert_eq!(status.code(), reason.code());
+            assert_eq!(status.message(), "test");
+            let (parsed, info) = parse_status(&status).unwrap();
+            assert_eq!(parsed, reason);
+            assert_eq!(info.domain, ERROR_DOMAIN);
+            assert_eq!(info.metadata["limit"], "10");
+        }
+    }
+
+    #[test]
+    fn test_parse_unknown() {
+        assert_eq!(parse_status(&Status::internal("lagged")), None);
+
+        let status = StatusReason::ShuttingDown.status("test");
+        let status = Status::with_details(Code::Internal, "test", status.details().to_vec().into());
+        assert_eq!(parse_status(&status), None);
+
+        let info = ErrorInfo {
+            reason: "SHUTTING_DOWN".to_owned(),
+            domain: "other".to_owned(),
+            metadata: HashMap::new(),
+        };
+        let status = info.to_status(Code::Unavailable, "test");
+        assert_eq!(parse_status(&status), None);
+    }
+}
@@ -0,0 +1,316 @@
+//! Zstd dictionaries per update type.
+//!
+//! Account and transaction payloads are highly repetitive (owners, programs,
+//! account layouts), but a single small frame does not have enough history for
+//! zstd to exploit it. Dictionaries trained on encoded updates of every type are
+//! shared between the plugin and tools as a file and used for compression of
+//! individual updates.
+//!
+//! File layout: magic, then entries of update type (`u8`), dictionary length
+//! (`u32`, little-endian) and dictionary. Frame is update type (`u8`) and zstd
+//! payload, update types without dictionary are compressed without it.
+
+use {
+    crate::geyser::{subscribe_update::UpdateOneof, SubscribeUpdate},
+    prost::Message,
+    std::{collections::BTreeMap, fs, io, path::Path},
+    zstd::{
+        bulk::{Compressor, Decompressor},
+        zstd_safe,
+    },
+};
+
+const MAGIC: &[u8; 8] = b"YSGRPCZD";
+/// Limit of the decompressed update, protects from decompression bombs
+const DECOMPRESSED_MAX: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ZstdDictError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("invalid dictionaries file")]
+    InvalidFile,
+    #[error("invalid frame")]
+    InvalidFrame,
+    #[error(transparent)]
+    Decode(#[from] prost::DecodeError),
+}
+
+pub type ZstdDictResult<T> = Result<T, ZstdDictError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum UpdateKind {
+    Other = 0,
+    Account = 1,
+    Slot = 2,
+    Transaction = 3,
+    TransactionStatus = 4,
+    Block = 5,
+    BlockMeta = 6,
+    Entry = 7,
+}
+
+impl UpdateKind {
+    pub const fn from_update(update: &SubscribeUpdate) -> Self {
+        match &update.update_oneof {
+            Some(UpdateOneof::Account(_)) => Self::Account,
+            Some(UpdateOneof::Slot(_)) => Self::Slot,
+            Some(UpdateOneof::Transaction(_)) => Self::Transaction,
+            Some(UpdateOneof::TransactionStatus(_)) => Self::TransactionStatus,
+            Some(UpdateOneof::Block(_)) => Self::Block,
+            Some(UpdateOneof::BlockMeta(_)) => Self::BlockMeta,
+            Some(UpdateOneof::Entry(_)) => Self::Entry,
+            _ => Self::Other,
+        }
+    }
+
+    pub const fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Self::Other,
+            1 => Self::Account,
+            2 => Self::Slot,
+            3 => Self::Transaction,
+            4 => Self::TransactionStatus,
+            5 => Self::Block,
+            6 => Self::BlockMeta,
+            7 => Self::Entry,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ZstdDictionaries {
+    dicts: BTreeMap<UpdateKind, Vec<u8>>,
+}
+
+impl ZstdDictionaries {
+    /// Train dictionaries of `max_size` bytes on encoded updates, update types
+    /// with too few or too small samples are left without dictionary
+    pub fn train<'a>(
+        samples: impl IntoIterator<Item = (UpdateKind, &'a [u8])>,
+        max_size: usize,
+    ) -> Self {
+        let mut grouped = BTreeMap::<UpdateKind, Vec<&[u8]>>::new();
+        for (kind, sample) in samples {
+            grouped.entry(kind).or_default().push(sample);
+        }
+
+        let dicts = grouped
+            .into_iter()
+            .filter_map(|(kind, samples)| {
+                zstd::dict::from_samples(&samples, max_size)
+                    .ok()
+                    .map(|dict| (kind, dict))
+            })
+            .collect();
+        Self { dicts }
+    }
+
+    pub fn get(&self, kind: UpdateKind) -> Option<&[u8]> {
+        self.dicts.get(&kind).map(Vec::as_slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dicts.is_empty()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        for (kind, dict) in self.dicts.iter() {
+            bytes.push(*kind as u8);
+            bytes.extend_from_slice(&(dict.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(dict);
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> ZstdDictResult<Self> {
+        let mut bytes = bytes
+            .strip_prefix(MAGIC.as_slice())
+            .ok_or(ZstdDictError::InvalidFile)?;
+        let mut dicts = BTreeMap::new();
+        while let Some((kind, rest)) = bytes.split_first() {
+            let kind = UpdateKind::from_u8(*kind).ok_or(ZstdDictError::InvalidFile)?;
+            let (len, rest) = rest.split_at_checked(4).ok_or(ZstdDictError::InvalidFile)?;
+            let len = u32::from_le_bytes(len.try_into().expect("4 bytes")) as usize;
+            let (dict, rest) = rest
+                .split_at_checked(len)
+                .ok_or(ZstdDictError::InvalidFile)?;
+            dicts.insert(kind, dict.to_vec());
+            bytes = rest;
+        }
+        Ok(Self { dicts })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> ZstdDictResult<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn compressor(&self, level: i32) -> ZstdDictResult<ZstdDictCompressor> {
+        Ok(ZstdDictCompressor {
+            compressors: self
+                .dicts
+                .iter()
+                .map(|(kind, dict)| Ok((*kind, Compressor::with_dictionary(level, dict)?)))
+                .collect::<io::Result<_>>()?,
+            fallback: Compressor::new(level)?,
+        })
+    }
+
+    pub fn decompressor(&self) -> ZstdDictResult<ZstdDictDecompressor> {
+        Ok(ZstdDictDecompressor {
+            decompressors: self
+                .dicts
+                .iter()
+                .map(|(kind, dict)| Ok((*kind, Decompressor::with_dictionary(dict)?)))
+                .collect::<io::Result<_>>()?,
+            fallback: Decompressor::new()?,
+        })
+    }
+}
+
+pub struct ZstdDictCompressor {
+    compressors: BTreeMap<UpdateKind, Compressor<'static>>,
+    fallback: Compressor<'static>,
+}
+
+impl ZstdDictCompressor {
+    pub fn compress(&mut self, update: &SubscribeUpdate) -> ZstdDictResult<Vec<u8>> {
+        self.compress_encoded(UpdateKind::from_update(update), &update.encode_to_vec())
+    }
+
+    /// Compress already encoded update of the `kind`
+    pub fn compress_encoded(&mut self, kind: UpdateKind, data: &[u8]) -> ZstdDictResult<Vec<u8>> {
+        let compressor = self
+            .compressors
+            .get_mut(&kind)
+            .unwrap_or(&mut self.fallback);
+        let payload = compressor.compress(data)?;
+
+        let mut frame = Vec::with_capacity(1 + payload.len());
+        frame.push(kind as u8);
+        frame.extend_from_slice(&payload);
+        Ok(frame)
+    }
+}
+
+pub struct ZstdDictDecompressor {
+    decompressors: BTreeMap<UpdateKind, Decompressor<'static>>,
+    fallback: Decompressor<'static>,
+}
+
+impl ZstdDictDecompressor {
+    pub fn decompress(&mut self, frame: &[u8]) -> ZstdDictResult<SubscribeUpdate> {
+        let (kind, payload) = frame.split_first().ok_or(ZstdDictError::InvalidFrame)?;
+        let kind = UpdateKind::from_u8(*kind).ok_or(ZstdDictError::InvalidFrame)?;
+        let size = match zstd_safe::get_frame_content_size(payload) {
+            Ok(Some(size)) if size <= DECOMPRESSED_MAX => size as usize,
+            _ => return Err(ZstdDictError::InvalidFrame),
+        };
+
+        let decompressor = self
+            .decompressors
+            .get_mut(&kind)
+            .unwrap_or(&mut self.fallback);
+        let data = decompressor.decompress(payload, size)?;
+        Ok(SubscribeUpdate::decode(data.as_slice())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{UpdateKind, ZstdDictError, ZstdDictionaries},
+        crate::geyser::{
+            subscribe_update::UpdateOneof, SubscribeUpdate, SubscribeUpdateAccount,
+            SubscribeUpdateAccountInfo, SubscribeUpdateSlot,
+        },
+        prost::Message,
+    };
+
+    fn account(index: u64) -> SubscribeUpdate {
+        // token account like layout: same mint and owner, different amounts
+        let mut data = vec![7; 32];
+        data.extend_from_slice(&[index as u8; 32]);
+        data.extend_from_slice(&(index * 1_000_000).to_le_bytes());
+        data.resize(165, 0);
+        SubscribeUpdate {
+            filters: vec!["accounts".to_owned()],
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: index.to_le_bytes().repeat(4),
+                    lamports: 2_039_280,
+                    owner: vec![6; 32],
+                    data,
+                    write_version: index,
+                    ..Default::default()
+                }),
+                slot: 300_000_000 + index / 10,
+                is_startup: false,
+                previous_owner: None,
+                data_omitted: false,
+            })),
+            created_at: None,
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let samples = (0..1_000).map(account).collect::<Vec<_>>();
+        let encoded = samples
+            .iter()
+            .map(|update| update.encode_to_vec())
+            .collect::<Vec<_>>();
+        let dicts = ZstdDictionaries::train(
+            encoded
+                .iter()
+                .map(|data| (UpdateKind::Account, data.as_slice())),
+            16 * 1024,
+        );
+        assert!(dicts.get(UpdateKind::Account).is_some());
+        assert!(dicts.get(UpdateKind::Slot).is_none());
+
+        let dicts = ZstdDictionaries::from_bytes(&dicts.to_bytes()).unwrap();
+        let mut compressor = dicts.compressor(3).unwrap();
+        let mut decompressor = dicts.decompressor().unwrap();
+
+        let update = account(1_001);
+        let frame = compressor.compress(&update).unwrap();
+        let plain = zstd::bulk::compress(&update.encode_to_vec(), 3).unwrap();
+        assert!(frame.len() < plain.len());
+        assert_eq!(decompressor.decompress(&frame).unwrap(), update);
+
+        // without dictionary
+        let update = SubscribeUpdate {
+            filters: vec![],
+            update_oneof: Some(UpdateOneof::Slot(SubscribeUpdateSlot {
+                slot: 42,
+                ..Default::default()
+            })),
+            created_at: None,
+            sequence: 0,
+            ingested_at: None,
+            replica: String::new(),
+        };
+        let frame = compressor.compress(&update).unwrap();
+        assert_eq!(decompressor.decompress(&frame).unwrap(), update);
+
+        assert!(matches!(
+            decompressor.decompress(&[42]),
+            Err(ZstdDictError::InvalidFrame)
+        ));
+        assert!(matches!(
+            ZstdDictionaries::from_bytes(b"invalid"),
+            Err(ZstdDictError::InvalidFile)
+        ));
+    }
+}